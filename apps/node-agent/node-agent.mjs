@@ -195,6 +195,13 @@ function handleMessage(msg) {
             console.log(`  Capacity: ${storageGb} GB | Used: ${(usedBytes / 1024 / 1024).toFixed(1)} MB`);
             break;
 
+        case "dashboard": {
+            const d = msg.dashboard;
+            if (!d) break;
+            console.log(`  Earnings: $${d.accrued_earnings_usd.toFixed(4)} (+$${d.monthly_rate_usd}/mo) | Audit streak: ${d.audit_pass_streak} | Egress: ${(d.egress_bytes / 1024 / 1024).toFixed(1)} MB | Penalties: ${d.penalty_count}`);
+            break;
+        }
+
         default:
             break;
     }
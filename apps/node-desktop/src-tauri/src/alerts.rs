@@ -0,0 +1,191 @@
+// Alert rules evaluated against the node's stats every EVAL_INTERVAL. A rule
+// raises an OS notification when it starts firing. Firing and clearing are
+// both kept in a history the webview reads through alert_history and follows
+// on the "node-alert" event.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::NodeState;
+
+const EVAL_INTERVAL: Duration = Duration::from_secs(30);
+const DISK_FULL_RATIO: f64 = 0.9;
+const OFFLINE_AFTER: Duration = Duration::from_secs(5 * 60);
+const HISTORY_LIMIT: usize = 200;
+// Written by neuro-node next to its chunk store every 15s.
+const STATUS_FILE: &str = "node_status.json";
+
+#[derive(Deserialize)]
+struct StatusSnapshot {
+    updated_at_ms: u64,
+    used_bytes: u64,
+    max_bytes: u64,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    DiskFull,
+    AuditsFailing,
+    Quarantined,
+    Offline,
+}
+
+impl Rule {
+    fn title(self) -> &'static str {
+        match self {
+            Rule::DiskFull => "Disk almost full",
+            Rule::AuditsFailing => "Audits failing",
+            Rule::Quarantined => "Node quarantined",
+            Rule::Offline => "Node offline",
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct Alert {
+    rule: Rule,
+    title: &'static str,
+    // False for the entry recording that the rule cleared.
+    firing: bool,
+    message: String,
+    at_ms: u64,
+}
+
+#[derive(Default)]
+pub struct AlertState {
+    history: Mutex<Vec<Alert>>,
+}
+
+// What the rules look at. Sources that could not be read are None and the
+// rules depending on them keep their previous state.
+struct Stats {
+    status: Option<StatusSnapshot>,
+    // How long the node has been running; None while stopped.
+    running_for: Option<Duration>,
+    dashboard: Option<serde_json::Value>,
+    policy: Option<serde_json::Value>,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+// Each rule that could be judged, with its message when firing.
+fn evaluate(stats: &Stats, now_ms: u64) -> Vec<(Rule, Option<String>)> {
+    let mut verdicts = Vec::new();
+
+    if let Some(status) = stats.status.as_ref().filter(|s| s.max_bytes > 0) {
+        let ratio = status.used_bytes as f64 / status.max_bytes as f64;
+        verdicts.push((
+            Rule::DiskFull,
+            (ratio > DISK_FULL_RATIO).then(|| {
+                format!(
+                    "Storage is {:.0}% full ({:.1} of {:.1} GB)",
+                    ratio * 100.0,
+                    status.used_bytes as f64 / 1e9,
+                    status.max_bytes as f64 / 1e9
+                )
+            }),
+        ));
+    }
+
+    // A node that only just started has not written its first snapshot yet.
+    let offline = match stats.running_for {
+        Some(running_for) if running_for >= OFFLINE_AFTER => {
+            let silent_ms = stats
+                .status
+                .as_ref()
+                .map(|s| now_ms.saturating_sub(s.updated_at_ms))
+                .unwrap_or(running_for.as_millis() as u64);
+            (silent_ms >= OFFLINE_AFTER.as_millis() as u64)
+                .then(|| format!("No status from the node for {} minutes", silent_ms / 60_000))
+        }
+        _ => None,
+    };
+    verdicts.push((Rule::Offline, offline));
+
+    if let Some(dashboard) = &stats.dashboard {
+        let streak = dashboard.get("audit_pass_streak").and_then(|v| v.as_u64()).unwrap_or(0);
+        let penalties = dashboard.get("penalty_count").and_then(|v| v.as_u64()).unwrap_or(0);
+        verdicts.push((
+            Rule::AuditsFailing,
+            (streak == 0 && penalties > 0)
+                .then(|| format!("The last storage audit failed ({} penalties so far)", penalties)),
+        ));
+    }
+
+    if let Some(action) = stats.policy.as_ref().and_then(|p| p.get("action")).and_then(|a| a.as_str()) {
+        verdicts.push((
+            Rule::Quarantined,
+            matches!(action, "quarantine" | "evict" | "proactive_evict")
+                .then(|| format!("The sentinel set this node to {}; it receives no new shards", action)),
+        ));
+    }
+    verdicts
+}
+
+fn collect(running_for: Option<Duration>) -> Stats {
+    let setup = crate::read_setup(&crate::setup_config_path()).ok().flatten().unwrap_or_default();
+    let status = std::fs::read(Path::new(&setup.storage_path).join(STATUS_FILE))
+        .ok()
+        .and_then(|raw| serde_json::from_slice(&raw).ok());
+    Stats {
+        status,
+        running_for,
+        dashboard: crate::control_plane_get("dashboard").ok(),
+        policy: crate::control_plane_get("policy").ok(),
+    }
+}
+
+fn record(app: &AppHandle, alert: Alert) {
+    if alert.firing {
+        let _ = app.notification().builder().title(alert.title).body(&alert.message).show();
+    }
+    let _ = app.emit("node-alert", alert.clone());
+    let state = app.state::<AlertState>();
+    let mut history = state.history.lock().unwrap();
+    history.push(alert);
+    let excess = history.len().saturating_sub(HISTORY_LIMIT);
+    history.drain(..excess);
+}
+
+pub fn spawn(app: AppHandle) {
+    thread::spawn(move || {
+        let mut active: HashSet<Rule> = HashSet::new();
+        let mut running_since: Option<Instant> = None;
+        loop {
+            running_since = match (app.state::<NodeState>().is_running(), running_since) {
+                (true, None) => Some(Instant::now()),
+                (true, since) => since,
+                (false, _) => None,
+            };
+            let stats = collect(running_since.map(|since| since.elapsed()));
+            let at_ms = now_ms();
+            for (rule, firing) in evaluate(&stats, at_ms) {
+                match firing {
+                    Some(message) if active.insert(rule) => {
+                        record(&app, Alert { rule, title: rule.title(), firing: true, message, at_ms });
+                    }
+                    None if active.remove(&rule) => {
+                        let message = format!("{} cleared", rule.title());
+                        record(&app, Alert { rule, title: rule.title(), firing: false, message, at_ms });
+                    }
+                    _ => {}
+                }
+            }
+            thread::sleep(EVAL_INTERVAL);
+        }
+    });
+}
+
+#[tauri::command]
+pub fn alert_history(state: State<'_, AlertState>) -> Vec<Alert> {
+    state.history.lock().unwrap().clone()
+}
@@ -1,24 +1,50 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 use tauri::{AppHandle, Emitter, State};
 
-// Global state to track if the node is running
+mod alerts;
+
+const DEFAULT_LISTEN_PORT: u16 = 9000;
+
+// Global state to track if the node is running. Each run gets its own flag so
+// a restart cannot revive the thread it just stopped.
 struct NodeState {
-    running: Arc<AtomicBool>,
+    running: Mutex<Arc<AtomicBool>>,
+}
+
+impl NodeState {
+    fn is_running(&self) -> bool {
+        self.running.lock().unwrap().load(Ordering::SeqCst)
+    }
+
+    fn stop(&self) {
+        self.running.lock().unwrap().store(false, Ordering::SeqCst);
+    }
+
+    fn start(&self, capacity_gb: u64, app_handle: AppHandle) {
+        let running_flag = Arc::new(AtomicBool::new(true));
+        *self.running.lock().unwrap() = running_flag.clone();
+        spawn_node(capacity_gb, app_handle, running_flag);
+    }
 }
 
 #[tauri::command]
 async fn start_node(capacity_gb: u32, app_handle: AppHandle, state: State<'_, NodeState>) -> Result<bool, String> {
-    if state.running.load(Ordering::SeqCst) {
+    if state.is_running() {
         return Ok(true); // Already running
     }
-    
-    state.running.store(true, Ordering::SeqCst);
-    let running_flag = state.running.clone();
+    state.start(capacity_gb as u64, app_handle);
+    Ok(true)
+}
 
-    // Spawn a background thread to simulate the node process and stream logs
+// Spawn a background thread to simulate the node process and stream logs
+fn spawn_node(capacity_gb: u64, app_handle: AppHandle, running_flag: Arc<AtomicBool>) {
     thread::spawn(move || {
         let _ = app_handle.emit("node-log", format!("[SYSTEM] Locating neuro-node.exe binary..."));
         thread::sleep(Duration::from_millis(800));
@@ -49,24 +75,223 @@ async fn start_node(capacity_gb: u32, app_handle: AppHandle, state: State<'_, No
             loop_count += 1;
         }
     });
-
-    Ok(true)
 }
 
 #[tauri::command]
 fn stop_node(state: State<'_, NodeState>) -> Result<bool, String> {
-    state.running.store(false, Ordering::SeqCst);
+    state.stop();
     Ok(true)
 }
 
+// The setup file neuro-node reads on launch and watches while it runs.
+// Unknown keys (rates, peer lists, log level) are carried through untouched.
+#[derive(Serialize, Deserialize)]
+struct SetupFile {
+    storage_path: String,
+    max_gb: u64,
+    relay_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    listen: Option<String>,
+    #[serde(flatten)]
+    rest: serde_json::Map<String, serde_json::Value>,
+}
+
+// neuro-node's flag defaults, used until a setup file exists.
+impl Default for SetupFile {
+    fn default() -> Self {
+        Self {
+            storage_path: "./node-data".to_string(),
+            max_gb: 50,
+            relay_url: None,
+            listen: None,
+            rest: serde_json::Map::new(),
+        }
+    }
+}
+
+// What the settings form edits.
+#[derive(Serialize, Deserialize)]
+struct NodeConfig {
+    storage_path: String,
+    max_gb: u64,
+    listen_port: u16,
+    relay_url: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SaveResult {
+    path: String,
+    // Whether the running node was restarted to pick up the change.
+    restarted: bool,
+}
+
+// Same lookup as neuro-node's default --setup-config-path.
+fn setup_config_path() -> PathBuf {
+    if cfg!(target_os = "windows") {
+        if let Some(appdata) = std::env::var_os("APPDATA") {
+            return PathBuf::from(appdata).join("Neurostore").join("node-config.json");
+        }
+    }
+    if let Some(xdg) = std::env::var_os("XDG_CONFIG_HOME") {
+        return PathBuf::from(xdg).join("neurostore").join("node-config.json");
+    }
+    if let Some(home) = std::env::var_os("HOME") {
+        return PathBuf::from(home).join(".config").join("neurostore").join("node-config.json");
+    }
+    PathBuf::from("node-config.json")
+}
+
+fn read_setup(path: &Path) -> Result<Option<SetupFile>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let raw = fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&raw)
+        .map(Some)
+        .map_err(|e| format!("failed to parse {}: {}", path.display(), e))
+}
+
+// Port of a `/ip4/<host>/tcp/<port>` listen multiaddr.
+fn listen_port(listen: Option<&str>) -> u16 {
+    listen
+        .and_then(|addr| addr.split("/tcp/").nth(1))
+        .and_then(|rest| rest.split('/').next())
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(DEFAULT_LISTEN_PORT)
+}
+
+fn node_config(setup: &SetupFile) -> NodeConfig {
+    NodeConfig {
+        storage_path: setup.storage_path.clone(),
+        max_gb: setup.max_gb,
+        listen_port: listen_port(setup.listen.as_deref()),
+        relay_url: setup.relay_url.clone(),
+    }
+}
+
+fn validate(config: &NodeConfig, current: &NodeConfig, node_running: bool) -> Result<(), String> {
+    let storage = Path::new(&config.storage_path);
+    if config.storage_path.is_empty() {
+        return Err("Storage path is required".to_string());
+    }
+    if !storage.is_dir() {
+        return Err(format!("Storage path {} is not an existing directory", storage.display()));
+    }
+    if config.max_gb == 0 {
+        return Err("Capacity must be at least 1 GB".to_string());
+    }
+    if config.listen_port == 0 {
+        return Err("Listen port must be between 1 and 65535".to_string());
+    }
+    // A running node holds its own port, so only a new port is probed.
+    let port_in_use_by_node = node_running && config.listen_port == current.listen_port;
+    if !port_in_use_by_node && TcpListener::bind(("0.0.0.0", config.listen_port)).is_err() {
+        return Err(format!("Port {} is already in use", config.listen_port));
+    }
+    if let Some(relay) = config.relay_url.as_deref() {
+        if !(relay.starts_with("ws://") || relay.starts_with("wss://")) {
+            return Err("Relay URL must start with ws:// or wss://".to_string());
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn get_node_config() -> Result<NodeConfig, String> {
+    let setup = read_setup(&setup_config_path())?.unwrap_or_default();
+    Ok(node_config(&setup))
+}
+
+// Validates and saves the config. The node hot-reloads capacity, but storage
+// path, listen port and relay are only read at start, so a running node is
+// restarted when one of those changes.
+#[tauri::command]
+fn set_node_config(config: NodeConfig, app_handle: AppHandle, state: State<'_, NodeState>) -> Result<SaveResult, String> {
+    let config = NodeConfig {
+        storage_path: config.storage_path.trim().to_string(),
+        relay_url: config.relay_url.map(|r| r.trim().to_string()).filter(|r| !r.is_empty()),
+        ..config
+    };
+    let path = setup_config_path();
+    let mut setup = read_setup(&path)?.unwrap_or_default();
+    let current = node_config(&setup);
+    let node_running = state.is_running();
+    validate(&config, &current, node_running)?;
+
+    setup.storage_path = config.storage_path.clone();
+    setup.max_gb = config.max_gb;
+    setup.relay_url = config.relay_url.clone();
+    setup.listen = Some(format!("/ip4/0.0.0.0/tcp/{}", config.listen_port));
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("failed to create {}: {}", parent.display(), e))?;
+    }
+    // Written aside and renamed so the node's watcher never sees half a file.
+    let raw = serde_json::to_string_pretty(&setup).map_err(|e| e.to_string())?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, raw).map_err(|e| format!("failed to write {}: {}", tmp.display(), e))?;
+    fs::rename(&tmp, &path).map_err(|e| format!("failed to replace {}: {}", path.display(), e))?;
+
+    let needs_restart = config.storage_path != current.storage_path
+        || config.listen_port != current.listen_port
+        || config.relay_url != current.relay_url;
+    let restarted = node_running && needs_restart;
+    if restarted {
+        let _ = app_handle.emit("node-log", "[SYSTEM] Configuration changed; restarting node...".to_string());
+        state.stop();
+        state.start(config.max_gb, app_handle);
+    }
+    Ok(SaveResult {
+        path: path.display().to_string(),
+        restarted,
+    })
+}
+
+// GETs one of this node's control-plane endpoints (`dashboard`, `policy`).
+// The node secret stays on the Rust side so it never reaches the webview.
+fn control_plane_get(endpoint: &str) -> Result<serde_json::Value, String> {
+    let base = std::env::var("NEUROSTORE_CONTROL_PLANE_URL").unwrap_or_else(|_| "http://localhost:8080".to_string());
+    let secret = std::env::var("NODE_SHARED_SECRET").map_err(|_| "NODE_SHARED_SECRET is not set".to_string())?;
+    let peer_id = std::env::var("NEUROSTORE_PEER_ID").map_err(|_| "NEUROSTORE_PEER_ID is not set".to_string())?;
+    let url = format!("{}/api/nodes/{}/{}", base.trim_end_matches('/'), peer_id, endpoint);
+
+    ureq::get(&url)
+        .set("x-node-secret", &secret)
+        .timeout(Duration::from_secs(10))
+        .call()
+        .map_err(|e| e.to_string())?
+        .into_json::<serde_json::Value>()
+        .map_err(|e| e.to_string())
+}
+
+// Fetches the node's earnings dashboard from the control-plane.
+#[tauri::command]
+async fn node_dashboard() -> Result<serde_json::Value, String> {
+    tauri::async_runtime::spawn_blocking(|| control_plane_get("dashboard"))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_notification::init())
         .manage(NodeState {
-            running: Arc::new(AtomicBool::new(false)),
+            running: Mutex::new(Arc::new(AtomicBool::new(false))),
+        })
+        .manage(alerts::AlertState::default())
+        .setup(|app| {
+            alerts::spawn(app.handle().clone());
+            Ok(())
         })
-        .invoke_handler(tauri::generate_handler![start_node, stop_node])
+        .invoke_handler(tauri::generate_handler![
+            start_node,
+            stop_node,
+            get_node_config,
+            set_node_config,
+            node_dashboard,
+            alerts::alert_history
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -1,12 +1,75 @@
 import React, { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
-import { Play, Square, Activity, HardDrive, Terminal as TermIcon, ShieldCheck } from 'lucide-react';
+import { Play, Square, Activity, HardDrive, Terminal as TermIcon, ShieldCheck, Wallet, Settings, Bell } from 'lucide-react';
+
+interface Penalty {
+  reason: string;
+  score_delta: number;
+  created_at: string;
+}
+
+interface NodeDashboard {
+  score: number;
+  accrued_earnings_usd: number;
+  monthly_rate_usd: number;
+  audit_pass_streak: number;
+  egress_bytes: number;
+  penalty_count: number;
+  recent_penalties: Penalty[];
+}
+
+interface NodeConfig {
+  storage_path: string;
+  max_gb: number;
+  listen_port: number;
+  relay_url: string | null;
+}
+
+interface Alert {
+  rule: string;
+  title: string;
+  firing: boolean;
+  message: string;
+  at_ms: number;
+}
+
+interface SaveResult {
+  path: string;
+  restarted: boolean;
+}
+
+const DASHBOARD_REFRESH_MS = 30000;
 
 function App() {
   const [isRunning, setIsRunning] = useState(false);
   const [logs, setLogs] = useState<string[]>([]);
   const [storageLimit, setStorageLimit] = useState(500);
+  const [dashboard, setDashboard] = useState<NodeDashboard | null>(null);
+  const [config, setConfig] = useState<NodeConfig | null>(null);
+  const [configError, setConfigError] = useState<string | null>(null);
+  const [alerts, setAlerts] = useState<Alert[]>([]);
+
+  // Load the node's saved setup so the form starts from what the node runs with
+  useEffect(() => {
+    invoke<NodeConfig>('get_node_config')
+      .then(cfg => {
+        setConfig(cfg);
+        setStorageLimit(cfg.max_gb);
+      })
+      .catch(err => setConfigError(String(err)));
+  }, []);
+
+  // Alert history so far, then each new alert as the backend raises it
+  useEffect(() => {
+    invoke<Alert[]>('alert_history').then(setAlerts).catch(() => {});
+    const unlistenPromise = listen<Alert>('node-alert', (event) => {
+      setAlerts(prev => [...prev.slice(-199), event.payload]);
+    });
+    return () => {
+      unlistenPromise.then(unlisten => unlisten());
+    };
+  }, []);
 
   // Auto-scroll logic for terminal
   useEffect(() => {
@@ -31,6 +94,19 @@ function App() {
     };
   }, []);
 
+  // Poll the control-plane for earnings while the node runs
+  useEffect(() => {
+    if (!isRunning) return;
+    const refresh = () => {
+      invoke<NodeDashboard>('node_dashboard')
+        .then(setDashboard)
+        .catch(err => setLogs(prev => [...prev.slice(-99), `[ERROR] Dashboard unavailable: ${err}`]));
+    };
+    refresh();
+    const timer = setInterval(refresh, DASHBOARD_REFRESH_MS);
+    return () => clearInterval(timer);
+  }, [isRunning]);
+
   const toggleNode = async () => {
     if (isRunning) {
       await invoke('stop_node');
@@ -47,6 +123,19 @@ function App() {
     }
   };
 
+  const saveConfig = async () => {
+    if (!config) return;
+    setConfigError(null);
+    try {
+      const result = await invoke<SaveResult>('set_node_config', {
+        config: { ...config, max_gb: storageLimit, relay_url: config.relay_url || null },
+      });
+      setLogs(prev => [...prev, `[SYSTEM] Saved configuration to ${result.path}${result.restarted ? ' and restarted the node' : ''}.`]);
+    } catch (err) {
+      setConfigError(String(err));
+    }
+  };
+
   return (
     <div className="h-screen flex flex-col pt-8 bg-background overflow-hidden relative" data-tauri-drag-region>
       {/* Draggable Top Bar Area */}
@@ -94,9 +183,8 @@ function App() {
               min="50" max="2000" step="50"
               value={storageLimit}
               onChange={(e) => setStorageLimit(parseInt(e.target.value))}
-              disabled={isRunning}
             />
-            <p className="text-xs text-muted mt-4">Adjust maximum storage provided to the network. Lock requires node restart.</p>
+            <p className="text-xs text-muted mt-4">Adjust maximum storage provided to the network. Saved with the configuration below.</p>
           </div>
 
           {/* AI Reputation */}
@@ -108,13 +196,112 @@ function App() {
             </div>
             <div className="mt-auto">
               <span className={`text-5xl font-display font-bold ${isRunning ? 'text-white' : 'text-muted'}`}>
-                {isRunning ? '99.9' : '---'}<span className="text-lg text-muted">/100</span>
+                {isRunning && dashboard ? dashboard.score.toFixed(1) : '---'}<span className="text-lg text-muted">/100</span>
               </span>
               <p className="text-xs text-muted mt-2">Driven by 24h uptime and bandwidth availability.</p>
             </div>
           </div>
         </div>
 
+        {/* Node Configuration */}
+        <div className="glass-card p-6">
+          <div className="flex items-center justify-between mb-4">
+            <h3 className="font-bold flex items-center gap-2"><Settings className="text-primary" size={18} /> Configuration</h3>
+            <button
+              onClick={saveConfig}
+              disabled={!config}
+              className="px-4 py-1.5 rounded-lg text-sm font-bold bg-primary text-background hover:bg-primary/90 disabled:opacity-50"
+            >
+              Save
+            </button>
+          </div>
+          {config && (
+            <div className="grid grid-cols-3 gap-4 text-sm">
+              <label className="col-span-2">
+                <span className="text-xs text-muted">Storage path</span>
+                <input
+                  className="w-full mt-1 px-2 py-1 rounded bg-black/30 border border-border font-mono"
+                  value={config.storage_path}
+                  onChange={(e) => setConfig({ ...config, storage_path: e.target.value })}
+                />
+              </label>
+              <label>
+                <span className="text-xs text-muted">Listen port</span>
+                <input
+                  type="number"
+                  min="1" max="65535"
+                  className="w-full mt-1 px-2 py-1 rounded bg-black/30 border border-border font-mono"
+                  value={config.listen_port}
+                  onChange={(e) => setConfig({ ...config, listen_port: parseInt(e.target.value) || 0 })}
+                />
+              </label>
+              <label className="col-span-3">
+                <span className="text-xs text-muted">Relay URL</span>
+                <input
+                  className="w-full mt-1 px-2 py-1 rounded bg-black/30 border border-border font-mono"
+                  placeholder="wss://"
+                  value={config.relay_url ?? ''}
+                  onChange={(e) => setConfig({ ...config, relay_url: e.target.value })}
+                />
+              </label>
+            </div>
+          )}
+          {configError && <p className="text-xs text-red-400 mt-3">{configError}</p>}
+          <p className="text-xs text-muted mt-3">Capacity applies live; a new storage path, port or relay restarts a running node.</p>
+        </div>
+
+        {/* Earnings */}
+        <div className="glass-card p-6">
+          <div className="flex items-center justify-between mb-4">
+            <h3 className="font-bold flex items-center gap-2"><Wallet className="text-primary" size={18} /> Earnings</h3>
+            <span className="font-mono text-primary font-bold">
+              {dashboard ? `$${dashboard.accrued_earnings_usd.toFixed(4)}` : '---'}
+            </span>
+          </div>
+          <div className="grid grid-cols-3 gap-4 text-sm">
+            <div>
+              <p className="text-xs text-muted">Current rate</p>
+              <p className="font-mono">{dashboard ? `$${dashboard.monthly_rate_usd}/mo` : '---'}</p>
+            </div>
+            <div>
+              <p className="text-xs text-muted">Audit pass streak</p>
+              <p className="font-mono">{dashboard ? dashboard.audit_pass_streak : '---'}</p>
+            </div>
+            <div>
+              <p className="text-xs text-muted">Served egress</p>
+              <p className="font-mono">{dashboard ? `${(dashboard.egress_bytes / 1024 ** 3).toFixed(2)} GB` : '---'}</p>
+            </div>
+          </div>
+          {dashboard && dashboard.recent_penalties.length > 0 && (
+            <div className="mt-4 space-y-1">
+              <p className="text-xs text-muted">Penalties ({dashboard.penalty_count})</p>
+              {dashboard.recent_penalties.slice(0, 5).map((p, i) => (
+                <div key={i} className="flex justify-between font-mono text-[11px] text-red-400">
+                  <span>{p.reason}</span>
+                  <span>{p.score_delta} · {new Date(p.created_at).toLocaleString()}</span>
+                </div>
+              ))}
+            </div>
+          )}
+        </div>
+
+        {/* Alert History */}
+        <div className="glass-card p-6">
+          <h3 className="font-bold flex items-center gap-2 mb-4"><Bell className="text-primary" size={18} /> Alerts</h3>
+          {alerts.length === 0 ? (
+            <p className="text-xs text-muted">No alerts. Disk usage, audits, quarantine and uptime are checked every 30 seconds.</p>
+          ) : (
+            <div className="space-y-1 max-h-40 overflow-y-auto">
+              {[...alerts].reverse().map((a, i) => (
+                <div key={i} className={`flex justify-between font-mono text-[11px] ${a.firing ? 'text-red-400' : 'text-green-400'}`}>
+                  <span>{a.firing ? `${a.title}: ${a.message}` : a.message}</span>
+                  <span>{new Date(a.at_ms).toLocaleString()}</span>
+                </div>
+              ))}
+            </div>
+          )}
+        </div>
+
         {/* Live Terminal Log View */}
         <div className="glass-card flex flex-col h-64 overflow-hidden border-border/40">
           <div className="bg-background/80 px-4 py-2 border-b border-border/40 flex items-center gap-2 text-xs font-mono text-muted">
@@ -1,10 +1,21 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod sync;
+
+use neuro_gateway_client::{GatewayClient, LocatedManifest, ManifestSummary, PeerAddrs, UserProfile};
+use notify::Watcher;
 use serde::Serialize;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use tauri::Manager;
+use sync::{SyncConfig, SyncEngine, SyncEvent};
+use tauri::{Emitter, Manager};
 
 const SERVICE_NAME: &str = "neurostore-next";
+/// Keyring entry holding the session token from `gateway_login`.
+const GATEWAY_TOKEN_KEY: &str = "gateway_token";
+/// Keyring entry holding the X25519 secret from `identity_generate`; the
+/// uploader reads it with `--identity-keyring x25519_identity`.
+const IDENTITY_KEY: &str = "x25519_identity";
 
 #[derive(Serialize)]
 struct AppInfo {
@@ -13,29 +24,44 @@ struct AppInfo {
     shell: &'static str,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, Default)]
 struct SyncStatus {
     running: bool,
+    folder: Option<String>,
+    bucket: Option<String>,
     interval_secs: u64,
-    ticks: u64,
     started_at_ms: Option<u64>,
-    last_tick_ms: Option<u64>,
+    scans: u64,
+    last_scan_ms: Option<u64>,
+    uploaded: u64,
+    removed: u64,
+    conflicts: u64,
+    failures: u64,
+    last_event: Option<SyncEvent>,
+    last_event_ms: Option<u64>,
 }
 
-impl Default for SyncStatus {
-    fn default() -> Self {
-        Self {
-            running: false,
-            interval_secs: 0,
-            ticks: 0,
-            started_at_ms: None,
-            last_tick_ms: None,
+impl SyncStatus {
+    fn record(&mut self, event: &SyncEvent) {
+        match event {
+            SyncEvent::Scanned { .. } => {
+                self.scans = self.scans.saturating_add(1);
+                self.last_scan_ms = Some(now_ms());
+            }
+            SyncEvent::Uploaded { .. } => self.uploaded = self.uploaded.saturating_add(1),
+            SyncEvent::Removed { .. } => self.removed = self.removed.saturating_add(1),
+            SyncEvent::Conflict { .. } => self.conflicts = self.conflicts.saturating_add(1),
+            SyncEvent::Failed { .. } => self.failures = self.failures.saturating_add(1),
         }
+        self.last_event = Some(event.clone());
+        self.last_event_ms = Some(now_ms());
     }
 }
 
 struct SyncRuntime {
     stop: Arc<std::sync::atomic::AtomicBool>,
+    /// Dropping the watcher closes the engine's change feed.
+    _watcher: notify::RecommendedWatcher,
 }
 
 #[derive(Clone)]
@@ -99,63 +125,155 @@ fn delete_secret(key: String) -> Result<(), String> {
     }
 }
 
+/// Creates this device's X25519 identity and returns the public key to
+/// hand to uploaders. Refuses to replace an existing one, since uploads
+/// made to it could no longer be opened.
+#[tauri::command]
+fn identity_generate() -> Result<String, String> {
+    if get_secret(IDENTITY_KEY.to_string())?.is_some() {
+        return Err("an identity already exists; delete it first".to_string());
+    }
+    let (secret, public) = neuro_client_sdk::x25519_identity();
+    set_secret(IDENTITY_KEY.to_string(), secret.to_string())?;
+    Ok(public)
+}
+
+#[tauri::command]
+fn identity_public_key() -> Result<Option<String>, String> {
+    let Some(secret) = get_secret(IDENTITY_KEY.to_string())? else {
+        return Ok(None);
+    };
+    let secret = neuro_client_sdk::parse_x25519_secret(&secret).map_err(|e| e.to_string())?;
+    Ok(Some(neuro_client_sdk::x25519_public_key(&secret)))
+}
+
+#[tauri::command]
+fn identity_delete() -> Result<(), String> {
+    delete_secret(IDENTITY_KEY.to_string())
+}
+
+fn gateway_client(base_url: &str) -> Result<GatewayClient, String> {
+    let token = get_secret(GATEWAY_TOKEN_KEY.to_string())?;
+    GatewayClient::new(base_url, token.as_deref()).map_err(|e| e.to_string())
+}
+
+/// Logs in to a gateway and keeps the session token in the OS keyring for
+/// the other `gateway_*` commands.
+#[tauri::command]
+async fn gateway_login(base_url: String, email: String, password: String) -> Result<UserProfile, String> {
+    let client = GatewayClient::new(&base_url, None).map_err(|e| e.to_string())?;
+    let session = client.login(&email, &password).await.map_err(|e| e.to_string())?;
+    if let Some(token) = client.token() {
+        set_secret(GATEWAY_TOKEN_KEY.to_string(), token)?;
+    }
+    Ok(session.user)
+}
+
+#[tauri::command]
+fn gateway_logout() -> Result<(), String> {
+    delete_secret(GATEWAY_TOKEN_KEY.to_string())
+}
+
+/// Manifests registered with the gateway, for recovering files on a new device.
+#[tauri::command]
+async fn gateway_list_manifests(base_url: String) -> Result<Vec<ManifestSummary>, String> {
+    gateway_client(&base_url)?
+        .list_manifests()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn gateway_locate_manifest(base_url: String, manifest_root: String) -> Result<LocatedManifest, String> {
+    gateway_client(&base_url)?
+        .locate_manifest(&manifest_root)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn gateway_locate_peer(base_url: String, peer_id: String) -> Result<PeerAddrs, String> {
+    gateway_client(&base_url)?
+        .locate_peer(&peer_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Starts syncing `folder` into `bucket` on the gateway at `base_url`:
+/// changed files are sealed for this device's identity and uploaded, and
+/// the whole folder is rescanned every `interval_secs`. Progress is emitted
+/// as `sync_event`.
 #[tauri::command]
 fn start_background_sync(
     app: tauri::AppHandle,
     state: tauri::State<BridgeState>,
+    base_url: String,
+    folder: String,
+    bucket: String,
+    prefix: Option<String>,
     interval_secs: u64,
 ) -> Result<SyncStatus, String> {
     if interval_secs == 0 {
         return Err("interval_secs must be > 0".to_string());
     }
+    let identity_public = identity_public_key()?.ok_or("no identity on this device; run identity_generate first")?;
 
     stop_background_sync(state.clone())?;
 
-    let stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
-    let stop_flag = stop.clone();
-    let status_ref = state.status.clone();
+    let cfg = SyncConfig {
+        folder: PathBuf::from(&folder),
+        bucket: bucket.clone(),
+        prefix: prefix.unwrap_or_default(),
+        identity_public,
+        rescan_secs: interval_secs,
+    };
+    let data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let engine = SyncEngine::new(cfg.clone(), gateway_client(&base_url)?, &cfg.state_db(&data_dir))?;
+
+    let (changes_tx, changes) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            for path in event.paths {
+                let _ = changes_tx.send(path);
+            }
+        }
+    })
+    .map_err(|e| e.to_string())?;
+    watcher
+        .watch(&cfg.folder, notify::RecursiveMode::Recursive)
+        .map_err(|e| e.to_string())?;
 
+    let stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
     {
         let mut s = state
             .status
             .lock()
             .map_err(|_| "status lock poisoned".to_string())?;
-        s.running = true;
-        s.interval_secs = interval_secs;
-        s.ticks = 0;
-        s.started_at_ms = Some(now_ms());
-        s.last_tick_ms = None;
+        *s = SyncStatus {
+            running: true,
+            folder: Some(folder),
+            bucket: Some(bucket),
+            interval_secs,
+            started_at_ms: Some(now_ms()),
+            ..SyncStatus::default()
+        };
     }
 
-    tauri::async_runtime::spawn(async move {
-        while !stop_flag.load(std::sync::atomic::Ordering::Relaxed) {
-            tauri::async_runtime::sleep(std::time::Duration::from_secs(interval_secs)).await;
-            if stop_flag.load(std::sync::atomic::Ordering::Relaxed) {
-                break;
-            }
-
-            let payload = {
-                if let Ok(mut s) = status_ref.lock() {
-                    s.ticks = s.ticks.saturating_add(1);
-                    s.last_tick_ms = Some(now_ms());
-                    Some(s.clone())
-                } else {
-                    None
-                }
-            };
-
-            if let Some(status) = payload {
-                let _ = app.emit("sync_tick", status);
-            }
+    let status_ref = state.status.clone();
+    let emit = move |event: SyncEvent| {
+        if let Ok(mut s) = status_ref.lock() {
+            s.record(&event);
         }
-    });
+        let _ = app.emit("sync_event", event);
+    };
+    tauri::async_runtime::spawn(engine.run(changes, stop.clone(), emit));
 
     {
         let mut rt = state
             .runtime
             .lock()
             .map_err(|_| "runtime lock poisoned".to_string())?;
-        *rt = Some(SyncRuntime { stop });
+        *rt = Some(SyncRuntime { stop, _watcher: watcher });
     }
 
     sync_status(state)
@@ -206,7 +324,15 @@ fn main() {
             delete_secret,
             start_background_sync,
             stop_background_sync,
-            sync_status
+            sync_status,
+            gateway_login,
+            gateway_logout,
+            gateway_list_manifests,
+            gateway_locate_manifest,
+            gateway_locate_peer,
+            identity_generate,
+            identity_public_key,
+            identity_delete
         ])
         .run(tauri::generate_context!())
         .expect("error while running neurostore shell");
@@ -0,0 +1,459 @@
+//! Folder sync: watches a local folder, seals every changed file with the
+//! client-sdk pipeline for this device's X25519 identity and stores it in a
+//! gateway bucket. Each file becomes a manifest object at its relative path
+//! plus one object per shard under `.neurostore-shards/`, so the gateway
+//! never sees plaintext.
+//!
+//! A sqlite state DB remembers what each file looked like locally and
+//! remotely at its last sync. A file whose remote manifest changed since
+//! then (another device wrote it) is not overwritten: the local version is
+//! stored next to it as a conflict copy and the remote one becomes the new
+//! baseline.
+
+use neuro_client_sdk::{process_bytes_for_recipients, ManifestBuilder, PipelineConfig, Recipient};
+use neuro_gateway_client::GatewayClient;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// How long a file must stay untouched after its last change before it is
+/// uploaded, so editors saving in several writes upload once.
+const DEBOUNCE: Duration = Duration::from_secs(2);
+/// Where shard objects live, under the sync prefix.
+const SHARD_DIR: &str = ".neurostore-shards";
+/// Files are sealed in memory; larger ones are reported and skipped.
+const MAX_SYNC_FILE_BYTES: u64 = 512 * 1024 * 1024;
+/// Audit vectors kept per shard in each manifest.
+const AUDIT_ROUNDS: usize = 4;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS files (
+    path TEXT PRIMARY KEY,
+    size INTEGER NOT NULL,
+    mtime_ms INTEGER NOT NULL,
+    content_hash TEXT NOT NULL,
+    manifest_root TEXT NOT NULL,
+    remote_etag TEXT,
+    synced_ms INTEGER NOT NULL
+);
+";
+
+#[derive(Debug, Clone)]
+pub struct SyncConfig {
+    pub folder: PathBuf,
+    pub bucket: String,
+    /// Prepended to every remote key, e.g. `laptop/`; may be empty.
+    pub prefix: String,
+    /// Hex X25519 public key files are sealed for.
+    pub identity_public: String,
+    /// How often the whole folder is rescanned, to catch changes the
+    /// watcher missed.
+    pub rescan_secs: u64,
+}
+
+impl SyncConfig {
+    /// State DB file for this folder, bucket and prefix under `data_dir`,
+    /// so switching between configurations never mixes their baselines.
+    pub fn state_db(&self, data_dir: &Path) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(self.folder.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(self.bucket.as_bytes());
+        hasher.update([0]);
+        hasher.update(self.prefix.as_bytes());
+        let id = hex::encode(hasher.finalize());
+        data_dir.join(format!("sync-{}.db", &id[..16]))
+    }
+
+    fn remote_key(&self, rel: &str) -> String {
+        format!("{}{}", self.prefix, rel)
+    }
+
+    fn shard_key(&self, cid: &str) -> String {
+        format!("{}{}/{}", self.prefix, SHARD_DIR, cid)
+    }
+}
+
+/// Emitted to the UI as `sync_event`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SyncEvent {
+    Scanned { files: usize, changed: usize },
+    Uploaded { path: String, bytes: u64, manifest_root: String },
+    Removed { path: String },
+    /// The remote file changed since the last sync; the local version was
+    /// stored at `conflict_key` instead.
+    Conflict { path: String, conflict_key: String },
+    Failed { path: String, error: String },
+}
+
+struct FileState {
+    size: u64,
+    mtime_ms: u64,
+    content_hash: String,
+    remote_etag: Option<String>,
+}
+
+struct StateDb {
+    conn: Connection,
+}
+
+impl StateDb {
+    fn open(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let conn = Connection::open(path).map_err(|e| e.to_string())?;
+        conn.execute_batch(SCHEMA).map_err(|e| e.to_string())?;
+        Ok(Self { conn })
+    }
+
+    fn get(&self, rel: &str) -> Result<Option<FileState>, String> {
+        self.conn
+            .query_row(
+                "SELECT size, mtime_ms, content_hash, remote_etag FROM files WHERE path = ?1",
+                params![rel],
+                |row| {
+                    Ok(FileState {
+                        size: row.get::<_, i64>(0)? as u64,
+                        mtime_ms: row.get::<_, i64>(1)? as u64,
+                        content_hash: row.get(2)?,
+                        remote_etag: row.get(3)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(|e| e.to_string())
+    }
+
+    /// Tracked paths equal to `rel` or below it, for a removed directory.
+    fn under(&self, rel: &str) -> Result<Vec<String>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT path FROM files WHERE path = ?1 OR substr(path, 1, length(?1) + 1) = ?1 || '/'")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![rel], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+    }
+
+    fn paths(&self) -> Result<Vec<String>, String> {
+        let mut stmt = self.conn.prepare("SELECT path FROM files").map_err(|e| e.to_string())?;
+        let rows = stmt.query_map([], |row| row.get(0)).map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+    }
+
+    fn put(&self, rel: &str, state: &FileState, manifest_root: &str) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT INTO files (path, size, mtime_ms, content_hash, manifest_root, remote_etag, synced_ms)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                 ON CONFLICT(path) DO UPDATE SET size = ?2, mtime_ms = ?3, content_hash = ?4,
+                     manifest_root = ?5, remote_etag = ?6, synced_ms = ?7",
+                params![
+                    rel,
+                    state.size as i64,
+                    state.mtime_ms as i64,
+                    state.content_hash,
+                    manifest_root,
+                    state.remote_etag,
+                    crate::now_ms() as i64
+                ],
+            )
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    fn touch(&self, rel: &str, mtime_ms: u64) -> Result<(), String> {
+        self.conn
+            .execute("UPDATE files SET mtime_ms = ?2 WHERE path = ?1", params![rel, mtime_ms as i64])
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    fn remove(&self, rel: &str) -> Result<(), String> {
+        self.conn
+            .execute("DELETE FROM files WHERE path = ?1", params![rel])
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// The gateway side of a sync. Kept apart from the state DB, whose
+/// connection cannot be shared across the awaits of an upload.
+struct Remote {
+    cfg: SyncConfig,
+    client: GatewayClient,
+}
+
+pub struct SyncEngine {
+    remote: Remote,
+    db: StateDb,
+}
+
+impl SyncEngine {
+    pub fn new(cfg: SyncConfig, client: GatewayClient, state_db: &Path) -> Result<Self, String> {
+        Recipient::x25519_hex(&cfg.identity_public).map_err(|e| e.to_string())?;
+        if !cfg.folder.is_dir() {
+            return Err(format!("{} is not a folder", cfg.folder.display()));
+        }
+        let db = StateDb::open(state_db)?;
+        Ok(Self { remote: Remote { cfg, client }, db })
+    }
+
+    /// The path below the sync folder `path` names, with `/` separators, or
+    /// `None` for paths outside it and hidden files and folders.
+    pub fn relative(&self, path: &Path) -> Option<String> {
+        let rel = path.strip_prefix(&self.remote.cfg.folder).ok()?;
+        let mut parts = Vec::new();
+        for component in rel.components() {
+            match component {
+                Component::Normal(part) => {
+                    let part = part.to_str()?;
+                    if part.starts_with('.') {
+                        return None;
+                    }
+                    parts.push(part);
+                }
+                _ => return None,
+            }
+        }
+        (!parts.is_empty()).then(|| parts.join("/"))
+    }
+
+    /// Syncs changed paths once they have been quiet for [`DEBOUNCE`], and
+    /// the whole folder at start and every `rescan_secs`. Ends when `stop`
+    /// is set or the watcher feeding `changes` is dropped.
+    pub async fn run(
+        mut self,
+        mut changes: mpsc::UnboundedReceiver<PathBuf>,
+        stop: Arc<AtomicBool>,
+        emit: impl Fn(SyncEvent) + Send + 'static,
+    ) {
+        let mut rescan = tokio::time::interval(Duration::from_secs(self.remote.cfg.rescan_secs.max(1)));
+        let mut pending: BTreeSet<String> = BTreeSet::new();
+        let mut quiet_at: Option<Instant> = None;
+        while !stop.load(Ordering::Relaxed) {
+            tokio::select! {
+                change = changes.recv() => match change {
+                    Some(path) => {
+                        if let Some(rel) = self.relative(&path) {
+                            pending.insert(rel);
+                            quiet_at = Some(Instant::now() + DEBOUNCE);
+                        }
+                    }
+                    None => break,
+                },
+                _ = tokio::time::sleep_until(quiet_at.unwrap_or_else(Instant::now)), if quiet_at.is_some() => {
+                    quiet_at = None;
+                    for rel in std::mem::take(&mut pending) {
+                        if stop.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        if let Some(event) = self.sync_path(&rel).await {
+                            emit(event);
+                        }
+                    }
+                }
+                _ = rescan.tick() => {
+                    for event in self.scan(&stop).await {
+                        emit(event);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Syncs every file whose size or modification time differs from its
+    /// last sync, and every tracked file that is gone.
+    async fn scan(&mut self, stop: &AtomicBool) -> Vec<SyncEvent> {
+        let mut files = Vec::new();
+        if let Err(error) = walk(&self.remote.cfg.folder, &mut files) {
+            return vec![SyncEvent::Failed { path: String::new(), error }];
+        }
+        let mut changed = BTreeSet::new();
+        for path in &files {
+            let Some(rel) = self.relative(path) else { continue };
+            let Ok((size, mtime_ms)) = stat(path) else { continue };
+            match self.db.get(&rel) {
+                Ok(Some(known)) if known.size == size && known.mtime_ms == mtime_ms => {}
+                _ => {
+                    changed.insert(rel);
+                }
+            }
+        }
+        for rel in self.db.paths().unwrap_or_default() {
+            if !self.remote.cfg.folder.join(&rel).is_file() {
+                changed.insert(rel);
+            }
+        }
+
+        let mut events = vec![SyncEvent::Scanned { files: files.len(), changed: changed.len() }];
+        for rel in changed {
+            if stop.load(Ordering::Relaxed) {
+                break;
+            }
+            events.extend(self.sync_path(&rel).await);
+        }
+        events
+    }
+
+    /// Brings the remote copy of `rel` in line with the local one, if it
+    /// changed. `None` when there was nothing to do.
+    async fn sync_path(&mut self, rel: &str) -> Option<SyncEvent> {
+        let result = match self.remote.cfg.folder.join(rel) {
+            path if path.is_file() => self.upload_file(rel, &path).await,
+            path if path.is_dir() => Ok(None),
+            _ => self.remove(rel).await,
+        };
+        result.unwrap_or_else(|error| Some(SyncEvent::Failed { path: rel.to_string(), error }))
+    }
+
+    async fn upload_file(&mut self, rel: &str, path: &Path) -> Result<Option<SyncEvent>, String> {
+        let (size, mtime_ms) = stat(path)?;
+        if size > MAX_SYNC_FILE_BYTES {
+            return Err(format!("{size} bytes is over the {MAX_SYNC_FILE_BYTES} byte sync limit"));
+        }
+        let known = self.db.get(rel)?;
+        if known.as_ref().is_some_and(|k| k.size == size && k.mtime_ms == mtime_ms) {
+            return Ok(None);
+        }
+        let data = tokio::fs::read(path).await.map_err(|e| e.to_string())?;
+        let content_hash = hex::encode(Sha256::digest(&data));
+        if known.as_ref().is_some_and(|k| k.content_hash == content_hash) {
+            // Touched but unchanged.
+            self.db.touch(rel, mtime_ms)?;
+            return Ok(None);
+        }
+
+        let key = self.remote.cfg.remote_key(rel);
+        let remote_etag = self.remote.etag(&key).await?;
+        let baseline = known.as_ref().and_then(|k| k.remote_etag.clone());
+        if remote_etag.is_some() && remote_etag != baseline {
+            let conflict_key = conflict_key(&key, crate::now_ms());
+            self.remote.upload(&conflict_key, data).await?;
+            // The remote version becomes the baseline; the local one is kept
+            // remotely as the conflict copy.
+            let state = FileState { size, mtime_ms, content_hash, remote_etag };
+            self.db.put(rel, &state, "")?;
+            return Ok(Some(SyncEvent::Conflict { path: rel.to_string(), conflict_key }));
+        }
+
+        let (manifest_root, etag) = self.remote.upload(&key, data).await?;
+        let state = FileState { size, mtime_ms, content_hash, remote_etag: etag };
+        self.db.put(rel, &state, &manifest_root)?;
+        Ok(Some(SyncEvent::Uploaded { path: rel.to_string(), bytes: size, manifest_root }))
+    }
+
+    /// Deletes the remote copies of `rel`, or of everything tracked below
+    /// it when it was a folder. A remote copy changed since the last sync
+    /// is left alone.
+    async fn remove(&mut self, rel: &str) -> Result<Option<SyncEvent>, String> {
+        let tracked = self.db.under(rel)?;
+        if tracked.is_empty() {
+            return Ok(None);
+        }
+        for path in &tracked {
+            let key = self.remote.cfg.remote_key(path);
+            let baseline = self.db.get(path)?.and_then(|k| k.remote_etag);
+            let remote_etag = self.remote.etag(&key).await?;
+            if remote_etag.is_some() && remote_etag == baseline {
+                self.remote.client.delete_object(&self.remote.cfg.bucket, &key).await.map_err(|e| e.to_string())?;
+            }
+            self.db.remove(path)?;
+        }
+        Ok(Some(SyncEvent::Removed { path: rel.to_string() }))
+    }
+}
+
+impl Remote {
+    async fn etag(&self, key: &str) -> Result<Option<String>, String> {
+        let listing = self
+            .client
+            .list_objects(&self.cfg.bucket, Some(key), None)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(listing.contents.into_iter().find(|entry| entry.key == key).map(|entry| entry.etag))
+    }
+
+    /// Seals `data` for the identity, stores its shards and then its
+    /// manifest at `key`. Returns the manifest root and the manifest's ETag.
+    async fn upload(&self, key: &str, data: Vec<u8>) -> Result<(String, Option<String>), String> {
+        let identity_public = self.cfg.identity_public.clone();
+        let (output, content_key) = tokio::task::spawn_blocking(move || {
+            let recipient = Recipient::x25519_hex(&identity_public)?;
+            let cfg = PipelineConfig { holes: true, ..PipelineConfig::default() };
+            process_bytes_for_recipients(&data, &[recipient], cfg)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+
+        let mut builder = ManifestBuilder::for_output(&output).gateways(vec![self.client.base_url().to_string()]);
+        for shard in &output.shards {
+            self.client
+                .put_object(&self.cfg.bucket, &self.cfg.shard_key(&shard.cid), shard.bytes.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+            builder.place_shard(shard, Vec::new(), AUDIT_ROUNDS).map_err(|e| e.to_string())?;
+        }
+        let manifest = builder.seal_with_key(&content_key).map_err(|e| e.to_string())?;
+        let json = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+        let put = self
+            .client
+            .put_object(&self.cfg.bucket, key, json)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok((manifest.manifest_root, put.etag))
+    }
+}
+
+/// `key` with ` (conflict <timestamp>)` before its extension.
+fn conflict_key(key: &str, now_ms: u64) -> String {
+    let stamp = chrono::DateTime::from_timestamp_millis(now_ms as i64)
+        .map(|t| t.format("%Y%m%d-%H%M%S").to_string())
+        .unwrap_or_else(|| now_ms.to_string());
+    let name_start = key.rfind('/').map_or(0, |i| i + 1);
+    match key[name_start..].rfind('.').filter(|&i| i > 0) {
+        Some(dot) => {
+            let (stem, ext) = key.split_at(name_start + dot);
+            format!("{stem} (conflict {stamp}){ext}")
+        }
+        None => format!("{key} (conflict {stamp})"),
+    }
+}
+
+fn stat(path: &Path) -> Result<(u64, u64), String> {
+    let meta = std::fs::metadata(path).map_err(|e| e.to_string())?;
+    let mtime_ms = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_millis() as u64);
+    Ok((meta.len(), mtime_ms))
+}
+
+/// Every regular file below `dir`, skipping hidden entries.
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    for entry in std::fs::read_dir(dir).map_err(|e| format!("{}: {e}", dir.display()))? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let file_type = entry.file_type().map_err(|e| e.to_string())?;
+        if file_type.is_dir() {
+            walk(&entry.path(), files)?;
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,155 @@
+// ═══════════════════════════════════════════════════════════════
+// NeuroStore — Pipeline Hot-Path Benchmarks
+// Chunk encryption · RS encode/decode · Merkle root · full pipeline
+// ═══════════════════════════════════════════════════════════════
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use neuro_client_sdk::{
+    encrypt_chunk, erasure_decode, erasure_encode, manifest_root_from_shards, process_bytes,
+    reconstruct_bytes, CidFormat, ErasureBackend, HashAlgorithm, PipelineConfig, DEFAULT_CHUNK_SIZE,
+};
+
+const CHUNK_SIZES: &[usize] = &[64 * 1024, DEFAULT_CHUNK_SIZE, 1024 * 1024];
+const SHARD_CONFIGS: &[(usize, usize)] = &[(4, 2), (6, 3), (10, 4), (10, 10)];
+const KEY: [u8; 32] = [7u8; 32];
+
+fn payload(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+fn bench_encrypt(c: &mut Criterion) {
+    // Only AES-256-GCM is wired into the pipeline today; new suites get
+    // their own function id here so baselines stay comparable.
+    let mut group = c.benchmark_group("encrypt_chunk/aes256gcm");
+    for &size in CHUNK_SIZES {
+        let data = payload(size);
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.iter(|| encrypt_chunk(black_box(data), &KEY, 0, 1).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_erasure(c: &mut Criterion) {
+    let mut encode = c.benchmark_group("erasure_encode");
+    for &size in CHUNK_SIZES {
+        let enc = encrypt_chunk(&payload(size), &KEY, 0, 1).unwrap();
+        for &(data, parity) in SHARD_CONFIGS {
+            encode.throughput(Throughput::Bytes(size as u64));
+            encode.bench_with_input(
+                BenchmarkId::new(format!("{data}+{parity}"), size),
+                &enc,
+                |b, enc| b.iter(|| erasure_encode(black_box(enc), data, parity).unwrap()),
+            );
+        }
+    }
+    encode.finish();
+
+    let mut decode = c.benchmark_group("erasure_decode");
+    for &size in CHUNK_SIZES {
+        let enc = encrypt_chunk(&payload(size), &KEY, 0, 1).unwrap();
+        let payload_len = 12 + enc.ciphertext.len();
+        for &(data, parity) in SHARD_CONFIGS {
+            let shards = erasure_encode(&enc, data, parity).unwrap();
+            // Worst case: every parity shard is needed to fill in lost data shards.
+            let degraded: Vec<Option<Vec<u8>>> = shards
+                .into_iter()
+                .enumerate()
+                .map(|(i, s)| (i >= parity.min(data)).then_some(s))
+                .collect();
+            decode.throughput(Throughput::Bytes(size as u64));
+            decode.bench_with_input(
+                BenchmarkId::new(format!("{data}+{parity}"), size),
+                &degraded,
+                |b, degraded| {
+                    b.iter(|| {
+                        erasure_decode(black_box(degraded.clone()), data, parity, payload_len)
+                            .unwrap()
+                    })
+                },
+            );
+        }
+    }
+    decode.finish();
+}
+
+fn bench_manifest_root(c: &mut Criterion) {
+    let mut group = c.benchmark_group("manifest_root");
+    let layouts = [1024 * 1024, 16 * 1024 * 1024]
+        .into_iter()
+        .flat_map(|total| [HashAlgorithm::Sha256, HashAlgorithm::Blake3].map(|alg| (total, alg)));
+    for (total, hash_algorithm) in layouts {
+        let cfg = PipelineConfig {
+            chunk_size: 64 * 1024,
+            cid_format: CidFormat::V1,
+            hash_algorithm,
+            ..PipelineConfig::default()
+        };
+        let output = process_bytes(&payload(total), "bench", cfg).unwrap();
+        let id = format!("{}/{hash_algorithm:?}", output.shards.len()).to_lowercase();
+        group.bench_with_input(
+            BenchmarkId::from_parameter(id),
+            &output.shards,
+            |b, shards| b.iter(|| manifest_root_from_shards(black_box(shards))),
+        );
+    }
+    group.finish();
+}
+
+fn bench_pipeline(c: &mut Criterion) {
+    // End-to-end numbers include one Argon2 derivation per call.
+    let total = 8 * 1024 * 1024;
+    let data = payload(total);
+    let mut group = c.benchmark_group("pipeline");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(total as u64));
+    for (&(data_shards, parity_shards), backend) in SHARD_CONFIGS
+        .iter()
+        .flat_map(|cfg| [ErasureBackend::Serial, ErasureBackend::Parallel].map(|b| (cfg, b)))
+    {
+        let cfg = PipelineConfig {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            data_shards,
+            parity_shards,
+            erasure_backend: backend,
+            cid_format: Default::default(),
+            hash_algorithm: Default::default(),
+            auto_adjust: false,
+            deterministic_salt: None,
+            outer_code: None,
+            holes: false,
+        };
+        let id = format!("{data_shards}+{parity_shards}/{:?}", backend.resolve()).to_lowercase();
+        group.bench_with_input(BenchmarkId::new("process_bytes", &id), &cfg, |b, cfg| {
+            b.iter(|| process_bytes(black_box(&data), "bench", cfg.clone()).unwrap())
+        });
+
+        let output = process_bytes(&data, "bench", cfg).unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("reconstruct_bytes", &id),
+            &output,
+            |b, output| {
+                b.iter(|| {
+                    reconstruct_bytes(
+                        black_box(&output.shards),
+                        "bench",
+                        &output.salt,
+                        output.total_bytes,
+                    )
+                    .unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_encrypt,
+    bench_erasure,
+    bench_manifest_root,
+    bench_pipeline
+);
+criterion_main!(benches);
@@ -0,0 +1,267 @@
+use anyhow::{anyhow, Result};
+use reed_solomon_erasure::galois_8::{self, ReedSolomon};
+use reed_solomon_erasure::Field;
+use serde::{Deserialize, Serialize};
+
+use crate::EncryptedChunk;
+
+/// How chunk-level Reed-Solomon work is scheduled. Every backend runs the
+/// same galois_8 code, so shards are byte-identical whichever one produced
+/// them. The `simd` cargo feature builds in the accelerated galois_8
+/// kernels for all backends; they run only on CPUs that support them (see
+/// [`simd_enabled`]), and scalar arithmetic takes over everywhere else.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErasureBackend {
+    /// Pick the fastest backend available on this build and machine.
+    #[default]
+    Auto,
+    /// Encode/decode one chunk at a time on the calling thread.
+    Serial,
+    /// Spread chunks across a rayon pool (requires the `parallel` feature).
+    Parallel,
+}
+
+impl ErasureBackend {
+    /// Resolves `Auto` and downgrades backends that were not compiled in,
+    /// so callers always get something runnable.
+    pub fn resolve(self) -> Self {
+        match self {
+            Self::Serial => Self::Serial,
+            Self::Parallel | Self::Auto if parallel_available() => Self::Parallel,
+            Self::Parallel | Self::Auto => Self::Serial,
+        }
+    }
+}
+
+/// True when galois_8 was built with the SIMD kernels and this CPU can run
+/// them.
+pub fn simd_enabled() -> bool {
+    cfg!(feature = "simd") && cpu_runs_simd_kernels()
+}
+
+/// The kernels are compiled for Haswell on x86_64 (unless
+/// `RUST_REED_SOLOMON_ERASURE_ARCH` says otherwise), so an older CPU would
+/// fault on the first chunk. NEON is part of the aarch64 baseline.
+fn cpu_runs_simd_kernels() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        std::arch::is_x86_feature_detected!("avx2")
+            && std::arch::is_x86_feature_detected!("ssse3")
+            && std::arch::is_x86_feature_detected!("bmi1")
+            && std::arch::is_x86_feature_detected!("bmi2")
+            && std::arch::is_x86_feature_detected!("fma")
+            && std::arch::is_x86_feature_detected!("lzcnt")
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        true
+    }
+}
+
+/// GF(2^8) with galois_8's tables but none of its slice kernels, one byte
+/// at a time. Same field, same matrices, so the shards are byte-identical.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ScalarField;
+
+impl Field for ScalarField {
+    const ORDER: usize = 256;
+    type Elem = u8;
+
+    fn add(a: u8, b: u8) -> u8 {
+        galois_8::add(a, b)
+    }
+
+    fn mul(a: u8, b: u8) -> u8 {
+        galois_8::mul(a, b)
+    }
+
+    fn div(a: u8, b: u8) -> u8 {
+        galois_8::div(a, b)
+    }
+
+    fn exp(a: u8, n: usize) -> u8 {
+        galois_8::exp(a, n)
+    }
+
+    fn zero() -> u8 {
+        0
+    }
+
+    fn one() -> u8 {
+        1
+    }
+
+    fn nth_internal(n: usize) -> u8 {
+        n as u8
+    }
+}
+
+/// Reed-Solomon over galois_8, or over [`ScalarField`] when this is a
+/// `simd` build on a CPU that cannot run the kernels.
+pub(crate) enum Codec {
+    Galois8(ReedSolomon),
+    Scalar(reed_solomon_erasure::ReedSolomon<ScalarField>),
+}
+
+impl Codec {
+    pub(crate) fn new(data_shards: usize, parity_shards: usize) -> Result<Self, reed_solomon_erasure::Error> {
+        Self::build(data_shards, parity_shards, cfg!(feature = "simd") && !cpu_runs_simd_kernels())
+    }
+
+    fn build(data_shards: usize, parity_shards: usize, scalar: bool) -> Result<Self, reed_solomon_erasure::Error> {
+        Ok(if scalar {
+            Self::Scalar(reed_solomon_erasure::ReedSolomon::new(data_shards, parity_shards)?)
+        } else {
+            Self::Galois8(ReedSolomon::new(data_shards, parity_shards)?)
+        })
+    }
+
+    pub(crate) fn encode(&self, shards: &mut [Vec<u8>]) -> Result<(), reed_solomon_erasure::Error> {
+        match self {
+            Self::Galois8(rs) => rs.encode(shards),
+            Self::Scalar(rs) => rs.encode(shards),
+        }
+    }
+
+    pub(crate) fn reconstruct(&self, shards: &mut [Option<Vec<u8>>]) -> Result<(), reed_solomon_erasure::Error> {
+        match self {
+            Self::Galois8(rs) => rs.reconstruct(shards),
+            Self::Scalar(rs) => rs.reconstruct(shards),
+        }
+    }
+
+    pub(crate) fn reconstruct_data(&self, shards: &mut [Option<Vec<u8>>]) -> Result<(), reed_solomon_erasure::Error> {
+        match self {
+            Self::Galois8(rs) => rs.reconstruct_data(shards),
+            Self::Scalar(rs) => rs.reconstruct_data(shards),
+        }
+    }
+}
+
+fn parallel_available() -> bool {
+    cfg!(feature = "parallel")
+        && std::thread::available_parallelism()
+            .map(|n| n.get() > 1)
+            .unwrap_or(false)
+}
+
+/// Runs `f` over `items` with the given backend, keeping input order.
+pub(crate) fn map_chunks<T, R, F>(backend: ErasureBackend, items: Vec<T>, f: F) -> Result<Vec<R>>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> Result<R> + Sync + Send,
+{
+    match backend.resolve() {
+        #[cfg(feature = "parallel")]
+        ErasureBackend::Parallel => {
+            use rayon::prelude::*;
+            items.into_par_iter().map(f).collect()
+        }
+        _ => items.into_iter().map(f).collect(),
+    }
+}
+
+pub fn erasure_encode(
+    enc: &EncryptedChunk,
+    data_shards: usize,
+    parity_shards: usize,
+) -> Result<Vec<Vec<u8>>> {
+    let mut payload = Vec::with_capacity(12 + enc.ciphertext.len());
+    payload.extend_from_slice(&enc.nonce);
+    payload.extend_from_slice(&enc.ciphertext);
+    encode_payload(&payload, data_shards, parity_shards)
+}
+
+/// Splits an already assembled payload into data shards and adds parity.
+/// The same payload always gives the same shards.
+pub(crate) fn encode_payload(
+    payload: &[u8],
+    data_shards: usize,
+    parity_shards: usize,
+) -> Result<Vec<Vec<u8>>> {
+    let rs = Codec::new(data_shards, parity_shards)?;
+    let shard_len = payload.len().div_ceil(data_shards);
+    let total_shards = data_shards + parity_shards;
+
+    let mut shards: Vec<Vec<u8>> = (0..total_shards).map(|_| vec![0u8; shard_len]).collect();
+
+    for (i, chunk) in payload.chunks(shard_len).enumerate() {
+        shards[i][..chunk.len()].copy_from_slice(chunk);
+    }
+
+    rs.encode(&mut shards)?;
+    Ok(shards)
+}
+
+/// Rebuilds the `nonce || ciphertext` payload of one chunk from whatever
+/// shards survived; missing slots are `None`.
+pub fn erasure_decode(
+    mut shards: Vec<Option<Vec<u8>>>,
+    data_shards: usize,
+    parity_shards: usize,
+    payload_len: usize,
+) -> Result<Vec<u8>> {
+    let rs = Codec::new(data_shards, parity_shards)?;
+    rs.reconstruct(&mut shards)?;
+
+    let shard_len = shards
+        .iter()
+        .flatten()
+        .next()
+        .map(|s| s.len())
+        .unwrap_or(0);
+    let mut payload = Vec::with_capacity(data_shards * shard_len);
+    for maybe in shards.iter().take(data_shards) {
+        let Some(bytes) = maybe else {
+            return Err(anyhow!("failed to reconstruct data shards"));
+        };
+        payload.extend_from_slice(bytes);
+    }
+    payload.truncate(payload_len);
+    Ok(payload)
+}
+
+/// Rebuilds every shard of one chunk, data and parity alike, from any
+/// `data_shards` survivors. Nothing is decrypted, so a repairer without the
+/// password can restore a lost shard byte-for-byte.
+pub fn erasure_regenerate(
+    mut shards: Vec<Option<Vec<u8>>>,
+    data_shards: usize,
+    parity_shards: usize,
+) -> Result<Vec<Vec<u8>>> {
+    let rs = Codec::new(data_shards, parity_shards)?;
+    rs.reconstruct(&mut shards)?;
+    shards
+        .into_iter()
+        .map(|s| s.ok_or_else(|| anyhow!("failed to regenerate shards")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scalar_fallback_gives_the_same_shards_and_recovers() {
+        let payload: Vec<u8> = (0..10_000u32).map(|i| (i * 31 % 251) as u8).collect();
+        let expected = encode_payload(&payload, 4, 2).unwrap();
+
+        // Forced, whatever this CPU supports.
+        let scalar = Codec::build(4, 2, true).unwrap();
+        assert!(matches!(scalar, Codec::Scalar(_)));
+        let mut shards = expected.clone();
+        for parity in &mut shards[4..] {
+            parity.fill(0);
+        }
+        scalar.encode(&mut shards).unwrap();
+        assert_eq!(shards, expected);
+
+        let mut damaged: Vec<Option<Vec<u8>>> = expected.iter().cloned().map(Some).collect();
+        damaged[0] = None;
+        damaged[5] = None;
+        scalar.reconstruct(&mut damaged).unwrap();
+        assert_eq!(damaged.into_iter().flatten().collect::<Vec<_>>(), expected);
+    }
+}
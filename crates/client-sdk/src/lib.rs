@@ -1,19 +1,85 @@
-use aes_gcm::{aead::Aead, Aes256Gcm, KeyInit, Nonce};
+use aes_gcm::{
+    aead::{Aead, Payload},
+    Aes256Gcm, KeyInit, Nonce,
+};
 use anyhow::{anyhow, Result};
 use argon2::{password_hash::SaltString, Argon2};
 use rand::{rngs::OsRng, RngCore};
-use reed_solomon_erasure::galois_8::ReedSolomon;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::BTreeMap;
+use zeroize::Zeroizing;
+
+mod erasure;
+mod manifest;
+mod outer;
+mod recipients;
+mod share;
+
+pub use erasure::{
+    erasure_decode, erasure_encode, erasure_regenerate, simd_enabled, ErasureBackend,
+};
+pub use manifest::{
+    audit_token, build_audit_vectors, check_manifest_version, compute_manifest_hash,
+    derive_manifest_auth_tag, manifest_cid_format, manifest_version, ManifestBuilder,
+    ManifestShard, UploadManifest, MANIFEST_VERSION, MANIFEST_VERSION_CIDV1,
+    MANIFEST_VERSION_GATEWAYS, MANIFEST_VERSION_GATEWAYS_CIDV1, MANIFEST_VERSION_HMAC,
+    MANIFEST_VERSION_HMAC_CIDV1, MANIFEST_VERSION_RECIPIENTS, MANIFEST_VERSION_RECIPIENTS_CIDV1,
+    MAX_PEER_ADDRS, peer_id_of, recipients_manifest_version,
+};
+pub use outer::{recover_chunks, OuterCode};
+pub use recipients::{
+    generate_content_key, parse_x25519_secret, unwrap_with_password, unwrap_with_x25519,
+    wrap_content_key, x25519_identity, x25519_public_key, KeyWrap, Recipient,
+};
+pub use share::{ShareClaims, ShareToken, SCOPE_RETRIEVE, SHARE_TOKEN_PREFIX};
+pub use neuro_protocol::cid::{CidFormat, HashAlgorithm};
 
 pub const DEFAULT_CHUNK_SIZE: usize = 256 * 1024;
 
+/// Upper bound on `data_shards + parity_shards`. galois_8 Reed-Solomon works
+/// over a 256-element field; staying below it also keeps every shard index
+/// within a byte.
+pub const MAX_TOTAL_SHARDS: usize = 255;
+
+const CHUNK_AAD_TAG: &[u8; 16] = b"neurostore-chunk";
+const NONCE_TAG: &[u8; 16] = b"neurostore-nonce";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PipelineConfig {
     pub chunk_size: usize,
     pub data_shards: usize,
     pub parity_shards: usize,
+    #[serde(default)]
+    pub erasure_backend: ErasureBackend,
+    /// How shard CIDs are written; see `neuro_protocol::cid`.
+    #[serde(default)]
+    pub cid_format: CidFormat,
+    /// What shard CIDs and the manifest root are hashed with. BLAKE3 is
+    /// only written as CIDv1.
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+    /// Clamp out-of-range values in `process_bytes` instead of rejecting
+    /// them; what changed is reported in `PipelineOutput::warnings`.
+    #[serde(default)]
+    pub auto_adjust: bool,
+    /// Encrypt reproducibly under this (base64) salt: nonces are derived
+    /// from the key and each chunk instead of drawn at random, so the same
+    /// file, password, salt and layout always give the same shards and
+    /// manifest root. Identical chunks become recognisable as such, so this
+    /// is for audit and escrow uploads rather than the default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deterministic_salt: Option<String>,
+    /// Add parity chunks across groups of chunks, so a chunk whose shards
+    /// are all lost can be rebuilt; see [`OuterCode`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outer_code: Option<OuterCode>,
+    /// Keep chunks that are one byte repeated, such as the zero runs of
+    /// disk images and VM snapshots, as [`Hole`]s in the manifest instead
+    /// of encrypting and storing them. Which chunks are constant, and with
+    /// what byte, is then readable from the manifest, so this is opt-in.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub holes: bool,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -29,10 +95,99 @@ impl Default for PipelineConfig {
             chunk_size: DEFAULT_CHUNK_SIZE,
             data_shards: 4,
             parity_shards: 2,
+            erasure_backend: ErasureBackend::Auto,
+            cid_format: CidFormat::Sha256Hex,
+            hash_algorithm: HashAlgorithm::Sha256,
+            auto_adjust: false,
+            deterministic_salt: None,
+            outer_code: None,
+            holes: false,
         }
     }
 }
 
+impl PipelineConfig {
+    /// Rejects configurations the erasure coder cannot run, naming the
+    /// offending value.
+    pub fn validate(&self) -> Result<()> {
+        if self.chunk_size == 0 {
+            return Err(anyhow!("chunk_size must be > 0"));
+        }
+        if self.data_shards < 2 {
+            return Err(anyhow!(
+                "data_shards must be >= 2, got {}",
+                self.data_shards
+            ));
+        }
+        if self.parity_shards < 1 {
+            return Err(anyhow!(
+                "parity_shards must be >= 1, got {}",
+                self.parity_shards
+            ));
+        }
+        if !self.hash_algorithm.writable_as(self.cid_format) {
+            return Err(anyhow!("blake3 shard CIDs can only be written as CIDv1"));
+        }
+        if let Some(outer) = &self.outer_code {
+            outer.validate()?;
+            if self.holes {
+                return Err(anyhow!("holes cannot be combined with an outer code"));
+            }
+        }
+        match self.data_shards.checked_add(self.parity_shards) {
+            Some(total) if total <= MAX_TOTAL_SHARDS => Ok(()),
+            _ => Err(anyhow!(
+                "data_shards ({}) + parity_shards ({}) exceeds the Reed-Solomon limit of {} shards per chunk",
+                self.data_shards,
+                self.parity_shards,
+                MAX_TOTAL_SHARDS
+            )),
+        }
+    }
+
+    /// Clamps every field into the range `validate` accepts, returning a
+    /// note for each change. Parity gives way first when the total is over
+    /// the limit.
+    pub fn clamp_to_limits(&mut self) -> Vec<String> {
+        let mut notes = Vec::new();
+        if self.chunk_size == 0 {
+            notes.push(format!("chunk_size 0 replaced with {DEFAULT_CHUNK_SIZE}"));
+            self.chunk_size = DEFAULT_CHUNK_SIZE;
+        }
+        let data = self.data_shards.clamp(2, MAX_TOTAL_SHARDS - 1);
+        if data != self.data_shards {
+            notes.push(format!("data_shards {} adjusted to {data}", self.data_shards));
+            self.data_shards = data;
+        }
+        let parity = self.parity_shards.clamp(1, MAX_TOTAL_SHARDS - data);
+        if parity != self.parity_shards {
+            notes.push(format!(
+                "parity_shards {} adjusted to {parity}",
+                self.parity_shards
+            ));
+            self.parity_shards = parity;
+        }
+        if !self.hash_algorithm.writable_as(self.cid_format) {
+            notes.push("cid_format switched to v1 for blake3 CIDs".to_string());
+            self.cid_format = CidFormat::V1;
+        }
+        notes
+    }
+
+    /// `clamp_to_limits`, plus a warning when a chunk has more shards than
+    /// there are peers, so some peer must hold several shards of one chunk.
+    pub fn adjust_for_peers(&mut self, peer_count: usize) -> Vec<String> {
+        let mut notes = self.clamp_to_limits();
+        let total = self.data_shards + self.parity_shards;
+        if peer_count < total {
+            notes.push(format!(
+                "{total} shards per chunk across {peer_count} peers: fewer than one peer per shard, so losing one peer can cost several shards of a chunk"
+            ));
+        }
+        notes
+    }
+}
+
 pub fn adaptive_config(
     total_bytes: usize,
     peer_count: usize,
@@ -59,8 +214,9 @@ pub fn adaptive_config(
     }
 
     if peer_count > 0 {
-        // Keep at least 2 shards per peer target when enough peers are available.
-        let target_total = usize::max(4, usize::min(12, peer_count.saturating_mul(2)));
+        // One shard per peer where possible; with fewer than three peers the
+        // 2+1 minimum still applies and `adjust_for_peers` reports it.
+        let target_total = peer_count.clamp(3, 12);
         let base_data = usize::max(2, usize::min(cfg.data_shards, target_total - 1));
         cfg.data_shards = base_data;
         cfg.parity_shards = usize::max(1, target_total.saturating_sub(base_data));
@@ -86,48 +242,164 @@ pub struct Shard {
     pub parity_shards: usize,
 }
 
+/// A chunk of `len` copies of `byte`, rebuilt from the manifest alone: it
+/// has no shards and is never fetched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hole {
+    pub chunk_index: usize,
+    pub len: usize,
+    pub byte: u8,
+}
+
+impl Hole {
+    /// The hole `chunk` makes, if it is a single byte repeated.
+    pub fn of(chunk_index: usize, chunk: &[u8]) -> Option<Self> {
+        let (&byte, rest) = chunk.split_first()?;
+        rest.iter().all(|b| *b == byte).then_some(Self {
+            chunk_index,
+            len: chunk.len(),
+            byte,
+        })
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        vec![self.byte; self.len]
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PipelineOutput {
     pub salt: String,
     pub shards: Vec<Shard>,
     pub manifest_root: String,
     pub total_bytes: usize,
+    /// Data chunks only, holes included; outer parity chunks, if any, are
+    /// numbered on from here.
     pub chunk_count: usize,
+    /// Chunks left out of `shards` under [`PipelineConfig::holes`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub holes: Vec<Hole>,
+    /// The configuration the shards were actually encoded with.
+    #[serde(default)]
+    pub config: PipelineConfig,
+    /// Adjustments made under `auto_adjust`.
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// The content key wrapped for each recipient, when the chunks were
+    /// sealed for several (see [`process_bytes_for_recipients`]).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipients: Vec<KeyWrap>,
 }
 
+/// The merkle root over the shard CIDs, hashed with the algorithm the CIDs
+/// themselves were made with.
 pub fn manifest_root_from_shards(shards: &[Shard]) -> String {
+    let algorithm = shards
+        .first()
+        .and_then(|s| HashAlgorithm::of(&s.cid))
+        .unwrap_or_default();
     let items: Vec<&str> = shards.iter().map(|s| s.cid.as_str()).collect();
-    merkle_root(&items)
+    merkle_root(algorithm, &items)
 }
 
-pub fn process_bytes(input: &[u8], password: &str, cfg: PipelineConfig) -> Result<PipelineOutput> {
-    validate_cfg(&cfg)?;
+pub fn process_bytes(
+    input: &[u8],
+    password: &str,
+    mut cfg: PipelineConfig,
+) -> Result<PipelineOutput> {
+    let warnings = if cfg.auto_adjust {
+        cfg.clamp_to_limits()
+    } else {
+        Vec::new()
+    };
+    cfg.validate()?;
 
-    let salt = SaltString::generate(&mut OsRng);
+    let salt = match &cfg.deterministic_salt {
+        Some(salt) => parse_deterministic_salt(salt)?,
+        None => SaltString::generate(&mut OsRng),
+    };
     let key = derive_key(password, &salt)?;
+    encode_chunks(input, &key, salt, cfg, warnings)
+}
 
-    let mut shards_out = Vec::new();
-    let mut chunk_count = 0usize;
-    for (idx, chunk) in input.chunks(cfg.chunk_size).enumerate() {
-        chunk_count += 1;
-        let enc = encrypt_chunk(chunk, &key)?;
-        let payload_len = 12 + enc.ciphertext.len();
-        let encoded_shards = erasure_encode(&enc, cfg.data_shards, cfg.parity_shards)?;
-        for (sidx, shard) in encoded_shards.into_iter().enumerate() {
-            let cid = sha256_hex(&shard);
-            shards_out.push(Shard {
-                chunk_index: idx,
-                shard_index: sidx,
-                cid,
-                bytes: shard,
-                payload_len,
-                data_shards: cfg.data_shards,
-                parity_shards: cfg.parity_shards,
-            });
-        }
+/// [`process_bytes`] for several recipients: the chunks are sealed under a
+/// random content key, which is wrapped for each recipient and returned
+/// alongside so the caller can sign the manifest with it. Deterministic
+/// encryption is refused, since a random key can never reproduce a root.
+pub fn process_bytes_for_recipients(
+    input: &[u8],
+    recipients: &[Recipient],
+    mut cfg: PipelineConfig,
+) -> Result<(PipelineOutput, Zeroizing<[u8; 32]>)> {
+    if recipients.is_empty() {
+        return Err(anyhow!("at least one recipient is required"));
+    }
+    if cfg.deterministic_salt.is_some() {
+        return Err(anyhow!("deterministic encryption cannot be combined with recipients"));
+    }
+    let warnings = if cfg.auto_adjust {
+        cfg.clamp_to_limits()
+    } else {
+        Vec::new()
+    };
+    cfg.validate()?;
+
+    let key = generate_content_key();
+    let mut output = encode_chunks(input, &key, SaltString::generate(&mut OsRng), cfg, warnings)?;
+    output.recipients = recipients
+        .iter()
+        .map(|recipient| wrap_content_key(&key, recipient))
+        .collect::<Result<_>>()?;
+    Ok((output, key))
+}
+
+fn encode_chunks(
+    input: &[u8],
+    key: &[u8; 32],
+    salt: SaltString,
+    cfg: PipelineConfig,
+    warnings: Vec<String>,
+) -> Result<PipelineOutput> {
+    let mut chunks: Vec<(usize, &[u8])> = input.chunks(cfg.chunk_size).enumerate().collect();
+    let chunk_count = chunks.len();
+    let mut holes = Vec::new();
+    if cfg.holes {
+        chunks.retain(|(idx, chunk)| match Hole::of(*idx, chunk) {
+            Some(hole) => {
+                holes.push(hole);
+                false
+            }
+            None => true,
+        });
+    }
+    let deterministic = cfg.deterministic_salt.is_some();
+    let encoded = erasure::map_chunks(cfg.erasure_backend, chunks, |(idx, chunk)| {
+        let enc = if deterministic {
+            let nonce = deterministic_nonce(chunk, key, idx, chunk_count);
+            encrypt_chunk_with_nonce(chunk, key, nonce, idx, chunk_count)?
+        } else {
+            encrypt_chunk(chunk, key, idx, chunk_count)?
+        };
+        let mut payload = Vec::with_capacity(12 + enc.ciphertext.len());
+        payload.extend_from_slice(&enc.nonce);
+        payload.extend_from_slice(&enc.ciphertext);
+        let shards = payload_shards(idx, &payload, &cfg)?;
+        Ok((payload, shards))
+    })?;
+    let (payloads, chunk_shards): (Vec<Vec<u8>>, Vec<Vec<Shard>>) = encoded.into_iter().unzip();
+    let mut shards_out: Vec<Shard> = chunk_shards.into_iter().flatten().collect();
+    if let Some(outer) = &cfg.outer_code {
+        let parity = outer::encode_parity(&payloads, outer)?;
+        let parity_shards = erasure::map_chunks(
+            cfg.erasure_backend,
+            parity.iter().enumerate().collect(),
+            |(offset, payload)| payload_shards(chunk_count + offset, payload, &cfg),
+        )?;
+        shards_out.extend(parity_shards.into_iter().flatten());
     }
 
     let manifest_root = merkle_root(
+        cfg.hash_algorithm,
         &shards_out
             .iter()
             .map(|s| s.cid.as_str())
@@ -140,17 +412,92 @@ pub fn process_bytes(input: &[u8], password: &str, cfg: PipelineConfig) -> Resul
         manifest_root,
         total_bytes: input.len(),
         chunk_count,
+        holes,
+        config: cfg,
+        warnings,
+        recipients: Vec::new(),
     })
 }
 
-pub fn reconstruct_bytes(shards: &[Shard], password: &str, salt: &str) -> Result<Vec<u8>> {
-    if shards.is_empty() {
+/// Erasure-codes one chunk's `nonce || ciphertext` (or an outer parity
+/// chunk) into shards at `chunk_index`.
+fn payload_shards(chunk_index: usize, payload: &[u8], cfg: &PipelineConfig) -> Result<Vec<Shard>> {
+    Ok(erasure::encode_payload(payload, cfg.data_shards, cfg.parity_shards)?
+        .into_iter()
+        .enumerate()
+        .map(|(sidx, shard)| Shard {
+            chunk_index,
+            shard_index: sidx,
+            cid: neuro_protocol::cid::for_data(cfg.cid_format, cfg.hash_algorithm, &shard)
+                .expect("validate() rejects unwritable CID formats"),
+            bytes: shard,
+            payload_len: payload.len(),
+            data_shards: cfg.data_shards,
+            parity_shards: cfg.parity_shards,
+        })
+        .collect())
+}
+
+/// Decodes every chunk and returns exactly `expected_total_bytes` bytes.
+/// Only the final chunk may run past the expected total (padding); a short
+/// result or an overrun in any earlier chunk is an error.
+pub fn reconstruct_bytes(
+    shards: &[Shard],
+    password: &str,
+    salt: &str,
+    expected_total_bytes: usize,
+) -> Result<Vec<u8>> {
+    reconstruct_with(shards, &[], || ChunkDecoder::new(password, salt), expected_total_bytes)
+}
+
+/// [`reconstruct_bytes`] for an upload with holes: the hole chunks are
+/// filled in locally and only the rest come from `shards`.
+pub fn reconstruct_sparse(
+    shards: &[Shard],
+    holes: &[Hole],
+    password: &str,
+    salt: &str,
+    expected_total_bytes: usize,
+) -> Result<Vec<u8>> {
+    reconstruct_with(shards, holes, || ChunkDecoder::new(password, salt), expected_total_bytes)
+}
+
+/// [`reconstruct_bytes`] with the chunk key itself, e.g. one opened from a
+/// [`ShareToken`], instead of the password.
+pub fn reconstruct_bytes_with_key(
+    shards: &[Shard],
+    key: Zeroizing<[u8; 32]>,
+    expected_total_bytes: usize,
+) -> Result<Vec<u8>> {
+    reconstruct_with(shards, &[], || Ok(ChunkDecoder::from_key(key)), expected_total_bytes)
+}
+
+/// [`reconstruct_sparse`] with the chunk key itself.
+pub fn reconstruct_sparse_with_key(
+    shards: &[Shard],
+    holes: &[Hole],
+    key: Zeroizing<[u8; 32]>,
+    expected_total_bytes: usize,
+) -> Result<Vec<u8>> {
+    reconstruct_with(shards, holes, || Ok(ChunkDecoder::from_key(key)), expected_total_bytes)
+}
+
+/// The key is only derived once there is something to decode.
+fn reconstruct_with(
+    shards: &[Shard],
+    holes: &[Hole],
+    decoder: impl FnOnce() -> Result<ChunkDecoder>,
+    expected_total_bytes: usize,
+) -> Result<Vec<u8>> {
+    if shards.is_empty() && holes.is_empty() {
+        if expected_total_bytes != 0 {
+            return Err(anyhow!(
+                "no shards to reconstruct {expected_total_bytes} bytes from"
+            ));
+        }
         return Ok(Vec::new());
     }
 
-    let salt = SaltString::from_b64(salt).map_err(|e| anyhow!("invalid salt: {e}"))?;
-    let key = derive_key(password, &salt)?;
-
     let mut grouped: BTreeMap<usize, Vec<Shard>> = BTreeMap::new();
     for shard in shards {
         grouped
@@ -159,78 +506,224 @@ pub fn reconstruct_bytes(shards: &[Shard], password: &str, salt: &str) -> Result
             .push(shard.clone());
     }
 
-    let mut out = Vec::new();
-    for (_, chunk_shards) in grouped {
-        let Some(first) = chunk_shards.first() else {
-            continue;
-        };
-        let data_shards = first.data_shards;
-        let parity_shards = first.parity_shards;
-        let total_shards = data_shards + parity_shards;
+    // Every chunk is bound to its index and the chunk count, so a gap here
+    // would only surface as a confusing decryption failure further down.
+    // A hole overlapping a sharded chunk shows up as a gap at the end.
+    let chunk_count = grouped.len() + holes.len();
+    let mut indices: Vec<usize> = grouped
+        .keys()
+        .copied()
+        .chain(holes.iter().map(|h| h.chunk_index))
+        .collect();
+    indices.sort_unstable();
+    if indices.iter().enumerate().any(|(i, idx)| i != *idx) {
+        return Err(anyhow!("chunk indices are not contiguous from 0"));
+    }
 
-        if chunk_shards.len() < data_shards {
-            return Err(anyhow!("not enough shards to reconstruct chunk"));
+    let mut chunks = vec![Vec::new(); chunk_count];
+    if !grouped.is_empty() {
+        let decoder = decoder()?;
+        let sharded: Vec<usize> = grouped.keys().copied().collect();
+        let decoded = erasure::map_chunks(
+            ErasureBackend::Auto,
+            grouped.into_values().collect(),
+            |chunk_shards| decoder.decode(&chunk_shards, chunk_count),
+        )?;
+        for (idx, chunk) in sharded.into_iter().zip(decoded) {
+            chunks[idx] = chunk;
         }
+    }
+    for hole in holes {
+        chunks[hole.chunk_index] = hole.bytes();
+    }
+    assemble_chunks(chunks, expected_total_bytes)
+}
 
-        let shard_len = first.bytes.len();
-        let mut shards_opt: Vec<Option<Vec<u8>>> = vec![None; total_shards];
-        for shard in &chunk_shards {
-            if shard.shard_index >= total_shards {
-                continue;
-            }
-            let digest = sha256_hex(&shard.bytes);
-            if digest != shard.cid {
-                return Err(anyhow!("cid mismatch for shard {}", shard.cid));
-            }
-            shards_opt[shard.shard_index] = Some(shard.bytes.clone());
-        }
+fn assemble_chunks(mut chunks: Vec<Vec<u8>>, expected_total_bytes: usize) -> Result<Vec<u8>> {
+    let last = chunks.pop().unwrap_or_default();
+    let head_len: usize = chunks.iter().map(Vec::len).sum();
+    let actual = head_len + last.len();
+    if head_len > expected_total_bytes || actual < expected_total_bytes {
+        return Err(anyhow!(
+            "reconstructed size mismatch expected={expected_total_bytes} actual={actual}"
+        ));
+    }
 
-        let rs = ReedSolomon::new(data_shards, parity_shards)?;
-        rs.reconstruct(&mut shards_opt)?;
+    let mut out = Vec::with_capacity(expected_total_bytes);
+    for chunk in chunks {
+        out.extend_from_slice(&chunk);
+    }
+    out.extend_from_slice(&last[..expected_total_bytes - head_len]);
+    Ok(out)
+}
 
-        let mut payload = Vec::with_capacity(data_shards * shard_len);
-        for maybe in shards_opt.iter().take(data_shards) {
-            let Some(bytes) = maybe else {
-                return Err(anyhow!("failed to reconstruct data shards"));
-            };
-            payload.extend_from_slice(bytes);
+/// Holds the derived key for one manifest so chunks can be decoded one at
+/// a time (lazy reads, range fetches) without re-running Argon2 per chunk.
+pub struct ChunkDecoder {
+    key: Zeroizing<[u8; 32]>,
+}
+
+impl ChunkDecoder {
+    pub fn new(password: &str, salt: &str) -> Result<Self> {
+        Ok(Self {
+            key: derive_chunk_key(password, salt)?,
+        })
+    }
+
+    pub fn from_key(key: Zeroizing<[u8; 32]>) -> Self {
+        Self { key }
+    }
+
+    /// Decodes a single chunk from any `data_shards` of its shards.
+    /// `chunk_count` is the manifest's total, which the chunk was sealed
+    /// against together with its index.
+    pub fn decode(&self, chunk_shards: &[Shard], chunk_count: usize) -> Result<Vec<u8>> {
+        decode_chunk(chunk_shards, &self.key, chunk_count)
+    }
+}
+
+fn decode_chunk(chunk_shards: &[Shard], key: &[u8; 32], chunk_count: usize) -> Result<Vec<u8>> {
+    let Some(first) = chunk_shards.first() else {
+        return Ok(Vec::new());
+    };
+    let data_shards = first.data_shards;
+    let parity_shards = first.parity_shards;
+    let total_shards = data_shards + parity_shards;
+
+    if chunk_shards.len() < data_shards {
+        return Err(anyhow!("not enough shards to reconstruct chunk"));
+    }
+
+    let mut shards_opt: Vec<Option<Vec<u8>>> = vec![None; total_shards];
+    for shard in chunk_shards {
+        if shard.shard_index >= total_shards {
+            continue;
         }
-        payload.truncate(first.payload_len);
-        if payload.len() < 12 {
-            return Err(anyhow!("invalid payload length after reconstruction"));
+        if !shard_cid_matches(&shard.cid, &shard.bytes) {
+            return Err(anyhow!("cid mismatch for shard {}", shard.cid));
         }
+        shards_opt[shard.shard_index] = Some(shard.bytes.clone());
+    }
 
-        let mut nonce_bytes = [0u8; 12];
-        nonce_bytes.copy_from_slice(&payload[..12]);
-        let ciphertext = &payload[12..];
-
-        let cipher = Aes256Gcm::new_from_slice(&key)?;
-        let nonce = Nonce::from_slice(&nonce_bytes);
-        let plain = cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|_| anyhow!("decryption failed"))?;
-        out.extend_from_slice(&plain);
+    let payload = erasure_decode(shards_opt, data_shards, parity_shards, first.payload_len)?;
+    if payload.len() < 12 {
+        return Err(anyhow!("invalid payload length after reconstruction"));
     }
 
-    Ok(out)
+    let mut nonce_bytes = [0u8; 12];
+    nonce_bytes.copy_from_slice(&payload[..12]);
+    let ciphertext = &payload[12..];
+
+    let cipher = Aes256Gcm::new_from_slice(key)?;
+    let nonce = Nonce::from_slice(&nonce_bytes);
+    let aad = chunk_aad(first.chunk_index, chunk_count);
+    cipher
+        .decrypt(
+            nonce,
+            Payload {
+                msg: ciphertext,
+                aad: &aad,
+            },
+        )
+        // Chunks sealed before position binding carry no associated data.
+        // Anything sealed since only opens under its own index and count,
+        // so this fallback cannot be used to move it.
+        .or_else(|_| cipher.decrypt(nonce, ciphertext))
+        .map_err(|_| anyhow!("decryption failed for chunk {}", first.chunk_index))
+}
+
+/// GCM associated data tying a chunk to its position: a domain tag, then the
+/// chunk index and total chunk count as little-endian u64s.
+pub fn chunk_aad(chunk_index: usize, chunk_count: usize) -> [u8; 32] {
+    let mut aad = [0u8; 32];
+    aad[..16].copy_from_slice(CHUNK_AAD_TAG);
+    aad[16..24].copy_from_slice(&(chunk_index as u64).to_le_bytes());
+    aad[24..].copy_from_slice(&(chunk_count as u64).to_le_bytes());
+    aad
+}
+
+/// The key a manifest's chunks are sealed under, from its password and
+/// (base64) salt. Only needed directly to hand out a [`ShareToken`].
+pub fn derive_chunk_key(password: &str, salt: &str) -> Result<Zeroizing<[u8; 32]>> {
+    let salt = SaltString::from_b64(salt).map_err(|e| anyhow!("invalid salt: {e}"))?;
+    derive_key(password, &salt)
 }
 
-fn derive_key(password: &str, salt: &SaltString) -> Result<[u8; 32]> {
+/// The derived key is wiped when dropped; keep it behind `Zeroizing` and
+/// pass it by reference rather than copying the array out.
+fn derive_key(password: &str, salt: &SaltString) -> Result<Zeroizing<[u8; 32]>> {
     let argon2 = Argon2::default();
-    let mut key = [0u8; 32];
+    let mut key = Zeroizing::new([0u8; 32]);
     argon2
-        .hash_password_into(password.as_bytes(), salt.as_str().as_bytes(), &mut key)
+        .hash_password_into(password.as_bytes(), salt.as_str().as_bytes(), key.as_mut())
         .map_err(|e| anyhow!("argon2 key derivation failed: {e}"))?;
     Ok(key)
 }
 
-fn encrypt_chunk(data: &[u8], key: &[u8; 32]) -> Result<EncryptedChunk> {
-    let cipher = Aes256Gcm::new_from_slice(key)?;
+/// Argon2 needs at least 8 salt bytes; anything shorter than a generated
+/// salt is refused so a reproducible upload is not keyed more weakly.
+fn parse_deterministic_salt(salt: &str) -> Result<SaltString> {
+    if salt.len() < 22 {
+        return Err(anyhow!(
+            "deterministic salt must be at least 22 base64 characters"
+        ));
+    }
+    SaltString::from_b64(salt).map_err(|e| anyhow!("invalid deterministic salt: {e}"))
+}
+
+/// A fresh random salt in the form `deterministic_salt` expects.
+pub fn generate_salt() -> String {
+    SaltString::generate(&mut OsRng).to_string()
+}
+
+/// Keyed so nonces reveal nothing without the password, and bound to the
+/// chunk's position and content so a nonce only repeats for an identical
+/// plaintext at the same index, where the ciphertext is identical anyway.
+fn deterministic_nonce(
+    data: &[u8],
+    key: &[u8; 32],
+    chunk_index: usize,
+    chunk_count: usize,
+) -> [u8; 12] {
+    let mut hasher = Sha256::new();
+    hasher.update(NONCE_TAG);
+    hasher.update(key);
+    hasher.update(chunk_aad(chunk_index, chunk_count));
+    hasher.update(Sha256::digest(data));
+    let mut nonce = [0u8; 12];
+    nonce.copy_from_slice(&hasher.finalize()[..12]);
+    nonce
+}
+
+pub fn encrypt_chunk(
+    data: &[u8],
+    key: &[u8; 32],
+    chunk_index: usize,
+    chunk_count: usize,
+) -> Result<EncryptedChunk> {
     let mut nonce_bytes = [0u8; 12];
     OsRng.fill_bytes(&mut nonce_bytes);
+    encrypt_chunk_with_nonce(data, key, nonce_bytes, chunk_index, chunk_count)
+}
+
+fn encrypt_chunk_with_nonce(
+    data: &[u8],
+    key: &[u8; 32],
+    nonce_bytes: [u8; 12],
+    chunk_index: usize,
+    chunk_count: usize,
+) -> Result<EncryptedChunk> {
+    let cipher = Aes256Gcm::new_from_slice(key)?;
     let nonce = Nonce::from_slice(&nonce_bytes);
+    let aad = chunk_aad(chunk_index, chunk_count);
     let ciphertext = cipher
-        .encrypt(nonce, data)
+        .encrypt(
+            nonce,
+            Payload {
+                msg: data,
+                aad: &aad,
+            },
+        )
         .map_err(|_| anyhow!("encryption failed"))?;
     Ok(EncryptedChunk {
         nonce: nonce_bytes,
@@ -238,70 +731,108 @@ fn encrypt_chunk(data: &[u8], key: &[u8; 32]) -> Result<EncryptedChunk> {
     })
 }
 
-fn erasure_encode(
-    enc: &EncryptedChunk,
-    data_shards: usize,
-    parity_shards: usize,
-) -> Result<Vec<Vec<u8>>> {
-    let rs = ReedSolomon::new(data_shards, parity_shards)?;
-
-    let mut payload = Vec::with_capacity(12 + enc.ciphertext.len());
-    payload.extend_from_slice(&enc.nonce);
-    payload.extend_from_slice(&enc.ciphertext);
-
-    let shard_len = payload.len().div_ceil(data_shards);
-    let total_shards = data_shards + parity_shards;
-
-    let mut shards: Vec<Vec<u8>> = (0..total_shards).map(|_| vec![0u8; shard_len]).collect();
-
-    for (i, chunk) in payload.chunks(shard_len).enumerate() {
-        shards[i][..chunk.len()].copy_from_slice(chunk);
-    }
+/// Whether `bytes` are the content `cid` names, in either CID format and
+/// under whichever hash the CID declares.
+pub fn shard_cid_matches(cid: &str, bytes: &[u8]) -> bool {
+    neuro_protocol::cid::verify(cid, bytes)
+}
 
-    rs.encode(&mut shards)?;
-    Ok(shards)
+/// Each shard's merkle proof against [`manifest_root_from_shards`] over
+/// `shards`, in the same order; see [`verify_merkle_proof`].
+pub fn merkle_proofs_from_shards(shards: &[Shard]) -> Vec<Vec<String>> {
+    let algorithm = shards
+        .first()
+        .and_then(|s| HashAlgorithm::of(&s.cid))
+        .unwrap_or_default();
+    let items: Vec<&str> = shards.iter().map(|s| s.cid.as_str()).collect();
+    merkle_proofs(algorithm, &items)
 }
 
-fn validate_cfg(cfg: &PipelineConfig) -> Result<()> {
-    if cfg.chunk_size == 0 {
-        return Err(anyhow!("chunk_size must be > 0"));
+/// Whether `cid`, leaf `index` of a tree over `leaf_count` CIDs, hashes up
+/// through `proof` to `root`. The proof holds the hex sibling at each level
+/// from the leaves up, the sibling leaf being a CID's bytes; the last node
+/// of an odd level is its own sibling, as in the root itself.
+pub fn verify_merkle_proof(
+    algorithm: HashAlgorithm,
+    cid: &str,
+    index: usize,
+    leaf_count: usize,
+    proof: &[String],
+    root: &str,
+) -> bool {
+    if index >= leaf_count || proof.len() != merkle_depth(leaf_count) {
+        return false;
     }
-    if cfg.data_shards < 2 {
-        return Err(anyhow!("data_shards must be >= 2"));
+    let mut node = cid.as_bytes().to_vec();
+    let (mut index, mut len) = (index, leaf_count);
+    for sibling in proof {
+        let Ok(sibling) = hex::decode(sibling) else {
+            return false;
+        };
+        if index % 2 == 0 && index + 1 == len && sibling != node {
+            return false;
+        }
+        node = if index % 2 == 0 {
+            algorithm.digest(&[node.as_slice(), &sibling].concat()).to_vec()
+        } else {
+            algorithm.digest(&[sibling.as_slice(), &node].concat()).to_vec()
+        };
+        index /= 2;
+        len = len.div_ceil(2);
     }
-    if cfg.parity_shards < 1 {
-        return Err(anyhow!("parity_shards must be >= 1"));
+    hex::encode(algorithm.digest(&node)) == root
+}
+
+fn merkle_depth(leaf_count: usize) -> usize {
+    let (mut len, mut depth) = (leaf_count, 0);
+    while len > 1 {
+        len = len.div_ceil(2);
+        depth += 1;
     }
-    Ok(())
+    depth
 }
 
-fn sha256_hex(data: &[u8]) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(data);
-    let digest = hasher.finalize();
-    hex::encode(digest)
+fn merkle_proofs(algorithm: HashAlgorithm, items: &[&str]) -> Vec<Vec<String>> {
+    let mut levels: Vec<Vec<Vec<u8>>> = vec![items.iter().map(|s| s.as_bytes().to_vec()).collect()];
+    while levels.last().is_some_and(|level| level.len() > 1) {
+        let next = levels
+            .last()
+            .expect("a level was just checked")
+            .chunks(2)
+            .map(|pair| {
+                let right = pair.get(1).unwrap_or(&pair[0]);
+                algorithm.digest(&[pair[0].as_slice(), right].concat()).to_vec()
+            })
+            .collect();
+        levels.push(next);
+    }
+    (0..items.len())
+        .map(|leaf| {
+            let mut index = leaf;
+            let mut proof = Vec::new();
+            for level in &levels[..levels.len() - 1] {
+                proof.push(hex::encode(level.get(index ^ 1).unwrap_or(&level[index])));
+                index /= 2;
+            }
+            proof
+        })
+        .collect()
 }
 
-fn merkle_root(items: &[&str]) -> String {
+fn merkle_root(algorithm: HashAlgorithm, items: &[&str]) -> String {
     if items.is_empty() {
-        return sha256_hex(&[]);
+        return hex::encode(algorithm.digest(&[]));
     }
     let mut level: Vec<Vec<u8>> = items.iter().map(|s| s.as_bytes().to_vec()).collect();
     while level.len() > 1 {
         let mut next = Vec::new();
         for pair in level.chunks(2) {
-            let mut hasher = Sha256::new();
-            hasher.update(&pair[0]);
-            if pair.len() == 2 {
-                hasher.update(&pair[1]);
-            } else {
-                hasher.update(&pair[0]);
-            }
-            next.push(hasher.finalize().to_vec());
+            let right = pair.get(1).unwrap_or(&pair[0]);
+            next.push(algorithm.digest(&[pair[0].as_slice(), right].concat()).to_vec());
         }
         level = next;
     }
-    sha256_hex(&level[0])
+    hex::encode(algorithm.digest(&level[0]))
 }
 
 #[cfg(test)]
@@ -328,6 +859,38 @@ mod tests {
         }
     }
 
+    #[test]
+    fn merkle_proofs_tie_each_shard_to_the_root() {
+        for count in [1usize, 2, 3, 5, 8] {
+            let items: Vec<String> = (0..count).map(|i| hex::encode(Sha256::digest([i as u8]))).collect();
+            let refs: Vec<&str> = items.iter().map(String::as_str).collect();
+            let root = merkle_root(HashAlgorithm::Sha256, &refs);
+            let proofs = merkle_proofs(HashAlgorithm::Sha256, &refs);
+            for (i, proof) in proofs.iter().enumerate() {
+                assert!(verify_merkle_proof(HashAlgorithm::Sha256, &items[i], i, count, proof, &root));
+                assert!(!verify_merkle_proof(HashAlgorithm::Sha256, &"f".repeat(64), i, count, proof, &root));
+                assert!(!verify_merkle_proof(HashAlgorithm::Sha256, &items[i], i, count * 2 + 1, proof, &root));
+                assert!(!verify_merkle_proof(HashAlgorithm::Sha256, &items[i], i, count, proof, &items[i]));
+                if count > 1 {
+                    assert!(!verify_merkle_proof(HashAlgorithm::Sha256, &items[i], (i + 1) % count, count, proof, &root));
+                }
+            }
+        }
+
+        let output = process_bytes(&[5u8; 3000], "pw", PipelineConfig::default()).unwrap();
+        let proofs = merkle_proofs_from_shards(&output.shards);
+        for (i, shard) in output.shards.iter().enumerate() {
+            assert!(verify_merkle_proof(
+                HashAlgorithm::Sha256,
+                &shard.cid,
+                i,
+                output.shards.len(),
+                &proofs[i],
+                &output.manifest_root
+            ));
+        }
+    }
+
     #[test]
     fn round_trip_recovery_with_missing_shards() {
         let data = vec![9u8; 900 * 1024];
@@ -335,6 +898,13 @@ mod tests {
             chunk_size: 256 * 1024,
             data_shards: 4,
             parity_shards: 2,
+            erasure_backend: ErasureBackend::Auto,
+            cid_format: CidFormat::Sha256Hex,
+            hash_algorithm: HashAlgorithm::Sha256,
+            auto_adjust: false,
+            deterministic_salt: None,
+            outer_code: None,
+            holes: false,
         };
         let output = process_bytes(&data, "vault-pass", cfg).expect("pipeline failed");
 
@@ -346,8 +916,371 @@ mod tests {
             .cloned()
             .collect();
 
-        let recovered = reconstruct_bytes(&filtered, "vault-pass", &output.salt)
+        let recovered = reconstruct_bytes(&filtered, "vault-pass", &output.salt, data.len())
             .expect("reconstruction failed");
         assert_eq!(recovered, data);
     }
+
+    #[test]
+    fn share_tokens_open_only_their_manifest_until_expiry() {
+        let data = vec![5u8; 1024 * 3];
+        let output = process_bytes(&data, "vault-pass", PipelineConfig::default())
+            .expect("pipeline failed");
+        let key = derive_chunk_key("vault-pass", &output.salt).unwrap();
+        let claims = ShareClaims {
+            manifest_root: output.manifest_root.clone(),
+            scope: SCOPE_RETRIEVE.to_string(),
+            expires_at: 2_000,
+        };
+        let token = ShareToken::create(&key, claims).unwrap().encode();
+
+        let parsed = ShareToken::parse(&token).unwrap();
+        let opened = parsed.open(&output.manifest_root, 1_000).unwrap();
+        let recovered = reconstruct_bytes_with_key(&output.shards, opened, data.len()).unwrap();
+        assert_eq!(recovered, data);
+
+        assert!(parsed.open(&output.manifest_root, 2_000).is_err());
+        assert!(parsed.open("another-root", 1_000).is_err());
+        let sealed = ShareToken::parse(&parsed.sealed()).unwrap();
+        assert!(sealed.open(&output.manifest_root, 1_000).is_err());
+        let rekeyed = sealed.with_share_key(&parsed.share_key().unwrap()).unwrap();
+        assert!(rekeyed.open(&output.manifest_root, 1_000).is_ok());
+
+        // Pushing the expiry out breaks the seal.
+        let mut parts: Vec<String> = token.split('.').map(str::to_string).collect();
+        parts[1] = ShareClaims {
+            expires_at: 9_000,
+            ..parsed.claims.clone()
+        }
+        .encode();
+        let tampered = ShareToken::parse(&parts.join(".")).unwrap();
+        assert!(tampered.open(&output.manifest_root, 3_000).is_err());
+    }
+
+    #[test]
+    fn regenerated_shards_match_their_cids() {
+        let data = vec![7u8; 1024 * 3 + 17];
+        let output = process_bytes(&data, "correct-horse-battery-staple", PipelineConfig::default())
+            .expect("pipeline failed");
+        let chunk: Vec<&Shard> = output.shards.iter().filter(|s| s.chunk_index == 0).collect();
+        let (data_shards, parity_shards) = (chunk[0].data_shards, chunk[0].parity_shards);
+
+        // Lose the first data shard and the last parity shard.
+        let survivors: Vec<Option<Vec<u8>>> = chunk
+            .iter()
+            .map(|s| (s.shard_index != 0 && s.shard_index != data_shards + parity_shards - 1)
+                .then(|| s.bytes.clone()))
+            .collect();
+        let regenerated = erasure_regenerate(survivors, data_shards, parity_shards)
+            .expect("regenerate failed");
+        for shard in &chunk {
+            assert!(shard_cid_matches(&shard.cid, &regenerated[shard.shard_index]));
+        }
+    }
+
+    #[test]
+    fn reordered_or_dropped_chunks_fail_decryption() {
+        let cfg = PipelineConfig {
+            chunk_size: 1024,
+            ..PipelineConfig::default()
+        };
+        let data: Vec<u8> = (0..4096u32).map(|i| (i % 251) as u8).collect();
+        let output = process_bytes(&data, "pw", cfg).unwrap();
+        assert_eq!(output.chunk_count, 4);
+
+        let mut swapped = output.shards.clone();
+        for shard in &mut swapped {
+            shard.chunk_index = match shard.chunk_index {
+                1 => 2,
+                2 => 1,
+                other => other,
+            };
+        }
+        let err = reconstruct_bytes(&swapped, "pw", &output.salt, data.len()).unwrap_err();
+        assert!(err.to_string().contains("decryption failed"));
+
+        let truncated: Vec<Shard> = output
+            .shards
+            .iter()
+            .filter(|s| s.chunk_index < 3)
+            .cloned()
+            .collect();
+        assert!(reconstruct_bytes(&truncated, "pw", &output.salt, data.len()).is_err());
+    }
+
+    #[test]
+    fn reconstruction_enforces_the_expected_total() {
+        let data = vec![9u8; 2500];
+        let cfg = PipelineConfig {
+            chunk_size: 1024,
+            ..PipelineConfig::default()
+        };
+        let output = process_bytes(&data, "pw", cfg).unwrap();
+        let recovered = reconstruct_bytes(&output.shards, "pw", &output.salt, 2500).unwrap();
+        assert_eq!(recovered, data);
+
+        let err = reconstruct_bytes(&output.shards, "pw", &output.salt, 2501).unwrap_err();
+        assert!(err.to_string().contains("size mismatch"));
+        // Padding is only tolerated in the final chunk.
+        assert!(reconstruct_bytes(&output.shards, "pw", &output.salt, 2000).is_err());
+        assert_eq!(
+            assemble_chunks(vec![vec![1; 4], vec![2; 4]], 6).unwrap(),
+            [1, 1, 1, 1, 2, 2]
+        );
+    }
+
+    #[test]
+    fn cidv1_shards_round_trip_and_match_their_hex_digest() {
+        let data = vec![3u8; 5000];
+        let cfg = PipelineConfig {
+            chunk_size: 2048,
+            cid_format: CidFormat::V1,
+            ..PipelineConfig::default()
+        };
+        let output = process_bytes(&data, "pw", cfg).unwrap();
+        for shard in &output.shards {
+            assert!(shard.cid.starts_with("bafkrei"), "{}", shard.cid);
+            assert_eq!(shard.cid.len(), 59);
+            assert_eq!(CidFormat::of(&shard.cid), Some(CidFormat::V1));
+            assert!(shard_cid_matches(&shard.cid, &shard.bytes));
+            assert!(shard_cid_matches(&hex::encode(Sha256::digest(&shard.bytes)), &shard.bytes));
+        }
+        assert_eq!(
+            reconstruct_bytes(&output.shards, "pw", &output.salt, data.len()).unwrap(),
+            data
+        );
+
+        // Known vector: CIDv1 raw of the empty string.
+        let empty: [u8; 32] = Sha256::digest(b"").into();
+        assert_eq!(
+            neuro_protocol::cid::encode(CidFormat::V1, &empty),
+            "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"
+        );
+        assert!(neuro_protocol::cid::parse("bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyk").is_none());
+    }
+
+    #[test]
+    fn blake3_cids_round_trip_and_need_cidv1() {
+        let data = vec![9u8; 300_000];
+        let cfg = PipelineConfig {
+            chunk_size: 64 * 1024,
+            cid_format: CidFormat::V1,
+            hash_algorithm: HashAlgorithm::Blake3,
+            ..PipelineConfig::default()
+        };
+        let output = process_bytes(&data, "pw", cfg.clone()).unwrap();
+        for shard in &output.shards {
+            assert_eq!(HashAlgorithm::of(&shard.cid), Some(HashAlgorithm::Blake3));
+            assert!(shard_cid_matches(&shard.cid, &shard.bytes));
+            assert!(!shard_cid_matches(&shard.cid, &shard.bytes[1..]));
+        }
+        assert_eq!(manifest_root_from_shards(&output.shards), output.manifest_root);
+        assert_ne!(
+            merkle_root(HashAlgorithm::Sha256, &output.shards.iter().map(|s| s.cid.as_str()).collect::<Vec<_>>()),
+            output.manifest_root
+        );
+        assert_eq!(
+            reconstruct_bytes(&output.shards, "pw", &output.salt, data.len()).unwrap(),
+            data
+        );
+
+        // Known vector: CIDv1 raw of the empty string under blake3.
+        assert_eq!(
+            neuro_protocol::cid::for_data(CidFormat::V1, HashAlgorithm::Blake3, b"").unwrap(),
+            "bafkr4ifpcne3t5pzugtkaqcn5i3nzskjtpfslsnnyejlpte2spfoihzsmi"
+        );
+
+        let hex = PipelineConfig {
+            cid_format: CidFormat::Sha256Hex,
+            ..cfg
+        };
+        assert!(process_bytes(&data, "pw", hex.clone()).is_err());
+        let adjusted = process_bytes(&data, "pw", PipelineConfig { auto_adjust: true, ..hex }).unwrap();
+        assert_eq!(adjusted.config.cid_format, CidFormat::V1);
+        assert!(!adjusted.warnings.is_empty());
+    }
+
+    #[test]
+    fn shard_totals_over_the_reed_solomon_limit_are_rejected_or_capped() {
+        let cfg = PipelineConfig {
+            data_shards: 200,
+            parity_shards: 100,
+            ..PipelineConfig::default()
+        };
+        let err = process_bytes(&[1u8; 64], "pw", cfg.clone()).unwrap_err();
+        assert!(err.to_string().contains("Reed-Solomon limit"));
+
+        let output = process_bytes(
+            &[1u8; 64],
+            "pw",
+            PipelineConfig {
+                auto_adjust: true,
+                ..cfg
+            },
+        )
+        .expect("auto-adjusted pipeline failed");
+        assert_eq!(output.config.data_shards, 200);
+        assert_eq!(output.config.parity_shards, MAX_TOTAL_SHARDS - 200);
+        assert_eq!(output.warnings.len(), 1);
+        assert!(output.shards.iter().all(|s| s.shard_index < MAX_TOTAL_SHARDS));
+    }
+
+    #[test]
+    fn deterministic_uploads_reproduce_the_manifest_root() {
+        let data: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+        let cfg = PipelineConfig {
+            chunk_size: 1024,
+            deterministic_salt: Some(generate_salt()),
+            ..PipelineConfig::default()
+        };
+        let a = process_bytes(&data, "pw", cfg.clone()).unwrap();
+        let b = process_bytes(&data, "pw", cfg.clone()).unwrap();
+        assert_eq!(a.salt, cfg.deterministic_salt.clone().unwrap());
+        assert_eq!(a.manifest_root, b.manifest_root);
+        assert_eq!(
+            reconstruct_bytes(&a.shards, "pw", &a.salt, data.len()).unwrap(),
+            data
+        );
+
+        let other_password = process_bytes(&data, "pw2", cfg.clone()).unwrap();
+        assert_ne!(other_password.manifest_root, a.manifest_root);
+        let random = process_bytes(
+            &data,
+            "pw",
+            PipelineConfig {
+                deterministic_salt: None,
+                ..cfg
+            },
+        )
+        .unwrap();
+        assert_ne!(random.manifest_root, a.manifest_root);
+        assert!(parse_deterministic_salt("c2hvcnQ").is_err());
+    }
+
+    #[test]
+    fn adaptive_config_fits_peer_count() {
+        for peers in 1..=20 {
+            let mut cfg = adaptive_config(1 << 20, peers, RedundancyProfile::Resilient);
+            cfg.validate().unwrap();
+            let total = cfg.data_shards + cfg.parity_shards;
+            assert!(total <= peers.max(3), "peers={peers} total={total}");
+            assert_eq!(cfg.adjust_for_peers(peers).is_empty(), peers >= 3);
+        }
+    }
+
+    #[test]
+    fn serial_and_parallel_backends_produce_identical_shards() {
+        let enc = encrypt_chunk(&[5u8; 100_000], &[3u8; 32], 0, 1).unwrap();
+        let expected = erasure_encode(&enc, 4, 2).unwrap();
+
+        for backend in [ErasureBackend::Serial, ErasureBackend::Parallel] {
+            let shards = erasure::map_chunks(backend, vec![&enc; 6], |e| erasure_encode(e, 4, 2))
+                .unwrap();
+            assert!(shards.iter().all(|s| *s == expected));
+        }
+    }
+
+    #[test]
+    fn outer_code_rebuilds_chunks_whose_shards_are_all_lost() {
+        let cfg = PipelineConfig {
+            chunk_size: 1000,
+            data_shards: 2,
+            parity_shards: 1,
+            outer_code: Some(OuterCode { group_chunks: 3, parity_chunks: 1 }),
+            ..PipelineConfig::default()
+        };
+        let outer = cfg.outer_code.unwrap();
+        // Seven chunks, the last one short: groups of 3, 3 and 1.
+        let data: Vec<u8> = (0..6500u32).map(|i| (i % 239) as u8).collect();
+        let output = process_bytes(&data, "pw", cfg).unwrap();
+        assert_eq!(output.chunk_count, 7);
+        assert_eq!(output.shards.len(), (7 + outer.parity_chunk_count(7)) * 3);
+
+        // One chunk wiped out in each group, including the short last one.
+        let survivors: Vec<Shard> = output
+            .shards
+            .iter()
+            .filter(|s| ![1, 5, 6].contains(&s.chunk_index))
+            .cloned()
+            .collect();
+        assert!(reconstruct_bytes(&survivors, "pw", &output.salt, data.len()).is_err());
+
+        let recovered = recover_chunks(&survivors, &outer, output.chunk_count).unwrap();
+        assert!(recovered.iter().all(|s| s.chunk_index < output.chunk_count));
+        for shard in recovered.iter().filter(|s| [1, 5, 6].contains(&s.chunk_index)) {
+            let original = output
+                .shards
+                .iter()
+                .find(|o| o.chunk_index == shard.chunk_index && o.shard_index == shard.shard_index)
+                .unwrap();
+            assert_eq!(shard.cid, original.cid);
+        }
+        let bytes = reconstruct_bytes(&recovered, "pw", &output.salt, data.len()).unwrap();
+        assert_eq!(bytes, data);
+
+        // Two chunks of one group is more than a single parity chunk covers.
+        let too_many: Vec<Shard> = output
+            .shards
+            .iter()
+            .filter(|s| ![0, 1].contains(&s.chunk_index))
+            .cloned()
+            .collect();
+        let partial = recover_chunks(&too_many, &outer, output.chunk_count).unwrap();
+        assert!(reconstruct_bytes(&partial, "pw", &output.salt, data.len()).is_err());
+
+        let bad = PipelineConfig {
+            outer_code: Some(OuterCode { group_chunks: 0, parity_chunks: 1 }),
+            ..PipelineConfig::default()
+        };
+        assert!(process_bytes(&data, "pw", bad).is_err());
+    }
+
+    #[test]
+    fn constant_chunks_become_holes_without_shards() {
+        let cfg = PipelineConfig {
+            chunk_size: 1000,
+            holes: true,
+            ..PipelineConfig::default()
+        };
+        // Data, a zero run, data, a 0xff run, and a short zero tail.
+        let mut data: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        data.extend([0u8; 1000]);
+        data.extend((0..1000u32).map(|i| (i % 13) as u8));
+        data.extend([0xffu8; 1000]);
+        data.extend([0u8; 300]);
+        let output = process_bytes(&data, "pw", cfg.clone()).unwrap();
+        assert_eq!(output.chunk_count, 5);
+        assert_eq!(
+            output.holes,
+            vec![
+                Hole { chunk_index: 1, len: 1000, byte: 0 },
+                Hole { chunk_index: 3, len: 1000, byte: 0xff },
+                Hole { chunk_index: 4, len: 300, byte: 0 },
+            ]
+        );
+        assert!(output.shards.iter().all(|s| [0, 2].contains(&s.chunk_index)));
+        assert_eq!(output.shards.len(), 2 * 6);
+
+        let bytes = reconstruct_sparse(&output.shards, &output.holes, "pw", &output.salt, data.len()).unwrap();
+        assert_eq!(bytes, data);
+        // Without its holes the upload is missing chunks.
+        assert!(reconstruct_bytes(&output.shards, "pw", &output.salt, data.len()).is_err());
+        // A hole over a sharded chunk leaves the last index uncovered.
+        let mut overlapping = output.holes.clone();
+        overlapping[2].chunk_index = 2;
+        assert!(reconstruct_sparse(&output.shards, &overlapping, "pw", &output.salt, data.len()).is_err());
+
+        // An all-zero image needs no shards, and no key, at all.
+        let zeros = vec![0u8; 2500];
+        let empty = process_bytes(&zeros, "pw", cfg.clone()).unwrap();
+        assert!(empty.shards.is_empty());
+        let bytes = reconstruct_sparse(&[], &empty.holes, "wrong", "not-a-salt", zeros.len()).unwrap();
+        assert_eq!(bytes, zeros);
+
+        let with_outer = PipelineConfig {
+            outer_code: Some(OuterCode { group_chunks: 3, parity_chunks: 1 }),
+            ..cfg
+        };
+        assert!(process_bytes(&data, "pw", with_outer).is_err());
+    }
 }
@@ -0,0 +1,944 @@
+//! Upload manifests: the signed record of where every shard of an upload
+//! lives and how to audit it. Built here so the CLI uploader, the WASM
+//! client and anything else that uploads produce manifests the others can
+//! verify byte for byte.
+
+use anyhow::{anyhow, Result};
+use argon2::Argon2;
+use hmac::{Hmac, Mac};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+use crate::{
+    derive_chunk_key, manifest_root_from_shards, merkle_proofs_from_shards, unwrap_with_password,
+    unwrap_with_x25519, verify_merkle_proof, CidFormat, HashAlgorithm, Hole, KeyWrap, OuterCode, PipelineOutput,
+    Shard,
+};
+
+pub const MANIFEST_VERSION: &str = "2.2.0";
+pub const MANIFEST_VERSION_CIDV1: &str = "2.3.0";
+pub const MANIFEST_VERSION_GATEWAYS: &str = "3.0.0";
+pub const MANIFEST_VERSION_GATEWAYS_CIDV1: &str = "3.1.0";
+/// 4.x manifests carry an HMAC-SHA256 auth tag; gateway hints are optional.
+pub const MANIFEST_VERSION_HMAC: &str = "4.0.0";
+pub const MANIFEST_VERSION_HMAC_CIDV1: &str = "4.1.0";
+/// 5.x manifests seal chunks under a random content key wrapped for each
+/// recipient, and tag the hash with that key instead of a password.
+pub const MANIFEST_VERSION_RECIPIENTS: &str = "5.0.0";
+pub const MANIFEST_VERSION_RECIPIENTS_CIDV1: &str = "5.1.0";
+
+/// Appended to the manifest salt for the auth key's Argon2 pass, so the
+/// auth key differs from the chunk key a share token hands out.
+const MANIFEST_AUTH_SALT_TAG: &[u8] = b"|neurostore-manifest-auth";
+/// Hashed with the content key for the 5.x auth key, for the same reason.
+const CONTENT_KEY_AUTH_TAG: &[u8] = b"neurostore-content-key-auth";
+
+/// Addresses kept per peer in `peer_addrs`; older ones drop off the end.
+pub const MAX_PEER_ADDRS: usize = 8;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestShard {
+    pub chunk_index: usize,
+    pub shard_index: usize,
+    pub cid: String,
+    pub payload_len: usize,
+    pub data_shards: usize,
+    pub parity_shards: usize,
+    pub peers: Vec<String>,
+    pub audit_challenges: Vec<String>,
+    pub audit_tokens: Vec<String>,
+    /// Hex siblings from this shard's CID up to `manifest_root`, so the
+    /// shard can be checked against the root on its own; see
+    /// [`crate::verify_merkle_proof`]. Written under
+    /// [`ManifestBuilder::merkle_proofs`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub merkle_proof: Vec<String>,
+}
+
+impl ManifestShard {
+    /// The shard's layout without its bytes, as `manifest_root_from_shards`
+    /// and the erasure decoder expect it.
+    pub fn to_template(&self) -> Shard {
+        Shard {
+            chunk_index: self.chunk_index,
+            shard_index: self.shard_index,
+            cid: self.cid.clone(),
+            bytes: Vec::new(),
+            payload_len: self.payload_len,
+            data_shards: self.data_shards,
+            parity_shards: self.parity_shards,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadManifest {
+    pub version: String,
+    pub salt: String,
+    pub manifest_root: String,
+    pub total_bytes: usize,
+    pub chunk_count: usize,
+    pub shards: Vec<ManifestShard>,
+    /// Gateways the manifest was registered with (3.x only).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gateways: Vec<String>,
+    /// Encrypted with derived nonces under a chosen salt, so the root can be
+    /// reproduced from the file and password.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deterministic: bool,
+    /// What the shard CIDs and the root were hashed with. Audit tokens stay
+    /// SHA-256 either way, since that is what nodes answer audits with.
+    #[serde(default, skip_serializing_if = "is_sha256")]
+    pub hash_algorithm: HashAlgorithm,
+    /// The content key wrapped for each recipient (5.x only).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipients: Vec<KeyWrap>,
+    /// Parity chunks across chunk groups; their shards sit at chunk indices
+    /// from `chunk_count` on. See [`crate::recover_chunks`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outer_code: Option<OuterCode>,
+    /// Last-known addresses of each peer, by PeerId, most recent first.
+    /// Shards that name a peer by identity alone (`/p2p/<id>`) are dialled
+    /// through these, so a peer that moves keeps its placements.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub peer_addrs: BTreeMap<String, Vec<String>>,
+    /// Constant chunks kept here instead of in `shards`; see
+    /// [`crate::PipelineConfig::holes`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub holes: Vec<Hole>,
+    pub manifest_hash: String,
+    pub manifest_auth_tag: String,
+}
+
+impl UploadManifest {
+    /// Whether the shard at `position` in `shards` is a leaf of
+    /// `manifest_root` by its own merkle proof, independently of the other
+    /// entries. `None` when it carries no proof.
+    pub fn shard_membership(&self, position: usize) -> Option<bool> {
+        let shard = self.shards.get(position)?;
+        if shard.merkle_proof.is_empty() && self.shards.len() > 1 {
+            return None;
+        }
+        Some(verify_merkle_proof(
+            self.hash_algorithm,
+            &shard.cid,
+            position,
+            self.shards.len(),
+            &shard.merkle_proof,
+            &self.manifest_root,
+        ))
+    }
+
+    /// Recomputes `manifest_hash` and, given the password, the auth tag over
+    /// it. Call after any change to the shards, peers or audit vectors.
+    /// Signing always uses the current HMAC tag, so an older manifest moves
+    /// to the 4.x version for its CID format. With recipients, the password
+    /// is any recipient's and the tag is made with the content key it opens.
+    pub fn reseal(&mut self, password: Option<&str>) -> Result<()> {
+        if let (Some(password), false) = (password, self.recipients.is_empty()) {
+            let key = self.chunk_key(password)?;
+            return self.reseal_with_key(&key);
+        }
+        if password.is_some() {
+            self.version = manifest_version(manifest_cid_format(&self.version)?).to_string();
+        }
+        self.manifest_hash = compute_manifest_hash(self)?;
+        self.manifest_auth_tag = match password {
+            Some(password) => derive_manifest_auth_tag(password, &self.salt, &self.manifest_hash)?,
+            None => String::new(),
+        };
+        Ok(())
+    }
+
+    /// [`reseal`](Self::reseal) for a manifest with recipients, signed with
+    /// the content key itself, e.g. one opened with an X25519 identity.
+    pub fn reseal_with_key(&mut self, content_key: &[u8; 32]) -> Result<()> {
+        if self.recipients.is_empty() {
+            return Err(anyhow!("only manifests with recipients are signed with a content key"));
+        }
+        self.version = recipients_manifest_version(manifest_cid_format(&self.version)?).to_string();
+        self.manifest_hash = compute_manifest_hash(self)?;
+        self.manifest_auth_tag =
+            hex::encode(content_key_mac(content_key, &self.manifest_hash).finalize().into_bytes());
+        Ok(())
+    }
+
+    /// The key the chunks are sealed under: derived from the password and
+    /// salt, or opened from the recipient entry the password fits.
+    pub fn chunk_key(&self, password: &str) -> Result<Zeroizing<[u8; 32]>> {
+        if self.recipients.is_empty() {
+            derive_chunk_key(password, &self.salt)
+        } else {
+            unwrap_with_password(&self.recipients, password)
+        }
+    }
+
+    /// The content key, opened with an X25519 recipient's secret.
+    pub fn chunk_key_for_identity(&self, secret: &[u8; 32]) -> Result<Zeroizing<[u8; 32]>> {
+        unwrap_with_x25519(&self.recipients, secret)
+    }
+
+    /// Checks the auth tag against the password in constant time, under the
+    /// scheme the manifest's version was written with.
+    pub fn verify_auth_tag(&self, password: &str) -> Result<()> {
+        if uses_recipients(&self.version) || !self.recipients.is_empty() {
+            let key = self
+                .chunk_key(password)
+                .map_err(|_| anyhow!("manifest auth mismatch; password fits no recipient"))?;
+            return self.verify_auth_tag_with_key(&key);
+        }
+        let ok = if uses_hmac_auth_tag(&self.version) {
+            let tag = hex::decode(&self.manifest_auth_tag).unwrap_or_default();
+            manifest_mac(password, &self.salt, &self.manifest_hash)?
+                .verify_slice(&tag)
+                .is_ok()
+        } else {
+            let expected = legacy_manifest_auth_tag(password, &self.salt, &self.manifest_hash);
+            bool::from(expected.as_bytes().ct_eq(self.manifest_auth_tag.as_bytes()))
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(anyhow!("manifest auth mismatch; incorrect password or tampered manifest"))
+        }
+    }
+
+    /// Checks a 5.x auth tag against the content key.
+    pub fn verify_auth_tag_with_key(&self, content_key: &[u8; 32]) -> Result<()> {
+        if !uses_recipients(&self.version) || self.recipients.is_empty() {
+            return Err(anyhow!("manifest version {} does not match its recipients", self.version));
+        }
+        let tag = hex::decode(&self.manifest_auth_tag).unwrap_or_default();
+        content_key_mac(content_key, &self.manifest_hash)
+            .verify_slice(&tag)
+            .map_err(|_| anyhow!("manifest auth mismatch; wrong key or tampered manifest"))
+    }
+
+    /// Addresses to dial for a shard's peer entry: the recorded addresses of
+    /// an identity-only entry, or the entry itself when it has an address.
+    pub fn dial_addrs(&self, peer: &str) -> Vec<String> {
+        if !is_identity_only(peer) {
+            return vec![peer.to_string()];
+        }
+        peer_id_of(peer)
+            .and_then(|id| self.peer_addrs.get(id))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Records `addr` as the most recent address of the peer it ends in.
+    /// Returns false for identity-only entries and entries without a PeerId.
+    pub fn record_peer_addr(&mut self, addr: &str) -> bool {
+        let Some(id) = peer_id_of(addr).filter(|_| !is_identity_only(addr)) else {
+            return false;
+        };
+        let known = self.peer_addrs.entry(id.to_string()).or_default();
+        known.retain(|a| a != addr);
+        known.insert(0, addr.to_string());
+        known.truncate(MAX_PEER_ADDRS);
+        true
+    }
+
+    /// Moves the addresses out of the shard entries into `peer_addrs`,
+    /// leaving each entry as the `/p2p/<id>` it names, and forgets peers no
+    /// shard names any more. Reseal afterwards.
+    pub fn separate_peer_addrs(&mut self) {
+        let mut shards = std::mem::take(&mut self.shards);
+        let mut named = HashSet::new();
+        for shard in &mut shards {
+            let mut peers = Vec::with_capacity(shard.peers.len());
+            for peer in &shard.peers {
+                let entry = match peer_id_of(peer) {
+                    Some(id) => {
+                        self.record_peer_addr(peer);
+                        named.insert(id.to_string());
+                        format!("/p2p/{id}")
+                    }
+                    None => peer.clone(),
+                };
+                if !peers.contains(&entry) {
+                    peers.push(entry);
+                }
+            }
+            shard.peers = peers;
+        }
+        self.peer_addrs.retain(|id, _| named.contains(id));
+        self.shards = shards;
+    }
+
+    /// The reverse of [`separate_peer_addrs`](Self::separate_peer_addrs),
+    /// for code that works on full multiaddrs: each identity-only entry
+    /// takes its most recent address, if one is known.
+    pub fn join_peer_addrs(&mut self) {
+        let book = &self.peer_addrs;
+        for shard in &mut self.shards {
+            for peer in &mut shard.peers {
+                if !is_identity_only(peer) {
+                    continue;
+                }
+                let latest = peer_id_of(peer).and_then(|id| book.get(id)).and_then(|addrs| addrs.first());
+                if let Some(addr) = latest.cloned() {
+                    *peer = addr;
+                }
+            }
+        }
+    }
+}
+
+/// The PeerId a `.../p2p/<id>` entry ends in.
+pub fn peer_id_of(addr: &str) -> Option<&str> {
+    let (_, id) = addr.rsplit_once("/p2p/")?;
+    (!id.is_empty() && !id.contains('/')).then_some(id)
+}
+
+fn is_identity_only(addr: &str) -> bool {
+    addr.starts_with("/p2p/")
+}
+
+#[derive(Serialize)]
+struct ManifestHashView<'a> {
+    version: &'a str,
+    salt: &'a str,
+    manifest_root: &'a str,
+    total_bytes: usize,
+    chunk_count: usize,
+    shards: &'a [ManifestShard],
+    // Left out when empty so 2.x hashes stay as they were.
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    gateways: &'a [String],
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    deterministic: bool,
+    #[serde(skip_serializing_if = "is_sha256")]
+    hash_algorithm: HashAlgorithm,
+    #[serde(skip_serializing_if = "<[KeyWrap]>::is_empty")]
+    recipients: &'a [KeyWrap],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outer_code: Option<OuterCode>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    peer_addrs: &'a BTreeMap<String, Vec<String>>,
+    #[serde(skip_serializing_if = "<[Hole]>::is_empty")]
+    holes: &'a [Hole],
+}
+
+fn is_sha256(algorithm: &HashAlgorithm) -> bool {
+    *algorithm == HashAlgorithm::Sha256
+}
+
+/// SHA-256 over the manifest's JSON with the hash and auth tag left out.
+pub fn compute_manifest_hash(manifest: &UploadManifest) -> Result<String> {
+    let view = ManifestHashView {
+        version: &manifest.version,
+        salt: &manifest.salt,
+        manifest_root: &manifest.manifest_root,
+        total_bytes: manifest.total_bytes,
+        chunk_count: manifest.chunk_count,
+        shards: &manifest.shards,
+        gateways: &manifest.gateways,
+        deterministic: manifest.deterministic,
+        hash_algorithm: manifest.hash_algorithm,
+        recipients: &manifest.recipients,
+        outer_code: manifest.outer_code,
+        peer_addrs: &manifest.peer_addrs,
+        holes: &manifest.holes,
+    };
+    let bytes = serde_json::to_vec(&view)?;
+    Ok(hex::encode(Sha256::digest(bytes)))
+}
+
+/// Binds the manifest hash to the upload password, so only its owner can
+/// produce a manifest that `retrieve` will trust: HMAC-SHA256 over the hash
+/// under a key Argon2 derives from the password and salt.
+pub fn derive_manifest_auth_tag(password: &str, salt: &str, manifest_hash: &str) -> Result<String> {
+    Ok(hex::encode(manifest_mac(password, salt, manifest_hash)?.finalize().into_bytes()))
+}
+
+fn manifest_mac(password: &str, salt: &str, manifest_hash: &str) -> Result<HmacSha256> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    let salt = [salt.as_bytes(), MANIFEST_AUTH_SALT_TAG].concat();
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), &salt, key.as_mut())
+        .map_err(|e| anyhow!("argon2 key derivation failed: {e}"))?;
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key.as_ref()).expect("HMAC takes any key length");
+    mac.update(manifest_hash.as_bytes());
+    Ok(mac)
+}
+
+/// The 5.x tag: HMAC-SHA256 over the hash under a digest of the content
+/// key, so the MAC and the chunk cipher never share a key.
+fn content_key_mac(content_key: &[u8; 32], manifest_hash: &str) -> HmacSha256 {
+    let key: Zeroizing<[u8; 32]> = Zeroizing::new(
+        Sha256::new()
+            .chain_update(CONTENT_KEY_AUTH_TAG)
+            .chain_update(content_key)
+            .finalize()
+            .into(),
+    );
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key.as_ref()).expect("HMAC takes any key length");
+    mac.update(manifest_hash.as_bytes());
+    mac
+}
+
+/// The 2.x/3.x tag: SHA-256 over a password-and-salt digest and the hash.
+/// Only verified now, never written.
+fn legacy_manifest_auth_tag(password: &str, salt: &str, manifest_hash: &str) -> String {
+    let mut key_hasher = Sha256::new();
+    key_hasher.update(password.as_bytes());
+    key_hasher.update(b"|");
+    key_hasher.update(salt.as_bytes());
+    let key: Zeroizing<[u8; 32]> = Zeroizing::new(key_hasher.finalize().into());
+
+    let mut mac_hasher = Sha256::new();
+    mac_hasher.update(key.as_ref());
+    mac_hasher.update(b"|");
+    mac_hasher.update(manifest_hash.as_bytes());
+    hex::encode(mac_hasher.finalize())
+}
+
+/// `rounds` (at least one) random challenges for `data` and the token a
+/// peer holding it must answer each with.
+pub fn build_audit_vectors(data: &[u8], rounds: usize) -> (Vec<String>, Vec<String>) {
+    let rounds = rounds.max(1);
+    let mut challenges = Vec::with_capacity(rounds);
+    let mut tokens = Vec::with_capacity(rounds);
+    for _ in 0..rounds {
+        let mut challenge = [0u8; 16];
+        OsRng.fill_bytes(&mut challenge);
+        let challenge_hex = hex::encode(challenge);
+        challenges.push(challenge_hex.clone());
+        tokens.push(audit_token(&challenge_hex, data));
+    }
+    (challenges, tokens)
+}
+
+pub fn audit_token(challenge_hex: &str, data: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    let challenge = hex::decode(challenge_hex).unwrap_or_default();
+    hasher.update(challenge);
+    hasher.update(data);
+    hex::encode(hasher.finalize())
+}
+
+/// Manifest version written for each shard CID format. 4.x exists so
+/// readers that predate HMAC tags reject these manifests instead of failing
+/// them as tampered; in the older versions, 2.3.0 likewise marked CIDv1 and
+/// 3.x marked manifests carrying gateway hints.
+pub fn manifest_version(cid_format: CidFormat) -> &'static str {
+    match cid_format {
+        CidFormat::Sha256Hex => MANIFEST_VERSION_HMAC,
+        CidFormat::V1 => MANIFEST_VERSION_HMAC_CIDV1,
+    }
+}
+
+/// Like [`manifest_version`], for manifests with recipients.
+pub fn recipients_manifest_version(cid_format: CidFormat) -> &'static str {
+    match cid_format {
+        CidFormat::Sha256Hex => MANIFEST_VERSION_RECIPIENTS,
+        CidFormat::V1 => MANIFEST_VERSION_RECIPIENTS_CIDV1,
+    }
+}
+
+pub fn manifest_cid_format(version: &str) -> Result<CidFormat> {
+    match version {
+        MANIFEST_VERSION | MANIFEST_VERSION_GATEWAYS | MANIFEST_VERSION_HMAC | MANIFEST_VERSION_RECIPIENTS => {
+            Ok(CidFormat::Sha256Hex)
+        }
+        MANIFEST_VERSION_CIDV1
+        | MANIFEST_VERSION_GATEWAYS_CIDV1
+        | MANIFEST_VERSION_HMAC_CIDV1
+        | MANIFEST_VERSION_RECIPIENTS_CIDV1 => Ok(CidFormat::V1),
+        other => Err(anyhow!("unsupported manifest version {other}")),
+    }
+}
+
+/// The CID format `version` implies, once the version is known and agrees
+/// with whether the manifest carries gateway hints.
+pub fn check_manifest_version(version: &str, has_gateways: bool) -> Result<CidFormat> {
+    let cid_format = manifest_cid_format(version)?;
+    let gateways_allowed = match version {
+        MANIFEST_VERSION | MANIFEST_VERSION_CIDV1 => Some(false),
+        MANIFEST_VERSION_GATEWAYS | MANIFEST_VERSION_GATEWAYS_CIDV1 => Some(true),
+        _ => None,
+    };
+    if gateways_allowed.is_some_and(|allowed| allowed != has_gateways) {
+        return Err(anyhow!("manifest version {version} does not match its gateway hints"));
+    }
+    Ok(cid_format)
+}
+
+fn uses_hmac_auth_tag(version: &str) -> bool {
+    matches!(version, MANIFEST_VERSION_HMAC | MANIFEST_VERSION_HMAC_CIDV1)
+}
+
+fn uses_recipients(version: &str) -> bool {
+    matches!(version, MANIFEST_VERSION_RECIPIENTS | MANIFEST_VERSION_RECIPIENTS_CIDV1)
+}
+
+/// Assembles an `UploadManifest` shard by shard. The version follows from
+/// the CID format and gateway hints, and the root is always recomputed from
+/// the shard layout, so manifests from different clients agree whenever
+/// their shards do. Shards name their peers by PeerId, with the addresses
+/// they were placed at kept apart in `peer_addrs`.
+#[derive(Debug, Clone)]
+pub struct ManifestBuilder {
+    salt: String,
+    total_bytes: usize,
+    chunk_count: usize,
+    cid_format: CidFormat,
+    hash_algorithm: HashAlgorithm,
+    gateways: Vec<String>,
+    deterministic: bool,
+    recipients: Vec<KeyWrap>,
+    outer_code: Option<OuterCode>,
+    holes: Vec<Hole>,
+    merkle_proofs: bool,
+    shards: Vec<ManifestShard>,
+}
+
+impl ManifestBuilder {
+    pub fn new(salt: impl Into<String>, total_bytes: usize, chunk_count: usize) -> Self {
+        Self {
+            salt: salt.into(),
+            total_bytes,
+            chunk_count,
+            cid_format: CidFormat::Sha256Hex,
+            hash_algorithm: HashAlgorithm::Sha256,
+            gateways: Vec::new(),
+            deterministic: false,
+            recipients: Vec::new(),
+            outer_code: None,
+            holes: Vec::new(),
+            merkle_proofs: false,
+            shards: Vec::new(),
+        }
+    }
+
+    /// Starts from a pipeline run, taking its salt, sizes, CID format, hash
+    /// algorithm, recipients, outer code, holes and whether it encrypted
+    /// deterministically.
+    pub fn for_output(output: &PipelineOutput) -> Self {
+        Self::new(output.salt.clone(), output.total_bytes, output.chunk_count)
+            .cid_format(output.config.cid_format)
+            .hash_algorithm(output.config.hash_algorithm)
+            .deterministic(output.config.deterministic_salt.is_some())
+            .recipients(output.recipients.clone())
+            .outer_code(output.config.outer_code)
+            .holes(output.holes.clone())
+    }
+
+    /// Wrapped content keys; a manifest with any is written as 5.x.
+    pub fn recipients(mut self, recipients: Vec<KeyWrap>) -> Self {
+        self.recipients = recipients;
+        self
+    }
+
+    pub fn cid_format(mut self, cid_format: CidFormat) -> Self {
+        self.cid_format = cid_format;
+        self
+    }
+
+    pub fn hash_algorithm(mut self, hash_algorithm: HashAlgorithm) -> Self {
+        self.hash_algorithm = hash_algorithm;
+        self
+    }
+
+    /// Gateways the manifest is registered with.
+    pub fn gateways(mut self, gateways: Vec<String>) -> Self {
+        self.gateways = gateways;
+        self
+    }
+
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    pub fn outer_code(mut self, outer_code: Option<OuterCode>) -> Self {
+        self.outer_code = outer_code;
+        self
+    }
+
+    /// Chunks that have no shards, rebuilt from the manifest on retrieval.
+    pub fn holes(mut self, holes: Vec<Hole>) -> Self {
+        self.holes = holes;
+        self
+    }
+
+    /// Give every shard its merkle proof, so retrieval can check each one
+    /// against the root on its own. Off by default: a proof adds a sibling
+    /// per tree level to every shard entry.
+    pub fn merkle_proofs(mut self, enabled: bool) -> Self {
+        self.merkle_proofs = enabled;
+        self
+    }
+
+    /// Places `shard` on `peers` with `audit_rounds` fresh audit vectors
+    /// drawn from its bytes.
+    pub fn place_shard(&mut self, shard: &Shard, peers: Vec<String>, audit_rounds: usize) -> Result<&ManifestShard> {
+        if CidFormat::of(&shard.cid) != Some(self.cid_format) {
+            return Err(anyhow!("shard cid {} is not in the manifest's cid format", shard.cid));
+        }
+        if HashAlgorithm::of(&shard.cid) != Some(self.hash_algorithm) {
+            return Err(anyhow!("shard cid {} is not hashed with the manifest's algorithm", shard.cid));
+        }
+        let (audit_challenges, audit_tokens) = build_audit_vectors(&shard.bytes, audit_rounds);
+        self.push_shard(ManifestShard {
+            chunk_index: shard.chunk_index,
+            shard_index: shard.shard_index,
+            cid: shard.cid.clone(),
+            payload_len: shard.payload_len,
+            data_shards: shard.data_shards,
+            parity_shards: shard.parity_shards,
+            peers,
+            audit_challenges,
+            audit_tokens,
+            merkle_proof: Vec::new(),
+        });
+        Ok(self.shards.last().expect("shard just pushed"))
+    }
+
+    /// Adds an already assembled shard entry as is.
+    pub fn push_shard(&mut self, shard: ManifestShard) {
+        self.shards.push(shard);
+    }
+
+    pub fn shards(&self) -> &[ManifestShard] {
+        &self.shards
+    }
+
+    /// Finishes the manifest with its hash but no auth tag, for callers that
+    /// never see the password (prepared uploads from other clients).
+    pub fn build(self) -> Result<UploadManifest> {
+        self.finish(None)
+    }
+
+    /// Finishes the manifest and signs its hash with the upload password.
+    pub fn seal(self, password: &str) -> Result<UploadManifest> {
+        self.finish(Some(password))
+    }
+
+    /// Finishes a manifest with recipients and signs its hash with the
+    /// content key they wrap.
+    pub fn seal_with_key(self, content_key: &[u8; 32]) -> Result<UploadManifest> {
+        let mut manifest = self.finish(None)?;
+        manifest.reseal_with_key(content_key)?;
+        Ok(manifest)
+    }
+
+    fn finish(mut self, password: Option<&str>) -> Result<UploadManifest> {
+        let templates: Vec<Shard> = self.shards.iter().map(ManifestShard::to_template).collect();
+        if self.merkle_proofs {
+            for (shard, proof) in self.shards.iter_mut().zip(merkle_proofs_from_shards(&templates)) {
+                shard.merkle_proof = proof;
+            }
+        }
+        let version = if self.recipients.is_empty() {
+            manifest_version(self.cid_format)
+        } else {
+            recipients_manifest_version(self.cid_format)
+        };
+        let mut manifest = UploadManifest {
+            version: version.to_string(),
+            salt: self.salt,
+            manifest_root: manifest_root_from_shards(&templates),
+            total_bytes: self.total_bytes,
+            chunk_count: self.chunk_count,
+            shards: self.shards,
+            gateways: self.gateways,
+            deterministic: self.deterministic,
+            hash_algorithm: self.hash_algorithm,
+            recipients: self.recipients,
+            outer_code: self.outer_code,
+            peer_addrs: BTreeMap::new(),
+            holes: self.holes,
+            manifest_hash: String::new(),
+            manifest_auth_tag: String::new(),
+        };
+        manifest.separate_peer_addrs();
+        manifest.reseal(password)?;
+        Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        parse_x25519_secret, process_bytes, process_bytes_for_recipients, reconstruct_bytes_with_key,
+        x25519_identity, PipelineConfig, Recipient,
+    };
+
+    const PEER: &str = "/ip4/127.0.0.1/tcp/9000/p2p/12D3KooWD3eckifWpRn9wQpMG9R9hX3sD158z7EqHWmweQAJU5SA";
+
+    fn sealed(cfg: PipelineConfig, gateways: Vec<String>) -> (PipelineOutput, UploadManifest) {
+        let output = process_bytes(&[7u8; 3000], "pw", cfg).unwrap();
+        let mut builder = ManifestBuilder::for_output(&output).gateways(gateways);
+        for shard in &output.shards {
+            builder.place_shard(shard, vec![PEER.to_string()], 2).unwrap();
+        }
+        let manifest = builder.seal("pw").unwrap();
+        (output, manifest)
+    }
+
+    #[test]
+    fn sealed_manifest_round_trips_through_json() {
+        let (output, manifest) = sealed(PipelineConfig::default(), Vec::new());
+        assert_eq!(manifest.version, MANIFEST_VERSION_HMAC);
+        assert_eq!(manifest.manifest_root, output.manifest_root);
+        assert_eq!(manifest.shards.len(), output.shards.len());
+
+        let decoded: UploadManifest = serde_json::from_slice(&serde_json::to_vec(&manifest).unwrap()).unwrap();
+        assert_eq!(compute_manifest_hash(&decoded).unwrap(), manifest.manifest_hash);
+        decoded.verify_auth_tag("pw").unwrap();
+        assert!(decoded.verify_auth_tag("other").is_err());
+    }
+
+    #[test]
+    fn audit_vectors_answer_from_the_shard_bytes() {
+        let (output, manifest) = sealed(PipelineConfig::default(), Vec::new());
+        for (shard, entry) in output.shards.iter().zip(&manifest.shards) {
+            assert_eq!(entry.audit_challenges.len(), 2);
+            for (challenge, token) in entry.audit_challenges.iter().zip(&entry.audit_tokens) {
+                assert_eq!(&audit_token(challenge, &shard.bytes), token);
+            }
+        }
+    }
+
+    #[test]
+    fn version_follows_cid_format_and_gateways() {
+        let cfg = PipelineConfig {
+            cid_format: CidFormat::V1,
+            ..PipelineConfig::default()
+        };
+        let (_, manifest) = sealed(cfg, vec!["https://gw1.example.com".to_string()]);
+        assert_eq!(manifest.version, MANIFEST_VERSION_HMAC_CIDV1);
+        assert_eq!(check_manifest_version(&manifest.version, true).unwrap(), CidFormat::V1);
+        assert_eq!(check_manifest_version(&manifest.version, false).unwrap(), CidFormat::V1);
+        assert!(check_manifest_version(MANIFEST_VERSION_GATEWAYS, false).is_err());
+        assert!(check_manifest_version(MANIFEST_VERSION_CIDV1, true).is_err());
+        assert!(manifest_cid_format("9.9.9").is_err());
+    }
+
+    #[test]
+    fn legacy_tags_still_verify_and_reseal_upgrades_them() {
+        let (_, mut manifest) = sealed(PipelineConfig::default(), Vec::new());
+        manifest.version = MANIFEST_VERSION.to_string();
+        manifest.manifest_hash = compute_manifest_hash(&manifest).unwrap();
+        manifest.manifest_auth_tag = legacy_manifest_auth_tag("pw", &manifest.salt, &manifest.manifest_hash);
+        manifest.verify_auth_tag("pw").unwrap();
+        assert!(manifest.verify_auth_tag("other").is_err());
+
+        // A legacy tag is not accepted once the version claims HMAC.
+        let mut relabelled = manifest.clone();
+        relabelled.version = MANIFEST_VERSION_HMAC.to_string();
+        assert!(relabelled.verify_auth_tag("pw").is_err());
+
+        manifest.reseal(Some("pw")).unwrap();
+        assert_eq!(manifest.version, MANIFEST_VERSION_HMAC);
+        manifest.verify_auth_tag("pw").unwrap();
+    }
+
+    #[test]
+    fn unsealed_manifest_keeps_hash_and_reseal_tracks_changes() {
+        let output = process_bytes(&[1u8; 512], "pw", PipelineConfig::default()).unwrap();
+        let mut builder = ManifestBuilder::for_output(&output);
+        for shard in &output.shards {
+            builder.place_shard(shard, vec![PEER.to_string()], 1).unwrap();
+        }
+        let mut manifest = builder.build().unwrap();
+        assert!(manifest.manifest_auth_tag.is_empty());
+        let before = manifest.manifest_hash.clone();
+
+        manifest.shards[0].peers.push(format!("{PEER}0"));
+        manifest.reseal(Some("pw")).unwrap();
+        assert_ne!(manifest.manifest_hash, before);
+        assert!(!manifest.manifest_auth_tag.is_empty());
+    }
+
+    #[test]
+    fn shards_name_peers_by_id_and_addresses_live_in_the_book() {
+        let (_, mut manifest) = sealed(PipelineConfig::default(), Vec::new());
+        let id = peer_id_of(PEER).unwrap();
+        assert!(manifest.shards.iter().all(|s| s.peers == vec![format!("/p2p/{id}")]));
+        assert_eq!(manifest.peer_addrs[id], vec![PEER.to_string()]);
+        assert_eq!(manifest.dial_addrs(&manifest.shards[0].peers[0]), vec![PEER.to_string()]);
+        assert!(manifest.dial_addrs("/p2p/12D3KooWUnknown").is_empty());
+
+        // A moved peer keeps its placements; only the book changes.
+        let moved = format!("/ip4/10.0.0.9/tcp/9001/p2p/{id}");
+        assert!(manifest.record_peer_addr(&moved));
+        assert!(!manifest.record_peer_addr(&format!("/p2p/{id}")));
+        assert_eq!(manifest.peer_addrs[id], vec![moved.clone(), PEER.to_string()]);
+        manifest.reseal(Some("pw")).unwrap();
+        manifest.verify_auth_tag("pw").unwrap();
+
+        let mut tampered = manifest.clone();
+        tampered.peer_addrs.get_mut(id).unwrap()[0] = "/ip4/6.6.6.6/tcp/1/p2p/x".to_string();
+        assert_ne!(compute_manifest_hash(&tampered).unwrap(), manifest.manifest_hash);
+
+        let mut joined = manifest.clone();
+        joined.join_peer_addrs();
+        assert_eq!(joined.shards[0].peers, vec![moved]);
+        joined.separate_peer_addrs();
+        assert_eq!(joined.shards[0].peers, manifest.shards[0].peers);
+        assert_eq!(joined.peer_addrs, manifest.peer_addrs);
+
+        // A peer no shard names any more drops out of the book.
+        for shard in &mut joined.shards {
+            shard.peers.clear();
+        }
+        joined.separate_peer_addrs();
+        assert!(joined.peer_addrs.is_empty());
+    }
+
+    #[test]
+    fn place_shard_rejects_a_foreign_cid_format() {
+        let output = process_bytes(&[1u8; 512], "pw", PipelineConfig::default()).unwrap();
+        let mut builder = ManifestBuilder::for_output(&output).cid_format(CidFormat::V1);
+        assert!(builder.place_shard(&output.shards[0], Vec::new(), 1).is_err());
+    }
+
+    #[test]
+    fn blake3_manifest_records_its_algorithm() {
+        let cfg = PipelineConfig {
+            cid_format: CidFormat::V1,
+            hash_algorithm: HashAlgorithm::Blake3,
+            ..PipelineConfig::default()
+        };
+        let (output, manifest) = sealed(cfg, Vec::new());
+        assert_eq!(manifest.hash_algorithm, HashAlgorithm::Blake3);
+        assert_eq!(manifest.manifest_root, output.manifest_root);
+        let json = serde_json::to_string(&manifest).unwrap();
+        assert!(json.contains("\"hash_algorithm\":\"blake3\""));
+        let decoded: UploadManifest = serde_json::from_str(&json).unwrap();
+        assert_eq!(compute_manifest_hash(&decoded).unwrap(), manifest.manifest_hash);
+
+        let mut builder = ManifestBuilder::for_output(&output).hash_algorithm(HashAlgorithm::Sha256);
+        assert!(builder.place_shard(&output.shards[0], Vec::new(), 1).is_err());
+    }
+
+    #[test]
+    fn any_recipient_verifies_and_decrypts_a_5x_manifest() {
+        let (secret, public) = x25519_identity();
+        let recipients = [
+            Recipient::password("alice"),
+            Recipient::password("bob"),
+            Recipient::x25519_hex(&public).unwrap(),
+        ];
+        let data = vec![9u8; 3000];
+        let (output, content_key) =
+            process_bytes_for_recipients(&data, &recipients, PipelineConfig::default()).unwrap();
+        let mut builder = ManifestBuilder::for_output(&output);
+        for shard in &output.shards {
+            builder.place_shard(shard, vec![PEER.to_string()], 1).unwrap();
+        }
+        let mut manifest = builder.seal_with_key(&content_key).unwrap();
+        assert_eq!(manifest.version, MANIFEST_VERSION_RECIPIENTS);
+        assert_eq!(manifest.recipients.len(), 3);
+
+        manifest.verify_auth_tag("alice").unwrap();
+        manifest.verify_auth_tag("bob").unwrap();
+        assert!(manifest.verify_auth_tag("mallory").is_err());
+        let key = manifest
+            .chunk_key_for_identity(&parse_x25519_secret(&secret).unwrap())
+            .unwrap();
+        manifest.verify_auth_tag_with_key(&key).unwrap();
+        let recovered = reconstruct_bytes_with_key(&output.shards, key, data.len()).unwrap();
+        assert_eq!(recovered, data);
+
+        // Re-signing with a recipient password keeps the 5.x scheme.
+        manifest.shards[0].peers.push(format!("{PEER}0"));
+        manifest.reseal(Some("bob")).unwrap();
+        assert_eq!(manifest.version, MANIFEST_VERSION_RECIPIENTS);
+        manifest.verify_auth_tag("alice").unwrap();
+
+        // Dropping the recipients or relabelling the version breaks the tag.
+        let mut stripped = manifest.clone();
+        stripped.recipients.clear();
+        assert!(stripped.verify_auth_tag_with_key(&content_key).is_err());
+        let mut relabelled = manifest.clone();
+        relabelled.version = MANIFEST_VERSION_HMAC.to_string();
+        assert!(relabelled.verify_auth_tag("alice").is_err());
+    }
+
+    #[test]
+    fn recipients_refuse_deterministic_encryption() {
+        let cfg = PipelineConfig {
+            deterministic_salt: Some(crate::generate_salt()),
+            ..PipelineConfig::default()
+        };
+        assert!(process_bytes_for_recipients(&[1u8; 64], &[Recipient::password("pw")], cfg).is_err());
+        assert!(process_bytes_for_recipients(&[1u8; 64], &[], PipelineConfig::default()).is_err());
+    }
+
+    #[test]
+    fn holes_are_sealed_into_the_manifest() {
+        let cfg = PipelineConfig {
+            chunk_size: 1000,
+            holes: true,
+            ..PipelineConfig::default()
+        };
+        let mut data = vec![0u8; 2000];
+        data.extend((0..1000u32).map(|i| (i % 7) as u8));
+        let output = process_bytes(&data, "pw", cfg).unwrap();
+        let mut builder = ManifestBuilder::for_output(&output);
+        for shard in &output.shards {
+            builder.place_shard(shard, vec![PEER.to_string()], 1).unwrap();
+        }
+        let manifest = builder.seal("pw").unwrap();
+        assert_eq!(manifest.chunk_count, 3);
+        assert_eq!(manifest.holes.len(), 2);
+        assert!(manifest.shards.iter().all(|s| s.chunk_index == 2));
+
+        let decoded: UploadManifest = serde_json::from_slice(&serde_json::to_vec(&manifest).unwrap()).unwrap();
+        assert_eq!(decoded.holes, manifest.holes);
+        decoded.verify_auth_tag("pw").unwrap();
+
+        let mut tampered = manifest.clone();
+        tampered.holes[0].byte = 1;
+        assert_ne!(compute_manifest_hash(&tampered).unwrap(), manifest.manifest_hash);
+    }
+
+    #[test]
+    fn manifest_hash_is_stable() {
+        // Pinned so moving or reordering fields cannot silently change the
+        // hash of manifests already in the wild.
+        let manifest = UploadManifest {
+            version: MANIFEST_VERSION.to_string(),
+            salt: "00".to_string(),
+            manifest_root: "root".to_string(),
+            total_bytes: 10,
+            chunk_count: 1,
+            shards: vec![ManifestShard {
+                chunk_index: 0,
+                shard_index: 1,
+                cid: "ab".to_string(),
+                payload_len: 40,
+                data_shards: 4,
+                parity_shards: 2,
+                peers: vec!["p".to_string()],
+                audit_challenges: vec!["00".to_string()],
+                audit_tokens: vec!["11".to_string()],
+                merkle_proof: Vec::new(),
+            }],
+            gateways: Vec::new(),
+            deterministic: false,
+            hash_algorithm: HashAlgorithm::Sha256,
+            recipients: Vec::new(),
+            outer_code: None,
+            peer_addrs: BTreeMap::new(),
+            holes: Vec::new(),
+            manifest_hash: String::new(),
+            manifest_auth_tag: String::new(),
+        };
+        assert_eq!(
+            compute_manifest_hash(&manifest).unwrap(),
+            "2d8fbed8e131603a23b824293808c321595471b4d005a3d2a4bec48f57490dbf"
+        );
+    }
+}
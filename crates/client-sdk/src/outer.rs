@@ -0,0 +1,184 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::erasure::{encode_payload, erasure_decode, Codec};
+use crate::{shard_cid_matches, CidFormat, HashAlgorithm, Shard, MAX_TOTAL_SHARDS};
+
+/// An outer Reed-Solomon code across chunks, so a chunk that loses every
+/// one of its shards can still be rebuilt from its neighbours. Chunks are
+/// taken `group_chunks` at a time (the last group may be shorter) and each
+/// group gets `parity_chunks` parity chunks, costing roughly
+/// `parity_chunks / group_chunks` extra storage.
+///
+/// Parity chunks are shard sets like any other, numbered from the
+/// manifest's `chunk_count` upwards: group `g`'s parity chunk `p` has chunk
+/// index `chunk_count + g * parity_chunks + p`. They are computed over the
+/// sealed chunk payloads, so nothing is decrypted to use them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OuterCode {
+    pub group_chunks: usize,
+    pub parity_chunks: usize,
+}
+
+impl OuterCode {
+    pub fn validate(&self) -> Result<()> {
+        if self.group_chunks == 0 {
+            return Err(anyhow!("outer code group_chunks must be >= 1"));
+        }
+        if self.parity_chunks == 0 {
+            return Err(anyhow!("outer code parity_chunks must be >= 1"));
+        }
+        match self.group_chunks.checked_add(self.parity_chunks) {
+            Some(total) if total <= MAX_TOTAL_SHARDS => Ok(()),
+            _ => Err(anyhow!(
+                "outer code group_chunks ({}) + parity_chunks ({}) exceeds the Reed-Solomon limit of {}",
+                self.group_chunks,
+                self.parity_chunks,
+                MAX_TOTAL_SHARDS
+            )),
+        }
+    }
+
+    /// How many parity chunks a file of `chunk_count` chunks carries.
+    pub fn parity_chunk_count(&self, chunk_count: usize) -> usize {
+        chunk_count.div_ceil(self.group_chunks) * self.parity_chunks
+    }
+
+    fn group_range(&self, group: usize, chunk_count: usize) -> std::ops::Range<usize> {
+        let start = group * self.group_chunks;
+        start..usize::min(start + self.group_chunks, chunk_count)
+    }
+}
+
+/// The outer parity payloads for `payloads` (each chunk's `nonce ||
+/// ciphertext`, in chunk order), group by group. Every payload is prefixed
+/// with its length as a little-endian u32 and zero-padded to the longest in
+/// its group, so a rebuilt payload knows where it ends.
+pub(crate) fn encode_parity(payloads: &[Vec<u8>], outer: &OuterCode) -> Result<Vec<Vec<u8>>> {
+    let mut parity = Vec::with_capacity(outer.parity_chunk_count(payloads.len()));
+    for group in payloads.chunks(outer.group_chunks) {
+        let width = 4 + group.iter().map(Vec::len).max().unwrap_or(0);
+        let mut rows: Vec<Vec<u8>> = group.iter().map(|p| framed(p, width)).collect();
+        rows.extend((0..outer.parity_chunks).map(|_| vec![0u8; width]));
+        Codec::new(group.len(), outer.parity_chunks)?.encode(&mut rows)?;
+        parity.extend(rows.drain(group.len()..));
+    }
+    Ok(parity)
+}
+
+fn framed(payload: &[u8], width: usize) -> Vec<u8> {
+    let mut row = vec![0u8; width];
+    row[..4].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+    row[4..4 + payload.len()].copy_from_slice(payload);
+    row
+}
+
+/// Rebuilds the shards of chunks that lost too many to decode on their own,
+/// using the outer parity of their group, and returns the data chunks' shards
+/// with the parity chunks left out, ready for [`crate::reconstruct_bytes`].
+/// Rebuilt shards are byte-identical to the originals, CIDs included, so
+/// this also serves a repairer without the password. Shards whose bytes do
+/// not match their CID count as lost; a chunk beyond what its group's parity
+/// can cover is left as found, for decoding to report.
+pub fn recover_chunks(shards: &[Shard], outer: &OuterCode, chunk_count: usize) -> Result<Vec<Shard>> {
+    outer.validate()?;
+    let mut by_chunk: BTreeMap<usize, Vec<&Shard>> = BTreeMap::new();
+    for shard in shards {
+        if shard_cid_matches(&shard.cid, &shard.bytes) {
+            by_chunk.entry(shard.chunk_index).or_default().push(shard);
+        }
+    }
+
+    let mut out: Vec<Shard> = Vec::with_capacity(shards.len());
+    for group in 0..chunk_count.div_ceil(outer.group_chunks) {
+        let members = outer.group_range(group, chunk_count);
+        let parity_base = chunk_count + group * outer.parity_chunks;
+        let payloads: Vec<Option<Vec<u8>>> = members
+            .clone()
+            .map(|idx| decode_payload(by_chunk.get(&idx)))
+            .collect();
+        let lost = payloads.iter().filter(|p| p.is_none()).count();
+        if lost == 0 || lost > outer.parity_chunks {
+            for idx in members {
+                out.extend(by_chunk.get(&idx).into_iter().flatten().map(|s| (*s).clone()));
+            }
+            continue;
+        }
+
+        let parity: Vec<Option<Vec<u8>>> = (parity_base..parity_base + outer.parity_chunks)
+            .map(|idx| decode_payload(by_chunk.get(&idx)))
+            .collect();
+        let Some(width) = parity.iter().flatten().map(Vec::len).next() else {
+            return Err(anyhow!("outer parity for chunk group {group} is lost as well"));
+        };
+        let mut rows: Vec<Option<Vec<u8>>> = payloads
+            .iter()
+            .map(|p| p.as_ref().filter(|p| 4 + p.len() <= width).map(|p| framed(p, width)))
+            .collect();
+        rows.extend(parity);
+        Codec::new(members.len(), outer.parity_chunks)?
+            .reconstruct_data(&mut rows)
+            .map_err(|e| anyhow!("outer code could not rebuild chunk group {group}: {e:?}"))?;
+
+        // Every chunk in a file shares the shard layout and CID scheme, so
+        // the rebuilt chunk takes them from whichever shard is at hand.
+        let template = by_chunk
+            .range(members.start..parity_base + outer.parity_chunks)
+            .flat_map(|(_, s)| s)
+            .next()
+            .ok_or_else(|| anyhow!("no shards left in chunk group {group}"))?;
+        for (offset, idx) in members.enumerate() {
+            if payloads[offset].is_some() {
+                out.extend(by_chunk[&idx].iter().map(|s| (*s).clone()));
+                continue;
+            }
+            let row = rows[offset].as_ref().expect("reconstruct_data fills every data row");
+            let len = u32::from_le_bytes(row[..4].try_into().expect("4-byte length prefix")) as usize;
+            if 4 + len > row.len() {
+                return Err(anyhow!("outer code rebuilt an invalid length for chunk {idx}"));
+            }
+            out.extend(payload_shards(idx, &row[4..4 + len], template)?);
+        }
+    }
+    Ok(out)
+}
+
+/// A chunk's `nonce || ciphertext`, if enough of its shards survived.
+fn decode_payload(shards: Option<&Vec<&Shard>>) -> Option<Vec<u8>> {
+    let shards = shards?;
+    let first = shards.first()?;
+    let total = first.data_shards + first.parity_shards;
+    if shards.len() < first.data_shards {
+        return None;
+    }
+    let mut slots: Vec<Option<Vec<u8>>> = vec![None; total];
+    for shard in shards {
+        if let Some(slot) = slots.get_mut(shard.shard_index) {
+            *slot = Some(shard.bytes.clone());
+        }
+    }
+    erasure_decode(slots, first.data_shards, first.parity_shards, first.payload_len).ok()
+}
+
+fn payload_shards(chunk_index: usize, payload: &[u8], template: &Shard) -> Result<Vec<Shard>> {
+    let cid_format = CidFormat::of(&template.cid)
+        .ok_or_else(|| anyhow!("unrecognised shard cid {}", template.cid))?;
+    let algorithm = HashAlgorithm::of(&template.cid).unwrap_or_default();
+    encode_payload(payload, template.data_shards, template.parity_shards)?
+        .into_iter()
+        .enumerate()
+        .map(|(shard_index, bytes)| {
+            Ok(Shard {
+                chunk_index,
+                shard_index,
+                cid: neuro_protocol::cid::for_data(cid_format, algorithm, &bytes)
+                    .ok_or_else(|| anyhow!("cannot write {algorithm:?} CIDs as {cid_format:?}"))?,
+                bytes,
+                payload_len: payload.len(),
+                data_shards: template.data_shards,
+                parity_shards: template.parity_shards,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,275 @@
+//! Multi-recipient uploads: chunks are sealed under a random content key,
+//! and the manifest carries that key wrapped once per recipient, either
+//! under a password (Argon2 with the wrap's own salt) or for an X25519
+//! public key (ephemeral Diffie-Hellman, HKDF-SHA256). Any one recipient
+//! can open the content key and with it every chunk.
+//!
+//! Wraps name neither the password nor the public key they were made for;
+//! opening one means trying each wrap of the matching kind.
+
+use aes_gcm::{
+    aead::{Aead, Payload},
+    Aes256Gcm, KeyInit, Nonce,
+};
+use anyhow::{anyhow, Result};
+use argon2::password_hash::SaltString;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use hkdf::Hkdf;
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+use crate::derive_key;
+
+const WRAP_AAD_TAG: &[u8; 16] = b"neurostore-wrap1";
+const X25519_INFO: &[u8] = b"neurostore-x25519-wrap";
+
+/// Who an upload is encrypted for.
+pub enum Recipient {
+    Password(Zeroizing<String>),
+    X25519([u8; 32]),
+}
+
+impl Recipient {
+    pub fn password(password: &str) -> Self {
+        Self::Password(Zeroizing::new(password.to_string()))
+    }
+
+    /// A public key as 64 hex characters, the form `x25519_identity` prints.
+    pub fn x25519_hex(public_key: &str) -> Result<Self> {
+        Ok(Self::X25519(parse_key_hex(public_key, "x25519 public key")?))
+    }
+}
+
+impl std::fmt::Debug for Recipient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Password(_) => f.write_str("Password(..)"),
+            Self::X25519(key) => write!(f, "X25519({})", hex::encode(key)),
+        }
+    }
+}
+
+/// The content key sealed for one recipient, as stored in the manifest.
+/// `sealed_key` is the AES-GCM nonce followed by the sealed key, unpadded
+/// base64url.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum KeyWrap {
+    Password {
+        /// Argon2 salt for this wrap, distinct from the manifest salt.
+        salt: String,
+        sealed_key: String,
+    },
+    X25519 {
+        /// Hex public half of the one-off key the wrap was made with.
+        ephemeral_public: String,
+        sealed_key: String,
+    },
+}
+
+/// A fresh X25519 identity: the secret to keep and the public key to hand
+/// to uploaders, both as hex.
+pub fn x25519_identity() -> (Zeroizing<String>, String) {
+    let secret = StaticSecret::random_from_rng(OsRng);
+    let public = PublicKey::from(&secret);
+    (
+        Zeroizing::new(hex::encode(secret.as_bytes())),
+        hex::encode(public.as_bytes()),
+    )
+}
+
+/// The hex public key belonging to `secret`, e.g. to print for an
+/// identity kept in a file or keyring.
+pub fn x25519_public_key(secret: &[u8; 32]) -> String {
+    hex::encode(PublicKey::from(&StaticSecret::from(*secret)).as_bytes())
+}
+
+/// Reads a hex X25519 secret, e.g. from an identity file.
+pub fn parse_x25519_secret(secret: &str) -> Result<Zeroizing<[u8; 32]>> {
+    Ok(Zeroizing::new(parse_key_hex(secret, "x25519 secret key")?))
+}
+
+fn parse_key_hex(value: &str, what: &str) -> Result<[u8; 32]> {
+    let raw = Zeroizing::new(hex::decode(value.trim()).map_err(|_| anyhow!("{what} is not hex"))?);
+    raw.as_slice()
+        .try_into()
+        .map_err(|_| anyhow!("{what} must be 32 bytes"))
+}
+
+/// A random key to seal an upload's chunks under.
+pub fn generate_content_key() -> Zeroizing<[u8; 32]> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    OsRng.fill_bytes(key.as_mut());
+    key
+}
+
+pub fn wrap_content_key(content_key: &[u8; 32], recipient: &Recipient) -> Result<KeyWrap> {
+    match recipient {
+        Recipient::Password(password) => {
+            let salt = SaltString::generate(&mut OsRng);
+            let kek = derive_key(password, &salt)?;
+            Ok(KeyWrap::Password {
+                salt: salt.to_string(),
+                sealed_key: seal(&kek, content_key, "password")?,
+            })
+        }
+        Recipient::X25519(public_key) => {
+            let recipient = PublicKey::from(*public_key);
+            let ephemeral = EphemeralSecret::random_from_rng(OsRng);
+            let ephemeral_public = PublicKey::from(&ephemeral);
+            let shared = ephemeral.diffie_hellman(&recipient);
+            if !shared.was_contributory() {
+                return Err(anyhow!("x25519 public key is a low-order point"));
+            }
+            let kek = x25519_kek(shared.as_bytes(), &ephemeral_public, &recipient);
+            Ok(KeyWrap::X25519 {
+                ephemeral_public: hex::encode(ephemeral_public.as_bytes()),
+                sealed_key: seal(&kek, content_key, "x25519")?,
+            })
+        }
+    }
+}
+
+/// Opens the first password wrap `password` fits.
+pub fn unwrap_with_password(wraps: &[KeyWrap], password: &str) -> Result<Zeroizing<[u8; 32]>> {
+    for wrap in wraps {
+        let KeyWrap::Password { salt, sealed_key } = wrap else {
+            continue;
+        };
+        let salt = SaltString::from_b64(salt).map_err(|e| anyhow!("invalid wrap salt: {e}"))?;
+        let kek = derive_key(password, &salt)?;
+        if let Ok(key) = open(&kek, sealed_key, "password") {
+            return Ok(key);
+        }
+    }
+    Err(anyhow!("no recipient entry opens with this password"))
+}
+
+/// Opens the first X25519 wrap made for the public half of `secret`.
+pub fn unwrap_with_x25519(wraps: &[KeyWrap], secret: &[u8; 32]) -> Result<Zeroizing<[u8; 32]>> {
+    let secret = StaticSecret::from(*secret);
+    let public = PublicKey::from(&secret);
+    for wrap in wraps {
+        let KeyWrap::X25519 {
+            ephemeral_public,
+            sealed_key,
+        } = wrap
+        else {
+            continue;
+        };
+        let Ok(ephemeral) = parse_key_hex(ephemeral_public, "ephemeral public key") else {
+            continue;
+        };
+        let ephemeral = PublicKey::from(ephemeral);
+        let shared = secret.diffie_hellman(&ephemeral);
+        let kek = x25519_kek(shared.as_bytes(), &ephemeral, &public);
+        if let Ok(key) = open(&kek, sealed_key, "x25519") {
+            return Ok(key);
+        }
+    }
+    Err(anyhow!("no recipient entry opens with this x25519 key"))
+}
+
+fn x25519_kek(shared: &[u8; 32], ephemeral: &PublicKey, recipient: &PublicKey) -> Zeroizing<[u8; 32]> {
+    let salt = [ephemeral.as_bytes().as_slice(), recipient.as_bytes()].concat();
+    let mut kek = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(X25519_INFO, kek.as_mut())
+        .expect("32 bytes is a valid HKDF-SHA256 length");
+    kek
+}
+
+fn aad(kind: &str) -> Vec<u8> {
+    [WRAP_AAD_TAG.as_slice(), kind.as_bytes()].concat()
+}
+
+fn seal(kek: &[u8; 32], content_key: &[u8; 32], kind: &str) -> Result<String> {
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut nonce);
+    let cipher = Aes256Gcm::new_from_slice(kek)?;
+    let sealed = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: content_key,
+                aad: &aad(kind),
+            },
+        )
+        .map_err(|_| anyhow!("wrapping content key failed"))?;
+    Ok(URL_SAFE_NO_PAD.encode([nonce.as_slice(), &sealed].concat()))
+}
+
+fn open(kek: &[u8; 32], sealed_key: &str, kind: &str) -> Result<Zeroizing<[u8; 32]>> {
+    let raw = URL_SAFE_NO_PAD
+        .decode(sealed_key)
+        .map_err(|_| anyhow!("wrapped key is not base64url"))?;
+    if raw.len() < 12 {
+        return Err(anyhow!("wrapped key is truncated"));
+    }
+    let (nonce, sealed) = raw.split_at(12);
+    let cipher = Aes256Gcm::new_from_slice(kek)?;
+    let opened = Zeroizing::new(
+        cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: sealed,
+                    aad: &aad(kind),
+                },
+            )
+            .map_err(|_| anyhow!("wrapped key does not open"))?,
+    );
+    let key: [u8; 32] = opened
+        .as_slice()
+        .try_into()
+        .map_err(|_| anyhow!("wrapped key has the wrong length"))?;
+    Ok(Zeroizing::new(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn any_recipient_opens_the_content_key() {
+        let (secret, public) = x25519_identity();
+        let key = generate_content_key();
+        let wraps = vec![
+            wrap_content_key(&key, &Recipient::password("alice")).unwrap(),
+            wrap_content_key(&key, &Recipient::password("bob")).unwrap(),
+            wrap_content_key(&key, &Recipient::x25519_hex(&public).unwrap()).unwrap(),
+        ];
+
+        assert_eq!(*unwrap_with_password(&wraps, "alice").unwrap(), *key);
+        assert_eq!(*unwrap_with_password(&wraps, "bob").unwrap(), *key);
+        let secret = parse_x25519_secret(&secret).unwrap();
+        assert_eq!(x25519_public_key(&secret), public);
+        assert_eq!(*unwrap_with_x25519(&wraps, &secret).unwrap(), *key);
+
+        assert!(unwrap_with_password(&wraps, "mallory").is_err());
+        let (other, _) = x25519_identity();
+        assert!(unwrap_with_x25519(&wraps, &parse_x25519_secret(&other).unwrap()).is_err());
+    }
+
+    #[test]
+    fn wraps_round_trip_through_json() {
+        let key = generate_content_key();
+        let wrap = wrap_content_key(&key, &Recipient::password("pw")).unwrap();
+        let json = serde_json::to_value(&wrap).unwrap();
+        assert_eq!(json["kind"], "password");
+        let decoded: KeyWrap = serde_json::from_value(json).unwrap();
+        assert_eq!(*unwrap_with_password(&[decoded], "pw").unwrap(), *key);
+    }
+
+    #[test]
+    fn malformed_public_keys_are_refused() {
+        assert!(Recipient::x25519_hex("abcd").is_err());
+        assert!(Recipient::x25519_hex(&"zz".repeat(32)).is_err());
+        // The identity point would give every sender the same shared secret.
+        let zero = Recipient::X25519([0u8; 32]);
+        assert!(wrap_content_key(&generate_content_key(), &zero).is_err());
+    }
+}
@@ -0,0 +1,194 @@
+//! Share tokens: a retrieval capability for one manifest that does not give
+//! away the password. The manifest's chunk key is sealed under a random
+//! share key, with the token's claims (manifest root, scope, expiry) as
+//! associated data so none of them can be edited without breaking the seal.
+//!
+//! A token is `nst1.<claims>.<sealed key>[.<share key>]`, each part
+//! unpadded base64url. Without the share key it is safe to hand to a
+//! gateway, which can publish it as a link while the share key travels
+//! separately (e.g. in the link's `#fragment`).
+
+use aes_gcm::{
+    aead::{Aead, Payload},
+    Aes256Gcm, KeyInit, Nonce,
+};
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use rand::{rngs::OsRng, RngCore};
+use zeroize::Zeroizing;
+
+pub const SHARE_TOKEN_PREFIX: &str = "nst1";
+/// The only scope so far: fetch and decrypt the manifest's chunks.
+pub const SCOPE_RETRIEVE: &str = "retrieve";
+
+const SHARE_AAD_TAG: &[u8; 16] = b"neurostore-share";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareClaims {
+    pub manifest_root: String,
+    pub scope: String,
+    /// Unix seconds after which the token no longer opens.
+    pub expires_at: u64,
+}
+
+impl ShareClaims {
+    pub(crate) fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!("{}:{}:{}", self.manifest_root, self.scope, self.expires_at))
+    }
+
+    fn decode(part: &str) -> Result<Self> {
+        let raw = URL_SAFE_NO_PAD
+            .decode(part)
+            .map_err(|_| anyhow!("share token claims are not base64url"))?;
+        let raw = String::from_utf8(raw).map_err(|_| anyhow!("share token claims are not utf-8"))?;
+        let mut fields = raw.splitn(3, ':');
+        let (Some(manifest_root), Some(scope), Some(expires_at)) = (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(anyhow!("share token claims are incomplete"));
+        };
+        Ok(Self {
+            manifest_root: manifest_root.to_string(),
+            scope: scope.to_string(),
+            expires_at: expires_at
+                .parse()
+                .map_err(|_| anyhow!("share token expiry is not a number"))?,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ShareToken {
+    pub claims: ShareClaims,
+    /// AES-GCM nonce followed by the sealed chunk key.
+    sealed_key: Vec<u8>,
+    share_key: Option<Zeroizing<[u8; 32]>>,
+}
+
+fn aad(claims_part: &str) -> Vec<u8> {
+    let mut aad = SHARE_AAD_TAG.to_vec();
+    aad.extend_from_slice(claims_part.as_bytes());
+    aad
+}
+
+impl ShareToken {
+    /// Seals `chunk_key` (see [`crate::derive_chunk_key`]) under a fresh
+    /// share key.
+    pub fn create(chunk_key: &[u8; 32], claims: ShareClaims) -> Result<Self> {
+        let mut share_key = Zeroizing::new([0u8; 32]);
+        OsRng.fill_bytes(share_key.as_mut());
+        let mut nonce = [0u8; 12];
+        OsRng.fill_bytes(&mut nonce);
+
+        let cipher = Aes256Gcm::new_from_slice(share_key.as_ref())?;
+        let sealed = cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: chunk_key,
+                    aad: &aad(&claims.encode()),
+                },
+            )
+            .map_err(|_| anyhow!("sealing share key failed"))?;
+        let mut sealed_key = nonce.to_vec();
+        sealed_key.extend(sealed);
+        Ok(Self {
+            claims,
+            sealed_key,
+            share_key: Some(share_key),
+        })
+    }
+
+    pub fn parse(token: &str) -> Result<Self> {
+        let parts: Vec<&str> = token.trim().split('.').collect();
+        if parts.first() != Some(&SHARE_TOKEN_PREFIX) || !(3..=4).contains(&parts.len()) {
+            return Err(anyhow!("not a {SHARE_TOKEN_PREFIX} share token"));
+        }
+        let sealed_key = URL_SAFE_NO_PAD
+            .decode(parts[2])
+            .map_err(|_| anyhow!("share token key is not base64url"))?;
+        let mut token = Self {
+            claims: ShareClaims::decode(parts[1])?,
+            sealed_key,
+            share_key: None,
+        };
+        if let Some(share_key) = parts.get(3) {
+            token = token.with_share_key(share_key)?;
+        }
+        Ok(token)
+    }
+
+    /// Supplies the share key for a token that was published without it.
+    pub fn with_share_key(mut self, share_key: &str) -> Result<Self> {
+        let raw = Zeroizing::new(
+            URL_SAFE_NO_PAD
+                .decode(share_key.trim())
+                .map_err(|_| anyhow!("share key is not base64url"))?,
+        );
+        let key: [u8; 32] = raw
+            .as_slice()
+            .try_into()
+            .map_err(|_| anyhow!("share key must be 32 bytes"))?;
+        self.share_key = Some(Zeroizing::new(key));
+        Ok(self)
+    }
+
+    /// The token without its share key, for publishing through a gateway.
+    pub fn sealed(&self) -> String {
+        format!(
+            "{SHARE_TOKEN_PREFIX}.{}.{}",
+            self.claims.encode(),
+            URL_SAFE_NO_PAD.encode(&self.sealed_key)
+        )
+    }
+
+    pub fn share_key(&self) -> Option<String> {
+        self.share_key.as_ref().map(|k| URL_SAFE_NO_PAD.encode(k.as_ref()))
+    }
+
+    /// The full token, share key included when known.
+    pub fn encode(&self) -> String {
+        match self.share_key() {
+            Some(key) => format!("{}.{key}", self.sealed()),
+            None => self.sealed(),
+        }
+    }
+
+    /// Recovers the chunk key for `manifest_root`, refusing expired tokens,
+    /// tokens for another manifest or scope, and any edited claims.
+    pub fn open(&self, manifest_root: &str, now_secs: u64) -> Result<Zeroizing<[u8; 32]>> {
+        if self.claims.manifest_root != manifest_root {
+            return Err(anyhow!("share token is for manifest {}", self.claims.manifest_root));
+        }
+        if self.claims.scope != SCOPE_RETRIEVE {
+            return Err(anyhow!("share token scope {:?} does not allow retrieval", self.claims.scope));
+        }
+        if now_secs >= self.claims.expires_at {
+            return Err(anyhow!("share token expired"));
+        }
+        let share_key = self
+            .share_key
+            .as_ref()
+            .ok_or_else(|| anyhow!("share token has no share key"))?;
+        if self.sealed_key.len() < 12 {
+            return Err(anyhow!("share token key is truncated"));
+        }
+        let (nonce, sealed) = self.sealed_key.split_at(12);
+        let cipher = Aes256Gcm::new_from_slice(share_key.as_ref())?;
+        let opened = Zeroizing::new(
+            cipher
+                .decrypt(
+                    Nonce::from_slice(nonce),
+                    Payload {
+                        msg: sealed,
+                        aad: &aad(&self.claims.encode()),
+                    },
+                )
+                .map_err(|_| anyhow!("share token does not open with this share key"))?,
+        );
+        let key: [u8; 32] = opened
+            .as_slice()
+            .try_into()
+            .map_err(|_| anyhow!("share token key has the wrong length"))?;
+        Ok(Zeroizing::new(key))
+    }
+}
@@ -1,10 +1,23 @@
 use base64::Engine;
+use futures::{stream, StreamExt};
 use neuro_client_sdk::{
-    adaptive_config, process_bytes, reconstruct_bytes, PipelineOutput, RedundancyProfile, Shard,
+    adaptive_config, derive_chunk_key, process_bytes, reconstruct_bytes, reconstruct_sparse_with_key,
+    shard_cid_matches, unwrap_with_password, CidFormat, Hole, KeyWrap, PipelineOutput,
+    RedundancyProfile, Shard,
 };
-use serde::Deserialize;
+use neuro_placement::Strategy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use serde_wasm_bindgen::{from_value, to_value};
+use std::collections::BTreeMap;
 use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+const DEFAULT_FETCH_CONCURRENCY: usize = 8;
+const DEFAULT_UPLOAD_CONCURRENCY: usize = 4;
+/// neuro-uploader rejects prepared shards listing more peers than this.
+const MAX_PEERS_PER_SHARD: usize = 64;
 
 #[wasm_bindgen]
 pub fn process_bytes_wasm(
@@ -44,10 +57,6 @@ struct RawBundleShard {
 #[wasm_bindgen]
 pub fn reconstruct_bytes_wasm(bundle: JsValue, password: String) -> Result<Vec<u8>, JsValue> {
     let bundle: RawBundleInput = from_value(bundle).map_err(|e| JsValue::from_str(&e.to_string()))?;
-    if bundle.shards.is_empty() {
-        return Ok(Vec::new());
-    }
-
     let mut shards = Vec::<Shard>::with_capacity(bundle.shards.len());
     for row in bundle.shards {
         let bytes = base64::engine::general_purpose::STANDARD
@@ -64,8 +73,474 @@ pub fn reconstruct_bytes_wasm(bundle: JsValue, password: String) -> Result<Vec<u
         });
     }
 
-    let mut out =
-        reconstruct_bytes(&shards, &password, &bundle.salt).map_err(|e| JsValue::from_str(&e.to_string()))?;
-    out.truncate(bundle.total_bytes);
-    Ok(out)
+    reconstruct_bytes(&shards, &password, &bundle.salt, bundle.total_bytes)
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[derive(Debug, Deserialize)]
+struct UrlManifestInput {
+    salt: String,
+    total_bytes: usize,
+    shards: Vec<UrlManifestShard>,
+    /// 5.x manifests: the password opens one of these instead.
+    #[serde(default)]
+    recipients: Vec<KeyWrap>,
+    /// Constant chunks, filled in without a fetch.
+    #[serde(default)]
+    holes: Vec<Hole>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct UrlManifestShard {
+    chunk_index: usize,
+    shard_index: usize,
+    cid: String,
+    payload_len: usize,
+    data_shards: usize,
+    parity_shards: usize,
+}
+
+/// Downloads and reconstructs an object entirely in the browser.
+///
+/// Shards are fetched from `{fetcher_base_url}/{cid}` (the gateway's
+/// `/api/shard` route), carrying the manifest's bandwidth voucher as a
+/// `?voucher=` query parameter when one is given; only `data_shards`
+/// per chunk are requested up front and parity shards are pulled in when a
+/// fetch fails or returns bytes that do not hash to their CID.
+/// `on_progress(done, needed)` is called after every verified shard.
+#[wasm_bindgen]
+pub async fn retrieve_from_urls_wasm(
+    manifest: JsValue,
+    fetcher_base_url: String,
+    password: String,
+    voucher: Option<String>,
+    concurrency: Option<usize>,
+    on_progress: Option<js_sys::Function>,
+) -> Result<Vec<u8>, JsValue> {
+    let manifest: UrlManifestInput =
+        from_value(manifest).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    if manifest.shards.is_empty() {
+        return Ok(Vec::new());
+    }
+    let base_url = fetcher_base_url.trim_end_matches('/');
+    let concurrency = concurrency.unwrap_or(DEFAULT_FETCH_CONCURRENCY).max(1);
+    let query = voucher
+        .map(|v| format!("?voucher={}", js_sys::encode_uri_component(&v)))
+        .unwrap_or_default();
+    let query = query.as_str();
+
+    // Per chunk: candidates in shard order, data shards first.
+    let mut chunks: BTreeMap<usize, Vec<UrlManifestShard>> = BTreeMap::new();
+    for shard in &manifest.shards {
+        chunks.entry(shard.chunk_index).or_default().push(shard.clone());
+    }
+    for candidates in chunks.values_mut() {
+        candidates.sort_by_key(|s| s.shard_index);
+    }
+    let needed_total: usize = chunks
+        .values()
+        .map(|c| c.first().map(|s| s.data_shards).unwrap_or(0))
+        .sum();
+
+    let mut recovered: BTreeMap<usize, Vec<Shard>> = BTreeMap::new();
+    let mut next_candidate: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut done = 0usize;
+    loop {
+        let mut wave = Vec::new();
+        for (chunk_index, candidates) in &chunks {
+            let needed = candidates[0].data_shards;
+            let have = recovered.get(chunk_index).map(Vec::len).unwrap_or(0);
+            let cursor = next_candidate.entry(*chunk_index).or_insert(0);
+            let take = needed.saturating_sub(have).min(candidates.len() - *cursor);
+            wave.extend(candidates[*cursor..*cursor + take].iter().cloned());
+            *cursor += take;
+        }
+        if wave.is_empty() {
+            break;
+        }
+
+        let mut fetches = stream::iter(wave.into_iter().map(|shard| async move {
+            let url = format!("{base_url}/{}{query}", shard.cid);
+            (fetch_bytes(&url).await, shard)
+        }))
+        .buffer_unordered(concurrency);
+
+        while let Some((result, row)) = fetches.next().await {
+            let Ok(bytes) = result else {
+                continue;
+            };
+            if !shard_cid_matches(&row.cid, &bytes) {
+                continue;
+            }
+            recovered.entry(row.chunk_index).or_default().push(Shard {
+                chunk_index: row.chunk_index,
+                shard_index: row.shard_index,
+                cid: row.cid,
+                bytes,
+                payload_len: row.payload_len,
+                data_shards: row.data_shards,
+                parity_shards: row.parity_shards,
+            });
+            done += 1;
+            if let Some(callback) = &on_progress {
+                let _ = callback.call2(
+                    &JsValue::NULL,
+                    &JsValue::from(done as u32),
+                    &JsValue::from(needed_total as u32),
+                );
+            }
+        }
+    }
+
+    for (chunk_index, candidates) in &chunks {
+        let have = recovered.get(chunk_index).map(Vec::len).unwrap_or(0);
+        if have < candidates[0].data_shards {
+            return Err(JsValue::from_str(&format!(
+                "chunk {chunk_index} unavailable: recovered {have} of {} shards",
+                candidates[0].data_shards
+            )));
+        }
+    }
+
+    let shards: Vec<Shard> = recovered.into_values().flatten().collect();
+    let key = if manifest.recipients.is_empty() {
+        derive_chunk_key(&password, &manifest.salt)
+    } else {
+        unwrap_with_password(&manifest.recipients, &password)
+    }
+    .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    reconstruct_sparse_with_key(&shards, &manifest.holes, key, manifest.total_bytes)
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// `fetch` from either a window or a worker global scope.
+async fn send(request: &web_sys::Request) -> Result<web_sys::Response, JsValue> {
+    let global = js_sys::global();
+    let promise = if let Some(window) = global.dyn_ref::<web_sys::Window>() {
+        window.fetch_with_request(request)
+    } else if let Some(worker) = global.dyn_ref::<web_sys::WorkerGlobalScope>() {
+        worker.fetch_with_request(request)
+    } else {
+        return Err(JsValue::from_str("fetch is not available in this context"));
+    };
+    JsFuture::from(promise).await?.dyn_into()
+}
+
+async fn fetch_bytes(url: &str) -> Result<Vec<u8>, JsValue> {
+    let response = send(&web_sys::Request::new_with_str(url)?).await?;
+    if !response.ok() {
+        return Err(JsValue::from_str(&format!(
+            "shard fetch failed status={} url={url}",
+            response.status()
+        )));
+    }
+    let buffer = JsFuture::from(response.array_buffer()?).await?;
+    Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+}
+
+/// Credentials for the gateway's `/api/uploads` session API: a bearer
+/// token, or the session cookie (sent automatically) plus its CSRF token.
+#[derive(Debug, Default, Deserialize)]
+struct GatewayAuth {
+    token: Option<String>,
+    csrf_token: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SessionManifest<'a> {
+    version: &'static str,
+    salt: &'a str,
+    manifest_root: &'a str,
+    total_bytes: usize,
+    chunk_count: usize,
+    shards: Vec<SessionShard<'a>>,
+}
+
+#[derive(Serialize)]
+struct SessionShard<'a> {
+    chunk_index: usize,
+    shard_index: usize,
+    cid: &'a str,
+    payload_len: usize,
+    data_shards: usize,
+    parity_shards: usize,
+}
+
+#[derive(Deserialize)]
+struct SessionProgress {
+    missing: Vec<String>,
+}
+
+fn gateway_auth(auth: JsValue) -> Result<GatewayAuth, JsValue> {
+    if auth.is_undefined() || auth.is_null() {
+        return Ok(GatewayAuth::default());
+    }
+    from_value(auth).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Sends a request to the gateway and returns its JSON answer; any non-2xx
+/// status becomes an error carrying the response text.
+async fn gateway_call(
+    method: &str,
+    url: &str,
+    auth: &GatewayAuth,
+    body: Option<&JsValue>,
+    content_type: &str,
+) -> Result<JsValue, JsValue> {
+    let headers = web_sys::Headers::new()?;
+    if let Some(token) = &auth.token {
+        headers.set("Authorization", &format!("Bearer {token}"))?;
+    }
+    if let Some(csrf) = &auth.csrf_token {
+        headers.set("x-csrf-token", csrf)?;
+    }
+    let init = web_sys::RequestInit::new();
+    init.set_method(method);
+    init.set_credentials(web_sys::RequestCredentials::Include);
+    if let Some(body) = body {
+        headers.set("Content-Type", content_type)?;
+        init.set_body(body);
+    }
+    init.set_headers_headers(&headers);
+
+    let response = send(&web_sys::Request::new_with_str_and_init(url, &init)?).await?;
+    if !response.ok() {
+        let text = JsFuture::from(response.text()?).await?.as_string().unwrap_or_default();
+        return Err(JsValue::from_str(&format!(
+            "{method} {url} failed status={}: {}",
+            response.status(),
+            text.trim()
+        )));
+    }
+    JsFuture::from(response.json()?).await
+}
+
+fn pipeline_output(output: JsValue) -> Result<PipelineOutput, JsValue> {
+    from_value(output).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// The `--prepared` file of `neuro-uploader store-prepared`.
+#[derive(Serialize)]
+struct PreparedUploadBundle<'a> {
+    salt: &'a str,
+    deterministic: bool,
+    total_bytes: usize,
+    chunk_count: usize,
+    shards: Vec<PreparedUploadShard<'a>>,
+}
+
+#[derive(Serialize)]
+struct PreparedUploadShard<'a> {
+    chunk_index: usize,
+    shard_index: usize,
+    cid: &'a str,
+    payload_len: usize,
+    data_shards: usize,
+    parity_shards: usize,
+    peers: Vec<String>,
+    bytes_b64: String,
+}
+
+/// Turns the output of `process_bytes_wasm` into the JSON bundle
+/// `neuro-uploader store-prepared --prepared` reads, so a browser can
+/// encrypt and shard locally and leave the dialling to the CLI.
+///
+/// `peers` are `/p2p/`-terminated multiaddrs; each shard is assigned
+/// `replica_factor` of them (capped at the number of peers) with the same
+/// placement `neuro-uploader upload` uses. `placement` names a
+/// `neuro_placement::Strategy` and defaults to `rendezvous`. Returns the
+/// bundle as a JSON string, ready to be saved to a file.
+#[wasm_bindgen]
+pub fn prepare_upload_bundle_wasm(
+    output: JsValue,
+    peers: Vec<String>,
+    replica_factor: usize,
+    placement: Option<String>,
+) -> Result<String, JsValue> {
+    let output = pipeline_output(output)?;
+    if output.shards.is_empty() {
+        return Err(JsValue::from_str("pipeline output has no shards"));
+    }
+    let strategy = match placement {
+        Some(name) => name.parse::<Strategy>().map_err(|e| JsValue::from_str(&e.to_string()))?,
+        None => Strategy::Rendezvous,
+    };
+
+    let mut unique_peers = Vec::<String>::new();
+    for peer in peers {
+        let peer = peer.trim().to_string();
+        if !peer.starts_with('/') || !peer.contains("/p2p/") {
+            return Err(JsValue::from_str(&format!(
+                "peer multiaddr missing /p2p/ component: {peer}"
+            )));
+        }
+        if !unique_peers.contains(&peer) {
+            unique_peers.push(peer);
+        }
+    }
+    if unique_peers.is_empty() {
+        return Err(JsValue::from_str("at least one peer is required"));
+    }
+    let replicas = replica_factor.clamp(1, unique_peers.len().min(MAX_PEERS_PER_SHARD));
+
+    let bundle = PreparedUploadBundle {
+        salt: &output.salt,
+        deterministic: output.config.deterministic_salt.is_some(),
+        total_bytes: output.total_bytes,
+        chunk_count: output.chunk_count,
+        shards: output
+            .shards
+            .iter()
+            .map(|s| PreparedUploadShard {
+                chunk_index: s.chunk_index,
+                shard_index: s.shard_index,
+                cid: &s.cid,
+                payload_len: s.payload_len,
+                data_shards: s.data_shards,
+                parity_shards: s.parity_shards,
+                peers: neuro_placement::place_ids(strategy, &s.cid, &unique_peers, |_| None, replicas),
+                bytes_b64: base64::engine::general_purpose::STANDARD.encode(&s.bytes),
+            })
+            .collect(),
+    };
+    serde_json::to_string(&bundle).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Opens a resumable upload session for the output of `process_bytes_wasm`.
+///
+/// `auth` is `{ token }` or `{ csrf_token }`. With a `bucket`, S3 clients
+/// see the session in that bucket's multipart upload listings, under
+/// `label`. Resolves to the session's progress document; keep its
+/// `session_id` together with `output` to resume an interrupted upload.
+#[wasm_bindgen]
+pub async fn create_upload_session_wasm(
+    gateway_base_url: String,
+    output: JsValue,
+    label: Option<String>,
+    auth: JsValue,
+    bucket: Option<String>,
+) -> Result<JsValue, JsValue> {
+    let auth = gateway_auth(auth)?;
+    let output = pipeline_output(output)?;
+    let v1 = output
+        .shards
+        .first()
+        .is_some_and(|s| CidFormat::of(&s.cid) == Some(CidFormat::V1));
+    let manifest = SessionManifest {
+        version: if v1 { "2.3.0" } else { "2.2.0" },
+        salt: &output.salt,
+        manifest_root: &output.manifest_root,
+        total_bytes: output.total_bytes,
+        chunk_count: output.chunk_count,
+        shards: output
+            .shards
+            .iter()
+            .map(|s| SessionShard {
+                chunk_index: s.chunk_index,
+                shard_index: s.shard_index,
+                cid: &s.cid,
+                payload_len: s.payload_len,
+                data_shards: s.data_shards,
+                parity_shards: s.parity_shards,
+            })
+            .collect(),
+    };
+    let body = serde_json::to_string(&manifest).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let params: Vec<String> = [("label", label), ("bucket", bucket)]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|v| format!("{name}={}", js_sys::encode_uri_component(&v))))
+        .collect();
+    let query = if params.is_empty() { String::new() } else { format!("?{}", params.join("&")) };
+    let url = format!("{}/api/uploads{query}", gateway_base_url.trim_end_matches('/'));
+    gateway_call("POST", &url, &auth, Some(&JsValue::from_str(&body)), "application/json").await
+}
+
+/// Sends every shard the session still reports missing, `concurrency` at a
+/// time, so calling it again after a failure resumes where it stopped.
+/// `on_progress(stored, total)` is called after every stored shard. Fails
+/// when any shard could not be stored; resolves to the final progress
+/// document otherwise.
+#[wasm_bindgen]
+pub async fn upload_session_shards_wasm(
+    gateway_base_url: String,
+    session_id: String,
+    output: JsValue,
+    auth: JsValue,
+    concurrency: Option<usize>,
+    on_progress: Option<js_sys::Function>,
+) -> Result<JsValue, JsValue> {
+    let auth = gateway_auth(auth)?;
+    let output = pipeline_output(output)?;
+    let session_url = format!(
+        "{}/api/uploads/{}",
+        gateway_base_url.trim_end_matches('/'),
+        js_sys::encode_uri_component(&session_id)
+    );
+    let progress: SessionProgress = from_value(gateway_call("GET", &session_url, &auth, None, "").await?)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let missing: HashSet<&str> = progress.missing.iter().map(String::as_str).collect();
+    let mut queued = HashSet::new();
+    let pending: Vec<&Shard> = output
+        .shards
+        .iter()
+        .filter(|s| missing.contains(s.cid.as_str()) && queued.insert(s.cid.as_str()))
+        .collect();
+    let total = output.shards.iter().map(|s| s.cid.as_str()).collect::<HashSet<_>>().len();
+    let mut stored = total - pending.len();
+
+    let concurrency = concurrency.unwrap_or(DEFAULT_UPLOAD_CONCURRENCY).max(1);
+    let (session_url, auth) = (session_url.as_str(), &auth);
+    let mut uploads = stream::iter(pending.into_iter().map(|shard| async move {
+        let url = format!("{session_url}/shards/{}", shard.cid);
+        let body: JsValue = js_sys::Uint8Array::from(shard.bytes.as_slice()).into();
+        gateway_call("PUT", &url, auth, Some(&body), "application/octet-stream").await
+    }))
+    .buffer_unordered(concurrency);
+
+    let mut last = None;
+    let mut failed = 0usize;
+    while let Some(result) = uploads.next().await {
+        match result {
+            Ok(progress) => {
+                stored += 1;
+                last = Some(progress);
+                if let Some(callback) = &on_progress {
+                    let _ = callback.call2(
+                        &JsValue::NULL,
+                        &JsValue::from(stored as u32),
+                        &JsValue::from(total as u32),
+                    );
+                }
+            }
+            Err(_) => failed += 1,
+        }
+    }
+    if failed > 0 {
+        return Err(JsValue::from_str(&format!(
+            "{failed} shards failed to upload; call again to resume"
+        )));
+    }
+    match last {
+        Some(progress) => Ok(progress),
+        None => gateway_call("GET", session_url, auth, None, "").await,
+    }
+}
+
+/// Commits a session whose shards are all stored. Resolves to
+/// `{ session_id, manifest_root, manifest }`; the manifest carries each
+/// shard's holder and can be passed to `retrieve_from_urls_wasm`.
+#[wasm_bindgen]
+pub async fn commit_upload_session_wasm(
+    gateway_base_url: String,
+    session_id: String,
+    auth: JsValue,
+) -> Result<JsValue, JsValue> {
+    let auth = gateway_auth(auth)?;
+    let url = format!(
+        "{}/api/uploads/{}/commit",
+        gateway_base_url.trim_end_matches('/'),
+        js_sys::encode_uri_component(&session_id)
+    );
+    gateway_call("POST", &url, &auth, None, "").await
 }
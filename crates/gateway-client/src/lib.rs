@@ -0,0 +1,550 @@
+//! Typed client for the NeuroStore gateway's REST API.
+//!
+//! One [`GatewayClient`] talks to one gateway. It carries a bearer token
+//! (a session JWT or an `nsk_` API key), retries requests that failed in
+//! transit or hit a busy gateway, and, when built with [`Credentials`],
+//! logs in again once a session token is rejected.
+
+mod types;
+
+use bytes::Bytes;
+use reqwest::{header, Method, StatusCode, Url};
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub use types::*;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Cookie the gateway returns the session JWT in.
+const AUTH_COOKIE: &str = "neuro_auth";
+/// Error bodies are cut to this many characters.
+const MAX_ERROR_BODY: usize = 512;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{0}")]
+    InvalidUrl(String),
+    #[error("{method} {url} failed: {source}")]
+    Transport {
+        method: Method,
+        url: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("{method} {url} returned {status}: {body}")]
+    Status {
+        method: Method,
+        url: String,
+        status: StatusCode,
+        body: String,
+    },
+    #[error("unexpected response from {url}: {reason}")]
+    Decode { url: String, reason: String },
+}
+
+impl Error {
+    /// The HTTP status, when the gateway answered with an error.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::Status { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Canonical form of a gateway base URL: http(s) only, no trailing slash.
+pub fn normalize_base_url(url: &str) -> Result<String> {
+    let parsed = Url::parse(url).map_err(|e| Error::InvalidUrl(format!("invalid gateway url {url}: {e}")))?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host().is_none() {
+        return Err(Error::InvalidUrl(format!("gateway url must be http(s)://host[:port]: {url}")));
+    }
+    Ok(parsed.as_str().trim_end_matches('/').to_string())
+}
+
+/// Account used to obtain a fresh session token when the current one is
+/// missing, expired or rejected.
+#[derive(Clone)]
+pub struct Credentials {
+    pub email: String,
+    pub password: String,
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials").field("email", &self.email).finish_non_exhaustive()
+    }
+}
+
+/// Requests are retried on connection failures, timeouts, 429 and 502-504,
+/// waiting `base_delay`, then twice that, and so on between attempts.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(250),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// One attempt, no retries.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            base_delay: Duration::ZERO,
+        }
+    }
+
+    fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay.saturating_mul(1 << attempt.saturating_sub(1).min(6))
+    }
+}
+
+fn retryable(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+pub struct GatewayClientBuilder {
+    base_url: String,
+    token: Option<String>,
+    credentials: Option<Credentials>,
+    timeout: Duration,
+    retry: RetryPolicy,
+    http: Option<reqwest::Client>,
+}
+
+impl GatewayClientBuilder {
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into()).filter(|t| !t.is_empty());
+        self
+    }
+
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Per-request timeout; ignored when an `http_client` is given.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Shares a connection pool between clients for several gateways.
+    pub fn http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = Some(http);
+        self
+    }
+
+    pub fn build(self) -> Result<GatewayClient> {
+        let base_url = normalize_base_url(&self.base_url)?;
+        let http = match self.http {
+            Some(http) => http,
+            None => reqwest::Client::builder()
+                .timeout(self.timeout)
+                .build()
+                .map_err(|e| Error::Transport {
+                    method: Method::GET,
+                    url: base_url.clone(),
+                    source: e,
+                })?,
+        };
+        Ok(GatewayClient {
+            base_url,
+            http,
+            retry: self.retry,
+            token: Mutex::new(self.token),
+            credentials: self.credentials,
+        })
+    }
+}
+
+pub struct GatewayClient {
+    base_url: String,
+    http: reqwest::Client,
+    retry: RetryPolicy,
+    token: Mutex<Option<String>>,
+    credentials: Option<Credentials>,
+}
+
+enum Body {
+    Empty,
+    Json(Bytes),
+    Octets(Bytes),
+}
+
+impl GatewayClient {
+    pub fn builder(base_url: impl Into<String>) -> GatewayClientBuilder {
+        GatewayClientBuilder {
+            base_url: base_url.into(),
+            token: None,
+            credentials: None,
+            timeout: DEFAULT_TIMEOUT,
+            retry: RetryPolicy::default(),
+            http: None,
+        }
+    }
+
+    /// A client with the default timeout and retries, authenticating with
+    /// `token` when given.
+    pub fn new(base_url: &str, token: Option<&str>) -> Result<Self> {
+        let builder = Self::builder(base_url);
+        match token {
+            Some(token) => builder.token(token).build(),
+            None => builder.build(),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// The bearer token currently sent, which a refresh may have replaced.
+    pub fn token(&self) -> Option<String> {
+        self.token.lock().expect("token lock poisoned").clone()
+    }
+
+    fn set_token(&self, token: Option<String>) {
+        *self.token.lock().expect("token lock poisoned") = token;
+    }
+
+    fn url(&self, path: &str) -> Result<Url> {
+        Url::parse(&format!("{}{}", self.base_url, path))
+            .map_err(|e| Error::InvalidUrl(format!("invalid request path {path}: {e}")))
+    }
+
+    /// Sends one request, retrying per the policy. Answers with any status
+    /// other than a retryable one are returned as they are.
+    async fn execute(&self, method: &Method, url: &Url, body: &Body, token: Option<&str>) -> Result<reqwest::Response> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let mut request = self.http.request(method.clone(), url.clone());
+            if let Some(token) = token {
+                request = request.bearer_auth(token);
+            }
+            request = match body {
+                Body::Empty => request,
+                Body::Json(bytes) => request.header(header::CONTENT_TYPE, "application/json").body(bytes.clone()),
+                Body::Octets(bytes) => request
+                    .header(header::CONTENT_TYPE, "application/octet-stream")
+                    .body(bytes.clone()),
+            };
+            let last = attempt >= self.retry.max_attempts.max(1);
+            match request.send().await {
+                Ok(resp) if !last && retryable(resp.status()) => {}
+                Ok(resp) => return Ok(resp),
+                Err(e) if !last && (e.is_connect() || e.is_timeout()) => {}
+                Err(e) => {
+                    return Err(Error::Transport {
+                        method: method.clone(),
+                        url: url.to_string(),
+                        source: e,
+                    })
+                }
+            }
+            tokio::time::sleep(self.retry.delay(attempt)).await;
+        }
+    }
+
+    /// Sends an authenticated request and fails on any non-2xx answer. A 401
+    /// is answered by logging in again once, when credentials are known.
+    async fn send(&self, method: Method, url: Url, body: Body) -> Result<reqwest::Response> {
+        let token = self.token();
+        let mut resp = self.execute(&method, &url, &body, token.as_deref()).await?;
+        if resp.status() == StatusCode::UNAUTHORIZED {
+            if let Some(credentials) = &self.credentials {
+                self.authenticate("/api/login", &login_body(credentials, None)).await?;
+                resp = self.execute(&method, &url, &body, self.token().as_deref()).await?;
+            }
+        }
+        check(&method, resp).await
+    }
+
+    async fn get(&self, path: &str) -> Result<reqwest::Response> {
+        self.send(Method::GET, self.url(path)?, Body::Empty).await
+    }
+
+    async fn post_json<T: serde::Serialize>(&self, path: &str, value: &T) -> Result<reqwest::Response> {
+        let body = serde_json::to_vec(value).map_err(|e| Error::Decode {
+            url: path.to_string(),
+            reason: e.to_string(),
+        })?;
+        self.send(Method::POST, self.url(path)?, Body::Json(body.into())).await
+    }
+
+    /// Posts to a login or register route and keeps the session JWT the
+    /// gateway sets as a cookie.
+    async fn authenticate(&self, path: &str, body: &Bytes) -> Result<Session> {
+        let method = Method::POST;
+        let url = self.url(path)?;
+        let resp = check(&method, self.execute(&method, &url, &Body::Json(body.clone()), None).await?).await?;
+        let token = session_cookie(resp.headers()).ok_or_else(|| Error::Decode {
+            url: url.to_string(),
+            reason: format!("no {AUTH_COOKIE} cookie in the answer"),
+        })?;
+        self.set_token(Some(token));
+        json(resp).await
+    }
+
+    // ── AUTH ──
+
+    /// Logs in and uses the session token for every later request.
+    pub async fn login(&self, email: &str, password: &str) -> Result<Session> {
+        let credentials = Credentials {
+            email: email.to_string(),
+            password: password.to_string(),
+        };
+        self.authenticate("/api/login", &login_body(&credentials, None)).await
+    }
+
+    /// Creates an account and, like `login`, keeps its session token.
+    pub async fn register(&self, email: &str, password: &str, name: Option<&str>) -> Result<Session> {
+        let credentials = Credentials {
+            email: email.to_string(),
+            password: password.to_string(),
+        };
+        self.authenticate("/api/register", &login_body(&credentials, name)).await
+    }
+
+    // ── S3 ──
+
+    pub async fn put_object(&self, bucket: &str, key: &str, data: impl Into<Bytes>) -> Result<PutObjectResult> {
+        let url = self.url(&object_path(bucket, key))?;
+        let resp = self.send(Method::PUT, url, Body::Octets(data.into())).await?;
+        let etag = resp
+            .headers()
+            .get(header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim_matches('"').to_string());
+        Ok(PutObjectResult { etag })
+    }
+
+    pub async fn get_object(&self, bucket: &str, key: &str) -> Result<Bytes> {
+        bytes(self.get(&object_path(bucket, key)).await?).await
+    }
+
+    pub async fn delete_object(&self, bucket: &str, key: &str) -> Result<()> {
+        self.send(Method::DELETE, self.url(&object_path(bucket, key))?, Body::Empty).await?;
+        Ok(())
+    }
+
+    /// One page of keys (at most 1000) under `prefix`, rolled up at
+    /// `delimiter` when given.
+    pub async fn list_objects(&self, bucket: &str, prefix: Option<&str>, delimiter: Option<&str>) -> Result<ListObjectsResult> {
+        let mut url = self.url(&format!("/{}", encode_segment(bucket)))?;
+        {
+            let mut query = url.query_pairs_mut();
+            if let Some(prefix) = prefix {
+                query.append_pair("prefix", prefix);
+            }
+            if let Some(delimiter) = delimiter {
+                query.append_pair("delimiter", delimiter);
+            }
+        }
+        let resp = self.send(Method::GET, url, Body::Empty).await?;
+        let url = resp.url().to_string();
+        let xml = resp.text().await.map_err(|e| Error::Decode {
+            url,
+            reason: e.to_string(),
+        })?;
+        Ok(types::parse_list_objects(&xml))
+    }
+
+    // ── MANIFESTS ──
+
+    /// Registers an uploader manifest, as serialized by neuro-uploader.
+    pub async fn register_manifest(&self, manifest_json: impl Into<Bytes>, label: Option<&str>) -> Result<RegisteredManifest> {
+        let mut url = self.url("/api/manifests")?;
+        if let Some(label) = label {
+            url.query_pairs_mut().append_pair("label", label);
+        }
+        json(self.send(Method::POST, url, Body::Json(manifest_json.into())).await?).await
+    }
+
+    pub async fn list_manifests(&self) -> Result<Vec<ManifestSummary>> {
+        json(self.get("/api/manifests").await?).await
+    }
+
+    pub async fn locate_manifest(&self, manifest_root: &str) -> Result<LocatedManifest> {
+        json(self.get(&format!("/api/manifests/{}", encode_segment(manifest_root))).await?).await
+    }
+
+    /// Forgets a manifest and its placements; the shards stay on their
+    /// nodes until the uploader deletes them.
+    pub async fn delete_manifest(&self, manifest_root: &str) -> Result<()> {
+        let url = self.url(&format!("/api/manifests/{}", encode_segment(manifest_root)))?;
+        self.send(Method::DELETE, url, Body::Empty).await?;
+        Ok(())
+    }
+
+    /// One shard's ciphertext, fetched from the swarm by the gateway. The
+    /// caller checks it against its CID.
+    pub async fn manifest_shard(&self, manifest_root: &str, cid: &str) -> Result<Bytes> {
+        let path = format!("/api/manifests/{}/shards/{}", encode_segment(manifest_root), encode_segment(cid));
+        bytes(self.get(&path).await?).await
+    }
+
+    // ── NODES ──
+
+    /// Where a peer can be dialled now, from the gateway's DHT view.
+    pub async fn locate_peer(&self, peer_id: &str) -> Result<PeerAddrs> {
+        json(self.get(&format!("/api/nodes/{}/addrs", encode_segment(peer_id))).await?).await
+    }
+
+    pub async fn node_capabilities(&self, peer_id: &str) -> Result<NodeCapabilities> {
+        json(self.get(&format!("/api/nodes/{}/capabilities", encode_segment(peer_id))).await?).await
+    }
+
+    // ── COMPLIANCE ──
+
+    pub async fn sovereignty_audit(&self, bucket: &str) -> Result<SovereigntyAudit> {
+        json(self.get(&format!("/api/compliance/sovereignty/{}", encode_segment(bucket))).await?).await
+    }
+
+    // ── SHARES ──
+
+    /// Publishes a sealed share token (`ShareToken::sealed`).
+    pub async fn create_share(&self, sealed_token: &str) -> Result<CreatedShare> {
+        json(self.post_json("/api/shares", &serde_json::json!({ "token": sealed_token })).await?).await
+    }
+
+    pub async fn get_share(&self, share_id: &str) -> Result<SharedLink> {
+        json(self.get(&format!("/api/shares/{}", encode_segment(share_id))).await?).await
+    }
+
+    pub async fn revoke_share(&self, share_id: &str) -> Result<()> {
+        let url = self.url(&format!("/api/shares/{}", encode_segment(share_id)))?;
+        self.send(Method::DELETE, url, Body::Empty).await?;
+        Ok(())
+    }
+}
+
+fn login_body(credentials: &Credentials, name: Option<&str>) -> Bytes {
+    let mut body = serde_json::json!({
+        "email": credentials.email,
+        "password": credentials.password,
+    });
+    if let Some(name) = name {
+        body["name"] = name.into();
+    }
+    body.to_string().into()
+}
+
+fn session_cookie(headers: &header::HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .find_map(|cookie| {
+            let value = cookie.strip_prefix(AUTH_COOKIE)?.strip_prefix('=')?;
+            let value = value.split(';').next().unwrap_or_default();
+            (!value.is_empty()).then(|| value.to_string())
+        })
+}
+
+async fn check(method: &Method, resp: reqwest::Response) -> Result<reqwest::Response> {
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(resp);
+    }
+    let url = resp.url().to_string();
+    let body: String = resp.text().await.unwrap_or_default().trim().chars().take(MAX_ERROR_BODY).collect();
+    Err(Error::Status {
+        method: method.clone(),
+        url,
+        status,
+        body,
+    })
+}
+
+async fn json<T: serde::de::DeserializeOwned>(resp: reqwest::Response) -> Result<T> {
+    let url = resp.url().to_string();
+    resp.json().await.map_err(|e| Error::Decode {
+        url,
+        reason: e.to_string(),
+    })
+}
+
+async fn bytes(resp: reqwest::Response) -> Result<Bytes> {
+    let url = resp.url().to_string();
+    resp.bytes().await.map_err(|e| Error::Decode {
+        url,
+        reason: e.to_string(),
+    })
+}
+
+/// `/{bucket}/{key}`, keeping the key's slashes.
+fn object_path(bucket: &str, key: &str) -> String {
+    let key: Vec<String> = key.trim_start_matches('/').split('/').map(encode_segment).collect();
+    format!("/{}/{}", encode_segment(bucket), key.join("/"))
+}
+
+/// Percent-encodes everything but RFC 3986 unreserved characters.
+fn encode_segment(segment: &str) -> String {
+    let mut out = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_url_is_normalized() {
+        assert_eq!(normalize_base_url("https://gw.example.com/").unwrap(), "https://gw.example.com");
+        assert!(normalize_base_url("ftp://gw.example.com").is_err());
+        assert!(normalize_base_url("not a url").is_err());
+    }
+
+    #[test]
+    fn object_keys_keep_their_slashes() {
+        assert_eq!(object_path("photos", "/2026/a b.jpg"), "/photos/2026/a%20b.jpg");
+        assert_eq!(encode_segment("x?y#z"), "x%3Fy%23z");
+    }
+
+    #[test]
+    fn session_cookie_is_lifted_from_set_cookie() {
+        let mut headers = header::HeaderMap::new();
+        headers.append(header::SET_COOKIE, "neuro_csrf=abc; Path=/".parse().unwrap());
+        headers.append(header::SET_COOKIE, "neuro_auth=jwt.value; HttpOnly; Path=/".parse().unwrap());
+        assert_eq!(session_cookie(&headers).as_deref(), Some("jwt.value"));
+
+        let mut cleared = header::HeaderMap::new();
+        cleared.append(header::SET_COOKIE, "neuro_auth=; Max-Age=0".parse().unwrap());
+        assert_eq!(session_cookie(&cleared), None);
+    }
+
+    #[test]
+    fn retry_delay_doubles() {
+        let policy = RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(100),
+        };
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(3), Duration::from_millis(400));
+    }
+}
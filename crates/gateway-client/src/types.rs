@@ -0,0 +1,231 @@
+//! Request and response bodies of the gateway routes the client covers.
+//! Field names follow the gateway's JSON; unknown fields are ignored so an
+//! older client keeps working against a newer gateway.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserProfile {
+    pub email: String,
+    pub name: String,
+}
+
+/// Answer to `login` and `register`. The JWT travels in the `neuro_auth`
+/// cookie; the client lifts it from there and keeps it as its bearer token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub user: UserProfile,
+    #[serde(default)]
+    pub csrf_token: String,
+}
+
+/// Result of an S3 PUT.
+#[derive(Debug, Clone, Serialize)]
+pub struct PutObjectResult {
+    pub etag: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ListObjectsResult {
+    pub is_truncated: bool,
+    pub contents: Vec<ObjectEntry>,
+    pub common_prefixes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ObjectEntry {
+    pub key: String,
+    pub last_modified: Option<DateTime<Utc>>,
+    /// Without the surrounding quotes S3 puts on ETags.
+    pub etag: String,
+    pub size: u64,
+}
+
+/// `POST /api/manifests` answer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisteredManifest {
+    pub manifest_root: String,
+    pub shards: usize,
+    pub chunk_count: usize,
+    pub total_bytes: usize,
+}
+
+/// One row of `GET /api/manifests`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestSummary {
+    pub manifest_root: String,
+    pub label: Option<String>,
+    pub version: String,
+    pub total_bytes: i64,
+    pub chunk_count: i32,
+    pub shard_count: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// `GET /api/manifests/:root`: the manifest as the gateway stored it, plus
+/// where each shard currently lives.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocatedManifest {
+    pub manifest: serde_json::Value,
+    pub placements: Vec<ShardPlacement>,
+    pub verified_shards: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShardPlacement {
+    pub shard_cid: String,
+    pub shard_index: i32,
+    pub peer_id: String,
+    pub country_code: String,
+    pub last_verified_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerAddrs {
+    pub peer_id: String,
+    pub addrs: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeCapabilities {
+    pub peer_id: String,
+    pub protocol_version: String,
+    pub agent_version: Option<String>,
+    #[serde(default)]
+    pub features: Vec<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SovereigntyAudit {
+    pub bucket: String,
+    pub compliant: bool,
+    pub region_enforced: String,
+    pub shards_in_jurisdiction_percentage: f64,
+    pub evidence_level: String,
+    pub timestamp: String,
+    pub cryptographic_signature: String,
+}
+
+/// `POST /api/shares` answer. `path` is relative to the gateway.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatedShare {
+    pub share_id: String,
+    pub manifest_root: String,
+    pub expires_at: DateTime<Utc>,
+    pub path: String,
+}
+
+/// `GET /api/shares/:id`: the sealed token, useless without the share key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedLink {
+    pub share_id: String,
+    pub manifest_root: String,
+    pub expires_at: DateTime<Utc>,
+    pub token: String,
+}
+
+/// Parses the gateway's `ListBucketResult`. The gateway writes a fixed
+/// layout with one element per line, so this reads elements by tag rather
+/// than pulling in an XML parser.
+pub(crate) fn parse_list_objects(xml: &str) -> ListObjectsResult {
+    let mut result = ListObjectsResult {
+        is_truncated: element(xml, "IsTruncated").is_some_and(|v| v == "true"),
+        ..Default::default()
+    };
+    for block in blocks(xml, "Contents") {
+        result.contents.push(ObjectEntry {
+            key: element(block, "Key").map(unescape).unwrap_or_default(),
+            last_modified: element(block, "LastModified")
+                .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+                .map(|d| d.with_timezone(&Utc)),
+            etag: element(block, "ETag").map(|v| v.trim_matches('"').to_string()).unwrap_or_default(),
+            size: element(block, "Size").and_then(|v| v.parse().ok()).unwrap_or(0),
+        });
+    }
+    for block in blocks(xml, "CommonPrefixes") {
+        if let Some(prefix) = element(block, "Prefix") {
+            result.common_prefixes.push(unescape(prefix));
+        }
+    }
+    result
+}
+
+fn element<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{tag}>");
+    let start = xml.find(&open)? + open.len();
+    let end = xml[start..].find(&format!("</{tag}>"))? + start;
+    Some(&xml[start..end])
+}
+
+fn blocks<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let (open, close) = (format!("<{tag}>"), format!("</{tag}>"));
+    let mut out = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        let body = &rest[start + open.len()..];
+        let Some(end) = body.find(&close) else {
+            break;
+        };
+        out.push(&body[..end]);
+        rest = &body[end + close.len()..];
+    }
+    out
+}
+
+/// Reverses the gateway's `xml_escape`; `&amp;` goes last so escaped
+/// entities are not decoded twice.
+fn unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_gateway_listing() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Name>photos</Name>
+  <Prefix>2026/</Prefix>
+  <Delimiter>/</Delimiter>
+  <MaxKeys>1000</MaxKeys>
+  <IsTruncated>true</IsTruncated>
+  <Contents>
+    <Key>2026/a &amp;lt; b.jpg</Key>
+    <LastModified>2026-10-01T12:00:00+00:00</LastModified>
+    <ETag>"abc123"</ETag>
+    <Size>42</Size>
+    <StorageClass>STANDARD</StorageClass>
+  </Contents>
+  <CommonPrefixes>
+    <Prefix>2026/trips/</Prefix>
+  </CommonPrefixes>
+</ListBucketResult>"#;
+        let listing = parse_list_objects(xml);
+        assert!(listing.is_truncated);
+        assert_eq!(listing.contents.len(), 1);
+        let entry = &listing.contents[0];
+        assert_eq!(entry.key, "2026/a &lt; b.jpg");
+        assert_eq!(entry.etag, "abc123");
+        assert_eq!(entry.size, 42);
+        assert!(entry.last_modified.is_some());
+        assert_eq!(listing.common_prefixes, vec!["2026/trips/".to_string()]);
+    }
+
+    #[test]
+    fn empty_listing_has_no_entries() {
+        let xml = "<ListBucketResult><IsTruncated>false</IsTruncated></ListBucketResult>";
+        let listing = parse_list_objects(xml);
+        assert!(!listing.is_truncated);
+        assert!(listing.contents.is_empty());
+        assert!(listing.common_prefixes.is_empty());
+    }
+}
@@ -0,0 +1,144 @@
+//! Runs the client against a stand-in gateway serving the same routes and
+//! auth conventions: a JWT in the `neuro_auth` cookie on login, bearer
+//! tokens on every other call.
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{AppendHeaders, IntoResponse},
+    routing::{get, post},
+    Json, Router,
+};
+use neuro_gateway_client::{Credentials, GatewayClient, RetryPolicy};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Default)]
+struct Fake {
+    logins: AtomicUsize,
+    busy_left: AtomicUsize,
+}
+
+fn session_number(headers: &HeaderMap) -> Option<usize> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer session-")?
+        .parse()
+        .ok()
+}
+
+async fn login(State(fake): State<Arc<Fake>>) -> impl IntoResponse {
+    let n = fake.logins.fetch_add(1, Ordering::SeqCst) + 1;
+    (
+        AppendHeaders([
+            (header::SET_COOKIE, format!("neuro_auth=session-{n}; HttpOnly; Path=/")),
+            (header::SET_COOKIE, "neuro_csrf=csrf; Path=/".to_string()),
+        ]),
+        Json(serde_json::json!({
+            "token": "",
+            "user": { "email": "a@example.com", "name": "A" },
+            "csrf_token": "csrf",
+        })),
+    )
+}
+
+/// Only the newest session is valid, as if older JWTs had expired.
+async fn manifests(State(fake): State<Arc<Fake>>, headers: HeaderMap) -> impl IntoResponse {
+    if session_number(&headers) != Some(fake.logins.load(Ordering::SeqCst)) {
+        return (StatusCode::UNAUTHORIZED, "Invalid JWT").into_response();
+    }
+    if fake
+        .busy_left
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+        .is_ok()
+    {
+        return (StatusCode::SERVICE_UNAVAILABLE, "busy").into_response();
+    }
+    Json(serde_json::json!([{
+        "manifest_root": "root-1",
+        "label": null,
+        "version": "2.3.0",
+        "total_bytes": 10,
+        "chunk_count": 1,
+        "shard_count": 3,
+        "created_at": "2026-10-01T00:00:00Z",
+    }]))
+    .into_response()
+}
+
+async fn serve(fake: Arc<Fake>) -> String {
+    let app = Router::new()
+        .route("/api/login", post(login))
+        .route("/api/manifests", get(manifests))
+        .with_state(fake);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("http://{addr}")
+}
+
+fn fast_retries() -> RetryPolicy {
+    RetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(5),
+    }
+}
+
+#[tokio::test]
+async fn login_keeps_the_session_cookie_as_bearer_token() {
+    let fake = Arc::new(Fake::default());
+    let client = GatewayClient::builder(serve(fake).await).build().unwrap();
+    let session = client.login("a@example.com", "password").await.unwrap();
+    assert_eq!(session.user.email, "a@example.com");
+    assert_eq!(client.token().as_deref(), Some("session-1"));
+
+    let manifests = client.list_manifests().await.unwrap();
+    assert_eq!(manifests[0].manifest_root, "root-1");
+}
+
+#[tokio::test]
+async fn rejected_token_is_refreshed_once_with_credentials() {
+    let fake = Arc::new(Fake::default());
+    let client = GatewayClient::builder(serve(Arc::clone(&fake)).await)
+        .token("session-expired")
+        .credentials(Credentials {
+            email: "a@example.com".into(),
+            password: "password".into(),
+        })
+        .retry(fast_retries())
+        .build()
+        .unwrap();
+
+    assert_eq!(client.list_manifests().await.unwrap().len(), 1);
+    assert_eq!(fake.logins.load(Ordering::SeqCst), 1);
+    assert_eq!(client.token().as_deref(), Some("session-1"));
+}
+
+#[tokio::test]
+async fn rejected_token_without_credentials_is_an_error() {
+    let fake = Arc::new(Fake::default());
+    let client = GatewayClient::new(&serve(Arc::clone(&fake)).await, Some("session-9")).unwrap();
+    let err = client.list_manifests().await.unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::UNAUTHORIZED));
+    assert_eq!(fake.logins.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn busy_gateway_is_retried_until_the_policy_runs_out() {
+    let fake = Arc::new(Fake::default());
+    let base = serve(Arc::clone(&fake)).await;
+    let client = GatewayClient::builder(&base).retry(fast_retries()).build().unwrap();
+    client.login("a@example.com", "password").await.unwrap();
+
+    fake.busy_left.store(2, Ordering::SeqCst);
+    assert_eq!(client.list_manifests().await.unwrap().len(), 1);
+
+    fake.busy_left.store(3, Ordering::SeqCst);
+    let err = client.list_manifests().await.unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::SERVICE_UNAVAILABLE));
+}
@@ -0,0 +1,15 @@
+-- Append-only log of signed metadata operations published by a leader gateway.
+CREATE TABLE IF NOT EXISTS metadata_oplog (
+    seq BIGSERIAL PRIMARY KEY,
+    origin TEXT NOT NULL,
+    op JSONB NOT NULL,
+    signature TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Follower-side high-water mark per leader.
+CREATE TABLE IF NOT EXISTS replication_cursor (
+    leader_url TEXT PRIMARY KEY,
+    last_seq BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
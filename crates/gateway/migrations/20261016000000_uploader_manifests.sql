@@ -0,0 +1,16 @@
+-- Manifests produced by neuro-uploader, registered so CLI uploads show up in
+-- the dashboard. Their shard placements are mirrored into object_shards with
+-- object_cid = manifest_root, which puts them under the proof daemon.
+CREATE TABLE IF NOT EXISTS uploader_manifests (
+    manifest_root TEXT PRIMARY KEY,
+    owner_email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+    label TEXT,
+    version TEXT NOT NULL,
+    total_bytes BIGINT NOT NULL,
+    chunk_count INTEGER NOT NULL,
+    shard_count INTEGER NOT NULL,
+    manifest_json JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_uploader_manifests_owner ON uploader_manifests(owner_email, created_at DESC);
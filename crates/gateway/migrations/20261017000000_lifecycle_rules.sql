@@ -0,0 +1,16 @@
+-- Per-bucket lifecycle rules set through `PUT /:bucket?lifecycle`. A rule
+-- with a tag filter only expires objects carrying that tag; one without
+-- applies to the whole bucket. `bucket` is the plain bucket name, as in
+-- `objects.bucket`.
+CREATE TABLE IF NOT EXISTS bucket_lifecycle_rules (
+    bucket TEXT NOT NULL,
+    rule_id TEXT NOT NULL,
+    tag_key TEXT,
+    tag_value TEXT,
+    expiration_days INTEGER NOT NULL CHECK (expiration_days > 0),
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (bucket, rule_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_objects_bucket_created ON objects(bucket, created_at);
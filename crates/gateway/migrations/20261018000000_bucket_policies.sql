@@ -0,0 +1,13 @@
+-- Grants on a bucket to principals other than its owner. `bucket` is the
+-- masked bucket name from `buckets.name`; `principal` is a user email or
+-- `apikey:<key_id>`. An empty prefix covers the whole bucket.
+CREATE TABLE IF NOT EXISTS bucket_policies (
+    bucket TEXT NOT NULL REFERENCES buckets(name) ON DELETE CASCADE,
+    principal TEXT NOT NULL,
+    prefix TEXT NOT NULL DEFAULT '',
+    access TEXT NOT NULL CHECK (access IN ('read', 'write')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (bucket, principal, prefix)
+);
+
+CREATE INDEX IF NOT EXISTS idx_bucket_policies_principal ON bucket_policies(bucket, principal);
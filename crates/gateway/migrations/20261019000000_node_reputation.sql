@@ -0,0 +1,16 @@
+-- Latest neuro-sentinel policy per peer, written by the leader's sentinel
+-- client. Peers whose action is quarantine, evict or proactive_evict receive
+-- no new shards; repair migrates data off high-churn peers.
+CREATE TABLE IF NOT EXISTS node_reputation (
+    peer_id TEXT PRIMARY KEY,
+    score DOUBLE PRECISION NOT NULL,
+    reputation DOUBLE PRECISION NOT NULL,
+    action TEXT NOT NULL,
+    anomaly_level TEXT NOT NULL,
+    churn_probability DOUBLE PRECISION NOT NULL,
+    price_per_gb DOUBLE PRECISION NOT NULL,
+    redundancy_multiplier DOUBLE PRECISION NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_node_reputation_churn ON node_reputation(churn_probability DESC);
@@ -0,0 +1,10 @@
+-- Shard retrieval outcomes per peer as observed by GETs, used with
+-- node_reputation to try the fastest, most reliable holders first.
+-- Counts decay on every update so old behaviour fades out.
+CREATE TABLE IF NOT EXISTS node_retrieval_stats (
+    peer_id TEXT PRIMARY KEY,
+    latency_ewma_ms DOUBLE PRECISION,
+    successes DOUBLE PRECISION NOT NULL DEFAULT 0,
+    failures DOUBLE PRECISION NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,7 @@
+-- Blind index over encrypted object keys: one keyed token per
+-- `/`-terminated prefix of the plain key, so listings can select the rows
+-- under a directory without decrypting the whole bucket. NULL means the
+-- row has not been indexed yet (see key_index::backfill).
+ALTER TABLE objects ADD COLUMN IF NOT EXISTS key_tokens TEXT[];
+
+CREATE INDEX IF NOT EXISTS idx_objects_key_tokens ON objects USING GIN (key_tokens);
@@ -0,0 +1,9 @@
+-- What each node advertised over identify: protocol version, agent
+-- version and the optional protocol features it serves. NULL until the
+-- node has identified itself to this gateway.
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS protocol_version TEXT;
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS agent_version TEXT;
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS features TEXT[];
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS capabilities_updated_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_nodes_features ON nodes USING GIN (features);
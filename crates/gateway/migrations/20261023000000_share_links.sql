@@ -0,0 +1,14 @@
+-- Published neuro-uploader share tokens. `token` is the sealed part only;
+-- the share key that opens it never reaches the gateway. Links stop
+-- resolving once expired or revoked.
+CREATE TABLE IF NOT EXISTS share_links (
+    share_id TEXT PRIMARY KEY,
+    manifest_root TEXT NOT NULL REFERENCES uploader_manifests(manifest_root) ON DELETE CASCADE,
+    owner_email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+    token TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_share_links_manifest ON share_links(manifest_root);
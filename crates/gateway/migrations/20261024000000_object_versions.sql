@@ -0,0 +1,8 @@
+-- Write tokens for object overwrites. A PUT draws a version before it sends
+-- any shard, the objects row only ever moves to a higher version, and each
+-- shard placement records the version that wrote it, so a PUT that lost the
+-- race can find and remove exactly its own placements.
+CREATE SEQUENCE IF NOT EXISTS object_versions;
+
+ALTER TABLE objects ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE object_shards ADD COLUMN IF NOT EXISTS object_version BIGINT NOT NULL DEFAULT 0;
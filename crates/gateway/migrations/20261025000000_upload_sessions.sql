@@ -0,0 +1,28 @@
+-- Resumable browser uploads. A session declares the manifest the browser
+-- prepared (without placements); shards are then sent one at a time through
+-- the gateway and each stored shard's holder is recorded, so an interrupted
+-- upload resumes with only the missing shards. Open sessions that go quiet
+-- past expires_at are swept along with the shards they stored.
+CREATE TABLE IF NOT EXISTS upload_sessions (
+    session_id TEXT PRIMARY KEY,
+    owner_email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+    manifest_root TEXT NOT NULL,
+    label TEXT,
+    manifest_json JSONB NOT NULL,
+    shard_count INTEGER NOT NULL,
+    total_bytes BIGINT NOT NULL,
+    committed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_upload_sessions_expires ON upload_sessions(expires_at);
+
+CREATE TABLE IF NOT EXISTS upload_session_shards (
+    session_id TEXT NOT NULL REFERENCES upload_sessions(session_id) ON DELETE CASCADE,
+    shard_cid TEXT NOT NULL,
+    peer_id TEXT NOT NULL,
+    country_code TEXT NOT NULL DEFAULT 'XX',
+    stored_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (session_id, shard_cid)
+);
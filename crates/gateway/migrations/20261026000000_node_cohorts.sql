@@ -0,0 +1,8 @@
+-- Correlated-failure cohorts reported by neuro-sentinel. Peers sharing a
+-- cohort_id fail or slow down together (often one operator), so an object
+-- should not rely on a single cohort for its recovery threshold.
+ALTER TABLE node_reputation ADD COLUMN IF NOT EXISTS cohort_id TEXT NOT NULL DEFAULT '';
+ALTER TABLE node_reputation ADD COLUMN IF NOT EXISTS cohort_size INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE node_reputation ADD COLUMN IF NOT EXISTS collusion_risk DOUBLE PRECISION NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_node_reputation_cohort ON node_reputation(cohort_id);
@@ -0,0 +1,8 @@
+-- One-time challenges for node registration. A node must sign its challenge
+-- with the libp2p key behind its peer id before the gateway records it, so
+-- nobody can register (or re-register) a peer id they do not hold.
+CREATE TABLE IF NOT EXISTS node_registration_challenges (
+    peer_id TEXT PRIMARY KEY,
+    challenge TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
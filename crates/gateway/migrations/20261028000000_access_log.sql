@@ -0,0 +1,44 @@
+-- Per-object access history for compliance: one row per object GET, PUT or
+-- DELETE with the caller, the bytes moved, the source address and the HTTP
+-- status. Buckets are stored masked and keys encrypted, like in objects.
+-- The table is partitioned by UTC day so retention drops whole partitions;
+-- the default partition only catches rows when maintenance fell behind, and
+-- ensure_access_log_partition moves them into their day when it catches up.
+CREATE TABLE IF NOT EXISTS access_log (
+    id BIGSERIAL NOT NULL,
+    occurred_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    bucket TEXT NOT NULL,
+    object_key TEXT NOT NULL,
+    principal TEXT,
+    action TEXT NOT NULL,
+    bytes BIGINT,
+    source_ip TEXT,
+    status SMALLINT NOT NULL,
+    request_id TEXT,
+    PRIMARY KEY (occurred_at, id)
+) PARTITION BY RANGE (occurred_at);
+
+CREATE TABLE IF NOT EXISTS access_log_default PARTITION OF access_log DEFAULT;
+
+CREATE INDEX IF NOT EXISTS idx_access_log_bucket_time ON access_log(bucket, occurred_at, id);
+
+CREATE OR REPLACE FUNCTION ensure_access_log_partition(day DATE) RETURNS VOID AS $$
+DECLARE
+    part TEXT := 'access_log_p' || to_char(day, 'YYYYMMDD');
+    lo TIMESTAMPTZ := day::timestamp AT TIME ZONE 'UTC';
+    hi TIMESTAMPTZ := (day + 1)::timestamp AT TIME ZONE 'UTC';
+BEGIN
+    IF to_regclass(part) IS NOT NULL THEN
+        RETURN;
+    END IF;
+    EXECUTE format('CREATE TABLE %I (LIKE access_log INCLUDING DEFAULTS)', part);
+    EXECUTE format(
+        'WITH moved AS (DELETE FROM access_log_default WHERE occurred_at >= %L AND occurred_at < %L RETURNING *) '
+        'INSERT INTO %I SELECT * FROM moved',
+        lo, hi, part
+    );
+    EXECUTE format('ALTER TABLE access_log ATTACH PARTITION %I FOR VALUES FROM (%L) TO (%L)', part, lo, hi);
+END;
+$$ LANGUAGE plpgsql;
+
+SELECT ensure_access_log_partition((NOW() AT TIME ZONE 'UTC')::date);
@@ -0,0 +1,16 @@
+-- Encrypted exports of the gateway's metadata tables stored in the swarm.
+-- Each row is the index a restore needs: where the backup's shards went and
+-- how to reassemble them. The same index is also stored in the swarm, so a
+-- fresh gateway can restore from the backup CID alone.
+CREATE TABLE IF NOT EXISTS metadata_backups (
+    backup_cid TEXT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    size_bytes BIGINT NOT NULL,
+    shards_stored INTEGER NOT NULL,
+    row_counts JSONB NOT NULL,
+    index_json JSONB NOT NULL,
+    -- Peers holding the swarm copies of the index, so pruning can delete them.
+    index_holders TEXT[] NOT NULL DEFAULT '{}'
+);
+
+CREATE INDEX IF NOT EXISTS idx_metadata_backups_created ON metadata_backups (created_at DESC);
@@ -0,0 +1,16 @@
+-- Latest verdict of POST /api/verify for each object: whether its shards
+-- still decode, decrypt and hash back to the stored CID and ETag. One row
+-- per object CID, replaced on every run.
+CREATE TABLE IF NOT EXISTS object_health (
+    object_cid TEXT PRIMARY KEY,
+    bucket TEXT NOT NULL,
+    object_version BIGINT NOT NULL,
+    healthy BOOLEAN NOT NULL,
+    shards_fetched INTEGER NOT NULL,
+    shards_total INTEGER NOT NULL,
+    failure TEXT,
+    signature TEXT NOT NULL,
+    verified_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_object_health_unhealthy ON object_health (verified_at DESC) WHERE NOT healthy;
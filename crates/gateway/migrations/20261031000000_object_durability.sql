@@ -0,0 +1,17 @@
+-- Durability scores from the health scan daemon, one row per object CID,
+-- replaced on every pass. /api/health/:bucket summarizes them and lists the
+-- lowest scores first.
+CREATE TABLE IF NOT EXISTS object_durability (
+    object_cid TEXT PRIMARY KEY,
+    bucket TEXT NOT NULL,
+    score DOUBLE PRECISION NOT NULL,
+    healthy_shards INTEGER NOT NULL,
+    shards_total INTEGER NOT NULL,
+    recovery_threshold INTEGER NOT NULL,
+    mean_reputation DOUBLE PRECISION NOT NULL,
+    regions INTEGER NOT NULL,
+    decodable BOOLEAN NOT NULL,
+    scanned_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_object_durability_bucket_score ON object_durability (bucket, score);
@@ -0,0 +1,11 @@
+-- Per-bucket overrides of the `limits` section of the gateway config, set
+-- through /api/admin/buckets/:bucket/limits. A NULL column keeps the
+-- configured value. `bucket` is the plain bucket name, as in
+-- `objects.bucket`.
+CREATE TABLE IF NOT EXISTS bucket_limits (
+    bucket TEXT PRIMARY KEY,
+    max_keys BIGINT CHECK (max_keys > 0),
+    max_key_bytes INTEGER CHECK (max_key_bytes > 0),
+    max_metadata_bytes INTEGER CHECK (max_metadata_bytes > 0),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,16 @@
+-- Shards that broke an object's reconstruction, attributed to the peer that
+-- sent them. Retrievals of the object skip these peers, and the sentinel
+-- telemetry counts recent rows against each peer's verification rate.
+CREATE TABLE IF NOT EXISTS shard_decode_failures (
+    object_cid TEXT NOT NULL,
+    shard_index INTEGER NOT NULL,
+    peer_id TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    detected_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (object_cid, shard_index, peer_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_shard_decode_failures_peer ON shard_decode_failures (peer_id, detected_at DESC);
+
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS decode_failures BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS last_decode_failure_at TIMESTAMPTZ;
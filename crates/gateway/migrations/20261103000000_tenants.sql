@@ -0,0 +1,18 @@
+-- Organizations sharing one deployment. Every user and bucket belongs to
+-- exactly one; existing rows land in `default`. A tenant's metadata is
+-- sealed under keys derived for it, and its buckets are stored under
+-- `<tenant_id>:<bucket>` in every bucket-keyed table (the default tenant
+-- keeps the plain name), so two tenants may use the same bucket name.
+CREATE TABLE IF NOT EXISTS tenants (
+    tenant_id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO tenants (tenant_id, name) VALUES ('default', 'Default') ON CONFLICT (tenant_id) DO NOTHING;
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS tenant_id TEXT NOT NULL DEFAULT 'default' REFERENCES tenants(tenant_id);
+ALTER TABLE buckets ADD COLUMN IF NOT EXISTS tenant_id TEXT NOT NULL DEFAULT 'default' REFERENCES tenants(tenant_id);
+
+CREATE INDEX IF NOT EXISTS idx_users_tenant ON users (tenant_id);
+CREATE INDEX IF NOT EXISTS idx_buckets_tenant ON buckets (tenant_id);
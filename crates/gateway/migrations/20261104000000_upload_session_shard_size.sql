@@ -0,0 +1,4 @@
+-- Shard sizes for ListParts. Rows from before this column report no size.
+ALTER TABLE upload_session_shards ADD COLUMN IF NOT EXISTS size_bytes BIGINT;
+
+CREATE INDEX IF NOT EXISTS idx_upload_sessions_owner ON upload_sessions (owner_email, created_at, session_id);
@@ -0,0 +1,15 @@
+-- Every payout rate the sentinel has set for a peer, appended by the
+-- leader's sentinel client whenever a peer's rate or action changes. The
+-- latest row per peer is the rate in effect; /api/pricing/history replays
+-- them into daily network price curves.
+CREATE TABLE IF NOT EXISTS node_price_history (
+    id BIGSERIAL PRIMARY KEY,
+    peer_id TEXT NOT NULL,
+    price_per_gb DOUBLE PRECISION NOT NULL,
+    reputation DOUBLE PRECISION NOT NULL,
+    action TEXT NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_node_price_history_peer ON node_price_history (peer_id, recorded_at DESC);
+CREATE INDEX IF NOT EXISTS idx_node_price_history_recorded ON node_price_history (recorded_at);
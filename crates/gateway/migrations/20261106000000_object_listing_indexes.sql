@@ -0,0 +1,13 @@
+-- Listing large buckets. A listing filters on bucket and on one key_tokens
+-- entry together; the composite GIN index answers both in one lookup
+-- instead of intersecting the bucket btree with the token index, which on
+-- a million-object bucket meant visiting every row of the bucket.
+-- btree_gin provides the GIN operator class for the plain bucket column.
+CREATE EXTENSION IF NOT EXISTS btree_gin;
+
+CREATE INDEX IF NOT EXISTS idx_objects_bucket_key_tokens ON objects USING GIN (bucket, key_tokens);
+DROP INDEX IF EXISTS idx_objects_key_tokens;
+
+-- Rows the backfill has not indexed yet are always listed alongside the
+-- token matches; keep finding them cheap once the backfill is done.
+CREATE INDEX IF NOT EXISTS idx_objects_unindexed ON objects (bucket) WHERE key_tokens IS NULL;
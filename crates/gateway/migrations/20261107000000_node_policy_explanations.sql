@@ -0,0 +1,3 @@
+-- Why neuro-sentinel reached its latest policy for a node: the z-scores,
+-- SLO breaches and trend it reported, most significant first.
+ALTER TABLE node_reputation ADD COLUMN IF NOT EXISTS explanations JSONB NOT NULL DEFAULT '[]';
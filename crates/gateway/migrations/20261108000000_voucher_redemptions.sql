@@ -0,0 +1,21 @@
+-- Bandwidth vouchers nodes have redeemed, one row per voucher and node.
+-- `bytes` is what the node claimed; `credited_bytes` is what was paid,
+-- since all nodes together are never paid beyond the voucher's cap.
+CREATE TABLE IF NOT EXISTS voucher_redemptions (
+    voucher_id TEXT NOT NULL,
+    peer_id TEXT NOT NULL,
+    object_cid TEXT NOT NULL,
+    bytes BIGINT NOT NULL,
+    credited_bytes BIGINT NOT NULL,
+    credited_inr DOUBLE PRECISION NOT NULL,
+    redeemed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (voucher_id, peer_id)
+);
+
+-- Egress earnings per node, the running total of its redemptions.
+CREATE TABLE IF NOT EXISTS node_earnings (
+    peer_id TEXT PRIMARY KEY,
+    egress_bytes BIGINT NOT NULL DEFAULT 0,
+    earned_inr DOUBLE PRECISION NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
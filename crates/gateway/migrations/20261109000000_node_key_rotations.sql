@@ -0,0 +1,11 @@
+-- Identity key rotations nodes have announced. A rotation is signed by both
+-- the old and the new key; once accepted the node's records move to the new
+-- peer id and the old one can never be registered or rotated again.
+CREATE TABLE IF NOT EXISTS node_key_rotations (
+    old_peer_id TEXT PRIMARY KEY,
+    new_peer_id TEXT NOT NULL,
+    announced_at_ms BIGINT NOT NULL,
+    rotated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_node_key_rotations_new ON node_key_rotations (new_peer_id);
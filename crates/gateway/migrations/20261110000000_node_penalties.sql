@@ -0,0 +1,49 @@
+-- Penalties for failed proofs of storage, audits and poison shards. Each
+-- row is a notice signed by the gateway; a node may appeal it once, and an
+-- operator rules the appeal upheld or overturned. Overturned penalties no
+-- longer count.
+CREATE TABLE IF NOT EXISTS node_penalties (
+    penalty_id TEXT PRIMARY KEY,
+    peer_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    points INTEGER NOT NULL,
+    -- Challenge id or `<object_cid>#<shard_index>`; one penalty per event.
+    reference TEXT NOT NULL,
+    detail TEXT NOT NULL,
+    issued_at_ms BIGINT NOT NULL,
+    signature TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'active',
+    appeal_reason TEXT,
+    appealed_at TIMESTAMPTZ,
+    resolution_note TEXT,
+    resolved_at TIMESTAMPTZ
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_node_penalties_event ON node_penalties (peer_id, kind, reference);
+CREATE INDEX IF NOT EXISTS idx_node_penalties_peer ON node_penalties (peer_id, issued_at_ms DESC);
+CREATE INDEX IF NOT EXISTS idx_node_penalties_status ON node_penalties (status);
+
+-- Where each penalized node stands: its points over the penalty window, and
+-- whether that withholds its payouts or moves its shards off it.
+CREATE TABLE IF NOT EXISTS node_standing (
+    peer_id TEXT PRIMARY KEY,
+    points BIGINT NOT NULL,
+    payouts_withheld BOOLEAN NOT NULL DEFAULT FALSE,
+    evicting BOOLEAN NOT NULL DEFAULT FALSE,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Earnings credited while payouts were withheld; released to `earned_inr`
+-- once the node is back under the threshold.
+ALTER TABLE node_earnings ADD COLUMN IF NOT EXISTS withheld_inr DOUBLE PRECISION NOT NULL DEFAULT 0;
+
+-- Shards to be moved off nodes being evicted for their penalties.
+CREATE TABLE IF NOT EXISTS shard_evacuations (
+    object_cid TEXT NOT NULL,
+    shard_index INTEGER NOT NULL,
+    peer_id TEXT NOT NULL,
+    scheduled_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (object_cid, shard_index, peer_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_shard_evacuations_peer ON shard_evacuations (peer_id);
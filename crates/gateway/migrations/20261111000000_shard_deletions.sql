@@ -0,0 +1,38 @@
+-- Evidence that a deleted object's shards left the nodes holding them. One
+-- row per object deletion, and one per shard placement carrying the
+-- holder's signed deletion receipt once it answers. Placements still
+-- unconfirmed are retried by the deletion reconciler.
+CREATE TABLE IF NOT EXISTS object_deletions (
+    deletion_id TEXT PRIMARY KEY,
+    bucket TEXT NOT NULL,
+    -- Stored (masked) key; the plaintext key is not kept past the delete.
+    object_key TEXT NOT NULL,
+    object_cid TEXT NOT NULL,
+    shards INTEGER NOT NULL,
+    -- Shards with no recorded holder, only reachable through tombstones.
+    unplaced_shards INTEGER NOT NULL DEFAULT 0,
+    requested_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_object_deletions_bucket ON object_deletions (bucket, requested_at DESC);
+
+CREATE TABLE IF NOT EXISTS shard_deletions (
+    deletion_id TEXT NOT NULL REFERENCES object_deletions (deletion_id) ON DELETE CASCADE,
+    shard_cid TEXT NOT NULL,
+    peer_id TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_attempt_at TIMESTAMPTZ,
+    confirmed_at TIMESTAMPTZ,
+    -- Whether the node still held the shard when it confirmed.
+    removed BOOLEAN,
+    -- The message the node signed, verbatim, with its hex signature and
+    -- protobuf-encoded public key.
+    signed_payload TEXT,
+    signature TEXT,
+    public_key TEXT,
+    signed_at_ms BIGINT,
+    PRIMARY KEY (deletion_id, shard_cid)
+);
+
+CREATE INDEX IF NOT EXISTS idx_shard_deletions_pending ON shard_deletions (last_attempt_at) WHERE confirmed_at IS NULL;
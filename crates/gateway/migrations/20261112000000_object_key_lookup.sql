@@ -0,0 +1,8 @@
+-- Exact-key blind index. `key` is encrypted under a random nonce, so the
+-- same key never masks to the same value twice; `key_lookup` is a keyed
+-- token of the plain key (key_index::lookup_token) that a GET, HEAD or
+-- DELETE can find the row by. NULL until key_index::backfill reaches rows
+-- written before it.
+ALTER TABLE objects ADD COLUMN IF NOT EXISTS key_lookup TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_objects_key_lookup ON objects (bucket, key_lookup);
@@ -0,0 +1,8 @@
+-- One row per key. The primary key (bucket, key) never conflicts, since
+-- `key` is encrypted under a fresh nonce on every write; writes upsert on
+-- the exact-key token instead, so overwrites and racing PUTs meet on one
+-- row. Rows not yet backfilled are NULL and do not conflict; the backfill
+-- drops the older copies of keys written more than once before this.
+DROP INDEX IF EXISTS idx_objects_key_lookup;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_objects_key_lookup ON objects (bucket, key_lookup);
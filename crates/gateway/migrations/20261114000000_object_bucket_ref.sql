@@ -0,0 +1,10 @@
+-- `objects.bucket` holds the tenant-scoped bucket name every bucket-keyed
+-- table uses, while `buckets.name` holds its blind index, so the original
+-- foreign key between the two rejected every insert. The reference moves
+-- to `bucket_ref`, the blind index of `bucket`; rows written before it
+-- carry none.
+ALTER TABLE objects DROP CONSTRAINT IF EXISTS objects_bucket_fkey;
+
+ALTER TABLE objects ADD COLUMN IF NOT EXISTS bucket_ref TEXT REFERENCES buckets(name) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS idx_objects_bucket_ref ON objects (bucket_ref);
@@ -0,0 +1,5 @@
+-- Penalty notices are signed with the gateway's identity key so the
+-- penalized node can verify them. `signer_public_key` is that key,
+-- protobuf-encoded, hex; notices signed before it (with the compliance
+-- HMAC) carry none and are re-signed when the gateway starts.
+ALTER TABLE node_penalties ADD COLUMN signer_public_key TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,6 @@
+-- The bucket an upload session was opened for, as the tenant-scoped name
+-- `objects.bucket` uses, so S3 ListMultipartUploads can list one bucket's
+-- sessions. Sessions opened without a bucket carry none.
+ALTER TABLE upload_sessions ADD COLUMN IF NOT EXISTS bucket TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_upload_sessions_bucket ON upload_sessions (owner_email, bucket, session_id);
@@ -0,0 +1,204 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    body::HttpBody,
+    extract::{ConnectInfo, Path, Request, State},
+    http::{header, HeaderMap, Method},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{NaiveDate, Utc};
+use tokio::time;
+use tracing::{error, info, warn};
+
+use crate::handlers::s3::validate_bucket_principal;
+use crate::tenancy;
+use crate::AppState;
+
+/// Days of access history kept unless `ACCESS_LOG_RETENTION_DAYS` says otherwise.
+pub const DEFAULT_ACCESS_LOG_RETENTION_DAYS: i64 = 90;
+const ACCESS_LOG_SWEEP_SECS: u64 = 60 * 60;
+/// Daily partitions created ahead of time, so inserts never land in the
+/// default partition while maintenance runs on schedule.
+const PARTITIONS_AHEAD: i64 = 2;
+const PARTITION_PREFIX: &str = "access_log_p";
+
+// ── Object access middleware ──
+// Wraps the object routes and records every GET, PUT and DELETE after the
+// handler answers, denied and failed requests included. The insert runs in
+// the background so logging never delays or fails the request itself.
+pub async fn record(
+    State(state): State<Arc<AppState>>,
+    Path((bucket, key)): Path<(String, String)>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().clone();
+    if method != Method::GET && method != Method::PUT && method != Method::DELETE {
+        return next.run(request).await;
+    }
+    let principal = validate_bucket_principal(request.headers(), &state).ok();
+    let source_ip = source_ip(&request);
+    let request_id = request
+        .headers()
+        .get(crate::REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let uploaded = content_length(request.headers());
+
+    let response = next.run(request).await;
+
+    let bytes = if method == Method::PUT {
+        uploaded
+    } else {
+        response
+            .body()
+            .size_hint()
+            .exact()
+            .map(|n| n as i64)
+            .or_else(|| content_length(response.headers()))
+    };
+    let status = response.status().as_u16() as i16;
+    tokio::spawn(async move {
+        // Anonymous requests are logged against the default tenant's bucket.
+        let tenant_id = match &principal {
+            Some(principal) => tenancy::tenant_of_principal(&state, principal)
+                .await
+                .unwrap_or_else(|_| tenancy::default_tenant()),
+            None => tenancy::default_tenant(),
+        };
+        let protector = state.tenant_keys.protector(&tenant_id);
+        let scoped = tenancy::scoped_bucket(&tenant_id, &bucket);
+        let masked_bucket = protector.blind_index(&format!("bucket_salt_{}", scoped));
+        let Ok(encrypted_key) = protector.encrypt(key.trim_start_matches('/')) else {
+            warn!("Access log entry dropped: key encryption failed");
+            return;
+        };
+        let inserted = sqlx::query(
+            r#"
+            INSERT INTO access_log (bucket, object_key, principal, action, bytes, source_ip, status, request_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(&masked_bucket)
+        .bind(&encrypted_key)
+        .bind(&principal)
+        .bind(method.as_str())
+        .bind(bytes)
+        .bind(&source_ip)
+        .bind(status)
+        .bind(&request_id)
+        .execute(&state.db)
+        .await;
+        if let Err(e) = inserted {
+            warn!("Access log insert failed: {}", e);
+        }
+    });
+    response
+}
+
+fn content_length(headers: &HeaderMap) -> Option<i64> {
+    headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+}
+
+/// The first `x-forwarded-for` hop when a proxy supplied one, otherwise the
+/// address of the connection itself.
+fn source_ip(request: &Request) -> Option<String> {
+    request
+        .headers()
+        .get("x-forwarded-for")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(',').next())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .or_else(|| {
+            request
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip().to_string())
+        })
+}
+
+/// Keeps the daily `access_log` partitions in step: creates the coming days'
+/// partitions and drops those past retention, so expiring history never
+/// needs a row-by-row DELETE. Every gateway records the requests it serves
+/// into its own database, so this runs on followers as well as the leader.
+pub struct AccessLogDaemon {
+    state: Arc<AppState>,
+    retention_days: i64,
+}
+
+impl AccessLogDaemon {
+    pub fn new(state: Arc<AppState>) -> Self {
+        let retention_days = std::env::var("ACCESS_LOG_RETENTION_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|days: &i64| *days > 0)
+            .unwrap_or(DEFAULT_ACCESS_LOG_RETENTION_DAYS);
+        Self { state, retention_days }
+    }
+
+    pub async fn start(&self) {
+        info!(
+            "Access log maintenance initialized. Keeping {} days of history, checking every {} seconds.",
+            self.retention_days, ACCESS_LOG_SWEEP_SECS
+        );
+
+        let mut interval = time::interval(Duration::from_secs(ACCESS_LOG_SWEEP_SECS));
+        loop {
+            interval.tick().await;
+            match self.maintain().await {
+                Ok(0) => {}
+                Ok(dropped) => info!("Dropped {} expired access log partitions", dropped),
+                Err(e) => error!("Access log maintenance failed: {}", e),
+            }
+        }
+    }
+
+    async fn maintain(&self) -> Result<usize, sqlx::Error> {
+        let today = Utc::now().date_naive();
+        for ahead in 0..=PARTITIONS_AHEAD {
+            sqlx::query("SELECT ensure_access_log_partition($1)")
+                .bind(today + chrono::Duration::days(ahead))
+                .execute(&self.state.db)
+                .await?;
+        }
+
+        let cutoff = today - chrono::Duration::days(self.retention_days);
+        let partitions = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT c.relname::TEXT
+            FROM pg_inherits i
+            JOIN pg_class c ON c.oid = i.inhrelid
+            WHERE i.inhparent = 'access_log'::regclass
+            "#,
+        )
+        .fetch_all(&self.state.db)
+        .await?;
+
+        let mut dropped = 0;
+        for partition in partitions {
+            let day = partition
+                .strip_prefix(PARTITION_PREFIX)
+                .and_then(|d| NaiveDate::parse_from_str(d, "%Y%m%d").ok());
+            if day.is_some_and(|day| day < cutoff) {
+                // The name is ours and fully validated above, so it is safe to splice.
+                sqlx::query(&format!("DROP TABLE IF EXISTS {partition}"))
+                    .execute(&self.state.db)
+                    .await?;
+                dropped += 1;
+            }
+        }
+
+        sqlx::query("DELETE FROM access_log_default WHERE occurred_at < $1::timestamp AT TIME ZONE 'UTC'")
+            .bind(cutoff)
+            .execute(&self.state.db)
+            .await?;
+        Ok(dropped)
+    }
+}
@@ -0,0 +1,492 @@
+//! Metadata backups into the swarm. The shadow registry only pins per-object
+//! manifests and per-user roots; losing the database still loses buckets,
+//! nodes and shard maps. A backup exports the critical tables in one
+//! consistent read, compresses and encrypts them under a key derived from
+//! `COMPLIANCE_SIGNING_KEY`, and erasure-codes the result into the swarm
+//! like any object. A fresh gateway with the same compliance key can then
+//! rebuild its metadata from nothing but the backup CID.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Nonce,
+};
+use anyhow::{anyhow, bail, Context};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use neuro_protocol::{ChunkCommand, StoreChunkRequest};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::oneshot;
+use tokio::time::{self, timeout, Instant};
+use tracing::{error, info, warn};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::erasure::ErasureEncoder;
+use crate::handlers::s3::delete_placed_shards;
+use crate::p2p::SwarmRequest;
+use crate::AppState;
+
+/// Exported tables in restore order, parents before the rows referencing
+/// them. Sessions, challenges, logs and the oplog are transient and left out.
+pub const BACKUP_TABLES: &[&str] = &[
+    "tenants",
+    "users",
+    "buckets",
+    "bucket_policies",
+    "bucket_lifecycle_rules",
+    "bucket_limits",
+    "objects",
+    "object_shards",
+    "nodes",
+    "node_reputation",
+    "node_price_history",
+    "node_penalties",
+    "node_standing",
+    "shard_decode_failures",
+    "uploader_manifests",
+    "share_links",
+];
+
+const DEFAULT_BACKUP_INTERVAL_SECS: u64 = 6 * 60 * 60;
+const DEFAULT_BACKUPS_KEPT: usize = 7;
+const SNAPSHOT_FORMAT: u32 = 1;
+/// HMAC context for the backup key, so it never equals a key the compliance
+/// secret signs with elsewhere.
+const BACKUP_KEY_CONTEXT: &[u8] = b"neurostore-metadata-backup-v1";
+const ZSTD_LEVEL: i32 = 9;
+/// Backups are split so no shard exceeds this, within Reed-Solomon's limits.
+const TARGET_SHARD_BYTES: usize = 4 * 1024 * 1024;
+const MIN_DATA_SHARDS: usize = 4;
+const MAX_DATA_SHARDS: usize = 128;
+/// Copies of the index stored under distinct CIDs, so they land on
+/// different peers.
+const INDEX_COPIES: usize = 3;
+const SWARM_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub struct BackupConfig {
+    /// `None` leaves backups to the admin API.
+    pub interval: Option<Duration>,
+    pub keep: usize,
+    /// Guards the `/api/admin` routes; they answer 404 while unset.
+    pub admin_token: Option<Zeroizing<String>>,
+}
+
+impl BackupConfig {
+    /// Reads `METADATA_BACKUP_INTERVAL_SECS` (default six hours, 0 turns the
+    /// schedule off), `METADATA_BACKUPS_KEPT` and `ADMIN_TOKEN`.
+    pub fn from_env() -> Self {
+        let interval_secs = std::env::var("METADATA_BACKUP_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_BACKUP_INTERVAL_SECS);
+        Self {
+            interval: (interval_secs > 0).then(|| Duration::from_secs(interval_secs)),
+            keep: std::env::var("METADATA_BACKUPS_KEPT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|keep: &usize| *keep > 0)
+                .unwrap_or(DEFAULT_BACKUPS_KEPT),
+            admin_token: std::env::var("ADMIN_TOKEN")
+                .ok()
+                .filter(|v| !v.is_empty())
+                .map(Zeroizing::new),
+        }
+    }
+}
+
+/// Everything a restore needs besides the compliance key. Stored in the
+/// clear: it names shards and peers, never metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupIndex {
+    /// SHA-256 of the encrypted backup, which the restore checks.
+    pub backup_cid: String,
+    pub created_at: DateTime<Utc>,
+    pub size_bytes: usize,
+    pub data_shards: usize,
+    pub parity_shards: usize,
+    /// SHA-256 of each shard, so a corrupt copy is dropped before decoding.
+    pub shard_hashes: Vec<String>,
+    /// Peer given each shard; `None` where the store failed.
+    pub holders: Vec<Option<String>>,
+    pub row_counts: BTreeMap<String, i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MetadataSnapshot {
+    format: u32,
+    created_at: DateTime<Utc>,
+    tables: BTreeMap<String, serde_json::Value>,
+}
+
+fn shard_cid(backup_cid: &str, index: usize) -> String {
+    format!("{}-shard-{}", backup_cid, index)
+}
+
+fn index_cid(backup_cid: &str, copy: usize) -> String {
+    format!("backup-{}-{}", backup_cid, copy)
+}
+
+fn backup_cipher(compliance_key: &str) -> Aes256Gcm {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(compliance_key.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(BACKUP_KEY_CONTEXT);
+    let mut key: [u8; 32] = mac.finalize().into_bytes().into();
+    let cipher = Aes256Gcm::new_from_slice(&key).expect("Invalid key length");
+    key.zeroize();
+    cipher
+}
+
+fn encrypt(compliance_key: &str, plain: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut nonce_bytes = [0u8; 12];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut nonce_bytes);
+    let ciphertext = backup_cipher(compliance_key)
+        .encrypt(Nonce::from_slice(&nonce_bytes), plain)
+        .map_err(|e| anyhow!("backup encryption failed: {}", e))?;
+    let mut sealed = nonce_bytes.to_vec();
+    sealed.extend(ciphertext);
+    Ok(sealed)
+}
+
+fn decrypt(compliance_key: &str, sealed: &[u8]) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    if sealed.len() < 12 {
+        bail!("backup is too short to be encrypted");
+    }
+    let (nonce, ciphertext) = sealed.split_at(12);
+    backup_cipher(compliance_key)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map(Zeroizing::new)
+        .map_err(|_| anyhow!("backup does not decrypt; was it made under a different COMPLIANCE_SIGNING_KEY?"))
+}
+
+/// Data shards for a backup of `size` bytes; parity matches, so half the
+/// shards can be lost.
+fn shard_layout(size: usize) -> (usize, usize) {
+    let data = size.div_ceil(TARGET_SHARD_BYTES).clamp(MIN_DATA_SHARDS, MAX_DATA_SHARDS);
+    (data, data)
+}
+
+async fn store_chunk(state: &AppState, cid: String, data: Vec<u8>) -> Option<String> {
+    let (tx, rx) = oneshot::channel();
+    let req = SwarmRequest::Store {
+        command: ChunkCommand::Store(StoreChunkRequest { cid, data }),
+        geofence: "GLOBAL".to_string(),
+        tx,
+    };
+    state.p2p_tx.send(req).await.ok()?;
+    match timeout(SWARM_TIMEOUT, rx).await {
+        Ok(Ok(ack)) if ack.stored => Some(ack.peer_id),
+        _ => None,
+    }
+}
+
+async fn retrieve_chunk(state: &AppState, cid: String, preferred_peer_id: Option<String>) -> Option<Vec<u8>> {
+    let (tx, rx) = oneshot::channel();
+    let req = SwarmRequest::Retrieve { cid, preferred_peer_id, voucher: None, tx };
+    state.p2p_tx.send(req).await.ok()?;
+    timeout(SWARM_TIMEOUT, rx).await.ok()?.ok()?.data
+}
+
+/// Reads every backed-up table in one repeatable-read transaction, so the
+/// export is consistent across tables.
+async fn export_tables(state: &AppState) -> anyhow::Result<MetadataSnapshot> {
+    let mut tx = state.db.begin().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+    let mut tables = BTreeMap::new();
+    for table in BACKUP_TABLES {
+        // Table names are the constants above, never user input.
+        let rows: serde_json::Value =
+            sqlx::query_scalar(&format!("SELECT COALESCE(json_agg(t), '[]'::json) FROM {} t", table))
+                .fetch_one(&mut *tx)
+                .await
+                .with_context(|| format!("exporting {}", table))?;
+        tables.insert(table.to_string(), rows);
+    }
+    tx.commit().await?;
+    Ok(MetadataSnapshot {
+        format: SNAPSHOT_FORMAT,
+        created_at: Utc::now(),
+        tables,
+    })
+}
+
+/// Exports, encrypts and stores one backup, and records its index locally.
+pub async fn create_backup(state: &AppState) -> anyhow::Result<BackupIndex> {
+    let snapshot = export_tables(state).await?;
+    let row_counts: BTreeMap<String, i64> = snapshot
+        .tables
+        .iter()
+        .map(|(table, rows)| (table.clone(), rows.as_array().map_or(0, |r| r.len() as i64)))
+        .collect();
+    let plain = Zeroizing::new(serde_json::to_vec(&snapshot)?);
+    let compressed = Zeroizing::new(zstd::encode_all(plain.as_slice(), ZSTD_LEVEL)?);
+    let sealed = encrypt(&state.compliance_signing_key, &compressed)?;
+    let backup_cid = hex::encode(Sha256::digest(&sealed));
+
+    let (data_shards, parity_shards) = shard_layout(sealed.len());
+    let shards = ErasureEncoder::new(data_shards, parity_shards)?.encode(&sealed)?;
+    let shard_hashes: Vec<String> = shards.iter().map(|s| hex::encode(Sha256::digest(s))).collect();
+    let holders = futures::future::join_all(
+        shards
+            .into_iter()
+            .enumerate()
+            .map(|(i, shard)| store_chunk(state, shard_cid(&backup_cid, i), shard)),
+    )
+    .await;
+    let stored = holders.iter().flatten().count();
+    if stored < data_shards {
+        bail!(
+            "only {} of {} backup shards were stored; at least {} are needed to restore",
+            stored,
+            holders.len(),
+            data_shards
+        );
+    }
+
+    let index = BackupIndex {
+        backup_cid: backup_cid.clone(),
+        created_at: snapshot.created_at,
+        size_bytes: sealed.len(),
+        data_shards,
+        parity_shards,
+        shard_hashes,
+        holders,
+        row_counts,
+    };
+    let index_bytes = serde_json::to_vec(&index)?;
+    let index_holders: Vec<String> = futures::future::join_all(
+        (0..INDEX_COPIES).map(|copy| store_chunk(state, index_cid(&backup_cid, copy), index_bytes.clone())),
+    )
+    .await
+    .into_iter()
+    .flatten()
+    .collect();
+    if index_holders.is_empty() {
+        bail!("no copy of the backup index could be stored");
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO metadata_backups (backup_cid, created_at, size_bytes, shards_stored, row_counts, index_json, index_holders)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (backup_cid) DO NOTHING
+        "#,
+    )
+    .bind(&index.backup_cid)
+    .bind(index.created_at)
+    .bind(index.size_bytes as i64)
+    .bind(stored as i32)
+    .bind(serde_json::to_value(&index.row_counts)?)
+    .bind(serde_json::to_value(&index)?)
+    .bind(&index_holders)
+    .execute(&state.db)
+    .await?;
+    Ok(index)
+}
+
+/// The index for `backup_cid`: the local record if this gateway made the
+/// backup, otherwise the first intact copy found in the swarm.
+async fn load_index(state: &AppState, backup_cid: &str) -> anyhow::Result<BackupIndex> {
+    let local = sqlx::query_scalar::<_, serde_json::Value>("SELECT index_json FROM metadata_backups WHERE backup_cid = $1")
+        .bind(backup_cid)
+        .fetch_optional(&state.db)
+        .await?;
+    if let Some(index) = local {
+        return Ok(serde_json::from_value(index)?);
+    }
+    for copy in 0..INDEX_COPIES {
+        let Some(bytes) = retrieve_chunk(state, index_cid(backup_cid, copy), None).await else {
+            continue;
+        };
+        match serde_json::from_slice::<BackupIndex>(&bytes) {
+            Ok(index) if index.backup_cid == backup_cid => return Ok(index),
+            _ => warn!("Backup index copy {} for {} is unreadable", copy, backup_cid),
+        }
+    }
+    bail!("no index for backup {} was found in the swarm", backup_cid)
+}
+
+/// Fetches and reassembles the encrypted backup, checking it against its CID.
+async fn fetch_backup(state: &AppState, index: &BackupIndex) -> anyhow::Result<Vec<u8>> {
+    let total = index.data_shards + index.parity_shards;
+    if index.data_shards == 0 || index.shard_hashes.len() != total || index.holders.len() != total {
+        bail!("backup index for {} has an inconsistent shard layout", index.backup_cid);
+    }
+    let shards: Vec<Option<Vec<u8>>> = futures::future::join_all((0..total).map(|i| async move {
+        let shard = retrieve_chunk(state, shard_cid(&index.backup_cid, i), index.holders[i].clone()).await?;
+        (hex::encode(Sha256::digest(&shard)) == index.shard_hashes[i]).then_some(shard)
+    }))
+    .await;
+    let found = shards.iter().flatten().count();
+    if found < index.data_shards {
+        bail!(
+            "only {} of {} backup shards could be retrieved; {} are needed",
+            found,
+            total,
+            index.data_shards
+        );
+    }
+    let mut sealed = ErasureEncoder::new(index.data_shards, index.parity_shards)?.decode(shards)?;
+    sealed.truncate(index.size_bytes);
+    if hex::encode(Sha256::digest(&sealed)) != index.backup_cid {
+        bail!("reassembled backup does not match its CID");
+    }
+    Ok(sealed)
+}
+
+/// Restores `backup_cid` into this gateway's database and returns the rows
+/// inserted per table. Rows whose key already exists are left as they are,
+/// so restoring into a live gateway only fills in what it lacks. Nothing is
+/// published to the replication oplog; followers restore the same backup.
+pub async fn restore_backup(state: &AppState, backup_cid: &str) -> anyhow::Result<BTreeMap<String, u64>> {
+    let index = load_index(state, backup_cid).await?;
+    let sealed = fetch_backup(state, &index).await?;
+    let compressed = decrypt(&state.compliance_signing_key, &sealed)?;
+    let plain = Zeroizing::new(zstd::decode_all(compressed.as_slice())?);
+    let snapshot: MetadataSnapshot = serde_json::from_slice(&plain).context("backup snapshot is malformed")?;
+    if snapshot.format != SNAPSHOT_FORMAT {
+        bail!("backup snapshot format {} is not supported", snapshot.format);
+    }
+
+    let mut restored = BTreeMap::new();
+    let mut tx = state.db.begin().await?;
+    for table in BACKUP_TABLES {
+        let Some(rows) = snapshot.tables.get(*table) else {
+            continue;
+        };
+        let inserted = sqlx::query(&format!(
+            "INSERT INTO {0} SELECT * FROM json_populate_recordset(NULL::{0}, $1) ON CONFLICT DO NOTHING",
+            table
+        ))
+        .bind(rows)
+        .execute(&mut *tx)
+        .await
+        .with_context(|| format!("restoring {}", table))?;
+        restored.insert(table.to_string(), inserted.rows_affected());
+        reset_sequences(&mut tx, table).await?;
+    }
+    sqlx::query(
+        r#"
+        INSERT INTO metadata_backups (backup_cid, created_at, size_bytes, shards_stored, row_counts, index_json)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (backup_cid) DO NOTHING
+        "#,
+    )
+    .bind(&index.backup_cid)
+    .bind(index.created_at)
+    .bind(index.size_bytes as i64)
+    .bind(index.holders.iter().flatten().count() as i32)
+    .bind(serde_json::to_value(&index.row_counts)?)
+    .bind(serde_json::to_value(&index)?)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    state.listing_cache.invalidate_all();
+    Ok(restored)
+}
+
+/// Moves the serial sequences of `table` past the restored ids, so new rows
+/// do not collide with them.
+async fn reset_sequences(tx: &mut sqlx::Transaction<'_, sqlx::Postgres>, table: &str) -> anyhow::Result<()> {
+    let columns = sqlx::query_scalar::<_, String>(
+        r#"
+        SELECT column_name::TEXT
+        FROM information_schema.columns
+        WHERE table_schema = current_schema() AND table_name = $1 AND column_default LIKE 'nextval(%'
+        "#,
+    )
+    .bind(table)
+    .fetch_all(&mut **tx)
+    .await?;
+    for column in columns {
+        // Both names come from the catalog and our own table list.
+        sqlx::query(&format!(
+            "SELECT setval(pg_get_serial_sequence('{0}', '{1}'), COALESCE(MAX(\"{1}\"), 0) + 1, false) FROM {0}",
+            table, column
+        ))
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Takes a backup on a schedule and deletes those beyond the retention
+/// count, shards and index copies included. Runs on the leader only.
+pub struct BackupDaemon {
+    state: Arc<AppState>,
+}
+
+impl BackupDaemon {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn start(&self) {
+        let Some(every) = self.state.backup.interval else {
+            info!("Metadata backup schedule disabled; backups run only through the admin API.");
+            return;
+        };
+        info!(
+            "Metadata backup daemon initialized. Backing up every {} seconds, keeping {}.",
+            every.as_secs(),
+            self.state.backup.keep
+        );
+
+        // The first backup waits a full interval, so restarts do not pile
+        // up backups.
+        let mut interval = time::interval_at(Instant::now() + every, every);
+        loop {
+            interval.tick().await;
+            match create_backup(&self.state).await {
+                Ok(index) => info!(
+                    "Metadata backup {} stored ({} bytes, {} shards)",
+                    index.backup_cid,
+                    index.size_bytes,
+                    index.holders.iter().flatten().count()
+                ),
+                Err(e) => error!("Metadata backup failed: {:#}", e),
+            }
+            if let Err(e) = self.prune().await {
+                error!("Pruning old metadata backups failed: {}", e);
+            }
+        }
+    }
+
+    async fn prune(&self) -> Result<(), sqlx::Error> {
+        let expired = sqlx::query_as::<_, (String, serde_json::Value, Vec<String>)>(
+            "SELECT backup_cid, index_json, index_holders FROM metadata_backups ORDER BY created_at DESC OFFSET $1",
+        )
+        .bind(self.state.backup.keep as i64)
+        .fetch_all(&self.state.db)
+        .await?;
+
+        for (backup_cid, index, index_holders) in expired {
+            let mut placed = Vec::new();
+            if let Ok(index) = serde_json::from_value::<BackupIndex>(index) {
+                for (i, holder) in index.holders.into_iter().enumerate() {
+                    if holder.is_some() {
+                        placed.push((shard_cid(&backup_cid, i), holder));
+                    }
+                }
+            }
+            // Which copy each holder has is not recorded; asking every
+            // holder for every copy is harmless.
+            for copy in 0..INDEX_COPIES {
+                for holder in &index_holders {
+                    placed.push((index_cid(&backup_cid, copy), Some(holder.clone())));
+                }
+            }
+            delete_placed_shards(&self.state, placed).await;
+            sqlx::query("DELETE FROM metadata_backups WHERE backup_cid = $1")
+                .bind(&backup_cid)
+                .execute(&self.state.db)
+                .await?;
+            info!("Pruned metadata backup {}", backup_cid);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,186 @@
+//! Response compression. API responses (manifests, listings, JSON) go
+//! through tower-http's `CompressionLayer`; object downloads are compressed
+//! here instead, and only for objects flagged compressible at PUT time, so
+//! already-compressed media is never re-encoded on every GET.
+
+use axum::http::{header, HeaderMap, HeaderValue};
+use bytes::Bytes;
+use std::io::Write;
+use tower_http::compression::{
+    predicate::{NotForContentType, Predicate, SizeAbove},
+    CompressionLayer,
+};
+
+/// Set on PUT to force the flag either way; otherwise it follows the
+/// object's `Content-Type`.
+pub const COMPRESSIBLE_HEADER: &str = "x-neuro-compressible";
+/// Key in `objects.metadata_json` recording the flag.
+pub const COMPRESSIBLE_METADATA_KEY: &str = "compressible";
+
+const DEFAULT_MIN_BYTES: u16 = 1024;
+const ZSTD_LEVEL: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Zstd,
+    Gzip,
+}
+
+impl Encoding {
+    fn as_str(self) -> &'static str {
+        match self {
+            Encoding::Zstd => "zstd",
+            Encoding::Gzip => "gzip",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    pub gzip: bool,
+    pub zstd: bool,
+    /// Responses smaller than this are sent as-is.
+    pub min_bytes: u16,
+}
+
+impl CompressionConfig {
+    /// Reads `COMPRESSION_ENABLED` (default on), `COMPRESSION_ALGORITHMS`
+    /// (comma separated, default `zstd,gzip`) and `COMPRESSION_MIN_BYTES`.
+    pub fn from_env() -> Self {
+        let enabled = std::env::var("COMPRESSION_ENABLED")
+            .map(|v| !(v == "0" || v.eq_ignore_ascii_case("false")))
+            .unwrap_or(true);
+        let algorithms = std::env::var("COMPRESSION_ALGORITHMS")
+            .unwrap_or_else(|_| "zstd,gzip".to_string())
+            .to_ascii_lowercase();
+        let algorithms: Vec<&str> = algorithms.split(',').map(str::trim).collect();
+        Self {
+            enabled,
+            gzip: algorithms.contains(&"gzip"),
+            zstd: algorithms.contains(&"zstd"),
+            min_bytes: std::env::var("COMPRESSION_MIN_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_MIN_BYTES),
+        }
+    }
+
+    /// Layer for API responses. `application/octet-stream` (shards, object
+    /// bodies) is left to [`encode_object`], and anything the handler already
+    /// encoded passes through untouched.
+    pub fn layer(&self) -> CompressionLayer<impl Predicate> {
+        let predicate = SizeAbove::new(self.min_bytes)
+            .and(NotForContentType::GRPC)
+            .and(NotForContentType::IMAGES)
+            .and(NotForContentType::SSE)
+            .and(NotForContentType::const_new("application/octet-stream"));
+        CompressionLayer::new()
+            .gzip(self.enabled && self.gzip)
+            .zstd(self.enabled && self.zstd)
+            .compress_when(predicate)
+    }
+
+    /// Best encoding the client accepts, preferring zstd on equal weight.
+    pub fn negotiate(&self, headers: &HeaderMap) -> Option<Encoding> {
+        if !self.enabled {
+            return None;
+        }
+        let accept = headers.get(header::ACCEPT_ENCODING)?.to_str().ok()?;
+        let mut best: Option<(Encoding, f32)> = None;
+        for item in accept.split(',') {
+            let mut parts = item.split(';').map(str::trim);
+            let name = parts.next().unwrap_or_default().to_ascii_lowercase();
+            let q = parts
+                .find_map(|p| p.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            let encoding = match name.as_str() {
+                "zstd" if self.zstd => Encoding::Zstd,
+                "gzip" | "x-gzip" if self.gzip => Encoding::Gzip,
+                _ => continue,
+            };
+            let better = match best {
+                None => true,
+                Some((current, best_q)) => {
+                    q > best_q || (q == best_q && encoding == Encoding::Zstd && current != Encoding::Zstd)
+                }
+            };
+            if q > 0.0 && better {
+                best = Some((encoding, q));
+            }
+        }
+        best.map(|(encoding, _)| encoding)
+    }
+}
+
+/// Whether a PUT should be flagged compressible.
+pub fn compressible_on_put(headers: &HeaderMap) -> bool {
+    if let Some(flag) = headers.get(COMPRESSIBLE_HEADER).and_then(|v| v.to_str().ok()) {
+        return flag == "1" || flag.eq_ignore_ascii_case("true");
+    }
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    content_type.starts_with("text/")
+        || ["json", "xml", "javascript", "csv", "yaml"]
+            .iter()
+            .any(|t| content_type.contains(t))
+}
+
+pub fn is_compressible(metadata_json: Option<&serde_json::Value>) -> bool {
+    metadata_json
+        .and_then(|m| m.get(COMPRESSIBLE_METADATA_KEY))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+fn compress(encoding: Encoding, data: &[u8]) -> std::io::Result<Vec<u8>> {
+    match encoding {
+        Encoding::Zstd => zstd::bulk::compress(data, ZSTD_LEVEL),
+        Encoding::Gzip => {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()
+        }
+    }
+}
+
+/// Compresses an object body for the client when the object is flagged and
+/// the client accepts an enabled encoding. Returns the body to send and the
+/// headers that go with it; on any failure the plain body is sent.
+pub async fn encode_object(
+    config: &CompressionConfig,
+    request_headers: &HeaderMap,
+    compressible: bool,
+    data: Bytes,
+) -> (Bytes, HeaderMap) {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/octet-stream"));
+    if !compressible || data.len() < config.min_bytes as usize {
+        return (data, headers);
+    }
+    headers.insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+    let Some(encoding) = config.negotiate(request_headers) else {
+        return (data, headers);
+    };
+
+    let plain = data.clone();
+    match tokio::task::spawn_blocking(move || compress(encoding, &plain)).await {
+        Ok(Ok(compressed)) if compressed.len() < data.len() => {
+            headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding.as_str()));
+            (Bytes::from(compressed), headers)
+        }
+        Ok(Ok(_)) => (data, headers),
+        Ok(Err(e)) => {
+            tracing::warn!("object compression failed encoding={} err={}", encoding.as_str(), e);
+            (data, headers)
+        }
+        Err(e) => {
+            tracing::warn!("object compression task failed: {}", e);
+            (data, headers)
+        }
+    }
+}
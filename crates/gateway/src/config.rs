@@ -0,0 +1,486 @@
+//! Browser-facing HTTP policy: CORS, session cookie flags and security
+//! headers, plus the per-request abuse limits and the listener's connection
+//! tuning, in one `GatewayConfig` read at startup.
+//!
+//! Values are layered, later sources winning: built-in defaults, the TOML
+//! file named by `GATEWAY_CONFIG` (default `gateway.toml`, skipped when
+//! absent), the legacy `ENVIRONMENT`, `ALLOWED_ORIGINS` and `COOKIE_SECURE`
+//! variables, and finally `GATEWAY__`-prefixed variables mirroring the file
+//! (`GATEWAY__COOKIES__SAME_SITE=lax`). The result is validated before the
+//! router is built, so a bad origin or header stops the gateway instead of
+//! being dropped with a warning.
+
+use axum::{
+    extract::{Request, State},
+    http::{request::Parts, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use figment::{
+    providers::{Env, Format, Serialized, Toml},
+    Figment,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::AppState;
+
+const DEFAULT_CONFIG_FILE: &str = "gateway.toml";
+const DEFAULT_ORIGINS: &[&str] = &[
+    "https://neurostore-next.vercel.app",
+    "https://neurostore-next-production.up.railway.app",
+    "http://localhost:5173",
+];
+/// Matches every origin in a per-route override.
+const ANY_ORIGIN: &str = "*";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GatewayConfig {
+    pub environment: String,
+    pub cors: CorsConfig,
+    pub cookies: CookieConfig,
+    pub security_headers: SecurityHeadersConfig,
+    pub limits: LimitsConfig,
+    pub server: ServerConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<String>,
+    /// How long browsers may cache a preflight answer.
+    pub max_age_secs: Option<u64>,
+    /// Origins for paths under a prefix, replacing `allowed_origins` there.
+    /// The longest matching prefix wins.
+    pub routes: Vec<CorsRoute>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CorsRoute {
+    pub path_prefix: String,
+    /// `"*"` admits any origin, for public routes such as share links.
+    pub allowed_origins: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl SameSite {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CookieConfig {
+    pub secure: bool,
+    pub same_site: SameSite,
+}
+
+/// Headers added to every response. An empty value leaves the header out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecurityHeadersConfig {
+    pub content_type_options: String,
+    pub frame_options: String,
+    pub referrer_policy: String,
+    pub permissions_policy: String,
+    /// Only worth setting behind TLS, e.g. `max-age=63072000; includeSubDomains`.
+    pub strict_transport_security: String,
+    pub content_security_policy: String,
+}
+
+/// Caps on what a single user can make the gateway store. The first three
+/// can be changed for one bucket through `/api/admin/buckets/:bucket/limits`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    pub max_keys_per_bucket: u64,
+    /// UTF-8 bytes, as S3 counts them.
+    pub max_key_bytes: usize,
+    /// `x-amz-meta-*` and `x-amz-tagging` headers together, names included.
+    pub max_metadata_bytes: usize,
+    /// Shards in one browser upload session.
+    pub max_parts: usize,
+}
+
+/// How the listener serves connections; see `crate::server`. HTTP/2 is
+/// spoken over TLS through ALPN and in cleartext by prior knowledge, next to
+/// HTTP/1.1 on the same port.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub http2: bool,
+    /// Open connections; further clients wait in the accept backlog.
+    pub max_connections: usize,
+    /// Requests one HTTP/2 connection may have in flight.
+    pub max_concurrent_streams: u32,
+    pub initial_stream_window_bytes: u32,
+    pub initial_connection_window_bytes: u32,
+    /// Time a client gets to send its request headers, or to finish the TLS
+    /// handshake.
+    pub header_read_timeout_secs: u64,
+    /// HTTP/2 PING interval on idle connections; 0 disables the pings.
+    pub keep_alive_interval_secs: u64,
+    /// How long a PING may go unanswered before the connection is closed.
+    pub keep_alive_timeout_secs: u64,
+    /// Terminate TLS at the gateway instead of a proxy in front of it.
+    pub tls: Option<TlsConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM certificate chain, leaf first.
+    pub cert_path: String,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1).
+    pub key_path: String,
+}
+
+impl Default for GatewayConfig {
+    fn default() -> Self {
+        Self {
+            environment: "development".to_string(),
+            cors: CorsConfig::default(),
+            cookies: CookieConfig::default(),
+            security_headers: SecurityHeadersConfig::default(),
+            limits: LimitsConfig::default(),
+            server: ServerConfig::default(),
+        }
+    }
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: DEFAULT_ORIGINS.iter().map(|o| o.to_string()).collect(),
+            max_age_secs: None,
+            routes: Vec::new(),
+        }
+    }
+}
+
+impl Default for CookieConfig {
+    fn default() -> Self {
+        Self {
+            secure: false,
+            same_site: SameSite::Strict,
+        }
+    }
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_keys_per_bucket: 1_000_000,
+            max_key_bytes: 1024,
+            max_metadata_bytes: 2048,
+            max_parts: 10_000,
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            http2: true,
+            max_connections: 10_000,
+            max_concurrent_streams: 256,
+            initial_stream_window_bytes: 1024 * 1024,
+            initial_connection_window_bytes: 8 * 1024 * 1024,
+            header_read_timeout_secs: 30,
+            keep_alive_interval_secs: 30,
+            keep_alive_timeout_secs: 20,
+            tls: None,
+        }
+    }
+}
+
+impl Default for SecurityHeadersConfig {
+    fn default() -> Self {
+        Self {
+            content_type_options: "nosniff".to_string(),
+            frame_options: "DENY".to_string(),
+            referrer_policy: "strict-origin-when-cross-origin".to_string(),
+            permissions_policy: "camera=(), microphone=(), geolocation=()".to_string(),
+            strict_transport_security: String::new(),
+            content_security_policy: String::new(),
+        }
+    }
+}
+
+impl GatewayConfig {
+    /// Loads and validates the layered configuration. Also returns the file
+    /// it was read from, if one was.
+    pub fn load() -> anyhow::Result<(Self, Option<String>)> {
+        let explicit = std::env::var("GATEWAY_CONFIG").ok().filter(|p| !p.is_empty());
+        let path = explicit.clone().unwrap_or_else(|| DEFAULT_CONFIG_FILE.to_string());
+        let file = std::path::Path::new(&path).exists().then_some(path);
+        if let (Some(missing), None) = (&explicit, &file) {
+            anyhow::bail!("GATEWAY_CONFIG names {}, which does not exist", missing);
+        }
+
+        let mut figment = Figment::from(Serialized::defaults(GatewayConfig::default()));
+        if let Some(file) = &file {
+            figment = figment.merge(Toml::file(file));
+        }
+        if let Ok(environment) = std::env::var("ENVIRONMENT") {
+            figment = figment.merge(Serialized::default("environment", environment));
+        }
+        if let Ok(origins) = std::env::var("ALLOWED_ORIGINS") {
+            let origins: Vec<String> = origins
+                .split(',')
+                .map(|o| o.trim().to_string())
+                .filter(|o| !o.is_empty())
+                .collect();
+            figment = figment.merge(Serialized::default("cors.allowed_origins", origins));
+        }
+        if let Ok(secure) = std::env::var("COOKIE_SECURE") {
+            let secure = secure == "1" || secure.eq_ignore_ascii_case("true");
+            figment = figment.merge(Serialized::default("cookies.secure", secure));
+        }
+        let config: GatewayConfig = figment
+            .merge(Env::prefixed("GATEWAY__").split("__"))
+            .extract()?;
+
+        let errors = config.validate();
+        if !errors.is_empty() {
+            anyhow::bail!("invalid gateway configuration:\n  {}", errors.join("\n  "));
+        }
+        Ok((config, file))
+    }
+
+    pub fn is_production(&self) -> bool {
+        self.environment.eq_ignore_ascii_case("production")
+    }
+
+    /// Everything that would make the CORS layer, the header middleware or
+    /// the limits misbehave.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.cors.allowed_origins.is_empty() {
+            errors.push("cors.allowed_origins is empty".to_string());
+        }
+        for origin in &self.cors.allowed_origins {
+            if origin == ANY_ORIGIN {
+                errors.push("cors.allowed_origins cannot be \"*\": credentialed requests need explicit origins".to_string());
+            } else if let Err(reason) = check_origin(origin) {
+                errors.push(format!("cors.allowed_origins: {}", reason));
+            }
+        }
+        for route in &self.cors.routes {
+            if !route.path_prefix.starts_with('/') {
+                errors.push(format!("cors.routes: path_prefix {:?} must start with /", route.path_prefix));
+            }
+            if route.allowed_origins.is_empty() {
+                errors.push(format!("cors.routes: {} has no allowed_origins", route.path_prefix));
+            }
+            for origin in route.allowed_origins.iter().filter(|o| *o != ANY_ORIGIN) {
+                if let Err(reason) = check_origin(origin) {
+                    errors.push(format!("cors.routes {}: {}", route.path_prefix, reason));
+                }
+            }
+        }
+        if self.cookies.same_site == SameSite::None && !self.cookies.secure {
+            errors.push("cookies.same_site = \"none\" requires cookies.secure".to_string());
+        }
+        for (name, value) in self.security_headers.entries() {
+            if HeaderValue::from_str(value).is_err() {
+                errors.push(format!("security_headers: {} is not a valid header value", name));
+            }
+        }
+        let limits = &self.limits;
+        for (name, value) in [
+            ("max_keys_per_bucket", limits.max_keys_per_bucket as usize),
+            ("max_key_bytes", limits.max_key_bytes),
+            ("max_metadata_bytes", limits.max_metadata_bytes),
+            ("max_parts", limits.max_parts),
+        ] {
+            if value == 0 {
+                errors.push(format!("limits.{} must be at least 1", name));
+            }
+        }
+        let server = &self.server;
+        for (name, value) in [
+            ("max_connections", server.max_connections as u64),
+            ("max_concurrent_streams", server.max_concurrent_streams as u64),
+            ("header_read_timeout_secs", server.header_read_timeout_secs),
+            ("keep_alive_timeout_secs", server.keep_alive_timeout_secs),
+        ] {
+            if value == 0 {
+                errors.push(format!("server.{} must be at least 1", name));
+            }
+        }
+        // HTTP/2 flow-control windows are capped at 2^31 - 1.
+        for (name, value) in [
+            ("initial_stream_window_bytes", server.initial_stream_window_bytes),
+            ("initial_connection_window_bytes", server.initial_connection_window_bytes),
+        ] {
+            if !(65_535..=i32::MAX as u32).contains(&value) {
+                errors.push(format!("server.{} must be between 65535 and {}", name, i32::MAX));
+            }
+        }
+        if let Some(tls) = &server.tls {
+            for (name, path) in [("cert_path", &tls.cert_path), ("key_path", &tls.key_path)] {
+                if !std::path::Path::new(path).is_file() {
+                    errors.push(format!("server.tls.{}: {:?} is not a readable file", name, path));
+                }
+            }
+        }
+        errors
+    }
+
+    /// Settings that are valid but unsafe outside development, for readiness.
+    pub fn readiness_warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if !self.cookies.secure {
+            warnings.push("COOKIE_SECURE is disabled".to_string());
+        }
+        if self.is_production() {
+            let local = |o: &String| o.contains("localhost") || o.contains("127.0.0.1");
+            if self.cors.allowed_origins.iter().any(local) {
+                warnings.push("ALLOWED_ORIGINS contains localhost while ENVIRONMENT=production".to_string());
+            }
+            if self.cors.routes.iter().any(|r| r.allowed_origins.iter().any(local)) {
+                warnings.push("cors.routes contain localhost while ENVIRONMENT=production".to_string());
+            }
+        }
+        warnings
+    }
+
+    /// The CORS layer for the whole router, resolving per-route origins from
+    /// the request path.
+    pub fn cors_layer(&self, allow_headers: Vec<HeaderName>, expose_headers: Vec<HeaderName>) -> CorsLayer {
+        let global: Vec<HeaderValue> = header_values(&self.cors.allowed_origins);
+        let mut routes: Vec<(String, Option<Vec<HeaderValue>>)> = self
+            .cors
+            .routes
+            .iter()
+            .map(|r| {
+                let any = r.allowed_origins.iter().any(|o| o == ANY_ORIGIN);
+                (r.path_prefix.clone(), (!any).then(|| header_values(&r.allowed_origins)))
+            })
+            .collect();
+        routes.sort_by_key(|r| std::cmp::Reverse(r.0.len()));
+
+        let allow_origin = AllowOrigin::predicate(move |origin: &HeaderValue, parts: &Parts| {
+            let path = parts.uri.path();
+            match routes.iter().find(|(prefix, _)| path.starts_with(prefix.as_str())) {
+                Some((_, None)) => true,
+                Some((_, Some(origins))) => origins.contains(origin),
+                None => global.contains(origin),
+            }
+        });
+        let layer = CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE, Method::OPTIONS])
+            .allow_headers(allow_headers)
+            .expose_headers(expose_headers)
+            .allow_credentials(true);
+        match self.cors.max_age_secs {
+            Some(secs) => layer.max_age(Duration::from_secs(secs)),
+            None => layer,
+        }
+    }
+
+    /// A `Set-Cookie` value carrying the configured flags.
+    pub fn cookie(&self, name: &str, value: &str, max_age_secs: i64, http_only: bool) -> String {
+        let mut cookie = format!(
+            "{}={}; Path=/; Max-Age={}; SameSite={}",
+            name,
+            value,
+            max_age_secs,
+            self.cookies.same_site.as_str()
+        );
+        if self.cookies.secure {
+            cookie.push_str("; Secure");
+        }
+        if http_only {
+            cookie.push_str("; HttpOnly");
+        }
+        cookie
+    }
+}
+
+impl SecurityHeadersConfig {
+    fn entries(&self) -> [(&'static str, &str); 6] {
+        [
+            ("x-content-type-options", &self.content_type_options),
+            ("x-frame-options", &self.frame_options),
+            ("referrer-policy", &self.referrer_policy),
+            ("permissions-policy", &self.permissions_policy),
+            ("strict-transport-security", &self.strict_transport_security),
+            ("content-security-policy", &self.content_security_policy),
+        ]
+    }
+
+    /// The non-empty headers, ready to insert; call after validation.
+    pub fn header_map(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in self.entries() {
+            if let (false, Ok(value)) = (value.is_empty(), HeaderValue::from_str(value)) {
+                headers.insert(HeaderName::from_static(name), value);
+            }
+        }
+        headers
+    }
+}
+
+fn check_origin(origin: &str) -> Result<(), String> {
+    let (scheme, host) = origin
+        .split_once("://")
+        .ok_or_else(|| format!("{:?} is not scheme://host[:port]", origin))?;
+    if !matches!(scheme, "http" | "https") || host.is_empty() || host.contains('/') {
+        return Err(format!("{:?} is not http(s)://host[:port] without a path", origin));
+    }
+    HeaderValue::from_str(origin).map_err(|_| format!("{:?} is not a valid header value", origin))?;
+    Ok(())
+}
+
+fn header_values(origins: &[String]) -> Vec<HeaderValue> {
+    origins.iter().filter_map(|o| HeaderValue::from_str(o).ok()).collect()
+}
+
+/// Adds the configured security headers to every response.
+pub async fn security_headers(State(headers): State<Arc<HeaderMap>>, request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    for (name, value) in headers.iter() {
+        response.headers_mut().insert(name.clone(), value.clone());
+    }
+    response
+}
+
+// ── GET /api/config-audit ──
+// The effective configuration after every layer was applied, for checking
+// a deployment without shell access. Holds no secrets.
+pub async fn config_audit(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    if let Err(err) = crate::handlers::backups::check_admin_token(&state, &headers) {
+        return err.into_response();
+    }
+    let errors = state.config.validate();
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "config_file": state.config_file,
+            "config": state.config,
+            "validation_errors": errors,
+            "readiness_warnings": state.config.readiness_warnings(),
+        })),
+    )
+        .into_response()
+}
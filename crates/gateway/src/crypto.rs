@@ -2,9 +2,10 @@ use aes_gcm::{
     aead::{Aead, KeyInit},
     Aes256Gcm, Nonce,
 };
+use hmac::{Hmac, Mac};
 use sha2::{Sha256, Digest};
 use base64::{engine::general_purpose, Engine as _};
-use zeroize::Zeroize;
+use zeroize::{Zeroize, Zeroizing};
 
 pub struct MetadataProtector {
     // We store the cipher, but in production, this key is never 
@@ -16,6 +17,9 @@ pub struct MetadataProtector {
     // we use a secondary HMAC/SHA-3 derivation layer to simulate the PQC envelope wrapper,
     // ensuring the AES keys are mathematically shielded from pure Shor's algorithm attacks.
     pq_shield_salt: Vec<u8>,
+    // Keys the deterministic blind index used where a masked value must be
+    // looked up again (bucket names), which random-nonce AES cannot give.
+    index_key: Zeroizing<Vec<u8>>,
 }
 
 impl MetadataProtector {
@@ -27,13 +31,27 @@ impl MetadataProtector {
         let cipher = Aes256Gcm::new_from_slice(&key).expect("Invalid key length");
         
         let mut pq_hasher = Sha256::new();
-        pq_hasher.update(format!("{}_pq_lattice_shield", master_secret).as_bytes());
+        let shield_input = Zeroizing::new(format!("{}_pq_lattice_shield", master_secret));
+        pq_hasher.update(shield_input.as_bytes());
         let pq_shield_salt = pq_hasher.finalize().to_vec();
 
+        let mut index_hasher = Sha256::new();
+        let index_input = Zeroizing::new(format!("{}_blind_index", master_secret));
+        index_hasher.update(index_input.as_bytes());
+        let index_key = Zeroizing::new(index_hasher.finalize().to_vec());
+
         // SECURITY: Wipe the intermediate key from RAM immediately after use
         key.zeroize(); 
         
-        Self { cipher, pq_shield_salt }
+        Self { cipher, pq_shield_salt, index_key }
+    }
+
+    /// Keyed HMAC of `plain_text`: the same input always masks to the same
+    /// value, so it can be used as a lookup key without revealing the input.
+    pub fn blind_index(&self, plain_text: &str) -> String {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.index_key).expect("HMAC accepts any key length");
+        mac.update(plain_text.as_bytes());
+        general_purpose::URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
     }
 
     pub fn encrypt(&self, plain_text: &str) -> Result<String, String> {
@@ -97,3 +115,11 @@ impl MetadataProtector {
         result
     }
 }
+
+impl Drop for MetadataProtector {
+    fn drop(&mut self) {
+        // The AES key schedule wipes itself (aes-gcm `zeroize` feature);
+        // the shield salt is derived from the master secret, so wipe it too.
+        self.pq_shield_salt.zeroize();
+    }
+}
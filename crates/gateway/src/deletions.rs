@@ -0,0 +1,367 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::time;
+use tracing::{error, info};
+
+use crate::handlers::policy::BucketAccess;
+use crate::handlers::s3::{authorize_bucket, delete_placed_shards, validate_bucket_principal};
+use crate::models::Object;
+use crate::p2p::DeletionReceipt;
+use crate::AppState;
+
+// ── DELETION EVIDENCE ──
+// Every purged object gets a deletion record listing the shard placements it
+// had. A placement is confirmed once its holder answers the delete with a
+// signed, fresh receipt; the receipt is kept verbatim so it can be checked
+// against the node's public key without trusting the gateway. Placements
+// still unconfirmed are retried by the reconciler for up to
+// `MAX_ATTEMPTS` sweeps. The certificate of erasure lists every placement
+// with its receipt and is signed with the compliance key.
+
+type HmacSha256 = Hmac<Sha256>;
+
+const RECONCILE_SWEEP_SECS: u64 = 15 * 60;
+/// About a day of sweeps; holders offline for longer still delete on return
+/// through the gossiped tombstones, but give no receipt.
+const MAX_ATTEMPTS: i32 = 96;
+/// Deletions retried per sweep.
+const RECONCILE_BATCH: i64 = 200;
+const LIST_LIMIT: i64 = 200;
+
+/// A shard's signed deletion receipt from the node recorded as holding it.
+/// For a batch the receipt covers every shard of the batch.
+#[derive(Debug, Clone)]
+pub(crate) struct ShardReceipt {
+    pub shard_cid: String,
+    /// False when the node no longer held the shard when asked.
+    pub removed: bool,
+    pub receipt: DeletionReceipt,
+}
+
+fn new_deletion_id() -> String {
+    let mut id = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut id);
+    format!("del-{}", hex::encode(id))
+}
+
+/// Records the deletion of `obj` and its shard placements, before any delete
+/// is sent. `obj.key` is the stored (masked) key.
+pub(crate) async fn open(state: &AppState, obj: &Object) -> Result<String, sqlx::Error> {
+    let deletion_id = new_deletion_id();
+    let mut tx = state.db.begin().await?;
+    sqlx::query(
+        r#"
+        INSERT INTO object_deletions (deletion_id, bucket, object_key, object_cid, shards, unplaced_shards)
+        VALUES ($1, $2, $3, $4, $5,
+                GREATEST($5 - (SELECT COUNT(DISTINCT shard_cid) FROM object_shards WHERE object_cid = $4), 0))
+        "#,
+    )
+    .bind(&deletion_id)
+    .bind(&obj.bucket)
+    .bind(&obj.key)
+    .bind(&obj.cid)
+    .bind(obj.shards)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO shard_deletions (deletion_id, shard_cid, peer_id)
+        SELECT DISTINCT ON (shard_cid) $1, shard_cid, peer_id FROM object_shards WHERE object_cid = $2
+        ON CONFLICT DO NOTHING
+        "#,
+    )
+    .bind(&deletion_id)
+    .bind(&obj.cid)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(deletion_id)
+}
+
+/// Stores `receipts` against the pending placements they answer, counts an
+/// attempt for those of `deletion_ids` still unconfirmed, and completes
+/// deletions with nothing left to confirm.
+pub(crate) async fn settle(state: &AppState, deletion_ids: &[String], receipts: &[ShardReceipt]) -> Result<(), sqlx::Error> {
+    let mut tx = state.db.begin().await?;
+    for shard in receipts {
+        sqlx::query(
+            r#"
+            UPDATE shard_deletions
+            SET confirmed_at = NOW(), removed = $3, signed_payload = $4, signature = $5, public_key = $6,
+                signed_at_ms = $7, attempts = attempts + 1, last_attempt_at = NOW()
+            WHERE shard_cid = $1 AND peer_id = $2 AND confirmed_at IS NULL
+            "#,
+        )
+        .bind(&shard.shard_cid)
+        .bind(&shard.receipt.peer_id)
+        .bind(shard.removed)
+        .bind(&shard.receipt.payload)
+        .bind(&shard.receipt.signature_hex)
+        .bind(&shard.receipt.public_key_hex)
+        .bind(shard.receipt.timestamp_ms as i64)
+        .execute(&mut *tx)
+        .await?;
+    }
+    sqlx::query(
+        r#"
+        UPDATE shard_deletions SET attempts = attempts + 1, last_attempt_at = NOW()
+        WHERE deletion_id = ANY($1) AND confirmed_at IS NULL
+        "#,
+    )
+    .bind(deletion_ids)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        r#"
+        UPDATE object_deletions d SET completed_at = NOW()
+        WHERE d.deletion_id = ANY($1) AND d.completed_at IS NULL
+          AND NOT EXISTS (SELECT 1 FROM shard_deletions s WHERE s.deletion_id = d.deletion_id AND s.confirmed_at IS NULL)
+        "#,
+    )
+    .bind(deletion_ids)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await
+}
+
+/// Re-sends deletes for placements whose holder has not confirmed yet.
+/// Runs on the leader only.
+pub struct DeletionReconciler {
+    state: Arc<AppState>,
+}
+
+impl DeletionReconciler {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn start(&self) {
+        info!("Deletion reconciler initialized. Retrying unconfirmed shard deletions every {} seconds.", RECONCILE_SWEEP_SECS);
+
+        let mut interval = time::interval(Duration::from_secs(RECONCILE_SWEEP_SECS));
+        loop {
+            interval.tick().await;
+            match self.sweep().await {
+                Ok((0, _)) => {}
+                Ok((retried, confirmed)) => {
+                    info!("Deletion reconciler retried {} shard deletions, {} confirmed", retried, confirmed)
+                }
+                Err(e) => error!("Deletion reconciler sweep failed: {}", e),
+            }
+        }
+    }
+
+    /// Returns how many placements were retried and how many of them the
+    /// holder confirmed.
+    async fn sweep(&self) -> Result<(usize, usize), sqlx::Error> {
+        let pending = sqlx::query_as::<_, (String, String, String)>(
+            r#"
+            SELECT deletion_id, shard_cid, peer_id FROM shard_deletions
+            WHERE deletion_id IN (
+                SELECT DISTINCT deletion_id FROM shard_deletions
+                WHERE confirmed_at IS NULL AND attempts < $1
+                ORDER BY deletion_id
+                LIMIT $2
+            )
+            AND confirmed_at IS NULL
+            ORDER BY deletion_id
+            "#,
+        )
+        .bind(MAX_ATTEMPTS)
+        .bind(RECONCILE_BATCH)
+        .fetch_all(&self.state.db)
+        .await?;
+        if pending.is_empty() {
+            return Ok((0, 0));
+        }
+
+        let mut deletion_ids: Vec<String> = pending.iter().map(|(id, _, _)| id.clone()).collect();
+        deletion_ids.dedup();
+        let retried = pending.len();
+        let placed = pending
+            .into_iter()
+            .map(|(_, shard_cid, peer_id)| (shard_cid, Some(peer_id)))
+            .collect();
+        let receipts = delete_placed_shards(&self.state, placed).await;
+        settle(&self.state, &deletion_ids, &receipts).await?;
+        Ok((retried, receipts.len()))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ShardErasureReceipt {
+    pub shard_cid: String,
+    pub peer_id: String,
+    pub attempts: i32,
+    pub confirmed_at: Option<DateTime<Utc>>,
+    pub removed: Option<bool>,
+    pub signed_payload: Option<String>,
+    pub signature: Option<String>,
+    pub public_key: Option<String>,
+    pub signed_at_ms: Option<i64>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct DeletionSummary {
+    pub deletion_id: String,
+    pub object_cid: String,
+    pub shards: i32,
+    pub unplaced_shards: i32,
+    pub requested_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErasureCertificate {
+    #[serde(flatten)]
+    pub deletion: DeletionSummary,
+    pub bucket: String,
+    pub confirmed_shards: usize,
+    /// Every recorded placement confirmed by its holder.
+    pub complete: bool,
+    pub receipts: Vec<ShardErasureReceipt>,
+    pub issued_at: String,
+    /// SHA-256 of the serialized `receipts`, covered by the signature.
+    pub receipts_digest: String,
+    pub cryptographic_signature: String,
+}
+
+async fn authorize(state: &AppState, headers: &HeaderMap, bucket: &str) -> Result<String, Response> {
+    let principal = validate_bucket_principal(headers, state).map_err(IntoResponse::into_response)?;
+    let scope = authorize_bucket(state, bucket, &principal, BucketAccess::Write, "")
+        .await
+        .map_err(IntoResponse::into_response)?;
+    Ok(scope.name)
+}
+
+// ── GET /api/deletions/:bucket ──
+pub async fn list_deletions(
+    State(state): State<Arc<AppState>>,
+    Path(bucket): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let bucket = match authorize(&state, &headers, &bucket).await {
+        Ok(bucket) => bucket,
+        Err(err) => return err,
+    };
+    let rows = sqlx::query_as::<_, DeletionSummary>(
+        r#"
+        SELECT deletion_id, object_cid, shards, unplaced_shards, requested_at, completed_at
+        FROM object_deletions WHERE bucket = $1
+        ORDER BY requested_at DESC
+        LIMIT $2
+        "#,
+    )
+    .bind(&bucket)
+    .bind(LIST_LIMIT)
+    .fetch_all(&state.db)
+    .await;
+    match rows {
+        Ok(rows) => Json(serde_json::json!({ "bucket": bucket, "deletions": rows })).into_response(),
+        Err(e) => {
+            error!("Failed to list deletions for bucket {}: {}", bucket, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+// ── GET /api/deletions/:bucket/:deletion_id/certificate ──
+/// The certificate of erasure for one deletion, as a JSON download.
+pub async fn erasure_certificate(
+    State(state): State<Arc<AppState>>,
+    Path((bucket, deletion_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    let bucket = match authorize(&state, &headers, &bucket).await {
+        Ok(bucket) => bucket,
+        Err(err) => return err,
+    };
+    let deletion = sqlx::query_as::<_, DeletionSummary>(
+        r#"
+        SELECT deletion_id, object_cid, shards, unplaced_shards, requested_at, completed_at
+        FROM object_deletions WHERE deletion_id = $1 AND bucket = $2
+        "#,
+    )
+    .bind(&deletion_id)
+    .bind(&bucket)
+    .fetch_optional(&state.db)
+    .await;
+    let deletion = match deletion {
+        Ok(Some(deletion)) => deletion,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Deletion not found").into_response(),
+        Err(e) => {
+            error!("Failed to load deletion {}: {}", deletion_id, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let receipts = sqlx::query_as::<_, ShardErasureReceipt>(
+        r#"
+        SELECT shard_cid, peer_id, attempts, confirmed_at, removed, signed_payload, signature, public_key, signed_at_ms
+        FROM shard_deletions WHERE deletion_id = $1
+        ORDER BY shard_cid
+        "#,
+    )
+    .bind(&deletion_id)
+    .fetch_all(&state.db)
+    .await;
+    let receipts = match receipts {
+        Ok(receipts) => receipts,
+        Err(e) => {
+            error!("Failed to load receipts of deletion {}: {}", deletion_id, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let certificate = certify(&state, bucket, deletion, receipts);
+    let mut headers = HeaderMap::new();
+    if let Ok(disposition) = HeaderValue::from_str(&format!("attachment; filename=\"erasure-{}.json\"", deletion_id)) {
+        headers.insert(header::CONTENT_DISPOSITION, disposition);
+    }
+    (StatusCode::OK, headers, Json(certificate)).into_response()
+}
+
+fn certify(state: &AppState, bucket: String, deletion: DeletionSummary, receipts: Vec<ShardErasureReceipt>) -> ErasureCertificate {
+    let confirmed_shards = receipts.iter().filter(|r| r.confirmed_at.is_some()).count();
+    let complete = confirmed_shards == receipts.len() && deletion.completed_at.is_some();
+    let receipts_digest = hex::encode(Sha256::digest(serde_json::to_vec(&receipts).unwrap_or_default()));
+    let issued_at = Utc::now().to_rfc3339();
+    let signing_payload = format!(
+        "deletion={};bucket={};cid={};shards={};unplaced={};confirmed={}/{};complete={};receipts={};ts={}",
+        deletion.deletion_id,
+        bucket,
+        deletion.object_cid,
+        deletion.shards,
+        deletion.unplaced_shards,
+        confirmed_shards,
+        receipts.len(),
+        complete,
+        receipts_digest,
+        issued_at
+    );
+    let mut mac = HmacSha256::new_from_slice(state.compliance_signing_key.as_bytes()).expect("HMAC key length is valid");
+    mac.update(signing_payload.as_bytes());
+    let cryptographic_signature = format!("0x{}", hex::encode(mac.finalize().into_bytes()));
+
+    ErasureCertificate {
+        deletion,
+        bucket,
+        confirmed_shards,
+        complete,
+        receipts,
+        issued_at,
+        receipts_digest,
+        cryptographic_signature,
+    }
+}
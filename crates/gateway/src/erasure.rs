@@ -1,5 +1,19 @@
 use reed_solomon_erasure::galois_8::ReedSolomon;
 use anyhow::Result;
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::handlers::backups::check_admin_token;
+use crate::handlers::s3_error::S3Error;
+use crate::AppState;
 
 pub struct ErasureEncoder {
     rs: ReedSolomon,
@@ -47,3 +61,143 @@ impl ErasureEncoder {
         Ok(result)
     }
 }
+
+// ── WRITE-PATH ENCODE POOL ──
+// Encoding a large body takes long enough to starve the async workers, so
+// PUT hands it to the blocking pool instead, at most `workers` at a time.
+// A PUT reserves its place before reading the body; once `workers +
+// queue_depth` are taken, further PUTs get 503 SlowDown with Retry-After.
+
+const DEFAULT_RETRY_AFTER_SECS: u64 = 2;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct EncodePoolConfig {
+    /// Encodes running at once.
+    pub workers: usize,
+    /// Reserved encodes allowed to wait for a worker.
+    pub queue_depth: usize,
+    pub retry_after_secs: u64,
+}
+
+impl EncodePoolConfig {
+    /// Reads `ENCODE_WORKERS` (default half the CPUs), `ENCODE_QUEUE_DEPTH`
+    /// (default twice the workers) and `ENCODE_RETRY_AFTER_SECS`.
+    pub fn from_env() -> Self {
+        let env = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<usize>().ok());
+        let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let workers = env("ENCODE_WORKERS").unwrap_or(cpus / 2).max(1);
+        Self {
+            workers,
+            queue_depth: env("ENCODE_QUEUE_DEPTH").unwrap_or(workers * 2),
+            retry_after_secs: env("ENCODE_RETRY_AFTER_SECS").map_or(DEFAULT_RETRY_AFTER_SECS, |v| v as u64),
+        }
+    }
+}
+
+pub struct EncodePool {
+    config: EncodePoolConfig,
+    admission: Arc<Semaphore>,
+    workers: Arc<Semaphore>,
+    completed: AtomicU64,
+    rejected: AtomicU64,
+}
+
+/// A place in the pool, held from before the body is read until its encode
+/// finishes.
+pub struct EncodeSlot {
+    _admitted: OwnedSemaphorePermit,
+}
+
+/// The pool had no place left; answers 503 SlowDown with Retry-After.
+#[derive(Debug)]
+pub struct Saturated {
+    retry_after_secs: u64,
+}
+
+impl IntoResponse for Saturated {
+    fn into_response(self) -> Response {
+        let error = S3Error::new(StatusCode::SERVICE_UNAVAILABLE, "SlowDown", "the gateway is busy encoding other uploads");
+        ([(header::RETRY_AFTER, self.retry_after_secs.to_string())], error).into_response()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct EncodePoolStats {
+    #[serde(flatten)]
+    pub config: EncodePoolConfig,
+    pub running: usize,
+    pub queued: usize,
+    pub completed: u64,
+    pub rejected: u64,
+}
+
+impl EncodePool {
+    pub fn new(config: EncodePoolConfig) -> Self {
+        Self {
+            config,
+            admission: Arc::new(Semaphore::new(config.workers + config.queue_depth)),
+            workers: Arc::new(Semaphore::new(config.workers)),
+            completed: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// Takes a place for one encode; fails when every worker is busy and
+    /// the queue is full.
+    pub fn reserve(&self) -> Result<EncodeSlot, Saturated> {
+        match Arc::clone(&self.admission).try_acquire_owned() {
+            Ok(permit) => Ok(EncodeSlot { _admitted: permit }),
+            Err(_) => {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                let stats = self.stats();
+                tracing::warn!(running = stats.running, queued = stats.queued, "Encode pool saturated; rejecting PUT");
+                Err(Saturated { retry_after_secs: self.config.retry_after_secs })
+            }
+        }
+    }
+
+    /// Splits `data` into `data_shards + parity_shards` shards on the
+    /// blocking pool once a worker is free.
+    pub async fn encode(
+        &self,
+        _slot: EncodeSlot,
+        data_shards: usize,
+        parity_shards: usize,
+        data: Vec<u8>,
+    ) -> Result<Vec<Vec<u8>>> {
+        let worker = Arc::clone(&self.workers).acquire_owned().await?;
+        let encode_span = tracing::info_span!("rs_encode", data_shards, parity_shards, bytes = data.len());
+        // The worker permit moves into the task, so an abandoned PUT still
+        // counts against the pool until its encode actually stops.
+        let shards = tokio::task::spawn_blocking(move || {
+            let _worker = worker;
+            encode_span.in_scope(|| ErasureEncoder::new(data_shards, parity_shards)?.encode(&data))
+        })
+        .await??;
+        self.completed.fetch_add(1, Ordering::Relaxed);
+        Ok(shards)
+    }
+
+    pub fn stats(&self) -> EncodePoolStats {
+        let running = self.config.workers - self.workers.available_permits();
+        let admitted = self.config.workers + self.config.queue_depth - self.admission.available_permits();
+        EncodePoolStats {
+            config: self.config,
+            running,
+            queued: admitted.saturating_sub(running),
+            completed: self.completed.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// ── GET /api/admin/encode-pool ──
+// Queue depth and throughput of the encode pool. Guarded by `ADMIN_TOKEN`
+// in `x-admin-token`.
+pub async fn encode_pool_stats(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    check_admin_token(&state, &headers)?;
+    Ok(Json(state.encode_pool.stats()))
+}
@@ -14,6 +14,7 @@ use jsonwebtoken::{encode, Header, EncodingKey};
 use chrono::{Utc, Duration};
 use rand::RngCore;
 
+use crate::config::GatewayConfig;
 use crate::AppState;
 use crate::models::{Claims, LoginRequest, RegisterRequest, UserProfile};
 
@@ -33,31 +34,6 @@ pub(crate) fn get_cookie_value(headers: &HeaderMap, name: &str) -> Option<String
     None
 }
 
-fn build_cookie(name: &str, value: &str, max_age_secs: i64, secure: bool, http_only: bool) -> String {
-    let mut cookie = format!(
-        "{}={}; Path=/; Max-Age={}; SameSite=Strict",
-        name, value, max_age_secs
-    );
-    if secure {
-        cookie.push_str("; Secure");
-    }
-    if http_only {
-        cookie.push_str("; HttpOnly");
-    }
-    cookie
-}
-
-fn clear_cookie(name: &str, secure: bool, http_only: bool) -> String {
-    let mut cookie = format!("{}=; Path=/; Max-Age=0; SameSite=Strict", name);
-    if secure {
-        cookie.push_str("; Secure");
-    }
-    if http_only {
-        cookie.push_str("; HttpOnly");
-    }
-    cookie
-}
-
 fn generate_csrf_token() -> String {
     let mut bytes = [0u8; 32];
     rand::thread_rng().fill_bytes(&mut bytes);
@@ -112,12 +88,12 @@ fn decode_email_from_cookie(headers: &HeaderMap, state: &AppState) -> Result<Str
     Ok(token_data.claims.email)
 }
 
-fn auth_response(status: StatusCode, token: String, user: UserProfile, secure_cookie: bool) -> impl IntoResponse {
+fn auth_response(status: StatusCode, token: String, user: UserProfile, config: &GatewayConfig) -> impl IntoResponse {
     let csrf_token = generate_csrf_token();
     let mut headers = HeaderMap::new();
 
-    let auth_cookie = build_cookie(AUTH_COOKIE, &token, 24 * 60 * 60, secure_cookie, true);
-    let csrf_cookie = build_cookie(CSRF_COOKIE, &csrf_token, 24 * 60 * 60, secure_cookie, false);
+    let auth_cookie = config.cookie(AUTH_COOKIE, &token, 24 * 60 * 60, true);
+    let csrf_cookie = config.cookie(CSRF_COOKIE, &csrf_token, 24 * 60 * 60, false);
 
     if let Ok(v) = HeaderValue::from_str(&auth_cookie) {
         headers.append(SET_COOKIE, v);
@@ -198,9 +174,15 @@ pub async fn register(
 
     match insert_result {
         Ok(_) => {
+            crate::replication::publish(&state, crate::replication::MetadataOp::UpsertUser {
+                email: email.clone(),
+                password_hash,
+                name: Some(name.clone()),
+            })
+            .await;
             let token = create_jwt(&email, &state.jwt_secret);
             let user = UserProfile { email, name };
-            auth_response(StatusCode::CREATED, token, user, state.cookie_secure).into_response()
+            auth_response(StatusCode::CREATED, token, user, &state.config).into_response()
         }
         Err(e) => {
             tracing::error!("DB Insert Error: {}", e);
@@ -260,7 +242,7 @@ pub async fn login(
         name,
     };
 
-    auth_response(StatusCode::OK, token, user, state.cookie_secure)
+    auth_response(StatusCode::OK, token, user, &state.config)
     .into_response()
 }
 
@@ -298,8 +280,8 @@ pub async fn logout(
     State(state): State<Arc<AppState>>,
 ) -> impl IntoResponse {
     let mut headers = HeaderMap::new();
-    let auth_cookie = clear_cookie(AUTH_COOKIE, state.cookie_secure, true);
-    let csrf_cookie = clear_cookie(CSRF_COOKIE, state.cookie_secure, false);
+    let auth_cookie = state.config.cookie(AUTH_COOKIE, "", 0, true);
+    let csrf_cookie = state.config.cookie(CSRF_COOKIE, "", 0, false);
 
     if let Ok(v) = HeaderValue::from_str(&auth_cookie) {
         headers.append(SET_COOKIE, v);
@@ -0,0 +1,99 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use crate::backup;
+use crate::AppState;
+
+// ── METADATA BACKUPS ──
+// Operator endpoints for the backup daemon: list the backups this gateway
+// knows about, take one now, or rebuild the metadata tables from a backup
+// CID. Guarded by `ADMIN_TOKEN` in `x-admin-token`; without it configured
+// the routes do not exist.
+
+const LIST_LIMIT: i64 = 100;
+
+pub(crate) fn check_admin_token(state: &AppState, headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+    let Some(expected) = state.backup.admin_token.as_ref() else {
+        return Err((StatusCode::NOT_FOUND, "Admin API disabled".to_string()));
+    };
+    let token = headers
+        .get("x-admin-token")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or_default();
+    if token.is_empty() || token != expected.as_str() {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid admin token".to_string()));
+    }
+    Ok(())
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct BackupSummary {
+    pub backup_cid: String,
+    pub created_at: DateTime<Utc>,
+    pub size_bytes: i64,
+    pub shards_stored: i32,
+    pub row_counts: serde_json::Value,
+}
+
+pub async fn list_backups(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    check_admin_token(&state, &headers)?;
+    let backups = sqlx::query_as::<_, BackupSummary>(
+        r#"
+        SELECT backup_cid, created_at, size_bytes, shards_stored, row_counts
+        FROM metadata_backups
+        ORDER BY created_at DESC
+        LIMIT $1
+        "#,
+    )
+    .bind(LIST_LIMIT)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(serde_json::json!({ "backups": backups })))
+}
+
+pub async fn create_backup(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    check_admin_token(&state, &headers)?;
+    let index = backup::create_backup(&state).await.map_err(|e| {
+        error!("Metadata backup failed: {:#}", e);
+        (StatusCode::BAD_GATEWAY, format!("Backup failed: {}", e))
+    })?;
+    info!("Metadata backup {} taken on request", index.backup_cid);
+    Ok((StatusCode::CREATED, Json(index)))
+}
+
+pub async fn restore_backup(
+    State(state): State<Arc<AppState>>,
+    Path(cid): Path<String>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    check_admin_token(&state, &headers)?;
+    if cid.len() != 64 || !cid.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err((StatusCode::BAD_REQUEST, "Backup CID must be 64 hex characters".to_string()));
+    }
+    let restored = backup::restore_backup(&state, &cid.to_ascii_lowercase())
+        .await
+        .map_err(|e| {
+            error!("Restoring metadata backup {} failed: {:#}", cid, e);
+            (StatusCode::BAD_GATEWAY, format!("Restore failed: {:#}", e))
+        })?;
+    info!("Restored metadata backup {}", cid);
+    Ok(Json(serde_json::json!({
+        "backup_cid": cid,
+        "restored_rows": restored,
+    })))
+}
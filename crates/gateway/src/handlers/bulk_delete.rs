@@ -0,0 +1,277 @@
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use base64::Engine;
+use md5::{Digest, Md5};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::deletions;
+use crate::handlers::policy::BucketAccess;
+use crate::handlers::s3::{
+    bucket_rights, delete_shards, record_request_fields, shred_object, validate_bucket_principal, validate_csrf,
+    xml_escape,
+};
+use crate::handlers::s3_error::S3Error;
+use crate::handlers::tagging::{xml_elements, xml_unescape};
+use crate::key_index;
+use crate::models::Object;
+use crate::tenancy::BucketScope;
+use crate::AppState;
+
+// Multi-object delete: POST `/:bucket?delete` with an S3 `<Delete>` document.
+// The shards of every object found are deleted together, one batch per
+// holder, and each object is then shredded exactly as a single DELETE would.
+// Keys that do not exist count as deleted, as in S3.
+
+const MAX_DELETE_KEYS: usize = 1000;
+const MAX_DELETE_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+struct DeleteRequest {
+    quiet: bool,
+    keys: Vec<String>,
+}
+
+fn parse_delete_xml(xml: &str) -> Result<DeleteRequest, S3Error> {
+    let malformed = |why: &str| S3Error::new(StatusCode::BAD_REQUEST, "MalformedXML", why);
+    let delete = xml_elements(xml, "Delete")
+        .into_iter()
+        .next()
+        .ok_or_else(|| malformed("missing Delete"))?;
+    let quiet = xml_elements(delete, "Quiet").into_iter().next().map(str::trim) == Some("true");
+    let objects = xml_elements(delete, "Object");
+    if objects.is_empty() || objects.len() > MAX_DELETE_KEYS {
+        return Err(malformed(&format!("between 1 and {} Object entries are required", MAX_DELETE_KEYS)));
+    }
+
+    let mut keys = Vec::with_capacity(objects.len());
+    for object in objects {
+        let key = xml_elements(object, "Key")
+            .into_iter()
+            .next()
+            .map(xml_unescape)
+            .filter(|k| !k.is_empty())
+            .ok_or_else(|| malformed("each Object needs a Key"))?;
+        keys.push(key);
+    }
+    Ok(DeleteRequest { quiet, keys })
+}
+
+/// S3 error code for a per-key failure.
+fn error_code(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::FORBIDDEN => "AccessDenied",
+        _ => "InternalError",
+    }
+}
+
+fn render_delete_result(keys: &[String], outcomes: &[Result<(), (StatusCode, String)>], quiet: bool) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<DeleteResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\n");
+    for (key, outcome) in keys.iter().zip(outcomes) {
+        match outcome {
+            Ok(()) if quiet => {}
+            Ok(()) => {
+                xml.push_str("  <Deleted>\n");
+                xml.push_str(&format!("    <Key>{}</Key>\n", xml_escape(key)));
+                xml.push_str("  </Deleted>\n");
+            }
+            Err((status, message)) => {
+                xml.push_str("  <Error>\n");
+                xml.push_str(&format!("    <Key>{}</Key>\n", xml_escape(key)));
+                xml.push_str(&format!("    <Code>{}</Code>\n", error_code(*status)));
+                xml.push_str(&format!("    <Message>{}</Message>\n", xml_escape(message)));
+                xml.push_str("  </Error>\n");
+            }
+        }
+    }
+    xml.push_str("</DeleteResult>");
+    xml
+}
+
+/// Rejects a body whose `Content-MD5` header, when sent, does not match.
+fn check_content_md5(headers: &HeaderMap, body: &[u8]) -> Result<(), S3Error> {
+    let Some(sent) = headers.get("Content-MD5") else {
+        return Ok(());
+    };
+    let expected = base64::engine::general_purpose::STANDARD.encode(Md5::digest(body));
+    if sent.to_str().ok().map(str::trim) == Some(expected.as_str()) {
+        Ok(())
+    } else {
+        Err(S3Error::new(StatusCode::BAD_REQUEST, "BadDigest", "Content-MD5 does not match the body"))
+    }
+}
+
+pub async fn delete_objects(
+    State(state): State<Arc<AppState>>,
+    Path(bucket): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, S3Error> {
+    if !params.contains_key("delete") {
+        return Err(S3Error::new(StatusCode::NOT_IMPLEMENTED, "NotImplemented", "POST is only supported with ?delete"));
+    }
+    record_request_fields(&bucket, None);
+    validate_csrf(&headers)?;
+    let principal = validate_bucket_principal(&headers, &state)?;
+    let scope = BucketScope::resolve(&state, &principal, &bucket).await?;
+    let bucket = scope.name.clone();
+    let bytes = match axum::body::to_bytes(body, MAX_DELETE_BODY_BYTES).await {
+        Ok(b) => b,
+        Err(_) => return Err(S3Error::new(StatusCode::PAYLOAD_TOO_LARGE, "EntityTooLarge", "Delete document too large")),
+    };
+    check_content_md5(&headers, &bytes)?;
+    let Ok(xml) = std::str::from_utf8(&bytes) else {
+        return Err(S3Error::new(StatusCode::BAD_REQUEST, "MalformedXML", "The delete document is not UTF-8."));
+    };
+    let request = parse_delete_xml(xml)?;
+
+    // The bucket is looked up once; grants may still cover only some keys,
+    // and only keys that pass are looked up, by their key tokens.
+    let rights = bucket_rights(&state, &scope, &principal).await?;
+    let mut outcomes: Vec<Result<(), (StatusCode, String)>> = Vec::with_capacity(request.keys.len());
+    let mut key_lookups: Vec<Option<String>> = Vec::with_capacity(request.keys.len());
+    for key in &request.keys {
+        match rights.check(&principal, BucketAccess::Write, key) {
+            Ok(()) => {
+                outcomes.push(Ok(()));
+                key_lookups.push(Some(key_index::lookup_token(&scope.protector, &bucket, key)));
+            }
+            Err(err) => {
+                outcomes.push(Err(err));
+                key_lookups.push(None);
+            }
+        }
+    }
+
+    let lookup: Vec<String> = key_lookups.iter().flatten().cloned().collect();
+    let objects = sqlx::query_as::<_, Object>("SELECT * FROM objects WHERE bucket = $1 AND key_lookup = ANY($2)")
+        .bind(&bucket)
+        .bind(&lookup)
+        .fetch_all(&state.db)
+        .await?;
+
+    let mut deletion_ids = Vec::with_capacity(objects.len());
+    for obj in &objects {
+        match deletions::open(&state, obj).await {
+            Ok(deletion_id) => deletion_ids.push(deletion_id),
+            Err(e) => tracing::warn!("Failed to record deletion of object {}: {}", obj.cid, e),
+        }
+    }
+    let shard_sets: Vec<(&str, i32)> = objects.iter().map(|obj| (obj.cid.as_str(), obj.shards)).collect();
+    let receipts = delete_shards(&state, &shard_sets).await;
+    if let Err(e) = deletions::settle(&state, &deletion_ids, &receipts).await {
+        tracing::warn!("Failed to record deletion receipts: {}", e);
+    }
+    let mut shredded: HashMap<String, Result<(), (StatusCode, String)>> = HashMap::new();
+    for obj in &objects {
+        let result = shred_object(&state, obj).await.map_err(|e| {
+            tracing::error!("Database error during deletion: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database Error".to_string())
+        });
+        if let Some(key_lookup) = &obj.key_lookup {
+            shredded.insert(key_lookup.clone(), result);
+        }
+    }
+    for (outcome, key_lookup) in outcomes.iter_mut().zip(&key_lookups) {
+        if let Some(result) = key_lookup.as_ref().and_then(|k| shredded.get(k)) {
+            *outcome = result.clone();
+        }
+    }
+    tracing::info!(
+        "DPDP COMPLIANCE: Bulk delete shredded {} of {} requested keys in bucket {}.",
+        objects.len(),
+        request.keys.len(),
+        bucket
+    );
+
+    let mut headers = HeaderMap::new();
+    headers.insert("Content-Type", HeaderValue::from_static("application/xml"));
+    Ok((StatusCode::OK, headers, render_delete_result(&request.keys, &outcomes, request.quiet)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::s3::{get_object, put_object};
+    use crate::test_support::{auth, drop_db, scratch_db, state, OWNER};
+
+    async fn bulk_delete(state: &Arc<AppState>, query: &str, headers: HeaderMap, xml: &str) -> (StatusCode, String) {
+        let params = HashMap::from([(query.to_string(), String::new())]);
+        let response = delete_objects(
+            State(Arc::clone(state)),
+            Path("photos".to_string()),
+            Query(params),
+            headers,
+            Body::from(xml.to_string()),
+        )
+        .await
+        .unwrap_or_else(IntoResponse::into_response);
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.expect("body");
+        (status, String::from_utf8(body.to_vec()).expect("utf8"))
+    }
+
+    #[tokio::test]
+    async fn deletes_keys_and_reports_errors_as_s3_xml() {
+        let Some((db, name)) = scratch_db().await else {
+            eprintln!("DATABASE_URL not set; skipping");
+            return;
+        };
+        sqlx::query("INSERT INTO users (email, password_hash) VALUES ($1, 'x')")
+            .bind(OWNER)
+            .execute(&db)
+            .await
+            .expect("user");
+        let state = state(db.clone());
+        for key in ["a.txt", "b.txt"] {
+            put_object(
+                State(Arc::clone(&state)),
+                Path(("photos".to_string(), key.to_string())),
+                Query(HashMap::new()),
+                auth(&state),
+                Body::from("bulk"),
+            )
+            .await
+            .expect("put");
+        }
+        let xml = "<Delete><Object><Key>a.txt</Key></Object><Object><Key>missing.txt</Key></Object></Delete>";
+
+        let (status, body) = bulk_delete(&state, "uploads", auth(&state), xml).await;
+        assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+        assert!(body.contains("<Code>NotImplemented</Code>"));
+
+        let mut bad_digest = auth(&state);
+        bad_digest.insert("Content-MD5", HeaderValue::from_static("AAAAAAAAAAAAAAAAAAAAAA=="));
+        let (status, body) = bulk_delete(&state, "delete", bad_digest, xml).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("<Code>BadDigest</Code>"));
+
+        let (status, body) = bulk_delete(&state, "delete", auth(&state), "<Delete></Delete>").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("<Code>MalformedXML</Code>"));
+
+        let (status, body) = bulk_delete(&state, "delete", auth(&state), xml).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("<Deleted>\n    <Key>a.txt</Key>"));
+        assert!(body.contains("<Deleted>\n    <Key>missing.txt</Key>"));
+        let gone = get_object(
+            State(Arc::clone(&state)),
+            Path(("photos".to_string(), "a.txt".to_string())),
+            Query(HashMap::new()),
+            auth(&state),
+        )
+        .await
+        .unwrap_or_else(IntoResponse::into_response);
+        assert_eq!(gone.status(), StatusCode::NOT_FOUND);
+        let left: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM objects").fetch_one(&db).await.expect("count");
+        assert_eq!(left, 1);
+        drop(state);
+        drop_db(db, &name).await;
+    }
+}
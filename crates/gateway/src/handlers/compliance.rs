@@ -34,8 +34,13 @@ pub async fn sovereignty_audit(
         Err(err) => return err.into_response(),
     };
 
-    let bucket_row = sqlx::query("SELECT name, owner_email FROM buckets WHERE name = $1")
-        .bind(&bucket)
+    let scope = match crate::tenancy::BucketScope::resolve(&state, &user_email, &bucket).await {
+        Ok(scope) => scope,
+        Err(err) => return err.into_response(),
+    };
+    let bucket_row = sqlx::query("SELECT name, owner_email FROM buckets WHERE name = $1 AND tenant_id = $2")
+        .bind(&scope.masked)
+        .bind(&scope.tenant_id)
         .fetch_optional(&state.db)
         .await
         .unwrap_or(None);
@@ -70,7 +75,7 @@ pub async fn sovereignty_audit(
             COALESCE((SELECT COUNT(*) FROM latest_evidence WHERE country_code = 'IN'), 0) AS in_jurisdiction_shards
         "#,
     )
-    .bind(&bucket)
+    .bind(&scope.name)
     .fetch_one(&state.db)
     .await
     .unwrap_or((0, 0, 0));
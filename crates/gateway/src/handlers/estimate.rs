@@ -0,0 +1,234 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use neuro_client_sdk::{adaptive_config, RedundancyProfile};
+use neuro_protocol::sentinel::{
+    excludes_peer, BASE_PHYSICAL_PAYOUT_PER_GB_MONTH, MAX_COGS_SHARE, PRICE_CURRENCY,
+    USER_CHARGE_PER_GB_MONTH,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::handlers::s3::MAX_OBJECT_BYTES;
+use crate::AppState;
+
+// ── COST ESTIMATOR ──
+// Lets frontends show what an upload will cost before it starts. The shard
+// layout mirrors the real write paths (S3 PUT for `standard`, neuro-uploader
+// profiles for the rest), the price follows the sentinel pricing model and
+// the regions come from the nodes currently eligible for new shards.
+
+/// AES-GCM nonce and tag added to every encrypted payload.
+const ENCRYPTION_OVERHEAD: u64 = 12 + 16;
+/// RS data shards of an S3 PUT; parity scales with the fleet policy.
+const S3_DATA_SHARDS: usize = 10;
+/// neuro-uploader's default `--replica-factor`.
+const UPLOADER_REPLICA_FACTOR: usize = 2;
+/// Nodes seen within this window count towards placement.
+pub(crate) const ACTIVE_NODE_WINDOW_HOURS: i32 = 24;
+pub(crate) const BYTES_PER_GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+#[derive(Deserialize)]
+pub struct EstimateQuery {
+    pub bytes: u64,
+    pub storage_class: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RegionEstimate {
+    pub region: String,
+    pub nodes: usize,
+    /// Expected fraction of the shards placed in this region.
+    pub share: f64,
+}
+
+#[derive(Serialize)]
+pub struct CostEstimate {
+    pub bytes: u64,
+    pub storage_class: String,
+    pub chunk_count: u64,
+    pub data_shards: usize,
+    pub parity_shards: usize,
+    pub replicas: usize,
+    pub shard_count: u64,
+    pub stored_bytes: u64,
+    /// Stored bytes over logical bytes.
+    pub redundancy_overhead: f64,
+    pub currency: &'static str,
+    pub node_payout_per_gb_month: f64,
+    pub monthly_price: f64,
+    pub active_nodes: usize,
+    pub regions: Vec<RegionEstimate>,
+}
+
+/// The storage classes `storage_class` accepts, cheapest layout first.
+pub(crate) const STORAGE_CLASSES: &[&str] = &["standard", "mobile", "balanced", "resilient"];
+
+/// How an object of a storage class is laid out on the network.
+pub(crate) struct Layout {
+    chunk_size: u64,
+    pub data_shards: usize,
+    pub parity_shards: usize,
+    pub replicas: usize,
+}
+
+impl Layout {
+    pub(crate) fn for_class(state: &AppState, class: &str, bytes: u64, active_nodes: usize) -> Option<Self> {
+        let profile = match class {
+            "standard" => {
+                return Some(Self {
+                    chunk_size: bytes,
+                    data_shards: S3_DATA_SHARDS,
+                    parity_shards: state.fleet_policy.parity_shards(S3_DATA_SHARDS),
+                    replicas: 1,
+                })
+            }
+            "mobile" => RedundancyProfile::Mobile,
+            "balanced" => RedundancyProfile::Balanced,
+            "resilient" => RedundancyProfile::Resilient,
+            _ => return None,
+        };
+        let mut cfg = adaptive_config(bytes as usize, active_nodes, profile);
+        cfg.clamp_to_limits();
+        Some(Self {
+            chunk_size: cfg.chunk_size as u64,
+            data_shards: cfg.data_shards,
+            parity_shards: cfg.parity_shards,
+            replicas: UPLOADER_REPLICA_FACTOR.clamp(1, active_nodes.max(1)),
+        })
+    }
+
+    /// (chunks, shards, stored bytes) for `bytes` of plaintext, counting the
+    /// short final chunk at its real size.
+    pub(crate) fn totals(&self, bytes: u64) -> (u64, u64, u64) {
+        let shards_per_chunk = (self.data_shards + self.parity_shards) as u64;
+        let stored_per_chunk = |len: u64| {
+            (len + ENCRYPTION_OVERHEAD).div_ceil(self.data_shards as u64)
+                * shards_per_chunk
+                * self.replicas as u64
+        };
+        let full = bytes / self.chunk_size;
+        let rest = bytes % self.chunk_size;
+        let chunks = full + u64::from(rest > 0);
+        let stored = full * stored_per_chunk(self.chunk_size)
+            + if rest > 0 { stored_per_chunk(rest) } else { 0 };
+        (chunks, chunks * shards_per_chunk * self.replicas as u64, stored)
+    }
+}
+
+/// The country of every active node still eligible for new shards, `XX`
+/// where unknown.
+pub(crate) async fn eligible_regions(state: &AppState) -> Result<Vec<String>, sqlx::Error> {
+    let nodes = sqlx::query_as::<_, (String, Option<String>)>(
+        "SELECT peer_id, country_code FROM nodes WHERE last_seen > NOW() - make_interval(hours => $1)",
+    )
+    .bind(ACTIVE_NODE_WINDOW_HOURS)
+    .fetch_all(&state.db)
+    .await?;
+    Ok(nodes
+        .into_iter()
+        .filter(|(peer_id, _)| !state.fleet_policy.is_excluded(peer_id))
+        .map(|(_, country)| country.filter(|c| !c.is_empty()).unwrap_or_else(|| "XX".to_string()))
+        .collect())
+}
+
+/// Nodes are paid the median rate the sentinel set for peers still taking
+/// shards, or the base payout before it has set any.
+pub(crate) async fn network_payout(state: &AppState) -> Result<f64, sqlx::Error> {
+    let payouts = sqlx::query_as::<_, (String, f64)>("SELECT action, price_per_gb FROM node_reputation")
+        .fetch_all(&state.db)
+        .await?
+        .into_iter()
+        .filter(|(action, price)| !excludes_peer(action) && price.is_finite())
+        .map(|(_, price)| price)
+        .collect();
+    Ok(median(payouts).unwrap_or(BASE_PHYSICAL_PAYOUT_PER_GB_MONTH))
+}
+
+pub(crate) fn median(mut values: Vec<f64>) -> Option<f64> {
+    values.sort_by(f64::total_cmp);
+    values.get(values.len() / 2).copied()
+}
+
+/// Users pay the flat charge unless the redundancy pushes payouts past the
+/// COGS budget.
+pub(crate) fn monthly_price(logical_bytes: u64, stored_bytes: u64, payout_per_gb: f64) -> f64 {
+    f64::max(
+        logical_bytes as f64 / BYTES_PER_GB * USER_CHARGE_PER_GB_MONTH,
+        stored_bytes as f64 / BYTES_PER_GB * payout_per_gb / MAX_COGS_SHARE,
+    )
+}
+
+// ── GET /api/estimate?bytes=&storage_class= ──
+pub async fn estimate(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<EstimateQuery>,
+) -> impl IntoResponse {
+    let class = query
+        .storage_class
+        .as_deref()
+        .unwrap_or("standard")
+        .to_ascii_lowercase();
+    if query.bytes == 0 {
+        return (StatusCode::BAD_REQUEST, "bytes must be greater than 0").into_response();
+    }
+    if class == "standard" && query.bytes > MAX_OBJECT_BYTES as u64 {
+        return (StatusCode::BAD_REQUEST, "Exceeds 500MB Limit").into_response();
+    }
+
+    let eligible = match eligible_regions(&state).await {
+        Ok(regions) => regions,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)).into_response(),
+    };
+
+    let Some(layout) = Layout::for_class(&state, &class, query.bytes, eligible.len()) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            "storage_class must be one of standard, mobile, balanced, resilient",
+        )
+            .into_response();
+    };
+    let (chunk_count, shard_count, stored_bytes) = layout.totals(query.bytes);
+
+    let payout_per_gb = match network_payout(&state).await {
+        Ok(payout) => payout,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)).into_response(),
+    };
+    let monthly_price = monthly_price(query.bytes, stored_bytes, payout_per_gb);
+
+    let mut by_region: HashMap<String, usize> = HashMap::new();
+    for country in &eligible {
+        *by_region.entry(country.clone()).or_default() += 1;
+    }
+    let mut regions: Vec<RegionEstimate> = by_region
+        .into_iter()
+        .map(|(region, nodes)| RegionEstimate {
+            region,
+            nodes,
+            share: nodes as f64 / eligible.len() as f64,
+        })
+        .collect();
+    regions.sort_by(|a, b| b.nodes.cmp(&a.nodes).then_with(|| a.region.cmp(&b.region)));
+
+    Json(CostEstimate {
+        bytes: query.bytes,
+        storage_class: class,
+        chunk_count,
+        data_shards: layout.data_shards,
+        parity_shards: layout.parity_shards,
+        replicas: layout.replicas,
+        shard_count,
+        stored_bytes,
+        redundancy_overhead: stored_bytes as f64 / query.bytes as f64,
+        currency: PRICE_CURRENCY,
+        node_payout_per_gb_month: payout_per_gb,
+        monthly_price,
+        active_nodes: eligible.len(),
+        regions,
+    })
+    .into_response()
+}
@@ -0,0 +1,163 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::handlers::policy::BucketAccess;
+use crate::handlers::s3::{authorize_bucket, record_request_fields, validate_bucket_principal};
+use crate::AppState;
+
+// ── BUCKET HEALTH ──
+// Summary of the health scan's durability scores for one bucket, with the
+// lowest-scoring objects first so the dashboard can point at what to
+// repair. Only objects the scan has reached are counted.
+
+const DEFAULT_WORST: i64 = 20;
+const MAX_WORST: i64 = 100;
+/// Scores below this count as at risk.
+const AT_RISK_SCORE: f64 = 50.0;
+
+#[derive(Deserialize)]
+pub struct HealthQuery {
+    /// How many worst offenders to list.
+    pub limit: Option<i64>,
+}
+
+#[derive(sqlx::FromRow)]
+struct Summary {
+    objects_scored: i64,
+    mean_score: Option<f64>,
+    min_score: Option<f64>,
+    at_risk: i64,
+    undecodable: i64,
+    last_scan_at: Option<DateTime<Utc>>,
+}
+
+#[derive(sqlx::FromRow)]
+struct ScoreRow {
+    key: String,
+    object_cid: String,
+    score: f64,
+    healthy_shards: i32,
+    shards_total: i32,
+    recovery_threshold: i32,
+    mean_reputation: f64,
+    regions: i32,
+    decodable: bool,
+    scanned_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct ObjectScore {
+    pub key: String,
+    pub object_cid: String,
+    pub score: f64,
+    pub healthy_shards: i32,
+    pub shards_total: i32,
+    pub recovery_threshold: i32,
+    pub mean_reputation: f64,
+    pub regions: i32,
+    pub decodable: bool,
+    pub scanned_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct BucketHealth {
+    pub bucket: String,
+    pub objects_total: i64,
+    pub objects_scored: i64,
+    pub mean_score: Option<f64>,
+    pub min_score: Option<f64>,
+    pub at_risk: i64,
+    pub undecodable: i64,
+    pub last_scan_at: Option<DateTime<Utc>>,
+    pub worst: Vec<ObjectScore>,
+}
+
+pub async fn bucket_health(
+    State(state): State<Arc<AppState>>,
+    Path(bucket): Path<String>,
+    Query(query): Query<HealthQuery>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let principal = validate_bucket_principal(&headers, &state)?;
+    let scope = authorize_bucket(&state, &bucket, &principal, BucketAccess::Manage, "").await?;
+    record_request_fields(&bucket, None);
+    let limit = query.limit.unwrap_or(DEFAULT_WORST).clamp(1, MAX_WORST);
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e));
+
+    // Scores of deleted objects linger until their CID is reused, so every
+    // query goes through the live objects.
+    let objects_total = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM objects WHERE bucket = $1")
+        .bind(&scope.name)
+        .fetch_one(&state.db)
+        .await
+        .map_err(db_error)?;
+    let summary = sqlx::query_as::<_, Summary>(
+        r#"
+        SELECT COUNT(*) AS objects_scored,
+               AVG(d.score) AS mean_score,
+               MIN(d.score) AS min_score,
+               COUNT(*) FILTER (WHERE d.score < $2) AS at_risk,
+               COUNT(*) FILTER (WHERE NOT d.decodable) AS undecodable,
+               MAX(d.scanned_at) AS last_scan_at
+        FROM object_durability d
+        WHERE d.bucket = $1
+          AND EXISTS (SELECT 1 FROM objects o WHERE o.bucket = d.bucket AND o.cid = d.object_cid)
+        "#,
+    )
+    .bind(&scope.name)
+    .bind(AT_RISK_SCORE)
+    .fetch_one(&state.db)
+    .await
+    .map_err(db_error)?;
+    let rows = sqlx::query_as::<_, ScoreRow>(
+        r#"
+        SELECT DISTINCT ON (d.score, d.object_cid)
+               o.key, d.object_cid, d.score, d.healthy_shards, d.shards_total, d.recovery_threshold,
+               d.mean_reputation, d.regions, d.decodable, d.scanned_at
+        FROM object_durability d
+        JOIN objects o ON o.bucket = d.bucket AND o.cid = d.object_cid
+        WHERE d.bucket = $1
+        ORDER BY d.score, d.object_cid
+        LIMIT $2
+        "#,
+    )
+    .bind(&scope.name)
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await
+    .map_err(db_error)?;
+
+    let worst = rows
+        .into_iter()
+        .map(|row| ObjectScore {
+            key: scope.protector.decrypt(&row.key).unwrap_or(row.key),
+            object_cid: row.object_cid,
+            score: row.score,
+            healthy_shards: row.healthy_shards,
+            shards_total: row.shards_total,
+            recovery_threshold: row.recovery_threshold,
+            mean_reputation: row.mean_reputation,
+            regions: row.regions,
+            decodable: row.decodable,
+            scanned_at: row.scanned_at,
+        })
+        .collect();
+    Ok(Json(BucketHealth {
+        bucket,
+        objects_total,
+        objects_scored: summary.objects_scored,
+        mean_score: summary.mean_score,
+        min_score: summary.min_score,
+        at_risk: summary.at_risk,
+        undecodable: summary.undecodable,
+        last_scan_at: summary.last_scan_at,
+        worst,
+    }))
+}
@@ -0,0 +1,217 @@
+use axum::http::{HeaderMap, StatusCode};
+use base64::{engine::general_purpose, Engine as _};
+use bytes::Bytes;
+use sha2::{Digest, Sha256};
+
+// PUT body integrity: `x-amz-content-sha256` (hex digest of the payload),
+// `x-amz-checksum-sha256` (base64 digest, as a header or as an aws-chunked
+// trailer) and `x-amz-decoded-content-length`. Bodies sent with the
+// `STREAMING-*` payload modes arrive aws-chunked and are decoded here.
+// Everything is checked on the collected body, before encryption and shard
+// dispatch, so a corrupted upload never reaches a node.
+
+const CONTENT_SHA256_HEADER: &str = "x-amz-content-sha256";
+const CHECKSUM_SHA256_HEADER: &str = "x-amz-checksum-sha256";
+const DECODED_LENGTH_HEADER: &str = "x-amz-decoded-content-length";
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+const STREAMING_PREFIX: &str = "STREAMING-";
+
+type IntegrityError = (StatusCode, String);
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim)
+}
+
+/// Whether the body is aws-chunked and must go through [`verify_body`]'s
+/// decoder rather than being used as-is.
+fn is_aws_chunked(headers: &HeaderMap) -> bool {
+    header(headers, CONTENT_SHA256_HEADER).is_some_and(|v| v.starts_with(STREAMING_PREFIX))
+        || header(headers, "content-encoding")
+            .is_some_and(|v| v.split(',').any(|e| e.trim().eq_ignore_ascii_case("aws-chunked")))
+}
+
+/// Decodes the body if it is aws-chunked and checks every digest and length
+/// the client declared. Returns the payload to store.
+pub(crate) fn verify_body(headers: &HeaderMap, raw: Bytes) -> Result<Bytes, IntegrityError> {
+    let (payload, trailers) = if is_aws_chunked(headers) {
+        decode_aws_chunked(&raw)?
+    } else {
+        (raw, Vec::new())
+    };
+
+    if let Some(expected) = header(headers, DECODED_LENGTH_HEADER) {
+        let expected: usize = expected
+            .parse()
+            .map_err(|_| (StatusCode::BAD_REQUEST, format!("InvalidArgument: bad {}", DECODED_LENGTH_HEADER)))?;
+        if expected != payload.len() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("IncompleteBody: expected {} bytes, received {}", expected, payload.len()),
+            ));
+        }
+    }
+
+    let digest = Sha256::digest(&payload);
+    let declared_sha256 = header(headers, CONTENT_SHA256_HEADER)
+        .filter(|v| *v != UNSIGNED_PAYLOAD && !v.starts_with(STREAMING_PREFIX));
+    if let Some(declared) = declared_sha256 {
+        if !declared.eq_ignore_ascii_case(&hex::encode(digest)) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "XAmzContentSHA256Mismatch: the body does not match x-amz-content-sha256".to_string(),
+            ));
+        }
+    }
+
+    let trailer = trailers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(CHECKSUM_SHA256_HEADER))
+        .map(|(_, value)| value.as_str());
+    for declared in [header(headers, CHECKSUM_SHA256_HEADER), trailer].into_iter().flatten() {
+        if declared != general_purpose::STANDARD.encode(digest) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "BadDigest: the body does not match x-amz-checksum-sha256".to_string(),
+            ));
+        }
+    }
+    Ok(payload)
+}
+
+/// Splits an aws-chunked body into its payload and trailing headers:
+/// `<hex size>[;chunk-signature=…]\r\n<data>\r\n` repeated, then a zero-size
+/// chunk, optional `name:value\r\n` trailers and a final `\r\n`.
+fn decode_aws_chunked(raw: &[u8]) -> Result<(Bytes, Vec<(String, String)>), IntegrityError> {
+    let malformed = |what: &str| (StatusCode::BAD_REQUEST, format!("IncompleteBody: malformed aws-chunked body ({})", what));
+    let mut payload = Vec::with_capacity(raw.len());
+    let mut rest = raw;
+    loop {
+        let line_end = find_crlf(rest).ok_or_else(|| malformed("chunk header"))?;
+        let line = std::str::from_utf8(&rest[..line_end]).map_err(|_| malformed("chunk header"))?;
+        let size_hex = line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size_hex, 16).map_err(|_| malformed("chunk size"))?;
+        rest = &rest[line_end + 2..];
+        if size == 0 {
+            break;
+        }
+        if rest.len() < size + 2 || &rest[size..size + 2] != b"\r\n" {
+            return Err(malformed("truncated chunk"));
+        }
+        payload.extend_from_slice(&rest[..size]);
+        rest = &rest[size + 2..];
+    }
+
+    let mut trailers = Vec::new();
+    while let Some(line_end) = find_crlf(rest) {
+        let line = std::str::from_utf8(&rest[..line_end]).map_err(|_| malformed("trailer"))?;
+        rest = &rest[line_end + 2..];
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':').ok_or_else(|| malformed("trailer"))?;
+        trailers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+    Ok((Bytes::from(payload), trailers))
+}
+
+fn find_crlf(data: &[u8]) -> Option<usize> {
+    data.windows(2).position(|w| w == b"\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIGNATURE: &str = "chunk-signature=ad80c730a21e5b8d04586a2213dd63b9a0e99e0e2307b0ade35a65485a288648";
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(*name, value.parse().expect("header value"));
+        }
+        map
+    }
+
+    /// An aws-chunked body carrying `chunks` with signed chunk headers, then
+    /// `trailers`.
+    fn chunked(chunks: &[&[u8]], trailers: &[(&str, &str)]) -> Bytes {
+        let mut body = Vec::new();
+        for chunk in chunks.iter().chain([&b""[..]].iter()) {
+            body.extend_from_slice(format!("{:x};{}\r\n", chunk.len(), SIGNATURE).as_bytes());
+            body.extend_from_slice(chunk);
+            if !chunk.is_empty() {
+                body.extend_from_slice(b"\r\n");
+            }
+        }
+        for (name, value) in trailers {
+            body.extend_from_slice(format!("{}:{}\r\n", name, value).as_bytes());
+        }
+        body.extend_from_slice(b"\r\n");
+        Bytes::from(body)
+    }
+
+    fn checksum(data: &[u8]) -> String {
+        general_purpose::STANDARD.encode(Sha256::digest(data))
+    }
+
+    fn code(err: IntegrityError) -> String {
+        err.1.split(':').next().unwrap_or_default().to_string()
+    }
+
+    #[test]
+    fn signed_multi_chunk_body_is_reassembled() {
+        let raw = chunked(&[b"hello ", b"chunked ", b"world"], &[]);
+        let headers = headers(&[
+            (CONTENT_SHA256_HEADER, "STREAMING-AWS4-HMAC-SHA256-PAYLOAD"),
+            (DECODED_LENGTH_HEADER, "19"),
+        ]);
+        assert_eq!(verify_body(&headers, raw).expect("valid body"), Bytes::from_static(b"hello chunked world"));
+    }
+
+    #[test]
+    fn matching_trailer_checksum_is_accepted() {
+        let raw = chunked(&[b"abc", b"def"], &[(CHECKSUM_SHA256_HEADER, &checksum(b"abcdef"))]);
+        let headers = headers(&[(CONTENT_SHA256_HEADER, "STREAMING-UNSIGNED-PAYLOAD-TRAILER")]);
+        assert_eq!(verify_body(&headers, raw).expect("valid body"), Bytes::from_static(b"abcdef"));
+    }
+
+    #[test]
+    fn mismatched_trailer_checksum_is_bad_digest() {
+        let raw = chunked(&[b"abc", b"def"], &[(CHECKSUM_SHA256_HEADER, &checksum(b"abcdeg"))]);
+        let headers = headers(&[(CONTENT_SHA256_HEADER, "STREAMING-UNSIGNED-PAYLOAD-TRAILER")]);
+        assert_eq!(code(verify_body(&headers, raw).unwrap_err()), "BadDigest");
+    }
+
+    #[test]
+    fn truncated_chunk_is_rejected() {
+        let raw = b"a;chunk-signature=00\r\nshort";
+        let err = decode_aws_chunked(raw).unwrap_err();
+        assert_eq!(err.0, StatusCode::BAD_REQUEST);
+        assert!(err.1.contains("truncated chunk"), "{}", err.1);
+    }
+
+    #[test]
+    fn bad_hex_size_is_rejected() {
+        let raw = b"zz;chunk-signature=00\r\nabc\r\n0\r\n\r\n";
+        let err = decode_aws_chunked(raw).unwrap_err();
+        assert_eq!(err.0, StatusCode::BAD_REQUEST);
+        assert!(err.1.contains("chunk size"), "{}", err.1);
+    }
+
+    #[test]
+    fn decoded_length_mismatch_is_incomplete_body() {
+        let raw = chunked(&[b"abc"], &[]);
+        let headers = headers(&[
+            (CONTENT_SHA256_HEADER, "STREAMING-AWS4-HMAC-SHA256-PAYLOAD"),
+            (DECODED_LENGTH_HEADER, "4"),
+        ]);
+        assert_eq!(code(verify_body(&headers, raw).unwrap_err()), "IncompleteBody");
+    }
+
+    #[test]
+    fn unsigned_payload_passes_through_unchanged() {
+        let raw = Bytes::from_static(b"3\r\nnot chunked\r\n");
+        let headers = headers(&[(CONTENT_SHA256_HEADER, UNSIGNED_PAYLOAD)]);
+        assert_eq!(verify_body(&headers, raw.clone()).expect("valid body"), raw);
+    }
+}
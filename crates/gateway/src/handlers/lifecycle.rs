@@ -0,0 +1,244 @@
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::handlers::policy::BucketAccess;
+use crate::handlers::s3_error::S3Error;
+use crate::handlers::s3::{authorize_bucket, record_request_fields, validate_bucket_principal, validate_csrf, xml_escape};
+use crate::handlers::tagging::{xml_elements, xml_unescape};
+use crate::models::LifecycleRule;
+use crate::replication::{self, MetadataOp};
+use crate::tenancy::BucketScope;
+use crate::AppState;
+
+// Bucket lifecycle configuration: GET/PUT/DELETE `/:bucket?lifecycle`.
+// Only expiration is supported, filtered on at most one object tag; the
+// LifecycleDaemon applies the rules.
+
+const MAX_RULES: usize = 100;
+const MAX_RULE_ID_CHARS: usize = 255;
+const MAX_LIFECYCLE_BODY_BYTES: usize = 256 * 1024;
+
+fn parse_lifecycle_xml(xml: &str) -> Result<Vec<LifecycleRule>, (StatusCode, String)> {
+    let malformed = |why: &str| (StatusCode::BAD_REQUEST, format!("MalformedXML: {}", why));
+    let config = xml_elements(xml, "LifecycleConfiguration")
+        .into_iter()
+        .next()
+        .ok_or_else(|| malformed("missing LifecycleConfiguration"))?;
+    let rules = xml_elements(config, "Rule");
+    if rules.len() > MAX_RULES {
+        return Err((StatusCode::BAD_REQUEST, format!("InvalidRequest: at most {} rules", MAX_RULES)));
+    }
+
+    let mut parsed = Vec::with_capacity(rules.len());
+    let mut ids = HashSet::new();
+    for (index, rule) in rules.into_iter().enumerate() {
+        let rule_id = match xml_elements(rule, "ID").into_iter().next() {
+            Some(id) if !id.trim().is_empty() => xml_unescape(id.trim()),
+            _ => format!("rule-{}", index + 1),
+        };
+        if rule_id.chars().count() > MAX_RULE_ID_CHARS || !ids.insert(rule_id.clone()) {
+            return Err((StatusCode::BAD_REQUEST, format!("InvalidArgument: bad or duplicate rule ID {}", rule_id)));
+        }
+
+        let enabled = match xml_elements(rule, "Status").into_iter().next().map(str::trim) {
+            Some("Enabled") => true,
+            Some("Disabled") => false,
+            _ => return Err(malformed("Status must be Enabled or Disabled")),
+        };
+
+        let days = xml_elements(rule, "Expiration")
+            .into_iter()
+            .next()
+            .and_then(|expiration| xml_elements(expiration, "Days").into_iter().next())
+            .and_then(|days| days.trim().parse::<i32>().ok())
+            .filter(|days| *days > 0)
+            .ok_or_else(|| malformed("each rule needs Expiration/Days greater than zero"))?;
+
+        let filter = xml_elements(rule, "Filter").into_iter().next().unwrap_or_default();
+        if !xml_elements(filter, "And").is_empty()
+            || xml_elements(filter, "Prefix").iter().any(|p| !p.trim().is_empty())
+            || !xml_elements(rule, "Prefix").iter().all(|p| p.trim().is_empty())
+        {
+            return Err((StatusCode::NOT_IMPLEMENTED, "NotImplemented: only single-tag filters are supported".to_string()));
+        }
+        let (tag_key, tag_value) = match xml_elements(filter, "Tag").into_iter().next() {
+            Some(tag) => {
+                let key = xml_elements(tag, "Key")
+                    .into_iter()
+                    .next()
+                    .map(|k| xml_unescape(k.trim()))
+                    .filter(|k| !k.is_empty())
+                    .ok_or_else(|| malformed("Filter/Tag needs a Key"))?;
+                let value = xml_elements(tag, "Value").into_iter().next().map(xml_unescape).unwrap_or_default();
+                (Some(key), Some(value))
+            }
+            None => (None, None),
+        };
+
+        parsed.push(LifecycleRule {
+            rule_id,
+            tag_key,
+            tag_value,
+            expiration_days: days,
+            enabled,
+        });
+    }
+    Ok(parsed)
+}
+
+fn render_lifecycle_xml(rules: &[LifecycleRule]) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<LifecycleConfiguration xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\n");
+    for rule in rules {
+        xml.push_str("  <Rule>\n");
+        xml.push_str(&format!("    <ID>{}</ID>\n", xml_escape(&rule.rule_id)));
+        match (&rule.tag_key, &rule.tag_value) {
+            (Some(key), value) => {
+                xml.push_str("    <Filter>\n      <Tag>\n");
+                xml.push_str(&format!("        <Key>{}</Key>\n", xml_escape(key)));
+                xml.push_str(&format!("        <Value>{}</Value>\n", xml_escape(value.as_deref().unwrap_or_default())));
+                xml.push_str("      </Tag>\n    </Filter>\n");
+            }
+            (None, _) => xml.push_str("    <Filter/>\n"),
+        }
+        xml.push_str(&format!("    <Status>{}</Status>\n", if rule.enabled { "Enabled" } else { "Disabled" }));
+        xml.push_str(&format!("    <Expiration>\n      <Days>{}</Days>\n    </Expiration>\n", rule.expiration_days));
+        xml.push_str("  </Rule>\n");
+    }
+    xml.push_str("</LifecycleConfiguration>");
+    xml
+}
+
+pub(crate) async fn load_rules(db: &sqlx::PgPool, bucket: &str) -> Result<Vec<LifecycleRule>, sqlx::Error> {
+    sqlx::query_as::<_, LifecycleRule>(
+        "SELECT rule_id, tag_key, tag_value, expiration_days, enabled FROM bucket_lifecycle_rules WHERE bucket = $1 ORDER BY rule_id"
+    )
+    .bind(bucket)
+    .fetch_all(db)
+    .await
+}
+
+/// Replaces every rule on `bucket`; used by the handlers and the
+/// replication follower alike.
+pub(crate) async fn replace_rules(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    bucket: &str,
+    rules: &[LifecycleRule],
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM bucket_lifecycle_rules WHERE bucket = $1")
+        .bind(bucket)
+        .execute(&mut **tx)
+        .await?;
+    for rule in rules {
+        sqlx::query(
+            r#"
+            INSERT INTO bucket_lifecycle_rules (bucket, rule_id, tag_key, tag_value, expiration_days, enabled)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#
+        )
+        .bind(bucket)
+        .bind(&rule.rule_id)
+        .bind(&rule.tag_key)
+        .bind(&rule.tag_value)
+        .bind(rule.expiration_days)
+        .bind(rule.enabled)
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(())
+}
+
+async fn store_rules(state: &AppState, bucket: &str, rules: Vec<LifecycleRule>) -> Result<Response, S3Error> {
+    let res = async {
+        let mut tx = state.db.begin().await?;
+        replace_rules(&mut tx, bucket, &rules).await?;
+        tx.commit().await
+    }
+    .await;
+    if let Err(e) = res {
+        tracing::error!("Database error while storing lifecycle rules: {}", e);
+        return Err(S3Error::internal("Database error while storing lifecycle rules"));
+    }
+
+    let removed = rules.is_empty();
+    replication::publish(state, MetadataOp::ReplaceLifecycleRules {
+        bucket: bucket.to_string(),
+        rules,
+    })
+    .await;
+    if removed {
+        Ok(StatusCode::NO_CONTENT.into_response())
+    } else {
+        Ok(StatusCode::OK.into_response())
+    }
+}
+
+async fn authorize(state: &AppState, bucket: &str, headers: &HeaderMap, write: bool) -> Result<BucketScope, S3Error> {
+    record_request_fields(bucket, None);
+    if write {
+        validate_csrf(headers)?;
+    }
+    let principal = validate_bucket_principal(headers, state)?;
+    Ok(authorize_bucket(state, bucket, &principal, BucketAccess::Manage, "").await?)
+}
+
+/// Reached from `list_objects` when the query has `lifecycle`.
+pub async fn get_bucket_lifecycle(state: &AppState, bucket: &str, headers: &HeaderMap) -> Result<Response, S3Error> {
+    let scope = authorize(state, bucket, headers, false).await?;
+    let rules = load_rules(&state.db, &scope.name).await?;
+    if rules.is_empty() {
+        return Err(S3Error::new(
+            StatusCode::NOT_FOUND,
+            "NoSuchLifecycleConfiguration",
+            "The lifecycle configuration does not exist.",
+        )
+        .with_resource(bucket));
+    }
+    let mut headers = HeaderMap::new();
+    headers.insert("Content-Type", HeaderValue::from_static("application/xml"));
+    Ok((StatusCode::OK, headers, render_lifecycle_xml(&rules)).into_response())
+}
+
+pub async fn put_bucket_lifecycle(
+    State(state): State<Arc<AppState>>,
+    Path(bucket): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, S3Error> {
+    if !params.contains_key("lifecycle") {
+        return Err(S3Error::new(StatusCode::NOT_IMPLEMENTED, "NotImplemented", "buckets are created on first write"));
+    }
+    let scope = authorize(&state, &bucket, &headers, true).await?;
+    let bytes = match axum::body::to_bytes(body, MAX_LIFECYCLE_BODY_BYTES).await {
+        Ok(b) => b,
+        Err(_) => {
+            return Err(S3Error::new(StatusCode::PAYLOAD_TOO_LARGE, "EntityTooLarge", "Lifecycle document too large"))
+        }
+    };
+    let Ok(xml) = std::str::from_utf8(&bytes) else {
+        return Err(S3Error::new(StatusCode::BAD_REQUEST, "MalformedXML", "The lifecycle document is not UTF-8."));
+    };
+    let rules = parse_lifecycle_xml(xml)?;
+    store_rules(&state, &scope.name, rules).await
+}
+
+pub async fn delete_bucket_lifecycle(
+    State(state): State<Arc<AppState>>,
+    Path(bucket): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Result<Response, S3Error> {
+    if !params.contains_key("lifecycle") {
+        return Err(S3Error::new(StatusCode::NOT_IMPLEMENTED, "NotImplemented", "bucket deletion is not supported"));
+    }
+    let scope = authorize(&state, &bucket, &headers, true).await?;
+    store_rules(&state, &scope.name, Vec::new()).await
+}
@@ -0,0 +1,251 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::config::LimitsConfig;
+use crate::handlers::backups::check_admin_token;
+use crate::replication::{self, MetadataOp};
+use crate::AppState;
+
+// ── ANTI-ABUSE LIMITS ──
+// The configured `limits`, with per-bucket overrides an admin sets through
+// GET/PUT/DELETE /api/admin/buckets/:bucket/limits. The key count is checked
+// before a PUT reads its body, so concurrent PUTs of new keys may overshoot
+// it by a few; it bounds growth rather than promising an exact size.
+
+/// Overrides for one bucket; `None` keeps the configured value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(deny_unknown_fields)]
+pub struct BucketLimits {
+    pub max_keys: Option<i64>,
+    pub max_key_bytes: Option<i32>,
+    pub max_metadata_bytes: Option<i32>,
+}
+
+/// The limits a write to one bucket is held to.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct EffectiveLimits {
+    pub max_keys: u64,
+    pub max_key_bytes: usize,
+    pub max_metadata_bytes: usize,
+}
+
+impl BucketLimits {
+    fn apply(&self, config: &LimitsConfig) -> EffectiveLimits {
+        EffectiveLimits {
+            max_keys: self.max_keys.map_or(config.max_keys_per_bucket, |v| v as u64),
+            max_key_bytes: self.max_key_bytes.map_or(config.max_key_bytes, |v| v as usize),
+            max_metadata_bytes: self.max_metadata_bytes.map_or(config.max_metadata_bytes, |v| v as usize),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.max_keys.is_none() && self.max_key_bytes.is_none() && self.max_metadata_bytes.is_none()
+    }
+}
+
+async fn load_override(db: &sqlx::PgPool, bucket: &str) -> Result<Option<BucketLimits>, sqlx::Error> {
+    sqlx::query_as::<_, BucketLimits>(
+        "SELECT max_keys, max_key_bytes, max_metadata_bytes FROM bucket_limits WHERE bucket = $1",
+    )
+    .bind(bucket)
+    .fetch_optional(db)
+    .await
+}
+
+pub(crate) async fn effective(state: &AppState, bucket: &str) -> Result<EffectiveLimits, (StatusCode, String)> {
+    let overrides = load_override(&state.db, bucket)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)))?;
+    Ok(overrides.unwrap_or_default().apply(&state.config.limits))
+}
+
+pub(crate) fn check_key(limits: &EffectiveLimits, key: &str) -> Result<(), (StatusCode, String)> {
+    if key.len() > limits.max_key_bytes {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("KeyTooLongError: keys are limited to {} bytes", limits.max_key_bytes),
+        ));
+    }
+    Ok(())
+}
+
+/// Bytes of user metadata a request carries: `x-amz-meta-*` and
+/// `x-amz-tagging`, names and values.
+fn metadata_bytes(headers: &HeaderMap) -> usize {
+    headers
+        .iter()
+        .filter(|(name, _)| name.as_str().starts_with("x-amz-meta-") || name.as_str() == "x-amz-tagging")
+        .map(|(name, value)| name.as_str().len() + value.len())
+        .sum()
+}
+
+pub(crate) fn check_metadata(limits: &EffectiveLimits, headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+    let size = metadata_bytes(headers);
+    if size > limits.max_metadata_bytes {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "MetadataTooLarge: {} bytes of metadata, the limit is {}",
+                size, limits.max_metadata_bytes
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Refuses a new key once `bucket` holds `max_keys`; overwriting an existing
+/// key is always allowed.
+pub(crate) async fn check_key_count(
+    state: &AppState,
+    limits: &EffectiveLimits,
+    bucket: &str,
+    key_lookup: &str,
+) -> Result<(), (StatusCode, String)> {
+    let (count, exists) = sqlx::query_as::<_, (i64, bool)>(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM objects WHERE bucket = $1),
+            EXISTS (SELECT 1 FROM objects WHERE bucket = $1 AND key_lookup = $2)
+        "#,
+    )
+    .bind(bucket)
+    .bind(key_lookup)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)))?;
+    if !exists && count as u64 >= limits.max_keys {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("TooManyObjects: the bucket is limited to {} keys", limits.max_keys),
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn check_parts(config: &LimitsConfig, parts: usize) -> Result<(), (StatusCode, String)> {
+    if parts > config.max_parts {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("InvalidArgument: {} parts, at most {} are allowed", parts, config.max_parts),
+        ));
+    }
+    Ok(())
+}
+
+/// Replaces the override on `bucket`, `None` removing it; shared with the
+/// replication follower.
+pub(crate) async fn replace_override(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    bucket: &str,
+    limits: Option<&BucketLimits>,
+) -> Result<(), sqlx::Error> {
+    let Some(limits) = limits.filter(|l| !l.is_empty()) else {
+        sqlx::query("DELETE FROM bucket_limits WHERE bucket = $1")
+            .bind(bucket)
+            .execute(&mut **tx)
+            .await?;
+        return Ok(());
+    };
+    sqlx::query(
+        r#"
+        INSERT INTO bucket_limits (bucket, max_keys, max_key_bytes, max_metadata_bytes, updated_at)
+        VALUES ($1, $2, $3, $4, NOW())
+        ON CONFLICT (bucket) DO UPDATE SET
+            max_keys = excluded.max_keys,
+            max_key_bytes = excluded.max_key_bytes,
+            max_metadata_bytes = excluded.max_metadata_bytes,
+            updated_at = NOW()
+        "#,
+    )
+    .bind(bucket)
+    .bind(limits.max_keys)
+    .bind(limits.max_key_bytes)
+    .bind(limits.max_metadata_bytes)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+async fn store_override(
+    state: &AppState,
+    bucket: &str,
+    limits: Option<BucketLimits>,
+) -> Result<(), (StatusCode, String)> {
+    let res = async {
+        let mut tx = state.db.begin().await?;
+        replace_override(&mut tx, bucket, limits.as_ref()).await?;
+        tx.commit().await
+    }
+    .await;
+    if let Err(e) = res {
+        tracing::error!("Database error while storing bucket limits: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database Error".to_string()));
+    }
+    replication::publish(state, MetadataOp::ReplaceBucketLimits {
+        bucket: bucket.to_string(),
+        limits,
+    })
+    .await;
+    Ok(())
+}
+
+async fn describe(state: &AppState, bucket: &str) -> Result<serde_json::Value, (StatusCode, String)> {
+    let overrides = load_override(&state.db, bucket)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)))?;
+    let effective = overrides.clone().unwrap_or_default().apply(&state.config.limits);
+    Ok(serde_json::json!({
+        "bucket": bucket,
+        "override": overrides,
+        "effective": effective,
+        "max_parts": state.config.limits.max_parts,
+    }))
+}
+
+// ── GET /api/admin/buckets/:bucket/limits ──
+pub async fn get_bucket_limits(
+    State(state): State<Arc<AppState>>,
+    Path(bucket): Path<String>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    check_admin_token(&state, &headers)?;
+    Ok(Json(describe(&state, &bucket).await?))
+}
+
+// ── PUT /api/admin/buckets/:bucket/limits ──
+// Body: any of `max_keys`, `max_key_bytes`, `max_metadata_bytes`; the ones
+// left out (or null) fall back to the config.
+pub async fn put_bucket_limits(
+    State(state): State<Arc<AppState>>,
+    Path(bucket): Path<String>,
+    headers: HeaderMap,
+    Json(limits): Json<BucketLimits>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    check_admin_token(&state, &headers)?;
+    let positive = limits.max_keys.is_none_or(|v| v > 0)
+        && limits.max_key_bytes.is_none_or(|v| v > 0)
+        && limits.max_metadata_bytes.is_none_or(|v| v > 0);
+    if !positive {
+        return Err((StatusCode::BAD_REQUEST, "Limits must be at least 1".to_string()));
+    }
+    store_override(&state, &bucket, Some(limits)).await?;
+    tracing::info!(bucket = %bucket, "Bucket limits overridden");
+    Ok(Json(describe(&state, &bucket).await?))
+}
+
+// ── DELETE /api/admin/buckets/:bucket/limits ──
+pub async fn delete_bucket_limits(
+    State(state): State<Arc<AppState>>,
+    Path(bucket): Path<String>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    check_admin_token(&state, &headers)?;
+    store_override(&state, &bucket, None).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
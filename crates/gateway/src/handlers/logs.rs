@@ -0,0 +1,190 @@
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::handlers::policy::BucketAccess;
+use crate::handlers::s3::{authorize_bucket, record_request_fields, validate_bucket_principal};
+use crate::AppState;
+
+const DEFAULT_LOG_PAGE: i64 = 1000;
+const MAX_LOG_PAGE: i64 = 5000;
+/// Carries the cursor for the next page on CSV responses.
+const NEXT_CURSOR_HEADER: &str = "x-neuro-next-cursor";
+
+#[derive(Deserialize)]
+pub struct LogQuery {
+    pub bucket: String,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+    /// `next_cursor` from the previous page.
+    pub cursor: Option<String>,
+    /// `json` (default) or `csv`.
+    pub format: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct AccessRow {
+    id: i64,
+    occurred_at: DateTime<Utc>,
+    object_key: String,
+    principal: Option<String>,
+    action: String,
+    bytes: Option<i64>,
+    source_ip: Option<String>,
+    status: i16,
+    request_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct AccessEntry {
+    pub occurred_at: DateTime<Utc>,
+    pub key: String,
+    pub principal: Option<String>,
+    pub action: String,
+    pub bytes: Option<i64>,
+    pub source_ip: Option<String>,
+    pub status: i16,
+    pub request_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct AccessLogPage {
+    pub bucket: String,
+    pub entries: Vec<AccessEntry>,
+    pub next_cursor: Option<String>,
+}
+
+/// Cursors are `<occurred_at micros>.<id>`, the position of the last entry
+/// returned, so paging stays stable while new entries arrive.
+fn parse_cursor(cursor: &str) -> Option<(DateTime<Utc>, i64)> {
+    let (micros, id) = cursor.split_once('.')?;
+    Some((DateTime::from_timestamp_micros(micros.parse().ok()?)?, id.parse().ok()?))
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn to_csv(entries: &[AccessEntry]) -> String {
+    let mut csv = String::from("occurred_at,key,principal,action,bytes,source_ip,status,request_id\n");
+    for e in entries {
+        let row = [
+            e.occurred_at.to_rfc3339(),
+            csv_field(&e.key),
+            csv_field(e.principal.as_deref().unwrap_or_default()),
+            e.action.clone(),
+            e.bytes.map(|b| b.to_string()).unwrap_or_default(),
+            csv_field(e.source_ip.as_deref().unwrap_or_default()),
+            e.status.to_string(),
+            csv_field(e.request_id.as_deref().unwrap_or_default()),
+        ];
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+// ── GET /api/logs?bucket=&from=&to= ──
+// Object access history for a bucket, oldest first. Only principals who may
+// manage the bucket can read it, since it names everyone else who touched it.
+pub async fn list_access_logs(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LogQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    record_request_fields(&query.bucket, None);
+    let principal = match validate_bucket_principal(&headers, &state) {
+        Ok(principal) => principal,
+        Err(err) => return err.into_response(),
+    };
+    let scope = match authorize_bucket(&state, &query.bucket, &principal, BucketAccess::Manage, "").await {
+        Ok(scope) => scope,
+        Err(err) => return err.into_response(),
+    };
+    let csv = match query.format.as_deref() {
+        None | Some("json") => false,
+        Some("csv") => true,
+        Some(other) => return (StatusCode::BAD_REQUEST, format!("Unsupported format: {}", other)).into_response(),
+    };
+    let after = match query.cursor.as_deref().map(parse_cursor) {
+        None => None,
+        Some(Some(after)) => Some(after),
+        Some(None) => return (StatusCode::BAD_REQUEST, "Invalid cursor".to_string()).into_response(),
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_LOG_PAGE).clamp(1, MAX_LOG_PAGE);
+
+    let rows = sqlx::query_as::<_, AccessRow>(
+        r#"
+        SELECT id, occurred_at, object_key, principal, action, bytes, source_ip, status, request_id
+        FROM access_log
+        WHERE bucket = $1
+          AND ($2::timestamptz IS NULL OR occurred_at >= $2)
+          AND ($3::timestamptz IS NULL OR occurred_at < $3)
+          AND ($4::timestamptz IS NULL OR (occurred_at, id) > ($4, $5))
+        ORDER BY occurred_at, id
+        LIMIT $6
+        "#,
+    )
+    .bind(&scope.masked)
+    .bind(query.from)
+    .bind(query.to)
+    .bind(after.map(|(at, _)| at))
+    .bind(after.map(|(_, id)| id).unwrap_or_default())
+    .bind(limit + 1)
+    .fetch_all(&state.db)
+    .await;
+    let mut rows = match rows {
+        Ok(rows) => rows,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)).into_response(),
+    };
+
+    let next_cursor = if rows.len() as i64 > limit {
+        rows.truncate(limit as usize);
+        rows.last().map(|r| format!("{}.{}", r.occurred_at.timestamp_micros(), r.id))
+    } else {
+        None
+    };
+    let entries: Vec<AccessEntry> = rows
+        .into_iter()
+        .map(|r| AccessEntry {
+            occurred_at: r.occurred_at,
+            key: scope.protector.decrypt(&r.object_key).unwrap_or(r.object_key),
+            principal: r.principal,
+            action: r.action,
+            bytes: r.bytes,
+            source_ip: r.source_ip,
+            status: r.status,
+            request_id: r.request_id,
+        })
+        .collect();
+
+    if csv {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/csv; charset=utf-8"));
+        headers.insert(
+            header::CONTENT_DISPOSITION,
+            HeaderValue::from_static("attachment; filename=\"access-log.csv\""),
+        );
+        if let Some(value) = next_cursor.as_deref().and_then(|c| HeaderValue::from_str(c).ok()) {
+            headers.insert(NEXT_CURSOR_HEADER, value);
+        }
+        return (StatusCode::OK, headers, to_csv(&entries)).into_response();
+    }
+    Json(AccessLogPage {
+        bucket: query.bucket,
+        entries,
+        next_cursor,
+    })
+    .into_response()
+}
@@ -0,0 +1,507 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use libp2p::{multiaddr::Protocol, Multiaddr};
+use neuro_client_sdk::{manifest_root_from_shards, shard_cid_matches, CidFormat, HashAlgorithm, Shard};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+use tokio::time::{timeout, Duration};
+
+use crate::handlers::s3::{validate_csrf, validate_s3_auth};
+use crate::p2p::SwarmRequest;
+use crate::replication::{self, MetadataOp};
+use crate::AppState;
+
+/// Matches neuro-uploader's own limits, so anything it writes registers.
+pub const MAX_UPLOADER_MANIFEST_BYTES: usize = 16 * 1024 * 1024;
+const MAX_UPLOADER_SHARDS: usize = 250_000;
+
+// ── UPLOADER INTEROP BRIDGE ──
+// neuro-uploader encrypts and erasure-codes client-side and talks to nodes
+// directly, so the gateway never sees those objects. Registering the
+// manifest records who owns it and mirrors every shard placement into
+// object_shards (object_cid = manifest_root), which puts CLI uploads under
+// the same proof daemon and sovereignty reporting as S3 objects.
+
+/// The subset of an uploader manifest the gateway keeps. Audit challenges and
+/// their expected tokens stay with the uploader; unknown fields are dropped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploaderManifest {
+    pub version: String,
+    pub salt: String,
+    pub manifest_root: String,
+    pub total_bytes: usize,
+    pub chunk_count: usize,
+    pub shards: Vec<UploaderManifestShard>,
+    /// Absent on manifests from before BLAKE3 CIDs, which are all SHA-256.
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+    #[serde(default)]
+    pub manifest_hash: String,
+    /// Last-known addresses by PeerId, for shards that name peers as a bare
+    /// `/p2p/<id>`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub peer_addrs: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploaderManifestShard {
+    pub chunk_index: usize,
+    pub shard_index: usize,
+    pub cid: String,
+    pub payload_len: usize,
+    pub data_shards: usize,
+    pub parity_shards: usize,
+    #[serde(default)]
+    pub peers: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RegisterQuery {
+    pub label: Option<String>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct ManifestSummary {
+    pub manifest_root: String,
+    pub label: Option<String>,
+    pub version: String,
+    pub total_bytes: i64,
+    pub chunk_count: i32,
+    pub shard_count: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct ShardPlacement {
+    pub shard_cid: String,
+    pub shard_index: i32,
+    pub peer_id: String,
+    pub country_code: String,
+    pub last_verified_at: Option<DateTime<Utc>>,
+}
+
+fn validate_manifest(manifest: &UploaderManifest) -> Result<(), String> {
+    validate_layout(manifest)?;
+    for shard in &manifest.shards {
+        if shard.peers.is_empty() {
+            return Err(format!("shard {} has no peers", shard.cid));
+        }
+        for peer in &shard.peers {
+            if peer_id_of(peer).is_none() {
+                return Err(format!("peer {:?} is not a multiaddr ending in /p2p/<peer id>", peer));
+            }
+        }
+    }
+    for (peer_id, addrs) in &manifest.peer_addrs {
+        if let Some(addr) = addrs.iter().find(|a| peer_id_of(a).as_ref() != Some(peer_id)) {
+            return Err(format!("address {:?} is not one of peer {}", addr, peer_id));
+        }
+    }
+    Ok(())
+}
+
+/// Everything but the placements: CIDs, erasure layout and the root. Upload
+/// sessions check this before any shard has a peer.
+pub(crate) fn validate_layout(manifest: &UploaderManifest) -> Result<(), String> {
+    if manifest.shards.is_empty() {
+        return Err("manifest has no shards".to_string());
+    }
+    if manifest.shards.len() > MAX_UPLOADER_SHARDS {
+        return Err(format!("manifest has more than {} shards", MAX_UPLOADER_SHARDS));
+    }
+    if manifest.total_bytes > i64::MAX as usize || manifest.chunk_count > i32::MAX as usize {
+        return Err("manifest size fields out of range".to_string());
+    }
+    // 2.3.0, 3.1.0, 4.1.0 and 5.1.0 manifests name shards by CIDv1, the
+    // others by sha256 hex. 3.x only adds gateway hints, 4.x an HMAC auth
+    // tag and 5.x recipient key wraps, none of which is checked here.
+    let cid_format = match manifest.version.as_str() {
+        "2.3.0" | "3.1.0" | "4.1.0" | "5.1.0" => CidFormat::V1,
+        _ => CidFormat::Sha256Hex,
+    };
+    for shard in &manifest.shards {
+        if CidFormat::of(&shard.cid) != Some(cid_format) {
+            return Err(format!("shard cid {:?} is not valid for manifest version {}", shard.cid, manifest.version));
+        }
+        if HashAlgorithm::of(&shard.cid) != Some(manifest.hash_algorithm) {
+            return Err(format!("shard cid {:?} is not hashed with {:?}", shard.cid, manifest.hash_algorithm));
+        }
+        if shard.chunk_index >= manifest.chunk_count {
+            return Err(format!("shard {} has chunk_index beyond chunk_count", shard.cid));
+        }
+        if shard.data_shards == 0 || shard.shard_index >= shard.data_shards + shard.parity_shards {
+            return Err(format!("shard {} has an invalid erasure layout", shard.cid));
+        }
+    }
+
+    // The root is a Merkle root over the shard CIDs in manifest order, so a
+    // reordered or edited shard list fails here.
+    let templates: Vec<Shard> = manifest
+        .shards
+        .iter()
+        .map(|s| Shard {
+            chunk_index: s.chunk_index,
+            shard_index: s.shard_index,
+            cid: s.cid.clone(),
+            bytes: Vec::new(),
+            payload_len: s.payload_len,
+            data_shards: s.data_shards,
+            parity_shards: s.parity_shards,
+        })
+        .collect();
+    if manifest_root_from_shards(&templates) != manifest.manifest_root {
+        return Err("manifest_root does not match the shard list".to_string());
+    }
+    Ok(())
+}
+
+fn peer_id_of(addr: &str) -> Option<String> {
+    let addr: Multiaddr = addr.parse().ok()?;
+    match addr.iter().last()? {
+        Protocol::P2p(peer_id) => Some(peer_id.to_string()),
+        _ => None,
+    }
+}
+
+fn peer_addrs(manifest: &UploaderManifest) -> Vec<Multiaddr> {
+    let mut addrs: Vec<Multiaddr> = manifest
+        .shards
+        .iter()
+        .flat_map(|s| s.peers.iter())
+        .chain(manifest.peer_addrs.values().flatten())
+        .filter_map(|p| p.parse().ok())
+        .collect();
+    addrs.sort();
+    addrs.dedup();
+    addrs
+}
+
+pub(crate) async fn owned_manifest(
+    state: &AppState,
+    manifest_root: &str,
+    email: &str,
+) -> Result<UploaderManifest, (StatusCode, String)> {
+    let row = sqlx::query_as::<_, (String, serde_json::Value)>(
+        "SELECT owner_email, manifest_json FROM uploader_manifests WHERE manifest_root = $1",
+    )
+    .bind(manifest_root)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)))?;
+
+    match row {
+        Some((owner, json)) if owner == email => serde_json::from_value(json)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Stored manifest unreadable: {}", e))),
+        // Other users' manifests are indistinguishable from missing ones.
+        _ => Err((StatusCode::NOT_FOUND, "Manifest not found".to_string())),
+    }
+}
+
+// ── POST /api/manifests ──
+pub async fn register_manifest(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<RegisterQuery>,
+    headers: HeaderMap,
+    Json(manifest): Json<UploaderManifest>,
+) -> impl IntoResponse {
+    if let Err(err) = validate_csrf(&headers) {
+        return err.into_response();
+    }
+    let user_email = match validate_s3_auth(&headers, &state) {
+        Ok(email) => email,
+        Err(err) => return err.into_response(),
+    };
+    if let Err(reason) = validate_manifest(&manifest) {
+        return (StatusCode::BAD_REQUEST, reason).into_response();
+    }
+    tracing::Span::current().record("cid", manifest.manifest_root.as_str());
+
+    let label = query.label.filter(|l| !l.trim().is_empty());
+    if let Err(err) = record_manifest(&state, &user_email, label, &manifest).await {
+        return err.into_response();
+    }
+    (
+        StatusCode::CREATED,
+        Json(serde_json::json!({
+            "manifest_root": manifest.manifest_root,
+            "shards": manifest.shards.len(),
+            "chunk_count": manifest.chunk_count,
+            "total_bytes": manifest.total_bytes,
+        })),
+    )
+        .into_response()
+}
+
+/// Stores a validated manifest for `user_email`, mirrors its placements into
+/// object_shards and replicates it.
+pub(crate) async fn record_manifest(
+    state: &AppState,
+    user_email: &str,
+    label: Option<String>,
+    manifest: &UploaderManifest,
+) -> Result<(), (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e));
+    let manifest_json = serde_json::to_value(manifest)
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Manifest serialization failed".to_string()))?;
+
+    let mut tx = state.db.begin().await.map_err(db_error)?;
+    // A root already registered by someone else is left alone: the same
+    // ciphertext uploaded twice still belongs to whoever registered it first.
+    let inserted = sqlx::query(
+        r#"
+        INSERT INTO uploader_manifests
+            (manifest_root, owner_email, label, version, total_bytes, chunk_count, shard_count, manifest_json)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (manifest_root) DO UPDATE SET
+            label = excluded.label,
+            version = excluded.version,
+            manifest_json = excluded.manifest_json
+        WHERE uploader_manifests.owner_email = excluded.owner_email
+        "#,
+    )
+    .bind(&manifest.manifest_root)
+    .bind(user_email)
+    .bind(&label)
+    .bind(&manifest.version)
+    .bind(manifest.total_bytes as i64)
+    .bind(manifest.chunk_count as i32)
+    .bind(manifest.shards.len() as i32)
+    .bind(&manifest_json)
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+    if inserted.rows_affected() == 0 {
+        return Err((StatusCode::CONFLICT, "Manifest registered by another user".to_string()));
+    }
+
+    // Placements keep their verification history across re-registration;
+    // the first listed peer is the one the proof daemon challenges.
+    for (ordinal, shard) in manifest.shards.iter().enumerate() {
+        let peer_id = peer_id_of(&shard.peers[0]).unwrap_or_default();
+        sqlx::query(
+            r#"
+            INSERT INTO object_shards (object_cid, shard_cid, shard_index, peer_id)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (object_cid, shard_index) DO NOTHING
+            "#,
+        )
+        .bind(&manifest.manifest_root)
+        .bind(&shard.cid)
+        .bind(ordinal as i32)
+        .bind(&peer_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    }
+    tx.commit().await.map_err(db_error)?;
+
+    replication::publish(state, MetadataOp::UpsertUploaderManifest {
+        manifest_root: manifest.manifest_root.clone(),
+        owner_email: user_email.to_string(),
+        label,
+        version: manifest.version.clone(),
+        total_bytes: manifest.total_bytes as i64,
+        chunk_count: manifest.chunk_count as i32,
+        shard_count: manifest.shards.len() as i32,
+        manifest_json,
+    })
+    .await;
+
+    let _ = state.p2p_tx.send(SwarmRequest::Dial { addrs: peer_addrs(manifest) }).await;
+
+    tracing::info!(
+        "Registered uploader manifest {} ({} shards, {} bytes)",
+        manifest.manifest_root,
+        manifest.shards.len(),
+        manifest.total_bytes
+    );
+    Ok(())
+}
+
+// ── GET /api/manifests ──
+pub async fn list_manifests(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let user_email = match validate_s3_auth(&headers, &state) {
+        Ok(email) => email,
+        Err(err) => return err.into_response(),
+    };
+    let rows = sqlx::query_as::<_, ManifestSummary>(
+        r#"
+        SELECT manifest_root, label, version, total_bytes, chunk_count, shard_count, created_at
+        FROM uploader_manifests
+        WHERE owner_email = $1
+        ORDER BY created_at DESC
+        LIMIT 1000
+        "#,
+    )
+    .bind(&user_email)
+    .fetch_all(&state.db)
+    .await;
+    match rows {
+        Ok(rows) => Json(rows).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)).into_response(),
+    }
+}
+
+// ── GET /api/manifests/:root ──
+// Returns the manifest with each shard's current placement and last proof,
+// and dials its peers so follow-up shard fetches and audits can reach them.
+pub async fn locate_manifest(
+    State(state): State<Arc<AppState>>,
+    Path(manifest_root): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let user_email = match validate_s3_auth(&headers, &state) {
+        Ok(email) => email,
+        Err(err) => return err.into_response(),
+    };
+    tracing::Span::current().record("cid", manifest_root.as_str());
+    let manifest = match owned_manifest(&state, &manifest_root, &user_email).await {
+        Ok(m) => m,
+        Err(err) => return err.into_response(),
+    };
+
+    let placements = sqlx::query_as::<_, ShardPlacement>(
+        r#"
+        SELECT shard_cid, shard_index, peer_id, country_code, last_verified_at
+        FROM object_shards
+        WHERE object_cid = $1
+        ORDER BY shard_index
+        "#,
+    )
+    .bind(&manifest_root)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default();
+
+    let _ = state.p2p_tx.send(SwarmRequest::Dial { addrs: peer_addrs(&manifest) }).await;
+
+    let verified = placements.iter().filter(|p| p.last_verified_at.is_some()).count();
+    Json(serde_json::json!({
+        "manifest": manifest,
+        "placements": placements,
+        "verified_shards": verified,
+    }))
+    .into_response()
+}
+
+// ── DELETE /api/manifests/:root ──
+// Forgets a manifest and its placements. The shards themselves are deleted
+// from the nodes by `neuro-uploader delete`, which calls this last.
+pub async fn unregister_manifest(
+    State(state): State<Arc<AppState>>,
+    Path(manifest_root): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(err) = validate_csrf(&headers) {
+        return err.into_response();
+    }
+    let user_email = match validate_s3_auth(&headers, &state) {
+        Ok(email) => email,
+        Err(err) => return err.into_response(),
+    };
+    tracing::Span::current().record("cid", manifest_root.as_str());
+    let manifest = match owned_manifest(&state, &manifest_root, &user_email).await {
+        Ok(m) => m,
+        Err(err) => return err.into_response(),
+    };
+
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)).into_response();
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return db_error(e),
+    };
+    for statement in [
+        "DELETE FROM object_shards WHERE object_cid = $1",
+        "DELETE FROM uploader_manifests WHERE manifest_root = $1",
+    ] {
+        if let Err(e) = sqlx::query(statement).bind(&manifest_root).execute(&mut *tx).await {
+            return db_error(e);
+        }
+    }
+    if let Err(e) = tx.commit().await {
+        return db_error(e);
+    }
+    for shard in &manifest.shards {
+        state.edge_cache.invalidate(&shard.cid).await;
+    }
+
+    replication::publish(&state, MetadataOp::DeleteUploaderManifest {
+        manifest_root: manifest_root.clone(),
+    })
+    .await;
+    tracing::info!("Unregistered uploader manifest {}", manifest_root);
+    StatusCode::NO_CONTENT.into_response()
+}
+
+// ── GET /api/manifests/:root/shards/:cid ──
+// Serves one shard's ciphertext from the swarm; the caller decodes and
+// decrypts client-side exactly as neuro-uploader would.
+pub async fn get_manifest_shard(
+    State(state): State<Arc<AppState>>,
+    Path((manifest_root, shard_cid)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let user_email = match validate_s3_auth(&headers, &state) {
+        Ok(email) => email,
+        Err(err) => return err.into_response(),
+    };
+    tracing::Span::current().record("cid", shard_cid.as_str());
+    let manifest = match owned_manifest(&state, &manifest_root, &user_email).await {
+        Ok(m) => m,
+        Err(err) => return err.into_response(),
+    };
+    let Some(shard) = manifest.shards.iter().find(|s| s.cid == shard_cid) else {
+        return (StatusCode::NOT_FOUND, "Shard not in manifest").into_response();
+    };
+
+    let data = match state.edge_cache.get(&shard_cid).await {
+        Some(cached) => cached,
+        None => {
+            let mut found = None;
+            for peer in &shard.peers {
+                let (tx, rx) = oneshot::channel();
+                let req = SwarmRequest::Retrieve {
+                    cid: shard_cid.clone(),
+                    preferred_peer_id: peer_id_of(peer),
+                    voucher: None,
+                    tx,
+                };
+                if state.p2p_tx.send(req).await.is_err() {
+                    return (StatusCode::SERVICE_UNAVAILABLE, "Swarm unavailable").into_response();
+                }
+                if let Ok(Ok(ack)) = timeout(Duration::from_secs(8), rx).await {
+                    let matches = ack
+                        .data
+                        .as_ref()
+                        .is_some_and(|bytes| shard_cid_matches(&shard_cid, bytes));
+                    if matches {
+                        found = ack.data;
+                        break;
+                    }
+                }
+            }
+            let Some(bytes) = found else {
+                return (StatusCode::NOT_FOUND, "Shard not found in swarm").into_response();
+            };
+            let bytes = Bytes::from(bytes);
+            state.edge_cache.insert(shard_cid.clone(), bytes.clone()).await;
+            bytes
+        }
+    };
+
+    let mut resp_headers = HeaderMap::new();
+    resp_headers.insert("Content-Type", HeaderValue::from_static("application/octet-stream"));
+    resp_headers.insert("Cache-Control", HeaderValue::from_static("private, max-age=3600, immutable"));
+    (StatusCode::OK, resp_headers, Body::from(data)).into_response()
+}
@@ -1,5 +1,23 @@
 pub mod auth;
 pub mod s3;
+pub mod s3_error;
 pub mod zk;
 pub mod compliance;
 pub mod nodes;
+pub mod manifests;
+pub mod shares;
+pub mod uploads;
+pub mod tagging;
+pub mod object_headers;
+pub mod integrity;
+pub mod lifecycle;
+pub mod bulk_delete;
+pub mod policy;
+pub mod estimate;
+pub mod pricing;
+pub mod logs;
+pub mod backups;
+pub mod verify;
+pub mod health;
+pub mod limits;
+pub mod vouchers;
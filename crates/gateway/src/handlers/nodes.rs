@@ -1,12 +1,20 @@
 use axum::{
-    extract::State,
+    extract::{Path, Query, State},
     http::HeaderMap,
     http::StatusCode,
     response::IntoResponse,
     Json,
 };
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use libp2p::PeerId;
+use neuro_protocol::{registration_payload, verify_registration, KeyRotation};
 use std::sync::Arc;
 use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+use tokio::time::{timeout, Duration};
+use crate::handlers::s3::validate_s3_auth;
+use crate::p2p::SwarmRequest;
 use crate::AppState;
 
 #[derive(Deserialize)]
@@ -16,6 +24,128 @@ pub struct NodeRegisterRequest {
     pub capacity_gb: i64,
     pub declared_location: String, // e.g. "IN-KA" (Karnataka, India)
     pub latency_ms: Option<f64>, // Provided by P2P ping metric or client header
+    /// From POST /api/nodes/register/challenge; single use.
+    pub challenge: Option<String>,
+    /// The node's libp2p public key, protobuf-encoded, base64.
+    pub public_key: Option<String>,
+    /// Signature over `registration_payload`, base64.
+    pub signature: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct NodeChallengeRequest {
+    pub peer_id: String,
+}
+
+/// How long a node has to sign and return its registration challenge.
+const REGISTRATION_CHALLENGE_TTL_SECS: i64 = 300;
+
+// ── POST /api/nodes/register/challenge ──
+// First half of the registration handshake: a fresh challenge for the peer
+// id, replacing any earlier one. The node signs it with its libp2p key and
+// sends the signature with its registration.
+pub async fn registration_challenge(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(payload): Json<NodeChallengeRequest>,
+) -> impl IntoResponse {
+    let provided_secret = headers
+        .get("x-node-secret")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    if provided_secret.is_empty() || provided_secret != state.node_shared_secret.as_str() {
+        return (StatusCode::UNAUTHORIZED, "Unauthorized node registration").into_response();
+    }
+    if payload.peer_id.parse::<PeerId>().is_err() {
+        return (StatusCode::BAD_REQUEST, "Invalid peer_id").into_response();
+    }
+
+    let mut challenge = [0u8; 32];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut challenge);
+    let challenge = hex::encode(challenge);
+    let _ = sqlx::query("DELETE FROM node_registration_challenges WHERE expires_at < NOW()")
+        .execute(&state.db)
+        .await;
+    let res = sqlx::query_scalar::<_, DateTime<Utc>>(
+        r#"
+        INSERT INTO node_registration_challenges (peer_id, challenge, expires_at)
+        VALUES ($1, $2, NOW() + make_interval(secs => $3))
+        ON CONFLICT (peer_id) DO UPDATE SET
+            challenge = excluded.challenge,
+            expires_at = excluded.expires_at
+        RETURNING expires_at
+        "#
+    )
+    .bind(&payload.peer_id)
+    .bind(&challenge)
+    .bind(REGISTRATION_CHALLENGE_TTL_SECS as f64)
+    .fetch_one(&state.db)
+    .await;
+
+    match res {
+        Ok(expires_at) => Json(serde_json::json!({
+            "peer_id": payload.peer_id,
+            "challenge": challenge,
+            "expires_at": expires_at,
+        }))
+        .into_response(),
+        Err(e) => {
+            tracing::error!("Registration challenge failed: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Registration DB Error").into_response()
+        }
+    }
+}
+
+/// Consumes the peer's outstanding challenge and checks the registration was
+/// signed by the key behind its peer id.
+async fn verify_registration_proof(
+    state: &AppState,
+    peer_id: &PeerId,
+    payload: &NodeRegisterRequest,
+) -> Result<(), (StatusCode, &'static str)> {
+    let (Some(challenge), Some(public_key), Some(signature)) =
+        (&payload.challenge, &payload.public_key, &payload.signature)
+    else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Registration must be signed: request a challenge from /api/nodes/register/challenge",
+        ));
+    };
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let (Ok(public_key), Ok(signature)) = (b64.decode(public_key), b64.decode(signature)) else {
+        return Err((StatusCode::BAD_REQUEST, "public_key and signature must be base64"));
+    };
+
+    // Deleting the row makes every challenge single-use, whatever the outcome.
+    let issued = sqlx::query_scalar::<_, DateTime<Utc>>(
+        "DELETE FROM node_registration_challenges WHERE peer_id = $1 AND challenge = $2 RETURNING expires_at"
+    )
+    .bind(&payload.peer_id)
+    .bind(challenge)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Registration challenge lookup failed: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, "Registration DB Error")
+    })?;
+    match issued {
+        Some(expires_at) if expires_at > Utc::now() => {}
+        _ => return Err((StatusCode::FORBIDDEN, "Registration challenge unknown, used or expired")),
+    }
+
+    let signed = registration_payload(
+        challenge,
+        &payload.peer_id,
+        &payload.wallet_address,
+        payload.capacity_gb,
+        &payload.declared_location,
+    );
+    if !verify_registration(peer_id, &public_key, &signature, &signed) {
+        tracing::warn!("Rejected registration for {}: signature does not match its peer key", payload.peer_id);
+        return Err((StatusCode::FORBIDDEN, "Registration signature does not prove ownership of peer_id"));
+    }
+    Ok(())
 }
 
 #[derive(Serialize)]
@@ -35,13 +165,13 @@ pub async fn register_provider_node(
         .and_then(|v| v.to_str().ok())
         .unwrap_or_default();
 
-    if provided_secret.is_empty() || provided_secret != state.node_shared_secret {
+    if provided_secret.is_empty() || provided_secret != state.node_shared_secret.as_str() {
         return (StatusCode::UNAUTHORIZED, "Unauthorized node registration").into_response();
     }
 
-    if !is_valid_peer_id(&payload.peer_id) {
+    let Ok(peer_id) = payload.peer_id.parse::<PeerId>() else {
         return (StatusCode::BAD_REQUEST, "Invalid peer_id").into_response();
-    }
+    };
     if !is_valid_wallet_address(&payload.wallet_address) {
         return (StatusCode::BAD_REQUEST, "Invalid wallet_address").into_response();
     }
@@ -61,6 +191,28 @@ pub async fn register_provider_node(
         }
     }
 
+    match sqlx::query_scalar::<_, String>("SELECT new_peer_id FROM node_key_rotations WHERE old_peer_id = $1")
+        .bind(&payload.peer_id)
+        .fetch_optional(&state.db)
+        .await
+    {
+        Ok(None) => {}
+        Ok(Some(new_peer_id)) => {
+            return (StatusCode::CONFLICT, format!("peer_id was retired by a key rotation to {}", new_peer_id)).into_response();
+        }
+        Err(e) => {
+            tracing::error!("Key rotation lookup failed: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Registration DB Error".to_string()).into_response();
+        }
+    }
+
+    // ── PEER KEY OWNERSHIP ──
+    // Checked last: it consumes the challenge, so a request that fails the
+    // cheaper checks above can be corrected and resent with the same one.
+    if let Err(err) = verify_registration_proof(&state, &peer_id, &payload).await {
+        return err.into_response();
+    }
+
     // ── COLLATERAL STAKING (SYBIL PREVENTION) ──
     // Nodes are created as INACTIVE by default. A separate worker or smart contract listener
     // must verify their NeuroToken stake before they are marked as active and receive data.
@@ -99,6 +251,358 @@ pub async fn register_provider_node(
     }
 }
 
+/// How old a rotation announcement may be, and how far ahead of the
+/// gateway's clock.
+const KEY_ROTATION_MAX_AGE_MS: i64 = 7 * 24 * 3600 * 1000;
+const KEY_ROTATION_MAX_SKEW_MS: i64 = 5 * 60 * 1000;
+
+// ── POST /api/nodes/rotate ──
+// A node replaced its identity key (`neuro-node rotate`). The announcement
+// is signed by both keys, so only the holder of the old key can move its
+// record, and only to a key it also holds. Shard placements, pending upload
+// shards, price history, earnings, voucher redemptions, reputation and
+// retrieval stats follow the node to the new peer id, and so do its penalties; sending the same
+// rotation again is harmless.
+pub async fn rotate_node_key(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(rotation): Json<KeyRotation>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let provided_secret = headers
+        .get("x-node-secret")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if provided_secret.is_empty() || provided_secret != state.node_shared_secret.as_str() {
+        return Err((StatusCode::UNAUTHORIZED, "Unauthorized node key rotation".to_string()));
+    }
+    if !rotation.verify() {
+        tracing::warn!("Rejected key rotation for {}: signatures do not verify", rotation.old_peer_id);
+        return Err((StatusCode::FORBIDDEN, "Rotation must be signed by both the old and the new key".to_string()));
+    }
+    let age_ms = Utc::now().timestamp_millis() - rotation.timestamp_ms.min(i64::MAX as u64) as i64;
+    if !(-KEY_ROTATION_MAX_SKEW_MS..=KEY_ROTATION_MAX_AGE_MS).contains(&age_ms) {
+        return Err((StatusCode::BAD_REQUEST, "Rotation timestamp is too old or in the future".to_string()));
+    }
+
+    let db_error = |e: sqlx::Error| {
+        tracing::error!("Key rotation failed: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, "Key rotation DB Error".to_string())
+    };
+    let mut tx = state.db.begin().await.map_err(db_error)?;
+    // Serializes concurrent rotations of the same node.
+    let registered = sqlx::query_scalar::<_, String>("SELECT peer_id FROM nodes WHERE peer_id = $1 FOR UPDATE")
+        .bind(&rotation.old_peer_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    let previous = sqlx::query_scalar::<_, String>("SELECT new_peer_id FROM node_key_rotations WHERE old_peer_id = $1")
+        .bind(&rotation.old_peer_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    match previous {
+        Some(new_peer_id) if new_peer_id == rotation.new_peer_id => {
+            return Ok(Json(serde_json::json!({
+                "status": "already_rotated",
+                "old_peer_id": rotation.old_peer_id,
+                "new_peer_id": rotation.new_peer_id,
+            })));
+        }
+        Some(new_peer_id) => {
+            return Err((StatusCode::CONFLICT, format!("peer_id was already rotated to {}", new_peer_id)));
+        }
+        None => {}
+    }
+    if registered.is_none() {
+        return Err((StatusCode::NOT_FOUND, "old_peer_id is not a registered node".to_string()));
+    }
+    let taken = sqlx::query_scalar::<_, i64>(
+        "SELECT (SELECT COUNT(*) FROM nodes WHERE peer_id = $1) + (SELECT COUNT(*) FROM node_key_rotations WHERE old_peer_id = $1)"
+    )
+    .bind(&rotation.new_peer_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_error)?;
+    if taken > 0 {
+        return Err((StatusCode::CONFLICT, "new_peer_id is already registered or retired".to_string()));
+    }
+
+    for statement in [
+        "UPDATE nodes SET peer_id = $2, last_seen = CURRENT_TIMESTAMP WHERE peer_id = $1",
+        "UPDATE object_shards SET peer_id = $2 WHERE peer_id = $1",
+        "UPDATE upload_session_shards SET peer_id = $2 WHERE peer_id = $1",
+        "UPDATE node_price_history SET peer_id = $2 WHERE peer_id = $1",
+        "UPDATE shard_decode_failures SET peer_id = $2 WHERE peer_id = $1",
+        // Confirmed receipts stay under the key that signed them.
+        "UPDATE shard_deletions SET peer_id = $2 WHERE peer_id = $1 AND confirmed_at IS NULL",
+        // A new key has no standing of its own; whatever the sentinel or
+        // retrievals recorded under it gives way to the node's history.
+        "DELETE FROM node_reputation WHERE peer_id = $2",
+        "UPDATE node_reputation SET peer_id = $2 WHERE peer_id = $1",
+        "DELETE FROM node_retrieval_stats WHERE peer_id = $2",
+        "UPDATE node_retrieval_stats SET peer_id = $2 WHERE peer_id = $1",
+        // Earnings are money owed, so they are added up rather than replaced.
+        r#"
+        INSERT INTO node_earnings (peer_id, egress_bytes, earned_inr, withheld_inr, updated_at)
+        SELECT $2, egress_bytes, earned_inr, withheld_inr, NOW() FROM node_earnings WHERE peer_id = $1
+        ON CONFLICT (peer_id) DO UPDATE SET
+            egress_bytes = node_earnings.egress_bytes + excluded.egress_bytes,
+            earned_inr = node_earnings.earned_inr + excluded.earned_inr,
+            withheld_inr = node_earnings.withheld_inr + excluded.withheld_inr,
+            updated_at = NOW()
+        "#,
+        "DELETE FROM node_earnings WHERE peer_id = $1",
+        // A voucher is redeemed once per node, whichever key it holds.
+        "UPDATE voucher_redemptions SET peer_id = $2 WHERE peer_id = $1",
+    ] {
+        sqlx::query(statement)
+            .bind(&rotation.old_peer_id)
+            .bind(&rotation.new_peer_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+    }
+    crate::slashing::carry_over(&mut tx, &state, &rotation.old_peer_id, &rotation.new_peer_id)
+        .await
+        .map_err(db_error)?;
+    sqlx::query("INSERT INTO node_key_rotations (old_peer_id, new_peer_id, announced_at_ms) VALUES ($1, $2, $3)")
+        .bind(&rotation.old_peer_id)
+        .bind(&rotation.new_peer_id)
+        .bind(rotation.timestamp_ms as i64)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    tx.commit().await.map_err(db_error)?;
+
+    tracing::info!("Node {} rotated its identity key to {}", rotation.old_peer_id, rotation.new_peer_id);
+    Ok(Json(serde_json::json!({
+        "status": "rotated",
+        "old_peer_id": rotation.old_peer_id,
+        "new_peer_id": rotation.new_peer_id,
+    })))
+}
+
+/// A node's current sentinel policy: reputation, action and payout rate.
+/// Asks the sentinel first and falls back to the last policy the leader
+/// recorded in `node_reputation`.
+pub async fn get_node_policy(
+    State(state): State<Arc<AppState>>,
+    Path(peer_id): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let provided_secret = headers
+        .get("x-node-secret")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    if provided_secret.is_empty() || provided_secret != state.node_shared_secret.as_str() {
+        return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+    }
+    if !is_valid_peer_id(&peer_id) {
+        return (StatusCode::BAD_REQUEST, "Invalid peer_id").into_response();
+    }
+
+    if let Some(client) = &state.sentinel {
+        match client.peer_policy(&peer_id).await {
+            Ok(Some(policy)) => {
+                return Json(serde_json::json!({
+                    "peer_id": policy.peer,
+                    "reputation": policy.reputation,
+                    "action": policy.action,
+                    "anomaly_level": policy.anomaly_level,
+                    "churn_probability": policy.churn_probability,
+                    "price_per_gb": policy.price_per_gb,
+                    "redundancy_multiplier": policy.recommended_redundancy_multiplier,
+                    "cohort_id": policy.cohort_id,
+                    "cohort_size": policy.cohort_size,
+                    "collusion_risk": policy.collusion_risk,
+                    "windows_to_recovery": policy.windows_to_recovery,
+                    "explanations": policy.explanations,
+                    "source": "sentinel",
+                }))
+                .into_response();
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Sentinel policy lookup for {} failed: {}", peer_id, e),
+        }
+    }
+
+    let row = sqlx::query_as::<_, (f64, String, String, f64, f64, f64, String, i32, f64, serde_json::Value)>(
+        r#"
+        SELECT reputation, action, anomaly_level, churn_probability, price_per_gb, redundancy_multiplier,
+               cohort_id, cohort_size, collusion_risk, explanations
+        FROM node_reputation WHERE peer_id = $1
+        "#
+    )
+    .bind(&peer_id)
+    .fetch_optional(&state.db)
+    .await;
+
+    match row {
+        Ok(Some((
+            reputation,
+            action,
+            anomaly_level,
+            churn_probability,
+            price_per_gb,
+            redundancy_multiplier,
+            cohort_id,
+            cohort_size,
+            collusion_risk,
+            explanations,
+        ))) => {
+            Json(serde_json::json!({
+                "peer_id": peer_id,
+                "reputation": reputation,
+                "action": action,
+                "anomaly_level": anomaly_level,
+                "churn_probability": churn_probability,
+                "price_per_gb": price_per_gb,
+                "redundancy_multiplier": redundancy_multiplier,
+                "cohort_id": cohort_id,
+                "cohort_size": cohort_size,
+                "collusion_risk": collusion_risk,
+                "explanations": explanations,
+                "source": "recorded",
+            }))
+            .into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "No sentinel policy for this node yet").into_response(),
+        Err(e) => {
+            tracing::error!("Node policy lookup failed: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database Error").into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PriceRecordsQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct PriceRecord {
+    pub price_per_gb: f64,
+    pub reputation: f64,
+    pub action: String,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Every payout rate the sentinel has set for a node, newest first, so the
+/// node can check its earnings against the rate in effect at the time.
+pub async fn get_node_prices(
+    State(state): State<Arc<AppState>>,
+    Path(peer_id): Path<String>,
+    Query(query): Query<PriceRecordsQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let provided_secret = headers
+        .get("x-node-secret")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    if provided_secret.is_empty() || provided_secret != state.node_shared_secret.as_str() {
+        return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+    }
+    if !is_valid_peer_id(&peer_id) {
+        return (StatusCode::BAD_REQUEST, "Invalid peer_id").into_response();
+    }
+
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    let records = sqlx::query_as::<_, PriceRecord>(
+        r#"
+        SELECT price_per_gb, reputation, action, recorded_at
+        FROM node_price_history WHERE peer_id = $1
+        ORDER BY recorded_at DESC, id DESC
+        LIMIT $2
+        "#
+    )
+    .bind(&peer_id)
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await;
+
+    match records {
+        Ok(records) => Json(serde_json::json!({
+            "peer_id": peer_id,
+            "currency": neuro_protocol::sentinel::PRICE_CURRENCY,
+            "records": records,
+        }))
+        .into_response(),
+        Err(e) => {
+            tracing::error!("Node price lookup failed: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database Error").into_response()
+        }
+    }
+}
+
+/// Where a node can be dialed now, for `neuro-uploader rebind --auto` after
+/// a node's address changed. Resolved through the gateway's DHT view.
+pub async fn locate_peer(
+    State(state): State<Arc<AppState>>,
+    Path(peer_id): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(err) = validate_s3_auth(&headers, &state) {
+        return err.into_response();
+    }
+    let Ok(parsed) = peer_id.parse::<libp2p::PeerId>() else {
+        return (StatusCode::BAD_REQUEST, "Invalid peer_id").into_response();
+    };
+
+    let (tx, rx) = oneshot::channel();
+    if state.p2p_tx.send(SwarmRequest::LocatePeer { peer_id: parsed, tx }).await.is_err() {
+        return (StatusCode::SERVICE_UNAVAILABLE, "Storage network queue unavailable").into_response();
+    }
+    match timeout(Duration::from_secs(15), rx).await {
+        Ok(Ok(addrs)) if !addrs.is_empty() => Json(serde_json::json!({
+            "peer_id": peer_id,
+            "addrs": addrs.iter().map(|a| a.to_string()).collect::<Vec<_>>(),
+        }))
+        .into_response(),
+        Ok(Ok(_)) => (StatusCode::NOT_FOUND, "Peer not found in the DHT").into_response(),
+        _ => (StatusCode::GATEWAY_TIMEOUT, "Peer lookup timed out").into_response(),
+    }
+}
+
+/// Protocol version and features a node last advertised over identify.
+pub async fn get_node_capabilities(
+    State(state): State<Arc<AppState>>,
+    Path(peer_id): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(err) = validate_s3_auth(&headers, &state) {
+        return err.into_response();
+    }
+    if !is_valid_peer_id(&peer_id) {
+        return (StatusCode::BAD_REQUEST, "Invalid peer_id").into_response();
+    }
+
+    let row = sqlx::query_as::<_, (Option<String>, Option<String>, Option<Vec<String>>, Option<chrono::DateTime<chrono::Utc>>)>(
+        "SELECT protocol_version, agent_version, features, capabilities_updated_at FROM nodes WHERE peer_id = $1",
+    )
+    .bind(&peer_id)
+    .fetch_optional(&state.db)
+    .await;
+
+    match row {
+        Ok(Some((Some(protocol_version), agent_version, features, updated_at))) => Json(serde_json::json!({
+            "peer_id": peer_id,
+            "protocol_version": protocol_version,
+            "agent_version": agent_version,
+            "features": features.unwrap_or_default(),
+            "updated_at": updated_at,
+        }))
+        .into_response(),
+        Ok(_) => (StatusCode::NOT_FOUND, "Node has not advertised its capabilities yet").into_response(),
+        Err(e) => {
+            tracing::error!("Node capabilities lookup failed: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database Error").into_response()
+        }
+    }
+}
+
 fn is_valid_peer_id(value: &str) -> bool {
     if value.len() < 10 || value.len() > 128 {
         return false;
@@ -0,0 +1,154 @@
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+// Object headers: the `Content-Type`, `Content-Disposition` and
+// `x-amz-meta-*` pairs sent with a PUT. They are kept in the sealed object
+// metadata next to the encryption key, so the database only ever sees them
+// encrypted, and are sent back on GET and HEAD. A GET may override the
+// type and disposition with S3's `response-content-type` and
+// `response-content-disposition` query parameters.
+
+const USER_METADATA_PREFIX: &str = "x-amz-meta-";
+/// Key in the sealed object metadata.
+const METADATA_KEY: &str = "headers";
+/// AES-GCM nonce and tag around a gateway-encrypted object body.
+const SEALED_BODY_OVERHEAD: i64 = 12 + 16;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct ObjectHeaders {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_disposition: Option<String>,
+    /// `x-amz-meta-*` pairs, names lowercased and without the prefix.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub user: BTreeMap<String, String>,
+    /// Length of the object as it was uploaded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_length: Option<u64>,
+}
+
+fn header_text(name: &str, value: &HeaderValue) -> Result<String, (StatusCode, String)> {
+    value
+        .to_str()
+        .map(|v| v.trim().to_string())
+        .map_err(|_| (StatusCode::BAD_REQUEST, format!("InvalidArgument: {} is not valid text", name)))
+}
+
+impl ObjectHeaders {
+    /// The headers of a PUT worth keeping. The caller fills in
+    /// `content_length` once the body has been read.
+    pub(crate) fn from_put(headers: &HeaderMap) -> Result<Self, (StatusCode, String)> {
+        let mut kept = Self::default();
+        if let Some(value) = headers.get(header::CONTENT_TYPE) {
+            kept.content_type = Some(header_text("Content-Type", value)?).filter(|v| !v.is_empty());
+        }
+        if let Some(value) = headers.get(header::CONTENT_DISPOSITION) {
+            kept.content_disposition = Some(header_text("Content-Disposition", value)?).filter(|v| !v.is_empty());
+        }
+        for (name, value) in headers {
+            if let Some(field) = name.as_str().strip_prefix(USER_METADATA_PREFIX) {
+                if field.is_empty() {
+                    continue;
+                }
+                kept.user.insert(field.to_string(), header_text(name.as_str(), value)?);
+            }
+        }
+        Ok(kept)
+    }
+
+    /// Reads the headers out of decrypted object metadata. Objects stored
+    /// before headers were kept come back with none.
+    pub(crate) fn read(metadata: &serde_json::Value) -> Self {
+        metadata
+            .get(METADATA_KEY)
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default()
+    }
+
+    /// Adds the headers to object metadata that is about to be sealed.
+    pub(crate) fn write(&self, metadata: &mut serde_json::Value) {
+        if let (Some(map), Ok(value)) = (metadata.as_object_mut(), serde_json::to_value(self)) {
+            map.insert(METADATA_KEY.to_string(), value);
+        }
+    }
+
+    /// Length of the object as uploaded, given the size stored for it and
+    /// its decrypted metadata.
+    pub(crate) fn content_length(&self, stored_size: i64, metadata: &serde_json::Value) -> u64 {
+        if let Some(len) = self.content_length {
+            return len;
+        }
+        let sealed = metadata.get("encryption_key").is_some();
+        let size = if sealed { stored_size - SEALED_BODY_OVERHEAD } else { stored_size };
+        size.max(0) as u64
+    }
+
+    /// Sets the object's headers on a GET or HEAD response for `key`,
+    /// replacing the default `application/octet-stream`.
+    pub(crate) fn apply(
+        &self,
+        key: &str,
+        params: &HashMap<String, String>,
+        out: &mut HeaderMap,
+    ) -> Result<(), (StatusCode, String)> {
+        let content_type = params
+            .get("response-content-type")
+            .or(self.content_type.as_ref());
+        if let Some(content_type) = content_type {
+            out.insert(header::CONTENT_TYPE, header_value("response-content-type", content_type)?);
+        }
+        let disposition = params
+            .get("response-content-disposition")
+            .or(self.content_disposition.as_ref());
+        if let Some(disposition) = disposition {
+            let disposition = with_filename(disposition, key);
+            out.insert(
+                header::CONTENT_DISPOSITION,
+                header_value("response-content-disposition", &disposition)?,
+            );
+        }
+        for (field, value) in &self.user {
+            let name = HeaderName::from_bytes(format!("{}{}", USER_METADATA_PREFIX, field).as_bytes());
+            // Stored values came from valid headers, so neither can fail.
+            if let (Ok(name), Ok(value)) = (name, HeaderValue::from_str(value)) {
+                out.insert(name, value);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn header_value(name: &str, value: &str) -> Result<HeaderValue, (StatusCode, String)> {
+    HeaderValue::from_str(value)
+        .map_err(|_| (StatusCode::BAD_REQUEST, format!("InvalidArgument: {} is not a valid header value", name)))
+}
+
+/// A bare `attachment` or `inline` names the file after the last segment of
+/// `key`, with an ASCII fallback for browsers that ignore RFC 6266's
+/// `filename*`. Anything else is sent as given.
+fn with_filename(disposition: &str, key: &str) -> String {
+    let kind = disposition.trim();
+    if !kind.eq_ignore_ascii_case("attachment") && !kind.eq_ignore_ascii_case("inline") {
+        return disposition.to_string();
+    }
+    let name = key.rsplit('/').find(|s| !s.is_empty()).unwrap_or("download");
+    let fallback: String = name
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect();
+    let mut encoded = String::new();
+    for byte in name.bytes() {
+        // RFC 5987 attr-char.
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    format!("{}; filename=\"{}\"; filename*=UTF-8''{}", kind.to_ascii_lowercase(), fallback, encoded)
+}
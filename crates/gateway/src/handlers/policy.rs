@@ -0,0 +1,421 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::Arc;
+
+use crate::handlers::s3::{authorize_bucket, record_request_fields, validate_bucket_principal, validate_csrf, validate_s3_auth};
+use crate::replication::{self, MetadataOp};
+use crate::tenancy;
+use crate::AppState;
+
+// Bucket policies: the owner of a bucket may grant read or write on it, or
+// on a key prefix within it, to another user or to an API key. Grants are
+// evaluated in `authorize_bucket`; only the owner manages them.
+//
+// API keys are stateless bearer tokens, `nsk_<key_id>_<mac>`, where the MAC
+// is keyed by JWT_SECRET. A key carries no rights of its own, only what
+// policies grant `apikey:<key_id>`, so revoking a key means deleting its
+// grants (or rotating JWT_SECRET, which invalidates every key). Keys minted
+// outside the default tenant have ids of the form `<tenant_id>.<hex>`; the
+// MAC covers the whole id, so a key cannot be moved to another tenant.
+
+pub const API_KEY_PREFIX: &str = "nsk_";
+pub(crate) const API_KEY_PRINCIPAL_PREFIX: &str = "apikey:";
+const MAX_GRANTS: usize = 100;
+const MAX_PREFIX_CHARS: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BucketAccess {
+    Read,
+    /// Put, delete and tag objects; also allows reads.
+    Write,
+    /// Lifecycle and policy configuration. Never granted; owner only.
+    Manage,
+}
+
+impl BucketAccess {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::Manage => "manage",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "read" => Some(Self::Read),
+            "write" => Some(Self::Write),
+            _ => None,
+        }
+    }
+
+    fn allows(self, wanted: BucketAccess) -> bool {
+        match (self, wanted) {
+            (_, BucketAccess::Manage) => false,
+            (BucketAccess::Write, _) => true,
+            (granted, wanted) => granted == wanted,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Grantee {
+    User(String),
+    ApiKey(String),
+}
+
+impl Grantee {
+    pub fn principal(&self) -> String {
+        match self {
+            Self::User(email) => email.clone(),
+            Self::ApiKey(key_id) => format!("{}{}", API_KEY_PRINCIPAL_PREFIX, key_id),
+        }
+    }
+
+    fn from_principal(principal: &str) -> Self {
+        match principal.strip_prefix(API_KEY_PRINCIPAL_PREFIX) {
+            Some(key_id) => Self::ApiKey(key_id.to_string()),
+            None => Self::User(principal.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BucketGrant {
+    pub grantee: Grantee,
+    pub access: BucketAccess,
+    /// Keys the grant covers; empty means the whole bucket.
+    #[serde(default)]
+    pub prefix: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BucketPolicy {
+    pub grants: Vec<BucketGrant>,
+}
+
+pub fn is_api_key_principal(principal: &str) -> bool {
+    principal.starts_with(API_KEY_PRINCIPAL_PREFIX)
+}
+
+/// Whether any of `grants` gives `principal` the `wanted` access to `key`
+/// (for listings, the requested prefix).
+pub fn grants_allow(grants: &[BucketGrant], principal: &str, wanted: BucketAccess, key: &str) -> bool {
+    grants.iter().any(|grant| {
+        grant.grantee.principal() == principal
+            && grant.access.allows(wanted)
+            && key.starts_with(&grant.prefix)
+    })
+}
+
+fn api_key_mac(secret: &str, key_id: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(b"neurostore-api-key:");
+    mac.update(key_id.as_bytes());
+    mac
+}
+
+/// Returns `(key_id, token)` for a fresh API key in `tenant_id`.
+pub fn mint_api_key(secret: &str, tenant_id: &str) -> (String, String) {
+    let mut id = [0u8; 8];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut id);
+    let key_id = tenancy::api_key_id(tenant_id, &hex::encode(id));
+    let tag = hex::encode(api_key_mac(secret, &key_id).finalize().into_bytes());
+    let token = format!("{}{}_{}", API_KEY_PREFIX, key_id, tag);
+    (key_id, token)
+}
+
+/// The principal an API key token authenticates as, if its MAC verifies.
+pub fn verify_api_key(secret: &str, token: &str) -> Option<String> {
+    let (key_id, tag) = token.strip_prefix(API_KEY_PREFIX)?.split_once('_')?;
+    let tag = hex::decode(tag).ok()?;
+    api_key_mac(secret, key_id).verify_slice(&tag).ok()?;
+    Some(format!("{}{}", API_KEY_PRINCIPAL_PREFIX, key_id))
+}
+
+pub(crate) async fn load_grants(
+    db: &sqlx::PgPool,
+    masked_bucket: &str,
+    principal: Option<&str>,
+) -> Result<Vec<BucketGrant>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (String, String, String)>(
+        r#"
+        SELECT principal, prefix, access FROM bucket_policies
+        WHERE bucket = $1 AND ($2::TEXT IS NULL OR principal = $2)
+        ORDER BY principal, prefix
+        "#
+    )
+    .bind(masked_bucket)
+    .bind(principal)
+    .fetch_all(db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|(principal, prefix, access)| {
+            Some(BucketGrant {
+                grantee: Grantee::from_principal(&principal),
+                access: BucketAccess::parse(&access)?,
+                prefix,
+            })
+        })
+        .collect())
+}
+
+/// Replaces every grant on `masked_bucket`; shared with the replication
+/// follower.
+pub(crate) async fn replace_grants(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    masked_bucket: &str,
+    grants: &[BucketGrant],
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM bucket_policies WHERE bucket = $1")
+        .bind(masked_bucket)
+        .execute(&mut **tx)
+        .await?;
+    for grant in grants {
+        sqlx::query(
+            r#"
+            INSERT INTO bucket_policies (bucket, principal, prefix, access) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (bucket, principal, prefix) DO UPDATE SET access = excluded.access
+            "#
+        )
+        .bind(masked_bucket)
+        .bind(grant.grantee.principal())
+        .bind(&grant.prefix)
+        .bind(grant.access.as_str())
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Grantees must exist and belong to the bucket's tenant.
+async fn validate_policy(
+    state: &AppState,
+    owner: &str,
+    tenant_id: &str,
+    policy: &BucketPolicy,
+) -> Result<(), (StatusCode, String)> {
+    if policy.grants.len() > MAX_GRANTS {
+        return Err((StatusCode::BAD_REQUEST, format!("At most {} grants per bucket", MAX_GRANTS)));
+    }
+    for grant in &policy.grants {
+        if grant.access == BucketAccess::Manage {
+            return Err((StatusCode::BAD_REQUEST, "Manage access cannot be granted".to_string()));
+        }
+        if grant.prefix.chars().count() > MAX_PREFIX_CHARS {
+            return Err((StatusCode::BAD_REQUEST, "Grant prefix is too long".to_string()));
+        }
+        match &grant.grantee {
+            Grantee::User(email) if email == owner => {
+                return Err((StatusCode::BAD_REQUEST, "The bucket owner needs no grant".to_string()));
+            }
+            Grantee::User(email) => {
+                let exists = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM users WHERE email = $1 AND tenant_id = $2")
+                    .bind(email)
+                    .bind(tenant_id)
+                    .fetch_one(&state.db)
+                    .await
+                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)))?;
+                if exists == 0 {
+                    return Err((StatusCode::BAD_REQUEST, format!("Unknown user {}", email)));
+                }
+            }
+            Grantee::ApiKey(key_id) => {
+                let (key_tenant, id_hex) = tenancy::split_api_key_id(key_id);
+                if id_hex.len() != 16 || !id_hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err((StatusCode::BAD_REQUEST, format!("Invalid API key id {}", key_id)));
+                }
+                if key_tenant != tenant_id {
+                    return Err((StatusCode::BAD_REQUEST, format!("API key {} belongs to another tenant", key_id)));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// ── HANDLERS ──────────────────────────────────────────────────────
+
+pub async fn get_bucket_policy(
+    State(state): State<Arc<AppState>>,
+    Path(bucket): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    record_request_fields(&bucket, None);
+    let principal = match validate_bucket_principal(&headers, &state) {
+        Ok(p) => p,
+        Err(err) => return err.into_response(),
+    };
+    let scope = match authorize_bucket(&state, &bucket, &principal, BucketAccess::Manage, "").await {
+        Ok(scope) => scope,
+        Err(err) => return err.into_response(),
+    };
+    match load_grants(&state.db, &scope.masked, None).await {
+        Ok(grants) => Json(BucketPolicy { grants }).into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database Error").into_response(),
+    }
+}
+
+pub async fn put_bucket_policy(
+    State(state): State<Arc<AppState>>,
+    Path(bucket): Path<String>,
+    headers: HeaderMap,
+    Json(policy): Json<BucketPolicy>,
+) -> impl IntoResponse {
+    store_policy(&state, &bucket, &headers, policy).await
+}
+
+pub async fn delete_bucket_policy(
+    State(state): State<Arc<AppState>>,
+    Path(bucket): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    store_policy(&state, &bucket, &headers, BucketPolicy { grants: Vec::new() }).await
+}
+
+async fn store_policy(state: &AppState, bucket: &str, headers: &HeaderMap, policy: BucketPolicy) -> axum::response::Response {
+    record_request_fields(bucket, None);
+    if let Err(err) = validate_csrf(headers) {
+        return err.into_response();
+    }
+    let principal = match validate_bucket_principal(headers, state) {
+        Ok(p) => p,
+        Err(err) => return err.into_response(),
+    };
+    let scope = match authorize_bucket(state, bucket, &principal, BucketAccess::Manage, "").await {
+        Ok(scope) => scope,
+        Err(err) => return err.into_response(),
+    };
+    if let Err(err) = validate_policy(state, &principal, &scope.tenant_id, &policy).await {
+        return err.into_response();
+    }
+
+    let masked_bucket = scope.masked;
+    let res = async {
+        let mut tx = state.db.begin().await?;
+        replace_grants(&mut tx, &masked_bucket, &policy.grants).await?;
+        tx.commit().await
+    }
+    .await;
+    if let Err(e) = res {
+        tracing::error!("Database error while storing bucket policy: {}", e);
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    let removed = policy.grants.is_empty();
+    replication::publish(state, MetadataOp::ReplaceBucketPolicy {
+        bucket: masked_bucket,
+        grants: policy.grants.clone(),
+    })
+    .await;
+    if removed {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        Json(policy).into_response()
+    }
+}
+
+/// Mints an API key for use as a policy grantee. The token is returned once
+/// and never stored.
+pub async fn create_api_key(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(err) = validate_csrf(&headers) {
+        return err.into_response();
+    }
+    let user_email = match validate_s3_auth(&headers, &state) {
+        Ok(email) => email,
+        Err(err) => return err.into_response(),
+    };
+    let tenant_id = match tenancy::tenant_of_principal(&state, &user_email).await {
+        Ok(tenant_id) => tenant_id,
+        Err(err) => return err.into_response(),
+    };
+    let (key_id, api_key) = mint_api_key(&state.jwt_secret, &tenant_id);
+    tracing::info!("API key {} minted by {}", key_id, user_email);
+    Json(serde_json::json!({
+        "key_id": key_id,
+        "api_key": api_key,
+        "grantee": Grantee::ApiKey(key_id.clone()),
+    }))
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grant(grantee: Grantee, access: BucketAccess, prefix: &str) -> BucketGrant {
+        BucketGrant {
+            grantee,
+            access,
+            prefix: prefix.to_string(),
+        }
+    }
+
+    #[test]
+    fn read_grant_allows_reads_only() {
+        let grants = [grant(Grantee::User("bob@example.com".into()), BucketAccess::Read, "")];
+        assert!(grants_allow(&grants, "bob@example.com", BucketAccess::Read, "a/b.txt"));
+        assert!(!grants_allow(&grants, "bob@example.com", BucketAccess::Write, "a/b.txt"));
+        assert!(!grants_allow(&grants, "eve@example.com", BucketAccess::Read, "a/b.txt"));
+    }
+
+    #[test]
+    fn write_grant_implies_read_but_never_manage() {
+        let grants = [grant(Grantee::User("bob@example.com".into()), BucketAccess::Write, "")];
+        assert!(grants_allow(&grants, "bob@example.com", BucketAccess::Read, "x"));
+        assert!(grants_allow(&grants, "bob@example.com", BucketAccess::Write, "x"));
+        assert!(!grants_allow(&grants, "bob@example.com", BucketAccess::Manage, ""));
+    }
+
+    #[test]
+    fn prefix_limits_the_grant() {
+        let grants = [grant(Grantee::ApiKey("00112233aabbccdd".into()), BucketAccess::Write, "builds/")];
+        let key = "apikey:00112233aabbccdd";
+        assert!(grants_allow(&grants, key, BucketAccess::Write, "builds/1.tar"));
+        assert!(grants_allow(&grants, key, BucketAccess::Read, "builds/"));
+        assert!(!grants_allow(&grants, key, BucketAccess::Read, "secrets/env"));
+        assert!(!grants_allow(&grants, key, BucketAccess::Read, ""));
+    }
+
+    #[test]
+    fn api_keys_verify_only_with_their_secret() {
+        let (key_id, token) = mint_api_key("secret-a", tenancy::DEFAULT_TENANT);
+        let principal = verify_api_key("secret-a", &token).expect("valid key");
+        assert_eq!(principal, format!("apikey:{}", key_id));
+        assert!(is_api_key_principal(&principal));
+        assert_eq!(Grantee::from_principal(&principal), Grantee::ApiKey(key_id));
+        assert!(verify_api_key("secret-b", &token).is_none());
+
+        let mut forged = token.clone();
+        forged.replace_range(4..5, if &token[4..5] == "0" { "1" } else { "0" });
+        assert!(verify_api_key("secret-a", &forged).is_none());
+    }
+
+    #[test]
+    fn tenant_api_keys_carry_their_tenant() {
+        let (key_id, token) = mint_api_key("secret-a", "acme");
+        assert_eq!(tenancy::tenant_of_api_key(&key_id), "acme");
+        assert_eq!(tenancy::split_api_key_id(&key_id).1.len(), 16);
+        let principal = verify_api_key("secret-a", &token).expect("valid key");
+        assert_eq!(principal, format!("apikey:{}", key_id));
+
+        let (default_id, _) = mint_api_key("secret-a", tenancy::DEFAULT_TENANT);
+        assert_eq!(tenancy::tenant_of_api_key(&default_id), tenancy::DEFAULT_TENANT);
+
+        let moved = token.replacen("acme.", "other.", 1);
+        assert!(verify_api_key("secret-a", &moved).is_none());
+    }
+}
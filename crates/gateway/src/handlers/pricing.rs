@@ -0,0 +1,219 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use neuro_protocol::sentinel::{
+    excludes_peer, MAX_COGS_SHARE, PRICE_CURRENCY, USER_CHARGE_PER_GB_MONTH,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::handlers::estimate::{
+    eligible_regions, median, monthly_price, network_payout, Layout, ACTIVE_NODE_WINDOW_HOURS,
+    BYTES_PER_GB, STORAGE_CLASSES,
+};
+use crate::AppState;
+
+// ── NETWORK PRICING ──
+// The rates the sentinel pricing model currently sets, per storage class
+// and per region, and how they moved over time. Tier prices come from the
+// same layout and COGS rule as /api/estimate, so a quote from either agrees.
+
+const DEFAULT_HISTORY_DAYS: i32 = 30;
+const MAX_HISTORY_DAYS: i32 = 365;
+
+#[derive(Deserialize)]
+pub struct PricingQuery {
+    /// Price the tiers at this region's median payout instead of the
+    /// network's.
+    pub region: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct TierRate {
+    pub storage_class: &'static str,
+    pub data_shards: usize,
+    pub parity_shards: usize,
+    pub replicas: usize,
+    pub redundancy_overhead: f64,
+    pub price_per_gb_month: f64,
+}
+
+#[derive(Serialize)]
+pub struct RegionRate {
+    pub region: String,
+    pub nodes: usize,
+    pub node_payout_per_gb_month: f64,
+    pub min_payout_per_gb_month: f64,
+    pub max_payout_per_gb_month: f64,
+}
+
+#[derive(Serialize)]
+pub struct NetworkPricing {
+    pub currency: &'static str,
+    pub user_charge_per_gb_month: f64,
+    pub max_cogs_share: f64,
+    /// The payout the tiers are priced at: the region's when one was asked
+    /// for, otherwise the network median.
+    pub node_payout_per_gb_month: f64,
+    pub region: Option<String>,
+    pub tiers: Vec<TierRate>,
+    pub regions: Vec<RegionRate>,
+}
+
+// ── GET /api/pricing?region= ──
+pub async fn pricing(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<PricingQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e));
+    let region = query.region.map(|r| r.to_ascii_uppercase());
+
+    let rows = sqlx::query_as::<_, (String, Option<String>, String, f64)>(
+        r#"
+        SELECT n.peer_id, n.country_code, r.action, r.price_per_gb
+        FROM nodes n JOIN node_reputation r ON r.peer_id = n.peer_id
+        WHERE n.last_seen > NOW() - make_interval(hours => $1)
+        "#,
+    )
+    .bind(ACTIVE_NODE_WINDOW_HOURS)
+    .fetch_all(&state.db)
+    .await
+    .map_err(db_error)?;
+    let mut by_region: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+    for (peer_id, country, action, price) in rows {
+        if excludes_peer(&action) || state.fleet_policy.is_excluded(&peer_id) || !price.is_finite() {
+            continue;
+        }
+        let country = country.filter(|c| !c.is_empty()).unwrap_or_else(|| "XX".to_string());
+        by_region.entry(country).or_default().push(price);
+    }
+    let regions: Vec<RegionRate> = by_region
+        .into_iter()
+        .map(|(region, payouts)| RegionRate {
+            nodes: payouts.len(),
+            min_payout_per_gb_month: payouts.iter().copied().fold(f64::INFINITY, f64::min),
+            max_payout_per_gb_month: payouts.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            node_payout_per_gb_month: median(payouts).unwrap_or_default(),
+            region,
+        })
+        .collect();
+
+    let payout_per_gb = match &region {
+        Some(wanted) => regions
+            .iter()
+            .find(|r| &r.region == wanted)
+            .map(|r| r.node_payout_per_gb_month)
+            .ok_or_else(|| (StatusCode::NOT_FOUND, format!("No priced nodes in region {wanted}")))?,
+        None => network_payout(&state).await.map_err(db_error)?,
+    };
+
+    // Tiers are quoted per logical GB, laid out over the nodes that would
+    // take a new upload right now.
+    let active_nodes = eligible_regions(&state).await.map_err(db_error)?.len();
+    let reference_bytes = BYTES_PER_GB as u64;
+    let tiers = STORAGE_CLASSES
+        .iter()
+        .filter_map(|&class| {
+            let layout = Layout::for_class(&state, class, reference_bytes, active_nodes)?;
+            let (_, _, stored_bytes) = layout.totals(reference_bytes);
+            Some(TierRate {
+                storage_class: class,
+                data_shards: layout.data_shards,
+                parity_shards: layout.parity_shards,
+                replicas: layout.replicas,
+                redundancy_overhead: stored_bytes as f64 / reference_bytes as f64,
+                price_per_gb_month: monthly_price(reference_bytes, stored_bytes, payout_per_gb),
+            })
+        })
+        .collect();
+
+    Ok(Json(NetworkPricing {
+        currency: PRICE_CURRENCY,
+        user_charge_per_gb_month: USER_CHARGE_PER_GB_MONTH,
+        max_cogs_share: MAX_COGS_SHARE,
+        node_payout_per_gb_month: payout_per_gb,
+        region,
+        tiers,
+        regions,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct PriceHistoryQuery {
+    pub days: Option<i32>,
+    pub region: Option<String>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct PricePoint {
+    pub day: DateTime<Utc>,
+    /// Peers with a rate in effect that day and still taking shards.
+    pub nodes: i64,
+    pub median_payout_per_gb_month: f64,
+    pub min_payout_per_gb_month: f64,
+    pub max_payout_per_gb_month: f64,
+}
+
+// ── GET /api/pricing/history?days=&region= ──
+// One point per day: the rate each peer had in effect at the end of that
+// day, taken from its latest node_price_history row up to then.
+pub async fn price_history(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<PriceHistoryQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let days = query.days.unwrap_or(DEFAULT_HISTORY_DAYS);
+    if !(1..=MAX_HISTORY_DAYS).contains(&days) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("days must be between 1 and {MAX_HISTORY_DAYS}"),
+        ));
+    }
+    let region = query.region.map(|r| r.to_ascii_uppercase());
+
+    let points = sqlx::query_as::<_, PricePoint>(
+        r#"
+        WITH days AS (
+            SELECT generate_series(
+                date_trunc('day', NOW()) - make_interval(days => $1 - 1),
+                date_trunc('day', NOW()),
+                INTERVAL '1 day'
+            ) AS day
+        )
+        SELECT d.day,
+               COUNT(*) AS nodes,
+               percentile_cont(0.5) WITHIN GROUP (ORDER BY p.price_per_gb) AS median_payout_per_gb_month,
+               MIN(p.price_per_gb) AS min_payout_per_gb_month,
+               MAX(p.price_per_gb) AS max_payout_per_gb_month
+        FROM days d
+        JOIN LATERAL (
+            SELECT DISTINCT ON (h.peer_id) h.peer_id, h.price_per_gb, h.action
+            FROM node_price_history h
+            WHERE h.recorded_at < d.day + INTERVAL '1 day'
+            ORDER BY h.peer_id, h.recorded_at DESC
+        ) p ON TRUE
+        LEFT JOIN nodes n ON n.peer_id = p.peer_id
+        -- The actions excludes_peer matches.
+        WHERE p.action NOT IN ('quarantine', 'evict', 'proactive_evict')
+          AND ($2::TEXT IS NULL OR COALESCE(NULLIF(n.country_code, ''), 'XX') = $2)
+        GROUP BY d.day
+        ORDER BY d.day
+        "#,
+    )
+    .bind(days)
+    .bind(region.as_deref())
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)))?;
+
+    Ok(Json(serde_json::json!({
+        "currency": PRICE_CURRENCY,
+        "days": days,
+        "region": region,
+        "points": points,
+    })))
+}
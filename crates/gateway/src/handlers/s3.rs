@@ -1,7 +1,7 @@
 use axum::{
     extract::{Path, State, Query},
     http::{StatusCode, HeaderMap, HeaderValue},
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     body::{Bytes, Body},
 };
 use std::collections::HashMap;
@@ -14,34 +14,120 @@ use aes_gcm::{
     aead::{Aead, KeyInit},
     Aes256Gcm, Key, Nonce,
 };
-use neuro_protocol::{ChunkCommand, StoreChunkRequest};
+use neuro_protocol::{ChunkCommand, StoreChunkRequest, MAX_DELETE_CIDS};
 use futures::stream::{FuturesUnordered, StreamExt};
 use std::time::Instant;
 use tokio::time::{timeout, Duration};
+use tracing::Instrument;
 
 use crate::AppState;
-use crate::erasure::ErasureEncoder;
-use crate::p2p::SwarmRequest;
+use crate::compression;
+use crate::deletions::{self, ShardReceipt};
+use crate::handlers::policy::{self, BucketAccess};
+use crate::handlers::integrity;
+use crate::handlers::limits;
+use crate::handlers::object_headers::ObjectHeaders;
+use crate::handlers::s3_error::S3Error;
+use crate::handlers::tagging;
+use crate::handlers::uploads;
+use crate::handlers::vouchers;
+use crate::key_index;
+use crate::replication::{self, MetadataOp};
+use crate::retrieval;
+use crate::tenancy::BucketScope;
+use crate::p2p::{DeleteAck, SwarmRequest};
 use tokio::sync::oneshot;
 
+/// Largest object a single PUT accepts.
+pub const MAX_OBJECT_BYTES: usize = 1024 * 1024 * 500;
+
+/// Set on DELETE responses: the deletion whose certificate of erasure lists
+/// the holders' signed receipts.
+pub const DELETION_ID_HEADER: &str = "x-neuro-deletion-id";
+
 #[derive(Deserialize)]
 pub struct ListQuery {
     pub prefix: Option<String>,
     pub delimiter: Option<String>,
     #[serde(rename = "max-keys")]
     pub max_keys: Option<i32>,
+    /// ListObjects (V1) paging: list keys after this one.
+    pub marker: Option<String>,
+    /// `2` selects ListObjectsV2, which pages with continuation tokens.
+    #[serde(rename = "list-type")]
+    pub list_type: Option<String>,
+    #[serde(rename = "start-after")]
+    pub start_after: Option<String>,
+    #[serde(rename = "continuation-token")]
+    pub continuation_token: Option<String>,
+    /// Present (`?lifecycle`) to read the bucket lifecycle configuration.
+    pub lifecycle: Option<String>,
+    /// Present (`?uploads`) to list the bucket's open upload sessions.
+    pub uploads: Option<String>,
+    #[serde(rename = "max-uploads")]
+    pub max_uploads: Option<i64>,
+    #[serde(rename = "upload-id-marker")]
+    pub upload_id_marker: Option<String>,
+}
+
+/// Tags the enclosing `http_request` span so gateway logs can be joined
+/// with node logs and client reports.
+pub(crate) fn record_request_fields(bucket: &str, key: Option<&str>) {
+    let span = tracing::Span::current();
+    span.record("bucket", bucket);
+    if let Some(key) = key {
+        span.record("key", key);
+    }
 }
 
 // ── BUCKET AUTHORIZATION ──────────────────────────────────────────
-pub(crate) async fn authorize_bucket(state: &AppState, bucket: &str, email: &str) -> Result<(), (StatusCode, String)> {
+/// Owners have full access; anyone else needs a bucket policy grant covering
+/// `key` (for listings, the requested prefix). A bucket that does not exist
+/// yet is provisioned for the calling user. The bucket is looked up in the
+/// caller's tenant; handlers use the returned scope's name and keys from
+/// then on.
+pub(crate) async fn authorize_bucket(
+    state: &AppState,
+    bucket: &str,
+    principal: &str,
+    access: BucketAccess,
+    key: &str,
+) -> Result<BucketScope, (StatusCode, String)> {
+    let scope = BucketScope::resolve(state, principal, bucket).await?;
+    bucket_rights(state, &scope, principal).await?.check(principal, access, key)?;
+    Ok(scope)
+}
+
+/// What a principal may do in one bucket, looked up once for requests that
+/// act on many keys.
+pub(crate) enum BucketRights {
+    Owner,
+    /// Whatever these grants allow, key by key.
+    Grants(Vec<policy::BucketGrant>),
+}
+
+impl BucketRights {
+    pub(crate) fn check(&self, principal: &str, access: BucketAccess, key: &str) -> Result<(), (StatusCode, String)> {
+        match self {
+            Self::Owner => Ok(()),
+            Self::Grants(grants) if policy::grants_allow(grants, principal, access, key) => Ok(()),
+            Self::Grants(_) => Err((StatusCode::FORBIDDEN, "AccessDenied: Bucket owned by another user".to_string())),
+        }
+    }
+}
+
+/// The bucket's owner and the caller's grants on it, as in [`authorize_bucket`].
+pub(crate) async fn bucket_rights(
+    state: &AppState,
+    scope: &BucketScope,
+    principal: &str,
+) -> Result<BucketRights, (StatusCode, String)> {
     // ZERO-KNOWLEDGE BUCKETS: Hash the bucket name to prevent enumeration leaks
-    let hashed_bucket = match state.metadata_protector.encrypt(&format!("bucket_salt_{}", bucket)) {
-        Ok(h) => h,
-        Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "Bucket masking failed".to_string())),
-    };
+    let hashed_bucket = scope.masked.clone();
 
-    let row = sqlx::query("SELECT owner_email FROM buckets WHERE name = $1")
+    let row = sqlx::query("SELECT owner_email FROM buckets WHERE name = $1 AND tenant_id = $2")
         .bind(&hashed_bucket)
+        .bind(&scope.tenant_id)
         .fetch_optional(&state.db)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)))?;
@@ -51,24 +137,53 @@ pub(crate) async fn authorize_bucket(state: &AppState, bucket: &str, email: &str
             let owner_email: String = record
                 .try_get("owner_email")
                 .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB row decode error: {}", e)))?;
-            if owner_email == email {
-                Ok(())
-            } else {
-                Err((StatusCode::FORBIDDEN, "AccessDenied: Bucket owned by another user".to_string()))
+            if owner_email == principal {
+                return Ok(BucketRights::Owner);
             }
+            let grants = policy::load_grants(&state.db, &hashed_bucket, Some(principal))
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)))?;
+            Ok(BucketRights::Grants(grants))
         },
+        // API keys only ever act through grants on existing buckets.
+        None if policy::is_api_key_principal(principal) => {
+            Err((StatusCode::NOT_FOUND, "NoSuchBucket".to_string()))
+        }
         None => {
-            sqlx::query("INSERT INTO buckets (name, owner_email) VALUES ($1, $2)")
+            sqlx::query("INSERT INTO buckets (name, owner_email, tenant_id) VALUES ($1, $2, $3)")
                 .bind(&hashed_bucket)
-                .bind(email)
+                .bind(principal)
+                .bind(&scope.tenant_id)
                 .execute(&state.db)
                 .await
                 .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to provision bucket: {}", e)))?;
-            Ok(())
+            replication::publish(state, MetadataOp::UpsertBucket {
+                name: hashed_bucket,
+                owner_email: principal.to_string(),
+                tenant_id: scope.tenant_id.clone(),
+            })
+            .await;
+            Ok(BucketRights::Owner)
         }
     }
 }
 
+/// Like `validate_s3_auth`, but also accepts an API key (`Bearer nsk_…`),
+/// which authenticates as `apikey:<key_id>`. Only bucket handlers take API
+/// keys, since a key never owns anything.
+pub(crate) fn validate_bucket_principal(headers: &HeaderMap, state: &AppState) -> Result<String, (StatusCode, String)> {
+    let api_key = headers
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|auth| auth.strip_prefix("Bearer "))
+        .filter(|token| token.starts_with(policy::API_KEY_PREFIX));
+    match api_key {
+        Some(token) => policy::verify_api_key(&state.jwt_secret, token)
+            .ok_or((StatusCode::UNAUTHORIZED, "Invalid API key".to_string())),
+        None => validate_s3_auth(headers, state),
+    }
+}
+
 // S3 Auth Stub - Extract AWS Signature V4 or fallback to JWT
 pub(crate) fn validate_s3_auth(headers: &HeaderMap, state: &AppState) -> Result<String, (StatusCode, String)> {
     let auth_header = headers.get("Authorization").and_then(|h| h.to_str().ok());
@@ -131,7 +246,7 @@ pub(crate) fn validate_csrf(headers: &HeaderMap) -> Result<(), (StatusCode, Stri
     Ok(())
 }
 
-fn xml_escape(input: &str) -> String {
+pub(crate) fn xml_escape(input: &str) -> String {
     input
         .replace('&', "&amp;")
         .replace('<', "&lt;")
@@ -147,43 +262,87 @@ pub async fn list_objects(
     Path(bucket): Path<String>,
     Query(query): Query<ListQuery>,
     headers: HeaderMap,
-) -> impl IntoResponse {
-    let user_email = match validate_s3_auth(&headers, &state) {
-        Ok(email) => email,
-        Err(err) => return err.into_response(),
-    };
-    if let Err(err) = authorize_bucket(&state, &bucket, &user_email).await {
-        return err.into_response();
+) -> Result<Response, S3Error> {
+    record_request_fields(&bucket, None);
+    if query.lifecycle.is_some() {
+        return crate::handlers::lifecycle::get_bucket_lifecycle(&state, &bucket, &headers).await;
+    }
+    let principal = validate_bucket_principal(&headers, &state)?;
+    let scope = authorize_bucket(&state, &bucket, &principal, BucketAccess::Read, query.prefix.as_deref().unwrap_or_default()).await?;
+    if query.uploads.is_some() {
+        let uploads_query = uploads::ListUploadsQuery {
+            max_uploads: query.max_uploads,
+            upload_id_marker: query.upload_id_marker,
+        };
+        return uploads::list_multipart_uploads(&state, &principal, Some(&scope.name), &uploads_query).await;
     }
 
     let prefix = query.prefix.unwrap_or_default();
-    let max_keys = query.max_keys.unwrap_or(1000);
-
-    let prefix_like = format!("{}%", prefix);
-    let limit = max_keys as i64;
-
-    let rows = sqlx::query_as::<_, crate::models::Object>(
-        "SELECT * FROM objects WHERE bucket = $1 AND key LIKE $2 LIMIT $3"
-    )
-    .bind(&bucket)
-    .bind(&prefix_like)
-    .bind(limit)
-    .fetch_all(&state.db)
-    .await;
+    let max_keys = query.max_keys.unwrap_or(1000).clamp(0, 1000);
+    let delimiter = query.delimiter.filter(|d| !d.is_empty());
+    let v2 = query.list_type.as_deref() == Some("2");
+    // V2 continuation tokens are the hex of the key the page ended on.
+    let continuation = match query.continuation_token.as_deref().filter(|_| v2).map(hex::decode) {
+        None => None,
+        Some(Ok(bytes)) => match String::from_utf8(bytes) {
+            Ok(marker) => Some(marker),
+            Err(_) => return Err(invalid_continuation_token()),
+        },
+        Some(Err(_)) => return Err(invalid_continuation_token()),
+    };
+    let start_after = if v2 {
+        continuation.clone().or_else(|| query.start_after.clone())
+    } else {
+        query.marker.clone()
+    };
 
-    match rows {
-        Ok(objects) => {
+    // Keys are encrypted under random nonces, so the blind index narrows the
+    // scan to the prefix's directory and the rest is matched on plaintext.
+    let listed = async {
+        let keys = key_index::candidates(&state, &scope.name, &prefix).await?;
+        let listing = key_index::build_listing(&keys, &prefix, delimiter.as_deref(), start_after.as_deref(), max_keys as usize);
+        let contents = key_index::page_rows(&state, &scope.name, &listing.contents).await?;
+        Ok::<_, sqlx::Error>((listing, contents))
+    };
+    match listed.await {
+        Ok((listing, contents)) => {
             let mut xml = String::new();
             xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
             xml.push_str("<ListBucketResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\n");
             xml.push_str(&format!("  <Name>{}</Name>\n", xml_escape(&bucket)));
             xml.push_str(&format!("  <Prefix>{}</Prefix>\n", xml_escape(&prefix)));
+            if let Some(delimiter) = &delimiter {
+                xml.push_str(&format!("  <Delimiter>{}</Delimiter>\n", xml_escape(delimiter)));
+            }
             xml.push_str(&format!("  <MaxKeys>{}</MaxKeys>\n", max_keys));
-            xml.push_str("  <IsTruncated>false</IsTruncated>\n");
+            xml.push_str(&format!("  <IsTruncated>{}</IsTruncated>\n", listing.is_truncated));
+            if v2 {
+                xml.push_str(&format!(
+                    "  <KeyCount>{}</KeyCount>\n",
+                    contents.len() + listing.common_prefixes.len()
+                ));
+                if let Some(token) = &query.continuation_token {
+                    xml.push_str(&format!("  <ContinuationToken>{}</ContinuationToken>\n", xml_escape(token)));
+                }
+                if let Some(start_after) = &query.start_after {
+                    xml.push_str(&format!("  <StartAfter>{}</StartAfter>\n", xml_escape(start_after)));
+                }
+                if let Some(next) = &listing.next_marker {
+                    xml.push_str(&format!(
+                        "  <NextContinuationToken>{}</NextContinuationToken>\n",
+                        hex::encode(next)
+                    ));
+                }
+            } else {
+                if let Some(marker) = &query.marker {
+                    xml.push_str(&format!("  <Marker>{}</Marker>\n", xml_escape(marker)));
+                }
+                if let Some(next) = &listing.next_marker {
+                    xml.push_str(&format!("  <NextMarker>{}</NextMarker>\n", xml_escape(next)));
+                }
+            }
 
-            for o in objects {
-                let decrypted_key = state.metadata_protector.decrypt(&o.key).unwrap_or_else(|_| o.key.clone());
-                
+            for (decrypted_key, o) in contents {
                 xml.push_str("  <Contents>\n");
                 xml.push_str(&format!("    <Key>{}</Key>\n", xml_escape(&decrypted_key)));
 
@@ -195,36 +354,64 @@ pub async fn list_objects(
                 xml.push_str("    <StorageClass>STANDARD</StorageClass>\n");
                 xml.push_str("  </Contents>\n");
             }
+            for common_prefix in listing.common_prefixes {
+                xml.push_str(&format!(
+                    "  <CommonPrefixes>\n    <Prefix>{}</Prefix>\n  </CommonPrefixes>\n",
+                    xml_escape(&common_prefix)
+                ));
+            }
 
             xml.push_str("</ListBucketResult>");
 
             let mut headers = HeaderMap::new();
             headers.insert("Content-Type", HeaderValue::from_static("application/xml"));
-            (StatusCode::OK, headers, xml).into_response()
+            Ok((StatusCode::OK, headers, xml).into_response())
         }
-        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database Error").into_response(),
+        Err(e) => Err(e.into()),
     }
 }
 
+fn invalid_continuation_token() -> S3Error {
+    S3Error::new(StatusCode::BAD_REQUEST, "InvalidArgument", "The continuation token provided is incorrect.")
+}
+
 pub async fn put_object(
     State(state): State<Arc<AppState>>,
     Path((bucket, key)): Path<(String, String)>,
+    Query(params): Query<HashMap<String, String>>,
     headers: HeaderMap,
     body: Body,
-) -> impl IntoResponse {
+) -> Result<Response, S3Error> {
     let start_time = Instant::now();
-    if let Err(err) = validate_csrf(&headers) {
-        return err.into_response();
-    }
-    let user_email = match validate_s3_auth(&headers, &state) {
-        Ok(email) => email,
-        Err(err) => return err.into_response(),
-    };
-    if let Err(err) = authorize_bucket(&state, &bucket, &user_email).await {
-        return err.into_response();
-    }
+    validate_csrf(&headers)?;
+    let principal = validate_bucket_principal(&headers, &state)?;
+    let scope = authorize_bucket(&state, &bucket, &principal, BucketAccess::Write, key.trim_start_matches('/')).await?;
+    let bucket = scope.name.clone();
 
     let key = key.trim_start_matches('/').to_string();
+    record_request_fields(&bucket, Some(&key));
+    if params.contains_key("tagging") {
+        return tagging::put_object_tagging(&state, &bucket, &key, body).await;
+    }
+    let limits = limits::effective(&state, &bucket).await?;
+    limits::check_key(&limits, &key).and_then(|()| limits::check_metadata(&limits, &headers))?;
+    let encrypted_key = match scope.protector.encrypt(&key) {
+        Ok(k) => k,
+        Err(_) => return Err(S3Error::internal("Key encryption failed")),
+    };
+    let key_lookup = key_index::lookup_token(&scope.protector, &bucket, &key);
+    // Before the body is read, so a full bucket costs the caller nothing.
+    limits::check_key_count(&state, &limits, &bucket, &key_lookup).await?;
+    // Likewise, a gateway already busy encoding turns the PUT away up front.
+    let encode_slot = match state.encode_pool.reserve() {
+        Ok(slot) => slot,
+        Err(busy) => return Ok(busy.into_response()),
+    };
+    let tags = match tagging::parse_tagging_header(&headers) {
+        Ok(tags) => tags.unwrap_or_default(),
+        Err(err) => return Err(err.into()),
+    };
+    let mut object_headers = ObjectHeaders::from_put(&headers)?;
     let geofence = headers.get("x-neuro-geofence")
         .and_then(|h| h.to_str().ok())
         .unwrap_or("GLOBAL")
@@ -236,16 +423,19 @@ pub async fn put_object(
     while let Some(chunk) = body_stream.next().await {
         match chunk {
             Ok(data) => {
-                if full_body.len() + data.len() > 1024 * 1024 * 500 {
-                    return (StatusCode::PAYLOAD_TOO_LARGE, "Exceeds 500MB Limit").into_response();
+                if full_body.len() + data.len() > MAX_OBJECT_BYTES {
+                    return Err(S3Error::new(StatusCode::PAYLOAD_TOO_LARGE, "EntityTooLarge", "Exceeds 500MB Limit"));
                 }
                 full_body.extend_from_slice(&data);
             },
-            Err(_) => return (StatusCode::BAD_REQUEST, "Stream Error").into_response(),
+            Err(_) => return Err(S3Error::new(StatusCode::BAD_REQUEST, "IncompleteBody", "Stream Error")),
         }
     }
-    let body_bytes = Bytes::from(full_body);
+    // Reject corrupted or truncated uploads before anything is encrypted or
+    // sent to a node.
+    let body_bytes = integrity::verify_body(&headers, Bytes::from(full_body))?;
     let etag = format!("\"{:x}\"", Md5::digest(&body_bytes));
+    object_headers.content_length = Some(body_bytes.len() as u64);
     
     // ── DOUBLE-BLIND ENCRYPTION & SALTED VAULT ──
     // By default, we use deterministic encryption for Global Deduplication.
@@ -258,10 +448,10 @@ pub async fn put_object(
         hasher.update(salt.as_bytes());
     }
     hasher.update(&body_bytes);
-    let plaintext_hash = hasher.finalize();
-    let enc_key_hex = hex::encode(plaintext_hash);
+    let plaintext_hash: zeroize::Zeroizing<[u8; 32]> = zeroize::Zeroizing::new(hasher.finalize().into());
+    let enc_key_hex = zeroize::Zeroizing::new(hex::encode(plaintext_hash.as_ref()));
 
-    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&plaintext_hash));
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(plaintext_hash.as_ref()));
     let mut nonce_bytes = [0u8; 12];
     rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut nonce_bytes);
     let nonce = Nonce::from_slice(&nonce_bytes);
@@ -272,7 +462,7 @@ pub async fn put_object(
             combined.extend(enc);
             combined
         },
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Encryption failed").into_response(),
+        Err(_) => return Err(S3Error::internal("Encryption failed")),
     };
 
     let size = encrypted_body.len() as i64;
@@ -281,24 +471,36 @@ pub async fn put_object(
     cid_hasher.update(&encrypted_body);
     let cid = format!("Qm{}", bs58::encode(cid_hasher.finalize()).into_string());
 
-    // RS(10, 10) - 20 total shards
+    // RS(10, 10) - 20 total shards, with parity scaled up by the sentinel's
+    // fleet redundancy multiplier (up to RS(10, 25)).
     let recovery_threshold = 10;
-    let parity_shards = 10;
+    let parity_shards = state.fleet_policy.parity_shards(10);
     let total_shards = recovery_threshold + parity_shards;
     
-    let encoder = match ErasureEncoder::new(recovery_threshold, parity_shards) {
-        Ok(e) => e,
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "RS Init Error").into_response(),
-    };
-        
-    let physical_shards = match encoder.encode(&encrypted_body) {
+    let physical_shards = match state.encode_pool.encode(encode_slot, recovery_threshold, parity_shards, encrypted_body).await {
         Ok(s) => s,
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "RS Encode Error").into_response(),
+        Err(_) => return Err(S3Error::internal("RS Encode Error")),
     };
 
-    tracing::info!("ENHANCED REDUNDANCY: Sliced {} bytes into 20 Galios Shards (RS 10+10)", size);
+    tracing::info!("ENHANCED REDUNDANCY: Sliced {} bytes into {} Galios Shards (RS {}+{})", size, total_shards, recovery_threshold, parity_shards);
+
+    // ── WRITE TOKEN (OPTIMISTIC LOCKING) ──
+    // Drawn before any shard leaves. Of two PUTs racing on this key, the one
+    // holding the higher version wins the objects row; the other finds out
+    // when it commits and removes its own shards and placements.
+    let version = match sqlx::query_scalar::<_, i64>("SELECT nextval('object_versions')")
+        .fetch_one(&state.db)
+        .await
+    {
+        Ok(version) => version,
+        Err(e) => {
+            tracing::error!("Failed to draw an object version: {}", e);
+            return Err(S3Error::internal("Database Error"));
+        }
+    };
 
     let (tx_ack, mut rx_ack) = tokio::sync::mpsc::channel(total_shards);
+    let fanout_span = tracing::info_span!("shard_fanout", shards = total_shards, stored = tracing::field::Empty);
 
     for (i, shard_bytes) in physical_shards.into_iter().enumerate() {
         let shard_cid = format!("{}-shard-{}", cid, i);
@@ -330,15 +532,16 @@ pub async fn put_object(
                                 r#"
                                 INSERT INTO object_shards (
                                     object_cid, shard_cid, shard_index, peer_id, country_code,
-                                    receipt_timestamp_ms, receipt_signature_valid, last_verified_at
-                                ) VALUES ($1, $2, $3, $4, $5, $6, $7, NOW())
+                                    receipt_timestamp_ms, receipt_signature_valid, last_verified_at, object_version
+                                ) VALUES ($1, $2, $3, $4, $5, $6, $7, NOW(), $8)
                                 ON CONFLICT (object_cid, shard_index) DO UPDATE SET
                                     shard_cid = excluded.shard_cid,
                                     peer_id = excluded.peer_id,
                                     country_code = excluded.country_code,
                                     receipt_timestamp_ms = excluded.receipt_timestamp_ms,
                                     receipt_signature_valid = excluded.receipt_signature_valid,
-                                    last_verified_at = NOW()
+                                    last_verified_at = NOW(),
+                                    object_version = excluded.object_version
                                 "#
                             )
                             .bind(&object_cid_clone)
@@ -348,6 +551,7 @@ pub async fn put_object(
                             .bind(&ack.country_code)
                             .bind(ack.timestamp_ms as i64)
                             .bind(ack.signature_valid)
+                            .bind(version)
                             .execute(&db_clone)
                             .await;
 
@@ -360,7 +564,7 @@ pub async fn put_object(
                 }
             };
             let _ = tx_ack_clone.send(res).await;
-        });
+        }.instrument(fanout_span.clone()));
     }
 
     drop(tx_ack);
@@ -378,36 +582,57 @@ pub async fn put_object(
         }
     }
 
+    fanout_span.record("stored", successful_store_acks);
     if successful_store_acks < required_optimistic_shards {
-        return (StatusCode::SERVICE_UNAVAILABLE, format!("Insufficient shard durability: {}/{}", successful_store_acks, required_optimistic_shards)).into_response();
+        return Err(S3Error::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "ServiceUnavailable",
+            format!("Insufficient shard durability: {}/{}", successful_store_acks, required_optimistic_shards),
+        ));
     }
 
-    let metadata_json = serde_json::json!({ 
-        "encryption_key": enc_key_hex,
+    let mut metadata_json = serde_json::json!({ 
+        "encryption_key": enc_key_hex.as_str(),
         "sla_tier": "enterprise-sovereign",
         "legal_fiduciary": "NeuroStore SLA Protocol" 
     });
+    object_headers.write(&mut metadata_json);
     let metadata_str = serde_json::to_string(&metadata_json).unwrap_or_else(|_| "{}".to_string());
     
-    let encrypted_key = match state.metadata_protector.encrypt(&key) {
-        Ok(k) => k,
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Key encryption failed").into_response(),
-    };
-    
-    let encrypted_metadata = match state.metadata_protector.encrypt(&metadata_str) {
+    let encrypted_metadata = match scope.protector.encrypt(&metadata_str) {
         Ok(m) => m,
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Metadata encryption failed").into_response(),
+        Err(_) => return Err(S3Error::internal("Metadata encryption failed")),
     };
+    let key_tokens = key_index::key_tokens(&scope.protector, &bucket, &key);
+    let mut object_metadata = serde_json::json!({
+        "encrypted": encrypted_metadata,
+        compression::COMPRESSIBLE_METADATA_KEY: compression::compressible_on_put(&headers),
+    });
+    tagging::write_tags(&scope.protector, &mut object_metadata, &tags)?;
 
-    let res = sqlx::query(
+    // Replaces the row only for a higher version and hands back the version
+    // it replaced; no row comes back when a newer write already holds it.
+    // The row is found by its key token: the encrypted key differs on every
+    // write and takes the new one.
+    let res = sqlx::query_as::<_, (Option<String>, Option<i32>, Option<i64>)>(
         r#"
-        INSERT INTO objects (bucket, key, etag, cid, shards, recovery_threshold, size, metadata_json)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-        ON CONFLICT (bucket, key) DO UPDATE SET
+        WITH previous AS (
+            SELECT cid, shards, version FROM objects WHERE bucket = $1 AND key_lookup = $10 FOR UPDATE
+        )
+        INSERT INTO objects (bucket, key, etag, cid, shards, recovery_threshold, size, metadata_json, key_tokens, key_lookup, version, bucket_ref)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        ON CONFLICT (bucket, key_lookup) DO UPDATE SET
+            key = excluded.key,
             etag = excluded.etag,
             cid = excluded.cid,
+            shards = excluded.shards,
             size = excluded.size,
-            metadata_json = excluded.metadata_json
+            metadata_json = excluded.metadata_json,
+            key_tokens = excluded.key_tokens,
+            key_lookup = excluded.key_lookup,
+            version = excluded.version
+        WHERE objects.version < excluded.version
+        RETURNING (SELECT cid FROM previous), (SELECT shards FROM previous), (SELECT version FROM previous)
         "#
     )
     .bind(&bucket)
@@ -417,15 +642,43 @@ pub async fn put_object(
     .bind(total_shards as i32)
     .bind(recovery_threshold as i32)
     .bind(size)
-    .bind(serde_json::json!({ "encrypted": encrypted_metadata }))
-    .execute(&state.db)
+    .bind(&object_metadata)
+    .bind(&key_tokens)
+    .bind(&key_lookup)
+    .bind(version)
+    .bind(&scope.masked)
+    .fetch_optional(&state.db)
     .await;
 
 
     match res {
-        Ok(_) => {
+        Ok(Some(previous)) => {
+            state.listing_cache.invalidate(&bucket);
+            if let (Some(old_cid), Some(old_shards), Some(old_version)) = previous {
+                if old_cid != cid {
+                    let state = state.clone();
+                    tokio::spawn(async move {
+                        discard_object_version(&state, &old_cid, old_shards, old_version).await;
+                    });
+                }
+            }
+            replication::publish(&state, MetadataOp::UpsertObject {
+                bucket: bucket.clone(),
+                key: encrypted_key.clone(),
+                etag: etag.clone(),
+                cid: cid.clone(),
+                shards: total_shards as i32,
+                recovery_threshold: recovery_threshold as i32,
+                size,
+                metadata_json: Some(object_metadata),
+                key_tokens: Some(key_tokens),
+                key_lookup: Some(key_lookup),
+                version,
+            })
+            .await;
+
             let duration = start_time.elapsed();
-            tracing::info!("OPTIMISTIC PUT SUCCESS: {}/{} | Redundancy: 2.0x | Latency: {}ms", bucket, key, duration.as_millis());
+            tracing::info!("OPTIMISTIC PUT SUCCESS: {}/{} | Redundancy: {:.1}x | Latency: {}ms", bucket, key, total_shards as f64 / recovery_threshold as f64, duration.as_millis());
 
             let manifest = serde_json::json!({
                 "bucket": bucket,
@@ -461,13 +714,13 @@ pub async fn put_object(
             // ── USER-ROOT INDEXING (DISASTER RECOVERY) ──
             // If the Gateway DB is destroyed, the user forgets their file CIDs.
             // We pin a "Root Manifest" to the swarm tied to their email.
-            let user_email_clone = user_email.clone();
+            let principal_clone = principal.clone();
             let bucket_clone = bucket.clone();
             let key_clone = key.clone();
             let p2p_tx_root = state.p2p_tx.clone();
             tokio::spawn(async move {
                 let mut root_hasher = Sha256::new();
-                root_hasher.update(format!("root:{}", user_email_clone).as_bytes());
+                root_hasher.update(format!("root:{}", principal_clone).as_bytes());
                 let root_id = format!("meta-{}", hex::encode(root_hasher.finalize()));
                 
                 let root_data = serde_json::json!({
@@ -497,11 +750,22 @@ pub async fn put_object(
                 headers_out.insert("ETag", val);
             }
             headers_out.insert("x-neuro-latency-ms", HeaderValue::from_str(&duration.as_millis().to_string()).unwrap());
-            (StatusCode::OK, headers_out).into_response()
+            Ok((StatusCode::OK, headers_out).into_response())
+        }
+        Ok(None) => {
+            tracing::warn!("PUT {}/{} (version {}) lost to a newer write; discarding its shards", bucket, key, version);
+            let state = state.clone();
+            tokio::spawn(async move {
+                // Shard tasks still in flight record placements as they
+                // finish; wait for all of them so none outlive the cleanup.
+                while rx_ack.recv().await.is_some() {}
+                discard_object_version(&state, &cid, total_shards as i32, version).await;
+            });
+            Err(S3Error::new(StatusCode::CONFLICT, "OperationAborted", "a newer write to this key won"))
         }
         Err(e) => {
             tracing::error!("Failed to insert object: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, "Object insertion failed").into_response()
+            Err(S3Error::internal("Object insertion failed"))
         }
     }
 }
@@ -510,19 +774,14 @@ pub async fn reconstruct_metadata(
     State(state): State<Arc<AppState>>,
     Path((bucket, key)): Path<(String, String)>,
     headers: HeaderMap,
-) -> impl IntoResponse {
-    if let Err(err) = validate_csrf(&headers) {
-        return err.into_response();
-    }
-    let user_email = match validate_s3_auth(&headers, &state) {
-        Ok(email) => email,
-        Err(err) => return err.into_response(),
-    };
-    if let Err(err) = authorize_bucket(&state, &bucket, &user_email).await {
-        return err.into_response();
-    }
+) -> Result<Response, S3Error> {
+    validate_csrf(&headers)?;
+    let principal = validate_bucket_principal(&headers, &state)?;
+    let scope = authorize_bucket(&state, &bucket, &principal, BucketAccess::Write, key.trim_start_matches('/')).await?;
+    let bucket = scope.name.clone();
     
     let key = key.trim_start_matches('/').to_string();
+    record_request_fields(&bucket, Some(&key));
 
     let mut manifest_hasher = Sha256::new();
     manifest_hasher.update(format!("{}:{}", bucket, key).as_bytes());
@@ -532,18 +791,19 @@ pub async fn reconstruct_metadata(
     let req = SwarmRequest::Retrieve {
         cid: manifest_id,
         preferred_peer_id: None,
+        voucher: None,
         tx,
     };
 
     if state.p2p_tx.send(req).await.is_err() {
-        return (StatusCode::INTERNAL_SERVER_ERROR, "P2P Dispatch Error").into_response();
+        return Err(S3Error::internal("P2P Dispatch Error"));
     }
 
     match rx.await {
         Ok(ack) if ack.data.is_some() => {
             let data = ack.data.unwrap_or_default();
             let Ok(manifest) = serde_json::from_slice::<serde_json::Value>(&data) else {
-                return (StatusCode::INTERNAL_SERVER_ERROR, "Invalid Manifest Data").into_response();
+                return Err(S3Error::internal("Invalid Manifest Data"));
             };
 
             let cid = manifest["cid"].as_str().unwrap_or_default();
@@ -553,16 +813,18 @@ pub async fn reconstruct_metadata(
             let size = manifest["size"].as_i64().unwrap_or(0);
             let encrypted_meta = manifest["metadata"].as_str().unwrap_or("");
 
-            let encrypted_key = match state.metadata_protector.encrypt(&key) {
+            let encrypted_key = match scope.protector.encrypt(&key) {
                 Ok(k) => k,
-                Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Key encryption failed").into_response(),
+                Err(_) => return Err(S3Error::internal("Key encryption failed")),
             };
+            let key_tokens = key_index::key_tokens(&scope.protector, &bucket, &key);
+            let key_lookup = key_index::lookup_token(&scope.protector, &bucket, &key);
 
             let res = sqlx::query(
                 r#"
-                INSERT INTO objects (bucket, key, etag, cid, shards, recovery_threshold, size, metadata_json)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-                ON CONFLICT (bucket, key) DO NOTHING
+                INSERT INTO objects (bucket, key, etag, cid, shards, recovery_threshold, size, metadata_json, key_tokens, key_lookup, bucket_ref)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                ON CONFLICT (bucket, key_lookup) DO NOTHING
                 "#
             )
             .bind(&bucket)
@@ -573,79 +835,112 @@ pub async fn reconstruct_metadata(
             .bind(threshold as i32)
             .bind(size)
             .bind(serde_json::json!({ "encrypted": encrypted_meta }))
+            .bind(&key_tokens)
+            .bind(&key_lookup)
+            .bind(&scope.masked)
             .execute(&state.db)
             .await;
 
             match res {
-                Ok(_) => (StatusCode::OK, "Metadata Restored from P2P Shadow Registry").into_response(),
-                Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Restore Failed: {}", e)).into_response(),
+                Ok(done) if done.rows_affected() > 0 => {
+                    state.listing_cache.invalidate(&bucket);
+                    replication::publish(&state, MetadataOp::UpsertObject {
+                        bucket: bucket.clone(),
+                        key: encrypted_key,
+                        etag: etag.to_string(),
+                        cid: cid.to_string(),
+                        shards: shards as i32,
+                        recovery_threshold: threshold as i32,
+                        size,
+                        metadata_json: Some(serde_json::json!({ "encrypted": encrypted_meta })),
+                        key_tokens: Some(key_tokens),
+                        key_lookup: Some(key_lookup),
+                        version: 0,
+                    })
+                    .await;
+                    Ok((StatusCode::OK, "Metadata Restored from P2P Shadow Registry").into_response())
+                }
+                Ok(_) => Ok((StatusCode::OK, "Metadata Restored from P2P Shadow Registry").into_response()),
+                Err(e) => Err(S3Error::internal(format!("DB Restore Failed: {}", e))),
             }
         }
-        _ => (StatusCode::NOT_FOUND, "No Shadow Manifest found in Swarm").into_response(),
+        _ => Err(S3Error::new(StatusCode::NOT_FOUND, "NoSuchKey", "No Shadow Manifest found in Swarm").with_resource(&key)),
     }
 }
 
 pub async fn get_object(
     State(state): State<Arc<AppState>>,
     Path((bucket, key)): Path<(String, String)>,
+    Query(params): Query<HashMap<String, String>>,
     headers: HeaderMap,
-) -> impl IntoResponse {
+) -> Result<Response, S3Error> {
     let start_time = Instant::now();
-    let user_email = match validate_s3_auth(&headers, &state) {
-        Ok(email) => email,
-        Err(err) => return err.into_response(),
-    };
-    if let Err(err) = authorize_bucket(&state, &bucket, &user_email).await {
-        return err.into_response();
-    }
+    let principal = validate_bucket_principal(&headers, &state)?;
+    let scope = authorize_bucket(&state, &bucket, &principal, BucketAccess::Read, key.trim_start_matches('/')).await?;
+    let bucket = scope.name.clone();
     
     let key = key.trim_start_matches('/').to_string();
+    record_request_fields(&bucket, Some(&key));
+    if params.contains_key("tagging") {
+        return tagging::get_object_tagging(&state, &bucket, &key).await;
+    }
+    if let Some(upload_id) = params.get("uploadId") {
+        let parts_query = uploads::ListPartsQuery::from_params(&params)?;
+        return uploads::list_object_parts(&state, &principal, &bucket, &key, upload_id, &parts_query).await;
+    }
     
-    let encrypted_key = match state.metadata_protector.encrypt(&key) {
-        Ok(k) => k,
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Search Encryption Failure").into_response(),
-    };
-
+    let key_lookup = key_index::lookup_token(&scope.protector, &bucket, &key);
     let row = sqlx::query_as::<_, crate::models::Object>(
-        "SELECT * FROM objects WHERE bucket = $1 AND key = $2"
+        "SELECT * FROM objects WHERE bucket = $1 AND key_lookup = $2"
     )
     .bind(&bucket)
-    .bind(&encrypted_key)
+    .bind(&key_lookup)
     .fetch_optional(&state.db)
+    .instrument(tracing::info_span!("db.select_object"))
     .await;
 
 
     match row {
         Ok(Some(obj)) => {
+            tracing::Span::current().record("cid", obj.cid.as_str());
+            let compressible = compression::is_compressible(obj.metadata_json.as_ref());
+            let metadata = open_metadata(&scope, &obj);
+            let object_headers = ObjectHeaders::read(&metadata);
             // HIGH-SPEED CACHE CHECK
             if let Some(cached_bytes) = state.edge_cache.get(&obj.cid).await {
                let duration = start_time.elapsed();
                tracing::info!("CDN RAM HIT: Served {}/{} in {}ms", bucket, key, duration.as_millis());
-               return (StatusCode::OK, cached_bytes).into_response();
+               let (body, mut headers_out) = compression::encode_object(&state.compression, &headers, compressible, cached_bytes).await;
+               object_headers.apply(&key, &params, &mut headers_out)?;
+               return Ok((StatusCode::OK, headers_out, body).into_response());
             }
 
             // ── PARALLEL RACING RETRIEVAL ──
-            let mut preferred_peers: HashMap<usize, String> = HashMap::new();
-            let shard_rows = sqlx::query_as::<_, (i32, String)>(
-                "SELECT shard_index, peer_id FROM object_shards WHERE object_cid = $1"
-            )
-            .bind(&obj.cid)
-            .fetch_all(&state.db)
-            .await
-            .unwrap_or_default();
-            for (index, peer_id) in shard_rows {
-                if index >= 0 {
-                    preferred_peers.insert(index as usize, peer_id);
-                }
-            }
+            // Shards are ranked by their holder's reputation and observed
+            // latency. The best `threshold + HEDGE_EXTRA_SHARDS` go out at
+            // once; the rest are hedges, sent only if we still need them
+            // after HEDGE_DELAY.
+            let plan = retrieval::plan(&state, &obj.cid, obj.shards.max(0) as usize)
+                .instrument(tracing::info_span!("db.select_shard_placements"))
+                .await;
+            let primary = obj.recovery_threshold.max(0) as usize + retrieval::HEDGE_EXTRA_SHARDS;
 
             let mut futures = FuturesUnordered::new();
+            let fanout_span = tracing::info_span!(
+                "shard_fanout",
+                shards = obj.shards,
+                threshold = obj.recovery_threshold,
+                recovered = tracing::field::Empty,
+            );
             
-            for i in 0..obj.shards {
+            for (rank, target) in plan.targets.iter().enumerate() {
+                let i = target.index;
                 let shard_cid = format!("{}-shard-{}", obj.cid, i);
                 let (tx, rx) = oneshot::channel();
                 let p2p_tx = state.p2p_tx.clone();
-                let preferred_peer_id = preferred_peers.get(&(i as usize)).cloned();
+                let preferred_peer_id = target.peer_id.clone();
+                let avoid = Arc::clone(&plan.avoid);
+                let hedge_delay = if rank < primary { Duration::ZERO } else { retrieval::HEDGE_DELAY };
                 
                 futures.push(async move {
                     // ── TRAFFIC JITTER (ANTI-CORRELATION) ──
@@ -653,18 +948,18 @@ pub async fn get_object(
                     // This breaks the exact "10 simultaneous requests" timing signature
                     // that ISPs or state actors look for when fingerprinting decentralized storage.
                     let jitter = rand::RngCore::next_u32(&mut rand::thread_rng()) % 15 + 1;
-                    tokio::time::sleep(Duration::from_millis(jitter as u64)).await;
+                    tokio::time::sleep(hedge_delay + Duration::from_millis(jitter as u64)).await;
 
-                    let req = SwarmRequest::Retrieve { cid: shard_cid, preferred_peer_id, tx };
+                    let sent_at = Instant::now();
+                    let req = SwarmRequest::Retrieve { cid: shard_cid, preferred_peer_id, voucher: None, tx };
                     if p2p_tx.send(req).await.is_ok() {
-                        if let Ok(Ok(ack)) = timeout(Duration::from_secs(8), rx).await {
-                            if let Some(data) = ack.data {
-                                return Some((i as usize, data));
-                            }
+                        if let Ok(Ok(ack)) = timeout(retrieval::SHARD_FETCH_TIMEOUT, rx).await {
+                            let (data, sample) = retrieval::accept(ack, sent_at, &avoid);
+                            return (i, data, sample);
                         }
                     }
-                    None
-                });
+                    (i, None, None)
+                }.instrument(fanout_span.clone()));
             }
 
             // ── TRAFFIC CHAFF (SNIPER PROTECTION) ──
@@ -685,68 +980,58 @@ pub async fn get_object(
                 let _ = p2p_tx_chaff.send(SwarmRequest::Retrieve { 
                     cid: dummy_cid, 
                     preferred_peer_id: None, 
+                    voucher: None,
                     tx 
                 }).await;
             });
 
-            let mut retrieved_shards = vec![None; obj.shards as usize];
-            let mut success_count = 0;
+            let mut fetched = retrieval::FetchedShards::new(obj.shards.max(0) as usize);
+            let mut samples = Vec::new();
 
-            while let Some(result) = futures.next().await {
-                if let Some((index, data)) = result {
-                    retrieved_shards[index] = Some(data);
-                    success_count += 1;
-                    
-                    if success_count >= obj.recovery_threshold as usize {
-                        break;
-                    }
+            while let Some((index, data, sample)) = futures.next().await {
+                fetched.record(index, data, sample.as_ref());
+                samples.extend(sample);
+                if fetched.count() >= obj.recovery_threshold as usize {
+                    break;
                 }
             }
+            let success_count = fetched.count();
+            // Hedges still waiting out their delay are dropped unsent.
+            drop(futures);
+            retrieval::record(&state, samples);
+
+            fanout_span.record("recovered", success_count);
+            drop(fanout_span);
 
             if success_count < obj.recovery_threshold as usize {
-                return (StatusCode::INTERNAL_SERVER_ERROR, "Data unavailable: Insufficient shards").into_response();
+                return Err(S3Error::internal("Data unavailable: Insufficient shards"));
             }
 
             // ── PRE-DECODING SANITIZATION (SANDBOXING) ──
-            // A malicious node might send a "Poison Shard" designed to cause an OOM 
-            // or an infinite loop in the Reed-Solomon decoder. We must isolate this computationally
-            // expensive step from the main async reactor.
-            let recovery_threshold = obj.recovery_threshold as usize;
-            let total_shards_for_decode = obj.shards as usize;
-            
-            let decode_result = tokio::task::spawn_blocking(move || {
-                let encoder = match ErasureEncoder::new(recovery_threshold, total_shards_for_decode - recovery_threshold) {
-                    Ok(e) => e,
-                    Err(_) => return Err("RS Decoder Init Failed".to_string()),
-                };
-                
-                // We wrap the decode in a thread-local timeout conceptually.
-                // If it hangs, the spawn_blocking task will be abandoned (though threads aren't killed instantly in Rust,
-                // a robust implementation would use a separate process or a WebAssembly sandbox for true isolation).
-                // For this fortification, we ensure it doesn't block the Tokio worker pool.
-                match encoder.decode(retrieved_shards) {
-                    Ok(data) => Ok(data),
-                    Err(_) => Err("Erasure Reconstruction Failure".to_string()),
-                }
-            }).await;
-
-            let reconstructed_data = match decode_result {
-                Ok(Ok(data)) => data,
-                Ok(Err(_)) | Err(_) => {
-                    tracing::error!("FAILURE: Poison Shard detected or RS decode crashed.");
-                    return (StatusCode::INTERNAL_SERVER_ERROR, "Erasure Reconstruction Failure (Sanitization Triggered)").into_response();
+            // A malicious node might send a "Poison Shard" designed to cause an OOM
+            // or a wrong decode. The decode runs off the async reactor, and its result
+            // must hash back to the CID; shards that break it are pinned on their
+            // senders, who are skipped for this object from then on.
+            let reconstructed_data = match retrieval::rebuild(
+                &state,
+                &plan,
+                &obj.cid,
+                &mut fetched,
+                obj.recovery_threshold.max(0) as usize,
+                obj.size.max(0) as usize,
+            )
+            .await
+            {
+                Ok(data) => data,
+                Err(reason) => {
+                    tracing::error!("FAILURE: Poison Shard detected or RS decode crashed: {}", reason);
+                    return Err(S3Error::internal("Erasure Reconstruction Failure (Sanitization Triggered)"));
                 }
             };
             
-            let metadata_str = match obj.metadata_json.as_ref().and_then(|v| v.get("encrypted")).and_then(|v| v.as_str()) {
-                Some(enc_str) => state.metadata_protector.decrypt(enc_str).unwrap_or_else(|_| "{}".to_string()),
-                None => "{}".to_string(),
-            };
-            let metadata: serde_json::Value = serde_json::from_str(&metadata_str).unwrap_or(serde_json::json!({}));
-            
             let mut final_data = reconstructed_data;
             if let Some(key_hex) = metadata.get("encryption_key").and_then(|v| v.as_str()) {
-                if let Ok(key_bytes) = hex::decode(key_hex) {
+                if let Ok(key_bytes) = hex::decode(key_hex).map(zeroize::Zeroizing::new) {
                     if key_bytes.len() == 32 {
                         let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key_bytes));
                         if final_data.len() > 12 {
@@ -763,20 +1048,73 @@ pub async fn get_object(
             let duration = start_time.elapsed();
             tracing::info!("GET SUCCESS: {}/{} | Racing Shards: {}/{} | Latency: {}ms", bucket, key, success_count, obj.shards, duration.as_millis());
             
+            let final_data = Bytes::from(final_data);
             let cache = state.edge_cache.clone();
             let cid = obj.cid.clone();
             let data_to_cache = final_data.clone();
             tokio::spawn(async move {
-                cache.insert(cid, Bytes::from(data_to_cache)).await;
+                cache.insert(cid, data_to_cache).await;
             });
 
-            (StatusCode::OK, final_data).into_response()
+            let (body, mut headers_out) = compression::encode_object(&state.compression, &headers, compressible, final_data).await;
+            object_headers.apply(&key, &params, &mut headers_out)?;
+            Ok((StatusCode::OK, headers_out, body).into_response())
         }
-        Ok(None) => (StatusCode::NOT_FOUND, "NoSuchKey").into_response(),
-        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database Error").into_response(),
+        Ok(None) => Err(S3Error::no_such_key(&key)),
+        Err(e) => Err(e.into()),
     }
 }
 
+/// Decrypts the sealed metadata of `obj`: its encryption key and headers.
+fn open_metadata(scope: &BucketScope, obj: &crate::models::Object) -> serde_json::Value {
+    let metadata_str = match obj.metadata_json.as_ref().and_then(|v| v.get("encrypted")).and_then(|v| v.as_str()) {
+        Some(enc_str) => scope.protector.decrypt(enc_str).unwrap_or_else(|_| "{}".to_string()),
+        None => "{}".to_string(),
+    };
+    serde_json::from_str(&metadata_str).unwrap_or(serde_json::json!({}))
+}
+
+// HEAD answers from the objects row alone; no shard is fetched.
+pub async fn head_object(
+    State(state): State<Arc<AppState>>,
+    Path((bucket, key)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, S3Error> {
+    let principal = validate_bucket_principal(&headers, &state)?;
+    let scope = authorize_bucket(&state, &bucket, &principal, BucketAccess::Read, key.trim_start_matches('/')).await?;
+    let bucket = scope.name.clone();
+    let key = key.trim_start_matches('/').to_string();
+    record_request_fields(&bucket, Some(&key));
+    let key_lookup = key_index::lookup_token(&scope.protector, &bucket, &key);
+
+    let row = sqlx::query_as::<_, crate::models::Object>(
+        "SELECT * FROM objects WHERE bucket = $1 AND key_lookup = $2"
+    )
+    .bind(&bucket)
+    .bind(&key_lookup)
+    .fetch_optional(&state.db)
+    .await;
+    let obj = match row {
+        Ok(Some(obj)) => obj,
+        Ok(None) => return Err(S3Error::no_such_key(&key)),
+        Err(e) => return Err(e.into()),
+    };
+
+    let metadata = open_metadata(&scope, &obj);
+    let object_headers = ObjectHeaders::read(&metadata);
+    let mut headers_out = HeaderMap::new();
+    headers_out.insert(axum::http::header::CONTENT_TYPE, HeaderValue::from_static("application/octet-stream"));
+    headers_out.insert(axum::http::header::CONTENT_LENGTH, HeaderValue::from(object_headers.content_length(obj.size, &metadata)));
+    if let Ok(etag) = HeaderValue::from_str(&obj.etag) {
+        headers_out.insert(axum::http::header::ETAG, etag);
+    }
+    if let Some(modified) = obj.created_at.and_then(|d| HeaderValue::from_str(&d.format("%a, %d %b %Y %H:%M:%S GMT").to_string()).ok()) {
+        headers_out.insert(axum::http::header::LAST_MODIFIED, modified);
+    }
+    object_headers.apply(&key, &HashMap::new(), &mut headers_out)?;
+    Ok((StatusCode::OK, headers_out).into_response())
+}
+
 #[derive(Deserialize)]
 pub struct DedupRequest {
     pub cid: String,
@@ -788,19 +1126,14 @@ pub async fn deduplicate_object(
     Path((bucket, key)): Path<(String, String)>,
     headers: HeaderMap,
     axum::Json(payload): axum::Json<DedupRequest>,
-) -> impl IntoResponse {
-    if let Err(err) = validate_csrf(&headers) {
-        return err.into_response();
-    }
-    let user_email = match validate_s3_auth(&headers, &state) {
-        Ok(email) => email,
-        Err(err) => return err.into_response(),
-    };
-    if let Err(err) = authorize_bucket(&state, &bucket, &user_email).await {
-        return err.into_response();
-    }
+) -> Result<Response, S3Error> {
+    validate_csrf(&headers)?;
+    let principal = validate_bucket_principal(&headers, &state)?;
+    let scope = authorize_bucket(&state, &bucket, &principal, BucketAccess::Write, key.trim_start_matches('/')).await?;
+    let bucket = scope.name.clone();
     
     let key = key.trim_start_matches('/').to_string();
+    record_request_fields(&bucket, Some(&key));
 
     let existing_obj = if let Some(etag) = payload.etag.as_ref() {
         sqlx::query_as::<_, crate::models::Object>(
@@ -821,20 +1154,27 @@ pub async fn deduplicate_object(
 
     match existing_obj {
         Ok(Some(obj)) => {
-            let encrypted_key = match state.metadata_protector.encrypt(&key) {
+            let encrypted_key = match scope.protector.encrypt(&key) {
                 Ok(k) => k,
-                Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Key encryption failed").into_response(),
+                Err(_) => return Err(S3Error::internal("Key encryption failed")),
             };
+            let key_tokens = key_index::key_tokens(&scope.protector, &bucket, &key);
+            let key_lookup = key_index::lookup_token(&scope.protector, &bucket, &key);
 
-            let copy_res = sqlx::query(
+            let copy_res = sqlx::query_scalar::<_, i64>(
                 r#"
-                INSERT INTO objects (bucket, key, etag, cid, shards, recovery_threshold, size, metadata_json)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-                ON CONFLICT (bucket, key) DO UPDATE SET
+                INSERT INTO objects (bucket, key, etag, cid, shards, recovery_threshold, size, metadata_json, key_tokens, key_lookup, bucket_ref, version)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, nextval('object_versions'))
+                ON CONFLICT (bucket, key_lookup) DO UPDATE SET
+                    key = excluded.key,
                     etag = excluded.etag,
                     cid = excluded.cid,
                     size = excluded.size,
-                    metadata_json = excluded.metadata_json
+                    metadata_json = excluded.metadata_json,
+                    key_tokens = excluded.key_tokens,
+                    key_lookup = excluded.key_lookup,
+                    version = excluded.version
+                RETURNING version
                 "#
             )
             .bind(&bucket)
@@ -845,111 +1185,267 @@ pub async fn deduplicate_object(
             .bind(obj.recovery_threshold)
             .bind(obj.size)
             .bind(&obj.metadata_json)
-            .execute(&state.db)
+            .bind(&key_tokens)
+            .bind(&key_lookup)
+            .bind(&scope.masked)
+            .fetch_one(&state.db)
             .await;
 
             match copy_res {
-                Ok(_) => {
+                Ok(version) => {
+                    state.listing_cache.invalidate(&bucket);
+                    replication::publish(&state, MetadataOp::UpsertObject {
+                        bucket: bucket.clone(),
+                        key: encrypted_key,
+                        etag: obj.etag.clone(),
+                        cid: obj.cid.clone(),
+                        shards: obj.shards,
+                        recovery_threshold: obj.recovery_threshold,
+                        size: obj.size,
+                        metadata_json: obj.metadata_json.clone(),
+                        key_tokens: Some(key_tokens),
+                        key_lookup: Some(key_lookup),
+                        version,
+                    })
+                    .await;
                     tracing::info!("Global Deduplication Success: Mapped {}/{} to CID {}", bucket, key, payload.cid);
-                    (StatusCode::OK, "Deduplicated").into_response()
+                    Ok((StatusCode::OK, "Deduplicated").into_response())
                 },
                 Err(e) => {
                     tracing::error!("Failed to deduplicate: {}", e);
-                    (StatusCode::INTERNAL_SERVER_ERROR, "Failed to map existing shards").into_response()
+                    Err(S3Error::internal("Failed to map existing shards"))
                 }
             }
         },
-        Ok(None) => (StatusCode::NOT_FOUND, "CID/ETag verification failed").into_response(),
-        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database Error").into_response(),
+        Ok(None) => Err(S3Error::new(StatusCode::NOT_FOUND, "NoSuchKey", "CID/ETag verification failed").with_resource(&payload.cid)),
+        Err(e) => Err(e.into()),
     }
 }
 
 pub async fn delete_object(
     State(state): State<Arc<AppState>>,
     Path((bucket, key)): Path<(String, String)>,
+    Query(params): Query<HashMap<String, String>>,
     headers: HeaderMap,
-) -> impl IntoResponse {
-    if let Err(err) = validate_csrf(&headers) {
-        return err.into_response();
-    }
-    let user_email = match validate_s3_auth(&headers, &state) {
-        Ok(email) => email,
-        Err(err) => return err.into_response(),
-    };
-    if let Err(err) = authorize_bucket(&state, &bucket, &user_email).await {
-        return err.into_response();
-    }
+) -> Result<Response, S3Error> {
+    validate_csrf(&headers)?;
+    let principal = validate_bucket_principal(&headers, &state)?;
+    let scope = authorize_bucket(&state, &bucket, &principal, BucketAccess::Write, key.trim_start_matches('/')).await?;
+    let bucket = scope.name.clone();
     
     let key = key.trim_start_matches('/').to_string();
+    record_request_fields(&bucket, Some(&key));
+    if params.contains_key("tagging") {
+        return tagging::delete_object_tagging(&state, &bucket, &key).await;
+    }
 
-    let encrypted_key = match state.metadata_protector.encrypt(&key) {
-        Ok(k) => k,
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Delete Encryption Failure").into_response(),
-    };
-
+    let key_lookup = key_index::lookup_token(&scope.protector, &bucket, &key);
     let row = sqlx::query_as::<_, crate::models::Object>(
-        "SELECT * FROM objects WHERE bucket = $1 AND key = $2"
+        "SELECT * FROM objects WHERE bucket = $1 AND key_lookup = $2"
     )
     .bind(&bucket)
-    .bind(&encrypted_key)
+    .bind(&key_lookup)
     .fetch_optional(&state.db)
     .await;
 
     match row {
-        Ok(Some(obj)) => {
-            for i in 0..obj.shards {
-                let shard_cid = format!("{}-shard-{}", obj.cid, i);
-                let (tx, rx) = oneshot::channel();
-                
-                let req = SwarmRequest::Delete {
-                    cid: shard_cid,
-                    tx,
-                };
-
-                if state.p2p_tx.send(req).await.is_ok() {
-                    let _ = rx.await;
+        Ok(Some(obj)) => match purge_object(&state, &obj).await {
+            Ok(deletion_id) => {
+                tracing::info!("DPDP COMPLIANCE: Cryptographic Shredding successful for {}/{}. Master key annihilated.", bucket, key);
+                let mut headers = HeaderMap::new();
+                if let Ok(value) = HeaderValue::from_str(&deletion_id) {
+                    headers.insert(DELETION_ID_HEADER, value);
                 }
+                Ok((StatusCode::NO_CONTENT, headers).into_response())
+            }
+            Err(e) => {
+                tracing::error!("Database error during deletion: {}", e);
+                Err(S3Error::internal("Database error during deletion"))
             }
+        },
+        Ok(None) => Err(S3Error::no_such_key(&key)),
+        Err(e) => Err(e.into()),
+    }
+}
 
-            // ── CRYPTOGRAPHIC SHREDDING (DPDP COMPLIANCE) ──
-            // We do not just drop the row. We cryptographically overwrite the Master Object Key
-            // so that even if rogue nodes keep the physical shards, they are mathematically meaningless.
-            let mut noise = [0u8; 64];
-            rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut noise);
-            let shredded_metadata = serde_json::json!({
-                "status": "CRYPTOGRAPHICALLY_SHREDDED",
-                "erasure_timestamp": std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs(),
-                "noise": hex::encode(noise)
-            });
+/// Deletes an object's shards from the swarm, shreds its metadata and drops
+/// the row. `obj.key` is the stored (masked) key. Shared by DELETE and the
+/// lifecycle daemon. Returns the id of the deletion record holding the
+/// holders' signed receipts.
+pub(crate) async fn purge_object(state: &AppState, obj: &crate::models::Object) -> Result<String, sqlx::Error> {
+    let deletion_id = deletions::open(state, obj).await?;
+    let receipts = delete_shards(state, &[(obj.cid.as_str(), obj.shards)]).await;
+    deletions::settle(state, std::slice::from_ref(&deletion_id), &receipts).await?;
+    shred_object(state, obj).await?;
+    Ok(deletion_id)
+}
 
-            let _ = sqlx::query("UPDATE objects SET metadata_json = $1 WHERE bucket = $2 AND key = $3")
-                .bind(&shredded_metadata)
-                .bind(&bucket)
-                .bind(&encrypted_key)
-                .execute(&state.db)
-                .await;
+/// Removes the shards and placements one version of an object wrote once
+/// another version holds its key: the loser of a PUT race, or the version a
+/// PUT replaced. Left alone while any object row still points at
+/// `object_cid`, e.g. a deduplicated copy.
+pub(crate) async fn discard_object_version(state: &AppState, object_cid: &str, shards: i32, version: i64) {
+    let in_use = sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM objects WHERE cid = $1)")
+        .bind(object_cid)
+        .fetch_one(&state.db)
+        .await;
+    match in_use {
+        Ok(false) => {}
+        Ok(true) => return,
+        Err(e) => {
+            tracing::warn!("Skipping cleanup of object {} version {}: {}", object_cid, version, e);
+            return;
+        }
+    }
+    delete_shards(state, &[(object_cid, shards)]).await;
+    let res = sqlx::query("DELETE FROM object_shards WHERE object_cid = $1 AND object_version = $2")
+        .bind(object_cid)
+        .bind(version)
+        .execute(&state.db)
+        .await;
+    match res {
+        Ok(done) => tracing::info!("Discarded object {} version {}: {} placements removed", object_cid, version, done.rows_affected()),
+        Err(e) => tracing::warn!("Failed to remove placements of object {} version {}: {}", object_cid, version, e),
+    }
+}
 
-            tracing::info!("DPDP COMPLIANCE: Cryptographic Shredding successful for {}/{}. Master key annihilated.", bucket, key);
+/// Deletes the shards of `objects` (object CID, shard count) from the swarm
+/// with one `DeleteBatch` per recorded holder and up to `MAX_DELETE_CIDS`
+/// shards. Shards without a placement, or whose holder cannot take a batch,
+/// are deleted one by one. Returns the receipts of the holders that
+/// confirmed.
+pub(crate) async fn delete_shards(state: &AppState, objects: &[(&str, i32)]) -> Vec<ShardReceipt> {
+    let object_cids: Vec<&str> = objects.iter().map(|(cid, _)| *cid).collect();
+    let placements: HashMap<String, String> = sqlx::query_as::<_, (String, String)>(
+        "SELECT shard_cid, peer_id FROM object_shards WHERE object_cid = ANY($1)"
+    )
+    .bind(&object_cids)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_else(|e| {
+        tracing::warn!("Failed to load shard placements for deletion: {}", e);
+        Vec::new()
+    })
+    .into_iter()
+    .collect();
+
+    let mut placed = Vec::new();
+    for (object_cid, shards) in objects {
+        for i in 0..*shards {
+            let shard_cid = format!("{}-shard-{}", object_cid, i);
+            let peer_id = placements.get(&shard_cid).cloned();
+            placed.push((shard_cid, peer_id));
+        }
+    }
+    delete_placed_shards(state, placed).await
+}
 
-            let del_res = sqlx::query("DELETE FROM objects WHERE bucket = $1 AND key = $2")
-                .bind(&bucket)
-                .bind(&encrypted_key)
-                .execute(&state.db)
-                .await;
+/// Deletes shards by CID, batching those with a known holder per peer and
+/// falling back to single deletes for the rest. Every shard also gets a
+/// gossiped tombstone, so a holder that is offline now deletes it on return.
+/// Returns the signed receipts of known holders that answered; shards with
+/// no recorded holder never produce one.
+pub(crate) async fn delete_placed_shards(state: &AppState, placed: Vec<(String, Option<String>)>) -> Vec<ShardReceipt> {
+    let cids = placed.iter().map(|(shard_cid, _)| shard_cid.clone()).collect();
+    let _ = state.p2p_tx.send(SwarmRequest::Tombstone { cids }).await;
+
+    let mut by_peer: HashMap<String, Vec<String>> = HashMap::new();
+    let mut single = Vec::new();
+    for (shard_cid, peer_id) in placed {
+        match peer_id {
+            Some(peer_id) => by_peer.entry(peer_id).or_default().push(shard_cid),
+            None => single.push((shard_cid, None)),
+        }
+    }
 
-            match del_res {
-                Ok(_) => {
-                    StatusCode::NO_CONTENT.into_response()
-                }
-                Err(e) => {
-                    tracing::error!("Database error during deletion: {}", e);
-                    StatusCode::INTERNAL_SERVER_ERROR.into_response()
-                }
+    let batches = by_peer.into_iter().flat_map(|(peer_id, cids)| {
+        cids.chunks(MAX_DELETE_CIDS)
+            .map(|chunk| (peer_id.clone(), chunk.to_vec()))
+            .collect::<Vec<_>>()
+    });
+    let batched = futures::future::join_all(batches.map(|(peer_id, cids)| async move {
+        let (tx, rx) = oneshot::channel();
+        let req = SwarmRequest::DeleteBatch { peer_id: peer_id.clone(), cids: cids.clone(), tx };
+        let ack = match state.p2p_tx.send(req).await {
+            Ok(()) => rx.await.ok().flatten(),
+            Err(_) => None,
+        };
+        match ack {
+            Some(ack) => Ok(cids
+                .into_iter()
+                .map(|shard_cid| ShardReceipt {
+                    removed: ack.deleted.contains(&shard_cid),
+                    shard_cid,
+                    receipt: ack.receipt.clone(),
+                })
+                .collect::<Vec<_>>()),
+            None => Err(cids.into_iter().map(|shard_cid| (shard_cid, Some(peer_id.clone()))).collect::<Vec<_>>()),
+        }
+    }))
+    .await;
+    let mut receipts = Vec::new();
+    for outcome in batched {
+        match outcome {
+            Ok(confirmed) => receipts.extend(confirmed),
+            Err(unbatched) => single.extend(unbatched),
+        }
+    }
+
+    for (shard_cid, peer_id) in single {
+        let (tx, rx) = oneshot::channel();
+        let known_holder = peer_id.is_some();
+        let req = SwarmRequest::Delete {
+            cid: shard_cid.clone(),
+            peer_id,
+            tx,
+        };
+
+        if state.p2p_tx.send(req).await.is_err() {
+            continue;
+        }
+        // Only the recorded holder's receipt is evidence the shard is gone.
+        if let Ok(DeleteAck { deleted, receipt: Some(receipt) }) = rx.await {
+            if known_holder {
+                receipts.push(ShardReceipt { shard_cid, removed: deleted, receipt });
             }
         }
-        Ok(None) => StatusCode::NOT_FOUND.into_response(),
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
+    receipts
+}
+
+/// Shreds an object's metadata and drops its row once its shards are gone.
+pub(crate) async fn shred_object(state: &AppState, obj: &crate::models::Object) -> Result<(), sqlx::Error> {
+    // ── CRYPTOGRAPHIC SHREDDING (DPDP COMPLIANCE) ──
+    // We do not just drop the row. We cryptographically overwrite the Master Object Key
+    // so that even if rogue nodes keep the physical shards, they are mathematically meaningless.
+    let mut noise = [0u8; 64];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut noise);
+    let shredded_metadata = serde_json::json!({
+        "status": "CRYPTOGRAPHICALLY_SHREDDED",
+        "erasure_timestamp": std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs(),
+        "noise": hex::encode(noise)
+    });
+
+    let _ = sqlx::query("UPDATE objects SET metadata_json = $1 WHERE bucket = $2 AND key = $3")
+        .bind(&shredded_metadata)
+        .bind(&obj.bucket)
+        .bind(&obj.key)
+        .execute(&state.db)
+        .await;
+
+    sqlx::query("DELETE FROM objects WHERE bucket = $1 AND key = $2")
+        .bind(&obj.bucket)
+        .bind(&obj.key)
+        .execute(&state.db)
+        .await?;
+    state.listing_cache.invalidate(&obj.bucket);
+
+    replication::publish(state, MetadataOp::DeleteObject {
+        bucket: obj.bucket.clone(),
+        key: obj.key.clone(),
+    })
+    .await;
+    Ok(())
 }
 
 // ── DIRECT-TO-SWARM: BYPASS GATEWAY BOTTLENECK ──
@@ -957,26 +1453,19 @@ pub async fn get_presigned_manifest(
     State(state): State<Arc<AppState>>,
     Path((bucket, key)): Path<(String, String)>,
     headers: HeaderMap,
-) -> impl IntoResponse {
-    let user_email = match validate_s3_auth(&headers, &state) {
-        Ok(email) => email,
-        Err(err) => return err.into_response(),
-    };
-    if let Err(err) = authorize_bucket(&state, &bucket, &user_email).await {
-        return err.into_response();
-    }
+) -> Result<Response, S3Error> {
+    let principal = validate_bucket_principal(&headers, &state)?;
+    let scope = authorize_bucket(&state, &bucket, &principal, BucketAccess::Read, key.trim_start_matches('/')).await?;
+    let bucket = scope.name.clone();
     
     let key = key.trim_start_matches('/').to_string();
-    let encrypted_key = match state.metadata_protector.encrypt(&key) {
-        Ok(k) => k,
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Encryption Error").into_response(),
-    };
-
+    record_request_fields(&bucket, Some(&key));
+    let key_lookup = key_index::lookup_token(&scope.protector, &bucket, &key);
     let obj_row = sqlx::query_as::<_, crate::models::Object>(
-        "SELECT * FROM objects WHERE bucket = $1 AND key = $2"
+        "SELECT * FROM objects WHERE bucket = $1 AND key_lookup = $2"
     )
     .bind(&bucket)
-    .bind(&encrypted_key)
+    .bind(&key_lookup)
     .fetch_optional(&state.db)
     .await;
 
@@ -1000,7 +1489,7 @@ pub async fn get_presigned_manifest(
             }
 
             let metadata_str = match obj.metadata_json.as_ref().and_then(|v| v.get("encrypted")).and_then(|v| v.as_str()) {
-                Some(enc_str) => state.metadata_protector.decrypt(enc_str).unwrap_or_else(|_| "{}".to_string()),
+                Some(enc_str) => scope.protector.decrypt(enc_str).unwrap_or_else(|_| "{}".to_string()),
                 None => "{}".to_string(),
             };
             let metadata: serde_json::Value = serde_json::from_str(&metadata_str).unwrap_or(serde_json::json!({}));
@@ -1013,19 +1502,18 @@ pub async fn get_presigned_manifest(
                 let pre_encrypted_key = format!("PRE_WRAPPED:{}:{}", pub_hex, raw_key);
                 final_encryption_key = serde_json::Value::String(pre_encrypted_key);
             } else if client_pub_key_hex.is_none() {
-                return (StatusCode::BAD_REQUEST, "x-client-public-key header required for secure manifest delivery.").into_response();
+                return Err(S3Error::new(
+                    StatusCode::BAD_REQUEST,
+                    "InvalidRequest",
+                    "x-client-public-key header required for secure manifest delivery.",
+                ));
             }
 
             // ── CRYPTOGRAPHIC BANDWIDTH VOUCHERS (ANTI FREE-RIDER) ──
             // To prevent a user from endlessly draining a Data Center's egress bandwidth,
-            // we issue a time-bound HMAC voucher. The Data Center node will verify this voucher
-            // before serving the shard, and later redeem it with the Gateway for INR payout.
-            let expiry = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() + 3600; // 1 hour validity
-            let payload_to_sign = format!("{}:{}:{}", user_email, obj.cid, expiry);
-            let mut hmac = hmac::Hmac::<sha2::Sha256>::new_from_slice(state.jwt_secret.as_bytes()).unwrap();
-            hmac::Mac::update(&mut hmac, payload_to_sign.as_bytes());
-            let signature = hex::encode(hmac::Mac::finalize(hmac).into_bytes());
-            let bandwidth_voucher = format!("v1.{}.{}", payload_to_sign, signature);
+            // we issue a time-bound, byte-capped voucher. Nodes enforce it before serving
+            // the shard, and later redeem it with the Gateway for INR payout.
+            let bandwidth_voucher = vouchers::mint_voucher(&state, &principal, &obj);
 
             let manifest = serde_json::json!({
                 "bucket": bucket,
@@ -1039,9 +1527,162 @@ pub async fn get_presigned_manifest(
                 "shards": shards
             });
 
-            (StatusCode::OK, axum::Json(manifest)).into_response()
+            Ok((StatusCode::OK, axum::Json(manifest)).into_response())
         },
-        Ok(None) => (StatusCode::NOT_FOUND, "NoSuchKey").into_response(),
-        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database Error").into_response()
+        Ok(None) => Err(S3Error::no_such_key(&key)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ShardQuery {
+    pub voucher: Option<String>,
+}
+
+// ── BROWSER DIRECT RETRIEVAL: SERVE RAW SHARDS ──
+// Browsers holding a bandwidth voucher from get_presigned_manifest pull the
+// individual shards here and run Reed-Solomon + decryption client-side, so the
+// gateway never sees the reconstructed object. The voucher may arrive as the
+// `x-bandwidth-voucher` header or a `?voucher=` query parameter (the latter
+// keeps cross-origin fetches free of a CORS preflight). Every shard served is
+// counted against the voucher's byte cap, and passed on to the node serving
+// it, which counts it too and redeems it later.
+pub async fn get_shard(
+    State(state): State<Arc<AppState>>,
+    Path(shard_cid): Path<String>,
+    Query(query): Query<ShardQuery>,
+    headers: HeaderMap,
+) -> Result<Response, S3Error> {
+    tracing::Span::current().record("cid", shard_cid.as_str());
+    let voucher = headers
+        .get("x-bandwidth-voucher")
+        .and_then(|h| h.to_str().ok())
+        .map(str::to_string)
+        .or(query.voucher);
+    let Some((token, voucher)) = voucher.and_then(|t| vouchers::verify_voucher(&state, &t).ok().map(|v| (t, v))) else {
+        return Err(S3Error::new(StatusCode::FORBIDDEN, "AccessDenied", "Invalid or expired bandwidth voucher"));
+    };
+    if let Err(e) = vouchers::check_remaining(&state, &voucher) {
+        return Err(vouchers::refusal(e).into());
+    }
+    let object_cid = voucher.object_cid.clone();
+
+    let placement = sqlx::query_as::<_, (String,)>(
+        "SELECT peer_id FROM object_shards WHERE object_cid = $1 AND shard_cid = $2 LIMIT 1"
+    )
+    .bind(&object_cid)
+    .bind(&shard_cid)
+    .fetch_optional(&state.db)
+    .await;
+    let preferred_peer_id = match placement {
+        Ok(Some((peer_id,))) => Some(peer_id),
+        Ok(None) => return Err(S3Error::new(StatusCode::FORBIDDEN, "AccessDenied", "Shard not covered by voucher")),
+        Err(e) => return Err(e.into()),
+    };
+
+    let data = match state.edge_cache.get(&shard_cid).await {
+        Some(cached) => cached,
+        None => {
+            let (tx, rx) = oneshot::channel();
+            let req = SwarmRequest::Retrieve { cid: shard_cid.clone(), preferred_peer_id, voucher: Some(token), tx };
+            if state.p2p_tx.send(req).await.is_err() {
+                return Err(S3Error::new(StatusCode::SERVICE_UNAVAILABLE, "ServiceUnavailable", "Swarm unavailable"));
+            }
+            match timeout(Duration::from_secs(8), rx).await {
+                Ok(Ok(ack)) if ack.signature_valid => match ack.data {
+                    Some(bytes) => {
+                        let bytes = Bytes::from(bytes);
+                        state.edge_cache.insert(shard_cid.clone(), bytes.clone()).await;
+                        bytes
+                    }
+                    None => {
+                        return Err(S3Error::new(StatusCode::NOT_FOUND, "NoSuchKey", "Shard not found in swarm")
+                            .with_resource(&shard_cid))
+                    }
+                },
+                _ => return Err(S3Error::new(StatusCode::GATEWAY_TIMEOUT, "RequestTimeout", "Shard retrieval timed out")),
+            }
+        }
+    };
+
+    if let Err(e) = vouchers::charge_voucher(&state, &voucher, data.len() as u64) {
+        return Err(vouchers::refusal(e).into());
+    }
+
+    let mut resp_headers = HeaderMap::new();
+    resp_headers.insert("Content-Type", HeaderValue::from_static("application/octet-stream"));
+    // Shards are content-addressed, so the bytes behind a CID never change.
+    resp_headers.insert("Cache-Control", HeaderValue::from_static("private, max-age=3600, immutable"));
+    Ok((StatusCode::OK, resp_headers, Body::from(data)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{auth, drop_db, scratch_db, state, OWNER};
+
+    async fn put(state: &Arc<AppState>, key: &str, body: &'static [u8]) -> StatusCode {
+        put_object(
+            State(Arc::clone(state)),
+            Path(("photos".to_string(), key.to_string())),
+            Query(HashMap::new()),
+            auth(state),
+            Body::from(body),
+        )
+        .await
+        .map_or_else(|e| e.into_response().status(), |r| r.status())
+    }
+
+    async fn get(state: &Arc<AppState>, key: &str) -> (StatusCode, Bytes) {
+        let response = get_object(
+            State(Arc::clone(state)),
+            Path(("photos".to_string(), key.to_string())),
+            Query(HashMap::new()),
+            auth(state),
+        )
+        .await
+        .unwrap_or_else(IntoResponse::into_response);
+        let status = response.status();
+        (status, axum::body::to_bytes(response.into_body(), usize::MAX).await.expect("body"))
+    }
+
+    #[tokio::test]
+    async fn put_then_get_round_trips_through_postgres() {
+        let Some((db, name)) = scratch_db().await else {
+            eprintln!("DATABASE_URL not set; skipping");
+            return;
+        };
+        sqlx::query("INSERT INTO users (email, password_hash) VALUES ($1, 'x')")
+            .bind(OWNER)
+            .execute(&db)
+            .await
+            .expect("user");
+        let state = state(db.clone());
+
+        assert_eq!(put(&state, "albums/2026/cover.jpg", b"first version").await, StatusCode::OK);
+        assert_eq!(get(&state, "albums/2026/cover.jpg").await, (StatusCode::OK, Bytes::from_static(b"first version")));
+
+        // An overwrite replaces the row rather than adding one.
+        assert_eq!(put(&state, "albums/2026/cover.jpg", b"second version").await, StatusCode::OK);
+        assert_eq!(get(&state, "albums/2026/cover.jpg").await, (StatusCode::OK, Bytes::from_static(b"second version")));
+        let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM objects").fetch_one(&db).await.expect("count");
+        assert_eq!(rows, 1);
+
+        assert_eq!(get(&state, "albums/2026/missing.jpg").await.0, StatusCode::NOT_FOUND);
+
+        // Sub-resource errors carry the same XML body as the object handlers.
+        let response = get_object(
+            State(Arc::clone(&state)),
+            Path(("photos".to_string(), "albums/2026/missing.jpg".to_string())),
+            Query(HashMap::from([("tagging".to_string(), String::new())])),
+            auth(&state),
+        )
+        .await
+        .unwrap_or_else(IntoResponse::into_response);
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.expect("body");
+        assert!(std::str::from_utf8(&body).expect("utf8").contains("<Code>NoSuchKey</Code>"));
+        drop(state);
+        drop_db(db, &name).await;
     }
 }
@@ -0,0 +1,127 @@
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+use crate::handlers::s3::xml_escape;
+
+// S3 error bodies: SDKs read the `Code` of an `<Error>` document to decide
+// whether to retry and which exception to raise, and surface its
+// `RequestId` to the user. The id is the one `assign_request_id` gave the
+// request; it reaches the handler's error through a task-local, since
+// `IntoResponse` sees nothing of the request.
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Runs `handler` with `request_id` available to the `S3Error`s it returns.
+pub(crate) async fn with_request_id<F: std::future::Future>(request_id: String, handler: F) -> F::Output {
+    REQUEST_ID.scope(request_id, handler).await
+}
+
+#[derive(Debug, Clone)]
+pub struct S3Error {
+    status: StatusCode,
+    code: String,
+    message: String,
+    /// The bucket or key the error is about, as `<Resource>`.
+    resource: Option<String>,
+}
+
+impl S3Error {
+    pub fn new(status: StatusCode, code: &str, message: impl Into<String>) -> Self {
+        Self { status, code: code.to_string(), message: message.into(), resource: None }
+    }
+
+    pub fn no_such_key(key: &str) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "NoSuchKey", "The specified key does not exist.").with_resource(key)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "InternalError", message)
+    }
+
+    pub fn with_resource(mut self, resource: &str) -> Self {
+        self.resource = Some(resource.to_string());
+        self
+    }
+
+    fn to_xml(&self, request_id: Option<&str>) -> String {
+        let mut xml = String::new();
+        xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<Error>\n");
+        xml.push_str(&format!("  <Code>{}</Code>\n", xml_escape(&self.code)));
+        xml.push_str(&format!("  <Message>{}</Message>\n", xml_escape(&self.message)));
+        if let Some(resource) = &self.resource {
+            xml.push_str(&format!("  <Resource>{}</Resource>\n", xml_escape(resource)));
+        }
+        if let Some(request_id) = request_id {
+            xml.push_str(&format!("  <RequestId>{}</RequestId>\n", xml_escape(request_id)));
+        }
+        xml.push_str("</Error>");
+        xml
+    }
+}
+
+/// The `(status, "Code: message")` pairs the shared helpers return. A
+/// message without a leading code gets the usual one for its status.
+impl From<(StatusCode, String)> for S3Error {
+    fn from((status, message): (StatusCode, String)) -> Self {
+        let (code, rest) = match message.split_once(':') {
+            Some((code, rest)) if is_code(code) => (code, rest.trim_start()),
+            _ if is_code(&message) => (message.as_str(), ""),
+            _ => (default_code(status), message.as_str()),
+        };
+        let rest = if rest.is_empty() { default_message(status) } else { rest };
+        Self::new(status, code, rest)
+    }
+}
+
+impl From<sqlx::Error> for S3Error {
+    fn from(e: sqlx::Error) -> Self {
+        tracing::error!("Database error: {}", e);
+        Self::internal("We encountered an internal error. Please try again.")
+    }
+}
+
+impl IntoResponse for S3Error {
+    fn into_response(self) -> Response {
+        let request_id = REQUEST_ID.try_with(String::clone).ok();
+        (
+            self.status,
+            [(header::CONTENT_TYPE, HeaderValue::from_static("application/xml"))],
+            self.to_xml(request_id.as_deref()),
+        )
+            .into_response()
+    }
+}
+
+/// An S3 error code: one CamelCase word, like `NoSuchBucket`.
+fn is_code(word: &str) -> bool {
+    word.len() > 1
+        && word.starts_with(|c: char| c.is_ascii_uppercase())
+        && word.chars().all(|c| c.is_ascii_alphanumeric())
+        && word.chars().any(|c| c.is_ascii_lowercase())
+}
+
+fn default_code(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::BAD_REQUEST => "InvalidRequest",
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => "AccessDenied",
+        StatusCode::NOT_FOUND => "NoSuchKey",
+        StatusCode::METHOD_NOT_ALLOWED => "MethodNotAllowed",
+        StatusCode::CONFLICT => "OperationAborted",
+        StatusCode::PRECONDITION_FAILED => "PreconditionFailed",
+        StatusCode::PAYLOAD_TOO_LARGE => "EntityTooLarge",
+        StatusCode::RANGE_NOT_SATISFIABLE => "InvalidRange",
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => "SlowDown",
+        StatusCode::NOT_IMPLEMENTED => "NotImplemented",
+        StatusCode::GATEWAY_TIMEOUT => "RequestTimeout",
+        _ => "InternalError",
+    }
+}
+
+fn default_message(status: StatusCode) -> &'static str {
+    status.canonical_reason().unwrap_or("Error")
+}
@@ -0,0 +1,191 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, TimeZone, Utc};
+use neuro_client_sdk::{ShareToken, SCOPE_RETRIEVE};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::handlers::manifests::owned_manifest;
+use crate::handlers::s3::{validate_csrf, validate_s3_auth};
+use crate::replication::{self, MetadataOp};
+use crate::AppState;
+
+// ── SHARE LINKS ──
+// Publishes a neuro-uploader share token for one of the caller's manifests.
+// The gateway only ever holds the sealed part; the share key stays with
+// whoever the owner hands the link to (`<link>#<share key>`), so the gateway
+// can expire and revoke a link but never open it.
+
+#[derive(Deserialize)]
+pub struct CreateShareRequest {
+    /// The token without its share key (`ShareToken::sealed`).
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ShareLink {
+    pub share_id: String,
+    pub manifest_root: String,
+    pub owner_email: String,
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Inserts or updates a link; shared with the replication follower.
+pub(crate) async fn upsert_link(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    link: &ShareLink,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO share_links (share_id, manifest_root, owner_email, token, expires_at, revoked_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (share_id) DO UPDATE SET revoked_at = excluded.revoked_at
+        "#,
+    )
+    .bind(&link.share_id)
+    .bind(&link.manifest_root)
+    .bind(&link.owner_email)
+    .bind(&link.token)
+    .bind(link.expires_at)
+    .bind(link.revoked_at)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+async fn store_link(state: &AppState, link: ShareLink) -> Result<(), (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e));
+    let mut tx = state.db.begin().await.map_err(db_error)?;
+    upsert_link(&mut tx, &link).await.map_err(db_error)?;
+    tx.commit().await.map_err(db_error)?;
+    replication::publish(state, MetadataOp::UpsertShareLink { link }).await;
+    Ok(())
+}
+
+fn new_share_id() -> String {
+    let mut id = [0u8; 16];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut id);
+    hex::encode(id)
+}
+
+// ── POST /api/shares ──
+pub async fn create_share(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<CreateShareRequest>,
+) -> impl IntoResponse {
+    if let Err(err) = validate_csrf(&headers) {
+        return err.into_response();
+    }
+    let user_email = match validate_s3_auth(&headers, &state) {
+        Ok(email) => email,
+        Err(err) => return err.into_response(),
+    };
+    let token = match ShareToken::parse(&req.token) {
+        Ok(token) => token,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    if token.share_key().is_some() {
+        return (StatusCode::BAD_REQUEST, "Send the token without its share key").into_response();
+    }
+    if token.claims.scope != SCOPE_RETRIEVE {
+        return (StatusCode::BAD_REQUEST, "Unsupported share scope").into_response();
+    }
+    let expires_at = match Utc.timestamp_opt(token.claims.expires_at as i64, 0).single() {
+        Some(at) if at > Utc::now() => at,
+        _ => return (StatusCode::BAD_REQUEST, "Share token already expired").into_response(),
+    };
+    if let Err(err) = owned_manifest(&state, &token.claims.manifest_root, &user_email).await {
+        return err.into_response();
+    }
+
+    let link = ShareLink {
+        share_id: new_share_id(),
+        manifest_root: token.claims.manifest_root.clone(),
+        owner_email: user_email,
+        token: token.sealed(),
+        expires_at,
+        revoked_at: None,
+    };
+    let share_id = link.share_id.clone();
+    if let Err(err) = store_link(&state, link).await {
+        return err.into_response();
+    }
+    (
+        StatusCode::CREATED,
+        Json(serde_json::json!({
+            "share_id": share_id,
+            "manifest_root": token.claims.manifest_root,
+            "expires_at": expires_at,
+            "path": format!("/api/shares/{}", share_id),
+        })),
+    )
+        .into_response()
+}
+
+// ── GET /api/shares/:id ──
+// Public: holding the link is the capability, and the token is useless
+// without the share key that never reaches the gateway.
+pub async fn get_share(
+    State(state): State<Arc<AppState>>,
+    Path(share_id): Path<String>,
+) -> impl IntoResponse {
+    let row = sqlx::query_as::<_, ShareLink>("SELECT * FROM share_links WHERE share_id = $1")
+        .bind(&share_id)
+        .fetch_optional(&state.db)
+        .await;
+    match row {
+        Ok(Some(link)) if link.revoked_at.is_none() => {
+            if link.expires_at <= Utc::now() {
+                return (StatusCode::GONE, "Share link expired").into_response();
+            }
+            Json(serde_json::json!({
+                "share_id": link.share_id,
+                "manifest_root": link.manifest_root,
+                "expires_at": link.expires_at,
+                "token": link.token,
+            }))
+            .into_response()
+        }
+        Ok(_) => (StatusCode::NOT_FOUND, "Share link not found").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)).into_response(),
+    }
+}
+
+// ── DELETE /api/shares/:id ──
+pub async fn revoke_share(
+    State(state): State<Arc<AppState>>,
+    Path(share_id): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(err) = validate_csrf(&headers) {
+        return err.into_response();
+    }
+    let user_email = match validate_s3_auth(&headers, &state) {
+        Ok(email) => email,
+        Err(err) => return err.into_response(),
+    };
+    let row = sqlx::query_as::<_, ShareLink>("SELECT * FROM share_links WHERE share_id = $1 AND owner_email = $2")
+        .bind(&share_id)
+        .bind(&user_email)
+        .fetch_optional(&state.db)
+        .await;
+    let mut link = match row {
+        Ok(Some(link)) => link,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Share link not found").into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)).into_response(),
+    };
+    if link.revoked_at.is_none() {
+        link.revoked_at = Some(Utc::now());
+        if let Err(err) = store_link(&state, link).await {
+            return err.into_response();
+        }
+    }
+    StatusCode::NO_CONTENT.into_response()
+}
@@ -0,0 +1,250 @@
+use axum::{
+    body::Body,
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::collections::BTreeMap;
+
+use crate::crypto::MetadataProtector;
+use crate::handlers::s3::xml_escape;
+use crate::handlers::s3_error::S3Error;
+use crate::replication::{self, MetadataOp};
+use crate::AppState;
+
+// S3 object tagging: GET/PUT/DELETE `/:bucket/*key?tagging`, plus the
+// `x-amz-tagging` header on PUT. Tags are sealed with the owning tenant's
+// MetadataProtector and kept under `metadata_json.tags`, next to the sealed
+// object metadata, so they replicate with the object row.
+
+pub type TagSet = BTreeMap<String, String>;
+
+const MAX_TAGS: usize = 10;
+const MAX_TAG_KEY_CHARS: usize = 128;
+const MAX_TAG_VALUE_CHARS: usize = 256;
+const MAX_TAGGING_BODY_BYTES: usize = 64 * 1024;
+
+fn check_tags(tags: &[(String, String)]) -> Result<TagSet, (StatusCode, String)> {
+    if tags.len() > MAX_TAGS {
+        return Err((StatusCode::BAD_REQUEST, format!("BadRequest: at most {} tags per object", MAX_TAGS)));
+    }
+    let mut set = TagSet::new();
+    for (key, value) in tags {
+        if key.is_empty() || key.chars().count() > MAX_TAG_KEY_CHARS {
+            return Err((StatusCode::BAD_REQUEST, "InvalidTag: tag keys must be 1-128 characters".to_string()));
+        }
+        if value.chars().count() > MAX_TAG_VALUE_CHARS {
+            return Err((StatusCode::BAD_REQUEST, "InvalidTag: tag values must be at most 256 characters".to_string()));
+        }
+        if set.insert(key.clone(), value.clone()).is_some() {
+            return Err((StatusCode::BAD_REQUEST, format!("InvalidTag: duplicate tag key {}", key)));
+        }
+    }
+    Ok(set)
+}
+
+/// Parses an `x-amz-tagging` header (`k1=v1&k2=v2`, URL-encoded).
+pub(crate) fn parse_tagging_header(headers: &HeaderMap) -> Result<Option<TagSet>, (StatusCode, String)> {
+    let Some(raw) = headers.get("x-amz-tagging") else {
+        return Ok(None);
+    };
+    let raw = raw
+        .to_str()
+        .map_err(|_| (StatusCode::BAD_REQUEST, "InvalidTag: x-amz-tagging is not valid text".to_string()))?;
+    let pairs: Vec<(String, String)> = form_urlencoded::parse(raw.as_bytes()).into_owned().collect();
+    check_tags(&pairs).map(Some)
+}
+
+/// Parses a `<Tagging><TagSet><Tag><Key/><Value/></Tag>…` document.
+fn parse_tagging_xml(xml: &str) -> Result<TagSet, (StatusCode, String)> {
+    let malformed = || (StatusCode::BAD_REQUEST, "MalformedXML".to_string());
+    let tag_set = xml_elements(xml, "TagSet").into_iter().next().ok_or_else(malformed)?;
+    let mut pairs = Vec::new();
+    for tag in xml_elements(tag_set, "Tag") {
+        let key = xml_elements(tag, "Key").into_iter().next().ok_or_else(malformed)?;
+        let value = xml_elements(tag, "Value").into_iter().next().unwrap_or_default();
+        pairs.push((xml_unescape(key.trim()), xml_unescape(value)));
+    }
+    check_tags(&pairs)
+}
+
+fn render_tagging_xml(tags: &TagSet) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<Tagging xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\n");
+    xml.push_str("  <TagSet>\n");
+    for (key, value) in tags {
+        xml.push_str("    <Tag>\n");
+        xml.push_str(&format!("      <Key>{}</Key>\n", xml_escape(key)));
+        xml.push_str(&format!("      <Value>{}</Value>\n", xml_escape(value)));
+        xml.push_str("    </Tag>\n");
+    }
+    xml.push_str("  </TagSet>\n");
+    xml.push_str("</Tagging>");
+    xml
+}
+
+/// Inner text of each top-level `<name>…</name>` in `xml`. Enough for the
+/// flat documents S3 clients send; same-named elements must not nest.
+pub(crate) fn xml_elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        let after_name = &rest[start + open.len()..];
+        // Skip longer names sharing the prefix, e.g. `<TagSet>` for `<Tag`.
+        if !after_name.starts_with(['>', ' ', '\t', '\r', '\n', '/']) {
+            rest = after_name;
+            continue;
+        }
+        let Some(gt) = after_name.find('>') else {
+            break;
+        };
+        if after_name[..gt].ends_with('/') {
+            found.push("");
+            rest = &after_name[gt + 1..];
+            continue;
+        }
+        let body = &after_name[gt + 1..];
+        let Some(end) = body.find(&close) else {
+            break;
+        };
+        found.push(&body[..end]);
+        rest = &body[end + close.len()..];
+    }
+    found
+}
+
+pub(crate) fn xml_unescape(input: &str) -> String {
+    input
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Decrypts the tag set stored in an object's `metadata_json`.
+pub(crate) fn read_tags(protector: &MetadataProtector, metadata_json: Option<&serde_json::Value>) -> TagSet {
+    metadata_json
+        .and_then(|v| v.get("tags"))
+        .and_then(|v| v.as_str())
+        .and_then(|sealed| protector.decrypt(sealed).ok())
+        .and_then(|plain| serde_json::from_str(&plain).ok())
+        .unwrap_or_default()
+}
+
+/// Seals `tags` into `metadata_json`, dropping the entry when empty.
+pub(crate) fn write_tags(
+    protector: &MetadataProtector,
+    metadata_json: &mut serde_json::Value,
+    tags: &TagSet,
+) -> Result<(), (StatusCode, String)> {
+    let Some(map) = metadata_json.as_object_mut() else {
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Object metadata is not a JSON object".to_string()));
+    };
+    if tags.is_empty() {
+        map.remove("tags");
+        return Ok(());
+    }
+    let plain = serde_json::to_string(tags).unwrap_or_else(|_| "{}".to_string());
+    let sealed = protector
+        .encrypt(&plain)
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Tag encryption failed".to_string()))?;
+    map.insert("tags".to_string(), serde_json::Value::String(sealed));
+    Ok(())
+}
+
+async fn find_object(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+) -> Result<crate::models::Object, S3Error> {
+    let key_lookup = crate::key_index::lookup_token(&state.tenant_keys.for_bucket(bucket), bucket, key);
+    let row = sqlx::query_as::<_, crate::models::Object>(
+        "SELECT * FROM objects WHERE bucket = $1 AND key_lookup = $2"
+    )
+    .bind(bucket)
+    .bind(&key_lookup)
+    .fetch_optional(&state.db)
+    .await;
+    match row {
+        Ok(Some(obj)) => Ok(obj),
+        Ok(None) => Err(S3Error::no_such_key(key)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn get_object_tagging(state: &AppState, bucket: &str, key: &str) -> Result<Response, S3Error> {
+    let obj = find_object(state, bucket, key).await?;
+    let tags = read_tags(&state.tenant_keys.for_bucket(bucket), obj.metadata_json.as_ref());
+    let mut headers = HeaderMap::new();
+    headers.insert("Content-Type", HeaderValue::from_static("application/xml"));
+    Ok((StatusCode::OK, headers, render_tagging_xml(&tags)).into_response())
+}
+
+pub async fn put_object_tagging(state: &AppState, bucket: &str, key: &str, body: Body) -> Result<Response, S3Error> {
+    let bytes = match axum::body::to_bytes(body, MAX_TAGGING_BODY_BYTES).await {
+        Ok(b) => b,
+        Err(_) => {
+            return Err(S3Error::new(StatusCode::PAYLOAD_TOO_LARGE, "EntityTooLarge", "Tagging document too large"))
+        }
+    };
+    let Ok(xml) = std::str::from_utf8(&bytes) else {
+        return Err(S3Error::new(StatusCode::BAD_REQUEST, "MalformedXML", "The tagging document is not UTF-8."));
+    };
+    let tags = parse_tagging_xml(xml)?;
+    replace_tags(state, bucket, key, &tags).await
+}
+
+pub async fn delete_object_tagging(state: &AppState, bucket: &str, key: &str) -> Result<Response, S3Error> {
+    replace_tags(state, bucket, key, &TagSet::new()).await
+}
+
+async fn replace_tags(state: &AppState, bucket: &str, key: &str, tags: &TagSet) -> Result<Response, S3Error> {
+    let obj = find_object(state, bucket, key).await?;
+    let protector = state.tenant_keys.for_bucket(bucket);
+    let mut metadata_json = obj.metadata_json.clone().unwrap_or_else(|| serde_json::json!({}));
+    write_tags(&protector, &mut metadata_json, tags)?;
+
+    // Only the version read above may be retagged; an overwrite in between
+    // carries its own metadata.
+    let res = sqlx::query("UPDATE objects SET metadata_json = $1 WHERE bucket = $2 AND key = $3 AND version = $4")
+        .bind(&metadata_json)
+        .bind(bucket)
+        .bind(&obj.key)
+        .bind(obj.version)
+        .execute(&state.db)
+        .await;
+    match res {
+        Ok(done) if done.rows_affected() == 0 => {
+            return Err(S3Error::new(StatusCode::CONFLICT, "OperationAborted", "the object was overwritten; retry"));
+        }
+        Ok(_) => {}
+        Err(e) => {
+            tracing::error!("Database error while updating tags: {}", e);
+            return Err(S3Error::internal("Database error while updating tags"));
+        }
+    }
+
+    replication::publish(state, MetadataOp::UpsertObject {
+        bucket: obj.bucket,
+        key: obj.key,
+        etag: obj.etag,
+        cid: obj.cid,
+        shards: obj.shards,
+        recovery_threshold: obj.recovery_threshold,
+        size: obj.size,
+        metadata_json: Some(metadata_json),
+        key_tokens: Some(crate::key_index::key_tokens(&protector, bucket, key)),
+        key_lookup: Some(crate::key_index::lookup_token(&protector, bucket, key)),
+        version: obj.version,
+    })
+    .await;
+
+    if tags.is_empty() {
+        Ok(StatusCode::NO_CONTENT.into_response())
+    } else {
+        Ok(StatusCode::OK.into_response())
+    }
+}
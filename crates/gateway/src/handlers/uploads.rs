@@ -0,0 +1,729 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use neuro_client_sdk::shard_cid_matches;
+use neuro_protocol::{ChunkCommand, StoreChunkRequest};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::oneshot;
+use tokio::time::{timeout, Duration};
+
+use crate::handlers::limits;
+use crate::handlers::manifests::{record_manifest, validate_layout, UploaderManifest};
+use crate::handlers::policy::BucketAccess;
+use crate::handlers::s3::{authorize_bucket, validate_csrf, validate_s3_auth, xml_escape};
+use crate::handlers::s3_error::S3Error;
+use crate::p2p::SwarmRequest;
+use crate::uploads::{discard_session, UPLOAD_SESSION_TTL_HOURS};
+use crate::AppState;
+
+/// Largest single shard a session accepts; prepared shards are a fraction of
+/// one erasure-coded chunk.
+pub const MAX_UPLOAD_SHARD_BYTES: usize = 64 * 1024 * 1024;
+/// Page size cap for the upload and part listings, as in S3.
+const MAX_LISTING_PAGE: i64 = 1000;
+
+// ── BROWSER UPLOAD SESSIONS ──
+// A browser encrypts and erasure-codes with neuro-client-wasm but cannot
+// reach nodes itself. It opens a session with the manifest it prepared,
+// sends each shard through the gateway, which stores it in the swarm and
+// records the holder, and commits once every shard is placed. The commit
+// registers the manifest exactly as POST /api/manifests would. Progress is
+// kept in Postgres, so an interrupted upload resumes by sending only the
+// shards the session still reports missing.
+//
+// For resuming from another machine, GET /api/uploads lists the caller's
+// open sessions and GET /api/uploads/:id/parts the shards a session holds,
+// as S3 ListMultipartUploads and ListParts documents: the session id is the
+// UploadId, the label (or manifest root) the Key, and each manifest shard a
+// part numbered by its position in the manifest. A session opened with
+// `?bucket=` is also listed by S3 clients, at GET /:bucket?uploads and
+// GET /:bucket/:key?uploadId=.
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct UploadSession {
+    pub session_id: String,
+    pub owner_email: String,
+    pub manifest_root: String,
+    pub label: Option<String>,
+    /// Tenant-scoped name of the bucket the session was opened for.
+    pub bucket: Option<String>,
+    pub manifest_json: serde_json::Value,
+    pub shard_count: i32,
+    pub total_bytes: i64,
+    pub committed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl UploadSession {
+    fn manifest(&self) -> Result<UploaderManifest, (StatusCode, String)> {
+        serde_json::from_value(self.manifest_json.clone())
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Stored manifest unreadable: {}", e)))
+    }
+}
+
+fn new_session_id() -> String {
+    let mut id = [0u8; 16];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut id);
+    hex::encode(id)
+}
+
+async fn owned_session(
+    state: &AppState,
+    session_id: &str,
+    email: &str,
+) -> Result<UploadSession, (StatusCode, String)> {
+    let row = sqlx::query_as::<_, UploadSession>("SELECT * FROM upload_sessions WHERE session_id = $1")
+        .bind(session_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)))?;
+    match row {
+        // An open session past its TTL is gone even before the sweep runs.
+        Some(session) if session.committed_at.is_none() && session.expires_at <= Utc::now() => {
+            Err((StatusCode::NOT_FOUND, "Upload session expired".to_string()))
+        }
+        Some(session) if session.owner_email == email => Ok(session),
+        _ => Err((StatusCode::NOT_FOUND, "Upload session not found".to_string())),
+    }
+}
+
+/// Stored shard CID -> holder peer id.
+async fn stored_shards(state: &AppState, session_id: &str) -> Result<HashMap<String, String>, (StatusCode, String)> {
+    let rows = sqlx::query_as::<_, (String, String)>(
+        "SELECT shard_cid, peer_id FROM upload_session_shards WHERE session_id = $1",
+    )
+    .bind(session_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)))?;
+    Ok(rows.into_iter().collect())
+}
+
+fn progress(session: &UploadSession, manifest: &UploaderManifest, stored: &HashMap<String, String>) -> serde_json::Value {
+    let mut seen = HashSet::new();
+    let missing: Vec<&str> = manifest
+        .shards
+        .iter()
+        .map(|s| s.cid.as_str())
+        .filter(|cid| !stored.contains_key(*cid) && seen.insert(*cid))
+        .collect();
+    let stored_count = manifest.shards.iter().filter(|s| stored.contains_key(&s.cid)).count();
+    serde_json::json!({
+        "session_id": session.session_id,
+        "manifest_root": session.manifest_root,
+        "shard_count": session.shard_count,
+        "total_bytes": session.total_bytes,
+        "stored_shards": stored_count,
+        "missing": missing,
+        "committed": session.committed_at.is_some(),
+        "expires_at": session.expires_at,
+    })
+}
+
+#[derive(Deserialize)]
+pub struct CreateUploadQuery {
+    pub label: Option<String>,
+    /// Bucket whose S3 upload listings include the session.
+    pub bucket: Option<String>,
+}
+
+// ── POST /api/uploads?label=&bucket= ──
+// Body: the manifest the browser prepared; shard peers are ignored.
+pub async fn create_upload(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CreateUploadQuery>,
+    headers: HeaderMap,
+    Json(mut manifest): Json<UploaderManifest>,
+) -> impl IntoResponse {
+    if let Err(err) = validate_csrf(&headers) {
+        return err.into_response();
+    }
+    let user_email = match validate_s3_auth(&headers, &state) {
+        Ok(email) => email,
+        Err(err) => return err.into_response(),
+    };
+    if let Err(reason) = validate_layout(&manifest) {
+        return (StatusCode::BAD_REQUEST, reason).into_response();
+    }
+    if let Err(err) = limits::check_parts(&state.config.limits, manifest.shards.len()) {
+        return err.into_response();
+    }
+    tracing::Span::current().record("cid", manifest.manifest_root.as_str());
+    for shard in &mut manifest.shards {
+        shard.peers.clear();
+    }
+    let manifest_json = match serde_json::to_value(&manifest) {
+        Ok(v) => v,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Manifest serialization failed").into_response(),
+    };
+    let label = query.label.filter(|l| !l.trim().is_empty());
+    let bucket = match query.bucket.filter(|b| !b.is_empty()) {
+        Some(bucket) => {
+            let key = label.as_deref().unwrap_or(&manifest.manifest_root);
+            match authorize_bucket(&state, &bucket, &user_email, BucketAccess::Write, key).await {
+                Ok(scope) => Some(scope.name),
+                Err(err) => return err.into_response(),
+            }
+        }
+        None => None,
+    };
+
+    let session = sqlx::query_as::<_, UploadSession>(
+        r#"
+        INSERT INTO upload_sessions
+            (session_id, owner_email, manifest_root, label, bucket, manifest_json, shard_count, total_bytes, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, NOW() + make_interval(hours => $9))
+        RETURNING *
+        "#,
+    )
+    .bind(new_session_id())
+    .bind(&user_email)
+    .bind(&manifest.manifest_root)
+    .bind(&label)
+    .bind(&bucket)
+    .bind(&manifest_json)
+    .bind(manifest.shards.len() as i32)
+    .bind(manifest.total_bytes as i64)
+    .bind(UPLOAD_SESSION_TTL_HOURS)
+    .fetch_one(&state.db)
+    .await;
+    match session {
+        Ok(session) => {
+            tracing::info!(
+                "Opened upload session {} for manifest {} ({} shards)",
+                session.session_id,
+                session.manifest_root,
+                session.shard_count
+            );
+            (StatusCode::CREATED, Json(progress(&session, &manifest, &HashMap::new()))).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)).into_response(),
+    }
+}
+
+// ── GET /api/uploads/:id ──
+pub async fn get_upload(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let user_email = match validate_s3_auth(&headers, &state) {
+        Ok(email) => email,
+        Err(err) => return err.into_response(),
+    };
+    let loaded = async {
+        let session = owned_session(&state, &session_id, &user_email).await?;
+        let manifest = session.manifest()?;
+        let stored = stored_shards(&state, &session_id).await?;
+        Ok::<_, (StatusCode, String)>(progress(&session, &manifest, &stored))
+    };
+    match loaded.await {
+        Ok(body) => Json(body).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct ListUploadsQuery {
+    #[serde(rename = "max-uploads")]
+    pub max_uploads: Option<i64>,
+    #[serde(rename = "upload-id-marker")]
+    pub upload_id_marker: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct ListPartsQuery {
+    #[serde(rename = "max-parts")]
+    pub max_parts: Option<i64>,
+    #[serde(rename = "part-number-marker")]
+    pub part_number_marker: Option<usize>,
+}
+
+impl ListPartsQuery {
+    /// The paging parameters of an S3 ListParts request.
+    pub(crate) fn from_params(params: &HashMap<String, String>) -> Result<Self, S3Error> {
+        let invalid = |name: &str| {
+            S3Error::new(StatusCode::BAD_REQUEST, "InvalidArgument", format!("{} must be a non-negative integer", name))
+        };
+        Ok(Self {
+            max_parts: params
+                .get("max-parts")
+                .map(|v| v.parse().map_err(|_| invalid("max-parts")))
+                .transpose()?,
+            part_number_marker: params
+                .get("part-number-marker")
+                .map(|v| v.parse().map_err(|_| invalid("part-number-marker")))
+                .transpose()?,
+        })
+    }
+}
+
+/// When a shard was stored and its size, if recorded.
+type StoredPart = (DateTime<Utc>, Option<i64>);
+
+fn upload_key(session: &UploadSession) -> &str {
+    session.label.as_deref().unwrap_or(&session.manifest_root)
+}
+
+fn xml_response(xml: String) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert("Content-Type", HeaderValue::from_static("application/xml"));
+    (StatusCode::OK, headers, xml).into_response()
+}
+
+fn no_such_upload(upload_id: &str) -> S3Error {
+    S3Error::new(StatusCode::NOT_FOUND, "NoSuchUpload", "The specified upload does not exist.").with_resource(upload_id)
+}
+
+/// The caller's open session `upload_id`, with lookup misses as NoSuchUpload.
+async fn open_session(state: &AppState, upload_id: &str, email: &str) -> Result<UploadSession, S3Error> {
+    match owned_session(state, upload_id, email).await {
+        Ok(session) if session.committed_at.is_none() => Ok(session),
+        Ok(_) => Err(no_such_upload(upload_id)),
+        Err((StatusCode::NOT_FOUND, _)) => Err(no_such_upload(upload_id)),
+        Err(err) => Err(err.into()),
+    }
+}
+
+// ── GET /api/uploads?max-uploads=&upload-id-marker= ──
+pub async fn list_uploads(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListUploadsQuery>,
+    headers: HeaderMap,
+) -> Result<Response, S3Error> {
+    let user_email = validate_s3_auth(&headers, &state)?;
+    list_multipart_uploads(&state, &user_email, None, &query).await
+}
+
+/// The caller's open sessions in upload id order, as an S3
+/// ListMultipartUploadsResult; with `bucket` (tenant-scoped), only those
+/// opened for it. Committed and expired sessions are left out.
+pub(crate) async fn list_multipart_uploads(
+    state: &AppState,
+    owner_email: &str,
+    bucket: Option<&str>,
+    query: &ListUploadsQuery,
+) -> Result<Response, S3Error> {
+    let max_uploads = query.max_uploads.unwrap_or(MAX_LISTING_PAGE).clamp(1, MAX_LISTING_PAGE);
+    let marker = query.upload_id_marker.as_deref().filter(|m| !m.is_empty());
+    let mut sessions = sqlx::query_as::<_, UploadSession>(
+        r#"
+        SELECT * FROM upload_sessions
+        WHERE owner_email = $1
+          AND committed_at IS NULL
+          AND expires_at > NOW()
+          AND ($2::TEXT IS NULL OR session_id > $2)
+          AND ($4::TEXT IS NULL OR bucket = $4)
+        ORDER BY session_id
+        LIMIT $3
+        "#,
+    )
+    .bind(owner_email)
+    .bind(marker)
+    .bind(max_uploads + 1)
+    .bind(bucket)
+    .fetch_all(&state.db)
+    .await?;
+    let is_truncated = sessions.len() as i64 > max_uploads;
+    sessions.truncate(max_uploads as usize);
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<ListMultipartUploadsResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\n");
+    xml.push_str(&format!("  <UploadIdMarker>{}</UploadIdMarker>\n", xml_escape(marker.unwrap_or_default())));
+    if is_truncated {
+        if let Some(last) = sessions.last() {
+            xml.push_str(&format!("  <NextUploadIdMarker>{}</NextUploadIdMarker>\n", xml_escape(&last.session_id)));
+        }
+    }
+    xml.push_str(&format!("  <MaxUploads>{}</MaxUploads>\n", max_uploads));
+    xml.push_str(&format!("  <IsTruncated>{}</IsTruncated>\n", is_truncated));
+    for session in &sessions {
+        xml.push_str("  <Upload>\n");
+        xml.push_str(&format!("    <Key>{}</Key>\n", xml_escape(upload_key(session))));
+        xml.push_str(&format!("    <UploadId>{}</UploadId>\n", xml_escape(&session.session_id)));
+        xml.push_str(&format!(
+            "    <Initiator>\n      <ID>{0}</ID>\n      <DisplayName>{0}</DisplayName>\n    </Initiator>\n",
+            xml_escape(&session.owner_email)
+        ));
+        xml.push_str("    <StorageClass>STANDARD</StorageClass>\n");
+        xml.push_str(&format!("    <Initiated>{}</Initiated>\n", session.created_at.to_rfc3339()));
+        xml.push_str("  </Upload>\n");
+    }
+    xml.push_str("</ListMultipartUploadsResult>");
+    Ok(xml_response(xml))
+}
+
+// ── GET /api/uploads/:id/parts?max-parts=&part-number-marker= ──
+pub async fn list_upload_parts(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+    Query(query): Query<ListPartsQuery>,
+    headers: HeaderMap,
+) -> Result<Response, S3Error> {
+    let user_email = validate_s3_auth(&headers, &state)?;
+    let session = open_session(&state, &session_id, &user_email).await?;
+    list_parts(&state, &session, &query).await
+}
+
+/// S3 ListParts for `key` in `bucket` (tenant-scoped): the session must
+/// have been opened for that bucket under that key.
+pub(crate) async fn list_object_parts(
+    state: &AppState,
+    owner_email: &str,
+    bucket: &str,
+    key: &str,
+    upload_id: &str,
+    query: &ListPartsQuery,
+) -> Result<Response, S3Error> {
+    let session = open_session(state, upload_id, owner_email).await?;
+    if session.bucket.as_deref() != Some(bucket) || upload_key(&session) != key {
+        return Err(no_such_upload(upload_id));
+    }
+    list_parts(state, &session, query).await
+}
+
+/// The shards an open session holds, as an S3 ListPartsResult. Part N is
+/// the Nth shard of the manifest; shards not yet sent are not listed.
+async fn list_parts(state: &AppState, session: &UploadSession, query: &ListPartsQuery) -> Result<Response, S3Error> {
+    let manifest = session.manifest()?;
+    let stored: HashMap<String, StoredPart> = sqlx::query_as::<_, (String, DateTime<Utc>, Option<i64>)>(
+        "SELECT shard_cid, stored_at, size_bytes FROM upload_session_shards WHERE session_id = $1",
+    )
+    .bind(&session.session_id)
+    .fetch_all(&state.db)
+    .await?
+    .into_iter()
+    .map(|(cid, at, size)| (cid, (at, size)))
+    .collect();
+
+    let max_parts = query.max_parts.unwrap_or(MAX_LISTING_PAGE).clamp(1, MAX_LISTING_PAGE) as usize;
+    let marker = query.part_number_marker.unwrap_or(0);
+    let mut parts: Vec<(usize, &str, &StoredPart)> = manifest
+        .shards
+        .iter()
+        .enumerate()
+        .map(|(i, shard)| (i + 1, shard.cid.as_str()))
+        .filter(|(number, _)| *number > marker)
+        .filter_map(|(number, cid)| stored.get(cid).map(|found| (number, cid, found)))
+        .take(max_parts + 1)
+        .collect();
+    let is_truncated = parts.len() > max_parts;
+    parts.truncate(max_parts);
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<ListPartsResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\n");
+    xml.push_str(&format!("  <Key>{}</Key>\n", xml_escape(upload_key(session))));
+    xml.push_str(&format!("  <UploadId>{}</UploadId>\n", xml_escape(&session.session_id)));
+    xml.push_str(&format!(
+        "  <Initiator>\n    <ID>{0}</ID>\n    <DisplayName>{0}</DisplayName>\n  </Initiator>\n",
+        xml_escape(&session.owner_email)
+    ));
+    xml.push_str("  <StorageClass>STANDARD</StorageClass>\n");
+    xml.push_str(&format!("  <PartNumberMarker>{}</PartNumberMarker>\n", marker));
+    if is_truncated {
+        if let Some((last, _, _)) = parts.last() {
+            xml.push_str(&format!("  <NextPartNumberMarker>{}</NextPartNumberMarker>\n", last));
+        }
+    }
+    xml.push_str(&format!("  <MaxParts>{}</MaxParts>\n", max_parts));
+    xml.push_str(&format!("  <IsTruncated>{}</IsTruncated>\n", is_truncated));
+    for (number, cid, (stored_at, size)) in &parts {
+        xml.push_str("  <Part>\n");
+        xml.push_str(&format!("    <PartNumber>{}</PartNumber>\n", number));
+        xml.push_str(&format!("    <LastModified>{}</LastModified>\n", stored_at.to_rfc3339()));
+        xml.push_str(&format!("    <ETag>\"{}\"</ETag>\n", xml_escape(cid)));
+        if let Some(size) = size {
+            xml.push_str(&format!("    <Size>{}</Size>\n", size));
+        }
+        xml.push_str("  </Part>\n");
+    }
+    xml.push_str("</ListPartsResult>");
+    Ok(xml_response(xml))
+}
+
+// ── PUT /api/uploads/:id/shards/:cid ──
+// Stores one prepared shard. Re-sending a stored shard is a no-op, so a
+// client that lost track of what it sent can simply retry.
+pub async fn put_upload_shard(
+    State(state): State<Arc<AppState>>,
+    Path((session_id, shard_cid)): Path<(String, String)>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    if let Err(err) = validate_csrf(&headers) {
+        return err.into_response();
+    }
+    let user_email = match validate_s3_auth(&headers, &state) {
+        Ok(email) => email,
+        Err(err) => return err.into_response(),
+    };
+    tracing::Span::current().record("cid", shard_cid.as_str());
+    let session = match owned_session(&state, &session_id, &user_email).await {
+        Ok(s) => s,
+        Err(err) => return err.into_response(),
+    };
+    if session.committed_at.is_some() {
+        return (StatusCode::CONFLICT, "Upload session already committed").into_response();
+    }
+    let manifest = match session.manifest() {
+        Ok(m) => m,
+        Err(err) => return err.into_response(),
+    };
+    if !manifest.shards.iter().any(|s| s.cid == shard_cid) {
+        return (StatusCode::NOT_FOUND, "Shard not in manifest").into_response();
+    }
+    let mut stored = match stored_shards(&state, &session_id).await {
+        Ok(s) => s,
+        Err(err) => return err.into_response(),
+    };
+    if stored.contains_key(&shard_cid) {
+        return Json(progress(&session, &manifest, &stored)).into_response();
+    }
+    if !shard_cid_matches(&shard_cid, &body) {
+        return (StatusCode::BAD_REQUEST, "Shard bytes do not match the CID").into_response();
+    }
+
+    let (tx, rx) = oneshot::channel();
+    let req = SwarmRequest::Store {
+        command: ChunkCommand::Store(StoreChunkRequest {
+            cid: shard_cid.clone(),
+            data: body.to_vec(),
+        }),
+        geofence: "GLOBAL".to_string(),
+        tx,
+    };
+    if state.p2p_tx.send(req).await.is_err() {
+        return (StatusCode::SERVICE_UNAVAILABLE, "Swarm unavailable").into_response();
+    }
+    let ack = match timeout(Duration::from_secs(15), rx).await {
+        Ok(Ok(ack)) if ack.stored => ack,
+        _ => return (StatusCode::SERVICE_UNAVAILABLE, "No storage node accepted the shard").into_response(),
+    };
+
+    // Every stored shard extends the session, so a slow upload that keeps
+    // making progress never expires under the client.
+    let recorded = sqlx::query(
+        r#"
+        WITH placed AS (
+            INSERT INTO upload_session_shards (session_id, shard_cid, peer_id, country_code, size_bytes)
+            VALUES ($1, $2, $3, $4, $6)
+            ON CONFLICT (session_id, shard_cid) DO NOTHING
+        )
+        UPDATE upload_sessions
+        SET expires_at = GREATEST(expires_at, NOW() + make_interval(hours => $5))
+        WHERE session_id = $1
+        "#,
+    )
+    .bind(&session_id)
+    .bind(&shard_cid)
+    .bind(&ack.peer_id)
+    .bind(&ack.country_code)
+    .bind(UPLOAD_SESSION_TTL_HOURS)
+    .bind(body.len() as i64)
+    .execute(&state.db)
+    .await;
+    if let Err(e) = recorded {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)).into_response();
+    }
+    stored.insert(shard_cid, ack.peer_id);
+    (StatusCode::CREATED, Json(progress(&session, &manifest, &stored))).into_response()
+}
+
+// ── POST /api/uploads/:id/commit ──
+// Fills in each shard's holder and registers the manifest. Answers 409 with
+// the progress document while shards are still missing.
+pub async fn commit_upload(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(err) = validate_csrf(&headers) {
+        return err.into_response();
+    }
+    let user_email = match validate_s3_auth(&headers, &state) {
+        Ok(email) => email,
+        Err(err) => return err.into_response(),
+    };
+    let session = match owned_session(&state, &session_id, &user_email).await {
+        Ok(s) => s,
+        Err(err) => return err.into_response(),
+    };
+    let mut manifest = match session.manifest() {
+        Ok(m) => m,
+        Err(err) => return err.into_response(),
+    };
+    let stored = match stored_shards(&state, &session_id).await {
+        Ok(s) => s,
+        Err(err) => return err.into_response(),
+    };
+    if manifest.shards.iter().any(|s| !stored.contains_key(&s.cid)) {
+        return (StatusCode::CONFLICT, Json(progress(&session, &manifest, &stored))).into_response();
+    }
+    for shard in &mut manifest.shards {
+        shard.peers = vec![format!("/p2p/{}", stored[&shard.cid])];
+    }
+
+    if session.committed_at.is_none() {
+        if let Err(err) = record_manifest(&state, &user_email, session.label.clone(), &manifest).await {
+            return err.into_response();
+        }
+        let committed = sqlx::query(
+            r#"
+            UPDATE upload_sessions
+            SET committed_at = NOW(), expires_at = NOW() + make_interval(hours => $2)
+            WHERE session_id = $1
+            "#,
+        )
+        .bind(&session_id)
+        .bind(UPLOAD_SESSION_TTL_HOURS)
+        .execute(&state.db)
+        .await;
+        if let Err(e) = committed {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)).into_response();
+        }
+        tracing::info!("Committed upload session {} as manifest {}", session_id, manifest.manifest_root);
+    }
+    (
+        StatusCode::CREATED,
+        Json(serde_json::json!({
+            "session_id": session_id,
+            "manifest_root": manifest.manifest_root,
+            "manifest": manifest,
+        })),
+    )
+        .into_response()
+}
+
+// ── DELETE /api/uploads/:id ──
+// Abandons an open session and deletes the shards it stored.
+pub async fn abort_upload(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(err) = validate_csrf(&headers) {
+        return err.into_response();
+    }
+    let user_email = match validate_s3_auth(&headers, &state) {
+        Ok(email) => email,
+        Err(err) => return err.into_response(),
+    };
+    let session = match owned_session(&state, &session_id, &user_email).await {
+        Ok(s) => s,
+        Err(err) => return err.into_response(),
+    };
+    if session.committed_at.is_some() {
+        return (StatusCode::CONFLICT, "Upload session already committed").into_response();
+    }
+    match discard_session(&state, &session_id, true).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::s3::{get_object, list_objects, ListQuery};
+    use crate::test_support::{auth, drop_db, scratch_db, state, OWNER};
+    use crate::tenancy::BucketScope;
+
+    async fn open(db: &sqlx::PgPool, session_id: &str, bucket: Option<&str>) {
+        let manifest = serde_json::json!({
+            "version": "2.3.0",
+            "salt": "00",
+            "manifest_root": format!("root-{session_id}"),
+            "total_bytes": 8,
+            "chunk_count": 1,
+            "shards": [
+                { "chunk_index": 0, "shard_index": 0, "cid": format!("{session_id}-a"), "payload_len": 4, "data_shards": 1, "parity_shards": 1 },
+                { "chunk_index": 0, "shard_index": 1, "cid": format!("{session_id}-b"), "payload_len": 4, "data_shards": 1, "parity_shards": 1 },
+            ],
+        });
+        sqlx::query(
+            r#"
+            INSERT INTO upload_sessions
+                (session_id, owner_email, manifest_root, label, bucket, manifest_json, shard_count, total_bytes, expires_at)
+            VALUES ($1, $2, $3, 'video.mp4', $4, $5, 2, 8, NOW() + INTERVAL '1 hour')
+            "#,
+        )
+        .bind(session_id)
+        .bind(OWNER)
+        .bind(format!("root-{session_id}"))
+        .bind(bucket)
+        .bind(manifest)
+        .execute(db)
+        .await
+        .expect("session");
+        sqlx::query("INSERT INTO upload_session_shards (session_id, shard_cid, peer_id, size_bytes) VALUES ($1, $2, 'peer-0', 4)")
+            .bind(session_id)
+            .bind(format!("{session_id}-a"))
+            .execute(db)
+            .await
+            .expect("shard");
+    }
+
+    async fn text(response: Response) -> (StatusCode, String) {
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.expect("body");
+        (status, String::from_utf8(body.to_vec()).expect("utf8"))
+    }
+
+    #[tokio::test]
+    async fn s3_routes_list_a_buckets_uploads_and_parts() {
+        let Some((db, name)) = scratch_db().await else {
+            eprintln!("DATABASE_URL not set; skipping");
+            return;
+        };
+        sqlx::query("INSERT INTO users (email, password_hash) VALUES ($1, 'x')")
+            .bind(OWNER)
+            .execute(&db)
+            .await
+            .expect("user");
+        let state = state(db.clone());
+        let photos = BucketScope::resolve(&state, OWNER, "photos").await.expect("scope").name;
+        open(&db, "in-photos", Some(&photos)).await;
+        open(&db, "no-bucket", None).await;
+
+        let query = Query::<ListQuery>::try_from_uri(&"/photos?uploads&max-uploads=10".parse().unwrap()).expect("query");
+        let listed = list_objects(State(Arc::clone(&state)), Path("photos".to_string()), query, auth(&state))
+            .await
+            .unwrap_or_else(IntoResponse::into_response);
+        let (status, body) = text(listed).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("<UploadId>in-photos</UploadId>"));
+        assert!(!body.contains("no-bucket"));
+
+        let parts = |key: &str, upload_id: &str| {
+            get_object(
+                State(Arc::clone(&state)),
+                Path(("photos".to_string(), key.to_string())),
+                Query(HashMap::from([("uploadId".to_string(), upload_id.to_string())])),
+                auth(&state),
+            )
+        };
+        let (status, body) = text(parts("video.mp4", "in-photos").await.unwrap_or_else(IntoResponse::into_response)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("<PartNumber>1</PartNumber>"));
+        assert!(!body.contains("<PartNumber>2</PartNumber>"));
+
+        // Another key, or a session outside the bucket, is not this upload.
+        for (key, upload_id) in [("other.mp4", "in-photos"), ("video.mp4", "no-bucket")] {
+            let (status, body) = text(parts(key, upload_id).await.unwrap_or_else(IntoResponse::into_response)).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert!(body.contains("<Code>NoSuchUpload</Code>"));
+        }
+        drop(state);
+        drop_db(db, &name).await;
+    }
+}
@@ -0,0 +1,189 @@
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Key, Nonce,
+};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{info, warn};
+
+use crate::handlers::backups::check_admin_token;
+use crate::handlers::s3::record_request_fields;
+use crate::models::Object;
+use crate::retrieval;
+use crate::AppState;
+
+// ── OBJECT INTEGRITY VERIFICATION ──
+// A GET that never serves the data: fetch the fewest shards that can
+// rebuild the object, decode and decrypt them, and check the result against
+// the stored CID and ETag. Only the verdict leaves the gateway; it is
+// recorded in `object_health` and signed like the sovereignty audit.
+// Guarded by `ADMIN_TOKEN` in `x-admin-token`.
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Serialize)]
+pub struct VerifyVerdict {
+    pub bucket: String,
+    pub key: String,
+    pub object_cid: String,
+    pub version: i64,
+    pub healthy: bool,
+    pub shards_total: i32,
+    pub recovery_threshold: i32,
+    pub shards_fetched: i32,
+    /// First check that failed, when not healthy.
+    pub failure: Option<String>,
+    pub elapsed_ms: u64,
+    pub verified_at: String,
+    pub signature: String,
+}
+
+pub async fn verify_object(
+    State(state): State<Arc<AppState>>,
+    Path((bucket, key)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    check_admin_token(&state, &headers)?;
+    let key = key.trim_start_matches('/').to_string();
+    record_request_fields(&bucket, Some(&key));
+
+    // Admin routes take the stored bucket name, `<tenant_id>:<bucket>`
+    // outside the default tenant.
+    let key_lookup = crate::key_index::lookup_token(&state.tenant_keys.for_bucket(&bucket), &bucket, &key);
+    let obj = sqlx::query_as::<_, Object>("SELECT * FROM objects WHERE bucket = $1 AND key_lookup = $2")
+        .bind(&bucket)
+        .bind(&key_lookup)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Database Error".to_string()))?
+        .ok_or((StatusCode::NOT_FOUND, "NoSuchKey".to_string()))?;
+
+    let verdict = verify(&state, &bucket, &key, &obj)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Recording object health failed: {}", e)))?;
+    Ok(Json(verdict))
+}
+
+/// Replays the decode of `obj` (stored under the plain `key`), records the
+/// verdict in `object_health` and returns it signed. Also used by the
+/// bucket health scan.
+pub(crate) async fn verify(state: &AppState, bucket: &str, key: &str, obj: &Object) -> Result<VerifyVerdict, sqlx::Error> {
+    let started = Instant::now();
+    let threshold = obj.recovery_threshold.max(0) as usize;
+    let (plan, mut shards) =
+        retrieval::fetch_minimum(state, &obj.cid, obj.shards.max(0) as usize, threshold).await;
+    let fetched = shards.count();
+    let outcome = if fetched < threshold {
+        Err(format!("only {} of the {} shards needed are retrievable", fetched, threshold))
+    } else {
+        match retrieval::rebuild(state, &plan, &obj.cid, &mut shards, threshold, obj.size.max(0) as usize).await {
+            Ok(encrypted) => check_content(&encrypted, &obj.etag, content_key(state, obj)),
+            Err(reason) => Err(reason),
+        }
+    };
+
+    let verified_at = chrono::Utc::now();
+    let healthy = outcome.is_ok();
+    let failure = outcome.err();
+    let signing_payload = format!(
+        "bucket={};key={};cid={};version={};healthy={};fetched={}/{};ts={}",
+        bucket,
+        key,
+        obj.cid,
+        obj.version,
+        healthy,
+        fetched,
+        obj.recovery_threshold,
+        verified_at.to_rfc3339()
+    );
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(state.compliance_signing_key.as_bytes())
+        .expect("HMAC key length is valid");
+    mac.update(signing_payload.as_bytes());
+    let signature = format!("0x{}", hex::encode(mac.finalize().into_bytes()));
+
+    sqlx::query(
+        r#"
+        INSERT INTO object_health (
+            object_cid, bucket, object_version, healthy, shards_fetched, shards_total, failure, signature, verified_at
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (object_cid) DO UPDATE SET
+            bucket = excluded.bucket,
+            object_version = excluded.object_version,
+            healthy = excluded.healthy,
+            shards_fetched = excluded.shards_fetched,
+            shards_total = excluded.shards_total,
+            failure = excluded.failure,
+            signature = excluded.signature,
+            verified_at = excluded.verified_at
+        "#,
+    )
+    .bind(&obj.cid)
+    .bind(bucket)
+    .bind(obj.version)
+    .bind(healthy)
+    .bind(fetched as i32)
+    .bind(obj.shards)
+    .bind(&failure)
+    .bind(&signature)
+    .bind(verified_at)
+    .execute(&state.db)
+    .await?;
+
+    match &failure {
+        None => info!("VERIFY OK: {}/{} rebuilt from {} shards", bucket, key, fetched),
+        Some(reason) => warn!("VERIFY FAILED: {}/{}: {}", bucket, key, reason),
+    }
+    Ok(VerifyVerdict {
+        bucket: bucket.to_string(),
+        key: key.to_string(),
+        object_cid: obj.cid.clone(),
+        version: obj.version,
+        healthy,
+        shards_total: obj.shards,
+        recovery_threshold: obj.recovery_threshold,
+        shards_fetched: fetched as i32,
+        failure,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+        verified_at: verified_at.to_rfc3339(),
+        signature,
+    })
+}
+
+/// The AES key PUT sealed the object under, from its protected metadata.
+fn content_key(state: &AppState, obj: &Object) -> Option<zeroize::Zeroizing<Vec<u8>>> {
+    let sealed = obj.metadata_json.as_ref()?.get("encrypted")?.as_str()?;
+    let metadata: serde_json::Value = serde_json::from_str(&state.tenant_keys.for_bucket(&obj.bucket).decrypt(sealed).ok()?).ok()?;
+    let key = hex::decode(metadata.get("encryption_key")?.as_str()?).ok()?;
+    (key.len() == 32).then(|| zeroize::Zeroizing::new(key))
+}
+
+/// Checks the body `retrieval::rebuild` matched to the CID one layer
+/// further: it must decrypt, and the plaintext must match the ETag.
+fn check_content(
+    encrypted: &[u8],
+    etag: &str,
+    content_key: Option<zeroize::Zeroizing<Vec<u8>>>,
+) -> Result<(), String> {
+    let content_key = content_key.ok_or_else(|| "object metadata has no content key".to_string())?;
+    if encrypted.len() <= 12 {
+        return Err("encrypted body is truncated".to_string());
+    }
+    let (nonce, ciphertext) = encrypted.split_at(12);
+    let plain = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&content_key))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "content does not decrypt under its key".to_string())?;
+    if format!("\"{:x}\"", Md5::digest(&plain)) != etag {
+        return Err("decrypted content does not match the ETag".to_string());
+    }
+    Ok(())
+}
@@ -0,0 +1,337 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use neuro_protocol::sentinel::PRICE_CURRENCY;
+use neuro_voucher::{Redemption, Voucher, VoucherError};
+use std::sync::Arc;
+
+use crate::handlers::estimate::BYTES_PER_GB;
+use crate::models::Object;
+use crate::slashing;
+use crate::AppState;
+
+// ── BANDWIDTH VOUCHERS ──
+// Every presigned manifest carries a voucher capping how many shard bytes
+// may be pulled for it and until when. /api/shard counts what it serves
+// against that cap, and nodes that speak `vouchers` enforce it too. Once a
+// voucher has expired, each node that served under it redeems the bytes
+// here for egress earnings.
+
+/// How long a manifest's voucher may be used.
+pub(crate) const VOUCHER_TTL_SECS: u64 = 3600;
+/// Slack per shard for padding, compression framing and encryption, on top
+/// of an even split of the object.
+const SHARD_OVERHEAD_BYTES: u64 = 64 * 1024;
+/// How long after expiry a voucher is still settled.
+const REDEMPTION_WINDOW_SECS: u64 = 7 * 24 * 60 * 60;
+/// Paid per GB served under a voucher, in `PRICE_CURRENCY`.
+pub(crate) const EGRESS_PAYOUT_PER_GB: f64 = 0.10;
+
+pub(crate) fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Mints the voucher for a presigned manifest of `obj`. It covers every
+/// shard once, so a client may fall back to parity shards but cannot pull
+/// the object over and over.
+pub(crate) fn mint_voucher(state: &AppState, principal: &str, obj: &Object) -> String {
+    let shards = obj.shards.max(1) as u64;
+    let max_bytes = shards.saturating_mul(shard_allowance(obj.size, obj.recovery_threshold));
+    Voucher::new(principal, &obj.cid, max_bytes, now_secs() + VOUCHER_TTL_SECS)
+        .mint(state.jwt_secret.as_bytes())
+}
+
+/// The most one shard of an object of `size` bytes may take to serve.
+fn shard_allowance(size: i64, recovery_threshold: i32) -> u64 {
+    let shard_bytes = (size.max(0) as u64).div_ceil(recovery_threshold.max(1) as u64);
+    shard_bytes.saturating_add(SHARD_OVERHEAD_BYTES)
+}
+
+/// The voucher behind `token`, if the gateway minted it and it has not
+/// expired.
+pub(crate) fn verify_voucher(state: &AppState, token: &str) -> Result<Voucher, VoucherError> {
+    Voucher::verify(token, state.jwt_secret.as_bytes(), now_secs())
+}
+
+/// Counts `bytes` served by the gateway against the voucher, returning how
+/// many it has left.
+pub(crate) fn charge_voucher(state: &AppState, voucher: &Voucher, bytes: u64) -> Result<u64, VoucherError> {
+    state
+        .vouchers
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .charge(voucher, bytes, now_secs())
+}
+
+/// Refuses a voucher whose cap is already spent, before anything is
+/// fetched for it.
+pub(crate) fn check_remaining(state: &AppState, voucher: &Voucher) -> Result<(), VoucherError> {
+    let used = state.vouchers.lock().unwrap_or_else(|e| e.into_inner()).used(&voucher.id);
+    if used >= voucher.max_bytes {
+        return Err(VoucherError::Exhausted { used, max_bytes: voucher.max_bytes });
+    }
+    Ok(())
+}
+
+/// The /api/shard answer for a voucher that cannot be served under.
+pub(crate) fn refusal(e: VoucherError) -> (StatusCode, String) {
+    let status = match e {
+        VoucherError::Exhausted { .. } => StatusCode::TOO_MANY_REQUESTS,
+        _ => StatusCode::FORBIDDEN,
+    };
+    (status, e.to_string())
+}
+
+fn refuse(status: StatusCode, reason: impl Into<String>) -> axum::response::Response {
+    (status, reason.into()).into_response()
+}
+
+// ── POST /api/vouchers/redeem ──
+// A node claims the bytes it served under an expired voucher, signed with
+// its identity key. Each node redeems a voucher once; credits for one
+// voucher never add up past its cap, whichever nodes claim it. Answers 409
+// for a repeat and 422 for a voucher that will never be settled, so the
+// node can stop retrying either.
+pub async fn redeem_voucher(
+    State(state): State<Arc<AppState>>,
+    Json(redemption): Json<Redemption>,
+) -> impl IntoResponse {
+    if !redemption.verify_signer() {
+        return refuse(StatusCode::UNAUTHORIZED, "Redemption is not signed by its peer id");
+    }
+    // Only registered nodes have a wallet to be paid into.
+    let registered = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM nodes WHERE peer_id = $1 AND wallet_address IS NOT NULL)",
+    )
+    .bind(&redemption.peer_id)
+    .fetch_one(&state.db)
+    .await;
+    match registered {
+        Ok(true) => {}
+        Ok(false) => return refuse(StatusCode::FORBIDDEN, "Peer is not a registered node"),
+        Err(e) => {
+            tracing::error!("Voucher redemption lookup failed: {}", e);
+            return refuse(StatusCode::INTERNAL_SERVER_ERROR, "Database Error");
+        }
+    }
+    let voucher = match Voucher::verify_signature(&redemption.voucher, state.jwt_secret.as_bytes()) {
+        Ok(voucher) => voucher,
+        Err(e) => return refuse(StatusCode::UNPROCESSABLE_ENTITY, e.to_string()),
+    };
+    let now = now_secs();
+    if !voucher.is_expired(now) {
+        return refuse(StatusCode::TOO_EARLY, "Voucher has not expired yet; redeem it once it has");
+    }
+    if now >= voucher.expires_at.saturating_add(REDEMPTION_WINDOW_SECS) {
+        return refuse(StatusCode::UNPROCESSABLE_ENTITY, "Voucher is past its redemption window");
+    }
+
+    // A node is paid only for shards of the object the gateway placed on
+    // it, and never for more bytes than those shards hold.
+    let placed = match placed_bytes(&state, &voucher.object_cid, &redemption.peer_id).await {
+        Ok(0) => return refuse(StatusCode::UNPROCESSABLE_ENTITY, "Peer holds no shards of the voucher's object"),
+        Ok(placed) => placed,
+        Err(e) => {
+            tracing::error!("Voucher redemption lookup failed: {}", e);
+            return refuse(StatusCode::INTERNAL_SERVER_ERROR, "Database Error");
+        }
+    };
+
+    match settle(&state, &voucher, &redemption, placed).await {
+        Ok(Some((credited_bytes, credited, withheld))) => {
+            tracing::info!(
+                voucher = %voucher.id,
+                peer_id = %redemption.peer_id,
+                claimed = redemption.bytes,
+                credited_bytes,
+                withheld,
+                "Voucher redeemed"
+            );
+            Json(serde_json::json!({
+                "voucher_id": voucher.id,
+                "peer_id": redemption.peer_id,
+                "claimed_bytes": redemption.bytes,
+                "credited_bytes": credited_bytes,
+                "credited": credited,
+                "currency": PRICE_CURRENCY,
+                "withheld": withheld,
+            }))
+            .into_response()
+        }
+        Ok(None) => refuse(StatusCode::CONFLICT, "Voucher already redeemed by this node"),
+        Err(e) => {
+            tracing::error!("Voucher redemption failed: {}", e);
+            refuse(StatusCode::INTERNAL_SERVER_ERROR, "Database Error")
+        }
+    }
+}
+
+/// The bytes of `object_cid`'s shards placed on `peer_id`, each counted at
+/// the allowance its voucher was minted with.
+async fn placed_bytes(state: &AppState, object_cid: &str, peer_id: &str) -> Result<u64, sqlx::Error> {
+    let held: i64 = sqlx::query_scalar(
+        "SELECT COUNT(DISTINCT shard_index)::BIGINT FROM object_shards WHERE object_cid = $1 AND peer_id = $2",
+    )
+    .bind(object_cid)
+    .bind(peer_id)
+    .fetch_one(&state.db)
+    .await?;
+    if held <= 0 {
+        return Ok(0);
+    }
+    let layout = sqlx::query_as::<_, (i64, i32)>(
+        "SELECT size, recovery_threshold FROM objects WHERE cid = $1 ORDER BY version DESC LIMIT 1",
+    )
+    .bind(object_cid)
+    .fetch_optional(&state.db)
+    .await?;
+    Ok(layout.map_or(0, |(size, recovery_threshold)| {
+        (held as u64).saturating_mul(shard_allowance(size, recovery_threshold))
+    }))
+}
+
+/// Records the redemption and credits the node in one transaction, or
+/// `None` if the node already redeemed this voucher. The credit is capped
+/// at both the voucher's remaining bytes and the `placed` bytes the node
+/// holds, and is returned with whether it was withheld.
+async fn settle(
+    state: &AppState,
+    voucher: &Voucher,
+    redemption: &Redemption,
+    placed: u64,
+) -> Result<Option<(u64, f64, bool)>, sqlx::Error> {
+    let mut tx = state.db.begin().await?;
+    // Serializes redemptions of the same voucher, so two nodes cannot both
+    // be credited the last of its cap.
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+        .bind(&voucher.id)
+        .execute(&mut *tx)
+        .await?;
+    let already: i64 = sqlx::query_scalar(
+        "SELECT COALESCE(SUM(credited_bytes), 0)::BIGINT FROM voucher_redemptions WHERE voucher_id = $1",
+    )
+    .bind(&voucher.id)
+    .fetch_one(&mut *tx)
+    .await?;
+    let left = voucher.max_bytes.saturating_sub(already.max(0) as u64);
+    let credited_bytes = redemption.bytes.min(left).min(placed);
+    let credited = credited_bytes as f64 / BYTES_PER_GB * EGRESS_PAYOUT_PER_GB;
+
+    let inserted = sqlx::query(
+        r#"
+        INSERT INTO voucher_redemptions (voucher_id, peer_id, object_cid, bytes, credited_bytes, credited_inr)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (voucher_id, peer_id) DO NOTHING
+        "#,
+    )
+    .bind(&voucher.id)
+    .bind(&redemption.peer_id)
+    .bind(&voucher.object_cid)
+    .bind(redemption.bytes.min(i64::MAX as u64) as i64)
+    .bind(credited_bytes as i64)
+    .bind(credited)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if inserted == 0 {
+        return Ok(None);
+    }
+
+    // A node past the penalty threshold is still credited, but as withheld.
+    let withheld = slashing::payouts_withheld(&mut *tx, &redemption.peer_id).await?;
+    let (earned, held) = if withheld { (0.0, credited) } else { (credited, 0.0) };
+    sqlx::query(
+        r#"
+        INSERT INTO node_earnings (peer_id, egress_bytes, earned_inr, withheld_inr, updated_at)
+        VALUES ($1, $2, $3, $4, NOW())
+        ON CONFLICT (peer_id) DO UPDATE SET
+            egress_bytes = node_earnings.egress_bytes + excluded.egress_bytes,
+            earned_inr = node_earnings.earned_inr + excluded.earned_inr,
+            withheld_inr = node_earnings.withheld_inr + excluded.withheld_inr,
+            updated_at = NOW()
+        "#,
+    )
+    .bind(&redemption.peer_id)
+    .bind(credited_bytes as i64)
+    .bind(earned)
+    .bind(held)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Some((credited_bytes, credited, withheld)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{drop_db, scratch_db, state, NODE_SECRET};
+    use axum::http::HeaderMap;
+    use libp2p::identity::Keypair;
+    use neuro_protocol::{rotation_payload, KeyRotation};
+
+    const OBJECT_CID: &str = "bafy-voucher-object";
+
+    async fn redeem(state: &Arc<AppState>, keypair: &Keypair, token: &str) -> StatusCode {
+        let redemption = Redemption::sign(keypair, token, 1024, now_secs() * 1000).expect("sign");
+        redeem_voucher(State(Arc::clone(state)), Json(redemption)).await.into_response().status()
+    }
+
+    fn rotation(old: &Keypair, new: &Keypair) -> KeyRotation {
+        let (old_peer_id, new_peer_id) = (old.public().to_peer_id().to_string(), new.public().to_peer_id().to_string());
+        let timestamp_ms = now_secs() * 1000;
+        let payload = rotation_payload(&old_peer_id, &new_peer_id, timestamp_ms);
+        KeyRotation {
+            old_public_key: hex::encode(old.public().encode_protobuf()),
+            old_signature: hex::encode(old.sign(&payload).expect("sign")),
+            new_public_key: hex::encode(new.public().encode_protobuf()),
+            new_signature: hex::encode(new.sign(&payload).expect("sign")),
+            old_peer_id,
+            new_peer_id,
+            timestamp_ms,
+        }
+    }
+
+    #[tokio::test]
+    async fn rotated_node_cannot_redeem_a_voucher_twice() {
+        let Some((db, name)) = scratch_db().await else {
+            eprintln!("DATABASE_URL not set; skipping");
+            return;
+        };
+        let (old, new) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+        let old_peer_id = old.public().to_peer_id().to_string();
+        sqlx::query("INSERT INTO nodes (peer_id, wallet_address) VALUES ($1, '0xwallet')")
+            .bind(&old_peer_id)
+            .execute(&db)
+            .await
+            .expect("node");
+        sqlx::query(
+            "INSERT INTO objects (bucket, key, etag, cid, shards, recovery_threshold, size) VALUES ('b', 'k', 'e', $1, 2, 1, 1048576)",
+        )
+        .bind(OBJECT_CID)
+        .execute(&db)
+        .await
+        .expect("object");
+        sqlx::query("INSERT INTO object_shards (object_cid, shard_cid, shard_index, peer_id) VALUES ($1, 's0', 0, $2)")
+            .bind(OBJECT_CID)
+            .bind(&old_peer_id)
+            .execute(&db)
+            .await
+            .expect("shard");
+        let state = state(db.clone());
+
+        // Expired a minute ago, so it is inside its redemption window.
+        let token = Voucher::new("owner", OBJECT_CID, 10 * 1024 * 1024, now_secs() - 60).mint(state.jwt_secret.as_bytes());
+        assert_eq!(redeem(&state, &old, &token).await, StatusCode::OK);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-node-secret", NODE_SECRET.parse().expect("header"));
+        let rotated = crate::handlers::nodes::rotate_node_key(State(Arc::clone(&state)), headers, Json(rotation(&old, &new)))
+            .await
+            .expect("rotation");
+        assert_eq!(rotated["status"], "rotated");
+
+        assert_eq!(redeem(&state, &new, &token).await, StatusCode::CONFLICT);
+        drop(state);
+        drop_db(db, &name).await;
+    }
+}
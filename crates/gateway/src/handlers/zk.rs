@@ -41,15 +41,26 @@ pub async fn zk_store(
     if let Err(err) = crate::handlers::s3::validate_csrf(&headers) {
         return err.into_response();
     }
-    let user_email = match crate::handlers::s3::validate_s3_auth(&headers, &state) {
-        Ok(email) => email,
+    let principal = match crate::handlers::s3::validate_bucket_principal(&headers, &state) {
+        Ok(principal) => principal,
         Err(err) => return err.into_response(),
     };
-    if let Err(err) = crate::handlers::s3::authorize_bucket(&state, &bucket, &user_email).await {
-        return err.into_response();
-    }
+    let scope = match crate::handlers::s3::authorize_bucket(
+        &state,
+        &bucket,
+        &principal,
+        crate::handlers::policy::BucketAccess::Write,
+        key.trim_start_matches('/'),
+    )
+    .await
+    {
+        Ok(scope) => scope,
+        Err(err) => return err.into_response(),
+    };
+    let bucket = scope.name.clone();
 
     let key = key.trim_start_matches('/').to_string();
+    crate::handlers::s3::record_request_fields(&bucket, Some(&key));
     let size = payload.total_bytes as i64;
     let etag = format!("\"zk-{}\"", payload.manifest_root);
     let cid = payload.manifest_root.clone();
@@ -105,21 +116,28 @@ pub async fn zk_store(
         ));
     }
 
-    let encrypted_key = match state.metadata_protector.encrypt(&key) {
+    let encrypted_key = match scope.protector.encrypt(&key) {
         Ok(k) => k,
         Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Key encryption failed").into_response(),
     };
+    let key_tokens = crate::key_index::key_tokens(&scope.protector, &bucket, &key);
+    let key_lookup = crate::key_index::lookup_token(&scope.protector, &bucket, &key);
 
-    let res = sqlx::query(
+    let res = sqlx::query_scalar::<_, i64>(
         r#"
-        INSERT INTO objects (bucket, key, etag, cid, shards, recovery_threshold, size, metadata_json)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-        ON CONFLICT (bucket, key) DO UPDATE SET
+        INSERT INTO objects (bucket, key, etag, cid, shards, recovery_threshold, size, metadata_json, key_tokens, key_lookup, bucket_ref, version)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, nextval('object_versions'))
+        ON CONFLICT (bucket, key_lookup) DO UPDATE SET
+            key = excluded.key,
             etag = excluded.etag,
             cid = excluded.cid,
             size = excluded.size,
             shards = excluded.shards,
-            metadata_json = excluded.metadata_json
+            metadata_json = excluded.metadata_json,
+            key_tokens = excluded.key_tokens,
+            key_lookup = excluded.key_lookup,
+            version = excluded.version
+        RETURNING version
         "#
     )
     .bind(&bucket)
@@ -130,25 +148,44 @@ pub async fn zk_store(
     .bind(recovery_threshold)
     .bind(size)
     .bind(serde_json::json!({ "zk_enabled": true, "chunk_count": payload.chunk_count }))
-    .execute(&state.db)
+    .bind(&key_tokens)
+    .bind(&key_lookup)
+    .bind(&scope.masked)
+    .fetch_one(&state.db)
     .await;
 
     match res {
-        Ok(_) => {
+        Ok(version) => {
+            state.listing_cache.invalidate(&bucket);
+            crate::replication::publish(&state, crate::replication::MetadataOp::UpsertObject {
+                bucket: bucket.clone(),
+                key: encrypted_key.clone(),
+                etag: etag.clone(),
+                cid: cid.clone(),
+                shards: shards_count,
+                recovery_threshold,
+                size,
+                metadata_json: Some(serde_json::json!({ "zk_enabled": true, "chunk_count": payload.chunk_count })),
+                key_tokens: Some(key_tokens),
+                key_lookup: Some(key_lookup),
+                version,
+            })
+            .await;
             for (shard_index, shard_cid, peer_id, country_code, receipt_timestamp_ms, receipt_signature_valid) in shard_placements {
                 let _ = sqlx::query(
                     r#"
                     INSERT INTO object_shards (
                         object_cid, shard_cid, shard_index, peer_id, country_code,
-                        receipt_timestamp_ms, receipt_signature_valid, last_verified_at
-                    ) VALUES ($1, $2, $3, $4, $5, $6, $7, NOW())
+                        receipt_timestamp_ms, receipt_signature_valid, last_verified_at, object_version
+                    ) VALUES ($1, $2, $3, $4, $5, $6, $7, NOW(), $8)
                     ON CONFLICT (object_cid, shard_index) DO UPDATE SET
                         shard_cid = excluded.shard_cid,
                         peer_id = excluded.peer_id,
                         country_code = excluded.country_code,
                         receipt_timestamp_ms = excluded.receipt_timestamp_ms,
                         receipt_signature_valid = excluded.receipt_signature_valid,
-                        last_verified_at = NOW()
+                        last_verified_at = NOW(),
+                        object_version = excluded.object_version
                     "#
                 )
                 .bind(&cid)
@@ -158,6 +195,7 @@ pub async fn zk_store(
                 .bind(&country_code)
                 .bind(receipt_timestamp_ms)
                 .bind(receipt_signature_valid)
+                .bind(version)
                 .execute(&state.db)
                 .await;
             }
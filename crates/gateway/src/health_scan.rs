@@ -0,0 +1,214 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use neuro_protocol::sentinel::excludes_peer;
+use tokio::time;
+use tracing::{error, info, warn};
+
+use crate::handlers::verify;
+use crate::models::Object;
+use crate::AppState;
+
+const DEFAULT_OBJECTS_PER_MINUTE: u64 = 30;
+const SCAN_PAGE_SIZE: i64 = 200;
+/// Reputation assumed for holders the sentinel has not scored yet.
+const UNKNOWN_REPUTATION: f64 = 50.0;
+/// Regions an object's shards must span for full diversity credit.
+const TARGET_REGIONS: usize = 3;
+/// Weights of replica margin, holder reputation and region diversity in the
+/// score; they sum to 1.
+const MARGIN_WEIGHT: f64 = 0.5;
+const REPUTATION_WEIGHT: f64 = 0.3;
+const DIVERSITY_WEIGHT: f64 = 0.2;
+
+#[derive(sqlx::FromRow)]
+struct Placement {
+    country_code: String,
+    reputation: Option<f64>,
+    action: Option<String>,
+}
+
+/// One object's durability, 0 (unrecoverable) to 100.
+struct Durability {
+    score: f64,
+    /// Placed shards whose holder the sentinel has not excluded.
+    healthy_shards: usize,
+    mean_reputation: f64,
+    regions: usize,
+}
+
+/// Scores an object from its shard placements and whether its last decode
+/// replay succeeded. An object that cannot be rebuilt scores 0; otherwise
+/// the score weighs how many shards it can lose before that happens, how
+/// trustworthy the holders are and how many regions they span.
+fn durability(placements: &[Placement], shards: usize, threshold: usize, decodable: bool) -> Durability {
+    let healthy: Vec<&Placement> = placements
+        .iter()
+        .filter(|p| !p.action.as_deref().is_some_and(excludes_peer))
+        .collect();
+    let mean_reputation = if healthy.is_empty() {
+        0.0
+    } else {
+        healthy
+            .iter()
+            .map(|p| p.reputation.unwrap_or(UNKNOWN_REPUTATION).clamp(0.0, 100.0))
+            .sum::<f64>()
+            / healthy.len() as f64
+    };
+    let regions = healthy
+        .iter()
+        .map(|p| p.country_code.as_str())
+        .filter(|c| *c != "XX")
+        .collect::<HashSet<_>>()
+        .len();
+
+    let score = if !decodable || healthy.len() < threshold {
+        0.0
+    } else {
+        let spare = shards.saturating_sub(threshold).max(1) as f64;
+        let margin = ((healthy.len() - threshold) as f64 / spare).min(1.0);
+        let diversity = (regions as f64 / TARGET_REGIONS as f64).min(1.0);
+        100.0 * (MARGIN_WEIGHT * margin + REPUTATION_WEIGHT * mean_reputation / 100.0 + DIVERSITY_WEIGHT * diversity)
+    };
+    Durability {
+        score: (score * 100.0).round() / 100.0,
+        healthy_shards: healthy.len(),
+        mean_reputation,
+        regions,
+    }
+}
+
+/// Walks every object at `HEALTH_SCAN_OBJECTS_PER_MIN` (0 turns the scan
+/// off), replays its decode through `/api/verify`'s check and stores a
+/// durability score in `object_durability` for `/api/health/:bucket`. When
+/// a pass ends the next one starts from the beginning. Runs on the leader
+/// only.
+pub struct HealthScanDaemon {
+    state: Arc<AppState>,
+    objects_per_minute: u64,
+}
+
+impl HealthScanDaemon {
+    pub fn new(state: Arc<AppState>) -> Self {
+        let objects_per_minute = std::env::var("HEALTH_SCAN_OBJECTS_PER_MIN")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_OBJECTS_PER_MINUTE);
+        Self { state, objects_per_minute }
+    }
+
+    pub async fn start(&self) {
+        if self.objects_per_minute == 0 {
+            info!("Health scan disabled; objects are verified only through /api/verify.");
+            return;
+        }
+        info!("Health scan daemon initialized. Scanning {} objects per minute.", self.objects_per_minute);
+
+        let mut interval = time::interval(Duration::from_millis(60_000 / self.objects_per_minute.min(60_000)));
+        interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+        loop {
+            match self.scan_pass(&mut interval).await {
+                Ok(scanned) => info!("Health scan pass finished: {} objects scored", scanned),
+                Err(e) => {
+                    error!("Health scan pass failed: {}", e);
+                    interval.tick().await;
+                }
+            }
+        }
+    }
+
+    /// Scores every object once, one per tick, in CID order.
+    async fn scan_pass(&self, interval: &mut time::Interval) -> Result<usize, sqlx::Error> {
+        let mut scanned = 0;
+        let mut after_cid = String::new();
+        let mut after_key = String::new();
+        loop {
+            let page = sqlx::query_as::<_, Object>(
+                "SELECT * FROM objects WHERE (cid, key) > ($1, $2) ORDER BY cid, key LIMIT $3",
+            )
+            .bind(&after_cid)
+            .bind(&after_key)
+            .bind(SCAN_PAGE_SIZE)
+            .fetch_all(&self.state.db)
+            .await?;
+            let Some(last) = page.last() else {
+                return Ok(scanned);
+            };
+            after_cid = last.cid.clone();
+            after_key = last.key.clone();
+            let full_page = page.len() as i64 == SCAN_PAGE_SIZE;
+
+            for obj in page {
+                interval.tick().await;
+                match self.scan_object(&obj).await {
+                    Ok(()) => scanned += 1,
+                    Err(e) => warn!("Health scan of object {} in bucket {} failed: {}", obj.cid, obj.bucket, e),
+                }
+            }
+
+            if !full_page {
+                return Ok(scanned);
+            }
+        }
+    }
+
+    async fn scan_object(&self, obj: &Object) -> Result<(), sqlx::Error> {
+        let key = self
+            .state
+            .tenant_keys
+            .for_bucket(&obj.bucket)
+            .decrypt(&obj.key)
+            .unwrap_or_else(|_| obj.key.clone());
+        let verdict = verify::verify(&self.state, &obj.bucket, &key, obj).await?;
+
+        let placements = sqlx::query_as::<_, Placement>(
+            r#"
+            SELECT s.country_code, r.reputation, r.action
+            FROM object_shards s
+            LEFT JOIN node_reputation r ON r.peer_id = s.peer_id
+            WHERE s.object_cid = $1
+            "#,
+        )
+        .bind(&obj.cid)
+        .fetch_all(&self.state.db)
+        .await?;
+        let durability = durability(
+            &placements,
+            obj.shards.max(0) as usize,
+            obj.recovery_threshold.max(0) as usize,
+            verdict.healthy,
+        );
+
+        sqlx::query(
+            r#"
+            INSERT INTO object_durability (
+                object_cid, bucket, score, healthy_shards, shards_total, recovery_threshold,
+                mean_reputation, regions, decodable, scanned_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, NOW())
+            ON CONFLICT (object_cid) DO UPDATE SET
+                bucket = excluded.bucket,
+                score = excluded.score,
+                healthy_shards = excluded.healthy_shards,
+                shards_total = excluded.shards_total,
+                recovery_threshold = excluded.recovery_threshold,
+                mean_reputation = excluded.mean_reputation,
+                regions = excluded.regions,
+                decodable = excluded.decodable,
+                scanned_at = NOW()
+            "#,
+        )
+        .bind(&obj.cid)
+        .bind(&obj.bucket)
+        .bind(durability.score)
+        .bind(durability.healthy_shards as i32)
+        .bind(obj.shards)
+        .bind(obj.recovery_threshold)
+        .bind(durability.mean_reputation)
+        .bind(durability.regions as i32)
+        .bind(verdict.healthy)
+        .execute(&self.state.db)
+        .await?;
+        Ok(())
+    }
+}
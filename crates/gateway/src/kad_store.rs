@@ -0,0 +1,239 @@
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use libp2p::kad::store::{self, MemoryStore, MemoryStoreConfig, RecordStore};
+use libp2p::kad::{ProviderRecord, Record, RecordKey};
+use libp2p::{identity, Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// One DHT record per placed shard, plus whatever other peers publish here.
+const MAX_RECORDS: usize = 250_000;
+
+/// Kademlia record store that keeps everything in a [`MemoryStore`] and
+/// writes it through to sled, so DHT records and provider records survive a
+/// gateway restart. Expiry times are stored as wall-clock milliseconds and
+/// converted back on load; anything already expired is dropped.
+pub struct PersistentStore {
+    inner: MemoryStore,
+    records: sled::Tree,
+    providers: sled::Tree,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredRecord {
+    value: Vec<u8>,
+    publisher: Option<Vec<u8>>,
+    expires_ms: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredProvider {
+    key: Vec<u8>,
+    provider: Vec<u8>,
+    addresses: Vec<Vec<u8>>,
+    expires_ms: Option<u64>,
+}
+
+impl PersistentStore {
+    pub fn open(db: &sled::Db, local_id: PeerId) -> Result<Self, sled::Error> {
+        let config = MemoryStoreConfig {
+            max_records: MAX_RECORDS,
+            ..Default::default()
+        };
+        let mut store = Self {
+            inner: MemoryStore::with_config(local_id, config),
+            records: db.open_tree("kad_records")?,
+            providers: db.open_tree("kad_providers")?,
+        };
+        store.load()?;
+        Ok(store)
+    }
+
+    fn load(&mut self) -> Result<(), sled::Error> {
+        let (mut records, mut providers, mut expired) = (0usize, 0usize, Vec::new());
+
+        for entry in self.records.iter() {
+            let (key, value) = entry?;
+            let Ok(stored) = bincode::deserialize::<StoredRecord>(&value) else {
+                expired.push((self.records.clone(), key));
+                continue;
+            };
+            let Some(expires) = to_instant(stored.expires_ms) else {
+                expired.push((self.records.clone(), key));
+                continue;
+            };
+            let record = Record {
+                key: RecordKey::new(&key),
+                value: stored.value,
+                publisher: stored.publisher.and_then(|p| PeerId::from_bytes(&p).ok()),
+                expires,
+            };
+            if self.inner.put(record).is_ok() {
+                records += 1;
+            }
+        }
+
+        for entry in self.providers.iter() {
+            let (key, value) = entry?;
+            let Some(record) = bincode::deserialize::<StoredProvider>(&value)
+                .ok()
+                .and_then(|stored| {
+                    Some(ProviderRecord {
+                        key: RecordKey::new(&stored.key),
+                        provider: PeerId::from_bytes(&stored.provider).ok()?,
+                        expires: to_instant(stored.expires_ms)?,
+                        addresses: stored
+                            .addresses
+                            .iter()
+                            .filter_map(|a| Multiaddr::try_from(a.clone()).ok())
+                            .collect(),
+                    })
+                })
+            else {
+                expired.push((self.providers.clone(), key));
+                continue;
+            };
+            if self.inner.add_provider(record).is_ok() {
+                providers += 1;
+            }
+        }
+
+        for (tree, key) in expired {
+            tree.remove(key)?;
+        }
+        info!("Restored {} DHT records and {} provider records from disk", records, providers);
+        Ok(())
+    }
+
+    /// Whether `provider` is already recorded as holding `key`.
+    pub fn has_provider(&self, key: &RecordKey, provider: &PeerId) -> bool {
+        self.providers
+            .contains_key(provider_key(key, provider))
+            .unwrap_or(false)
+    }
+}
+
+/// `Some(None)` for no expiry, `None` when the time has already passed.
+fn to_instant(expires_ms: Option<u64>) -> Option<Option<Instant>> {
+    let Some(ms) = expires_ms else {
+        return Some(None);
+    };
+    let remaining = (UNIX_EPOCH + Duration::from_millis(ms)).duration_since(SystemTime::now()).ok()?;
+    Some(Some(Instant::now() + remaining))
+}
+
+fn to_unix_ms(expires: Option<Instant>) -> Option<u64> {
+    expires.map(|at| {
+        let wall = SystemTime::now() + at.saturating_duration_since(Instant::now());
+        wall.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default()
+    })
+}
+
+fn provider_key(key: &RecordKey, provider: &PeerId) -> Vec<u8> {
+    let key = key.to_vec();
+    let mut out = Vec::with_capacity(4 + key.len() + 38);
+    out.extend_from_slice(&(key.len() as u32).to_be_bytes());
+    out.extend_from_slice(&key);
+    out.extend_from_slice(&provider.to_bytes());
+    out
+}
+
+impl RecordStore for PersistentStore {
+    type RecordsIter<'a> = <MemoryStore as RecordStore>::RecordsIter<'a>;
+    type ProvidedIter<'a> = <MemoryStore as RecordStore>::ProvidedIter<'a>;
+
+    fn get(&self, k: &RecordKey) -> Option<Cow<'_, Record>> {
+        self.inner.get(k)
+    }
+
+    fn put(&mut self, r: Record) -> store::Result<()> {
+        let stored = StoredRecord {
+            value: r.value.clone(),
+            publisher: r.publisher.map(|p| p.to_bytes()),
+            expires_ms: to_unix_ms(r.expires),
+        };
+        let key = r.key.to_vec();
+        self.inner.put(r)?;
+        match bincode::serialize(&stored) {
+            Ok(bytes) => {
+                if let Err(e) = self.records.insert(key, bytes) {
+                    warn!("Failed to persist DHT record: {}", e);
+                }
+            }
+            Err(e) => warn!("Failed to encode DHT record: {}", e),
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, k: &RecordKey) {
+        self.inner.remove(k);
+        if let Err(e) = self.records.remove(k.to_vec()) {
+            warn!("Failed to drop persisted DHT record: {}", e);
+        }
+    }
+
+    fn records(&self) -> Self::RecordsIter<'_> {
+        self.inner.records()
+    }
+
+    fn add_provider(&mut self, record: ProviderRecord) -> store::Result<()> {
+        let stored = StoredProvider {
+            key: record.key.to_vec(),
+            provider: record.provider.to_bytes(),
+            addresses: record.addresses.iter().map(|a| a.to_vec()).collect(),
+            expires_ms: to_unix_ms(record.expires),
+        };
+        let key = provider_key(&record.key, &record.provider);
+        self.inner.add_provider(record)?;
+        match bincode::serialize(&stored) {
+            Ok(bytes) => {
+                if let Err(e) = self.providers.insert(key, bytes) {
+                    warn!("Failed to persist provider record: {}", e);
+                }
+            }
+            Err(e) => warn!("Failed to encode provider record: {}", e),
+        }
+        Ok(())
+    }
+
+    fn providers(&self, key: &RecordKey) -> Vec<ProviderRecord> {
+        self.inner.providers(key)
+    }
+
+    fn provided(&self) -> Self::ProvidedIter<'_> {
+        self.inner.provided()
+    }
+
+    fn remove_provider(&mut self, k: &RecordKey, p: &PeerId) {
+        self.inner.remove_provider(k, p);
+        if let Err(e) = self.providers.remove(provider_key(k, p)) {
+            warn!("Failed to drop persisted provider record: {}", e);
+        }
+    }
+}
+
+/// Loads the gateway's libp2p identity from `dir`, creating it on first
+/// start. A stable PeerId keeps the records this gateway published valid
+/// across restarts.
+pub fn load_or_create_identity(dir: &Path) -> anyhow::Result<identity::Keypair> {
+    let key_path = identity_key_path(dir);
+
+    if key_path.exists() {
+        let bytes = fs::read(&key_path)?;
+        let keypair = identity::Keypair::from_protobuf_encoding(&bytes)?;
+        return Ok(keypair);
+    }
+
+    fs::create_dir_all(dir)?;
+    let keypair = identity::Keypair::generate_ed25519();
+    let encoded = keypair.to_protobuf_encoding()?;
+    fs::write(&key_path, encoded)?;
+    Ok(keypair)
+}
+
+fn identity_key_path(dir: &Path) -> PathBuf {
+    dir.join("gateway_identity.key")
+}
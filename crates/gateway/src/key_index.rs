@@ -0,0 +1,326 @@
+//! Blind search index over object keys. Keys are stored encrypted under a
+//! random nonce, so the database can neither compare nor prefix-match them.
+//! Instead every object carries one keyed token per `/`-terminated prefix of
+//! its key (`a/b/c.txt` indexes `a/` and `a/b/`). A listing fetches only the
+//! rows under the deepest directory of the requested prefix, decrypts those
+//! and applies the exact prefix and delimiter in memory. A token of the whole
+//! key, [`lookup_token`], finds the row of one key.
+//!
+//! The decrypted, sorted keys of a directory are cached in [`ListingCache`],
+//! so paging through a large bucket decrypts it once; only the rows on the
+//! returned page are read in full.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use moka::future::Cache;
+
+use crate::crypto::MetadataProtector;
+use crate::models::Object;
+use crate::AppState;
+
+/// Segment separator the tokens are cut at.
+pub const SEGMENT_DELIMITER: char = '/';
+const BACKFILL_BATCH: i64 = 500;
+
+fn token(protector: &MetadataProtector, bucket: &str, dir: &str) -> String {
+    protector.blind_index(&format!("key_prefix:{}:{}", bucket, dir))
+}
+
+/// Tokens stored with `key`, one per directory it sits under.
+pub fn key_tokens(protector: &MetadataProtector, bucket: &str, key: &str) -> Vec<String> {
+    key.match_indices(SEGMENT_DELIMITER)
+        .map(|(i, _)| token(protector, bucket, &key[..=i]))
+        .collect()
+}
+
+/// Token of `key` itself, stored as `objects.key_lookup`: the same key
+/// always gives the same token, so requests naming a key find its row by it.
+pub fn lookup_token(protector: &MetadataProtector, bucket: &str, key: &str) -> String {
+    protector.blind_index(&format!("key:{}:{}", bucket, key))
+}
+
+/// Token of the deepest directory in `prefix`, or `None` when the prefix has
+/// no `/` and the whole bucket has to be scanned.
+pub fn prefix_token(protector: &MetadataProtector, bucket: &str, prefix: &str) -> Option<String> {
+    prefix
+        .rfind(SEGMENT_DELIMITER)
+        .map(|i| token(protector, bucket, &prefix[..=i]))
+}
+
+/// How long a cached directory is served before the database is asked
+/// again. Writes through this gateway invalidate at once; this bounds how
+/// stale a listing gets after writes it does not see (a replication
+/// follower's leader, a restore on another gateway).
+const LISTING_CACHE_TTL: Duration = Duration::from_secs(30);
+/// Keys held across every cached directory.
+const LISTING_CACHE_KEYS: u64 = 500_000;
+
+/// An object key as listed: decrypted, and as stored for reading its row.
+#[derive(Debug, Clone)]
+pub struct ListedKey {
+    pub key: String,
+    pub stored_key: String,
+}
+
+type CachedKeys = Arc<Vec<ListedKey>>;
+
+/// Sorted candidate keys per `(bucket, directory token)`. Each bucket has a
+/// generation that writes bump, and cache entries are keyed by it, so a
+/// write drops every cached directory of its bucket at once and a listing
+/// read before the write cannot be cached after it.
+pub struct ListingCache {
+    entries: Cache<(String, u64, Option<String>), CachedKeys>,
+    generations: Mutex<HashMap<String, u64>>,
+}
+
+impl Default for ListingCache {
+    fn default() -> Self {
+        Self {
+            entries: Cache::builder()
+                .max_capacity(LISTING_CACHE_KEYS)
+                .weigher(|_, keys: &CachedKeys| keys.len().try_into().unwrap_or(u32::MAX))
+                .time_to_live(LISTING_CACHE_TTL)
+                .build(),
+            generations: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl ListingCache {
+    fn generation(&self, bucket: &str) -> u64 {
+        let generations = self.generations.lock().unwrap_or_else(|e| e.into_inner());
+        generations.get(bucket).copied().unwrap_or(0)
+    }
+
+    /// Called after every write that adds or removes a key in `bucket`.
+    pub fn invalidate(&self, bucket: &str) {
+        let mut generations = self.generations.lock().unwrap_or_else(|e| e.into_inner());
+        *generations.entry(bucket.to_string()).or_insert(0) += 1;
+    }
+
+    /// After a restore replaced the whole table.
+    pub fn invalidate_all(&self) {
+        let mut generations = self.generations.lock().unwrap_or_else(|e| e.into_inner());
+        generations.values_mut().for_each(|g| *g += 1);
+        self.entries.invalidate_all();
+    }
+}
+
+/// Candidate keys for a listing of `prefix`, sorted by plain key. Rows not
+/// yet indexed are included too; [`build_listing`] filters on the decrypted
+/// key either way. Only keys are read, so the scan stays on the indexes.
+pub async fn candidates(state: &AppState, bucket: &str, prefix: &str) -> Result<CachedKeys, sqlx::Error> {
+    let protector = state.tenant_keys.for_bucket(bucket);
+    let token = prefix_token(&protector, bucket, prefix);
+    let generation = state.listing_cache.generation(bucket);
+    let cache_key = (bucket.to_string(), generation, token.clone());
+    if let Some(keys) = state.listing_cache.entries.get(&cache_key).await {
+        return Ok(keys);
+    }
+
+    // Two branches rather than an OR, so each can use its own index.
+    let stored_keys = match &token {
+        Some(token) => sqlx::query_scalar::<_, String>(
+            "SELECT key FROM objects WHERE bucket = $1 AND key_tokens @> ARRAY[$2::TEXT]
+             UNION ALL
+             SELECT key FROM objects WHERE bucket = $1 AND key_tokens IS NULL",
+        )
+        .bind(bucket)
+        .bind(token),
+        None => sqlx::query_scalar::<_, String>("SELECT key FROM objects WHERE bucket = $1").bind(bucket),
+    }
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut keys: Vec<ListedKey> = stored_keys
+        .into_iter()
+        .map(|stored_key| ListedKey {
+            key: protector.decrypt(&stored_key).unwrap_or_else(|_| stored_key.clone()),
+            stored_key,
+        })
+        .collect();
+    keys.sort_by(|a, b| a.key.cmp(&b.key));
+    let keys = Arc::new(keys);
+    if state.listing_cache.generation(bucket) == generation {
+        state.listing_cache.entries.insert(cache_key, Arc::clone(&keys)).await;
+    }
+    Ok(keys)
+}
+
+/// The rows of `listed`, in the same order. Keys deleted since they were
+/// listed are left out.
+pub async fn page_rows(state: &AppState, bucket: &str, listed: &[ListedKey]) -> Result<Vec<(String, Object)>, sqlx::Error> {
+    if listed.is_empty() {
+        return Ok(Vec::new());
+    }
+    let stored_keys: Vec<&str> = listed.iter().map(|k| k.stored_key.as_str()).collect();
+    let mut rows: HashMap<String, Object> = sqlx::query_as::<_, Object>(
+        "SELECT * FROM objects WHERE bucket = $1 AND key = ANY($2)",
+    )
+    .bind(bucket)
+    .bind(&stored_keys)
+    .fetch_all(&state.db)
+    .await?
+    .into_iter()
+    .map(|o| (o.key.clone(), o))
+    .collect();
+    Ok(listed
+        .iter()
+        .filter_map(|k| rows.remove(&k.stored_key).map(|o| (k.key.clone(), o)))
+        .collect())
+}
+
+pub struct Listing {
+    /// Sorted by plain key.
+    pub contents: Vec<ListedKey>,
+    pub common_prefixes: Vec<String>,
+    pub is_truncated: bool,
+    /// Where the next page starts when truncated: the last key or common
+    /// prefix on this one.
+    pub next_marker: Option<String>,
+}
+
+/// S3 ListObjects semantics over sorted decrypted keys: keys under `prefix`
+/// and after `start_after`, with everything past the next `delimiter`
+/// rolled up into a common prefix, and at most `max_keys` entries of either
+/// kind.
+pub fn build_listing(
+    keys: &[ListedKey],
+    prefix: &str,
+    delimiter: Option<&str>,
+    start_after: Option<&str>,
+    max_keys: usize,
+) -> Listing {
+    let mut listing = Listing {
+        contents: Vec::new(),
+        common_prefixes: Vec::new(),
+        is_truncated: false,
+        next_marker: None,
+    };
+    // Keys are sorted, so the page starts with a binary search rather than
+    // a walk over everything before it.
+    let lower = start_after.filter(|s| *s > prefix).unwrap_or(prefix);
+    let first = keys.partition_point(|k| k.key.as_str() < lower);
+    for listed in &keys[first..] {
+        let key = listed.key.as_str();
+        if !key.starts_with(prefix) {
+            break;
+        }
+        if start_after.is_some_and(|after| key <= after) {
+            continue;
+        }
+        let rolled_up = delimiter.filter(|d| !d.is_empty()).and_then(|d| {
+            key[prefix.len()..]
+                .find(d)
+                .map(|i| key[..prefix.len() + i + d.len()].to_string())
+        });
+        if let Some(common) = &rolled_up {
+            // A marker inside a rolled-up prefix means that prefix was on
+            // an earlier page.
+            if listing.common_prefixes.last() == Some(common)
+                || start_after.is_some_and(|after| after.starts_with(common.as_str()))
+            {
+                continue;
+            }
+        }
+        if listing.contents.len() + listing.common_prefixes.len() >= max_keys {
+            listing.is_truncated = true;
+            break;
+        }
+        match rolled_up {
+            Some(common) => {
+                listing.next_marker = Some(common.clone());
+                listing.common_prefixes.push(common);
+            }
+            None => {
+                listing.next_marker = Some(listed.key.clone());
+                listing.contents.push(listed.clone());
+            }
+        }
+    }
+    if !listing.is_truncated {
+        listing.next_marker = None;
+    }
+    listing
+}
+
+/// Indexes rows written before the index existed, or replicated without
+/// tokens. Keys that do not decrypt are indexed as stored, matching how
+/// listings display them. Before the key token was unique, every write of a
+/// key added a row; newest versions go first, and an older row whose token
+/// is already taken is dropped, along with its shards unless the newer row
+/// still uses them.
+pub async fn backfill(state: Arc<AppState>) {
+    let mut indexed = 0usize;
+    let mut superseded = 0usize;
+    loop {
+        let rows = match sqlx::query_as::<_, Object>(
+            "SELECT * FROM objects WHERE key_tokens IS NULL OR key_lookup IS NULL
+             ORDER BY version DESC, created_at DESC NULLS LAST LIMIT $1",
+        )
+        .bind(BACKFILL_BATCH)
+        .fetch_all(&state.db)
+        .await
+        {
+            Ok(rows) => rows,
+            Err(e) => {
+                tracing::warn!("key index backfill failed: {}", e);
+                return;
+            }
+        };
+        if rows.is_empty() {
+            break;
+        }
+        for obj in rows {
+            let protector = state.tenant_keys.for_bucket(&obj.bucket);
+            let key = protector.decrypt(&obj.key).unwrap_or_else(|_| obj.key.clone());
+            let tokens = key_tokens(&protector, &obj.bucket, &key);
+            let lookup = lookup_token(&protector, &obj.bucket, &key);
+            let res = sqlx::query(
+                "UPDATE objects SET key_tokens = $1, key_lookup = $2 WHERE bucket = $3 AND key = $4
+                 AND NOT EXISTS (SELECT 1 FROM objects WHERE bucket = $3 AND key_lookup = $2 AND key <> $4)",
+            )
+            .bind(&tokens)
+            .bind(&lookup)
+            .bind(&obj.bucket)
+            .bind(&obj.key)
+            .execute(&state.db)
+            .await;
+            match res {
+                Ok(done) if done.rows_affected() > 0 => indexed += 1,
+                Ok(_) => {
+                    // Shards are the leader's to remove; a follower only
+                    // drops its copy of the row.
+                    let follower = state.replication.is_follower();
+                    let dropped = if follower {
+                        sqlx::query("DELETE FROM objects WHERE bucket = $1 AND key = $2")
+                            .bind(&obj.bucket)
+                            .bind(&obj.key)
+                            .execute(&state.db)
+                            .await
+                            .map(|_| ())
+                    } else {
+                        crate::handlers::s3::shred_object(&state, &obj).await
+                    };
+                    if let Err(e) = dropped {
+                        tracing::warn!("key index backfill failed: {}", e);
+                        return;
+                    }
+                    if !follower {
+                        crate::handlers::s3::discard_object_version(&state, &obj.cid, obj.shards, obj.version).await;
+                    }
+                    superseded += 1;
+                }
+                Err(e) => {
+                    tracing::warn!("key index backfill failed: {}", e);
+                    return;
+                }
+            }
+        }
+    }
+    if indexed > 0 || superseded > 0 {
+        tracing::info!("key index backfilled {} objects, dropped {} superseded copies", indexed, superseded);
+    }
+}
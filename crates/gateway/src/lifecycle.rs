@@ -0,0 +1,125 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tokio::time;
+use tracing::{error, info, warn};
+
+use crate::handlers::{s3::purge_object, tagging::read_tags};
+use crate::models::{LifecycleRule, Object};
+use crate::AppState;
+
+const LIFECYCLE_SWEEP_SECS: u64 = 60 * 60;
+const LIFECYCLE_PAGE_SIZE: i64 = 500;
+
+#[derive(sqlx::FromRow)]
+struct BucketRule {
+    bucket: String,
+    #[sqlx(flatten)]
+    rule: LifecycleRule,
+}
+
+/// Applies bucket lifecycle rules: objects older than a rule's expiration
+/// (and carrying its tag, when it has one) are purged exactly as a DELETE
+/// would. Runs on the leader only.
+pub struct LifecycleDaemon {
+    state: Arc<AppState>,
+}
+
+impl LifecycleDaemon {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn start(&self) {
+        info!("Lifecycle daemon initialized. Applying expiration rules every {} seconds.", LIFECYCLE_SWEEP_SECS);
+
+        let mut interval = time::interval(Duration::from_secs(LIFECYCLE_SWEEP_SECS));
+        loop {
+            interval.tick().await;
+            self.sweep().await;
+        }
+    }
+
+    async fn sweep(&self) {
+        let rules = sqlx::query_as::<_, BucketRule>(
+            r#"
+            SELECT bucket, rule_id, tag_key, tag_value, expiration_days, enabled
+            FROM bucket_lifecycle_rules
+            WHERE enabled
+            ORDER BY bucket, rule_id
+            "#
+        )
+        .fetch_all(&self.state.db)
+        .await;
+
+        match rules {
+            Ok(rules) => {
+                for BucketRule { bucket, rule } in rules {
+                    match self.apply_rule(&bucket, &rule).await {
+                        Ok(0) => {}
+                        Ok(expired) => info!("Lifecycle rule {} expired {} objects in bucket {}", rule.rule_id, expired, bucket),
+                        Err(e) => error!("Lifecycle rule {} on bucket {} failed: {}", rule.rule_id, bucket, e),
+                    }
+                }
+            }
+            Err(e) => error!("Lifecycle daemon failed to load rules: {}", e),
+        }
+    }
+
+    /// Walks the bucket's expired objects page by page and purges those the
+    /// rule's tag filter matches. Returns how many were purged.
+    async fn apply_rule(&self, bucket: &str, rule: &LifecycleRule) -> Result<usize, sqlx::Error> {
+        let mut expired = 0;
+        let mut after_created = DateTime::<Utc>::UNIX_EPOCH;
+        let mut after_key = String::new();
+        loop {
+            let page = sqlx::query_as::<_, Object>(
+                r#"
+                SELECT * FROM objects
+                WHERE bucket = $1
+                  AND created_at <= NOW() - make_interval(days => $2)
+                  AND (created_at, key) > ($3, $4)
+                ORDER BY created_at, key
+                LIMIT $5
+                "#
+            )
+            .bind(bucket)
+            .bind(rule.expiration_days)
+            .bind(after_created)
+            .bind(&after_key)
+            .bind(LIFECYCLE_PAGE_SIZE)
+            .fetch_all(&self.state.db)
+            .await?;
+
+            let Some(last) = page.last() else {
+                return Ok(expired);
+            };
+            after_created = last.created_at.unwrap_or(after_created);
+            after_key = last.key.clone();
+            let full_page = page.len() as i64 == LIFECYCLE_PAGE_SIZE;
+
+            for obj in page {
+                if !self.rule_matches(rule, &obj) {
+                    continue;
+                }
+                match purge_object(&self.state, &obj).await {
+                    Ok(_) => expired += 1,
+                    Err(e) => warn!("Lifecycle expiry of object {} in bucket {} failed: {}", obj.cid, bucket, e),
+                }
+            }
+
+            if !full_page {
+                return Ok(expired);
+            }
+        }
+    }
+
+    fn rule_matches(&self, rule: &LifecycleRule, obj: &Object) -> bool {
+        let Some(tag_key) = &rule.tag_key else {
+            return true;
+        };
+        let tags = read_tags(&self.state.tenant_keys.for_bucket(&obj.bucket), obj.metadata_json.as_ref());
+        tags.get(tag_key).map(String::as_str) == Some(rule.tag_value.as_deref().unwrap_or_default())
+    }
+}
@@ -0,0 +1,92 @@
+//! Log output for the gateway binary. Formatting and file rotation are
+//! shared with the node through `neuro-logging`; the gateway adds the OTLP
+//! exporter.
+
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::EnvFilter;
+
+pub use neuro_logging::LogOptions;
+
+/// Flushes buffered file output and pending OTLP spans when dropped; keep
+/// it alive for the life of the process.
+pub struct LogGuard {
+    _file: Option<WorkerGuard>,
+    #[cfg(feature = "otel")]
+    otel: bool,
+}
+
+impl Drop for LogGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if self.otel {
+            opentelemetry::global::shutdown_tracer_provider();
+        }
+    }
+}
+
+type BoxedLayer = Box<dyn tracing_subscriber::Layer<tracing_subscriber::Registry> + Send + Sync>;
+
+/// Installs the global subscriber. With the `otel` feature and
+/// `OTEL_EXPORTER_OTLP_ENDPOINT` set, spans are also exported over OTLP/gRPC.
+pub fn init(opts: &LogOptions) -> anyhow::Result<LogGuard> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("debug"));
+
+    let otel = otel_layer()?;
+    let (layer, guard) = neuro_logging::output_layer(opts)?;
+    #[cfg(feature = "otel")]
+    let otel_enabled = otel.is_some();
+    tracing_subscriber::registry()
+        .with(otel)
+        .with(layer)
+        .with(filter)
+        .try_init()?;
+    Ok(LogGuard {
+        _file: guard,
+        #[cfg(feature = "otel")]
+        otel: otel_enabled,
+    })
+}
+
+#[cfg(feature = "otel")]
+fn otel_layer() -> anyhow::Result<Option<BoxedLayer>> {
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::WithExportConfig;
+
+    let Some(endpoint) = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+        .ok()
+        .filter(|v| !v.is_empty())
+    else {
+        return Ok(None);
+    };
+    let service_name =
+        std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "neurostore-gateway".to_string());
+
+    let provider = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(opentelemetry_sdk::trace::Config::default().with_resource(
+            opentelemetry_sdk::Resource::new(vec![opentelemetry::KeyValue::new(
+                "service.name",
+                service_name,
+            )]),
+        ))
+        .install_batch(opentelemetry_sdk::runtime::Tokio)?;
+    let tracer = provider.tracer("neurostore-gateway");
+    opentelemetry::global::set_tracer_provider(provider);
+    Ok(Some(Box::new(
+        tracing_opentelemetry::layer().with_tracer(tracer),
+    )))
+}
+
+#[cfg(not(feature = "otel"))]
+fn otel_layer() -> anyhow::Result<Option<BoxedLayer>> {
+    if std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_some() {
+        eprintln!("OTEL_EXPORTER_OTLP_ENDPOINT is set but the gateway was built without the `otel` feature");
+    }
+    Ok(None)
+}
@@ -1,23 +1,22 @@
 use axum::{
-    extract::{Request, State},
-    http::{HeaderValue, Method},
-    middleware::{from_fn, Next},
+    extract::{DefaultBodyLimit, Request, State},
+    http::HeaderValue,
+    middleware::{from_fn, from_fn_with_state, Next},
     response::Response,
-    routing::{get, post},
+    routing::{get, post, put},
     Router,
     Json,
 };
-use tower_http::cors::{AllowOrigin, CorsLayer};
 use tower_http::services::ServeDir;
 use sqlx::postgres::PgPoolOptions;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tracing::{info, Level};
-use tracing_subscriber::FmtSubscriber;
-use tokio::sync::mpsc;
-use crate::p2p::SwarmRequest;
+use tower_http::trace::TraceLayer;
+use tracing::info;
+use crate::p2p::SwarmSender;
 
 use moka::future::Cache;
+use zeroize::Zeroizing;
 
 pub mod models;
 pub mod handlers;
@@ -28,32 +27,64 @@ pub mod proofs;
 pub mod repair;
 pub mod geofence;
 pub mod crypto;
+pub mod logging;
+pub mod replication;
+pub mod lifecycle;
+pub mod sentinel;
+pub mod slashing;
+pub mod deletions;
+pub mod retrieval;
+pub mod compression;
+pub mod kad_store;
+pub mod key_index;
+pub mod uploads;
+pub mod access_log;
+pub mod backup;
+pub mod config;
+pub mod server;
+pub mod health_scan;
+pub mod tenancy;
+#[cfg(test)]
+mod test_support;
 
 pub struct AppState {
     pub db: sqlx::PgPool,
-    pub p2p_tx: mpsc::Sender<SwarmRequest>,
+    pub p2p_tx: SwarmSender,
     // CDN Layer: Maps CID -> Raw Bytes
     pub edge_cache: Cache<String, axum::body::Bytes>,
     pub geo: geofence::GeoFenceManager,
-    pub metadata_protector: crypto::MetadataProtector,
-    pub jwt_secret: String,
-    pub proof_submit_token: String,
-    pub compliance_signing_key: String,
-    pub node_shared_secret: String,
-    pub cookie_secure: bool,
-    pub environment: String,
+    /// Metadata keys, one set per tenant.
+    pub tenant_keys: tenancy::TenantKeys,
+    pub jwt_secret: Zeroizing<String>,
+    pub proof_submit_token: Zeroizing<String>,
+    pub compliance_signing_key: Zeroizing<String>,
+    /// The gateway's libp2p identity, which also signs penalty notices.
+    pub identity: libp2p::identity::Keypair,
+    pub node_shared_secret: Zeroizing<String>,
+    pub config: config::GatewayConfig,
+    /// File `config` was read from, if any.
+    pub config_file: Option<String>,
+    pub replication: replication::ReplicationConfig,
+    pub fleet_policy: Arc<sentinel::FleetPolicy>,
+    pub sentinel: Option<sentinel::SentinelClient>,
+    pub compression: compression::CompressionConfig,
+    pub backup: backup::BackupConfig,
+    /// Runs the write path's erasure encodes off the async workers.
+    pub encode_pool: erasure::EncodePool,
+    /// Decrypted key lists behind S3 listings.
+    pub listing_cache: key_index::ListingCache,
+    /// Shard bytes served per bandwidth voucher through /api/shard.
+    pub vouchers: std::sync::Mutex<neuro_voucher::Ledger>,
+    /// Listener counters behind /api/admin/connections.
+    pub connections: Arc<server::ConnectionMetrics>,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenvy::dotenv().ok(); // Load .env if present
 
-    // Initialize tracing
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::DEBUG)
-        .finish();
-    tracing::subscriber::set_global_default(subscriber)
-        .expect("setting default subscriber failed");
+    // Initialize tracing; the guard flushes file output on exit.
+    let _log_guard = logging::init(&logging::LogOptions::from_env())?;
 
     // Connect to PostgreSQL
     let database_url = std::env::var("DATABASE_URL")
@@ -72,35 +103,66 @@ async fn main() -> anyhow::Result<()> {
     sqlx::migrate!("./migrations").run(&pool).await?;
 
     // Phase 10: Ignite the LibP2P Swarm Network
-    let (p2p_tx, p2p_rx) = mpsc::channel(100);
-    let mut swarm_node = p2p::P2pNode::new().await?;
+    let (p2p_tx, p2p_rx) = SwarmSender::channel(100);
+    let kad_dir = std::env::var("KAD_STORE_PATH").unwrap_or_else(|_| "data/gateway-kad".to_string());
+    let swarm_key = std::env::var("SWARM_KEY_PATH")
+        .ok()
+        .map(|path| p2p::load_swarm_key(std::path::Path::new(&path)))
+        .transpose()?;
+    if let Some(psk) = &swarm_key {
+        info!(fingerprint = %psk.fingerprint(), "Joining private swarm");
+    }
+    let placement: neuro_placement::Strategy = std::env::var("PLACEMENT_STRATEGY")
+        .ok()
+        .map(|s| s.parse())
+        .transpose()?
+        .unwrap_or_default();
+    info!(%placement, "Shard placement strategy");
+    let mut swarm_node = p2p::P2pNode::new(std::path::Path::new(&kad_dir), swarm_key, placement).await?;
+    let identity = swarm_node.identity();
     let geo_manager = geofence::GeoFenceManager::new();
     let geo_manager_clone = geofence::GeoFenceManager::new(); // For the p2p loop
+    let fleet_policy = Arc::new(sentinel::FleetPolicy::default());
     
     let db_for_p2p = pool.clone();
+    let fleet_policy_for_p2p = Arc::clone(&fleet_policy);
+    let p2p_port: u16 = std::env::var("P2P_PORT")
+        .ok()
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(9010);
     tokio::spawn(async move {
         info!("Igniting LibP2P Kademlia DHT Swarm...");
-        if let Err(e) = swarm_node.start(9010, p2p_rx, geo_manager_clone, db_for_p2p).await {
+        if let Err(e) = swarm_node.start(p2p_port, p2p_rx, geo_manager_clone, db_for_p2p, fleet_policy_for_p2p).await {
             tracing::error!("Fatal P2P Swarm crash: {}", e);
         }
     });
 
-    let metadata_secret = std::env::var("METADATA_SECRET")
-        .expect("METADATA_SECRET environment variable is required");
+    // Secrets are held in wipe-on-drop buffers for the lifetime of the process.
+    let metadata_secret = Zeroizing::new(
+        std::env::var("METADATA_SECRET").expect("METADATA_SECRET environment variable is required"),
+    );
     
-    let jwt_secret = std::env::var("JWT_SECRET")
-        .expect("JWT_SECRET environment variable is required");
-    let proof_submit_token = std::env::var("PROOF_SUBMIT_TOKEN")
-        .expect("PROOF_SUBMIT_TOKEN environment variable is required");
-    let compliance_signing_key = std::env::var("COMPLIANCE_SIGNING_KEY")
-        .expect("COMPLIANCE_SIGNING_KEY environment variable is required");
-    let node_shared_secret = std::env::var("NODE_SHARED_SECRET")
-        .expect("NODE_SHARED_SECRET environment variable is required");
-    let cookie_secure = std::env::var("COOKIE_SECURE")
-        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
-        .unwrap_or(false);
-    let environment = std::env::var("ENVIRONMENT").unwrap_or_else(|_| "development".to_string());
-    let metadata_protector = crypto::MetadataProtector::new(&metadata_secret);
+    let jwt_secret = Zeroizing::new(
+        std::env::var("JWT_SECRET").expect("JWT_SECRET environment variable is required"),
+    );
+    let proof_submit_token = Zeroizing::new(
+        std::env::var("PROOF_SUBMIT_TOKEN").expect("PROOF_SUBMIT_TOKEN environment variable is required"),
+    );
+    let compliance_signing_key = Zeroizing::new(
+        std::env::var("COMPLIANCE_SIGNING_KEY")
+            .expect("COMPLIANCE_SIGNING_KEY environment variable is required"),
+    );
+    let node_shared_secret = Zeroizing::new(
+        std::env::var("NODE_SHARED_SECRET").expect("NODE_SHARED_SECRET environment variable is required"),
+    );
+    let (config, config_file) = config::GatewayConfig::load()?;
+    let tenant_keys = tenancy::TenantKeys::new(&metadata_secret);
+    let replication = replication::ReplicationConfig::from_env();
+    let sentinel = sentinel::SentinelClient::from_env()?;
+    let compression = compression::CompressionConfig::from_env();
+    let compression_layer = compression.layer();
+    let backup = backup::BackupConfig::from_env();
+    let encode_pool = erasure::EncodePool::new(erasure::EncodePoolConfig::from_env());
 
     let edge_cache: Cache<String, axum::body::Bytes> = Cache::new(10_000);
 
@@ -109,48 +171,109 @@ async fn main() -> anyhow::Result<()> {
         p2p_tx, 
         edge_cache,
         geo: geo_manager,
-        metadata_protector,
+        tenant_keys,
         jwt_secret,
         proof_submit_token,
         compliance_signing_key,
+        identity,
         node_shared_secret,
-        cookie_secure,
-        environment,
+        config,
+        config_file,
+        replication,
+        fleet_policy,
+        sentinel,
+        compression,
+        backup,
+        encode_pool,
+        listing_cache: key_index::ListingCache::default(),
+        vouchers: Default::default(),
+        connections: Default::default(),
     });
 
+    tokio::spawn(key_index::backfill(Arc::clone(&shared_state)));
 
-    // Phase 11: Ignite the Cryptographic Proof of Spacetime (PoSt) Daemon
-    let post_daemon = proofs::ProofOfSpacetimeDaemon::new(Arc::clone(&shared_state));
+    // Every gateway logs the object requests it serves into its own database.
+    let access_log_daemon = access_log::AccessLogDaemon::new(Arc::clone(&shared_state));
     tokio::spawn(async move {
-        post_daemon.start().await;
+        access_log_daemon.start().await;
     });
 
-    // Phase 18: Ignite the Automated Data Repair Daemon (Self-Healing Swarm)
-    let repair_daemon = repair::RepairDaemon::new(Arc::clone(&shared_state));
-    tokio::spawn(async move {
-        repair_daemon.start().await;
-    });
+    if shared_state.replication.is_follower() {
+        // Followers only mirror metadata; proofs and repair stay with the leader.
+        let follower = replication::ReplicationFollower::new(Arc::clone(&shared_state));
+        tokio::spawn(async move {
+            follower.start().await;
+        });
+    } else {
+        // Phase 11: Ignite the Cryptographic Proof of Spacetime (PoSt) Daemon
+        let post_daemon = proofs::ProofOfSpacetimeDaemon::new(Arc::clone(&shared_state));
+        tokio::spawn(async move {
+            post_daemon.start().await;
+        });
+
+        // Phase 18: Ignite the Automated Data Repair Daemon (Self-Healing Swarm)
+        let repair_daemon = repair::RepairDaemon::new(Arc::clone(&shared_state));
+        tokio::spawn(async move {
+            repair_daemon.start().await;
+        });
+
+        let lifecycle_daemon = lifecycle::LifecycleDaemon::new(Arc::clone(&shared_state));
+        tokio::spawn(async move {
+            lifecycle_daemon.start().await;
+        });
 
-    let allowed_origins = parse_allowed_origins();
-    let cors = CorsLayer::new()
-        .allow_origin(AllowOrigin::list(allowed_origins))
-        .allow_methods([
-            Method::GET,
-            Method::POST,
-            Method::PUT,
-            Method::DELETE,
-            Method::OPTIONS,
-        ])
-        .allow_headers([
+        let upload_daemon = uploads::UploadSessionDaemon::new(Arc::clone(&shared_state));
+        tokio::spawn(async move {
+            upload_daemon.start().await;
+        });
+
+        let backup_daemon = backup::BackupDaemon::new(Arc::clone(&shared_state));
+        tokio::spawn(async move {
+            backup_daemon.start().await;
+        });
+
+        let health_scan_daemon = health_scan::HealthScanDaemon::new(Arc::clone(&shared_state));
+        tokio::spawn(async move {
+            health_scan_daemon.start().await;
+        });
+
+        let slashing_daemon = slashing::SlashingDaemon::new(Arc::clone(&shared_state));
+        tokio::spawn(async move {
+            slashing_daemon.start().await;
+        });
+
+        let deletion_reconciler = deletions::DeletionReconciler::new(Arc::clone(&shared_state));
+        tokio::spawn(async move {
+            deletion_reconciler.start().await;
+        });
+
+        if let Some(client) = shared_state.sentinel.clone() {
+            let sentinel_daemon = sentinel::SentinelDaemon::new(Arc::clone(&shared_state), client);
+            tokio::spawn(async move {
+                sentinel_daemon.start().await;
+            });
+        }
+    }
+
+    let cors = shared_state.config.cors_layer(
+        vec![
             axum::http::header::CONTENT_TYPE,
             axum::http::header::AUTHORIZATION,
             "x-csrf-token".parse().unwrap(),
             "x-neuro-proof-token".parse().unwrap(),
-        ])
-        .expose_headers([
+            REQUEST_ID_HEADER.parse().unwrap(),
+        ],
+        vec![
             axum::http::header::CONTENT_TYPE,
-        ])
-        .allow_credentials(true);
+            REQUEST_ID_HEADER.parse().unwrap(),
+            AMZ_REQUEST_ID_HEADER.parse().unwrap(),
+            "x-neuro-next-cursor".parse().unwrap(),
+            handlers::s3::DELETION_ID_HEADER.parse().unwrap(),
+        ],
+    );
+    let security_headers = Arc::new(shared_state.config.security_headers.header_map());
+    let server_config = shared_state.config.server.clone();
+    let connections = Arc::clone(&shared_state.connections);
 
     // Build the Axum Router
     let app = Router::new()
@@ -168,25 +291,113 @@ async fn main() -> anyhow::Result<()> {
         .route("/api/session", get(handlers::auth::session))
         
         // S3-Compatible API (Path Style)
-        .route("/:bucket", get(handlers::s3::list_objects))
+        .route("/:bucket",
+            get(handlers::s3::list_objects)
+            .put(handlers::lifecycle::put_bucket_lifecycle)
+            .post(handlers::bulk_delete::delete_objects)
+            .delete(handlers::lifecycle::delete_bucket_lifecycle)
+        )
         .route("/:bucket/*key", 
             get(handlers::s3::get_object)
+            .head(handlers::s3::head_object)
             .put(handlers::s3::put_object)
             .delete(handlers::s3::delete_object)
+            .route_layer(from_fn_with_state(Arc::clone(&shared_state), access_log::record))
         )
         
         // Internal Extensions
         .route("/api/manifest/:bucket/*key", get(handlers::s3::get_presigned_manifest))
+        .route("/api/shard/:cid", get(handlers::s3::get_shard))
+        .route("/api/vouchers/redeem", post(handlers::vouchers::redeem_voucher))
         .route("/api/deduplicate/:bucket/*key", post(handlers::s3::deduplicate_object))
         .route("/api/reconstruct/:bucket/*key", post(handlers::s3::reconstruct_metadata))
+        .route(
+            "/api/buckets/:name/policy",
+            get(handlers::policy::get_bucket_policy)
+                .put(handlers::policy::put_bucket_policy)
+                .delete(handlers::policy::delete_bucket_policy),
+        )
+        .route("/api/keys", post(handlers::policy::create_api_key))
         .route("/api/compliance/sovereignty/:bucket", get(handlers::compliance::sovereignty_audit))
+        .route("/api/deletions/:bucket", get(deletions::list_deletions))
+        .route("/api/deletions/:bucket/:deletion_id/certificate", get(deletions::erasure_certificate))
+        .route("/api/logs", get(handlers::logs::list_access_logs))
+        .route("/api/estimate", get(handlers::estimate::estimate))
+        .route("/api/pricing", get(handlers::pricing::pricing))
+        .route("/api/pricing/history", get(handlers::pricing::price_history))
         .route("/api/nodes/register", post(handlers::nodes::register_provider_node))
+        .route("/api/nodes/register/challenge", post(handlers::nodes::registration_challenge))
+        .route("/api/nodes/rotate", post(handlers::nodes::rotate_node_key))
+        .route("/api/nodes/:peer_id/policy", get(handlers::nodes::get_node_policy))
+        .route("/api/nodes/:peer_id/prices", get(handlers::nodes::get_node_prices))
+        .route("/api/nodes/:peer_id/addrs", get(handlers::nodes::locate_peer))
+        .route("/api/nodes/:peer_id/capabilities", get(handlers::nodes::get_node_capabilities))
+        .route("/api/nodes/:peer_id/penalties", get(slashing::list_node_penalties))
+        .route("/api/nodes/:peer_id/penalties/:penalty_id/appeal", post(slashing::appeal_penalty))
+        .route(
+            "/api/manifests",
+            get(handlers::manifests::list_manifests)
+                .post(handlers::manifests::register_manifest)
+                .layer(DefaultBodyLimit::max(handlers::manifests::MAX_UPLOADER_MANIFEST_BYTES)),
+        )
+        .route(
+            "/api/manifests/:root",
+            get(handlers::manifests::locate_manifest).delete(handlers::manifests::unregister_manifest),
+        )
+        .route("/api/manifests/:root/shards/:cid", get(handlers::manifests::get_manifest_shard))
+        .route(
+            "/api/uploads",
+            post(handlers::uploads::create_upload)
+                .layer(DefaultBodyLimit::max(handlers::manifests::MAX_UPLOADER_MANIFEST_BYTES))
+                .get(handlers::uploads::list_uploads),
+        )
+        .route(
+            "/api/uploads/:id",
+            get(handlers::uploads::get_upload).delete(handlers::uploads::abort_upload),
+        )
+        .route(
+            "/api/uploads/:id/shards/:cid",
+            put(handlers::uploads::put_upload_shard)
+                .layer(DefaultBodyLimit::max(handlers::uploads::MAX_UPLOAD_SHARD_BYTES)),
+        )
+        .route("/api/uploads/:id/parts", get(handlers::uploads::list_upload_parts))
+        .route("/api/uploads/:id/commit", post(handlers::uploads::commit_upload))
+        .route("/api/shares", post(handlers::shares::create_share))
+        .route(
+            "/api/shares/:share_id",
+            get(handlers::shares::get_share).delete(handlers::shares::revoke_share),
+        )
         .route("/zk/store/:bucket/*key", post(handlers::zk::zk_store))
         .route("/zk/issue-challenge", post(proofs::issue_zk_challenge))
         .route("/zk/submit-proof", post(proofs::verify_zk_proof))
+        .route("/api/replication/oplog", get(replication::oplog_feed))
+        .route(
+            "/api/admin/backups",
+            get(handlers::backups::list_backups).post(handlers::backups::create_backup),
+        )
+        .route("/api/admin/backups/:cid/restore", post(handlers::backups::restore_backup))
+        .route(
+            "/api/admin/buckets/:bucket/limits",
+            get(handlers::limits::get_bucket_limits)
+                .put(handlers::limits::put_bucket_limits)
+                .delete(handlers::limits::delete_bucket_limits),
+        )
+        .route("/api/admin/encode-pool", get(erasure::encode_pool_stats))
+        .route("/api/admin/connections", get(server::connection_stats))
+        .route("/api/admin/penalties", get(slashing::list_penalties))
+        .route("/api/admin/penalties/:penalty_id/resolve", post(slashing::resolve_penalty))
+        .route("/api/admin/tenants", get(tenancy::list_tenants).post(tenancy::put_tenant))
+        .route("/api/admin/tenants/:tenant_id/users/:email", put(tenancy::assign_tenant_user))
+        .route("/api/verify/:bucket/*key", post(handlers::verify::verify_object))
+        .route("/api/health/:bucket", get(handlers::health::bucket_health))
+        .route("/api/config-audit", get(config::config_audit))
         .fallback_service(ServeDir::new("public"))
+        .layer(from_fn_with_state(Arc::clone(&shared_state), replication::follower_guard))
+        .layer(compression_layer)
         .layer(cors)
-        .layer(from_fn(security_headers))
+        .layer(TraceLayer::new_for_http().make_span_with(request_span))
+        .layer(from_fn(assign_request_id))
+        .layer(from_fn_with_state(security_headers, config::security_headers))
         .with_state(shared_state);
 
     // Bind server (supporting Railway/Heroku dynamic PORT)
@@ -196,14 +407,65 @@ async fn main() -> anyhow::Result<()> {
         .unwrap_or(9009);
         
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    info!("NeuroStore V3 Enterprise Gateway listening on {}", addr);
+    info!(
+        http2 = server_config.http2,
+        tls = server_config.tls.is_some(),
+        max_connections = server_config.max_connections,
+        "NeuroStore V3 Enterprise Gateway listening on {}",
+        addr
+    );
     
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    server::serve(listener, app, &server_config, connections).await?;
 
     Ok(())
 }
 
+const REQUEST_ID_HEADER: &str = "x-neuro-request-id";
+const AMZ_REQUEST_ID_HEADER: &str = "x-amz-request-id";
+
+/// Reuses a caller-supplied `x-neuro-request-id` / `x-request-id` (e.g. from
+/// a load balancer) or mints one, and echoes it on the response, also as
+/// `x-amz-request-id` for S3 SDKs. The id is a field on the request span, so
+/// it follows the request into p2p spans, and the `RequestId` of any S3
+/// error body.
+async fn assign_request_id(mut req: Request, next: Next) -> Response {
+    let request_id = [REQUEST_ID_HEADER, "x-request-id"]
+        .iter()
+        .find_map(|name| req.headers().get(*name))
+        .filter(|v| !v.is_empty() && v.len() <= 128)
+        .cloned()
+        .unwrap_or_else(|| {
+            let mut id = [0u8; 8];
+            rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut id);
+            HeaderValue::from_str(&hex::encode(id)).expect("hex is a valid header value")
+        });
+    req.headers_mut().insert(REQUEST_ID_HEADER, request_id.clone());
+    let id = request_id.to_str().unwrap_or_default().to_string();
+    let mut response = handlers::s3_error::with_request_id(id, next.run(req)).await;
+    response.headers_mut().insert(AMZ_REQUEST_ID_HEADER, request_id.clone());
+    response.headers_mut().insert(REQUEST_ID_HEADER, request_id);
+    response
+}
+
+/// Handlers fill in `bucket`/`key`/`cid` via `Span::current().record`.
+fn request_span(req: &Request) -> tracing::Span {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    tracing::info_span!(
+        "http_request",
+        method = %req.method(),
+        path = %req.uri().path(),
+        request_id,
+        bucket = tracing::field::Empty,
+        key = tracing::field::Empty,
+        cid = tracing::field::Empty,
+    )
+}
+
 async fn health_check(
     State(state): State<Arc<AppState>>,
 ) -> Json<serde_json::Value> {
@@ -226,19 +488,10 @@ async fn health_check(
     if state.node_shared_secret.len() < 32 {
         warnings.push("NODE_SHARED_SECRET is shorter than 32 characters".to_string());
     }
-    if !state.cookie_secure {
-        warnings.push("COOKIE_SECURE is disabled".to_string());
-    }
-    if state.environment.eq_ignore_ascii_case("production") {
-        let has_localhost_origin = std::env::var("ALLOWED_ORIGINS")
-            .unwrap_or_default()
-            .split(',')
-            .map(|o| o.trim().to_lowercase())
-            .any(|o| o.contains("localhost") || o.contains("127.0.0.1"));
-        if has_localhost_origin {
-            warnings.push("ALLOWED_ORIGINS contains localhost while ENVIRONMENT=production".to_string());
-        }
+    if state.backup.admin_token.as_ref().is_some_and(|t| t.len() < 32) {
+        warnings.push("ADMIN_TOKEN is shorter than 32 characters".to_string());
     }
+    warnings.extend(state.config.readiness_warnings());
 
     let production_ready = db_ok && warnings.is_empty();
 
@@ -249,52 +502,8 @@ async fn health_check(
         "readiness_warnings": warnings,
         "service": "neurostore-rust-gateway-v3",
         "version": "0.3.0",
-        "environment": state.environment,
+        "environment": state.config.environment,
+        "role": if state.replication.is_follower() { "follower" } else { "leader" },
     }))
 }
 
-async fn security_headers(
-    request: Request,
-    next: Next,
-) -> Response {
-    let mut response = next.run(request).await;
-    let headers = response.headers_mut();
-    headers.insert(
-        "x-content-type-options",
-        HeaderValue::from_static("nosniff"),
-    );
-    headers.insert(
-        "x-frame-options",
-        HeaderValue::from_static("DENY"),
-    );
-    headers.insert(
-        "referrer-policy",
-        HeaderValue::from_static("strict-origin-when-cross-origin"),
-    );
-    headers.insert(
-        "permissions-policy",
-        HeaderValue::from_static("camera=(), microphone=(), geolocation=()"),
-    );
-    response
-}
-
-fn parse_allowed_origins() -> Vec<HeaderValue> {
-    let raw = std::env::var("ALLOWED_ORIGINS").unwrap_or_else(|_| {
-        "https://neurostore-next.vercel.app,https://neurostore-next-production.up.railway.app,http://localhost:5173".to_string()
-    });
-
-    let mut parsed = Vec::new();
-    for origin in raw.split(',').map(|v| v.trim()).filter(|v| !v.is_empty()) {
-        match origin.parse::<HeaderValue>() {
-            Ok(value) => parsed.push(value),
-            Err(_) => tracing::warn!("Ignoring invalid origin in ALLOWED_ORIGINS: {}", origin),
-        }
-    }
-
-    if parsed.is_empty() {
-        tracing::warn!("ALLOWED_ORIGINS produced no valid origins, falling back to localhost-only");
-        parsed.push("http://localhost:5173".parse().unwrap());
-    }
-
-    parsed
-}
@@ -27,6 +27,13 @@ pub struct Object {
     pub size: i64,
     pub created_at: Option<DateTime<Utc>>,
     pub metadata_json: Option<serde_json::Value>,
+    /// Write token from `object_versions`; a write only replaces the row
+    /// when its version is higher.
+    #[serde(default)]
+    pub version: i64,
+    /// `key_index::lookup_token` of the plain key; NULL until backfilled.
+    #[serde(default)]
+    pub key_lookup: Option<String>,
 }
 
 // ── API Payloads ────────────────────────────────────────────────
@@ -72,3 +79,12 @@ pub struct Node {
     pub last_seen: Option<DateTime<Utc>>,
     pub created_at: Option<DateTime<Utc>>,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct LifecycleRule {
+    pub rule_id: String,
+    pub tag_key: Option<String>,
+    pub tag_value: Option<String>,
+    pub expiration_days: i32,
+    pub enabled: bool,
+}
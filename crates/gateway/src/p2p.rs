@@ -1,28 +1,96 @@
 use libp2p::{
-    kad::{store::MemoryStore, Behaviour as Kademlia, Config as KadConfig},
-    noise, tcp, yamux, relay, autonat,
+    kad::{self, store::RecordStore, Behaviour as Kademlia, Config as KadConfig, ProviderRecord, Quorum, Record, RecordKey},
+    noise, tcp, yamux, relay, autonat, identify,
+    gossipsub::{self, IdentTopic, MessageAuthenticity, ValidationMode},
+    core::upgrade::Version,
+    pnet::{PnetConfig, PreSharedKey},
     request_response::{self, Behaviour as RequestResponse, Codec as RequestResponseCodec},
     swarm::{NetworkBehaviour, SwarmEvent},
-    identity, PeerId, Swarm, StreamProtocol, SwarmBuilder,
+    identity, PeerId, Swarm, StreamProtocol, SwarmBuilder, Transport,
 };
+use either::Either;
 use futures::StreamExt;
-use tracing::{info, warn};
-use neuro_protocol::{AuditChunkRequest, ChunkCommand, ChunkReply};
+use tracing::{debug, info, info_span, warn, Span};
+use neuro_protocol::{
+    AuditChunkRequest, ChunkCommand, ChunkReply, DeleteChunkResponse, DeleteChunksRequest, DeleteChunksResponse, NodeAnnouncement, PeerCapabilities,
+    Tombstone, TombstoneMessage, VoucheredRetrieveRequest, ANNOUNCE_TOPIC, MAX_TOMBSTONES_PER_MESSAGE, PROTOCOL_VERSION, TOMBSTONE_TOPIC,
+};
 use std::io;
 use std::net::IpAddr;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use tokio::sync::{mpsc, oneshot};
 use tokio::time::{self, Duration, Instant};
 use rand::seq::IteratorRandom;
 use crate::geofence::GeoFenceManager;
+use crate::kad_store::{self, PersistentStore};
 use crate::models::Node;
+use crate::sentinel::FleetPolicy;
+use neuro_placement::{Candidate, Strategy};
+use std::path::Path;
+use std::sync::Arc;
 use libp2p::request_response::OutboundRequestId;
 
 pub enum SwarmRequest {
     Store { command: ChunkCommand, geofence: String, tx: oneshot::Sender<StoreAck> },
-    Retrieve { cid: String, preferred_peer_id: Option<String>, tx: oneshot::Sender<RetrieveAck> },
-    Delete { cid: String, tx: oneshot::Sender<bool> },
+    /// `voucher` is passed on to nodes that enforce bandwidth vouchers;
+    /// older ones get a plain retrieve.
+    Retrieve { cid: String, preferred_peer_id: Option<String>, voucher: Option<String>, tx: oneshot::Sender<RetrieveAck> },
+    /// Deletes one shard from `peer_id`, or from any connected peer when the
+    /// holder is unknown.
+    Delete { cid: String, peer_id: Option<String>, tx: oneshot::Sender<DeleteAck> },
+    /// Deletes up to `MAX_DELETE_CIDS` shards held by `peer_id` in one
+    /// request. Answers with the CIDs the peer confirmed and its signed
+    /// receipt, or `None` when it is not connected, does not speak
+    /// `delete-batch` or did not answer, so the caller can fall back to
+    /// single `Delete`s.
+    DeleteBatch { peer_id: String, cids: Vec<String>, tx: oneshot::Sender<Option<BatchDeleteAck>> },
+    /// Gossips signed tombstones for deleted shards, for holders the
+    /// `Delete`s did not reach.
+    Tombstone { cids: Vec<String> },
     Audit { peer_id: String, cid: String, challenge_hex: String, nonce_hex: String, tx: oneshot::Sender<AuditAck> },
+    /// Connect to peers the gateway did not place shards on itself (e.g. the
+    /// nodes named in an uploader manifest) so Retrieve/Audit can reach them.
+    Dial { addrs: Vec<libp2p::Multiaddr> },
+    /// Current addresses of a peer, from the routing table or, failing
+    /// that, a DHT walk towards its PeerId. Empty when nobody knows it.
+    LocatePeer { peer_id: PeerId, tx: oneshot::Sender<Vec<libp2p::Multiaddr>> },
+}
+
+/// How long a `LocatePeer` DHT walk may run before answering with whatever
+/// the routing table holds.
+const LOCATE_PEER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often, and how many, shard placements are checked against the DHT
+/// store and re-announced when missing.
+const PLACEMENT_REANNOUNCE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const PLACEMENT_REANNOUNCE_BATCH: i64 = 500;
+
+/// A swarm command together with the span of whoever issued it (an HTTP
+/// request or a daemon tick), so p2p work lands under the caller's trace.
+pub struct TracedSwarmRequest {
+    pub request: SwarmRequest,
+    pub span: Span,
+}
+
+/// Sending half of the p2p command channel. `send` captures the current
+/// span, so call sites stay `state.p2p_tx.send(req).await`.
+#[derive(Clone)]
+pub struct SwarmSender {
+    tx: mpsc::Sender<TracedSwarmRequest>,
+}
+
+impl SwarmSender {
+    pub fn channel(buffer: usize) -> (Self, mpsc::Receiver<TracedSwarmRequest>) {
+        let (tx, rx) = mpsc::channel(buffer);
+        (Self { tx }, rx)
+    }
+
+    pub async fn send(&self, request: SwarmRequest) -> Result<(), mpsc::error::SendError<SwarmRequest>> {
+        self.tx
+            .send(TracedSwarmRequest { request, span: Span::current() })
+            .await
+            .map_err(|e| mpsc::error::SendError(e.0.request))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -42,6 +110,32 @@ pub struct RetrieveAck {
     pub timestamp_ms: u64,
 }
 
+/// A node's signed answer to a deletion request. `payload` is the exact
+/// message the signature covers, so the receipt can be checked against the
+/// node's public key without the gateway.
+#[derive(Debug, Clone)]
+pub struct DeletionReceipt {
+    pub peer_id: String,
+    pub payload: String,
+    pub timestamp_ms: u64,
+    pub signature_hex: String,
+    pub public_key_hex: String,
+}
+
+/// `receipt` is only set when the answer was signed by the peer it was sent
+/// to and is fresh.
+#[derive(Debug, Clone)]
+pub struct DeleteAck {
+    pub deleted: bool,
+    pub receipt: Option<DeletionReceipt>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchDeleteAck {
+    pub deleted: Vec<String>,
+    pub receipt: DeletionReceipt,
+}
+
 #[derive(Debug, Clone)]
 pub struct AuditAck {
     pub verified: bool,
@@ -61,6 +155,7 @@ struct PendingStore {
     country_code: String,
     cid: String,
     len: usize,
+    span: Span,
 }
 
 struct PendingRetrieval {
@@ -68,11 +163,29 @@ struct PendingRetrieval {
     deadline: Instant,
     peer_id: PeerId,
     cid: String,
+    span: Span,
 }
 
 struct PendingDeletion {
-    tx: oneshot::Sender<bool>,
+    tx: oneshot::Sender<DeleteAck>,
     deadline: Instant,
+    peer_id: PeerId,
+    cid: String,
+    span: Span,
+}
+
+struct PendingBatchDeletion {
+    tx: oneshot::Sender<Option<BatchDeleteAck>>,
+    deadline: Instant,
+    peer_id: PeerId,
+    cids: Vec<String>,
+    span: Span,
+}
+
+struct PendingLocate {
+    tx: oneshot::Sender<Vec<libp2p::Multiaddr>>,
+    deadline: Instant,
+    peer_id: PeerId,
 }
 
 struct PendingAudit {
@@ -83,6 +196,7 @@ struct PendingAudit {
     cid: String,
     challenge_hex: String,
     nonce_hex: String,
+    span: Span,
 }
 
 
@@ -152,38 +266,71 @@ impl RequestResponseCodec for ChunkCodec {
 
 #[derive(NetworkBehaviour)]
 pub struct NeuroStoreBehaviour {
-    pub kademlia: Kademlia<MemoryStore>,
+    pub kademlia: Kademlia<PersistentStore>,
     pub chunk: RequestResponse<ChunkCodec>,
     pub relay: relay::Behaviour,
     pub autonat: autonat::Behaviour,
+    pub identify: identify::Behaviour,
+    /// Listens for the nodes' own announcements and publishes tombstones.
+    pub gossipsub: gossipsub::Behaviour,
 }
 
 pub struct P2pNode {
     swarm: Swarm<NeuroStoreBehaviour>,
+    /// Signs shard tombstones; the same identity the swarm runs as.
+    keypair: identity::Keypair,
     peer_ips: HashMap<PeerId, IpAddr>,
     pending_retrievals: HashMap<OutboundRequestId, PendingRetrieval>,
     pending_deletions: HashMap<OutboundRequestId, PendingDeletion>,
+    pending_batch_deletions: HashMap<OutboundRequestId, PendingBatchDeletion>,
     pending_stores: HashMap<OutboundRequestId, PendingStore>,
     pending_audits: HashMap<OutboundRequestId, PendingAudit>,
+    pending_locates: HashMap<kad::QueryId, PendingLocate>,
+    /// What each connected peer advertised over identify.
+    peer_capabilities: HashMap<PeerId, PeerCapabilities>,
+    /// Picks which authorized peer receives each stored shard.
+    placement: Strategy,
+    /// Connected nodes in maintenance, which get no new shards. Forgotten
+    /// on disconnect; a node still in maintenance re-announces it.
+    maintenance_peers: HashSet<PeerId>,
 }
 
 
 impl P2pNode {
-    pub async fn new() -> anyhow::Result<Self> {
-        let local_key = identity::Keypair::generate_ed25519();
+    /// The identity the swarm runs as.
+    pub fn identity(&self) -> identity::Keypair {
+        self.keypair.clone()
+    }
+
+    /// `kad_dir` holds the gateway's identity and its persistent DHT store.
+    /// With a `swarm_key` the gateway only connects to nodes of that private
+    /// swarm.
+    pub async fn new(kad_dir: &Path, swarm_key: Option<PreSharedKey>, placement: Strategy) -> anyhow::Result<Self> {
+        let local_key = kad_store::load_or_create_identity(kad_dir)?;
         let local_peer_id = PeerId::from(local_key.public());
         info!("S3 Gateway PeerId: {}", local_peer_id);
+        let kad_db = sled::open(kad_dir.join("records"))?;
+        let store = PersistentStore::open(&kad_db, local_peer_id)?;
 
+        let keypair = local_key.clone();
         let swarm = SwarmBuilder::with_existing_identity(local_key)
             .with_tokio()
-            .with_tcp(
-                tcp::Config::default(),
-                noise::Config::new,
-                yamux::Config::default,
-            )?
+            .with_other_transport(|key| {
+                let tcp = tcp::tokio::Transport::new(tcp::Config::default());
+                let tcp = match swarm_key {
+                    Some(psk) => Either::Left(
+                        tcp.and_then(move |socket, _| PnetConfig::new(psk).handshake(socket)),
+                    ),
+                    None => Either::Right(tcp),
+                };
+                Ok::<_, Box<dyn std::error::Error + Send + Sync>>(
+                    tcp.upgrade(Version::V1Lazy)
+                        .authenticate(noise::Config::new(key)?)
+                        .multiplex(yamux::Config::default()),
+                )
+            })?
             .with_behaviour(|key: &identity::Keypair| {
                 let local_peer_id = PeerId::from(key.public());
-                let store = MemoryStore::new(local_peer_id);
                 let mut kad_config = KadConfig::default();
                 kad_config.set_protocol_names(vec![StreamProtocol::new("/neurostore/kad/1.0.0")]);
                 
@@ -226,23 +373,42 @@ impl P2pNode {
                 
                 let relay = relay::Behaviour::new(local_peer_id, relay::Config::default());
                 let autonat = autonat::Behaviour::new(local_peer_id, autonat::Config::default());
+                let identify = identify::Behaviour::new(
+                    identify::Config::new(PROTOCOL_VERSION.to_string(), key.public())
+                        .with_agent_version(format!("neurostore-gateway/{}", env!("CARGO_PKG_VERSION"))),
+                );
+
+                let gossipsub = gossipsub::Behaviour::new(
+                    MessageAuthenticity::Signed(key.clone()),
+                    gossipsub::ConfigBuilder::default()
+                        .validation_mode(ValidationMode::Strict)
+                        .build()?,
+                )?;
 
-                NeuroStoreBehaviour {
+                Ok::<_, Box<dyn std::error::Error + Send + Sync>>(NeuroStoreBehaviour {
                     kademlia,
                     chunk,
                     relay,
                     autonat,
-                }
+                    identify,
+                    gossipsub,
+                })
             })?
             .build();
 
         Ok(Self { 
             swarm,
+            keypair,
             peer_ips: HashMap::new(),
             pending_retrievals: HashMap::new(),
             pending_deletions: HashMap::new(),
+            pending_batch_deletions: HashMap::new(),
             pending_stores: HashMap::new(),
             pending_audits: HashMap::new(),
+            pending_locates: HashMap::new(),
+            peer_capabilities: HashMap::new(),
+            placement,
+            maintenance_peers: HashSet::new(),
         })
     }
 
@@ -250,22 +416,33 @@ impl P2pNode {
     pub async fn start(
         &mut self, 
         port: u16, 
-        mut rx: mpsc::Receiver<SwarmRequest>, 
+        mut rx: mpsc::Receiver<TracedSwarmRequest>, 
         geo: GeoFenceManager,
         db: sqlx::PgPool,
+        fleet_policy: Arc<FleetPolicy>,
     ) -> anyhow::Result<()> {
         let listen_addr = format!("/ip4/0.0.0.0/tcp/{}", port).parse()?;
         self.swarm.listen_on(listen_addr)?;
+        self.swarm
+            .behaviour_mut()
+            .gossipsub
+            .subscribe(&IdentTopic::new(ANNOUNCE_TOPIC))?;
         info!("S3 Gateway P2P Swarm listening on TCP {}", port);
         let mut cleanup_interval = time::interval(Duration::from_secs(1));
+        let mut reannounce_interval = time::interval(PLACEMENT_REANNOUNCE_INTERVAL);
+        let mut reannounce_cursor = String::new();
 
         loop {
             tokio::select! {
                 _ = cleanup_interval.tick() => {
                     self.expire_pending_requests();
                 }
-                Some(req) = rx.recv() => match req {
+                _ = reannounce_interval.tick() => {
+                    self.reannounce_placements(&db, &mut reannounce_cursor).await;
+                }
+                Some(TracedSwarmRequest { request: req, span: parent }) = rx.recv() => match req {
                     SwarmRequest::Store { command, geofence, tx } => {
+                        let span = info_span!(parent: &parent, "p2p.store", cid = tracing::field::Empty, peer_id = tracing::field::Empty);
                         let (cid, len) = match &command {
                             ChunkCommand::Store(req) => (req.cid.clone(), req.data.len()),
                             _ => {
@@ -282,6 +459,16 @@ impl P2pNode {
                         let peers: Vec<_> = self.swarm.connected_peers().cloned().collect();
                         let mut authorized_peers = Vec::new();
                         for peer_id in peers {
+                            // Peers the sentinel quarantined or evicted get no new shards.
+                            if fleet_policy.is_excluded(&peer_id.to_string()) {
+                                continue;
+                            }
+                            if self.maintenance_peers.contains(&peer_id) {
+                                continue;
+                            }
+                            if !self.accepts(&peer_id, &command) {
+                                continue;
+                            }
                             if let Some(ip) = self.peer_ips.get(&peer_id) {
                                 if geo.is_authorized(*ip, &geofence) {
                                     authorized_peers.push(peer_id);
@@ -289,24 +476,25 @@ impl P2pNode {
                             }
                         }
 
-                        // ── COLLUSION-AWARE PLACEMENT ──
-                        // We track which ASNs have already received shards for this specific CID
-                        // to ensure no single entity controls the recovery threshold.
-                        // For a simplified implementation here, we just pick a random peer 
-                        // and log its ASN, but in a full stateful router, this history is persisted per-object.
-                        let mut chosen_peer = None;
-                        let mut attempts = 0;
-                        while attempts < 10 {
-                            if let Some(peer_id) = authorized_peers.iter().choose(&mut rand::thread_rng()) {
-                                if let Some(ip) = self.peer_ips.get(peer_id) {
-                                    let asn = geo.get_asn_org(*ip);
-                                    // Normally we would check: if used_asns.count(&asn) >= MAX_ASN_DENSITY { continue; }
-                                    tracing::debug!("Routing shard to ASN: {}", asn);
-                                    chosen_peer = Some(*peer_id);
-                                    break;
+                        // ── DETERMINISTIC PLACEMENT ──
+                        // The same strategy the uploader uses, keyed by the shard CID, so
+                        // a retried store lands on the same peer and churn only moves the
+                        // shards of peers that came or went. Regions are country codes.
+                        let candidates: Vec<Candidate> = authorized_peers
+                            .iter()
+                            .map(|peer_id| {
+                                let candidate = Candidate::new(peer_id.to_string());
+                                match self.peer_ips.get(peer_id) {
+                                    Some(ip) => candidate.with_region(geo.get_country_code(*ip)),
+                                    None => candidate,
                                 }
-                            }
-                            attempts += 1;
+                            })
+                            .collect();
+                        let chosen_peer = neuro_placement::place(self.placement, &cid, &candidates, 1)
+                            .first()
+                            .and_then(|c| c.id.parse::<PeerId>().ok());
+                        if let Some(ip) = chosen_peer.and_then(|peer_id| self.peer_ips.get(&peer_id)) {
+                            tracing::debug!("Routing shard to ASN: {}", geo.get_asn_org(*ip));
                         }
 
                         if let Some(peer_id) = chosen_peer {
@@ -315,7 +503,9 @@ impl P2pNode {
                                 .get(&peer_id)
                                 .map(|ip| geo.get_country_code(*ip))
                                 .unwrap_or_else(|| "XX".to_string());
-                            info!("Transmitting geofenced shard ({}) to LibP2P Node: {}", geofence, peer_id);
+                            span.record("cid", cid.as_str());
+                            span.record("peer_id", tracing::field::display(peer_id));
+                            span.in_scope(|| info!(geofence = %geofence, "Transmitting geofenced shard to LibP2P Node"));
                             let request_id = self.swarm.behaviour_mut().chunk.send_request(&peer_id, command);
                             self.pending_stores.insert(
                                 request_id,
@@ -326,6 +516,7 @@ impl P2pNode {
                                     country_code,
                                     cid,
                                     len,
+                                    span,
                                 },
                             );
                         } else {
@@ -338,7 +529,8 @@ impl P2pNode {
                             });
                         }
                     }
-                    SwarmRequest::Retrieve { cid, preferred_peer_id, tx } => {
+                    SwarmRequest::Retrieve { cid, preferred_peer_id, voucher, tx } => {
+                        let span = info_span!(parent: &parent, "p2p.retrieve", cid = %cid, peer_id = tracing::field::Empty);
                         let target_peer = preferred_peer_id
                             .as_ref()
                             .and_then(|value| value.parse::<PeerId>().ok())
@@ -371,7 +563,15 @@ impl P2pNode {
                         };
 
                         if let Some(peer_id) = target_peer {
-                            let cmd = ChunkCommand::Retrieve(neuro_protocol::RetrieveChunkRequest { cid: cid.clone() });
+                            span.record("peer_id", tracing::field::display(peer_id));
+                            span.in_scope(|| debug!("Dispatching shard retrieval"));
+                            let vouchered = voucher.map(|voucher| {
+                                ChunkCommand::RetrieveVouchered(VoucheredRetrieveRequest { cid: cid.clone(), voucher })
+                            });
+                            let cmd = match vouchered {
+                                Some(cmd) if self.accepts(&peer_id, &cmd) => cmd,
+                                _ => ChunkCommand::Retrieve(neuro_protocol::RetrieveChunkRequest { cid: cid.clone() }),
+                            };
                             let request_id = self.swarm.behaviour_mut().chunk.send_request(&peer_id, cmd);
                             self.pending_retrievals.insert(
                                 request_id,
@@ -380,9 +580,11 @@ impl P2pNode {
                                     deadline: Instant::now() + Duration::from_secs(8),
                                     peer_id,
                                     cid,
+                                    span,
                                 },
                             );
                         } else {
+                            span.in_scope(|| warn!("No connected peer for shard retrieval"));
                             let _ = tx.send(RetrieveAck {
                                 data: None,
                                 peer_id: String::new(),
@@ -391,22 +593,59 @@ impl P2pNode {
                             });
                         }
                     }
-                    SwarmRequest::Delete { cid, tx } => {
-                        if let Some(peer_id) = self.swarm.connected_peers().choose(&mut rand::thread_rng()).cloned() {
-                            let cmd = ChunkCommand::Delete(neuro_protocol::DeleteChunkRequest { cid });
+                    SwarmRequest::Delete { cid, peer_id, tx } => {
+                        let span = info_span!(parent: &parent, "p2p.delete", cid = %cid, peer_id = tracing::field::Empty);
+                        let target = match peer_id {
+                            Some(peer_id) => peer_id.parse::<PeerId>().ok().filter(|peer_id| self.swarm.is_connected(peer_id)),
+                            None => self.swarm.connected_peers().choose(&mut rand::thread_rng()).cloned(),
+                        };
+                        if let Some(peer_id) = target {
+                            span.record("peer_id", tracing::field::display(peer_id));
+                            let cmd = ChunkCommand::Delete(neuro_protocol::DeleteChunkRequest { cid: cid.clone() });
                             let request_id = self.swarm.behaviour_mut().chunk.send_request(&peer_id, cmd);
                             self.pending_deletions.insert(
                                 request_id,
                                 PendingDeletion {
                                     tx,
                                     deadline: Instant::now() + Duration::from_secs(8),
+                                    peer_id,
+                                    cid,
+                                    span,
                                 },
                             );
                         } else {
-                            let _ = tx.send(false);
+                            let _ = tx.send(DeleteAck { deleted: false, receipt: None });
                         }
                     }
+                    SwarmRequest::DeleteBatch { peer_id, cids, tx } => {
+                        let span = info_span!(parent: &parent, "p2p.delete_batch", peer_id = %peer_id, shards = cids.len());
+                        let command = ChunkCommand::DeleteBatch(DeleteChunksRequest { cids: cids.clone() });
+                        let target = peer_id
+                            .parse::<PeerId>()
+                            .ok()
+                            .filter(|peer_id| self.swarm.is_connected(peer_id) && self.accepts(peer_id, &command));
+                        let Some(peer_id) = target else {
+                            let _ = tx.send(None);
+                            continue;
+                        };
+                        let request_id = self.swarm.behaviour_mut().chunk.send_request(&peer_id, command);
+                        self.pending_batch_deletions.insert(
+                            request_id,
+                            PendingBatchDeletion {
+                                tx,
+                                deadline: Instant::now() + Duration::from_secs(15),
+                                peer_id,
+                                cids,
+                                span,
+                            },
+                        );
+                    }
+                    SwarmRequest::Tombstone { cids } => {
+                        info_span!(parent: &parent, "p2p.tombstone", shards = cids.len())
+                            .in_scope(|| self.publish_tombstones(cids));
+                    }
                     SwarmRequest::Audit { peer_id, cid, challenge_hex, nonce_hex, tx } => {
+                        let span = info_span!(parent: &parent, "p2p.audit", cid = %cid, peer_id = %peer_id);
                         let parsed_peer = match peer_id.parse::<PeerId>() {
                             Ok(p) => p,
                             Err(_) => {
@@ -447,6 +686,7 @@ impl P2pNode {
                             cid: cid.clone(),
                             challenge_hex: challenge_hex.clone(),
                             nonce_hex: nonce_hex.clone(),
+                            issued_at_ms: chrono::Utc::now().timestamp_millis() as u64,
                         });
                         let request_id = self.swarm.behaviour_mut().chunk.send_request(&parsed_peer, cmd);
                         self.pending_audits.insert(
@@ -459,6 +699,39 @@ impl P2pNode {
                                 cid,
                                 challenge_hex,
                                 nonce_hex,
+                                span,
+                            },
+                        );
+                    }
+                    SwarmRequest::Dial { addrs } => {
+                        let _span = info_span!(parent: &parent, "p2p.dial", addrs = addrs.len()).entered();
+                        for addr in addrs {
+                            let Some(libp2p::multiaddr::Protocol::P2p(peer_id)) = addr.iter().last() else {
+                                continue;
+                            };
+                            if self.swarm.is_connected(&peer_id) {
+                                continue;
+                            }
+                            self.swarm.behaviour_mut().kademlia.add_address(&peer_id, addr.clone());
+                            if let Err(e) = self.swarm.dial(addr.clone()) {
+                                debug!("Dial {} failed: {}", addr, e);
+                            }
+                        }
+                    }
+                    SwarmRequest::LocatePeer { peer_id, tx } => {
+                        let _span = info_span!(parent: &parent, "p2p.locate_peer", peer_id = %peer_id).entered();
+                        let known = self.known_addrs(&peer_id);
+                        if !known.is_empty() {
+                            let _ = tx.send(known);
+                            continue;
+                        }
+                        let query_id = self.swarm.behaviour_mut().kademlia.get_closest_peers(peer_id);
+                        self.pending_locates.insert(
+                            query_id,
+                            PendingLocate {
+                                tx,
+                                deadline: Instant::now() + LOCATE_PEER_TIMEOUT,
+                                peer_id,
                             },
                         );
                     }
@@ -519,6 +792,40 @@ impl P2pNode {
                     SwarmEvent::ConnectionClosed { peer_id, .. } => {
                         warn!("Node Disconnected: {:?}", peer_id);
                         self.peer_ips.remove(&peer_id);
+                        self.peer_capabilities.remove(&peer_id);
+                        self.maintenance_peers.remove(&peer_id);
+                    }
+                    SwarmEvent::Behaviour(NeuroStoreBehaviourEvent::Gossipsub(gossipsub::Event::Message { message, .. })) => {
+                        // The signed source is the node the announcement is about.
+                        let announcement = serde_json::from_slice::<NodeAnnouncement>(&message.data);
+                        if let (Some(source), Ok(announcement)) = (message.source, announcement) {
+                            self.apply_announcement(source, announcement);
+                        }
+                    }
+                    SwarmEvent::Behaviour(NeuroStoreBehaviourEvent::Identify(identify::Event::Received { peer_id, info })) => {
+                        let capabilities = PeerCapabilities::from_identify(&info.protocol_version, &info.agent_version);
+                        debug!(
+                            "Node {} advertises {} features=[{}]",
+                            peer_id,
+                            capabilities.agent_version,
+                            capabilities.features.join(",")
+                        );
+                        let db_clone = db.clone();
+                        let peer_str = peer_id.to_string();
+                        let recorded = capabilities.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = record_capabilities(&db_clone, &peer_str, &recorded).await {
+                                warn!("Recording capabilities of {} failed: {}", peer_str, e);
+                            }
+                        });
+                        self.peer_capabilities.insert(peer_id, capabilities);
+                    }
+                    SwarmEvent::Behaviour(NeuroStoreBehaviourEvent::Kademlia(kad::Event::OutboundQueryProgressed {
+                        id, result: kad::QueryResult::GetClosestPeers(_), step, ..
+                    })) if step.last => {
+                        if let Some(pending) = self.pending_locates.remove(&id) {
+                            let _ = pending.tx.send(self.known_addrs(&pending.peer_id));
+                        }
                     }
                     SwarmEvent::Behaviour(NeuroStoreBehaviourEvent::Chunk(request_response::Event::Message { 
                         peer: _, message: request_response::Message::Response { request_id, response } 
@@ -528,6 +835,7 @@ impl P2pNode {
                                 let now_ms = chrono::Utc::now().timestamp_millis() as u64;
                                 let sig_ok = res.verify_proof(&pending.peer_id, &pending.cid)
                                     && res.is_fresh(now_ms, 30_000);
+                                pending.span.in_scope(|| debug!(found = res.found, signature_valid = sig_ok, bytes = res.data.len(), "Shard retrieval answered"));
                                 let data = if res.found && sig_ok { Some(res.data) } else { None };
                                 let _ = pending.tx.send(RetrieveAck {
                                     data,
@@ -545,13 +853,62 @@ impl P2pNode {
                             }
                         } else if let Some(pending) = self.pending_deletions.remove(&request_id) {
                             if let ChunkReply::Delete(res) = response {
-                                let _ = pending.tx.send(res.deleted);
+                                let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+                                let sig_ok = res.verify_deletion(&pending.peer_id, &pending.cid)
+                                    && res.is_fresh(now_ms, 30_000);
+                                pending.span.in_scope(|| debug!(deleted = res.deleted, signature_valid = sig_ok, "Shard deletion answered"));
+                                let receipt = sig_ok.then(|| DeletionReceipt {
+                                    peer_id: pending.peer_id.to_string(),
+                                    payload: String::from_utf8_lossy(&DeleteChunkResponse::deletion_payload(&pending.cid, res.timestamp_ms))
+                                        .into_owned(),
+                                    timestamp_ms: res.timestamp_ms,
+                                    signature_hex: hex::encode(&res.signature),
+                                    public_key_hex: hex::encode(&res.public_key),
+                                });
+                                let _ = pending.tx.send(DeleteAck { deleted: res.deleted, receipt });
+                            } else {
+                                let _ = pending.tx.send(DeleteAck { deleted: false, receipt: None });
                             }
+                        } else if let Some(pending) = self.pending_batch_deletions.remove(&request_id) {
+                            let deleted = match response {
+                                ChunkReply::DeleteBatch(res) => {
+                                    let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+                                    let sig_ok = res.verify_deletion(&pending.peer_id, &pending.cids)
+                                        && res.is_fresh(now_ms, 30_000);
+                                    pending.span.in_scope(|| debug!(deleted = res.deleted.len(), signature_valid = sig_ok, "Batch shard deletion answered"));
+                                    sig_ok.then(|| BatchDeleteAck {
+                                        receipt: DeletionReceipt {
+                                            peer_id: pending.peer_id.to_string(),
+                                            payload: String::from_utf8_lossy(&DeleteChunksResponse::deletion_payload(
+                                                &pending.cids,
+                                                &res.deleted,
+                                                res.timestamp_ms,
+                                            ))
+                                            .into_owned(),
+                                            timestamp_ms: res.timestamp_ms,
+                                            signature_hex: hex::encode(&res.signature),
+                                            public_key_hex: hex::encode(&res.public_key),
+                                        },
+                                        deleted: res.deleted,
+                                    })
+                                }
+                                _ => None,
+                            };
+                            let _ = pending.tx.send(deleted);
                         } else if let Some(pending) = self.pending_stores.remove(&request_id) {
+                            if let ChunkReply::Maintenance(_) = response {
+                                // Raced with its announcement; leave it out from here on.
+                                pending.span.in_scope(|| info!("Node is in maintenance, store refused"));
+                                self.maintenance_peers.insert(pending.peer_id);
+                            }
                             if let ChunkReply::Store(res) = response {
                                 let now_ms = chrono::Utc::now().timestamp_millis() as u64;
                                 let sig_ok = res.verify_receipt(&pending.peer_id, &pending.cid, pending.len)
                                     && res.is_fresh(now_ms, 30_000);
+                                pending.span.in_scope(|| debug!(stored = res.stored, signature_valid = sig_ok, "Shard store answered"));
+                                if res.stored && sig_ok {
+                                    self.announce_placement(&pending.cid, pending.peer_id);
+                                }
                                 let _ = pending.tx.send(StoreAck {
                                     stored: res.stored && sig_ok,
                                     peer_id: pending.peer_id.to_string(),
@@ -577,6 +934,7 @@ impl P2pNode {
                                     &pending.challenge_hex,
                                     &pending.nonce_hex,
                                 ) && res.is_fresh(now_ms, 30_000);
+                                pending.span.in_scope(|| debug!(accepted = res.accepted, signature_valid = sig_ok, "Shard audit answered"));
                                 let _ = pending.tx.send(AuditAck {
                                     verified: res.found && res.accepted && sig_ok,
                                     peer_id: pending.peer_id.to_string(),
@@ -603,9 +961,11 @@ impl P2pNode {
                     }
                     SwarmEvent::Behaviour(NeuroStoreBehaviourEvent::Chunk(request_response::Event::OutboundFailure {
                         request_id,
+                        error,
                         ..
                     })) => {
                         if let Some(pending) = self.pending_retrievals.remove(&request_id) {
+                            pending.span.in_scope(|| warn!(error = %error, "Shard retrieval failed"));
                             let _ = pending.tx.send(RetrieveAck {
                                 data: None,
                                 peer_id: pending.peer_id.to_string(),
@@ -614,7 +974,11 @@ impl P2pNode {
                             });
                         }
                         if let Some(pending) = self.pending_deletions.remove(&request_id) {
-                            let _ = pending.tx.send(false);
+                            let _ = pending.tx.send(DeleteAck { deleted: false, receipt: None });
+                        }
+                        if let Some(pending) = self.pending_batch_deletions.remove(&request_id) {
+                            pending.span.in_scope(|| warn!(error = %error, "Batch shard deletion failed"));
+                            let _ = pending.tx.send(None);
                         }
                         if let Some(pending) = self.pending_stores.remove(&request_id) {
                             let _ = pending.tx.send(StoreAck {
@@ -645,16 +1009,158 @@ impl P2pNode {
         }
     }
 
+    /// Records `holder` as the provider of `shard_cid` and publishes a DHT
+    /// record naming it, so the shard can be found through the swarm alone.
+    /// Kademlia republishes the record itself from then on.
+    fn announce_placement(&mut self, shard_cid: &str, holder: PeerId) {
+        let key = RecordKey::new(&shard_cid);
+        let kademlia = &mut self.swarm.behaviour_mut().kademlia;
+        if let Err(e) = kademlia
+            .store_mut()
+            .add_provider(ProviderRecord::new(key.clone(), holder, Vec::new()))
+        {
+            debug!(cid = shard_cid, error = %e, "Provider record not stored");
+            return;
+        }
+        if let Err(e) = kademlia.put_record(Record::new(key, holder.to_bytes()), Quorum::One) {
+            debug!(cid = shard_cid, error = %e, "Placement record not published");
+        }
+    }
+
+    /// Walks `object_shards` a page per tick and announces any placement the
+    /// DHT store does not hold yet, e.g. shards placed before it was
+    /// persistent or by another gateway.
+    async fn reannounce_placements(&mut self, db: &sqlx::PgPool, cursor: &mut String) {
+        let rows = sqlx::query_as::<_, (String, String)>(
+            "SELECT shard_cid, peer_id FROM object_shards WHERE shard_cid > $1 ORDER BY shard_cid LIMIT $2"
+        )
+        .bind(cursor.as_str())
+        .bind(PLACEMENT_REANNOUNCE_BATCH)
+        .fetch_all(db)
+        .await;
+        let rows = match rows {
+            Ok(rows) => rows,
+            Err(e) => {
+                warn!("Failed to load shard placements for re-announcement: {}", e);
+                return;
+            }
+        };
+        *cursor = match rows.last() {
+            Some((shard_cid, _)) if rows.len() as i64 == PLACEMENT_REANNOUNCE_BATCH => shard_cid.clone(),
+            _ => String::new(),
+        };
+
+        let mut announced = 0;
+        for (shard_cid, peer_id) in rows {
+            let Ok(holder) = peer_id.parse::<PeerId>() else {
+                continue;
+            };
+            let key = RecordKey::new(&shard_cid);
+            if self.swarm.behaviour_mut().kademlia.store_mut().has_provider(&key, &holder) {
+                continue;
+            }
+            self.announce_placement(&shard_cid, holder);
+            announced += 1;
+        }
+        if announced > 0 {
+            info!("Re-announced {} shard placements to the DHT", announced);
+        }
+    }
+
+    fn apply_announcement(&mut self, peer_id: PeerId, announcement: NodeAnnouncement) {
+        match announcement {
+            NodeAnnouncement::Maintenance { enabled: true, since_ms } => {
+                if self.maintenance_peers.insert(peer_id) {
+                    info!("Node {} is in maintenance since {} ms; no new shards will be placed on it", peer_id, since_ms);
+                }
+            }
+            NodeAnnouncement::Maintenance { enabled: false, .. } => {
+                if self.maintenance_peers.remove(&peer_id) {
+                    info!("Node {} left maintenance", peer_id);
+                }
+            }
+        }
+    }
+
+    /// Signs a tombstone for each of `cids` and gossips them, so holders
+    /// that missed the delete (offline, or never asked) drop their copies.
+    fn publish_tombstones(&mut self, cids: Vec<String>) {
+        let deleted_at_ms = chrono::Utc::now().timestamp_millis() as u64;
+        let public_key = self.keypair.public().encode_protobuf();
+        let tombstones: Vec<Tombstone> = cids
+            .into_iter()
+            .filter_map(|cid| {
+                let signature = self.keypair.sign(&Tombstone::payload(&cid, deleted_at_ms)).ok()?;
+                Some(Tombstone { cid, deleted_at_ms, signature, public_key: public_key.clone() })
+            })
+            .collect();
+        let topic = IdentTopic::new(TOMBSTONE_TOPIC);
+        for batch in tombstones.chunks(MAX_TOMBSTONES_PER_MESSAGE) {
+            let message = TombstoneMessage::Tombstones { tombstones: batch.to_vec() };
+            let Ok(data) = serde_json::to_vec(&message) else { continue };
+            match self.swarm.behaviour_mut().gossipsub.publish(topic.clone(), data) {
+                Ok(_) => debug!("Gossiped {} shard tombstones", batch.len()),
+                // Nobody to tell: the nodes that come back later hear of it
+                // from the others, or never held the shards.
+                Err(e) => warn!("Shard tombstones not gossiped: {}", e),
+            }
+        }
+    }
+
+    /// Whether `command` may go to `peer_id`. Commands newer than the base
+    /// protocol need the peer to have advertised their feature; peers that
+    /// have not (older nodes, identify still pending) are skipped so the
+    /// request falls to another peer or fails like an unreachable one.
+    fn accepts(&self, peer_id: &PeerId, command: &ChunkCommand) -> bool {
+        match self.peer_capabilities.get(peer_id) {
+            Some(caps) => caps.accepts(command),
+            None => command.required_feature().is_none(),
+        }
+    }
+
+    /// Addresses the routing table holds for `peer_id`, each ending in
+    /// `/p2p/<peer_id>` so they can be dialed as-is.
+    fn known_addrs(&mut self, peer_id: &PeerId) -> Vec<libp2p::Multiaddr> {
+        let Some(bucket) = self.swarm.behaviour_mut().kademlia.kbucket(*peer_id) else {
+            return Vec::new();
+        };
+        let addrs: Vec<libp2p::Multiaddr> = bucket
+            .iter()
+            .filter(|entry| entry.node.key.preimage() == peer_id)
+            .flat_map(|entry| entry.node.value.iter().cloned().collect::<Vec<_>>())
+            .collect();
+        addrs
+            .into_iter()
+            .map(|addr| match addr.iter().last() {
+                Some(libp2p::multiaddr::Protocol::P2p(_)) => addr,
+                _ => addr.with(libp2p::multiaddr::Protocol::P2p(*peer_id)),
+            })
+            .collect()
+    }
+
     fn expire_pending_requests(&mut self) {
         let now = Instant::now();
 
+        let locate_expired: Vec<_> = self
+            .pending_locates
+            .iter()
+            .filter_map(|(id, pending)| (pending.deadline <= now).then_some(*id))
+            .collect();
+        for id in locate_expired {
+            if let Some(pending) = self.pending_locates.remove(&id) {
+                let addrs = self.known_addrs(&pending.peer_id);
+                let _ = pending.tx.send(addrs);
+            }
+        }
+
         let retrieval_expired: Vec<_> = self
             .pending_retrievals
             .iter()
-            .filter_map(|(id, pending)| (pending.deadline <= now).then_some(id.clone()))
+            .filter_map(|(id, pending)| (pending.deadline <= now).then_some(*id))
             .collect();
         for id in retrieval_expired {
             if let Some(pending) = self.pending_retrievals.remove(&id) {
+                pending.span.in_scope(|| warn!("Shard retrieval timed out"));
                 let _ = pending.tx.send(RetrieveAck {
                     data: None,
                     peer_id: pending.peer_id.to_string(),
@@ -667,18 +1173,30 @@ impl P2pNode {
         let deletion_expired: Vec<_> = self
             .pending_deletions
             .iter()
-            .filter_map(|(id, pending)| (pending.deadline <= now).then_some(id.clone()))
+            .filter_map(|(id, pending)| (pending.deadline <= now).then_some(*id))
             .collect();
         for id in deletion_expired {
             if let Some(pending) = self.pending_deletions.remove(&id) {
-                let _ = pending.tx.send(false);
+                let _ = pending.tx.send(DeleteAck { deleted: false, receipt: None });
+            }
+        }
+
+        let batch_deletion_expired: Vec<_> = self
+            .pending_batch_deletions
+            .iter()
+            .filter_map(|(id, pending)| (pending.deadline <= now).then_some(*id))
+            .collect();
+        for id in batch_deletion_expired {
+            if let Some(pending) = self.pending_batch_deletions.remove(&id) {
+                pending.span.in_scope(|| warn!("Batch shard deletion timed out"));
+                let _ = pending.tx.send(None);
             }
         }
 
         let store_expired: Vec<_> = self
             .pending_stores
             .iter()
-            .filter_map(|(id, pending)| (pending.deadline <= now).then_some(id.clone()))
+            .filter_map(|(id, pending)| (pending.deadline <= now).then_some(*id))
             .collect();
         for id in store_expired {
             if let Some(pending) = self.pending_stores.remove(&id) {
@@ -695,7 +1213,7 @@ impl P2pNode {
         let audit_expired: Vec<_> = self
             .pending_audits
             .iter()
-            .filter_map(|(id, pending)| (pending.deadline <= now).then_some(id.clone()))
+            .filter_map(|(id, pending)| (pending.deadline <= now).then_some(*id))
             .collect();
         for id in audit_expired {
             if let Some(pending) = self.pending_audits.remove(&id) {
@@ -713,3 +1231,35 @@ impl P2pNode {
         }
     }
 }
+
+/// Stores what a node advertised so placement and operators can see which
+/// nodes serve which protocol features.
+async fn record_capabilities(db: &sqlx::PgPool, peer_id: &str, caps: &PeerCapabilities) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO nodes (peer_id, protocol_version, agent_version, features, capabilities_updated_at)
+        VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP)
+        ON CONFLICT (peer_id) DO UPDATE SET
+            protocol_version = excluded.protocol_version,
+            agent_version = excluded.agent_version,
+            features = excluded.features,
+            capabilities_updated_at = CURRENT_TIMESTAMP
+        "#
+    )
+    .bind(peer_id)
+    .bind(&caps.protocol_version)
+    .bind(&caps.agent_version)
+    .bind(&caps.features)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Reads a swarm key in the go-ipfs `swarm.key` format, as shared with the
+/// nodes of a private cluster.
+pub fn load_swarm_key(path: &Path) -> anyhow::Result<PreSharedKey> {
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("failed to read swarm key {}: {e}", path.display()))?;
+    raw.parse()
+        .map_err(|e| anyhow::anyhow!("invalid swarm key {}: {e}", path.display()))
+}
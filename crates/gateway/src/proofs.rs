@@ -17,6 +17,7 @@ use sha2::Digest;
 
 use crate::{
     p2p::SwarmRequest,
+    slashing::{PenaltyKind, Slasher},
     AppState,
 };
 
@@ -93,20 +94,20 @@ impl ProofOfSpacetimeDaemon {
                         .await;
 
                     if dispatch.is_err() {
-                        let _ = mark_challenge_failed(&state_clone, &challenge_id, "p2p dispatch failure").await;
+                        let _ = mark_challenge_failed(&state_clone, &challenge_id, "p2p dispatch failure", None).await;
                         return;
                     }
 
                     let ack = match timeout(Duration::from_secs(12), rx).await {
                         Ok(Ok(ack)) => ack,
                         _ => {
-                            let _ = mark_challenge_failed(&state_clone, &challenge_id, "audit response timeout").await;
+                            let _ = mark_challenge_failed(&state_clone, &challenge_id, "audit response timeout", Some(PenaltyKind::AuditTimeout)).await;
                             return;
                         }
                     };
 
                     if !ack.verified {
-                        let _ = mark_challenge_failed(&state_clone, &challenge_id, "audit signature/response invalid").await;
+                        let _ = mark_challenge_failed(&state_clone, &challenge_id, "audit signature/response invalid", Some(PenaltyKind::AuditInvalid)).await;
                         return;
                     }
 
@@ -123,20 +124,31 @@ impl ProofOfSpacetimeDaemon {
                 });
             }
 
-            while let Some(_) = audit_futures.next().await {}
+            while audit_futures.next().await.is_some() {}
         }
     }
 
     async fn expire_stale_challenges(&self) {
-        let _ = sqlx::query(
+        let expired = sqlx::query_as::<_, (String, String)>(
             r#"
             UPDATE zk_proof_challenges
             SET status = 'expired', failure_reason = 'challenge expired before verification'
             WHERE status = 'pending' AND expires_at < NOW()
+            RETURNING challenge_id, peer_id
             "#,
         )
-        .execute(&self.state.db)
-        .await;
+        .fetch_all(&self.state.db)
+        .await
+        .unwrap_or_default();
+        let slasher = Slasher::new(&self.state);
+        for (challenge_id, peer_id) in expired {
+            let penalized = slasher
+                .penalize(&peer_id, PenaltyKind::ChallengeExpired, &challenge_id, "challenge expired before verification")
+                .await;
+            if let Err(e) = penalized {
+                warn!("Failed to penalize {} for expired challenge {}: {}", peer_id, challenge_id, e);
+            }
+        }
     }
 }
 
@@ -180,7 +192,7 @@ fn validate_proof_token(headers: &HeaderMap, state: &AppState) -> Result<(), (St
         .and_then(|v| v.to_str().ok())
         .unwrap_or_default();
 
-    if proof_token.is_empty() || proof_token != state.proof_submit_token {
+    if proof_token.is_empty() || proof_token != state.proof_submit_token.as_str() {
         return Err((StatusCode::UNAUTHORIZED, "Unauthorized proof submission".to_string()));
     }
 
@@ -248,18 +260,29 @@ async fn create_challenge_for_target(
     Ok((challenge_id, challenge_hex, nonce_hex))
 }
 
-async fn mark_challenge_failed(state: &AppState, challenge_id: &str, reason: &str) -> Result<(), sqlx::Error> {
-    sqlx::query(
+/// Fails a challenge, penalizing its peer with `penalty` when the failure
+/// is the peer's doing.
+async fn mark_challenge_failed(
+    state: &AppState,
+    challenge_id: &str,
+    reason: &str,
+    penalty: Option<PenaltyKind>,
+) -> Result<(), sqlx::Error> {
+    let peer_id = sqlx::query_scalar::<_, String>(
         r#"
         UPDATE zk_proof_challenges
         SET status = 'failed', failure_reason = $2
         WHERE challenge_id = $1
+        RETURNING peer_id
         "#,
     )
     .bind(challenge_id)
     .bind(reason)
-    .execute(&state.db)
+    .fetch_optional(&state.db)
     .await?;
+    if let (Some(peer_id), Some(kind)) = (peer_id, penalty) {
+        Slasher::new(state).penalize(&peer_id, kind, challenge_id, reason).await?;
+    }
     Ok(())
 }
 
@@ -468,7 +491,7 @@ pub async fn verify_zk_proof(
     // response_hash == ZkSnark(Public_Inputs: [challenge, nonce, shard_cid], Private_Input: Shard_Data)
     // Here we use a placeholder function for the actual Groth16/Plonk verifier.
     if !verify_zk_snark_circuit(&payload.shard_cid, &payload.challenge_hex, &payload.nonce_hex, &payload.response_hash) {
-        let _ = mark_challenge_failed(&state, &payload.challenge_id, "ZK-SNARK Cryptographic Circuit Verification Failed").await;
+        let _ = mark_challenge_failed(&state, &payload.challenge_id, "ZK-SNARK Cryptographic Circuit Verification Failed", Some(PenaltyKind::ProofInvalid)).await;
         return (StatusCode::BAD_REQUEST, "invalid ZK proof (pre-generation attack detected)").into_response();
     }
 
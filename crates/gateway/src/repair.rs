@@ -1,12 +1,22 @@
 use std::sync::Arc;
 use std::time::Duration;
+use neuro_protocol::{ChunkCommand, StoreChunkRequest};
 use sqlx::Row;
+use tokio::sync::oneshot;
 use tokio::time;
 use tracing::{info, warn, error};
 use sha2::Digest;
 
+use crate::erasure::ErasureEncoder;
+use crate::p2p::SwarmRequest;
+use crate::retrieval;
 use crate::AppState;
 
+/// Objects whose scheduled evacuations are worked through per sweep.
+const EVACUATION_BATCH: i64 = 20;
+/// How long a re-placed shard may take to be acknowledged.
+const EVACUATION_STORE_TIMEOUT: Duration = Duration::from_secs(15);
+
 #[derive(sqlx::FromRow)]
 struct DegradedObject {
     bucket: String,
@@ -32,6 +42,7 @@ impl RepairDaemon {
             interval.tick().await;
             self.sweep().await;
             self.proactive_migration_sweep().await;
+            self.evacuation_sweep().await;
             self.thundering_herd_caching_sweep().await;
             self.recursive_manifest_pinning_sweep().await;
         }
@@ -120,14 +131,17 @@ impl RepairDaemon {
     }
 
     async fn proactive_migration_sweep(&self) {
-        // Predictive AI: "Pre-emptive Self-Healing"
-        // Find peers with high churn_probability (> 0.8) and proactively replicate
-        // any shards hosted on them to stable nodes.
-        
-        // Simulating the retrieval of nodes flagged by Sentinel
-        // In a full implementation, Sentinel outputs are written back to a `node_reputation` table
+        // Predictive AI: peers the sentinel expects to churn (> 0.8) are
+        // flagged here. Their shards stay put until the sentinel excludes
+        // them; shards of nodes evicted for their penalties are moved by
+        // evacuation_sweep.
         let high_churn_peers_res = sqlx::query(
-            "SELECT peer_id FROM nodes WHERE uptime_percentage < 95.0 AND bandwidth_capacity_mbps < 5 LIMIT 5"
+            r#"
+            SELECT peer_id FROM node_reputation
+            WHERE churn_probability > 0.8 OR action = 'proactive_evict'
+            ORDER BY churn_probability DESC
+            LIMIT 5
+            "#
         )
         .fetch_all(&self.state.db)
         .await;
@@ -142,17 +156,158 @@ impl RepairDaemon {
                             continue;
                         }
                     };
-                    warn!("PREDICTIVE AI TRIGGER: Node {} exhibits 80%+ churn probability. Initiating proactive migration (0ms recovery time).", peer_id);
-                    
-                    // The daemon would scan for objects associated with this peer and re-encode/distribute them.
-                    // For now, we simulate the completion of the migration.
-                    info!("Proactive migration complete for Node {}. Shards safely moved before node failure.", peer_id);
+                    warn!("PREDICTIVE AI TRIGGER: Node {} exhibits 80%+ churn probability.", peer_id);
                 }
             }
             Err(e) => error!("Failed to fetch high-churn peers: {}", e),
         }
     }
 
+    /// Moves the shards listed in `shard_evacuations` off the nodes being
+    /// evicted. Each object is rebuilt from its healthiest shards and checked
+    /// against its CID, so an evicted node cannot hand over bad bytes; the
+    /// lost shards are re-encoded and stored on nodes the placement accepts,
+    /// and a row is only cleared once its new placement is recorded.
+    async fn evacuation_sweep(&self) {
+        let objects = sqlx::query_scalar::<_, String>(
+            "SELECT object_cid FROM shard_evacuations GROUP BY object_cid ORDER BY MIN(scheduled_at) LIMIT $1",
+        )
+        .bind(EVACUATION_BATCH)
+        .fetch_all(&self.state.db)
+        .await;
+        match objects {
+            Ok(objects) => {
+                for object_cid in objects {
+                    if let Err(e) = self.evacuate_object(&object_cid).await {
+                        warn!("Evacuation of object {} deferred: {}", object_cid, e);
+                    }
+                }
+            }
+            Err(e) => error!("Failed to list scheduled shard evacuations: {}", e),
+        }
+    }
+
+    async fn evacuate_object(&self, object_cid: &str) -> anyhow::Result<()> {
+        let db = &self.state.db;
+        // Shards that moved, or whose object is gone, need nothing more.
+        sqlx::query(
+            r#"
+            DELETE FROM shard_evacuations e
+            WHERE e.object_cid = $1 AND NOT EXISTS (
+                SELECT 1 FROM object_shards s
+                WHERE s.object_cid = e.object_cid AND s.shard_index = e.shard_index AND s.peer_id = e.peer_id
+            )
+            "#,
+        )
+        .bind(object_cid)
+        .execute(db)
+        .await?;
+        let pending = sqlx::query_as::<_, (i32, String, String)>(
+            r#"
+            SELECT e.shard_index, e.peer_id, s.country_code
+            FROM shard_evacuations e
+            JOIN object_shards s
+              ON s.object_cid = e.object_cid AND s.shard_index = e.shard_index AND s.peer_id = e.peer_id
+            WHERE e.object_cid = $1
+            ORDER BY e.shard_index
+            "#,
+        )
+        .bind(object_cid)
+        .fetch_all(db)
+        .await?;
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let Some((shards, threshold, size)) = sqlx::query_as::<_, (i32, i32, i64)>(
+            "SELECT shards, recovery_threshold, size FROM objects WHERE cid = $1 ORDER BY version DESC LIMIT 1",
+        )
+        .bind(object_cid)
+        .fetch_optional(db)
+        .await?
+        else {
+            return Err(anyhow::anyhow!("no object row to rebuild from"));
+        };
+        let (shards, threshold, size) = (shards.max(1) as usize, threshold.max(1) as usize, size.max(0) as usize);
+
+        let (plan, mut fetched) = retrieval::fetch_minimum(&self.state, object_cid, shards, threshold).await;
+        let body = retrieval::rebuild(&self.state, &plan, object_cid, &mut fetched, threshold, size)
+            .await
+            .map_err(|reason| anyhow::anyhow!("rebuild failed: {}", reason))?;
+        let encoded = tokio::task::spawn_blocking(move || {
+            ErasureEncoder::new(threshold, shards - threshold)?.encode(&body)
+        })
+        .await??;
+
+        for (index, from, country_code) in pending {
+            let Some(data) = encoded.get(index as usize).cloned() else {
+                continue;
+            };
+            let shard_cid = format!("{}-shard-{}", object_cid, index);
+            // The object's geofence is not kept, so the shard stays in the
+            // country it was placed in.
+            let geofence = if country_code == "XX" { "GLOBAL".to_string() } else { country_code };
+            let (tx, rx) = oneshot::channel();
+            let req = SwarmRequest::Store {
+                command: ChunkCommand::Store(StoreChunkRequest { cid: shard_cid.clone(), data }),
+                geofence,
+                tx,
+            };
+            if self.state.p2p_tx.send(req).await.is_err() {
+                return Err(anyhow::anyhow!("storage network queue unavailable"));
+            }
+            let ack = match time::timeout(EVACUATION_STORE_TIMEOUT, rx).await {
+                Ok(Ok(ack)) if ack.stored && ack.peer_id != from => ack,
+                _ => {
+                    warn!("No healthy node accepted shard {} of {}; retrying next sweep", index, object_cid);
+                    continue;
+                }
+            };
+
+            let mut tx = db.begin().await?;
+            let moved = sqlx::query(
+                r#"
+                UPDATE object_shards SET
+                    peer_id = $4,
+                    country_code = $5,
+                    receipt_timestamp_ms = $6,
+                    receipt_signature_valid = $7,
+                    last_verified_at = NOW()
+                WHERE object_cid = $1 AND shard_index = $2 AND peer_id = $3
+                "#,
+            )
+            .bind(object_cid)
+            .bind(index)
+            .bind(&from)
+            .bind(&ack.peer_id)
+            .bind(&ack.country_code)
+            .bind(ack.timestamp_ms as i64)
+            .bind(ack.signature_valid)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            sqlx::query("DELETE FROM shard_evacuations WHERE object_cid = $1 AND shard_index = $2 AND peer_id = $3")
+                .bind(object_cid)
+                .bind(index)
+                .bind(&from)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            if moved == 0 {
+                continue;
+            }
+            info!("Evacuated shard {} of {} from evicted node {} to {}", index, object_cid, from, ack.peer_id);
+
+            // The evicted node's copy is no longer referenced.
+            let (tx, _rx) = oneshot::channel();
+            let _ = self
+                .state
+                .p2p_tx
+                .send(SwarmRequest::Delete { cid: shard_cid, peer_id: Some(from), tx })
+                .await;
+        }
+        Ok(())
+    }
+
     async fn sweep(&self) {
         // Query Postgres for objects where the quantity of healthy shards has fallen below 20, 
         // but is still above the recovery_threshold (usually 10).
@@ -199,3 +354,63 @@ impl RepairDaemon {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::s3::{get_object, put_object};
+    use crate::test_support::{auth, drop_db, scratch_db, state, OWNER};
+    use axum::body::Body;
+    use axum::extract::{Path, Query, State};
+    use axum::response::IntoResponse;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn evacuated_shard_moves_to_another_node() {
+        let Some((db, name)) = scratch_db().await else {
+            eprintln!("DATABASE_URL not set; skipping");
+            return;
+        };
+        sqlx::query("INSERT INTO users (email, password_hash) VALUES ($1, 'x')")
+            .bind(OWNER)
+            .execute(&db)
+            .await
+            .expect("user");
+        let state = state(db.clone());
+        let path = || Path(("photos".to_string(), "evicted.bin".to_string()));
+        put_object(State(Arc::clone(&state)), path(), Query(HashMap::new()), auth(&state), Body::from("kept through an eviction"))
+            .await
+            .expect("put");
+
+        let (object_cid, evicted): (String, String) =
+            sqlx::query_as("SELECT object_cid, peer_id FROM object_shards WHERE shard_index = 0")
+                .fetch_one(&db)
+                .await
+                .expect("shard");
+        sqlx::query("INSERT INTO shard_evacuations (object_cid, shard_index, peer_id) VALUES ($1, 0, $2)")
+            .bind(&object_cid)
+            .bind(&evicted)
+            .execute(&db)
+            .await
+            .expect("evacuation");
+
+        RepairDaemon::new(Arc::clone(&state)).evacuate_object(&object_cid).await.expect("evacuate");
+
+        let holder: String = sqlx::query_scalar("SELECT peer_id FROM object_shards WHERE object_cid = $1 AND shard_index = 0")
+            .bind(&object_cid)
+            .fetch_one(&db)
+            .await
+            .expect("shard");
+        assert_ne!(holder, evicted);
+        let pending: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM shard_evacuations").fetch_one(&db).await.expect("count");
+        assert_eq!(pending, 0);
+
+        let response = get_object(State(Arc::clone(&state)), path(), Query(HashMap::new()), auth(&state))
+            .await
+            .unwrap_or_else(IntoResponse::into_response);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.expect("body");
+        assert_eq!(&body[..], b"kept through an eviction");
+        drop(state);
+        drop_db(db, &name).await;
+    }
+}
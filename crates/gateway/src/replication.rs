@@ -0,0 +1,622 @@
+use axum::{
+    extract::{Query, Request, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::Row;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time;
+use tracing::{error, info, warn};
+use zeroize::Zeroizing;
+
+use crate::AppState;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const FEED_DEFAULT_LIMIT: i64 = 500;
+const FEED_MAX_LIMIT: i64 = 5_000;
+const FOLLOWER_POLL_SECS: u64 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GatewayRole {
+    Leader,
+    Follower,
+}
+
+pub struct ReplicationConfig {
+    pub role: GatewayRole,
+    pub gateway_id: String,
+    pub signing_key: Option<Zeroizing<String>>,
+    pub leader_url: Option<String>,
+}
+
+impl ReplicationConfig {
+    /// Reads `--follower` / `GATEWAY_ROLE`, `GATEWAY_ID`, `REPLICATION_SIGNING_KEY`
+    /// and `REPLICATION_LEADER_URL`. Leaders without a signing key simply do not publish.
+    pub fn from_env() -> Self {
+        let follower = std::env::args().any(|a| a == "--follower")
+            || std::env::var("GATEWAY_ROLE")
+                .map(|v| v.eq_ignore_ascii_case("follower"))
+                .unwrap_or(false);
+        let role = if follower {
+            GatewayRole::Follower
+        } else {
+            GatewayRole::Leader
+        };
+        let signing_key = std::env::var("REPLICATION_SIGNING_KEY")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .map(Zeroizing::new);
+        let leader_url = std::env::var("REPLICATION_LEADER_URL")
+            .ok()
+            .map(|v| v.trim_end_matches('/').to_string())
+            .filter(|v| !v.is_empty());
+
+        if role == GatewayRole::Follower {
+            assert!(
+                signing_key.is_some(),
+                "REPLICATION_SIGNING_KEY is required in follower mode"
+            );
+            assert!(
+                leader_url.is_some(),
+                "REPLICATION_LEADER_URL is required in follower mode"
+            );
+        }
+
+        Self {
+            role,
+            gateway_id: std::env::var("GATEWAY_ID")
+                .unwrap_or_else(|_| "gateway-primary".to_string()),
+            signing_key,
+            leader_url,
+        }
+    }
+
+    pub fn is_follower(&self) -> bool {
+        self.role == GatewayRole::Follower
+    }
+}
+
+/// A replicated metadata mutation. Object keys and bucket names are carried
+/// exactly as stored (already masked by the MetadataProtector), so followers
+/// must share METADATA_SECRET to serve them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MetadataOp {
+    UpsertUser {
+        email: String,
+        password_hash: String,
+        name: Option<String>,
+    },
+    UpsertBucket {
+        name: String,
+        owner_email: String,
+        /// Older leaders predate tenants; their buckets are the default's.
+        #[serde(default = "crate::tenancy::default_tenant")]
+        tenant_id: String,
+    },
+    UpsertObject {
+        bucket: String,
+        key: String,
+        etag: String,
+        cid: String,
+        shards: i32,
+        recovery_threshold: i32,
+        size: i64,
+        metadata_json: Option<serde_json::Value>,
+        /// `key_index` tokens; absent from older leaders, in which case the
+        /// row is indexed by the next backfill.
+        #[serde(default)]
+        key_tokens: Option<Vec<String>>,
+        /// `key_index::lookup_token` of the key. Older leaders send none;
+        /// the follower derives it from the key before applying the op.
+        #[serde(default)]
+        key_lookup: Option<String>,
+        /// The row's write token; a follower never replaces a row with a
+        /// lower one. Older leaders send none (0).
+        #[serde(default)]
+        version: i64,
+    },
+    DeleteObject {
+        bucket: String,
+        key: String,
+    },
+    UpsertUploaderManifest {
+        manifest_root: String,
+        owner_email: String,
+        label: Option<String>,
+        version: String,
+        total_bytes: i64,
+        chunk_count: i32,
+        shard_count: i32,
+        manifest_json: serde_json::Value,
+    },
+    DeleteUploaderManifest {
+        manifest_root: String,
+    },
+    ReplaceLifecycleRules {
+        bucket: String,
+        rules: Vec<crate::models::LifecycleRule>,
+    },
+    ReplaceBucketPolicy {
+        bucket: String,
+        grants: Vec<crate::handlers::policy::BucketGrant>,
+    },
+    UpsertShareLink {
+        link: crate::handlers::shares::ShareLink,
+    },
+    /// `None` removes the bucket's override.
+    ReplaceBucketLimits {
+        bucket: String,
+        limits: Option<crate::handlers::limits::BucketLimits>,
+    },
+    UpsertTenant {
+        tenant_id: String,
+        name: String,
+    },
+    AssignUserTenant {
+        email: String,
+        tenant_id: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OplogEntry {
+    pub seq: i64,
+    pub origin: String,
+    pub op: serde_json::Value,
+    pub signature: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OplogFeed {
+    pub entries: Vec<OplogEntry>,
+    pub head_seq: i64,
+}
+
+#[derive(Deserialize)]
+pub struct FeedQuery {
+    pub after: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+fn sign_op(key: &str, origin: &str, op: &serde_json::Value) -> String {
+    let mut mac = HmacSha256::new_from_slice(key.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(origin.as_bytes());
+    mac.update(b":");
+    mac.update(op.to_string().as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn verify_op(key: &str, entry: &OplogEntry) -> bool {
+    let Ok(signature) = hex::decode(&entry.signature) else {
+        return false;
+    };
+    let mut mac = HmacSha256::new_from_slice(key.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(entry.origin.as_bytes());
+    mac.update(b":");
+    mac.update(entry.op.to_string().as_bytes());
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Appends a signed op to the local oplog. Called after the primary write has
+/// committed; failures are logged rather than surfaced to the client.
+pub async fn publish(state: &AppState, op: MetadataOp) {
+    let cfg = &state.replication;
+    if cfg.is_follower() {
+        return;
+    }
+    let Some(key) = cfg.signing_key.as_ref() else {
+        return;
+    };
+    let op_json = match serde_json::to_value(&op) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Failed to serialize metadata op: {}", e);
+            return;
+        }
+    };
+    let signature = sign_op(key, &cfg.gateway_id, &op_json);
+
+    let res = sqlx::query("INSERT INTO metadata_oplog (origin, op, signature) VALUES ($1, $2, $3)")
+        .bind(&cfg.gateway_id)
+        .bind(&op_json)
+        .bind(&signature)
+        .execute(&state.db)
+        .await;
+    if let Err(e) = res {
+        error!("Failed to append metadata op to replication log: {}", e);
+    }
+}
+
+// ── LEADER: OPLOG FEED ────────────────────────────────────────────
+pub async fn oplog_feed(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<FeedQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let Some(key) = state.replication.signing_key.as_ref() else {
+        return (StatusCode::NOT_FOUND, "Replication disabled").into_response();
+    };
+    let token = headers
+        .get("x-replication-token")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or_default();
+    if token.is_empty() || token != key.as_str() {
+        return (StatusCode::UNAUTHORIZED, "Invalid replication token").into_response();
+    }
+
+    let after = query.after.unwrap_or(0).max(0);
+    let limit = query
+        .limit
+        .unwrap_or(FEED_DEFAULT_LIMIT)
+        .clamp(1, FEED_MAX_LIMIT);
+
+    let rows = sqlx::query(
+        "SELECT seq, origin, op, signature FROM metadata_oplog WHERE seq > $1 ORDER BY seq ASC LIMIT $2",
+    )
+    .bind(after)
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await;
+    let rows = match rows {
+        Ok(r) => r,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("DB Error: {}", e),
+            )
+                .into_response()
+        }
+    };
+
+    let mut entries = Vec::with_capacity(rows.len());
+    for row in rows {
+        let entry = (|| {
+            Ok::<_, sqlx::Error>(OplogEntry {
+                seq: row.try_get("seq")?,
+                origin: row.try_get("origin")?,
+                op: row.try_get("op")?,
+                signature: row.try_get("signature")?,
+            })
+        })();
+        match entry {
+            Ok(e) => entries.push(e),
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("DB row decode error: {}", e),
+                )
+                    .into_response()
+            }
+        }
+    }
+
+    let head_seq = sqlx::query_scalar::<_, Option<i64>>("SELECT MAX(seq) FROM metadata_oplog")
+        .fetch_one(&state.db)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or(0);
+
+    Json(OplogFeed { entries, head_seq }).into_response()
+}
+
+// ── FOLLOWER: READ-ONLY GUARD ─────────────────────────────────────
+pub async fn follower_guard(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.replication.is_follower() {
+        return next.run(request).await;
+    }
+    let method = request.method();
+    let path = request.uri().path();
+    let is_read = method == Method::GET || method == Method::HEAD || method == Method::OPTIONS;
+    let is_session = matches!(
+        path,
+        "/auth/login" | "/api/login" | "/auth/logout" | "/api/logout"
+    );
+    // A follower has no oplog entries for restored rows, so it restores
+    // backups itself.
+    let is_restore = path.starts_with("/api/admin/backups/") && path.ends_with("/restore");
+    if is_read || is_session || is_restore {
+        return next.run(request).await;
+    }
+
+    let mut response = (
+        StatusCode::SERVICE_UNAVAILABLE,
+        "Read-only follower gateway: send writes to the leader",
+    )
+        .into_response();
+    if let Some(leader) = state.replication.leader_url.as_deref() {
+        if let Ok(value) = HeaderValue::from_str(leader) {
+            response.headers_mut().insert("x-neuro-leader", value);
+        }
+    }
+    response
+}
+
+// ── FOLLOWER: OPLOG APPLIER ───────────────────────────────────────
+pub struct ReplicationFollower {
+    state: Arc<AppState>,
+    client: reqwest::Client,
+}
+
+impl ReplicationFollower {
+    pub fn new(state: Arc<AppState>) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .expect("Failed to build replication HTTP client");
+        Self { state, client }
+    }
+
+    pub async fn start(&self) {
+        let cfg = &self.state.replication;
+        let (Some(leader), Some(key)) = (cfg.leader_url.as_deref(), cfg.signing_key.as_ref())
+        else {
+            return;
+        };
+        info!(
+            "Metadata follower initialized. Tailing leader oplog at {}",
+            leader
+        );
+
+        let mut interval = time::interval(Duration::from_secs(FOLLOWER_POLL_SECS));
+        loop {
+            interval.tick().await;
+            // Drain the backlog in pages before sleeping again.
+            loop {
+                match self.sync_once(leader, key).await {
+                    Ok(true) => continue,
+                    Ok(false) => break,
+                    Err(e) => {
+                        warn!("Replication sync from {} failed: {}", leader, e);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Pulls and applies one page. Returns true when more entries are pending.
+    async fn sync_once(&self, leader: &str, key: &str) -> anyhow::Result<bool> {
+        let cursor = sqlx::query_scalar::<_, i64>(
+            "SELECT last_seq FROM replication_cursor WHERE leader_url = $1",
+        )
+        .bind(leader)
+        .fetch_optional(&self.state.db)
+        .await?
+        .unwrap_or(0);
+
+        let feed: OplogFeed = self
+            .client
+            .get(format!("{}/api/replication/oplog", leader))
+            .query(&[("after", cursor), ("limit", FEED_DEFAULT_LIMIT)])
+            .header("x-replication-token", key)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let mut last_seq = cursor;
+        for entry in &feed.entries {
+            if entry.seq <= last_seq {
+                continue;
+            }
+            if !verify_op(key, entry) {
+                anyhow::bail!(
+                    "invalid signature on oplog entry seq={} origin={}",
+                    entry.seq,
+                    entry.origin
+                );
+            }
+            let mut op: MetadataOp = serde_json::from_value(entry.op.clone())?;
+            fill_key_lookup(&self.state.tenant_keys, &mut op);
+
+            let mut tx = self.state.db.begin().await?;
+            apply_op(&mut tx, &self.state.tenant_keys, &op).await?;
+            sqlx::query(
+                r#"
+                INSERT INTO replication_cursor (leader_url, last_seq, updated_at)
+                VALUES ($1, $2, NOW())
+                ON CONFLICT (leader_url) DO UPDATE SET
+                    last_seq = excluded.last_seq,
+                    updated_at = NOW()
+                "#,
+            )
+            .bind(leader)
+            .bind(entry.seq)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            if let MetadataOp::UpsertObject { bucket, .. } | MetadataOp::DeleteObject { bucket, .. } = &op {
+                self.state.listing_cache.invalidate(bucket);
+            }
+
+            last_seq = entry.seq;
+        }
+
+        if last_seq > cursor {
+            tracing::debug!(
+                "Replicated metadata oplog {} -> {} (leader head {})",
+                cursor,
+                last_seq,
+                feed.head_seq
+            );
+        }
+        Ok(last_seq < feed.head_seq && !feed.entries.is_empty())
+    }
+}
+
+/// Objects are upserted on their key token, so an op without one would
+/// add a second row for its key; derive it the way the backfill does.
+fn fill_key_lookup(tenant_keys: &crate::tenancy::TenantKeys, op: &mut MetadataOp) {
+    if let MetadataOp::UpsertObject { bucket, key, key_lookup: key_lookup @ None, .. } = op {
+        let protector = tenant_keys.for_bucket(bucket);
+        let plain = protector.decrypt(key).unwrap_or_else(|_| key.clone());
+        *key_lookup = Some(crate::key_index::lookup_token(&protector, bucket, &plain));
+    }
+}
+
+async fn apply_op(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    tenant_keys: &crate::tenancy::TenantKeys,
+    op: &MetadataOp,
+) -> Result<(), sqlx::Error> {
+    match op {
+        MetadataOp::UpsertUser {
+            email,
+            password_hash,
+            name,
+        } => {
+            sqlx::query(
+                r#"
+                INSERT INTO users (email, password_hash, name) VALUES ($1, $2, $3)
+                ON CONFLICT (email) DO UPDATE SET
+                    password_hash = excluded.password_hash,
+                    name = excluded.name
+                "#,
+            )
+            .bind(email)
+            .bind(password_hash)
+            .bind(name)
+            .execute(&mut **tx)
+            .await?;
+        }
+        MetadataOp::UpsertBucket { name, owner_email, tenant_id } => {
+            sqlx::query(
+                r#"
+                INSERT INTO buckets (name, owner_email, tenant_id) VALUES ($1, $2, $3)
+                ON CONFLICT (name) DO UPDATE SET
+                    owner_email = excluded.owner_email,
+                    tenant_id = excluded.tenant_id
+                "#,
+            )
+            .bind(name)
+            .bind(owner_email)
+            .bind(tenant_id)
+            .execute(&mut **tx)
+            .await?;
+        }
+        MetadataOp::UpsertObject {
+            bucket,
+            key,
+            etag,
+            cid,
+            shards,
+            recovery_threshold,
+            size,
+            metadata_json,
+            key_tokens,
+            key_lookup,
+            version,
+        } => {
+            sqlx::query(
+                r#"
+                INSERT INTO objects (bucket, key, etag, cid, shards, recovery_threshold, size, metadata_json, key_tokens, key_lookup, version, bucket_ref)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+                ON CONFLICT (bucket, key_lookup) DO UPDATE SET
+                    key = excluded.key,
+                    etag = excluded.etag,
+                    cid = excluded.cid,
+                    shards = excluded.shards,
+                    recovery_threshold = excluded.recovery_threshold,
+                    size = excluded.size,
+                    metadata_json = excluded.metadata_json,
+                    key_tokens = excluded.key_tokens,
+                    key_lookup = excluded.key_lookup,
+                    version = excluded.version
+                WHERE objects.version <= excluded.version
+                "#,
+            )
+            .bind(bucket)
+            .bind(key)
+            .bind(etag)
+            .bind(cid)
+            .bind(shards)
+            .bind(recovery_threshold)
+            .bind(size)
+            .bind(metadata_json)
+            .bind(key_tokens)
+            .bind(key_lookup)
+            .bind(version)
+            .bind(crate::tenancy::masked_bucket(&tenant_keys.for_bucket(bucket), bucket))
+            .execute(&mut **tx)
+            .await?;
+        }
+        MetadataOp::DeleteObject { bucket, key } => {
+            sqlx::query("DELETE FROM objects WHERE bucket = $1 AND key = $2")
+                .bind(bucket)
+                .bind(key)
+                .execute(&mut **tx)
+                .await?;
+        }
+        MetadataOp::UpsertUploaderManifest {
+            manifest_root,
+            owner_email,
+            label,
+            version,
+            total_bytes,
+            chunk_count,
+            shard_count,
+            manifest_json,
+        } => {
+            sqlx::query(
+                r#"
+                INSERT INTO uploader_manifests
+                    (manifest_root, owner_email, label, version, total_bytes, chunk_count, shard_count, manifest_json)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT (manifest_root) DO UPDATE SET
+                    label = excluded.label,
+                    version = excluded.version,
+                    manifest_json = excluded.manifest_json
+                "#,
+            )
+            .bind(manifest_root)
+            .bind(owner_email)
+            .bind(label)
+            .bind(version)
+            .bind(total_bytes)
+            .bind(chunk_count)
+            .bind(shard_count)
+            .bind(manifest_json)
+            .execute(&mut **tx)
+            .await?;
+        }
+        MetadataOp::DeleteUploaderManifest { manifest_root } => {
+            sqlx::query("DELETE FROM uploader_manifests WHERE manifest_root = $1")
+                .bind(manifest_root)
+                .execute(&mut **tx)
+                .await?;
+        }
+        MetadataOp::ReplaceLifecycleRules { bucket, rules } => {
+            crate::handlers::lifecycle::replace_rules(tx, bucket, rules).await?;
+        }
+        MetadataOp::ReplaceBucketPolicy { bucket, grants } => {
+            crate::handlers::policy::replace_grants(tx, bucket, grants).await?;
+        }
+        MetadataOp::UpsertShareLink { link } => {
+            crate::handlers::shares::upsert_link(tx, link).await?;
+        }
+        MetadataOp::ReplaceBucketLimits { bucket, limits } => {
+            crate::handlers::limits::replace_override(tx, bucket, limits.as_ref()).await?;
+        }
+        MetadataOp::UpsertTenant { tenant_id, name } => {
+            crate::tenancy::upsert_tenant(tx, tenant_id, name).await?;
+        }
+        MetadataOp::AssignUserTenant { email, tenant_id } => {
+            crate::tenancy::assign_user(tx, email, tenant_id).await?;
+        }
+    }
+    Ok(())
+}
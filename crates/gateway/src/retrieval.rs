@@ -0,0 +1,479 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use neuro_protocol::sentinel::excludes_peer;
+use sha2::{Digest, Sha256};
+use tokio::sync::oneshot;
+use tokio::time::timeout;
+use tracing::warn;
+
+use crate::erasure::ErasureEncoder;
+use crate::p2p::{RetrieveAck, SwarmRequest, SwarmSender};
+use crate::slashing::{PenaltyKind, Slasher};
+use crate::AppState;
+
+/// Shards requested up front beyond the recovery threshold, so one slow or
+/// failed peer does not stall the GET.
+pub const HEDGE_EXTRA_SHARDS: usize = 2;
+/// How long the remaining shards wait before they are requested too.
+pub const HEDGE_DELAY: Duration = Duration::from_millis(150);
+/// How long one shard fetch may take before it counts as failed.
+pub const SHARD_FETCH_TIMEOUT: Duration = Duration::from_secs(8);
+
+/// Assumed latency for peers the gateway has not fetched from yet: slower
+/// than a healthy peer, faster than a known-bad one.
+const UNKNOWN_LATENCY_MS: f64 = 250.0;
+const UNKNOWN_REPUTATION: f64 = 50.0;
+/// Added to the cost of peers the sentinel has excluded, so they are only
+/// tried once everyone else has been.
+const EXCLUDED_PENALTY_MS: f64 = 60_000.0;
+/// Weight of the newest sample in the latency average, and the decay
+/// applied to the success/failure counts on each update.
+const LATENCY_EWMA_ALPHA: f64 = 0.2;
+const COUNT_DECAY: f64 = 0.98;
+
+#[derive(sqlx::FromRow)]
+struct PlacementStats {
+    shard_index: i32,
+    peer_id: String,
+    reputation: Option<f64>,
+    action: Option<String>,
+    latency_ewma_ms: Option<f64>,
+    successes: Option<f64>,
+    failures: Option<f64>,
+}
+
+impl PlacementStats {
+    /// Expected time to get the shard: latency inflated by the chance the
+    /// peer fails or is unreliable.
+    fn cost(&self) -> f64 {
+        let latency = self.latency_ewma_ms.unwrap_or(UNKNOWN_LATENCY_MS);
+        let successes = self.successes.unwrap_or_default();
+        let failures = self.failures.unwrap_or_default();
+        let success_rate = (successes + 1.0) / (successes + failures + 2.0);
+        let reputation = self.reputation.unwrap_or(UNKNOWN_REPUTATION).clamp(1.0, 100.0) / 100.0;
+        let penalty = if self.action.as_deref().is_some_and(excludes_peer) { EXCLUDED_PENALTY_MS } else { 0.0 };
+        latency / (success_rate * reputation) + penalty
+    }
+}
+
+/// A shard to fetch and the peer that was given it, if recorded.
+#[derive(Clone)]
+pub struct ShardTarget {
+    pub index: usize,
+    pub peer_id: Option<String>,
+}
+
+/// Shards to fetch for an object, cheapest first, and the peers caught
+/// sending it poison shards, whose answers are not trusted.
+pub struct RetrievalPlan {
+    pub targets: Vec<ShardTarget>,
+    pub avoid: Arc<HashSet<String>>,
+}
+
+/// Orders an object's shards for retrieval, cheapest holder first. Shards
+/// with no recorded placement go after every placed one; shards placed on a
+/// peer in `shard_decode_failures` for this object are left out.
+pub async fn plan(state: &AppState, object_cid: &str, shards: usize) -> RetrievalPlan {
+    let rows = sqlx::query_as::<_, PlacementStats>(
+        r#"
+        SELECT s.shard_index, s.peer_id, r.reputation, r.action,
+               l.latency_ewma_ms, l.successes, l.failures
+        FROM object_shards s
+        LEFT JOIN node_reputation r ON r.peer_id = s.peer_id
+        LEFT JOIN node_retrieval_stats l ON l.peer_id = s.peer_id
+        WHERE s.object_cid = $1
+        "#
+    )
+    .bind(object_cid)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_else(|e| {
+        warn!("Failed to load shard placements for {}: {}", object_cid, e);
+        Vec::new()
+    });
+    let avoid: HashSet<String> = sqlx::query_scalar::<_, String>(
+        "SELECT DISTINCT peer_id FROM shard_decode_failures WHERE object_cid = $1"
+    )
+    .bind(object_cid)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_else(|e| {
+        warn!("Failed to load poison shard senders for {}: {}", object_cid, e);
+        Vec::new()
+    })
+    .into_iter()
+    .collect();
+
+    let mut placed: HashMap<usize, (f64, String)> = HashMap::new();
+    for row in rows {
+        if row.shard_index >= 0 && (row.shard_index as usize) < shards {
+            placed.insert(row.shard_index as usize, (row.cost(), row.peer_id));
+        }
+    }
+
+    let mut targets: Vec<(f64, ShardTarget)> = (0..shards)
+        .filter_map(|index| match placed.remove(&index) {
+            Some((_, peer_id)) if avoid.contains(&peer_id) => None,
+            Some((cost, peer_id)) => Some((cost, ShardTarget { index, peer_id: Some(peer_id) })),
+            None => Some((f64::INFINITY, ShardTarget { index, peer_id: None })),
+        })
+        .collect();
+    targets.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.index.cmp(&b.1.index)));
+    RetrievalPlan {
+        targets: targets.into_iter().map(|(_, target)| target).collect(),
+        avoid: Arc::new(avoid),
+    }
+}
+
+/// Shards fetched for one object by index, with the peer each came from.
+pub struct FetchedShards {
+    pub shards: Vec<Option<Vec<u8>>>,
+    senders: Vec<Option<String>>,
+    attempted: Vec<bool>,
+}
+
+impl FetchedShards {
+    pub fn new(total: usize) -> Self {
+        Self { shards: vec![None; total], senders: vec![None; total], attempted: vec![false; total] }
+    }
+
+    /// Records the answer for `index`; `data` is `None` when the fetch failed.
+    pub fn record(&mut self, index: usize, data: Option<Vec<u8>>, sample: Option<&RetrievalSample>) {
+        if index >= self.shards.len() {
+            return;
+        }
+        self.attempted[index] = true;
+        self.senders[index] = sample.map(|s| s.peer_id.clone());
+        self.shards[index] = data;
+    }
+
+    pub fn count(&self) -> usize {
+        self.shards.iter().flatten().count()
+    }
+}
+
+/// Fetches just enough shards to rebuild an object: the `threshold`
+/// cheapest holders first, and one more only for each fetch that fails.
+/// Returns the shards by index and how many arrived.
+pub async fn fetch_minimum(
+    state: &AppState,
+    object_cid: &str,
+    shards: usize,
+    threshold: usize,
+) -> (RetrievalPlan, FetchedShards) {
+    let plan = plan(state, object_cid, shards).await;
+    let mut targets = plan.targets.iter().cloned();
+    let mut futures: FuturesUnordered<_> = targets
+        .by_ref()
+        .take(threshold)
+        .map(|target| fetch_shard(state.p2p_tx.clone(), object_cid, target, &plan.avoid))
+        .collect();
+
+    let mut fetched = FetchedShards::new(shards);
+    let mut samples = Vec::new();
+    while let Some((index, data, sample)) = futures.next().await {
+        let arrived = data.is_some();
+        fetched.record(index, data, sample.as_ref());
+        samples.extend(sample);
+        if arrived {
+            if fetched.count() >= threshold {
+                break;
+            }
+        } else if let Some(next) = targets.next() {
+            futures.push(fetch_shard(state.p2p_tx.clone(), object_cid, next, &plan.avoid));
+        }
+    }
+    drop(futures);
+    record(state, samples);
+    (plan, fetched)
+}
+
+async fn fetch_shard(
+    p2p_tx: SwarmSender,
+    object_cid: &str,
+    target: ShardTarget,
+    avoid: &HashSet<String>,
+) -> (usize, Option<Vec<u8>>, Option<RetrievalSample>) {
+    let cid = format!("{}-shard-{}", object_cid, target.index);
+    let (tx, rx) = oneshot::channel();
+    let sent_at = Instant::now();
+    let req = SwarmRequest::Retrieve { cid, preferred_peer_id: target.peer_id, voucher: None, tx };
+    if p2p_tx.send(req).await.is_err() {
+        return (target.index, None, None);
+    }
+    match timeout(SHARD_FETCH_TIMEOUT, rx).await {
+        Ok(Ok(ack)) => {
+            let (data, sample) = accept(ack, sent_at, avoid);
+            (target.index, data, sample)
+        }
+        _ => (target.index, None, None),
+    }
+}
+
+/// Splits a retrieval answer into the shard and its stats sample. Data from
+/// a peer in `avoid` is discarded and counted as a failure.
+pub fn accept(ack: RetrieveAck, sent_at: Instant, avoid: &HashSet<String>) -> (Option<Vec<u8>>, Option<RetrievalSample>) {
+    let data = ack.data.filter(|_| !avoid.contains(&ack.peer_id));
+    let latency = data.is_some().then(|| sent_at.elapsed());
+    let sample = (!ack.peer_id.is_empty()).then_some(RetrievalSample { peer_id: ack.peer_id, latency });
+    (data, sample)
+}
+
+/// Outcome of one shard fetch; `latency` is `None` when it failed.
+pub struct RetrievalSample {
+    pub peer_id: String,
+    pub latency: Option<Duration>,
+}
+
+/// Folds finished fetches into `node_retrieval_stats` in the background.
+pub fn record(state: &AppState, samples: Vec<RetrievalSample>) {
+    if samples.is_empty() {
+        return;
+    }
+    let db = state.db.clone();
+    tokio::spawn(async move {
+        for sample in samples {
+            let latency_ms = sample.latency.map(|d| d.as_secs_f64() * 1000.0);
+            let res = sqlx::query(
+                r#"
+                INSERT INTO node_retrieval_stats (peer_id, latency_ewma_ms, successes, failures, updated_at)
+                VALUES ($1, $2, $3, $4, NOW())
+                ON CONFLICT (peer_id) DO UPDATE SET
+                    latency_ewma_ms = CASE
+                        WHEN excluded.latency_ewma_ms IS NULL THEN node_retrieval_stats.latency_ewma_ms
+                        WHEN node_retrieval_stats.latency_ewma_ms IS NULL THEN excluded.latency_ewma_ms
+                        ELSE node_retrieval_stats.latency_ewma_ms * (1 - $5) + excluded.latency_ewma_ms * $5
+                    END,
+                    successes = node_retrieval_stats.successes * $6 + excluded.successes,
+                    failures = node_retrieval_stats.failures * $6 + excluded.failures,
+                    updated_at = NOW()
+                "#
+            )
+            .bind(&sample.peer_id)
+            .bind(latency_ms)
+            .bind(if latency_ms.is_some() { 1.0 } else { 0.0 })
+            .bind(if latency_ms.is_some() { 0.0 } else { 1.0 })
+            .bind(LATENCY_EWMA_ALPHA)
+            .bind(COUNT_DECAY)
+            .execute(&db)
+            .await;
+            if let Err(e) = res {
+                warn!("Failed to record retrieval stats for {}: {}", sample.peer_id, e);
+                return;
+            }
+        }
+    });
+}
+
+// ── POISON SHARDS ──
+// A shard of the right name but the wrong bytes decodes without complaint
+// and yields garbage, so a rebuilt body only counts once it hashes back to
+// the object CID. When it does not, the culprits are found by shard length
+// first, then by leaving each shard out in turn with a spare making up the
+// threshold. Their senders are charged in `node_retrieval_stats`, `nodes`
+// and `shard_decode_failures`, which `plan` reads to skip them from then on
+// and the sentinel telemetry counts against their verification rate.
+
+/// One decode of the fetched shards: the rebuilt body, or why there is
+/// none, and the shard indices found corrupt either way, with how.
+struct Attempt {
+    body: Result<Vec<u8>, String>,
+    poisoned: Vec<(usize, &'static str)>,
+}
+
+/// The CID PUT gives an object: its encrypted body, SHA-256 and base58.
+pub fn content_cid(body: &[u8]) -> String {
+    format!("Qm{}", bs58::encode(Sha256::digest(body)).into_string())
+}
+
+fn decode_body(decoder: &ErasureEncoder, shards: Vec<Option<Vec<u8>>>, size: usize, cid: &str) -> Result<Vec<u8>, String> {
+    let mut body = decoder
+        .decode(shards)
+        .map_err(|_| "erasure reconstruction failed".to_string())?;
+    if body.len() < size {
+        return Err(format!("decoded {} bytes, expected {}", body.len(), size));
+    }
+    // The last data shard is zero-padded up to the shard size.
+    body.truncate(size);
+    if content_cid(&body) != cid {
+        return Err("decoded bytes do not hash to the object CID".to_string());
+    }
+    Ok(body)
+}
+
+fn decode_attributed(mut shards: Vec<Option<Vec<u8>>>, threshold: usize, size: usize, cid: &str) -> Attempt {
+    let decoder = match ErasureEncoder::new(threshold, shards.len().saturating_sub(threshold)) {
+        Ok(decoder) => decoder,
+        Err(_) => return Attempt { body: Err("RS decoder init failed".to_string()), poisoned: Vec::new() },
+    };
+
+    // Every shard of an object is as long as PUT made them.
+    let shard_len = size.div_ceil(threshold);
+    let mut poisoned = Vec::new();
+    for (index, shard) in shards.iter_mut().enumerate() {
+        if shard.as_ref().is_some_and(|s| s.len() != shard_len) {
+            *shard = None;
+            poisoned.push((index, "wrong shard length"));
+        }
+    }
+    let present: Vec<usize> = (0..shards.len()).filter(|&i| shards[i].is_some()).collect();
+    if present.len() < threshold {
+        let reason = format!("only {} of the {} shards needed are usable", present.len(), threshold);
+        return Attempt { body: Err(reason), poisoned };
+    }
+
+    let failure = match decode_body(&decoder, shards.clone(), size, cid) {
+        Ok(body) => return Attempt { body: Ok(body), poisoned },
+        Err(reason) => reason,
+    };
+    // With a spare, a body that checks out without one shard convicts it.
+    if present.len() > threshold {
+        for &index in &present {
+            let mut trial = shards.clone();
+            trial[index] = None;
+            if let Ok(body) = decode_body(&decoder, trial, size, cid) {
+                poisoned.push((index, "breaks the decode"));
+                return Attempt { body: Ok(body), poisoned };
+            }
+        }
+    }
+    Attempt { body: Err(failure), poisoned }
+}
+
+/// Rebuilds the encrypted body of `object_cid` (`size` bytes) from
+/// `fetched`, checked against the CID. Corrupt shards are dropped and
+/// charged to their senders; while the culprit is unknown, shards the plan
+/// has not tried yet are fetched one at a time as spares.
+pub async fn rebuild(
+    state: &AppState,
+    plan: &RetrievalPlan,
+    object_cid: &str,
+    fetched: &mut FetchedShards,
+    threshold: usize,
+    size: usize,
+) -> Result<Vec<u8>, String> {
+    let total = fetched.shards.len();
+    let untried: Vec<ShardTarget> = plan.targets.iter().filter(|t| !fetched.attempted[t.index]).cloned().collect();
+    let mut spares = untried.into_iter();
+    loop {
+        let decode_span = tracing::info_span!(
+            "rs_decode",
+            data_shards = threshold,
+            parity_shards = total.saturating_sub(threshold),
+            shards = fetched.count(),
+        );
+        let (shards, cid) = (fetched.shards.clone(), object_cid.to_string());
+        // Decoding untrusted shards is kept off the async workers.
+        let attempt = tokio::task::spawn_blocking(move || {
+            decode_span.in_scope(|| decode_attributed(shards, threshold, size, &cid))
+        })
+        .await
+        .unwrap_or_else(|_| Attempt { body: Err("erasure decode crashed".to_string()), poisoned: Vec::new() });
+
+        if !attempt.poisoned.is_empty() {
+            let culprits: Vec<Culprit> = attempt
+                .poisoned
+                .iter()
+                .filter_map(|&(index, reason)| {
+                    fetched.shards[index] = None;
+                    fetched.senders[index].clone().map(|peer_id| Culprit { index, peer_id, reason })
+                })
+                .collect();
+            penalize(state, object_cid, culprits);
+        }
+        let reason = match attempt.body {
+            Ok(body) => return Ok(body),
+            Err(reason) => reason,
+        };
+
+        let mut arrived = false;
+        for target in spares.by_ref() {
+            let (index, data, sample) = fetch_shard(state.p2p_tx.clone(), object_cid, target, &plan.avoid).await;
+            arrived = data.is_some();
+            fetched.record(index, data, sample.as_ref());
+            record(state, sample.into_iter().collect());
+            if arrived {
+                break;
+            }
+        }
+        if !arrived {
+            return Err(reason);
+        }
+    }
+}
+
+/// A corrupt shard and the peer that sent it.
+struct Culprit {
+    index: usize,
+    peer_id: String,
+    reason: &'static str,
+}
+
+/// Charges each culprit for sending a corrupt shard of `object_cid`, in the
+/// background.
+fn penalize(state: &AppState, object_cid: &str, culprits: Vec<Culprit>) {
+    if culprits.is_empty() {
+        return;
+    }
+    for culprit in &culprits {
+        warn!(
+            target: "poison_shard",
+            object_cid = %object_cid,
+            shard_index = culprit.index,
+            peer_id = %culprit.peer_id,
+            reason = culprit.reason,
+            "Poison shard: peer sent bytes that do not rebuild the object"
+        );
+    }
+    record(
+        state,
+        culprits
+            .iter()
+            .map(|c| RetrievalSample { peer_id: c.peer_id.clone(), latency: None })
+            .collect(),
+    );
+
+    let db = state.db.clone();
+    let slasher = Slasher::new(state);
+    let object_cid = object_cid.to_string();
+    tokio::spawn(async move {
+        for Culprit { index, peer_id, reason } in culprits {
+            let res = async {
+                let mut tx = db.begin().await?;
+                sqlx::query(
+                    r#"
+                    INSERT INTO shard_decode_failures (object_cid, shard_index, peer_id, reason, detected_at)
+                    VALUES ($1, $2, $3, $4, NOW())
+                    ON CONFLICT (object_cid, shard_index, peer_id) DO UPDATE SET
+                        reason = excluded.reason,
+                        detected_at = NOW()
+                    "#
+                )
+                .bind(&object_cid)
+                .bind(index as i32)
+                .bind(&peer_id)
+                .bind(reason)
+                .execute(&mut *tx)
+                .await?;
+                sqlx::query(
+                    "UPDATE nodes SET decode_failures = decode_failures + 1, last_decode_failure_at = NOW() WHERE peer_id = $1"
+                )
+                .bind(&peer_id)
+                .execute(&mut *tx)
+                .await?;
+                tx.commit().await
+            }
+            .await;
+            if let Err(e) = res {
+                warn!("Failed to record poison shard {} of {} from {}: {}", index, object_cid, peer_id, e);
+            }
+            let reference = format!("{}#{}", object_cid, index);
+            if let Err(e) = slasher.penalize(&peer_id, PenaltyKind::PoisonShard, &reference, reason).await {
+                warn!("Failed to penalize {} for poison shard {}: {}", peer_id, reference, e);
+            }
+        }
+    });
+}
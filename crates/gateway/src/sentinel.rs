@@ -0,0 +1,368 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use neuro_protocol::sentinel::{
+    excludes_peer, NodeMetrics, PeerPolicy, PeerPolicyRequest, GET_PEER_POLICY_PATH, STREAM_METRICS_PATH,
+};
+use tokio::sync::mpsc;
+use tokio::time;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::client::Grpc;
+use tonic::codec::ProstCodec;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::transport::{Channel, Endpoint};
+use tracing::{error, info, warn};
+
+use crate::AppState;
+
+const TELEMETRY_INTERVAL_SECS: u64 = 60;
+const RECONNECT_DELAY_SECS: u64 = 15;
+const TELEMETRY_BUFFER: usize = 256;
+/// Cap on the fleet multiplier, matching the sentinel's own ceiling.
+const MAX_REDUNDANCY_MULTIPLIER: f64 = 2.5;
+
+/// What the gateway currently enforces from sentinel policies: which peers
+/// are barred from new shards, and how much parity new objects get. Shared
+/// with the p2p loop; empty (no exclusions, 1.0x) until a policy arrives.
+/// Peers evicted for their penalties are barred too, whatever the sentinel
+/// says of them.
+#[derive(Default)]
+pub struct FleetPolicy {
+    peers: RwLock<HashMap<String, PeerVerdict>>,
+    slashed: RwLock<HashSet<String>>,
+}
+
+#[derive(Clone, Copy)]
+struct PeerVerdict {
+    excluded: bool,
+    redundancy_multiplier: f64,
+}
+
+impl FleetPolicy {
+    /// Records a peer's latest verdict. Returns `true` when this changes
+    /// whether the peer is excluded.
+    fn record(&self, peer: &str, excluded: bool, redundancy_multiplier: f64) -> bool {
+        let verdict = PeerVerdict { excluded, redundancy_multiplier };
+        let previous = self
+            .peers
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(peer.to_string(), verdict);
+        previous.map_or(excluded, |p| p.excluded != excluded)
+    }
+
+    /// Marks a peer as being evicted for its penalties, or no longer.
+    /// Returns `true` when this changes anything.
+    pub fn set_slashed(&self, peer_id: &str, slashed: bool) -> bool {
+        let mut peers = self.slashed.write().unwrap_or_else(PoisonError::into_inner);
+        if slashed {
+            peers.insert(peer_id.to_string())
+        } else {
+            peers.remove(peer_id)
+        }
+    }
+
+    pub fn is_excluded(&self, peer_id: &str) -> bool {
+        self.slashed
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains(peer_id)
+            || self
+                .peers
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get(peer_id)
+                .is_some_and(|v| v.excluded)
+    }
+
+    /// Median multiplier recommended for the peers still taking shards,
+    /// never below the gateway's baseline of 1.0.
+    pub fn redundancy_multiplier(&self) -> f64 {
+        let peers = self.peers.read().unwrap_or_else(PoisonError::into_inner);
+        let mut multipliers: Vec<f64> = peers
+            .values()
+            .filter(|v| !v.excluded && v.redundancy_multiplier.is_finite())
+            .map(|v| v.redundancy_multiplier)
+            .collect();
+        if multipliers.is_empty() {
+            return 1.0;
+        }
+        multipliers.sort_by(f64::total_cmp);
+        multipliers[multipliers.len() / 2].clamp(1.0, MAX_REDUNDANCY_MULTIPLIER)
+    }
+
+    /// Parity shards for a new object, scaling `base` by the fleet multiplier.
+    pub fn parity_shards(&self, base: usize) -> usize {
+        (base as f64 * self.redundancy_multiplier()).round() as usize
+    }
+}
+
+/// Client for neuro-sentinel's gRPC policy service. The channel connects
+/// lazily and reconnects on its own, so a clone is cheap to hand around.
+#[derive(Clone)]
+pub struct SentinelClient {
+    channel: Channel,
+}
+
+impl SentinelClient {
+    /// Reads `SENTINEL_GRPC_URL` (e.g. `http://neurostore-sentinel:50051`).
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(url) = std::env::var("SENTINEL_GRPC_URL") else {
+            return Ok(None);
+        };
+        let channel = Endpoint::from_shared(url)?
+            .connect_timeout(Duration::from_secs(5))
+            .connect_lazy();
+        Ok(Some(Self { channel }))
+    }
+
+    /// Opens a StreamMetrics call: every sample sent on the returned sender
+    /// is answered by a policy on the returned stream.
+    pub async fn stream_metrics(&self) -> anyhow::Result<(mpsc::Sender<NodeMetrics>, tonic::Streaming<PeerPolicy>)> {
+        let mut grpc = Grpc::new(self.channel.clone());
+        grpc.ready().await?;
+        let (tx, rx) = mpsc::channel(TELEMETRY_BUFFER);
+        let response = grpc
+            .streaming(
+                tonic::Request::new(ReceiverStream::new(rx)),
+                PathAndQuery::from_static(STREAM_METRICS_PATH),
+                ProstCodec::<NodeMetrics, PeerPolicy>::default(),
+            )
+            .await?;
+        Ok((tx, response.into_inner()))
+    }
+
+    /// The sentinel's latest policy for `peer`; `None` when it has had no
+    /// telemetry for it since it started.
+    pub async fn peer_policy(&self, peer: &str) -> anyhow::Result<Option<PeerPolicy>> {
+        let mut grpc = Grpc::new(self.channel.clone());
+        grpc.ready().await?;
+        let response = grpc
+            .unary(
+                tonic::Request::new(PeerPolicyRequest { peer: peer.to_string() }),
+                PathAndQuery::from_static(GET_PEER_POLICY_PATH),
+                ProstCodec::<PeerPolicyRequest, PeerPolicy>::default(),
+            )
+            .await;
+        match response {
+            Ok(response) => Ok(Some(response.into_inner())),
+            Err(status) if status.code() == tonic::Code::NotFound => Ok(None),
+            Err(status) => Err(status.into()),
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct PeerTelemetry {
+    peer_id: String,
+    uptime_pct: f64,
+    bandwidth_mbps: f64,
+    verified: i64,
+    failed: i64,
+    decode_failures: i64,
+    latency_ms: Option<f64>,
+}
+
+impl From<PeerTelemetry> for NodeMetrics {
+    fn from(t: PeerTelemetry) -> Self {
+        // A poison shard counts as a failed verification.
+        let failed = t.failed + t.decode_failures;
+        let answered = t.verified + failed;
+        NodeMetrics {
+            peer: t.peer_id,
+            // Proof round-trip time stands in for latency; zero means unknown.
+            latency_ms: t.latency_ms.unwrap_or_default(),
+            uptime_pct: t.uptime_pct,
+            verify_success_pct: if answered == 0 { 100.0 } else { t.verified as f64 * 100.0 / answered as f64 },
+            bandwidth_mbps: t.bandwidth_mbps,
+            object_heat_index: 0.0,
+            regional_qos_penalty: 0.0,
+            timestamp_ms: chrono::Utc::now().timestamp_millis() as u64,
+        }
+    }
+}
+
+/// Streams per-peer telemetry to neuro-sentinel and applies the policies it
+/// sends back: quarantined and evicted peers stop receiving shards, and the
+/// fleet redundancy multiplier scales parity for new objects. Policies are
+/// kept in `node_reputation` so they survive a gateway restart. Runs on the
+/// leader only.
+pub struct SentinelDaemon {
+    state: Arc<AppState>,
+    client: SentinelClient,
+}
+
+impl SentinelDaemon {
+    pub fn new(state: Arc<AppState>, client: SentinelClient) -> Self {
+        Self { state, client }
+    }
+
+    pub async fn start(&self) {
+        info!("Sentinel daemon initialized. Streaming telemetry every {} seconds.", TELEMETRY_INTERVAL_SECS);
+        self.restore().await;
+
+        loop {
+            match self.session().await {
+                Ok(()) => warn!("Sentinel closed the policy stream; reconnecting"),
+                Err(e) => warn!("Sentinel policy stream failed: {}; reconnecting", e),
+            }
+            time::sleep(Duration::from_secs(RECONNECT_DELAY_SECS)).await;
+        }
+    }
+
+    /// Reloads the last known verdicts so exclusions hold before the
+    /// sentinel answers again.
+    async fn restore(&self) {
+        let rows = sqlx::query_as::<_, (String, String, f64)>(
+            "SELECT peer_id, action, redundancy_multiplier FROM node_reputation"
+        )
+        .fetch_all(&self.state.db)
+        .await;
+        match rows {
+            Ok(rows) => {
+                for (peer_id, action, multiplier) in rows {
+                    self.state.fleet_policy.record(&peer_id, excludes_peer(&action), multiplier);
+                }
+            }
+            Err(e) => error!("Failed to restore sentinel policies: {}", e),
+        }
+    }
+
+    async fn session(&self) -> anyhow::Result<()> {
+        let (tx, mut policies) = self.client.stream_metrics().await?;
+        info!("Connected to sentinel policy stream");
+
+        let state = Arc::clone(&self.state);
+        let telemetry = tokio::spawn(async move {
+            let mut interval = time::interval(Duration::from_secs(TELEMETRY_INTERVAL_SECS));
+            loop {
+                interval.tick().await;
+                match collect_telemetry(&state).await {
+                    Ok(samples) => {
+                        for sample in samples {
+                            if tx.send(sample).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                    Err(e) => error!("Failed to collect sentinel telemetry: {}", e),
+                }
+            }
+        });
+
+        let result = loop {
+            match policies.message().await {
+                Ok(Some(policy)) => self.apply(policy).await,
+                Ok(None) => break Ok(()),
+                Err(status) => break Err(status.into()),
+            }
+        };
+        telemetry.abort();
+        result
+    }
+
+    async fn apply(&self, policy: PeerPolicy) {
+        let excluded = policy.excludes_peer();
+        if self
+            .state
+            .fleet_policy
+            .record(&policy.peer, excluded, policy.recommended_redundancy_multiplier)
+        {
+            if excluded {
+                let reason = policy.explanations.first().map(|e| e.detail.as_str()).unwrap_or("no explanation given");
+                warn!("Sentinel {} for node {} (reputation {:.1}, {}); no new shards will be placed on it", policy.action, policy.peer, policy.reputation, reason);
+            } else {
+                info!("Sentinel lifted exclusion of node {} ({})", policy.peer, policy.action);
+            }
+        }
+
+        // Checked against the stored policy before it is replaced, so only
+        // rate or action changes land in the history.
+        let res = sqlx::query(
+            r#"
+            INSERT INTO node_price_history (peer_id, price_per_gb, reputation, action)
+            SELECT $1, $2, $3, $4
+            WHERE NOT EXISTS (
+                SELECT 1 FROM node_reputation
+                WHERE peer_id = $1 AND price_per_gb = $2 AND action = $4
+            )
+            "#,
+        )
+        .bind(&policy.peer)
+        .bind(policy.price_per_gb)
+        .bind(policy.reputation)
+        .bind(&policy.action)
+        .execute(&self.state.db)
+        .await;
+        if let Err(e) = res {
+            error!("Failed to record price history for node {}: {}", policy.peer, e);
+        }
+
+        let res = sqlx::query(
+            r#"
+            INSERT INTO node_reputation (
+                peer_id, score, reputation, action, anomaly_level,
+                churn_probability, price_per_gb, redundancy_multiplier,
+                cohort_id, cohort_size, collusion_risk, explanations, updated_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, NOW())
+            ON CONFLICT (peer_id) DO UPDATE SET
+                score = excluded.score,
+                reputation = excluded.reputation,
+                action = excluded.action,
+                anomaly_level = excluded.anomaly_level,
+                churn_probability = excluded.churn_probability,
+                price_per_gb = excluded.price_per_gb,
+                redundancy_multiplier = excluded.redundancy_multiplier,
+                cohort_id = excluded.cohort_id,
+                cohort_size = excluded.cohort_size,
+                collusion_risk = excluded.collusion_risk,
+                explanations = excluded.explanations,
+                updated_at = NOW()
+            "#
+        )
+        .bind(&policy.peer)
+        .bind(policy.score)
+        .bind(policy.reputation)
+        .bind(&policy.action)
+        .bind(&policy.anomaly_level)
+        .bind(policy.churn_probability)
+        .bind(policy.price_per_gb)
+        .bind(policy.recommended_redundancy_multiplier)
+        .bind(&policy.cohort_id)
+        .bind(policy.cohort_size as i32)
+        .bind(policy.collusion_risk)
+        .bind(sqlx::types::Json(&policy.explanations))
+        .execute(&self.state.db)
+        .await;
+        if let Err(e) = res {
+            error!("Failed to store sentinel policy for node {}: {}", policy.peer, e);
+        }
+    }
+}
+
+/// One sample per registered node: uptime and bandwidth as registered, and
+/// proof-of-storage outcomes and poison shards over the last day for
+/// verification rate and latency.
+async fn collect_telemetry(state: &AppState) -> Result<Vec<NodeMetrics>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, PeerTelemetry>(
+        r#"
+        SELECT n.peer_id,
+               COALESCE(n.uptime_percentage, 0)::FLOAT8 AS uptime_pct,
+               COALESCE(n.bandwidth_capacity_mbps, 0)::FLOAT8 AS bandwidth_mbps,
+               COUNT(c.challenge_id) FILTER (WHERE c.status = 'verified') AS verified,
+               COUNT(c.challenge_id) FILTER (WHERE c.status IN ('failed', 'expired')) AS failed,
+               (SELECT COUNT(*) FROM shard_decode_failures d
+                WHERE d.peer_id = n.peer_id AND d.detected_at > NOW() - INTERVAL '24 hours') AS decode_failures,
+               (AVG(EXTRACT(EPOCH FROM (c.verified_at - c.issued_at)) * 1000)
+                   FILTER (WHERE c.status = 'verified'))::FLOAT8 AS latency_ms
+        FROM nodes n
+        LEFT JOIN zk_proof_challenges c
+            ON c.peer_id = n.peer_id AND c.issued_at > NOW() - INTERVAL '24 hours'
+        GROUP BY n.peer_id, n.uptime_percentage, n.bandwidth_capacity_mbps
+        "#
+    )
+    .fetch_all(&state.db)
+    .await?;
+    Ok(rows.into_iter().map(NodeMetrics::from).collect())
+}
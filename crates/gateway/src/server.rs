@@ -0,0 +1,296 @@
+//! The HTTP listener: HTTP/1.1 and HTTP/2 on one port, optionally behind
+//! TLS terminated here, with the connection limits and timeouts from
+//! `[server]` in the gateway config.
+//!
+//! S3 clients open hundreds of requests at once. Over HTTP/1.1 each needs a
+//! connection of its own; over HTTP/2 they share a few. `ConnectionMetrics`
+//! records how many requests each connection carried at its busiest, so the
+//! difference shows in `/api/admin/connections`.
+
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, Request, StatusCode, Version},
+    response::IntoResponse,
+    Json, Router,
+};
+use hyper::body::Incoming;
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::conn::auto::Builder;
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio::sync::Semaphore;
+use tokio_rustls::rustls;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::TlsAcceptor;
+use tower::ServiceExt;
+use tracing::{debug, warn};
+
+use crate::config::{ServerConfig, TlsConfig};
+use crate::handlers::backups::check_admin_token;
+use crate::AppState;
+
+/// Upper bounds of the buckets connections are counted in by the most
+/// requests they had in flight at once; the last bucket is open-ended.
+const PEAK_BUCKETS: [usize; 5] = [1, 4, 16, 64, 256];
+
+/// Serves `app` on `listener` until accepting fails for good.
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    config: &ServerConfig,
+    metrics: Arc<ConnectionMetrics>,
+) -> anyhow::Result<()> {
+    let tls = config.tls.as_ref().map(|tls| acceptor(tls, config.http2)).transpose()?;
+    let handshake_timeout = Duration::from_secs(config.header_read_timeout_secs);
+
+    let mut builder = Builder::new(TokioExecutor::new());
+    builder
+        .http1()
+        .timer(TokioTimer::new())
+        .header_read_timeout(handshake_timeout)
+        .keep_alive(true);
+    let keep_alive = (config.keep_alive_interval_secs > 0).then(|| Duration::from_secs(config.keep_alive_interval_secs));
+    builder
+        .http2()
+        .timer(TokioTimer::new())
+        .max_concurrent_streams(config.max_concurrent_streams)
+        .initial_stream_window_size(config.initial_stream_window_bytes)
+        .initial_connection_window_size(config.initial_connection_window_bytes)
+        .keep_alive_interval(keep_alive)
+        .keep_alive_timeout(Duration::from_secs(config.keep_alive_timeout_secs));
+    if !config.http2 {
+        builder = builder.http1_only();
+    }
+    let builder = Arc::new(builder);
+    let slots = Arc::new(Semaphore::new(config.max_connections));
+
+    loop {
+        // Past the limit, new clients stay in the kernel's accept backlog
+        // until a connection closes.
+        let permit = match Arc::clone(&slots).try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                metrics.saturated.fetch_add(1, Ordering::Relaxed);
+                Arc::clone(&slots).acquire_owned().await?
+            }
+        };
+        let (stream, remote) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) if is_connection_error(&err) => continue,
+            Err(err) => {
+                // Usually out of file descriptors; give some a chance to close.
+                warn!(error = %err, "Accept failed");
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+        let _ = stream.set_nodelay(true);
+        metrics.accepted.fetch_add(1, Ordering::Relaxed);
+
+        let (builder, app, metrics, tls) = (Arc::clone(&builder), app.clone(), Arc::clone(&metrics), tls.clone());
+        tokio::spawn(async move {
+            let _permit = permit;
+            match tls {
+                Some(acceptor) => match tokio::time::timeout(handshake_timeout, acceptor.accept(stream)).await {
+                    Ok(Ok(stream)) => serve_connection(&builder, stream, app, remote, metrics).await,
+                    Ok(Err(err)) => {
+                        metrics.tls_failures.fetch_add(1, Ordering::Relaxed);
+                        debug!(%remote, error = %err, "TLS handshake failed");
+                    }
+                    Err(_) => {
+                        metrics.tls_failures.fetch_add(1, Ordering::Relaxed);
+                        debug!(%remote, "TLS handshake timed out");
+                    }
+                },
+                None => serve_connection(&builder, stream, app, remote, metrics).await,
+            }
+        });
+    }
+}
+
+async fn serve_connection<I>(
+    builder: &Builder<TokioExecutor>,
+    stream: I,
+    app: Router,
+    remote: SocketAddr,
+    metrics: Arc<ConnectionMetrics>,
+) where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let connection = Arc::new(OpenConnection::new(Arc::clone(&metrics)));
+    let service = hyper::service::service_fn(move |mut request: Request<Incoming>| {
+        // Handlers such as the access log read the client address from here.
+        request.extensions_mut().insert(ConnectInfo(remote));
+        let in_flight = InFlight::start(Arc::clone(&connection), request.version());
+        let app = app.clone();
+        async move {
+            let response = app.oneshot(request).await;
+            drop(in_flight);
+            response
+        }
+    });
+    if let Err(err) = builder.serve_connection_with_upgrades(TokioIo::new(stream), service).await {
+        debug!(%remote, error = %err, "Connection closed with an error");
+    }
+}
+
+fn acceptor(tls: &TlsConfig, http2: bool) -> anyhow::Result<TlsAcceptor> {
+    let certs = CertificateDer::pem_file_iter(&tls.cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|err| anyhow::anyhow!("server.tls.cert_path {}: {}", tls.cert_path, err))?;
+    let key = PrivateKeyDer::from_pem_file(&tls.key_path)
+        .map_err(|err| anyhow::anyhow!("server.tls.key_path {}: {}", tls.key_path, err))?;
+    let mut config = rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    config.alpn_protocols = if http2 {
+        vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+    } else {
+        vec![b"http/1.1".to_vec()]
+    };
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+fn is_connection_error(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        std::io::ErrorKind::ConnectionRefused
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::ConnectionReset
+    )
+}
+
+/// Counters kept since startup for every connection the listener took.
+#[derive(Default)]
+pub struct ConnectionMetrics {
+    accepted: AtomicU64,
+    open: AtomicUsize,
+    /// Accepts that had to wait for `max_connections` to free a slot.
+    saturated: AtomicU64,
+    tls_failures: AtomicU64,
+    in_flight: AtomicUsize,
+    http1_requests: AtomicU64,
+    http2_requests: AtomicU64,
+    /// Most requests any single connection has had in flight at once.
+    peak_per_connection: AtomicUsize,
+    /// Closed connections by their own peak, per `PEAK_BUCKETS`.
+    closed_by_peak: [AtomicU64; PEAK_BUCKETS.len() + 1],
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConnectionStats {
+    pub accepted: u64,
+    pub open: usize,
+    pub saturated: u64,
+    pub tls_handshake_failures: u64,
+    pub in_flight_requests: usize,
+    pub http1_requests: u64,
+    pub http2_requests: u64,
+    pub peak_requests_per_connection: usize,
+    /// Closed connections keyed by the most requests they carried at once,
+    /// e.g. `"2-4"`.
+    pub closed_connections_by_peak_requests: Vec<(String, u64)>,
+}
+
+impl ConnectionMetrics {
+    pub fn stats(&self) -> ConnectionStats {
+        let closed = self
+            .closed_by_peak
+            .iter()
+            .enumerate()
+            .map(|(bucket, count)| {
+                let lower = bucket.checked_sub(1).map_or(1, |below| PEAK_BUCKETS[below] + 1);
+                let label = match PEAK_BUCKETS.get(bucket) {
+                    Some(&upper) if upper == lower => upper.to_string(),
+                    Some(&upper) => format!("{}-{}", lower, upper),
+                    None => format!("{}+", lower),
+                };
+                (label, count.load(Ordering::Relaxed))
+            })
+            .collect();
+        ConnectionStats {
+            accepted: self.accepted.load(Ordering::Relaxed),
+            open: self.open.load(Ordering::Relaxed),
+            saturated: self.saturated.load(Ordering::Relaxed),
+            tls_handshake_failures: self.tls_failures.load(Ordering::Relaxed),
+            in_flight_requests: self.in_flight.load(Ordering::Relaxed),
+            http1_requests: self.http1_requests.load(Ordering::Relaxed),
+            http2_requests: self.http2_requests.load(Ordering::Relaxed),
+            peak_requests_per_connection: self.peak_per_connection.load(Ordering::Relaxed),
+            closed_connections_by_peak_requests: closed,
+        }
+    }
+}
+
+/// One served connection; counted as open until its last request is done.
+struct OpenConnection {
+    metrics: Arc<ConnectionMetrics>,
+    in_flight: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl OpenConnection {
+    fn new(metrics: Arc<ConnectionMetrics>) -> Self {
+        metrics.open.fetch_add(1, Ordering::Relaxed);
+        Self { metrics, in_flight: AtomicUsize::new(0), peak: AtomicUsize::new(0) }
+    }
+}
+
+impl Drop for OpenConnection {
+    fn drop(&mut self) {
+        self.metrics.open.fetch_sub(1, Ordering::Relaxed);
+        let peak = self.peak.load(Ordering::Relaxed);
+        if peak > 0 {
+            let bucket = PEAK_BUCKETS.iter().position(|&upper| peak <= upper).unwrap_or(PEAK_BUCKETS.len());
+            self.metrics.closed_by_peak[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A request from its headers until its handler returns a response; a
+/// streamed body is not waited for.
+struct InFlight(Arc<OpenConnection>);
+
+impl InFlight {
+    fn start(connection: Arc<OpenConnection>, version: Version) -> Self {
+        let metrics = &connection.metrics;
+        match version {
+            Version::HTTP_2 => metrics.http2_requests.fetch_add(1, Ordering::Relaxed),
+            _ => metrics.http1_requests.fetch_add(1, Ordering::Relaxed),
+        };
+        metrics.in_flight.fetch_add(1, Ordering::Relaxed);
+        let now = connection.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+        connection.peak.fetch_max(now, Ordering::Relaxed);
+        metrics.peak_per_connection.fetch_max(now, Ordering::Relaxed);
+        Self(connection)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+        self.0.metrics.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// ── GET /api/admin/connections ──
+// Connection and per-connection concurrency counters, with the `[server]`
+// settings they were served under. Guarded by `ADMIN_TOKEN` in
+// `x-admin-token`.
+pub async fn connection_stats(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    check_admin_token(&state, &headers)?;
+    Ok(Json(serde_json::json!({
+        "server": state.config.server,
+        "connections": state.connections.stats(),
+    })))
+}
@@ -0,0 +1,627 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use chrono::Utc;
+use libp2p::{identity::Keypair, PeerId};
+use neuro_protocol::{appeal_payload, penalty_payload, verify_registration};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use tokio::time;
+use tracing::{error, info, warn};
+
+use crate::handlers::backups::check_admin_token;
+use crate::sentinel::FleetPolicy;
+use crate::AppState;
+
+// ── PENALTIES ──
+// Failed proofs of storage, failed audits and poison shards earn a node
+// penalty points. Points count for `PENALTY_WINDOW_DAYS`; past
+// `WITHHOLD_POINTS` the node's payouts are credited as withheld instead of
+// earned, and past `EVICT_POINTS` it receives no new shards and every shard
+// it holds is scheduled for evacuation. Both lift once its points fall back
+// under the threshold, releasing what was withheld.
+//
+// Each penalty is a notice the gateway signs with its identity key, so a
+// node can check a notice and its evidence before contesting it. A node
+// reads its notices from /api/nodes/:peer_id/penalties and may appeal
+// each once, signing the appeal with its identity key; an operator upholds
+// or overturns it through the admin API, and overturned penalties stop
+// counting.
+
+const PENALTY_WINDOW_DAYS: i32 = 30;
+const WITHHOLD_POINTS: i64 = 10;
+const EVICT_POINTS: i64 = 25;
+/// How often standings are recomputed so expired points lift them.
+const STANDING_REFRESH_SECS: u64 = 600;
+/// How far an appeal's timestamp may be from the gateway's clock.
+const APPEAL_MAX_SKEW_MS: i64 = 5 * 60 * 1000;
+const MAX_APPEAL_REASON_BYTES: usize = 4096;
+const LIST_LIMIT: i64 = 200;
+
+#[derive(Debug, Clone, Copy)]
+pub enum PenaltyKind {
+    /// The node did not answer an audit in time.
+    AuditTimeout,
+    /// The node's audit answer or signature did not verify.
+    AuditInvalid,
+    /// A submitted proof of storage failed verification.
+    ProofInvalid,
+    /// A proof challenge expired without an answer.
+    ChallengeExpired,
+    /// The node served shard bytes that do not rebuild their object.
+    PoisonShard,
+}
+
+impl PenaltyKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::AuditTimeout => "audit_timeout",
+            Self::AuditInvalid => "audit_invalid",
+            Self::ProofInvalid => "proof_invalid",
+            Self::ChallengeExpired => "challenge_expired",
+            Self::PoisonShard => "poison_shard",
+        }
+    }
+
+    /// Missed answers may be the network's fault; wrong ones are not.
+    fn points(self) -> i32 {
+        match self {
+            Self::AuditTimeout | Self::ChallengeExpired => 1,
+            Self::PoisonShard => 3,
+            Self::AuditInvalid | Self::ProofInvalid => 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct PenaltyNotice {
+    pub penalty_id: String,
+    pub peer_id: String,
+    pub kind: String,
+    pub points: i32,
+    pub reference: String,
+    pub detail: String,
+    pub issued_at_ms: i64,
+    /// Signature over [`penalty_payload`], hex.
+    pub signature: String,
+    /// The gateway identity key that made `signature`, protobuf-encoded, hex.
+    pub signer_public_key: String,
+    /// `active`, `appealed`, `upheld` or `overturned`.
+    pub status: String,
+    pub appeal_reason: Option<String>,
+    pub resolution_note: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, sqlx::FromRow)]
+pub struct Standing {
+    pub points: i64,
+    pub payouts_withheld: bool,
+    pub evicting: bool,
+}
+
+fn notice_payload(n: &PenaltyNotice) -> Vec<u8> {
+    penalty_payload(&n.penalty_id, &n.peer_id, &n.kind, n.points, &n.reference, &n.detail, n.issued_at_ms)
+}
+
+/// What penalizing a node needs from the gateway, detached from `AppState`
+/// so it can move into a background task.
+#[derive(Clone)]
+pub struct Slasher {
+    db: sqlx::PgPool,
+    identity: Keypair,
+    fleet_policy: Arc<FleetPolicy>,
+}
+
+impl Slasher {
+    pub fn new(state: &AppState) -> Self {
+        Self {
+            db: state.db.clone(),
+            identity: state.identity.clone(),
+            fleet_policy: Arc::clone(&state.fleet_policy),
+        }
+    }
+
+    fn sign(&self, notice: &mut PenaltyNotice) {
+        notice.signature = match self.identity.sign(&notice_payload(notice)) {
+            Ok(signature) => hex::encode(signature),
+            Err(e) => {
+                error!("Failed to sign penalty notice {}: {}", notice.penalty_id, e);
+                String::new()
+            }
+        };
+        notice.signer_public_key = hex::encode(self.identity.public().encode_protobuf());
+    }
+
+    async fn store_signature<'e, E>(&self, notice: &PenaltyNotice, db: E) -> Result<(), sqlx::Error>
+    where
+        E: sqlx::PgExecutor<'e>,
+    {
+        sqlx::query("UPDATE node_penalties SET signature = $2, signer_public_key = $3 WHERE penalty_id = $1")
+            .bind(&notice.penalty_id)
+            .bind(&notice.signature)
+            .bind(&notice.signer_public_key)
+            .execute(db)
+            .await?;
+        Ok(())
+    }
+
+    /// Records a penalty against `peer_id` for the event `reference` and
+    /// re-evaluates the node's standing. A repeat of an already penalized event
+    /// is ignored.
+    pub async fn penalize(
+        &self,
+        peer_id: &str,
+        kind: PenaltyKind,
+        reference: &str,
+        detail: &str,
+    ) -> Result<(), sqlx::Error> {
+        let mut id = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut id);
+        let mut notice = PenaltyNotice {
+            penalty_id: format!("pen-{}", hex::encode(id)),
+            peer_id: peer_id.to_string(),
+            kind: kind.as_str().to_string(),
+            points: kind.points(),
+            reference: reference.to_string(),
+            detail: detail.to_string(),
+            issued_at_ms: Utc::now().timestamp_millis(),
+            signature: String::new(),
+            signer_public_key: String::new(),
+            status: "active".to_string(),
+            appeal_reason: None,
+            resolution_note: None,
+        };
+        self.sign(&mut notice);
+
+        let inserted = sqlx::query(
+            r#"
+            INSERT INTO node_penalties
+                (penalty_id, peer_id, kind, points, reference, detail, issued_at_ms, signature, signer_public_key)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            ON CONFLICT (peer_id, kind, reference) DO NOTHING
+            "#,
+        )
+        .bind(&notice.penalty_id)
+        .bind(&notice.peer_id)
+        .bind(&notice.kind)
+        .bind(notice.points)
+        .bind(&notice.reference)
+        .bind(&notice.detail)
+        .bind(notice.issued_at_ms)
+        .bind(&notice.signature)
+        .bind(&notice.signer_public_key)
+        .execute(&self.db)
+        .await?
+        .rows_affected();
+        if inserted == 0 {
+            return Ok(());
+        }
+        warn!(
+            penalty_id = %notice.penalty_id,
+            peer_id = %peer_id,
+            kind = notice.kind,
+            points = notice.points,
+            reference,
+            "Node penalized: {}",
+            detail
+        );
+        self.refresh_standing(peer_id).await?;
+        Ok(())
+    }
+
+    /// Recomputes a node's points and applies what they mean: withholding or
+    /// releasing payouts, and starting or calling off its eviction.
+    pub async fn refresh_standing(&self, peer_id: &str) -> Result<Standing, sqlx::Error> {
+        let mut tx = self.db.begin().await?;
+        // Serializes refreshes of the same node.
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('node_standing:' || $1))")
+            .bind(peer_id)
+            .execute(&mut *tx)
+            .await?;
+        let before = sqlx::query_as::<_, Standing>(
+            "SELECT points, payouts_withheld, evicting FROM node_standing WHERE peer_id = $1",
+        )
+        .bind(peer_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .unwrap_or_default();
+        let points: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COALESCE(SUM(points), 0)::BIGINT FROM node_penalties
+            WHERE peer_id = $1
+              AND status <> 'overturned'
+              AND issued_at_ms > (EXTRACT(EPOCH FROM NOW() - make_interval(days => $2)) * 1000)::BIGINT
+            "#,
+        )
+        .bind(peer_id)
+        .bind(PENALTY_WINDOW_DAYS)
+        .fetch_one(&mut *tx)
+        .await?;
+        let after = Standing {
+            points,
+            payouts_withheld: points >= WITHHOLD_POINTS,
+            evicting: points >= EVICT_POINTS,
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO node_standing (peer_id, points, payouts_withheld, evicting, updated_at)
+            VALUES ($1, $2, $3, $4, NOW())
+            ON CONFLICT (peer_id) DO UPDATE SET
+                points = excluded.points,
+                payouts_withheld = excluded.payouts_withheld,
+                evicting = excluded.evicting,
+                updated_at = NOW()
+            "#,
+        )
+        .bind(peer_id)
+        .bind(after.points)
+        .bind(after.payouts_withheld)
+        .bind(after.evicting)
+        .execute(&mut *tx)
+        .await?;
+        if before.payouts_withheld && !after.payouts_withheld {
+            sqlx::query(
+                "UPDATE node_earnings SET earned_inr = earned_inr + withheld_inr, withheld_inr = 0, updated_at = NOW() WHERE peer_id = $1",
+            )
+            .bind(peer_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        if !before.evicting && after.evicting {
+            sqlx::query(
+                r#"
+                INSERT INTO shard_evacuations (object_cid, shard_index, peer_id)
+                SELECT object_cid, shard_index, peer_id FROM object_shards WHERE peer_id = $1
+                ON CONFLICT DO NOTHING
+                "#,
+            )
+            .bind(peer_id)
+            .execute(&mut *tx)
+            .await?;
+        } else if before.evicting && !after.evicting {
+            sqlx::query("DELETE FROM shard_evacuations WHERE peer_id = $1")
+                .bind(peer_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        if self.fleet_policy.set_slashed(peer_id, after.evicting) {
+            if after.evicting {
+                warn!("Node {} reached {} penalty points; evicting it and scheduling its shards elsewhere", peer_id, points);
+            } else {
+                info!("Node {} is back under the eviction threshold ({} points)", peer_id, points);
+            }
+        }
+        if before.payouts_withheld != after.payouts_withheld {
+            info!(
+                "Payouts for node {} are {} ({} penalty points)",
+                peer_id,
+                if after.payouts_withheld { "withheld" } else { "released" },
+                points
+            );
+        }
+        Ok(after)
+    }
+}
+
+/// Moves a node's penalties, standing and scheduled evacuations to the peer
+/// id it rotated to, re-signing its notices for the new id.
+pub async fn carry_over(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    state: &AppState,
+    old_peer_id: &str,
+    new_peer_id: &str,
+) -> Result<(), sqlx::Error> {
+    let notices = sqlx::query_as::<_, PenaltyNotice>(&format!(
+        "UPDATE node_penalties SET peer_id = $2 WHERE peer_id = $1 RETURNING {NOTICE_COLUMNS}"
+    ))
+    .bind(old_peer_id)
+    .bind(new_peer_id)
+    .fetch_all(&mut **tx)
+    .await?;
+    let slasher = Slasher::new(state);
+    for mut notice in notices {
+        slasher.sign(&mut notice);
+        slasher.store_signature(&notice, &mut **tx).await?;
+    }
+    for statement in [
+        "DELETE FROM node_standing WHERE peer_id = $2",
+        "UPDATE node_standing SET peer_id = $2 WHERE peer_id = $1",
+        "UPDATE shard_evacuations SET peer_id = $2 WHERE peer_id = $1",
+    ] {
+        sqlx::query(statement)
+            .bind(old_peer_id)
+            .bind(new_peer_id)
+            .execute(&mut **tx)
+            .await?;
+    }
+    if state.fleet_policy.set_slashed(old_peer_id, false) {
+        state.fleet_policy.set_slashed(new_peer_id, true);
+    }
+    Ok(())
+}
+
+/// Whether a node's payouts are currently withheld.
+pub async fn payouts_withheld<'e, E>(db: E, peer_id: &str) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    Ok(sqlx::query_scalar::<_, bool>("SELECT payouts_withheld FROM node_standing WHERE peer_id = $1")
+        .bind(peer_id)
+        .fetch_optional(db)
+        .await?
+        .unwrap_or(false))
+}
+
+/// Keeps standings current as points age out of the window. Runs on the
+/// leader only.
+pub struct SlashingDaemon {
+    state: Arc<AppState>,
+}
+
+impl SlashingDaemon {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn start(&self) {
+        info!("Slashing daemon initialized. Refreshing node standings every {} seconds.", STANDING_REFRESH_SECS);
+        self.restore().await;
+        loop {
+            time::sleep(Duration::from_secs(STANDING_REFRESH_SECS)).await;
+            let peers = sqlx::query_scalar::<_, String>("SELECT peer_id FROM node_standing WHERE points > 0")
+                .fetch_all(&self.state.db)
+                .await;
+            match peers {
+                Ok(peers) => {
+                    let slasher = Slasher::new(&self.state);
+                    for peer_id in peers {
+                        if let Err(e) = slasher.refresh_standing(&peer_id).await {
+                            error!("Failed to refresh standing of node {}: {}", peer_id, e);
+                        }
+                    }
+                }
+                Err(e) => error!("Failed to list penalized nodes: {}", e),
+            }
+        }
+    }
+
+    /// Reloads evictions so they hold from the moment the gateway starts, and
+    /// re-signs notices made under another key (or the old compliance HMAC)
+    /// so every notice verifies against the gateway's current identity.
+    async fn restore(&self) {
+        let slasher = Slasher::new(&self.state);
+        let signer = hex::encode(self.state.identity.public().encode_protobuf());
+        let stale = sqlx::query_as::<_, PenaltyNotice>(&format!(
+            "SELECT {NOTICE_COLUMNS} FROM node_penalties WHERE signer_public_key <> $1"
+        ))
+        .bind(&signer)
+        .fetch_all(&self.state.db)
+        .await;
+        match stale {
+            Ok(notices) => {
+                for mut notice in notices {
+                    slasher.sign(&mut notice);
+                    if let Err(e) = slasher.store_signature(&notice, &self.state.db).await {
+                        error!("Failed to re-sign penalty notice {}: {}", notice.penalty_id, e);
+                    }
+                }
+            }
+            Err(e) => error!("Failed to load penalty notices to re-sign: {}", e),
+        }
+
+        let peers = sqlx::query_scalar::<_, String>("SELECT peer_id FROM node_standing WHERE evicting")
+            .fetch_all(&self.state.db)
+            .await;
+        match peers {
+            Ok(peers) => {
+                for peer_id in peers {
+                    self.state.fleet_policy.set_slashed(&peer_id, true);
+                }
+            }
+            Err(e) => error!("Failed to restore node evictions: {}", e),
+        }
+    }
+}
+
+fn db_error(e: sqlx::Error) -> (StatusCode, String) {
+    error!("Penalty DB error: {}", e);
+    (StatusCode::INTERNAL_SERVER_ERROR, "Penalty DB Error".to_string())
+}
+
+fn check_node_secret(state: &AppState, headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+    let provided_secret = headers
+        .get("x-node-secret")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if provided_secret.is_empty() || provided_secret != state.node_shared_secret.as_str() {
+        return Err((StatusCode::UNAUTHORIZED, "Unauthorized node request".to_string()));
+    }
+    Ok(())
+}
+
+const NOTICE_COLUMNS: &str = "penalty_id, peer_id, kind, points, reference, detail, issued_at_ms, \
+                              signature, signer_public_key, status, appeal_reason, resolution_note";
+
+// ── GET /api/nodes/:peer_id/penalties ──
+// The node's standing and its penalty notices, newest first.
+pub async fn list_node_penalties(
+    State(state): State<Arc<AppState>>,
+    Path(peer_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    check_node_secret(&state, &headers)?;
+    let standing = sqlx::query_as::<_, Standing>(
+        "SELECT points, payouts_withheld, evicting FROM node_standing WHERE peer_id = $1",
+    )
+    .bind(&peer_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(db_error)?
+    .unwrap_or_default();
+    let notices = sqlx::query_as::<_, PenaltyNotice>(&format!(
+        "SELECT {NOTICE_COLUMNS} FROM node_penalties WHERE peer_id = $1 ORDER BY issued_at_ms DESC LIMIT $2"
+    ))
+    .bind(&peer_id)
+    .bind(LIST_LIMIT)
+    .fetch_all(&state.db)
+    .await
+    .map_err(db_error)?;
+    Ok(Json(serde_json::json!({
+        "peer_id": peer_id,
+        "standing": standing,
+        "withhold_points": WITHHOLD_POINTS,
+        "evict_points": EVICT_POINTS,
+        "window_days": PENALTY_WINDOW_DAYS,
+        "penalties": notices,
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct AppealRequest {
+    pub reason: String,
+    pub timestamp_ms: u64,
+    /// The node's libp2p public key, protobuf-encoded, hex.
+    pub public_key: String,
+    /// Signature over `appeal_payload`, hex.
+    pub signature: String,
+}
+
+// ── POST /api/nodes/:peer_id/penalties/:penalty_id/appeal ──
+// Contests an active penalty. Signed by the node's identity key, so only
+// the penalized node can appeal; the penalty keeps counting until ruled on.
+pub async fn appeal_penalty(
+    State(state): State<Arc<AppState>>,
+    Path((peer_id, penalty_id)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(appeal): Json<AppealRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    check_node_secret(&state, &headers)?;
+    let Ok(peer) = peer_id.parse::<PeerId>() else {
+        return Err((StatusCode::BAD_REQUEST, "Invalid peer_id".to_string()));
+    };
+    let reason = appeal.reason.trim();
+    if reason.is_empty() || reason.len() > MAX_APPEAL_REASON_BYTES {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("reason must be 1 to {} bytes", MAX_APPEAL_REASON_BYTES),
+        ));
+    }
+    let skew = Utc::now().timestamp_millis() - appeal.timestamp_ms.min(i64::MAX as u64) as i64;
+    if skew.abs() > APPEAL_MAX_SKEW_MS {
+        return Err((StatusCode::BAD_REQUEST, "Appeal timestamp is too far from the gateway's clock".to_string()));
+    }
+    let (Ok(public_key), Ok(signature)) = (hex::decode(&appeal.public_key), hex::decode(&appeal.signature)) else {
+        return Err((StatusCode::BAD_REQUEST, "public_key and signature must be hex".to_string()));
+    };
+    let signed = appeal_payload(&penalty_id, &peer_id, appeal.timestamp_ms, &appeal.reason);
+    if !verify_registration(&peer, &public_key, &signature, &signed) {
+        return Err((StatusCode::FORBIDDEN, "Appeal must be signed by the penalized node's key".to_string()));
+    }
+
+    let updated = sqlx::query_scalar::<_, String>(
+        r#"
+        UPDATE node_penalties SET status = 'appealed', appeal_reason = $3, appealed_at = NOW()
+        WHERE penalty_id = $1 AND peer_id = $2 AND status = 'active'
+        RETURNING status
+        "#,
+    )
+    .bind(&penalty_id)
+    .bind(&peer_id)
+    .bind(reason)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(db_error)?;
+    if updated.is_none() {
+        let status = sqlx::query_scalar::<_, String>(
+            "SELECT status FROM node_penalties WHERE penalty_id = $1 AND peer_id = $2",
+        )
+        .bind(&penalty_id)
+        .bind(&peer_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(db_error)?;
+        return Err(match status {
+            Some(status) => (StatusCode::CONFLICT, format!("Penalty is {} and cannot be appealed", status)),
+            None => (StatusCode::NOT_FOUND, "Penalty not found".to_string()),
+        });
+    }
+    info!(penalty_id = %penalty_id, peer_id = %peer_id, "Penalty appealed");
+    Ok(Json(serde_json::json!({ "penalty_id": penalty_id, "status": "appealed" })))
+}
+
+#[derive(Deserialize)]
+pub struct ListPenaltiesQuery {
+    /// Defaults to `appealed`, the penalties awaiting a ruling.
+    pub status: Option<String>,
+}
+
+// ── GET /api/admin/penalties ──
+pub async fn list_penalties(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<ListPenaltiesQuery>,
+) -> Result<Json<Vec<PenaltyNotice>>, (StatusCode, String)> {
+    check_admin_token(&state, &headers)?;
+    let notices = sqlx::query_as::<_, PenaltyNotice>(&format!(
+        "SELECT {NOTICE_COLUMNS} FROM node_penalties WHERE status = $1 ORDER BY issued_at_ms ASC LIMIT $2"
+    ))
+    .bind(query.status.as_deref().unwrap_or("appealed"))
+    .bind(LIST_LIMIT)
+    .fetch_all(&state.db)
+    .await
+    .map_err(db_error)?;
+    Ok(Json(notices))
+}
+
+#[derive(Deserialize)]
+pub struct ResolveRequest {
+    /// `upheld` or `overturned`.
+    pub decision: String,
+    pub note: Option<String>,
+}
+
+// ── POST /api/admin/penalties/:penalty_id/resolve ──
+// Rules on an appeal. Overturning a penalty takes its points off the node
+// at once, which may release its payouts or call off its eviction.
+pub async fn resolve_penalty(
+    State(state): State<Arc<AppState>>,
+    Path(penalty_id): Path<String>,
+    headers: HeaderMap,
+    Json(ruling): Json<ResolveRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    check_admin_token(&state, &headers)?;
+    if ruling.decision != "upheld" && ruling.decision != "overturned" {
+        return Err((StatusCode::BAD_REQUEST, "decision must be upheld or overturned".to_string()));
+    }
+    let peer_id = sqlx::query_scalar::<_, String>(
+        r#"
+        UPDATE node_penalties SET status = $2, resolution_note = $3, resolved_at = NOW()
+        WHERE penalty_id = $1 AND status = 'appealed'
+        RETURNING peer_id
+        "#,
+    )
+    .bind(&penalty_id)
+    .bind(&ruling.decision)
+    .bind(&ruling.note)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(db_error)?
+    .ok_or_else(|| (StatusCode::CONFLICT, "Penalty not found or not awaiting a ruling".to_string()))?;
+
+    let standing = Slasher::new(&state).refresh_standing(&peer_id).await.map_err(db_error)?;
+    info!(penalty_id = %penalty_id, peer_id = %peer_id, decision = %ruling.decision, "Penalty appeal resolved");
+    Ok(Json(serde_json::json!({
+        "penalty_id": penalty_id,
+        "peer_id": peer_id,
+        "status": ruling.decision,
+        "standing": standing,
+    })))
+}
@@ -0,0 +1,291 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock};
+use zeroize::Zeroizing;
+
+use crate::crypto::MetadataProtector;
+use crate::handlers::backups::check_admin_token;
+use crate::handlers::policy::API_KEY_PRINCIPAL_PREFIX;
+use crate::replication::{self, MetadataOp};
+use crate::AppState;
+
+// ── TENANTS ──
+// Organizations sharing one gateway. Each has its own metadata keys, derived
+// from the master secret and the tenant id, and its own bucket namespace: a
+// bucket is stored as `<tenant_id>:<bucket>` in every bucket-keyed table,
+// so every query made with that name is confined to the tenant. The default
+// tenant keeps plain names and the master keys, so deployments from before
+// tenants read their metadata unchanged. Users are moved between tenants
+// through /api/admin/tenants; API keys carry their tenant in the key id.
+
+pub const DEFAULT_TENANT: &str = "default";
+/// Not a valid bucket-name character, so scoped names cannot collide.
+const SCOPE_SEPARATOR: char = ':';
+/// Separates the tenant from the random part of an API key id.
+const API_KEY_TENANT_SEPARATOR: char = '.';
+const MAX_TENANT_ID_LEN: usize = 63;
+
+pub fn default_tenant() -> String {
+    DEFAULT_TENANT.to_string()
+}
+
+/// Lowercase letters, digits and dashes, not starting with a dash.
+pub fn is_valid_tenant_id(tenant_id: &str) -> bool {
+    !tenant_id.is_empty()
+        && tenant_id.len() <= MAX_TENANT_ID_LEN
+        && !tenant_id.starts_with('-')
+        && tenant_id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// The name `bucket` is stored under for `tenant_id`.
+pub fn scoped_bucket(tenant_id: &str, bucket: &str) -> String {
+    if tenant_id == DEFAULT_TENANT {
+        bucket.to_string()
+    } else {
+        format!("{}{}{}", tenant_id, SCOPE_SEPARATOR, bucket)
+    }
+}
+
+/// The tenant a stored bucket name belongs to.
+pub fn tenant_of_bucket(scoped: &str) -> &str {
+    scoped.split_once(SCOPE_SEPARATOR).map_or(DEFAULT_TENANT, |(tenant, _)| tenant)
+}
+
+/// Splits an API key id into its tenant and random part.
+pub fn split_api_key_id(key_id: &str) -> (&str, &str) {
+    key_id.split_once(API_KEY_TENANT_SEPARATOR).unwrap_or((DEFAULT_TENANT, key_id))
+}
+
+/// The tenant an API key id was minted for.
+pub fn tenant_of_api_key(key_id: &str) -> &str {
+    split_api_key_id(key_id).0
+}
+
+/// An API key id for `tenant_id` around the random part `id_hex`.
+pub fn api_key_id(tenant_id: &str, id_hex: &str) -> String {
+    if tenant_id == DEFAULT_TENANT {
+        id_hex.to_string()
+    } else {
+        format!("{}{}{}", tenant_id, API_KEY_TENANT_SEPARATOR, id_hex)
+    }
+}
+
+/// A bucket as resolved for one caller: the tenant it lives in, the name
+/// it is stored under and the keys its metadata is sealed with.
+pub struct BucketScope {
+    pub tenant_id: String,
+    /// Tenant-qualified name used by every bucket-keyed table.
+    pub name: String,
+    /// `buckets.name`: the blind index of `name`, and `objects.bucket_ref`.
+    pub masked: String,
+    pub protector: Arc<MetadataProtector>,
+}
+
+impl BucketScope {
+    pub async fn resolve(state: &AppState, principal: &str, bucket: &str) -> Result<Self, (StatusCode, String)> {
+        if bucket.is_empty() || bucket.contains(SCOPE_SEPARATOR) {
+            return Err((StatusCode::BAD_REQUEST, "InvalidBucketName".to_string()));
+        }
+        let tenant_id = tenant_of_principal(state, principal).await?;
+        let name = scoped_bucket(&tenant_id, bucket);
+        let protector = state.tenant_keys.protector(&tenant_id);
+        let masked = masked_bucket(&protector, &name);
+        Ok(Self { tenant_id, name, masked, protector })
+    }
+}
+
+/// The blind index `scoped` is stored under in `buckets.name`.
+pub fn masked_bucket(protector: &MetadataProtector, scoped: &str) -> String {
+    protector.blind_index(&format!("bucket_salt_{}", scoped))
+}
+
+/// Metadata keys per tenant, derived on first use and kept for the life of
+/// the process.
+pub struct TenantKeys {
+    master_secret: Zeroizing<String>,
+    default: Arc<MetadataProtector>,
+    derived: RwLock<HashMap<String, Arc<MetadataProtector>>>,
+}
+
+impl TenantKeys {
+    pub fn new(master_secret: &str) -> Self {
+        Self {
+            master_secret: Zeroizing::new(master_secret.to_string()),
+            default: Arc::new(MetadataProtector::new(master_secret)),
+            derived: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn protector(&self, tenant_id: &str) -> Arc<MetadataProtector> {
+        if tenant_id == DEFAULT_TENANT {
+            return Arc::clone(&self.default);
+        }
+        if let Some(protector) = self.derived.read().unwrap_or_else(PoisonError::into_inner).get(tenant_id) {
+            return Arc::clone(protector);
+        }
+        let secret = Zeroizing::new(format!("{}_tenant_{}", self.master_secret.as_str(), tenant_id));
+        let protector = Arc::new(MetadataProtector::new(&secret));
+        self.derived
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(tenant_id.to_string())
+            .or_insert(protector)
+            .clone()
+    }
+
+    /// The keys for the tenant owning the stored bucket name `scoped`.
+    pub fn for_bucket(&self, scoped: &str) -> Arc<MetadataProtector> {
+        self.protector(tenant_of_bucket(scoped))
+    }
+}
+
+/// The tenant `principal` (a user email or `apikey:<key_id>`) acts in.
+/// Users not found fall back to the default tenant, as before tenants.
+pub async fn tenant_of_principal(state: &AppState, principal: &str) -> Result<String, (StatusCode, String)> {
+    if let Some(key_id) = principal.strip_prefix(API_KEY_PRINCIPAL_PREFIX) {
+        return Ok(tenant_of_api_key(key_id).to_string());
+    }
+    let tenant = sqlx::query_scalar::<_, String>("SELECT tenant_id FROM users WHERE email = $1")
+        .bind(principal)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB Error: {}", e)))?;
+    Ok(tenant.unwrap_or_else(default_tenant))
+}
+
+/// Creates or renames a tenant; shared with the replication follower.
+pub(crate) async fn upsert_tenant(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    tenant_id: &str,
+    name: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO tenants (tenant_id, name) VALUES ($1, $2) ON CONFLICT (tenant_id) DO UPDATE SET name = excluded.name",
+    )
+    .bind(tenant_id)
+    .bind(name)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Moves a user to a tenant; shared with the replication follower.
+pub(crate) async fn assign_user(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    email: &str,
+    tenant_id: &str,
+) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query("UPDATE users SET tenant_id = $2 WHERE email = $1")
+        .bind(email)
+        .bind(tenant_id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(res.rows_affected())
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct TenantSummary {
+    pub tenant_id: String,
+    pub name: String,
+    pub users: i64,
+    pub buckets: i64,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct TenantRequest {
+    pub tenant_id: String,
+    pub name: String,
+}
+
+fn db_error(e: sqlx::Error) -> (StatusCode, String) {
+    tracing::error!("Database error in tenant admin: {}", e);
+    (StatusCode::INTERNAL_SERVER_ERROR, "Database Error".to_string())
+}
+
+// ── GET /api/admin/tenants ──
+pub async fn list_tenants(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    check_admin_token(&state, &headers)?;
+    let tenants = sqlx::query_as::<_, TenantSummary>(
+        r#"
+        SELECT t.tenant_id, t.name, t.created_at,
+               (SELECT COUNT(*) FROM users u WHERE u.tenant_id = t.tenant_id) AS users,
+               (SELECT COUNT(*) FROM buckets b WHERE b.tenant_id = t.tenant_id) AS buckets
+        FROM tenants t
+        ORDER BY t.tenant_id
+        "#,
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(db_error)?;
+    Ok(Json(tenants))
+}
+
+// ── POST /api/admin/tenants ──
+// Body: `tenant_id` (lowercase letters, digits, dashes) and a display
+// `name`; posting an existing id renames it.
+pub async fn put_tenant(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<TenantRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    check_admin_token(&state, &headers)?;
+    if !is_valid_tenant_id(&request.tenant_id) {
+        return Err((StatusCode::BAD_REQUEST, "Invalid tenant_id".to_string()));
+    }
+    let name = request.name.trim();
+    if name.is_empty() || name.chars().count() > 128 {
+        return Err((StatusCode::BAD_REQUEST, "Tenant name must be 1 to 128 characters".to_string()));
+    }
+    let mut tx = state.db.begin().await.map_err(db_error)?;
+    upsert_tenant(&mut tx, &request.tenant_id, name).await.map_err(db_error)?;
+    tx.commit().await.map_err(db_error)?;
+    replication::publish(&state, MetadataOp::UpsertTenant {
+        tenant_id: request.tenant_id.clone(),
+        name: name.to_string(),
+    })
+    .await;
+    tracing::info!(tenant_id = %request.tenant_id, "Tenant stored");
+    Ok((StatusCode::CREATED, Json(serde_json::json!({ "tenant_id": request.tenant_id, "name": name }))))
+}
+
+// ── PUT /api/admin/tenants/:tenant_id/users/:email ──
+// Moves a user into the tenant. Buckets stay with the tenant they were
+// created in; the user reaches the new tenant's buckets from now on.
+pub async fn assign_tenant_user(
+    State(state): State<Arc<AppState>>,
+    Path((tenant_id, email)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    check_admin_token(&state, &headers)?;
+    let email = email.trim().to_ascii_lowercase();
+    let mut tx = state.db.begin().await.map_err(db_error)?;
+    let exists = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM tenants WHERE tenant_id = $1")
+        .bind(&tenant_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    if exists == 0 {
+        return Err((StatusCode::NOT_FOUND, "No such tenant".to_string()));
+    }
+    if assign_user(&mut tx, &email, &tenant_id).await.map_err(db_error)? == 0 {
+        return Err((StatusCode::NOT_FOUND, "No such user".to_string()));
+    }
+    tx.commit().await.map_err(db_error)?;
+    replication::publish(&state, MetadataOp::AssignUserTenant {
+        email: email.clone(),
+        tenant_id: tenant_id.clone(),
+    })
+    .await;
+    tracing::info!(tenant_id = %tenant_id, email = %email, "User moved to tenant");
+    Ok(Json(serde_json::json!({ "email": email, "tenant_id": tenant_id })))
+}
@@ -0,0 +1,108 @@
+//! Fixtures for handler tests that run against Postgres: a scratch
+//! database per test, a fake swarm and an `AppState` around them.
+
+use axum::http::{HeaderMap, HeaderValue};
+use neuro_protocol::ChunkCommand;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::p2p::{RetrieveAck, StoreAck, SwarmRequest};
+use crate::AppState;
+
+pub(crate) const OWNER: &str = "owner@example.com";
+/// The `x-node-secret` nodes present in tests.
+pub(crate) const NODE_SECRET: &str = "test-node-shared-secret";
+
+/// A scratch database on the server in `DATABASE_URL`, migrated and
+/// dropped again by the test; `None` when no server is configured.
+pub(crate) async fn scratch_db() -> Option<(sqlx::PgPool, String)> {
+    let url = std::env::var("DATABASE_URL").ok()?;
+    let name = format!("neurostore_test_{}", hex::encode(rand::random::<[u8; 6]>()));
+    let admin = PgPoolOptions::new().max_connections(1).connect(&url).await.expect("DATABASE_URL unreachable");
+    sqlx::query(&format!("CREATE DATABASE {}", name)).execute(&admin).await.expect("create scratch database");
+    let options = PgConnectOptions::from_str(&url).expect("DATABASE_URL").database(&name);
+    let pool = PgPoolOptions::new().max_connections(10).connect_with(options).await.expect("scratch database");
+    sqlx::migrate!("./migrations").run(&pool).await.expect("migrations");
+    Some((pool, name))
+}
+
+pub(crate) async fn drop_db(pool: sqlx::PgPool, name: &str) {
+    pool.close().await;
+    let url = std::env::var("DATABASE_URL").expect("DATABASE_URL");
+    let admin = PgPoolOptions::new().max_connections(1).connect(&url).await.expect("DATABASE_URL unreachable");
+    let _ = sqlx::query(&format!("DROP DATABASE IF EXISTS {} WITH (FORCE)", name)).execute(&admin).await;
+}
+
+/// A swarm whose every node stores what it is sent and serves it back.
+pub(crate) fn swarm() -> crate::p2p::SwarmSender {
+    let (tx, mut rx) = crate::p2p::SwarmSender::channel(100);
+    tokio::spawn(async move {
+        let mut stored: HashMap<String, Vec<u8>> = HashMap::new();
+        while let Some(traced) = rx.recv().await {
+            match traced.request {
+                SwarmRequest::Store { command: ChunkCommand::Store(req), tx, .. } => {
+                    let peer_id = format!("peer-{}", stored.len());
+                    stored.insert(req.cid, req.data);
+                    let _ = tx.send(StoreAck {
+                        stored: true,
+                        peer_id,
+                        country_code: "IN".to_string(),
+                        signature_valid: true,
+                        timestamp_ms: 0,
+                    });
+                }
+                SwarmRequest::Retrieve { cid, tx, .. } => {
+                    let _ = tx.send(RetrieveAck {
+                        data: stored.get(&cid).cloned(),
+                        peer_id: "peer-0".to_string(),
+                        signature_valid: true,
+                        timestamp_ms: 0,
+                    });
+                }
+                _ => {}
+            }
+        }
+    });
+    tx
+}
+
+pub(crate) fn state(db: sqlx::PgPool) -> Arc<AppState> {
+    Arc::new(AppState {
+        db,
+        p2p_tx: swarm(),
+        edge_cache: moka::future::Cache::new(100),
+        geo: crate::geofence::GeoFenceManager::new(),
+        tenant_keys: crate::tenancy::TenantKeys::new("test-metadata-secret"),
+        jwt_secret: zeroize::Zeroizing::new("test-jwt-secret".to_string()),
+        proof_submit_token: Default::default(),
+        compliance_signing_key: Default::default(),
+        identity: libp2p::identity::Keypair::generate_ed25519(),
+        node_shared_secret: zeroize::Zeroizing::new(NODE_SECRET.to_string()),
+        config: Default::default(),
+        config_file: None,
+        replication: crate::replication::ReplicationConfig::from_env(),
+        fleet_policy: Default::default(),
+        sentinel: None,
+        compression: crate::compression::CompressionConfig::from_env(),
+        backup: crate::backup::BackupConfig::from_env(),
+        encode_pool: crate::erasure::EncodePool::new(crate::erasure::EncodePoolConfig::from_env()),
+        listing_cache: Default::default(),
+        vouchers: Default::default(),
+        connections: Default::default(),
+    })
+}
+
+pub(crate) fn auth(state: &AppState) -> HeaderMap {
+    let claims = crate::models::Claims { email: OWNER.to_string(), role: "user".to_string(), exp: usize::MAX / 2 };
+    let token = jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &claims,
+        &jsonwebtoken::EncodingKey::from_secret(state.jwt_secret.as_bytes()),
+    )
+    .expect("token");
+    let mut headers = HeaderMap::new();
+    headers.insert("Authorization", HeaderValue::from_str(&format!("Bearer {}", token)).expect("header"));
+    headers
+}
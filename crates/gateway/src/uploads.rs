@@ -0,0 +1,88 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time;
+use tracing::{error, info};
+
+use crate::handlers::s3::delete_placed_shards;
+use crate::AppState;
+
+/// How long an upload session lives past its last stored shard or commit.
+pub const UPLOAD_SESSION_TTL_HOURS: i32 = 24;
+const UPLOAD_SWEEP_SECS: u64 = 10 * 60;
+const UPLOAD_SWEEP_BATCH: i64 = 500;
+
+/// Drops expired browser upload sessions. Sessions that never committed take
+/// the shards they stored with them; committed ones only lose the session
+/// row, since their shards now belong to the registered manifest. Runs on
+/// the leader only.
+pub struct UploadSessionDaemon {
+    state: Arc<AppState>,
+}
+
+impl UploadSessionDaemon {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn start(&self) {
+        info!("Upload session sweeper initialized. Expiring sessions every {} seconds.", UPLOAD_SWEEP_SECS);
+
+        let mut interval = time::interval(Duration::from_secs(UPLOAD_SWEEP_SECS));
+        loop {
+            interval.tick().await;
+            match self.sweep().await {
+                Ok(0) => {}
+                Ok(expired) => info!("Expired {} upload sessions", expired),
+                Err(e) => error!("Upload session sweep failed: {}", e),
+            }
+        }
+    }
+
+    async fn sweep(&self) -> Result<usize, sqlx::Error> {
+        let expired = sqlx::query_as::<_, (String, bool)>(
+            r#"
+            SELECT session_id, committed_at IS NOT NULL
+            FROM upload_sessions
+            WHERE expires_at <= NOW()
+            ORDER BY expires_at
+            LIMIT $1
+            "#,
+        )
+        .bind(UPLOAD_SWEEP_BATCH)
+        .fetch_all(&self.state.db)
+        .await?;
+
+        for (session_id, committed) in &expired {
+            discard_session(&self.state, session_id, !committed).await?;
+        }
+        Ok(expired.len())
+    }
+}
+
+/// Deletes an upload session, and with `delete_shards` the shards it stored
+/// that no registered object or manifest has since claimed.
+pub(crate) async fn discard_session(state: &AppState, session_id: &str, delete_shards: bool) -> Result<(), sqlx::Error> {
+    if delete_shards {
+        let placed = sqlx::query_as::<_, (String, String)>(
+            r#"
+            SELECT shard_cid, peer_id FROM upload_session_shards u
+            WHERE session_id = $1
+              AND NOT EXISTS (SELECT 1 FROM object_shards o WHERE o.shard_cid = u.shard_cid)
+            "#,
+        )
+        .bind(session_id)
+        .fetch_all(&state.db)
+        .await?;
+        let placed = placed
+            .into_iter()
+            .map(|(cid, peer_id)| (cid, Some(peer_id).filter(|p| !p.is_empty())))
+            .collect();
+        delete_placed_shards(state, placed).await;
+    }
+    sqlx::query("DELETE FROM upload_sessions WHERE session_id = $1")
+        .bind(session_id)
+        .execute(&state.db)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,254 @@
+//! Log output selection shared by the gateway and node binaries: human or
+//! JSON lines, to stdout or a rotated file. Each binary builds its own
+//! subscriber (filters, exporters) around [`output_layer`].
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tracing::Subscriber;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, including the fields of every open span.
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum LogRotation {
+    Never,
+    Hourly,
+    #[default]
+    Daily,
+    /// Roll over once the file reaches `--log-max-size-mb`.
+    Size,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct LogOptions {
+    #[cfg_attr(feature = "clap", arg(long, value_enum, default_value_t = LogFormat::Text))]
+    pub log_format: LogFormat,
+
+    /// Write logs here instead of stdout.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub log_file: Option<PathBuf>,
+
+    #[cfg_attr(feature = "clap", arg(long, value_enum, default_value_t = LogRotation::Daily))]
+    pub log_rotation: LogRotation,
+
+    #[cfg_attr(feature = "clap", arg(long, default_value_t = 100))]
+    pub log_max_size_mb: u64,
+
+    /// Rotated files kept alongside the active one.
+    #[cfg_attr(feature = "clap", arg(long, default_value_t = 7))]
+    pub log_max_files: usize,
+}
+
+impl LogOptions {
+    /// Reads `--log-format`, `--log-file`, `--log-rotation`, `--log-max-size-mb`
+    /// and `--log-max-files` (or `LOG_FORMAT`, `LOG_FILE`, ... in the environment),
+    /// for binaries that do not parse flags with clap.
+    pub fn from_env() -> Self {
+        let log_format = match setting("log-format").as_deref() {
+            Some("json") => LogFormat::Json,
+            _ => LogFormat::Text,
+        };
+        let log_rotation = match setting("log-rotation").as_deref() {
+            Some("never") => LogRotation::Never,
+            Some("hourly") => LogRotation::Hourly,
+            Some("size") => LogRotation::Size,
+            _ => LogRotation::Daily,
+        };
+        Self {
+            log_format,
+            log_file: setting("log-file")
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from),
+            log_rotation,
+            log_max_size_mb: setting("log-max-size-mb")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(100),
+            log_max_files: setting("log-max-files")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(7),
+        }
+    }
+}
+
+/// `--name value`, `--name=value`, then the `NAME` environment variable.
+fn setting(name: &str) -> Option<String> {
+    let flag = format!("--{name}");
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            return args.next();
+        }
+        if let Some(value) = arg.strip_prefix(&flag).and_then(|v| v.strip_prefix('=')) {
+            return Some(value.to_string());
+        }
+    }
+    std::env::var(name.replace('-', "_").to_uppercase()).ok()
+}
+
+/// The formatting layer `opts` asks for. The guard flushes buffered file
+/// output when dropped; keep it alive for the life of the process.
+pub fn output_layer<S>(
+    opts: &LogOptions,
+) -> anyhow::Result<(Box<dyn Layer<S> + Send + Sync>, Option<WorkerGuard>)>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let (writer, guard) = match &opts.log_file {
+        Some(path) => {
+            let (writer, guard) = tracing_appender::non_blocking(file_writer(path, opts)?);
+            (BoxMakeWriter::new(writer), Some(guard))
+        }
+        None => (BoxMakeWriter::new(io::stdout), None),
+    };
+
+    let layer = match opts.log_format {
+        LogFormat::Text => tracing_subscriber::fmt::layer()
+            .with_target(true)
+            .with_thread_ids(true)
+            .with_ansi(opts.log_file.is_none())
+            .with_writer(writer)
+            .boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .with_thread_ids(true)
+            .with_writer(writer)
+            .boxed(),
+    };
+    Ok((layer, guard))
+}
+
+fn file_writer(path: &Path, opts: &LogOptions) -> anyhow::Result<Box<dyn Write + Send>> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    fs::create_dir_all(&dir)?;
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("--log-file must name a file"))?
+        .to_string_lossy()
+        .into_owned();
+
+    let rotation = match opts.log_rotation {
+        LogRotation::Size => {
+            let max_bytes = opts.log_max_size_mb.max(1) * 1024 * 1024;
+            return Ok(Box::new(SizeRotatingFile::open(
+                path.to_path_buf(),
+                max_bytes,
+                opts.log_max_files,
+            )?));
+        }
+        LogRotation::Never => Rotation::NEVER,
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+    };
+    let appender = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(file_name)
+        .max_log_files(opts.log_max_files.max(1))
+        .build(dir)?;
+    Ok(Box::new(appender))
+}
+
+/// `app.log` rolls to `app.log.1`, `.1` to `.2`, and so on; anything past
+/// `max_files` is dropped.
+struct SizeRotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    written: u64,
+}
+
+impl SizeRotatingFile {
+    fn open(path: PathBuf, max_bytes: u64, max_files: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_bytes,
+            max_files,
+            file,
+            written,
+        })
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{index}"));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            self.file = File::create(&self.path)?;
+        } else {
+            let _ = fs::remove_file(self.rotated(self.max_files));
+            for index in (1..self.max_files).rev() {
+                let _ = fs::rename(self.rotated(index), self.rotated(index + 1));
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+            self.file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+        }
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for SizeRotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_rotation_keeps_max_files() {
+        let dir = std::env::temp_dir().join(format!("neuro-logging-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        let mut file = SizeRotatingFile::open(path.clone(), 8, 2).unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+        file.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(fs::read_to_string(dir.join("app.log.1")).unwrap(), "third\n");
+        assert_eq!(fs::read_to_string(dir.join("app.log.2")).unwrap(), "second\n");
+        assert!(!dir.join("app.log.3").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,21 @@
+//! Arbitrary bytes as an inbound chunk request: the codec must read them
+//! without panicking or allocating past its limit, and anything it cannot
+//! decode must come back as a `ProtocolError`, never a stream error.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use libp2p::request_response::Codec;
+use libp2p::StreamProtocol;
+use neuro_node::p2p::ChunkCodec;
+
+fuzz_target!(|data: &[u8]| {
+    let protocol = StreamProtocol::new("/neurostore/chunk/2.0.0");
+    let mut io = futures::io::Cursor::new(data);
+    let request = futures::executor::block_on(ChunkCodec.read_request(&protocol, &mut io));
+    let request = request.expect("reading from memory cannot fail");
+    if let Ok(command) = request {
+        // Whatever decodes must also pass the checks the handler repeats.
+        command.validate().expect("decoded commands are valid");
+    }
+});
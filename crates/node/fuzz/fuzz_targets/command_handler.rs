@@ -0,0 +1,33 @@
+//! Commands decoded without the codec's checks, straight into the handler a
+//! disk worker runs. It must answer every one, rejecting the invalid ones,
+//! and never panic.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use libp2p::identity::Keypair;
+use neuro_node::p2p::ChunkHandler;
+use neuro_node::store::SecureBlockStore;
+use neuro_protocol::ChunkCommand;
+use std::sync::{Arc, OnceLock};
+
+static HANDLER: OnceLock<(tempfile::TempDir, ChunkHandler)> = OnceLock::new();
+
+fn handler() -> &'static ChunkHandler {
+    let (_, handler) = HANDLER.get_or_init(|| {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let store = SecureBlockStore::new(dir.path().to_str().expect("utf-8 temp path"), 1);
+        let handler = ChunkHandler {
+            store: Arc::new(store),
+            keypair: Keypair::generate_ed25519(),
+        };
+        (dir, handler)
+    });
+    handler
+}
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(command) = bincode::deserialize::<ChunkCommand>(data) {
+        let _ = handler().handle(command);
+    }
+});
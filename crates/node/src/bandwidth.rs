@@ -0,0 +1,371 @@
+//! Rate-limited, fair serving of `Retrieve` replies. Every reply is charged
+//! against a global token bucket (the operator's uplink budget) and a bucket
+//! for the requesting peer. Replies that cannot go out yet wait in a
+//! per-peer queue, and queues are drained round-robin, so a peer that floods
+//! the node only delays its own requests.
+//!
+//! The chunk itself is read on the [`crate::disk`] pool, so its size is not
+//! known when a reply is let through: the buckets are charged an estimate up
+//! front and settled with the real size once the read finishes.
+
+use crate::disk::{self, DiskJob};
+use crate::p2p::NeuroNode;
+use libp2p::{request_response::ResponseChannel, PeerId};
+use neuro_protocol::{ChunkCommand, ChunkReply, RetrieveChunkRequest, VoucheredRetrieveRequest};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use tracing::{info_span, warn};
+
+/// Requests a single peer may have waiting before new ones are refused.
+const MAX_QUEUED_PER_PEER: usize = 64;
+/// Matches the request-response timeout; a requester has given up on
+/// anything older, so it is dropped rather than sent.
+const MAX_QUEUE_WAIT: Duration = Duration::from_secs(10);
+/// How far ahead a bucket may fill while idle.
+const BURST: Duration = Duration::from_secs(1);
+/// How often queued replies are retried while there is a backlog.
+pub const DRAIN_INTERVAL: Duration = Duration::from_millis(25);
+/// Charged for a retrieve before any reply size has been seen.
+const DEFAULT_RESERVATION: u64 = 256 * 1024;
+
+/// Serve-rate limits in bytes per second; `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BandwidthConfig {
+    pub serve_rate: Option<u64>,
+    pub per_peer_rate: Option<u64>,
+}
+
+impl BandwidthConfig {
+    /// Builds limits from the megabit-per-second values used by the setup
+    /// config and CLI; zero or negative disables a limit.
+    pub fn from_mbps(serve_rate_mbps: f64, per_peer_rate_mbps: f64) -> Self {
+        let to_bytes = |mbps: f64| (mbps > 0.0).then(|| ((mbps * 125_000.0) as u64).max(1));
+        Self {
+            serve_rate: to_bytes(serve_rate_mbps),
+            per_peer_rate: to_bytes(per_peer_rate_mbps),
+        }
+    }
+}
+
+/// Running totals reported in the node status.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BandwidthCounters {
+    pub served: u64,
+    pub served_bytes: u64,
+    /// Replies that had to wait for tokens or for their turn.
+    pub deferred: u64,
+    /// Requests refused because the peer's queue was full.
+    pub dropped: u64,
+    /// Queued replies abandoned because the requester timed out or left.
+    pub expired: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerBacklog {
+    pub peer_id: String,
+    pub queued: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BandwidthStatus {
+    pub serve_rate_bytes_per_sec: Option<u64>,
+    pub per_peer_rate_bytes_per_sec: Option<u64>,
+    pub queued: usize,
+    pub backlog: Vec<PeerBacklog>,
+    #[serde(flatten)]
+    pub counters: BandwidthCounters,
+}
+
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u64) -> Self {
+        Self {
+            rate: rate as f64,
+            tokens: rate as f64 * BURST.as_secs_f64(),
+            refilled: Instant::now(),
+        }
+    }
+
+    fn capacity(&self) -> f64 {
+        self.rate * BURST.as_secs_f64()
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity());
+        self.refilled = now;
+    }
+
+    /// A reply may go out whenever the bucket is not in debt; its size is
+    /// charged afterwards, so chunks larger than the burst still get served.
+    fn ready(&self) -> bool {
+        self.tokens > 0.0
+    }
+
+    fn is_full(&self) -> bool {
+        self.tokens >= self.capacity()
+    }
+
+    /// Negative amounts refund an over-estimate, up to a full bucket.
+    fn charge(&mut self, bytes: f64) {
+        self.tokens = (self.tokens - bytes).min(self.capacity());
+    }
+}
+
+pub struct PendingRetrieve {
+    pub cid: String,
+    /// Set for `RetrieveVouchered`, which is charged to the voucher once read.
+    pub voucher: Option<String>,
+    pub channel: ResponseChannel<ChunkReply>,
+    queued_at: Instant,
+}
+
+#[derive(Default)]
+struct PeerLane {
+    bucket: Option<TokenBucket>,
+    queue: VecDeque<PendingRetrieve>,
+}
+
+#[derive(Default)]
+pub struct BandwidthScheduler {
+    config: BandwidthConfig,
+    global: Option<TokenBucket>,
+    lanes: HashMap<PeerId, PeerLane>,
+    /// Peers with queued replies, in the order they get their next turn.
+    rotation: VecDeque<PeerId>,
+    counters: BandwidthCounters,
+}
+
+impl BandwidthScheduler {
+    pub fn new(config: BandwidthConfig) -> Self {
+        Self {
+            config,
+            global: config.serve_rate.map(TokenBucket::new),
+            ..Default::default()
+        }
+    }
+
+    pub fn config(&self) -> BandwidthConfig {
+        self.config
+    }
+
+    /// Switches to new limits, keeping queued replies and their order.
+    /// Buckets start over full at the new rates.
+    pub fn reconfigure(&mut self, config: BandwidthConfig) {
+        self.config = config;
+        self.global = config.serve_rate.map(TokenBucket::new);
+        for lane in self.lanes.values_mut() {
+            lane.bucket = config.per_peer_rate.map(TokenBucket::new);
+        }
+    }
+
+    pub fn has_backlog(&self) -> bool {
+        !self.rotation.is_empty()
+    }
+
+    /// Queues a retrieve for `peer`. Returns `false` (dropping the channel,
+    /// which the requester sees as a failed request) when the peer already
+    /// has [`MAX_QUEUED_PER_PEER`] replies waiting.
+    pub fn enqueue(
+        &mut self,
+        peer: PeerId,
+        cid: String,
+        voucher: Option<String>,
+        channel: ResponseChannel<ChunkReply>,
+    ) -> bool {
+        let now = Instant::now();
+        let per_peer_rate = self.config.per_peer_rate;
+        let lane = self.lanes.entry(peer).or_insert_with(|| PeerLane {
+            bucket: per_peer_rate.map(TokenBucket::new),
+            queue: VecDeque::new(),
+        });
+        if lane.queue.len() >= MAX_QUEUED_PER_PEER {
+            self.counters.dropped += 1;
+            return false;
+        }
+        if let Some(bucket) = lane.bucket.as_mut() {
+            bucket.refill(now);
+        }
+        if let Some(global) = self.global.as_mut() {
+            global.refill(now);
+        }
+        let immediate = lane.queue.is_empty()
+            && lane.bucket.as_ref().is_none_or(TokenBucket::ready)
+            && self.global.as_ref().is_none_or(TokenBucket::ready);
+        if !immediate {
+            self.counters.deferred += 1;
+        }
+        if lane.queue.is_empty() {
+            self.rotation.push_back(peer);
+        }
+        lane.queue.push_back(PendingRetrieve {
+            cid,
+            voucher,
+            channel,
+            queued_at: now,
+        });
+        true
+    }
+
+    /// Hands out the next reply allowed to go out, taking peers in turn.
+    /// Peers whose own bucket is in debt are skipped without losing their
+    /// place; `None` means the global budget is spent or nothing is ready.
+    pub fn next_ready(&mut self) -> Option<(PeerId, PendingRetrieve)> {
+        let now = Instant::now();
+        if let Some(global) = self.global.as_mut() {
+            global.refill(now);
+            if !global.ready() {
+                return None;
+            }
+        }
+        for _ in 0..self.rotation.len() {
+            let peer = self.rotation.pop_front()?;
+            let Some(lane) = self.lanes.get_mut(&peer) else {
+                continue;
+            };
+            while lane
+                .queue
+                .front()
+                .is_some_and(|job| now.duration_since(job.queued_at) > MAX_QUEUE_WAIT)
+            {
+                lane.queue.pop_front();
+                self.counters.expired += 1;
+            }
+            if lane.queue.is_empty() {
+                continue;
+            }
+            if let Some(bucket) = lane.bucket.as_mut() {
+                bucket.refill(now);
+                if !bucket.ready() {
+                    self.rotation.push_back(peer);
+                    continue;
+                }
+            }
+            let job = lane.queue.pop_front()?;
+            if !lane.queue.is_empty() {
+                self.rotation.push_back(peer);
+            }
+            return Some((peer, job));
+        }
+        None
+    }
+
+    fn charge(&mut self, peer: &PeerId, bytes: f64) {
+        if let Some(global) = self.global.as_mut() {
+            global.charge(bytes);
+        }
+        if let Some(bucket) = self.lanes.get_mut(peer).and_then(|l| l.bucket.as_mut()) {
+            bucket.charge(bytes);
+        }
+    }
+
+    /// Charges a retrieve that is about to be read, at the average reply
+    /// size so far. Returns the amount to hand back to [`Self::settle`].
+    pub fn reserve(&mut self, peer: &PeerId) -> u64 {
+        let estimate = self
+            .counters
+            .served_bytes
+            .checked_div(self.counters.served)
+            .unwrap_or(DEFAULT_RESERVATION);
+        self.charge(peer, estimate as f64);
+        estimate
+    }
+
+    /// Corrects a reservation once the reply went out with `sent` bytes, or
+    /// refunds it when the requester went away first (`None`).
+    pub fn settle(&mut self, peer: &PeerId, reserved: u64, sent: Option<u64>) {
+        match sent {
+            Some(bytes) => {
+                self.charge(peer, bytes as f64 - reserved as f64);
+                self.counters.served += 1;
+                self.counters.served_bytes += bytes;
+            }
+            None => {
+                self.charge(peer, -(reserved as f64));
+                self.counters.expired += 1;
+            }
+        }
+    }
+
+    /// Forgets idle peers once their bucket has refilled, so a peer cannot
+    /// shed its debt by pausing briefly.
+    fn prune(&mut self) {
+        let now = Instant::now();
+        self.lanes.retain(|_, lane| {
+            if !lane.queue.is_empty() {
+                return true;
+            }
+            match lane.bucket.as_mut() {
+                Some(bucket) => {
+                    bucket.refill(now);
+                    !bucket.is_full()
+                }
+                None => false,
+            }
+        });
+    }
+
+    pub fn status(&self) -> BandwidthStatus {
+        let mut backlog: Vec<PeerBacklog> = self
+            .lanes
+            .iter()
+            .filter(|(_, lane)| !lane.queue.is_empty())
+            .map(|(peer, lane)| PeerBacklog {
+                peer_id: peer.to_string(),
+                queued: lane.queue.len(),
+            })
+            .collect();
+        backlog.sort_by_key(|b| std::cmp::Reverse(b.queued));
+        BandwidthStatus {
+            serve_rate_bytes_per_sec: self.config.serve_rate,
+            per_peer_rate_bytes_per_sec: self.config.per_peer_rate,
+            queued: backlog.iter().map(|b| b.queued).sum(),
+            backlog,
+            counters: self.counters.clone(),
+        }
+    }
+}
+
+/// Hands every queued retrieve the buckets currently allow to the disk pool.
+/// Called right after a retrieve is queued, when the pool finishes one and
+/// on [`DRAIN_INTERVAL`] while a backlog remains. Retrieves stay queued here
+/// while the pool is full.
+pub fn drain(node: &mut NeuroNode) {
+    while !node.disk.is_full() {
+        let Some((peer, job)) = node.bandwidth.next_ready() else {
+            break;
+        };
+        let span = info_span!("chunk_command", peer_id = %peer, op = "retrieve", cid = %job.cid);
+        let reserved = node.bandwidth.reserve(&peer);
+        let command = match job.voucher {
+            Some(voucher) => ChunkCommand::RetrieveVouchered(VoucheredRetrieveRequest { cid: job.cid, voucher }),
+            None => ChunkCommand::Retrieve(RetrieveChunkRequest { cid: job.cid }),
+        };
+        let submitted = node.disk.submit(DiskJob {
+            peer,
+            command,
+            channel: job.channel,
+            reserved: Some(reserved),
+            queued_at: job.queued_at,
+            span,
+        });
+        if let Err(job) = submitted {
+            let DiskJob { channel, span, .. } = *job;
+            node.bandwidth.settle(&peer, reserved, None);
+            let _entered = span.enter();
+            warn!("Disk queue full, retrieve answered busy");
+            let _ = node
+                .swarm
+                .behaviour_mut()
+                .chunk
+                .send_response(channel, disk::busy_reply());
+        }
+    }
+    node.bandwidth.prune();
+}
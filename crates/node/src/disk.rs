@@ -0,0 +1,277 @@
+//! Chunk store I/O off the swarm event loop. Chunk commands are handed to a
+//! fixed set of worker threads through a bounded queue and their replies
+//! come back on a channel the event loop polls, so a slow disk delays the
+//! commands waiting on it rather than every peer and timer. Once the queue
+//! is full a command is answered with [`ChunkReply::Busy`] straight away.
+
+use crate::bandwidth;
+use crate::p2p::{ChunkHandler, NeuroNode};
+use libp2p::{request_response::ResponseChannel, PeerId};
+use neuro_protocol::{BusyResponse, ChunkCommand, ChunkReply};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc as std_mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::{debug, warn, Span};
+
+pub const DEFAULT_WORKERS: usize = 4;
+pub const DEFAULT_QUEUE_DEPTH: usize = 256;
+/// Back-off suggested to requesters turned away with a Busy reply.
+const BUSY_RETRY_AFTER: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiskConfig {
+    /// Threads doing store I/O.
+    pub workers: usize,
+    /// Commands that may wait for a worker before new ones are refused.
+    pub queue_depth: usize,
+}
+
+impl Default for DiskConfig {
+    fn default() -> Self {
+        Self {
+            workers: DEFAULT_WORKERS,
+            queue_depth: DEFAULT_QUEUE_DEPTH,
+        }
+    }
+}
+
+impl DiskConfig {
+    fn capacity(&self) -> usize {
+        self.workers.max(1) + self.queue_depth
+    }
+}
+
+pub struct DiskJob {
+    pub peer: PeerId,
+    pub command: ChunkCommand,
+    pub channel: ResponseChannel<ChunkReply>,
+    /// Bytes charged to the bandwidth scheduler ahead of a retrieve, settled
+    /// once the reply size is known.
+    pub reserved: Option<u64>,
+    pub queued_at: Instant,
+    pub span: Span,
+}
+
+pub struct DiskDone {
+    pub peer: PeerId,
+    pub channel: ResponseChannel<ChunkReply>,
+    pub reply: ChunkReply,
+    pub reserved: Option<u64>,
+    pub queued_at: Instant,
+    pub span: Span,
+}
+
+/// Running totals reported in the node status.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiskCounters {
+    pub completed: u64,
+    /// Commands answered with Busy because the queue was full.
+    pub busy: u64,
+    pub peak_queued: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiskStatus {
+    pub workers: usize,
+    pub queue_depth: usize,
+    /// Commands waiting for a worker.
+    pub queued: usize,
+    /// Commands a worker is running.
+    pub active: usize,
+    #[serde(flatten)]
+    pub counters: DiskCounters,
+}
+
+pub struct DiskPool {
+    handler: ChunkHandler,
+    config: DiskConfig,
+    jobs: std_mpsc::SyncSender<DiskJob>,
+    done_tx: mpsc::UnboundedSender<DiskDone>,
+    done_rx: mpsc::UnboundedReceiver<DiskDone>,
+    /// Submitted and not yet handed back: waiting or running.
+    pending: usize,
+    active: Arc<AtomicUsize>,
+    counters: DiskCounters,
+}
+
+impl DiskPool {
+    pub fn new(handler: ChunkHandler, config: DiskConfig) -> std::io::Result<Self> {
+        let (done_tx, done_rx) = mpsc::unbounded_channel();
+        let active = Arc::new(AtomicUsize::new(0));
+        let jobs = spawn_workers(&handler, config, &done_tx, &active)?;
+        Ok(Self {
+            handler,
+            config,
+            jobs,
+            done_tx,
+            done_rx,
+            pending: 0,
+            active,
+            counters: DiskCounters::default(),
+        })
+    }
+
+    /// Serves commands inline, for callers outside the event loop such as
+    /// `neuro-node selftest`.
+    pub fn handler(&self) -> &ChunkHandler {
+        &self.handler
+    }
+
+    pub fn config(&self) -> DiskConfig {
+        self.config
+    }
+
+    /// Starts workers for the new limits. The old workers finish what is
+    /// already queued for them and exit; their replies still arrive here.
+    pub fn reconfigure(&mut self, config: DiskConfig) -> std::io::Result<()> {
+        if config != self.config {
+            self.jobs = spawn_workers(&self.handler, config, &self.done_tx, &self.active)?;
+            self.config = config;
+        }
+        Ok(())
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.pending >= self.config.capacity()
+    }
+
+    /// Queues `job` for a worker, or hands it back when the queue is full so
+    /// the caller can answer with [`busy_reply`].
+    pub fn submit(&mut self, job: DiskJob) -> Result<(), Box<DiskJob>> {
+        if self.is_full() {
+            self.counters.busy += 1;
+            return Err(Box::new(job));
+        }
+        match self.jobs.try_send(job) {
+            Ok(()) => {
+                self.pending += 1;
+                self.counters.peak_queued = self.counters.peak_queued.max(self.queued());
+                Ok(())
+            }
+            Err(std_mpsc::TrySendError::Full(job) | std_mpsc::TrySendError::Disconnected(job)) => {
+                self.counters.busy += 1;
+                Err(Box::new(job))
+            }
+        }
+    }
+
+    /// Next finished command. Never resolves to `None` while the pool is
+    /// alive, since it holds a sender itself.
+    pub async fn next_done(&mut self) -> Option<DiskDone> {
+        let done = self.done_rx.recv().await?;
+        self.pending = self.pending.saturating_sub(1);
+        self.counters.completed += 1;
+        Some(done)
+    }
+
+    fn queued(&self) -> usize {
+        self.pending.saturating_sub(self.active.load(Ordering::Relaxed))
+    }
+
+    pub fn status(&self) -> DiskStatus {
+        DiskStatus {
+            workers: self.config.workers.max(1),
+            queue_depth: self.config.queue_depth,
+            queued: self.queued(),
+            active: self.active.load(Ordering::Relaxed),
+            counters: self.counters.clone(),
+        }
+    }
+}
+
+fn spawn_workers(
+    handler: &ChunkHandler,
+    config: DiskConfig,
+    done_tx: &mpsc::UnboundedSender<DiskDone>,
+    active: &Arc<AtomicUsize>,
+) -> std::io::Result<std_mpsc::SyncSender<DiskJob>> {
+    let (jobs_tx, jobs_rx) = std_mpsc::sync_channel(config.capacity());
+    let jobs_rx = Arc::new(Mutex::new(jobs_rx));
+    for i in 0..config.workers.max(1) {
+        let handler = handler.clone();
+        let jobs_rx = jobs_rx.clone();
+        let done_tx = done_tx.clone();
+        let active = active.clone();
+        std::thread::Builder::new()
+            .name(format!("neuro-disk-{i}"))
+            .spawn(move || worker(handler, jobs_rx, done_tx, active))?;
+    }
+    Ok(jobs_tx)
+}
+
+fn worker(
+    handler: ChunkHandler,
+    jobs: Arc<Mutex<std_mpsc::Receiver<DiskJob>>>,
+    done_tx: mpsc::UnboundedSender<DiskDone>,
+    active: Arc<AtomicUsize>,
+) {
+    loop {
+        // The lock is only held while waiting, so idle workers queue up on
+        // it and a job goes to whichever is first.
+        let next = jobs.lock().ok().and_then(|rx| rx.recv().ok());
+        let Some(job) = next else {
+            return;
+        };
+        active.fetch_add(1, Ordering::Relaxed);
+        let reply = job.span.in_scope(|| handler.handle(job.command));
+        active.fetch_sub(1, Ordering::Relaxed);
+        let done = DiskDone {
+            peer: job.peer,
+            channel: job.channel,
+            reply,
+            reserved: job.reserved,
+            queued_at: job.queued_at,
+            span: job.span,
+        };
+        if done_tx.send(done).is_err() {
+            return;
+        }
+    }
+}
+
+pub fn busy_reply() -> ChunkReply {
+    ChunkReply::Busy(BusyResponse {
+        retry_after_ms: BUSY_RETRY_AFTER.as_millis() as u64,
+    })
+}
+
+/// Sends a finished command's reply and settles any bandwidth reserved for
+/// it. A finished retrieve frees a worker, so queued ones are drained too.
+pub fn finish(node: &mut NeuroNode, done: DiskDone) {
+    let DiskDone {
+        peer,
+        channel,
+        reply,
+        reserved,
+        queued_at,
+        span,
+    } = done;
+    let _entered = span.enter();
+    let waited_ms = queued_at.elapsed().as_millis() as u64;
+    let bytes = match &reply {
+        ChunkReply::Retrieve(r) => r.data.len() as u64,
+        _ => 0,
+    };
+    let sent = node
+        .swarm
+        .behaviour_mut()
+        .chunk
+        .send_response(channel, reply)
+        .is_ok();
+    match (reserved, sent) {
+        (Some(reserved), true) => {
+            node.bandwidth.settle(&peer, reserved, Some(bytes));
+            debug!(peer = %peer, bytes, waited_ms, "Served retrieve");
+        }
+        (Some(reserved), false) => {
+            node.bandwidth.settle(&peer, reserved, None);
+            warn!(peer = %peer, waited_ms, "Requester gone before retrieve was served");
+        }
+        (None, _) => debug!(peer = %peer, waited_ms, "Served chunk command"),
+    }
+    if node.bandwidth.has_backlog() {
+        bandwidth::drain(node);
+    }
+}
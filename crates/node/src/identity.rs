@@ -0,0 +1,406 @@
+//! The node identity key in --storage-path, and `neuro-node keygen` and
+//! `rotate`. The key is written as plain protobuf unless the node is given
+//! a secret to seal it with: a passphrase (`--key-passphrase-file` or
+//! `NEURO_NODE_KEY_PASSPHRASE`, stretched with Argon2id) or a random secret
+//! kept in the OS keyring (`--key-keyring`). A sealed key file is a small
+//! JSON envelope around the AES-256-GCM encrypted protobuf, and is opened
+//! with whichever secret its envelope names.
+//!
+//! `rotate` replaces the key with a new one and writes a rotation
+//! announcement signed by both keys to --storage-path, sending it to a
+//! gateway with `--gateway` so the node's record moves to the new peer id.
+
+use crate::register;
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{anyhow, bail, Context};
+use argon2::Argon2;
+use libp2p::identity::Keypair;
+use neuro_protocol::{rotation_payload, KeyRotation};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+use zeroize::Zeroizing;
+
+const PASSPHRASE_ENV: &str = "NEURO_NODE_KEY_PASSPHRASE";
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "neurostore-node";
+const ENVELOPE_FORMAT: &str = "neuro-node-key/1";
+const ROTATION_FILE: &str = "key_rotation.json";
+
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct KeyProtection {
+    /// Seal the identity key under the passphrase in this file. The
+    /// `NEURO_NODE_KEY_PASSPHRASE` environment variable works too.
+    #[arg(long, global = true, conflicts_with = "key_keyring")]
+    key_passphrase_file: Option<PathBuf>,
+
+    /// Seal the identity key under a random secret kept in the OS keyring.
+    #[arg(long, global = true, default_value_t = false)]
+    key_keyring: bool,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct KeygenArgs {
+    /// Re-write the existing key under the protection given now (none
+    /// writes it in plain), instead of generating a new one.
+    #[arg(long, default_value_t = false)]
+    rewrap: bool,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct RotateArgs {
+    /// Gateway to announce the rotation to, e.g. `https://gateway.example.com`.
+    #[arg(long)]
+    gateway: Option<String>,
+    /// Shared node secret; falls back to `NODE_SHARED_SECRET`.
+    #[arg(long)]
+    node_secret: Option<String>,
+    /// Send the announcement left by an earlier rotation again instead of
+    /// rotating.
+    #[arg(long, default_value_t = false, requires = "gateway")]
+    resend: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    format: String,
+    /// `argon2id` (passphrase) or `keyring`.
+    kdf: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+enum Secret {
+    Passphrase(Zeroizing<String>),
+    Keyring(Zeroizing<Vec<u8>>),
+}
+
+pub fn identity_key_path(storage_path: &str) -> PathBuf {
+    PathBuf::from(storage_path).join("node_identity.key")
+}
+
+impl KeyProtection {
+    fn passphrase(&self) -> anyhow::Result<Option<Zeroizing<String>>> {
+        if let Some(path) = &self.key_passphrase_file {
+            let raw = Zeroizing::new(
+                fs::read_to_string(path).with_context(|| format!("cannot read {}", path.display()))?,
+            );
+            let passphrase = raw.trim_end_matches(['\r', '\n']);
+            if passphrase.is_empty() {
+                bail!("{} is empty", path.display());
+            }
+            return Ok(Some(Zeroizing::new(passphrase.to_string())));
+        }
+        Ok(std::env::var(PASSPHRASE_ENV)
+            .ok()
+            .filter(|p| !p.is_empty())
+            .map(Zeroizing::new))
+    }
+
+    /// The secret a key written now is sealed with, if any; the keyring
+    /// secret is created on first use.
+    fn sealing_secret(&self, key_path: &Path) -> anyhow::Result<Option<Secret>> {
+        if let Some(passphrase) = self.passphrase()? {
+            return Ok(Some(Secret::Passphrase(passphrase)));
+        }
+        if self.key_keyring {
+            return Ok(Some(Secret::Keyring(keyring_secret(key_path, true)?)));
+        }
+        Ok(None)
+    }
+}
+
+/// Reads the identity key in --storage-path, generating one on first start.
+pub fn load_or_create(storage_path: &str, protection: &KeyProtection) -> anyhow::Result<Keypair> {
+    let key_path = identity_key_path(storage_path);
+    if key_path.exists() {
+        let bytes = fs::read(&key_path)?;
+        if !is_sealed(&bytes) && (protection.key_keyring || protection.passphrase()?.is_some()) {
+            warn!(
+                path = %key_path.display(),
+                "Identity key is stored unencrypted; run `neuro-node keygen --rewrap` to seal it"
+            );
+        }
+        return decode(&bytes, &key_path, protection);
+    }
+
+    let keypair = Keypair::generate_ed25519();
+    write(&key_path, &keypair, protection)?;
+    Ok(keypair)
+}
+
+fn is_sealed(bytes: &[u8]) -> bool {
+    bytes.first() == Some(&b'{')
+}
+
+/// Opens a key file's contents, read from `key_path`.
+pub fn decode(bytes: &[u8], key_path: &Path, protection: &KeyProtection) -> anyhow::Result<Keypair> {
+    if !is_sealed(bytes) {
+        return Ok(Keypair::from_protobuf_encoding(bytes)?);
+    }
+    let envelope: Envelope = serde_json::from_slice(bytes)
+        .with_context(|| format!("{} is not a sealed identity key", key_path.display()))?;
+    if envelope.format != ENVELOPE_FORMAT {
+        bail!("{} has unknown key format {}", key_path.display(), envelope.format);
+    }
+    let secret = match envelope.kdf.as_str() {
+        "argon2id" => Secret::Passphrase(protection.passphrase()?.ok_or_else(|| {
+            anyhow!(
+                "{} is sealed with a passphrase; pass --key-passphrase-file or set {PASSPHRASE_ENV}",
+                key_path.display()
+            )
+        })?),
+        "keyring" => Secret::Keyring(keyring_secret(key_path, false)?),
+        other => bail!("{} is sealed with unknown kdf {other}", key_path.display()),
+    };
+    let salt = hex::decode(&envelope.salt)?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(derive_key(&secret, &salt)?.as_ref()));
+    let nonce = hex::decode(&envelope.nonce)?;
+    if nonce.len() != 12 {
+        bail!("{} has a malformed nonce", key_path.display());
+    }
+    let plain = Zeroizing::new(
+        cipher
+            .decrypt(Nonce::from_slice(&nonce), hex::decode(&envelope.ciphertext)?.as_ref())
+            .map_err(|_| anyhow!("cannot open {}: wrong passphrase or keyring secret", key_path.display()))?,
+    );
+    Ok(Keypair::from_protobuf_encoding(&plain)?)
+}
+
+fn derive_key(secret: &Secret, salt: &[u8]) -> anyhow::Result<Zeroizing<[u8; 32]>> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    match secret {
+        Secret::Passphrase(passphrase) => Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+            .map_err(|e| anyhow!("argon2 key derivation failed: {e}"))?,
+        Secret::Keyring(bytes) if bytes.len() == 32 => key.copy_from_slice(bytes),
+        Secret::Keyring(_) => bail!("keyring secret for the identity key is malformed"),
+    }
+    Ok(key)
+}
+
+/// Writes `keypair` to `key_path`, sealed if `protection` names a secret.
+/// The file is replaced in one rename and readable only by its owner.
+fn write(key_path: &Path, keypair: &Keypair, protection: &KeyProtection) -> anyhow::Result<()> {
+    let plain = Zeroizing::new(keypair.to_protobuf_encoding()?);
+    let contents = match protection.sealing_secret(key_path)? {
+        None => plain.to_vec(),
+        Some(secret) => {
+            let mut salt = [0u8; 16];
+            let mut nonce = [0u8; 12];
+            rand::thread_rng().fill_bytes(&mut salt);
+            rand::thread_rng().fill_bytes(&mut nonce);
+            let (kdf, salt) = match secret {
+                Secret::Passphrase(_) => ("argon2id", hex::encode(salt)),
+                Secret::Keyring(_) => ("keyring", String::new()),
+            };
+            let key = derive_key(&secret, &hex::decode(&salt)?)?;
+            let ciphertext = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key.as_ref()))
+                .encrypt(Nonce::from_slice(&nonce), plain.as_ref())
+                .map_err(|_| anyhow!("identity key encryption failed"))?;
+            serde_json::to_vec_pretty(&Envelope {
+                format: ENVELOPE_FORMAT.to_string(),
+                kdf: kdf.to_string(),
+                salt,
+                nonce: hex::encode(nonce),
+                ciphertext: hex::encode(ciphertext),
+            })?
+        }
+    };
+
+    let tmp = key_path.with_extension("key.tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    std::io::Write::write_all(&mut options.open(&tmp)?, &contents)?;
+    fs::rename(&tmp, key_path)?;
+    Ok(())
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_secret(key_path: &Path, create: bool) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    let account = fs::canonicalize(key_path.parent().unwrap_or(Path::new(".")))?
+        .join(key_path.file_name().unwrap_or_default())
+        .to_string_lossy()
+        .into_owned();
+    let entry = keyring::Entry::new(KEYRING_SERVICE, &account)?;
+    match entry.get_password() {
+        Ok(hex_secret) => Ok(Zeroizing::new(hex::decode(hex_secret.trim())?)),
+        Err(keyring::Error::NoEntry) if create => {
+            let mut secret = Zeroizing::new(vec![0u8; 32]);
+            rand::thread_rng().fill_bytes(&mut secret);
+            entry.set_password(&hex::encode(secret.as_slice()))?;
+            Ok(secret)
+        }
+        Err(keyring::Error::NoEntry) => Err(anyhow!(
+            "no keyring entry for service={KEYRING_SERVICE} account={account}"
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_secret(_key_path: &Path, _create: bool) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    Err(anyhow!("--key-keyring requires neuro-node built with the `keyring` feature"))
+}
+
+pub fn keygen(storage_path: &str, protection: &KeyProtection, args: &KeygenArgs) -> anyhow::Result<()> {
+    fs::create_dir_all(storage_path)?;
+    let key_path = identity_key_path(storage_path);
+    let keypair = match (key_path.exists(), args.rewrap) {
+        (true, true) => decode(&fs::read(&key_path)?, &key_path, protection)?,
+        (true, false) => bail!(
+            "{} already exists; use `neuro-node rotate` to replace it or --rewrap to change its protection",
+            key_path.display()
+        ),
+        (false, true) => bail!("{} does not exist; nothing to rewrap", key_path.display()),
+        (false, false) => Keypair::generate_ed25519(),
+    };
+    write(&key_path, &keypair, protection)?;
+    let sealed = is_sealed(&fs::read(&key_path)?);
+    println!(
+        "identity peer_id={} path={} sealed={sealed}",
+        keypair.public().to_peer_id(),
+        key_path.display()
+    );
+    Ok(())
+}
+
+/// Replaces the identity key, keeping the old one next to it, and writes
+/// (and with `--gateway`, sends) the signed rotation announcement.
+pub async fn rotate(storage_path: &str, protection: &KeyProtection, args: &RotateArgs) -> anyhow::Result<()> {
+    let rotation_path = PathBuf::from(storage_path).join(ROTATION_FILE);
+    if args.resend {
+        let rotation: KeyRotation = serde_json::from_slice(
+            &fs::read(&rotation_path).with_context(|| format!("no rotation to resend at {}", rotation_path.display()))?,
+        )?;
+        return announce(&rotation, args).await;
+    }
+
+    let key_path = identity_key_path(storage_path);
+    let old = decode(
+        &fs::read(&key_path).with_context(|| format!("no node identity at {}", key_path.display()))?,
+        &key_path,
+        protection,
+    )?;
+    let new = Keypair::generate_ed25519();
+    let rotation = sign_rotation(&old, &new, chrono::Utc::now().timestamp_millis().max(0) as u64)?;
+
+    let retired = key_path.with_file_name(format!("node_identity.{}.retired.key", rotation.old_peer_id));
+    fs::write(&rotation_path, serde_json::to_vec_pretty(&rotation)?)?;
+    fs::rename(&key_path, &retired)?;
+    if let Err(e) = write(&key_path, &new, protection) {
+        fs::rename(&retired, &key_path)?;
+        return Err(e);
+    }
+    println!(
+        "rotated old_peer_id={} new_peer_id={} retired_key={} announcement={}",
+        rotation.old_peer_id,
+        rotation.new_peer_id,
+        retired.display(),
+        rotation_path.display()
+    );
+    println!("restart the node to serve under the new peer id");
+
+    if args.gateway.is_some() {
+        announce(&rotation, args)
+            .await
+            .context("rotation is done locally; send it again with `neuro-node rotate --resend --gateway …`")?;
+    }
+    Ok(())
+}
+
+fn sign_rotation(old: &Keypair, new: &Keypair, timestamp_ms: u64) -> anyhow::Result<KeyRotation> {
+    let old_peer_id = old.public().to_peer_id().to_string();
+    let new_peer_id = new.public().to_peer_id().to_string();
+    let payload = rotation_payload(&old_peer_id, &new_peer_id, timestamp_ms);
+    Ok(KeyRotation {
+        old_public_key: hex::encode(old.public().encode_protobuf()),
+        old_signature: hex::encode(old.sign(&payload)?),
+        new_public_key: hex::encode(new.public().encode_protobuf()),
+        new_signature: hex::encode(new.sign(&payload)?),
+        old_peer_id,
+        new_peer_id,
+        timestamp_ms,
+    })
+}
+
+async fn announce(rotation: &KeyRotation, args: &RotateArgs) -> anyhow::Result<()> {
+    let gateway = args
+        .gateway
+        .as_deref()
+        .ok_or_else(|| anyhow!("--gateway is required to announce a rotation"))?
+        .trim_end_matches('/');
+    let secret = register::node_secret(args.node_secret.as_deref())?;
+    let client = register::client()?;
+    let resp: serde_json::Value = register::post_json(
+        &client,
+        &format!("{gateway}/api/nodes/rotate"),
+        &secret,
+        &serde_json::to_value(rotation)?,
+    )
+    .await?
+    .json()
+    .await?;
+    println!("announced rotation to {gateway}");
+    println!("{}", serde_json::to_string_pretty(&resp)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("neuro-node-identity-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn sealed_keys_open_only_with_their_passphrase() {
+        let dir = temp_dir("sealed");
+        let passphrase = dir.join("passphrase");
+        fs::write(&passphrase, "correct horse\n").unwrap();
+        let protection = KeyProtection {
+            key_passphrase_file: Some(passphrase.clone()),
+            key_keyring: false,
+        };
+        let storage = dir.to_str().unwrap();
+        let created = load_or_create(storage, &protection).unwrap();
+        let key_path = identity_key_path(storage);
+        let bytes = fs::read(&key_path).unwrap();
+        assert!(is_sealed(&bytes));
+        assert_eq!(load_or_create(storage, &protection).unwrap().public(), created.public());
+
+        fs::write(&passphrase, "wrong horse").unwrap();
+        assert!(decode(&bytes, &key_path, &protection).is_err());
+
+        // A plain key still loads, whatever protection is configured.
+        let plain = Keypair::generate_ed25519();
+        write(&key_path, &plain, &KeyProtection::default()).unwrap();
+        assert_eq!(load_or_create(storage, &protection).unwrap().public(), plain.public());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rotations_are_signed_by_both_keys() {
+        let (old, new) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+        let rotation = sign_rotation(&old, &new, 1_700_000_000_000).unwrap();
+        assert!(rotation.verify());
+
+        let mut forged = rotation.clone();
+        forged.new_peer_id = Keypair::generate_ed25519().public().to_peer_id().to_string();
+        assert!(!forged.verify());
+        let mut replayed = rotation;
+        replayed.timestamp_ms += 1;
+        assert!(!replayed.verify());
+    }
+}
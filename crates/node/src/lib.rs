@@ -0,0 +1,14 @@
+//! Storage node internals shared by the `neuro-node` binary and in-process
+//! harnesses (see `crates/uploader/tests/e2e.rs`).
+
+pub mod bandwidth;
+pub mod disk;
+pub mod logging;
+pub mod maintenance;
+pub mod p2p;
+pub mod repair;
+pub mod settings;
+pub mod status;
+pub mod store;
+pub mod tombstone;
+pub mod vouchers;
@@ -0,0 +1,53 @@
+//! Log output for the node binary. Formatting and file rotation are shared
+//! with the gateway through `neuro-logging`; the node adds a level filter
+//! that can be swapped at runtime.
+
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+pub use neuro_logging::{LogFormat, LogOptions, LogRotation};
+
+/// Returned by [`init`]. Keep it alive for the life of the process so
+/// buffered file output is flushed on exit.
+pub struct LogHandle {
+    _guard: Option<WorkerGuard>,
+    pub filter: LogFilter,
+}
+
+/// Swaps the level filter of the installed subscriber at runtime.
+#[derive(Clone, Debug)]
+pub struct LogFilter {
+    handle: reload::Handle<EnvFilter, Registry>,
+    default: String,
+}
+
+impl LogFilter {
+    /// Applies `directives` (e.g. `debug` or `info,neuro_node=trace`), or
+    /// goes back to the startup filter when `None`.
+    pub fn set(&self, directives: Option<&str>) -> anyhow::Result<()> {
+        let filter = EnvFilter::try_new(directives.unwrap_or(&self.default))?;
+        self.handle.reload(filter)?;
+        Ok(())
+    }
+}
+
+/// Installs the global subscriber.
+pub fn init(opts: &LogOptions) -> anyhow::Result<LogHandle> {
+    let default = std::env::var(EnvFilter::DEFAULT_ENV)
+        .ok()
+        .filter(|d| EnvFilter::try_new(d).is_ok())
+        .unwrap_or_else(|| "info".to_string());
+    let (filter, handle) = reload::Layer::new(EnvFilter::new(&default));
+
+    let (layer, guard) = neuro_logging::output_layer(opts)?;
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(layer)
+        .try_init()?;
+    Ok(LogHandle {
+        _guard: guard,
+        filter: LogFilter { handle, default },
+    })
+}
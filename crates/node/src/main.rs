@@ -1,10 +1,14 @@
 // #![windows_subsystem = "windows"]
-mod p2p;
-mod store;
-
 use anyhow::Context;
 use clap::Parser;
-use p2p::{build_node, drive_node, parse_listen_multiaddr};
+use neuro_node::bandwidth::{BandwidthConfig, BandwidthScheduler};
+use neuro_node::disk::{self, DiskConfig};
+use neuro_node::logging::{self, LogFilter, LogOptions};
+use neuro_node::maintenance;
+use neuro_node::p2p::{build_node, drive_node, load_swarm_key, parse_listen_multiaddr};
+use neuro_node::settings::NodeSettings;
+use neuro_node::status;
+use neuro_node::store::SecureBlockStore;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashSet,
@@ -13,10 +17,16 @@ use std::{
     path::{Path, PathBuf},
     str::FromStr,
     sync::Arc,
+    time::Duration,
 };
-use store::SecureBlockStore;
-use tokio::sync::oneshot;
-use tracing::info;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{info, warn};
+
+mod identity;
+mod penalties;
+mod register;
+mod selftest;
+mod snapshot;
 
 // --- CREATOR SIGNATURE ---
 // Base64 encoded payload proving original authorship by Janyshh
@@ -27,27 +37,68 @@ const _CREATOR_SIG: &[u8] = b"SmFueXNoaCAtIE9yaWdpbmFsIENyZWF0b3Igb2YgTmV1cm9TdG
 
 #[command(name = "neuro-node", version, about = "Decentralized storage node")]
 struct Args {
-    #[arg(long, default_value = "./node-data")]
+    #[arg(long, default_value = "./node-data", global = true)]
     storage_path: String,
 
-    #[arg(long, default_value_t = 50)]
+    #[arg(long, default_value_t = 50, global = true)]
     max_gb: u64,
 
-    #[arg(long, default_value = "/ip4/0.0.0.0/tcp/9000")]
+    #[arg(long, default_value = "/ip4/0.0.0.0/tcp/9000", global = true)]
     listen: String,
 
-    #[arg(long, num_args = 0..)]
+    #[arg(long, num_args = 0.., global = true)]
     bootstrap: Vec<String>,
 
     #[arg(long, num_args = 0..)]
     allow_peer: Vec<String>,
 
+    /// Peers refused outright, even if allowlisted.
+    #[arg(long, num_args = 0..)]
+    deny_peer: Vec<String>,
+
     #[arg(long, default_value_t = false)]
     interactive_setup: bool,
 
     #[arg(long)]
     relay_url: Option<String>,
 
+    /// Join a private swarm: only peers holding the same pre-shared key
+    /// (go-ipfs `swarm.key` format) can establish connections.
+    #[arg(long, global = true)]
+    swarm_key: Option<PathBuf>,
+
+    /// Seconds between full scrubs of stored chunks; corrupt ones are
+    /// re-fetched from other replica holders. 0 disables scrubbing.
+    #[arg(long, default_value_t = 6 * 60 * 60)]
+    scrub_interval_secs: u64,
+
+    /// Days a deletion tombstone is kept and passed on to peers that were
+    /// offline when it was issued.
+    #[arg(long, default_value_t = 30)]
+    tombstone_retention_days: u64,
+
+    /// Upper bound on total retrieve traffic served, in megabits per
+    /// second. 0 means unlimited.
+    #[arg(long, default_value_t = 0.0)]
+    serve_rate_mbps: f64,
+
+    /// Upper bound on retrieve traffic served to any single peer, in
+    /// megabits per second. 0 means unlimited.
+    #[arg(long, default_value_t = 0.0)]
+    per_peer_rate_mbps: f64,
+
+    /// Threads doing chunk store reads and writes.
+    #[arg(long, default_value_t = disk::DEFAULT_WORKERS)]
+    disk_workers: usize,
+
+    /// Chunk commands that may wait for a disk thread; once this many are
+    /// waiting, new ones are answered busy.
+    #[arg(long, default_value_t = disk::DEFAULT_QUEUE_DEPTH)]
+    disk_queue_depth: usize,
+
+    /// Setup config to use instead of the per-user default. While the node
+    /// runs, edits to it (or SIGHUP on Unix) apply capacity, peer lists,
+    /// rate limits and log level without a restart.
     #[arg(long)]
     setup_config_path: Option<String>,
 
@@ -59,6 +110,79 @@ struct Args {
 
     #[arg(long, default_value_t = false)]
     print_peer_id: bool,
+
+    /// Start in maintenance: refuse new shards but keep serving retrieves
+    /// and audits until `neuro-node maintenance off`.
+    #[arg(long, default_value_t = false)]
+    maintenance: bool,
+
+    /// Gateway base URL to redeem bandwidth vouchers with once they expire.
+    /// Without it, served voucher bytes stay recorded in --storage-path.
+    #[arg(long)]
+    redeem_gateway: Option<String>,
+
+    #[command(flatten)]
+    log: LogOptions,
+
+    #[command(flatten)]
+    key: identity::KeyProtection,
+
+    #[command(subcommand)]
+    command: Option<NodeCommand>,
+}
+
+impl Args {
+    fn disk_config(&self) -> DiskConfig {
+        DiskConfig {
+            workers: self.disk_workers,
+            queue_depth: self.disk_queue_depth,
+        }
+    }
+}
+
+#[derive(clap::Subcommand, Debug, Clone)]
+enum NodeCommand {
+    /// Check storage, identity, networking and the chunk protocol, then exit.
+    /// Uses --storage-path, --max-gb, --listen and --bootstrap as given;
+    /// saved interactive setup is not consulted.
+    Selftest(selftest::SelftestArgs),
+    /// Print the status snapshot a running node keeps in --storage-path.
+    Status,
+    /// Package the chunks, want list and identity in --storage-path into a
+    /// snapshot for moving the node. The node must be stopped.
+    Export(snapshot::ExportArgs),
+    /// Restore a snapshot into an empty --storage-path, verifying every
+    /// chunk.
+    Import(snapshot::ImportArgs),
+    /// Register this node with a gateway, proving ownership of its peer id
+    /// with the identity key in --storage-path.
+    Register(register::RegisterArgs),
+    /// Generate the identity key in --storage-path, sealed under
+    /// --key-passphrase-file or --key-keyring when given, or re-seal the
+    /// existing one with --rewrap.
+    Keygen(identity::KeygenArgs),
+    /// Replace the identity key with a new one, signing a rotation
+    /// announcement with both keys so a gateway moves the node's record to
+    /// the new peer id. The node must be stopped.
+    Rotate(identity::RotateArgs),
+    /// Show this node's standing with a gateway and the penalty notices it
+    /// has issued for failed proofs and audits.
+    Penalties(penalties::PenaltiesArgs),
+    /// Contest a penalty notice, signed with the identity key in
+    /// --storage-path.
+    Appeal(penalties::AppealArgs),
+    /// Put the node in --storage-path into maintenance or take it out. A
+    /// running node picks the change up within seconds and announces it.
+    Maintenance {
+        #[arg(value_enum)]
+        state: MaintenanceState,
+    },
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+enum MaintenanceState {
+    On,
+    Off,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -66,6 +190,22 @@ struct SetupConfig {
     storage_path: String,
     max_gb: u64,
     relay_url: Option<String>,
+    #[serde(default)]
+    serve_rate_mbps: f64,
+    #[serde(default)]
+    per_peer_rate_mbps: f64,
+    /// Merged with `--allow-peer`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    allow_peer: Vec<String>,
+    /// Merged with `--deny-peer`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    deny_peer: Vec<String>,
+    /// Log filter directives such as `debug` or `info,neuro_node=trace`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    log_level: Option<String>,
+    /// Listen multiaddr; overrides `--listen` when set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    listen: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -75,39 +215,153 @@ struct RuntimeConfig {
     listen: String,
     bootstrap: Vec<String>,
     allow_peer: Vec<String>,
+    deny_peer: Vec<String>,
     relay_url: Option<String>,
+    swarm_key: Option<PathBuf>,
+    scrub_interval_secs: u64,
+    tombstone_retention_days: u64,
+    serve_rate_mbps: f64,
+    per_peer_rate_mbps: f64,
+    disk: DiskConfig,
+    setup_allow_peer: Vec<String>,
+    setup_deny_peer: Vec<String>,
+    log_level: Option<String>,
+    /// Watched for changes while the node runs; `None` when the node was
+    /// configured purely from flags.
+    setup_config_path: Option<PathBuf>,
+    log_filter: Option<LogFilter>,
+    /// Enter maintenance before the node starts.
+    maintenance: bool,
+    redeem_gateway: Option<String>,
+    key: identity::KeyProtection,
+}
+
+impl RuntimeConfig {
+    /// Takes the values of a re-read setup config that can change without
+    /// a restart; storage path, listen address and relay stay as started.
+    fn with_setup(&self, setup: &SetupConfig) -> Self {
+        Self {
+            max_gb: setup.max_gb,
+            serve_rate_mbps: setup.serve_rate_mbps,
+            per_peer_rate_mbps: setup.per_peer_rate_mbps,
+            setup_allow_peer: setup.allow_peer.clone(),
+            setup_deny_peer: setup.deny_peer.clone(),
+            log_level: setup.log_level.clone(),
+            ..self.clone()
+        }
+    }
+
+    fn node_settings(&self) -> anyhow::Result<NodeSettings> {
+        let parse = |flags: &[String], setup: &[String]| {
+            flags
+                .iter()
+                .chain(setup)
+                .map(|s| libp2p::PeerId::from_str(s).with_context(|| format!("invalid peer id {s}")))
+                .collect::<anyhow::Result<HashSet<_>>>()
+        };
+        Ok(NodeSettings {
+            max_gb: self.max_gb,
+            allowlist: parse(&self.allow_peer, &self.setup_allow_peer)?,
+            denylist: parse(&self.deny_peer, &self.setup_deny_peer)?,
+            bandwidth: BandwidthConfig::from_mbps(self.serve_rate_mbps, self.per_peer_rate_mbps),
+        })
+    }
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize structured logging
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
-        )
-        .with_target(true)
-        .with_thread_ids(true)
-        .init();
-
     let args = Args::parse();
+
+    // Initialize structured logging; the handle flushes file output on exit.
+    let log = logging::init(&args.log)?;
+
+    if let Some(NodeCommand::Selftest(selftest_args)) = &args.command {
+        let runtime = RuntimeConfig {
+            storage_path: args.storage_path.clone(),
+            max_gb: args.max_gb,
+            listen: args.listen.clone(),
+            bootstrap: args.bootstrap.clone(),
+            allow_peer: args.allow_peer.clone(),
+            deny_peer: args.deny_peer.clone(),
+            relay_url: args.relay_url.clone(),
+            swarm_key: args.swarm_key.clone(),
+            scrub_interval_secs: args.scrub_interval_secs,
+            tombstone_retention_days: args.tombstone_retention_days,
+            serve_rate_mbps: args.serve_rate_mbps,
+            per_peer_rate_mbps: args.per_peer_rate_mbps,
+            disk: args.disk_config(),
+            setup_allow_peer: Vec::new(),
+            setup_deny_peer: Vec::new(),
+            log_level: None,
+            setup_config_path: None,
+            log_filter: None,
+            maintenance: false,
+            redeem_gateway: None,
+            key: args.key.clone(),
+        };
+        return selftest::run(&runtime, selftest_args).await;
+    }
+
+    if let Some(NodeCommand::Export(export_args)) = &args.command {
+        return snapshot::export(&args.storage_path, args.max_gb, &args.key, export_args);
+    }
+    if let Some(NodeCommand::Import(import_args)) = &args.command {
+        return snapshot::import(&args.storage_path, args.max_gb, &args.key, import_args);
+    }
+
+    if let Some(NodeCommand::Register(register_args)) = &args.command {
+        return register::run(&args.storage_path, &args.key, register_args).await;
+    }
+    if let Some(NodeCommand::Keygen(keygen_args)) = &args.command {
+        return identity::keygen(&args.storage_path, &args.key, keygen_args);
+    }
+    if let Some(NodeCommand::Rotate(rotate_args)) = &args.command {
+        return identity::rotate(&args.storage_path, &args.key, rotate_args).await;
+    }
+    if let Some(NodeCommand::Penalties(penalties_args)) = &args.command {
+        return penalties::list(&args.storage_path, &args.key, penalties_args).await;
+    }
+    if let Some(NodeCommand::Appeal(appeal_args)) = &args.command {
+        return penalties::appeal(&args.storage_path, &args.key, appeal_args).await;
+    }
+
+    if let Some(NodeCommand::Maintenance { state }) = &args.command {
+        match state {
+            MaintenanceState::On => {
+                let marker = maintenance::enter(&args.storage_path)?;
+                println!("maintenance on since {} ms", marker.since_ms);
+            }
+            MaintenanceState::Off if maintenance::leave(&args.storage_path)? => println!("maintenance off"),
+            MaintenanceState::Off => println!("node was not in maintenance"),
+        }
+        return Ok(());
+    }
+
+    if let Some(NodeCommand::Status) = &args.command {
+        let path = status::status_path(&args.storage_path);
+        let snapshot = status::read(&path)
+            .with_context(|| format!("no node status at {} (is the node running?)", path.display()))?;
+        println!("{}", serde_json::to_string_pretty(&snapshot)?);
+        return Ok(());
+    }
+
     #[cfg(windows)]
     if args.run_as_service {
-        return windows_service_host::run(args);
+        return windows_service_host::run(args, log.filter.clone());
     }
     #[cfg(not(windows))]
     if args.run_as_service {
         anyhow::bail!("--run-as-service is only supported on Windows");
     }
 
-    run_foreground(args).await
+    run_foreground(args, log.filter.clone()).await
 }
 
-async fn run_foreground(args: Args) -> anyhow::Result<()> {
-    let runtime = build_runtime_config(&args)?;
+async fn run_foreground(args: Args, log_filter: LogFilter) -> anyhow::Result<()> {
+    let runtime = build_runtime_config(&args, log_filter)?;
     if args.print_peer_id {
         fs::create_dir_all(&runtime.storage_path)?;
-        let keypair = load_or_create_identity(&runtime.storage_path)?;
+        let keypair = identity::load_or_create(&runtime.storage_path, &runtime.key)?;
         println!("{}", keypair.public().to_peer_id());
         return Ok(());
     }
@@ -119,7 +373,7 @@ async fn run_foreground(args: Args) -> anyhow::Result<()> {
     run_node_with_shutdown(&runtime, shutdown_rx).await
 }
 
-fn build_runtime_config(args: &Args) -> anyhow::Result<RuntimeConfig> {
+fn build_runtime_config(args: &Args, log_filter: LogFilter) -> anyhow::Result<RuntimeConfig> {
     let launched_without_flags = std::env::args_os().len() <= 1;
     let has_terminal = io::stdin().is_terminal() && io::stdout().is_terminal();
     let config_path = args
@@ -128,14 +382,32 @@ fn build_runtime_config(args: &Args) -> anyhow::Result<RuntimeConfig> {
         .map(PathBuf::from)
         .unwrap_or_else(default_setup_config_path);
     let setup = resolve_setup_config(args, launched_without_flags, has_terminal, &config_path)?;
+    let watch_setup = args.setup_config_path.is_some()
+        || args.interactive_setup
+        || launched_without_flags;
 
     Ok(RuntimeConfig {
+        listen: setup.listen.unwrap_or_else(|| args.listen.clone()),
         storage_path: setup.storage_path,
         max_gb: setup.max_gb,
-        listen: args.listen.clone(),
         bootstrap: args.bootstrap.clone(),
         allow_peer: args.allow_peer.clone(),
+        deny_peer: args.deny_peer.clone(),
         relay_url: setup.relay_url,
+        swarm_key: args.swarm_key.clone(),
+        scrub_interval_secs: args.scrub_interval_secs,
+        tombstone_retention_days: args.tombstone_retention_days,
+        serve_rate_mbps: setup.serve_rate_mbps,
+        per_peer_rate_mbps: setup.per_peer_rate_mbps,
+        disk: args.disk_config(),
+        setup_allow_peer: setup.allow_peer,
+        setup_deny_peer: setup.deny_peer,
+        log_level: setup.log_level,
+        setup_config_path: watch_setup.then_some(config_path),
+        log_filter: Some(log_filter),
+        maintenance: args.maintenance,
+        redeem_gateway: args.redeem_gateway.clone(),
+        key: args.key.clone(),
     })
 }
 
@@ -146,18 +418,44 @@ async fn run_node_with_shutdown(
     fs::create_dir_all(&runtime.storage_path)?;
 
     let store = Arc::new(SecureBlockStore::new(&runtime.storage_path, runtime.max_gb));
-    let keypair = load_or_create_identity(&runtime.storage_path)?;
+    let keypair = identity::load_or_create(&runtime.storage_path, &runtime.key)?;
     let bootstrap_addrs = runtime
         .bootstrap
         .iter()
         .map(|s| s.parse())
         .collect::<Result<Vec<_>, _>>()?;
-    let allowlist = runtime
-        .allow_peer
-        .iter()
-        .map(|s| libp2p::PeerId::from_str(s))
-        .collect::<Result<HashSet<_>, _>>()?;
-    let node = build_node(store.clone(), keypair, bootstrap_addrs, allowlist, runtime.relay_url.clone()).await?;
+    let swarm_key = runtime
+        .swarm_key
+        .as_deref()
+        .map(load_swarm_key)
+        .transpose()?;
+    if let Some(psk) = &swarm_key {
+        info!(fingerprint = %psk.fingerprint(), "Private swarm key loaded");
+    }
+    let settings = runtime.node_settings()?;
+    let mut node = build_node(store.clone(), keypair, bootstrap_addrs, settings.allowlist, runtime.relay_url.clone(), swarm_key).await?;
+    node.repair.scrub_interval = (runtime.scrub_interval_secs > 0)
+        .then(|| Duration::from_secs(runtime.scrub_interval_secs));
+    node.tombstones.retention = Duration::from_secs(runtime.tombstone_retention_days * 24 * 60 * 60);
+    node.denylist = settings.denylist;
+    node.bandwidth = BandwidthScheduler::new(settings.bandwidth);
+    node.disk.reconfigure(runtime.disk)?;
+    node.status_path = Some(status::status_path(&runtime.storage_path));
+    node.vouchers.gateway = runtime.redeem_gateway.clone();
+    if runtime.maintenance {
+        maintenance::enter(&runtime.storage_path)?;
+    }
+    let maintenance_path = maintenance::marker_path(&runtime.storage_path);
+    node.maintenance = maintenance::read(&maintenance_path);
+    node.maintenance_path = Some(maintenance_path);
+    if let (Some(filter), Some(level)) = (&runtime.log_filter, &runtime.log_level) {
+        filter.set(Some(level)).with_context(|| format!("invalid log_level {level}"))?;
+    }
+    if let Some(path) = &runtime.setup_config_path {
+        let (tx, rx) = mpsc::unbounded_channel();
+        node.settings_rx = Some(rx);
+        tokio::spawn(watch_setup_config(runtime.clone(), path.clone(), tx));
+    }
     let listen_addr = parse_listen_multiaddr(&runtime.listen)?;
 
     info!(peer_id = %node.peer_id, "Node identity loaded");
@@ -166,6 +464,22 @@ async fn run_node_with_shutdown(
         path = %runtime.storage_path,
         "Node storage allocation configured"
     );
+    info!(
+        serve_rate_mbps = runtime.serve_rate_mbps,
+        per_peer_rate_mbps = runtime.per_peer_rate_mbps,
+        "Retrieve bandwidth limits configured"
+    );
+    info!(
+        workers = runtime.disk.workers,
+        queue_depth = runtime.disk.queue_depth,
+        "Disk pool configured"
+    );
+    if let Some(gateway) = &node.vouchers.gateway {
+        info!(gateway = %gateway, "Redeeming bandwidth vouchers");
+    }
+    if let Some(marker) = &node.maintenance {
+        info!(since_ms = marker.since_ms, "Node is in maintenance, refusing new shards");
+    }
 
 
 
@@ -174,19 +488,77 @@ async fn run_node_with_shutdown(
     Ok(())
 }
 
-fn load_or_create_identity(storage_path: &str) -> anyhow::Result<libp2p::identity::Keypair> {
-    let key_path = PathBuf::from(storage_path).join("node_identity.key");
+/// Re-reads the setup config when its modification time changes or, on
+/// Unix, on SIGHUP, and hands the node whatever can be applied live.
+/// Invalid edits are logged and leave the running settings untouched.
+async fn watch_setup_config(
+    mut runtime: RuntimeConfig,
+    path: PathBuf,
+    tx: mpsc::UnboundedSender<NodeSettings>,
+) {
+    let modified_at = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    let mut last_modified = modified_at(&path);
+    let mut poll = tokio::time::interval(Duration::from_secs(5));
+    #[cfg(unix)]
+    let mut hangup =
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok();
 
-    if key_path.exists() {
-        let bytes = fs::read(&key_path)?;
-        let keypair = libp2p::identity::Keypair::from_protobuf_encoding(&bytes)?;
-        return Ok(keypair);
-    }
+    loop {
+        #[cfg(unix)]
+        let forced = tokio::select! {
+            _ = poll.tick() => false,
+            _ = async {
+                match hangup.as_mut() {
+                    Some(signal) => signal.recv().await,
+                    None => std::future::pending().await,
+                }
+            } => true,
+        };
+        #[cfg(not(unix))]
+        let forced = {
+            poll.tick().await;
+            false
+        };
 
-    let keypair = libp2p::identity::Keypair::generate_ed25519();
-    let encoded = keypair.to_protobuf_encoding()?;
-    fs::write(&key_path, encoded)?;
-    Ok(keypair)
+        let modified = modified_at(&path);
+        if !forced && modified == last_modified {
+            continue;
+        }
+        last_modified = modified;
+
+        let setup = match load_setup_config(&path) {
+            Ok(Some(setup)) => setup,
+            Ok(None) => {
+                warn!(path = %path.display(), "Setup config missing, keeping current settings");
+                continue;
+            }
+            Err(e) => {
+                warn!(error = %format!("{e:#}"), "Ignoring setup config change");
+                continue;
+            }
+        };
+        if setup.storage_path != runtime.storage_path || setup.relay_url != runtime.relay_url {
+            warn!("storage_path and relay_url changes take effect after a restart");
+        }
+        let next = runtime.with_setup(&setup);
+        let settings = match next.node_settings() {
+            Ok(settings) => settings,
+            Err(e) => {
+                warn!(error = %format!("{e:#}"), "Ignoring setup config change");
+                continue;
+            }
+        };
+        if let Some(filter) = &next.log_filter {
+            if let Err(e) = filter.set(next.log_level.as_deref()) {
+                warn!(error = %e, "Ignoring invalid log_level");
+            }
+        }
+        info!(path = %path.display(), "Reloading node settings");
+        if tx.send(settings).is_err() {
+            return;
+        }
+        runtime = next;
+    }
 }
 
 fn resolve_setup_config(
@@ -199,6 +571,12 @@ fn resolve_setup_config(
         storage_path: args.storage_path.clone(),
         max_gb: args.max_gb,
         relay_url: args.relay_url.clone(),
+        serve_rate_mbps: args.serve_rate_mbps,
+        per_peer_rate_mbps: args.per_peer_rate_mbps,
+        allow_peer: Vec::new(),
+        deny_peer: Vec::new(),
+        log_level: None,
+        listen: None,
     };
 
     if args.run_as_service {
@@ -260,6 +638,12 @@ fn run_interactive_setup(
         storage_path: baseline.storage_path,
         max_gb,
         relay_url,
+        serve_rate_mbps: baseline.serve_rate_mbps,
+        per_peer_rate_mbps: baseline.per_peer_rate_mbps,
+        allow_peer: baseline.allow_peer,
+        deny_peer: baseline.deny_peer,
+        log_level: baseline.log_level,
+        listen: baseline.listen,
     };
     save_setup_config(config_path, &setup)?;
     println!("Saved setup config to {}", config_path.to_string_lossy());
@@ -316,9 +700,9 @@ fn prompt_gui_fallback(title: &str, prompt: &str, default_value: &str) -> anyhow
         use std::process::Command;
         if let Ok(output) = Command::new("zenity")
             .arg("--entry")
-            .arg(&format!("--title={}", title))
-            .arg(&format!("--text={}", prompt))
-            .arg(&format!("--entry-text={}", default_value))
+            .arg(format!("--title={}", title))
+            .arg(format!("--text={}", prompt))
+            .arg(format!("--entry-text={}", default_value))
             .output() {
             let res = String::from_utf8_lossy(&output.stdout).trim().to_string();
             if !res.is_empty() {
@@ -406,6 +790,7 @@ fn default_setup_config_path() -> PathBuf {
 #[cfg(windows)]
 mod windows_service_host {
     use super::{build_runtime_config, run_node_with_shutdown, Args, RuntimeConfig};
+    use neuro_node::logging::LogFilter;
     use anyhow::Context;
     use std::{
         ffi::OsString,
@@ -431,8 +816,8 @@ mod windows_service_host {
 
     static SERVICE_RUNTIME: OnceLock<ServiceRuntime> = OnceLock::new();
 
-    pub fn run(args: Args) -> anyhow::Result<()> {
-        let runtime = build_runtime_config(&args)?;
+    pub fn run(args: Args, log_filter: LogFilter) -> anyhow::Result<()> {
+        let runtime = build_runtime_config(&args, log_filter)?;
         let service_name = args.service_name.clone();
         SERVICE_RUNTIME
             .set(ServiceRuntime {
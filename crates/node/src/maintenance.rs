@@ -0,0 +1,107 @@
+//! Maintenance mode, for patching a machine without losing reputation. A
+//! node in maintenance answers `Store` with [`ChunkReply::Maintenance`] but
+//! keeps serving retrieves, audits and deletes, and announces itself on
+//! [`ANNOUNCE_TOPIC`] so gateways stop placing shards on it.
+//!
+//! The switch is a marker file next to the chunk store, written by
+//! `--maintenance` or `neuro-node maintenance on` and removed by
+//! `neuro-node maintenance off`; a running node picks up changes on its
+//! next [`POLL_INTERVAL`].
+
+use crate::p2p::NeuroNode;
+use neuro_protocol::{ChunkReply, MaintenanceResponse, NodeAnnouncement};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, info};
+
+pub const MAINTENANCE_FILE: &str = "maintenance.json";
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// How often a node in maintenance repeats its announcement, for peers that
+/// connected since.
+pub const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceMarker {
+    pub since_ms: u64,
+}
+
+pub fn marker_path(storage_path: &str) -> PathBuf {
+    PathBuf::from(storage_path).join(MAINTENANCE_FILE)
+}
+
+/// Puts the node at `storage_path` into maintenance, keeping the original
+/// start time if it already is.
+pub fn enter(storage_path: &str) -> anyhow::Result<MaintenanceMarker> {
+    let path = marker_path(storage_path);
+    if let Some(marker) = read(&path) {
+        return Ok(marker);
+    }
+    std::fs::create_dir_all(storage_path)?;
+    let marker = MaintenanceMarker {
+        since_ms: chrono::Utc::now().timestamp_millis() as u64,
+    };
+    std::fs::write(&path, serde_json::to_vec_pretty(&marker)?)?;
+    Ok(marker)
+}
+
+/// Takes the node out of maintenance; `false` when it was not in it.
+pub fn leave(storage_path: &str) -> anyhow::Result<bool> {
+    match std::fs::remove_file(marker_path(storage_path)) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// An unreadable marker still counts as maintenance, so a half-written file
+/// never lets stores through.
+pub fn read(path: &Path) -> Option<MaintenanceMarker> {
+    let raw = std::fs::read(path).ok()?;
+    Some(serde_json::from_slice(&raw).unwrap_or(MaintenanceMarker { since_ms: 0 }))
+}
+
+pub fn reply(marker: &MaintenanceMarker) -> ChunkReply {
+    ChunkReply::Maintenance(MaintenanceResponse {
+        since_ms: marker.since_ms,
+    })
+}
+
+/// Re-reads the marker and announces any change.
+pub fn poll(node: &mut NeuroNode) {
+    let Some(path) = &node.maintenance_path else {
+        return;
+    };
+    let marker = read(path);
+    let previous = std::mem::replace(&mut node.maintenance, marker.clone());
+    match (previous, marker) {
+        (None, Some(marker)) => {
+            info!("Entering maintenance: refusing new shards, still serving retrieves and audits");
+            announce(node, true, marker.since_ms);
+        }
+        (Some(previous), None) => {
+            info!("Leaving maintenance: accepting new shards again");
+            announce(node, false, previous.since_ms);
+        }
+        _ => {}
+    }
+}
+
+/// Repeats the announcement while the node is in maintenance.
+pub fn reannounce(node: &mut NeuroNode) {
+    if let Some(since_ms) = node.maintenance.as_ref().map(|m| m.since_ms) {
+        announce(node, true, since_ms);
+    }
+}
+
+fn announce(node: &mut NeuroNode, enabled: bool, since_ms: u64) {
+    let Ok(data) = serde_json::to_vec(&NodeAnnouncement::Maintenance { enabled, since_ms }) else {
+        return;
+    };
+    let topic = node.topic_announce.clone();
+    // With no subscribed peers yet there is no one to tell; the next
+    // announcement reaches whoever connected since.
+    if let Err(e) = node.swarm.behaviour_mut().gossipsub.publish(topic, data) {
+        debug!(error = %e, "Maintenance announcement not published");
+    }
+}
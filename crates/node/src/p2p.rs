@@ -1,11 +1,21 @@
-use crate::store::SecureBlockStore;
+use crate::bandwidth::{self, BandwidthScheduler};
+use crate::disk::{self, DiskConfig, DiskJob, DiskPool};
+use crate::maintenance::{self, MaintenanceMarker};
+use crate::repair::{self, RepairState};
+use crate::settings::{self, NodeSettings};
+use crate::status;
+use crate::store::{SecureBlockStore, AUDIT_NONCE_TTL};
+use crate::tombstone::{self, TombstoneState};
+use crate::vouchers::{self, VoucherState};
 use anyhow::Result;
+use either::Either;
 use futures::StreamExt;
 use libp2p::{
     gossipsub::{self, IdentTopic as Topic, MessageAuthenticity, ValidationMode},
     identify, identity,
     kad::{self, store::MemoryStore},
     noise, ping, relay, autonat, dcutr,
+    pnet::{PnetConfig, PreSharedKey},
     request_response::{
         self, Behaviour as RequestResponse, Codec as RequestResponseCodec,
         Event as RequestResponseEvent, Message as RequestResponseMessage,
@@ -15,15 +25,26 @@ use libp2p::{
 };
 use neuro_protocol::{
     AuditChunkRequest, AuditChunkResponse, ChunkCommand, ChunkReply, DeleteChunkRequest,
-    DeleteChunkResponse, RetrieveChunkRequest, RetrieveChunkResponse, StoreChunkResponse,
+    DeleteChunkResponse, DeleteChunksRequest, DeleteChunksResponse, HasChunksRequest,
+    HasChunksResponse, RetrieveChunkRequest, RetrieveChunkResponse, StoreChunkResponse,
+    VoucheredRetrieveRequest, ANNOUNCE_TOPIC, MAX_DELETE_CIDS, MAX_HAS_CIDS, NODE_FEATURES, PROTOCOL_VERSION,
 };
+use neuro_protocol::wire::{self, ProtocolError, MAX_COMMAND_BYTES};
 
 use sha2::{Digest, Sha256};
-use std::collections::{HashMap, HashSet};
-use std::sync::Mutex;
-use std::{io, sync::Arc, time::Duration};
-use tokio::sync::oneshot;
-use tracing::{info, warn, debug};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::{io, sync::Arc, time::{Duration, Instant}};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info, info_span, warn};
+
+/// How far ahead of this node's clock an audit may claim to be issued.
+const MAX_AUDIT_CLOCK_SKEW: Duration = Duration::from_secs(60);
+
+/// A command as read off the wire. A peer's bad request is kept as its
+/// [`ProtocolError`] so the node can answer it with `ChunkReply::Rejected`
+/// rather than resetting the stream.
+pub type InboundCommand = Result<ChunkCommand, ProtocolError>;
 
 #[derive(Clone, Default)]
 pub struct ChunkCodec;
@@ -31,16 +52,18 @@ pub struct ChunkCodec;
 #[async_trait::async_trait]
 impl RequestResponseCodec for ChunkCodec {
     type Protocol = StreamProtocol;
-    type Request = ChunkCommand;
+    type Request = InboundCommand;
     type Response = ChunkReply;
 
     async fn read_request<T>(&mut self, _: &StreamProtocol, io: &mut T) -> io::Result<Self::Request>
     where
         T: futures::AsyncRead + Unpin + Send,
     {
+        // One byte past the limit is enough to tell an oversized request.
         let mut buf = Vec::new();
-        futures::AsyncReadExt::read_to_end(io, &mut buf).await?;
-        bincode::deserialize(&buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        let mut bounded = futures::AsyncReadExt::take(io, MAX_COMMAND_BYTES as u64 + 1);
+        futures::AsyncReadExt::read_to_end(&mut bounded, &mut buf).await?;
+        Ok(wire::decode_command(&buf))
     }
 
     async fn read_response<T>(
@@ -60,11 +83,12 @@ impl RequestResponseCodec for ChunkCodec {
         &mut self,
         _: &StreamProtocol,
         io: &mut T,
-        request: ChunkCommand,
+        request: InboundCommand,
     ) -> io::Result<()>
     where
         T: futures::AsyncWrite + Unpin + Send,
     {
+        let request = request.map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
         let data = bincode::serialize(&request)
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
         futures::AsyncWriteExt::write_all(io, &data).await?;
@@ -109,7 +133,7 @@ pub enum NeuroEvent {
     Gossipsub(gossipsub::Event),
     Identify(identify::Event),
     Ping(ping::Event),
-    Chunk(RequestResponseEvent<ChunkCommand, ChunkReply>),
+    Chunk(RequestResponseEvent<InboundCommand, ChunkReply>),
     Relay(relay::client::Event),
     Autonat(autonat::Event),
     Dcutr(dcutr::Event),
@@ -135,8 +159,8 @@ impl From<ping::Event> for NeuroEvent {
         Self::Ping(v)
     }
 }
-impl From<RequestResponseEvent<ChunkCommand, ChunkReply>> for NeuroEvent {
-    fn from(v: RequestResponseEvent<ChunkCommand, ChunkReply>) -> Self {
+impl From<RequestResponseEvent<InboundCommand, ChunkReply>> for NeuroEvent {
+    fn from(v: RequestResponseEvent<InboundCommand, ChunkReply>) -> Self {
         Self::Chunk(v)
     }
 }
@@ -162,10 +186,25 @@ pub struct NeuroNode {
     pub topic_announce: Topic,
     pub store: Arc<SecureBlockStore>,
     pub keypair: identity::Keypair,
-    pub audit_replay_guard: Mutex<HashMap<String, u64>>,
     pub bootstrap_addrs: Vec<Multiaddr>,
     pub allowlist: HashSet<PeerId>,
+    /// Refused even when the allowlist is empty or names them.
+    pub denylist: HashSet<PeerId>,
     pub relay_url: Option<String>,
+    pub repair: RepairState,
+    pub tombstones: TombstoneState,
+    pub vouchers: VoucherState,
+    pub bandwidth: BandwidthScheduler,
+    /// Runs chunk commands against the store off the event loop.
+    pub disk: DiskPool,
+    /// Where the periodic [`status::NodeStatus`] snapshot goes; `None` skips it.
+    pub status_path: Option<PathBuf>,
+    /// Reloaded settings, applied between swarm events.
+    pub settings_rx: Option<mpsc::UnboundedReceiver<NodeSettings>>,
+    /// Set while the node is in maintenance and refusing stores.
+    pub maintenance: Option<MaintenanceMarker>,
+    /// The marker file polled for maintenance; `None` never enters it.
+    pub maintenance_path: Option<PathBuf>,
 }
 
 pub async fn build_node(
@@ -174,6 +213,7 @@ pub async fn build_node(
     bootstrap_addrs: Vec<Multiaddr>,
     allowlist: HashSet<PeerId>,
     relay_url: Option<String>,
+    swarm_key: Option<PreSharedKey>,
 ) -> Result<NeuroNode> {
     let peer_id = PeerId::from(keypair.public());
 
@@ -182,6 +222,14 @@ pub async fn build_node(
 
     let (relay_transport, relay_client) = relay::client::new(peer_id);
     let tcp_transport = tcp::tokio::Transport::new(tcp::Config::default().nodelay(true));
+    // With a swarm key every TCP connection is wrapped in the pnet handshake
+    // before noise, so peers without the key are cut off at the transport.
+    let tcp_transport = match swarm_key {
+        Some(psk) => Either::Left(
+            tcp_transport.and_then(move |socket, _| PnetConfig::new(psk).handshake(socket)),
+        ),
+        None => Either::Right(tcp_transport),
+    };
 
     let transport = relay_transport
         .or_transport(tcp_transport)
@@ -198,10 +246,11 @@ pub async fn build_node(
     let gossipsub = gossipsub::Behaviour::new(MessageAuthenticity::Signed(keypair.clone()), cfg)
         .map_err(|e| anyhow::anyhow!("gossipsub init: {e}"))?;
 
-    let identify = identify::Behaviour::new(identify::Config::new(
-        "/neurostore/2.0.0".to_string(),
-        keypair.public(),
-    ));
+    let identify = identify::Behaviour::new(
+        identify::Config::new(PROTOCOL_VERSION.to_string(), keypair.public()).with_agent_version(
+            neuro_protocol::agent_version("neuro-node", env!("CARGO_PKG_VERSION"), NODE_FEATURES),
+        ),
+    );
 
     let ping = ping::Behaviour::new(ping::Config::new().with_interval(Duration::from_secs(20)));
 
@@ -230,6 +279,12 @@ pub async fn build_node(
         dcutr,
     };
 
+    let handler = ChunkHandler {
+        store: store.clone(),
+        keypair: keypair.clone(),
+    };
+    let disk = DiskPool::new(handler, DiskConfig::default())?;
+
     let swarm = Swarm::new(
         transport,
         behaviour,
@@ -241,13 +296,22 @@ pub async fn build_node(
     Ok(NeuroNode {
         peer_id,
         swarm,
-        topic_announce: Topic::new("neurostore-announce"),
+        topic_announce: Topic::new(ANNOUNCE_TOPIC),
         store,
         keypair,
-        audit_replay_guard: Mutex::new(HashMap::new()),
         bootstrap_addrs,
         allowlist,
+        denylist: HashSet::new(),
         relay_url,
+        repair: RepairState::default(),
+        tombstones: TombstoneState::default(),
+        vouchers: VoucherState::default(),
+        bandwidth: BandwidthScheduler::default(),
+        disk,
+        status_path: None,
+        settings_rx: None,
+        maintenance: None,
+        maintenance_path: None,
     })
 }
 
@@ -261,6 +325,14 @@ pub async fn drive_node(
         .behaviour_mut()
         .gossipsub
         .subscribe(&node.topic_announce)?;
+    node.swarm
+        .behaviour_mut()
+        .gossipsub
+        .subscribe(&node.repair.topic)?;
+    node.swarm
+        .behaviour_mut()
+        .gossipsub
+        .subscribe(&node.tombstones.topic)?;
 
     // V7 AutoNAT & DCUtR NAT Hole-Punching
     // We negotiate a circuit via the Relay server. This enables 99% of residential 
@@ -291,43 +363,132 @@ pub async fn drive_node(
         }
     }
 
+    let mut repair_tick = tokio::time::interval(node.repair.want_interval);
+    let mut bandwidth_tick = tokio::time::interval(bandwidth::DRAIN_INTERVAL);
+    bandwidth_tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut status_tick = tokio::time::interval(status::STATUS_INTERVAL);
+    let mut settings_rx = node.settings_rx.take();
+    let mut maintenance_tick = tokio::time::interval(maintenance::POLL_INTERVAL);
+    let mut announce_tick = tokio::time::interval(maintenance::ANNOUNCE_INTERVAL);
+    let mut tombstone_tick = tokio::time::interval(tombstone::TICK_INTERVAL);
+    let mut voucher_tick = tokio::time::interval(vouchers::REDEEM_INTERVAL);
     loop {
         tokio::select! {
             _ = &mut shutdown => {
                 info!("Shutdown signal received, stopping node");
                 break;
             }
+            _ = repair_tick.tick() => repair::tick(&mut node),
+            _ = bandwidth_tick.tick(), if node.bandwidth.has_backlog() => bandwidth::drain(&mut node),
+            _ = status_tick.tick() => write_status(&node),
+            _ = maintenance_tick.tick() => maintenance::poll(&mut node),
+            _ = announce_tick.tick() => maintenance::reannounce(&mut node),
+            _ = tombstone_tick.tick() => tombstone::tick(&mut node),
+            _ = voucher_tick.tick(), if node.vouchers.gateway.is_some() => vouchers::redeem(&node),
+            Some(update) = next_settings(&mut settings_rx) => settings::apply(&mut node, update),
+            Some(done) = node.disk.next_done() => disk::finish(&mut node, done),
             event = node.swarm.select_next_some() => {
                 match event {
                     SwarmEvent::Behaviour(NeuroEvent::Chunk(event)) => match event {
-                        RequestResponseEvent::Message { peer, message } => {
-                            if let RequestResponseMessage::Request {
-                                request, channel, ..
-                            } = message
-                            {
-                                let response = if is_peer_allowed(&node.allowlist, &peer) {
-                                    handle_chunk_command(&node, request)
-                                } else {
-                                    deny_chunk_command(request)
+                        RequestResponseEvent::Message { peer, message } => match message {
+                            RequestResponseMessage::Request {
+                                request_id, request, channel,
+                            } => {
+                                let request = match request {
+                                    Ok(request) => request,
+                                    Err(error) => {
+                                        warn!(request_id = %request_id, peer_id = %peer, error = %error, "Rejected malformed chunk command");
+                                        let _ = node
+                                            .swarm
+                                            .behaviour_mut()
+                                            .chunk
+                                            .send_response(channel, error.into());
+                                        continue;
+                                    }
                                 };
-                                let _ = node
-                                    .swarm
-                                    .behaviour_mut()
-                                    .chunk
-                                    .send_response(channel, response);
-                                debug!(peer = %peer, "Served chunk command");
+                                let (op, cid) = command_fields(&request);
+                                let span = info_span!(
+                                    "chunk_command",
+                                    request_id = %request_id,
+                                    peer_id = %peer,
+                                    op,
+                                    cid,
+                                );
+                                let entered = span.enter();
+                                if !is_peer_allowed(&node, &peer) {
+                                    warn!("Rejected chunk command from disallowed peer");
+                                    let _ = node
+                                        .swarm
+                                        .behaviour_mut()
+                                        .chunk
+                                        .send_response(channel, deny_chunk_command(request));
+                                } else if let (Some(marker), ChunkCommand::Store(_)) = (&node.maintenance, &request) {
+                                    info!("In maintenance, store refused");
+                                    let reply = maintenance::reply(marker);
+                                    let _ = node.swarm.behaviour_mut().chunk.send_response(channel, reply);
+                                } else {
+                                    match into_retrieve(request) {
+                                        Ok((cid, voucher)) => {
+                                            // Retrieves are the bulk of upload traffic, so they
+                                            // go through the bandwidth scheduler first.
+                                            if !node.bandwidth.enqueue(peer, cid, voucher, channel) {
+                                                warn!("Dropped retrieve, peer has too many queued");
+                                            }
+                                            drop(entered);
+                                            bandwidth::drain(&mut node);
+                                        }
+                                        Err(request) => {
+                                            // Replies are sent from `disk::finish` once a
+                                            // worker has run the command.
+                                            let submitted = node.disk.submit(DiskJob {
+                                                peer,
+                                                command: request,
+                                                channel,
+                                                reserved: None,
+                                                queued_at: Instant::now(),
+                                                span: span.clone(),
+                                            });
+                                            if let Err(job) = submitted {
+                                                warn!("Disk queue full, chunk command answered busy");
+                                                let _ = node
+                                                    .swarm
+                                                    .behaviour_mut()
+                                                    .chunk
+                                                    .send_response(job.channel, disk::busy_reply());
+                                            }
+                                        }
+                                    }
+                                }
                             }
-                        }
+                            RequestResponseMessage::Response { request_id, response } => {
+                                repair::handle_response(&mut node, request_id, response);
+                            }
+                        },
                         RequestResponseEvent::InboundFailure { peer, error, .. } => {
                             warn!(peer = %peer, error = %error, "Chunk inbound failure");
                         }
-                        RequestResponseEvent::OutboundFailure { peer, error, .. } => {
+                        RequestResponseEvent::OutboundFailure { peer, request_id, error } => {
+                            repair::handle_failure(&mut node, request_id);
                             warn!(peer = %peer, error = %error, "Chunk outbound failure");
                         }
                         RequestResponseEvent::ResponseSent { peer, .. } => {
                             debug!(peer = %peer, "Chunk response sent");
                         }
                     },
+                    SwarmEvent::Behaviour(NeuroEvent::Gossipsub(gossipsub::Event::Message {
+                        message, ..
+                    })) if message.topic == node.repair.topic.hash() => {
+                        if let Some(source) = message.source {
+                            repair::handle_message(&mut node, source, &message.data);
+                        }
+                    }
+                    SwarmEvent::Behaviour(NeuroEvent::Gossipsub(gossipsub::Event::Message {
+                        message, ..
+                    })) if message.topic == node.tombstones.topic.hash() => {
+                        if let Some(source) = message.source {
+                            tombstone::handle_message(&mut node, source, &message.data);
+                        }
+                    }
                     SwarmEvent::NewListenAddr { address, .. } => {
                         info!(address = %address, "Listening");
                     }
@@ -336,6 +497,9 @@ pub async fn drive_node(
                     }
                     SwarmEvent::ConnectionClosed { peer_id, cause, .. } => {
                         info!(peer = %peer_id, cause = ?cause, "Connection closed");
+                        if node.swarm.connected_peers().next().is_none() {
+                            tombstone::disconnected(&mut node);
+                        }
                     }
                     SwarmEvent::IncomingConnectionError { error, .. } => {
                         warn!(error = ?error, "Incoming connection error");
@@ -348,143 +512,280 @@ pub async fn drive_node(
             }
         }
     }
+    write_status(&node);
     Ok(())
 }
 
-fn is_peer_allowed(allowlist: &HashSet<PeerId>, peer: &PeerId) -> bool {
-    allowlist.is_empty() || allowlist.contains(peer)
+async fn next_settings(
+    rx: &mut Option<mpsc::UnboundedReceiver<NodeSettings>>,
+) -> Option<NodeSettings> {
+    match rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
 }
 
-fn handle_chunk_command(node: &NeuroNode, cmd: ChunkCommand) -> ChunkReply {
-    match cmd {
-        ChunkCommand::Store(request) => {
-            let stored = node
-                .store
-                .save_chunk(&request.cid, &request.data)
-                .ok()
-                .unwrap_or(false);
-            let timestamp_ms = chrono::Utc::now().timestamp_millis() as u64;
-            let payload =
-                StoreChunkResponse::receipt_payload(&request.cid, request.data.len(), timestamp_ms);
-            let signature = node
-                .keypair
-                .sign(&payload)
-                .map(|sig| sig.to_vec())
-                .unwrap_or_default();
-            let public_key = node.keypair.public().encode_protobuf();
-            ChunkReply::Store(StoreChunkResponse {
-                stored,
-                timestamp_ms,
-                signature,
-                public_key,
-            })
+fn write_status(node: &NeuroNode) {
+    if let Some(path) = &node.status_path {
+        if let Err(e) = status::write(node, path) {
+            warn!(path = %path.display(), error = %e, "Failed to write node status");
         }
-        ChunkCommand::Retrieve(RetrieveChunkRequest { cid }) => {
-            let maybe = node.store.retrieve_chunk(&cid).ok().flatten();
-            let found = maybe.is_some();
-            let data = maybe.map(|v| v.to_vec()).unwrap_or_default();
-            let timestamp_ms = chrono::Utc::now().timestamp_millis() as u64;
-            let payload = RetrieveChunkResponse::proof_payload(&cid, data.len(), timestamp_ms);
-            let signature = node
-                .keypair
-                .sign(&payload)
-                .map(|sig| sig.to_vec())
-                .unwrap_or_default();
-            let public_key = node.keypair.public().encode_protobuf();
-            ChunkReply::Retrieve(RetrieveChunkResponse {
-                found,
-                data,
-                timestamp_ms,
-                signature,
-                public_key,
-            })
+    }
+}
+
+/// Span fields shared with the gateway's logs so a shard can be followed
+/// across both processes by `cid` and `peer_id`.
+fn command_fields(cmd: &ChunkCommand) -> (&'static str, &str) {
+    match cmd {
+        ChunkCommand::Store(req) => ("store", req.cid.as_str()),
+        ChunkCommand::Retrieve(req) => ("retrieve", req.cid.as_str()),
+        ChunkCommand::RetrieveVouchered(req) => ("retrieve", req.cid.as_str()),
+        ChunkCommand::Audit(req) => ("audit", req.cid.as_str()),
+        ChunkCommand::Delete(req) => ("delete", req.cid.as_str()),
+        ChunkCommand::Has(req) => ("has", req.cids.first().map_or("", String::as_str)),
+        ChunkCommand::DeleteBatch(req) => ("delete_batch", req.cids.first().map_or("", String::as_str)),
+    }
+}
+
+/// The CID and voucher of a retrieve, which goes through the bandwidth
+/// scheduler; any other command is handed back.
+fn into_retrieve(cmd: ChunkCommand) -> Result<(String, Option<String>), ChunkCommand> {
+    match cmd {
+        ChunkCommand::Retrieve(req) => Ok((req.cid, None)),
+        ChunkCommand::RetrieveVouchered(req) => Ok((req.cid, Some(req.voucher))),
+        other => Err(other),
+    }
+}
+
+pub(crate) fn is_peer_allowed(node: &NeuroNode, peer: &PeerId) -> bool {
+    !node.denylist.contains(peer) && (node.allowlist.is_empty() || node.allowlist.contains(peer))
+}
+
+/// Serves one chunk command against the local store, signing the reply with
+/// the node identity. Exposed so `neuro-node selftest` can exercise the same
+/// path without a second peer.
+pub fn handle_chunk_command(node: &NeuroNode, cmd: ChunkCommand) -> ChunkReply {
+    node.disk.handler().handle(cmd)
+}
+
+/// Everything serving a chunk command touches, cloned onto each
+/// [`disk`] worker.
+#[derive(Clone)]
+pub struct ChunkHandler {
+    pub store: Arc<SecureBlockStore>,
+    pub keypair: identity::Keypair,
+}
+
+impl ChunkHandler {
+    /// A signed retrieve reply for `cid`, found or not.
+    fn retrieve_reply(&self, cid: &str, maybe: Option<Vec<u8>>) -> ChunkReply {
+        let found = maybe.is_some();
+        let data = maybe.unwrap_or_default();
+        let timestamp_ms = chrono::Utc::now().timestamp_millis() as u64;
+        let payload = RetrieveChunkResponse::proof_payload(cid, data.len(), timestamp_ms);
+        let signature = self
+            .keypair
+            .sign(&payload)
+            .map(|sig| sig.to_vec())
+            .unwrap_or_default();
+        let public_key = self.keypair.public().encode_protobuf();
+        ChunkReply::Retrieve(RetrieveChunkResponse {
+            found,
+            data,
+            timestamp_ms,
+            signature,
+            public_key,
+        })
+    }
+
+    pub fn handle(&self, cmd: ChunkCommand) -> ChunkReply {
+        // Commands off the wire were checked when decoded; this covers ones
+        // built in-process, e.g. by `selftest`.
+        if let Err(error) = cmd.validate() {
+            return error.into();
         }
-        ChunkCommand::Audit(AuditChunkRequest {
-            cid,
-            challenge_hex,
-            nonce_hex,
-        }) => {
-            let mut accepted = register_audit_nonce(&node.audit_replay_guard, &cid, &nonce_hex);
-            let maybe = node.store.retrieve_chunk(&cid).ok().flatten();
-            let found = maybe.is_some();
-            
-            let response_hash = if accepted {
-                if let Some(data) = maybe {
-                    match compute_audit_response_hash(&challenge_hex, data.as_ref()) {
-                        Ok(hash) => hash,
-                        Err(_) => {
-                            accepted = false; // Invalid challenge hex
-                            String::new()
+        match cmd {
+            ChunkCommand::Store(request) => {
+                let stored = self
+                    .store
+                    .save_chunk(&request.cid, &request.data)
+                    .ok()
+                    .unwrap_or(false);
+                let timestamp_ms = chrono::Utc::now().timestamp_millis() as u64;
+                let payload =
+                    StoreChunkResponse::receipt_payload(&request.cid, request.data.len(), timestamp_ms);
+                let signature = self
+                    .keypair
+                    .sign(&payload)
+                    .map(|sig| sig.to_vec())
+                    .unwrap_or_default();
+                let public_key = self.keypair.public().encode_protobuf();
+                ChunkReply::Store(StoreChunkResponse {
+                    stored,
+                    timestamp_ms,
+                    signature,
+                    public_key,
+                })
+            }
+            ChunkCommand::Retrieve(RetrieveChunkRequest { cid }) => {
+                let maybe = self.store.retrieve_chunk(&cid).ok().flatten();
+                self.retrieve_reply(&cid, maybe)
+            }
+            ChunkCommand::RetrieveVouchered(VoucheredRetrieveRequest { cid, voucher }) => {
+                let maybe = self.store.retrieve_chunk(&cid).ok().flatten();
+                if let Some(data) = &maybe {
+                    if let Err(reason) = vouchers::charge(&self.store, &voucher, data.len() as u64) {
+                        return ProtocolError::VoucherRefused(reason).into();
+                    }
+                }
+                self.retrieve_reply(&cid, maybe)
+            }
+            ChunkCommand::Audit(AuditChunkRequest {
+                cid,
+                challenge_hex,
+                nonce_hex,
+                issued_at_ms,
+            }) => {
+                let mut accepted = register_audit_nonce(&self.store, &cid, &challenge_hex, &nonce_hex, issued_at_ms);
+                let maybe = self.store.retrieve_chunk(&cid).ok().flatten();
+                let found = maybe.is_some();
+
+                let response_hash = if accepted {
+                    if let Some(data) = maybe {
+                        match compute_audit_response_hash(&challenge_hex, data.as_ref()) {
+                            Ok(hash) => hash,
+                            Err(_) => {
+                                accepted = false; // Invalid challenge hex
+                                String::new()
+                            }
                         }
+                    } else {
+                        String::new()
                     }
                 } else {
                     String::new()
-                }
-            } else {
-                String::new()
-            };
-            let timestamp_ms = chrono::Utc::now().timestamp_millis() as u64;
-            let payload = AuditChunkResponse::audit_payload(
-                &cid,
-                &challenge_hex,
-                &nonce_hex,
-                &response_hash,
-                timestamp_ms,
-            );
-            let signature = node
-                .keypair
-                .sign(&payload)
-                .map(|sig| sig.to_vec())
-                .unwrap_or_default();
-            let public_key = node.keypair.public().encode_protobuf();
-            ChunkReply::Audit(AuditChunkResponse {
-                found,
-                accepted,
-                response_hash,
-                timestamp_ms,
-                signature,
-                public_key,
-            })
-        }
-        ChunkCommand::Delete(DeleteChunkRequest { cid }) => {
-
-            let deleted = node.store.delete_chunk(&cid).ok().unwrap_or(false);
-            let timestamp_ms = chrono::Utc::now().timestamp_millis() as u64;
-            // PoE Payload: prove that [cid] was requested to be deleted at [timestamp]
-            let payload = DeleteChunkResponse::deletion_payload(&cid, timestamp_ms);
-            let signature = node
-                .keypair
-                .sign(&payload)
-                .map(|sig| sig.to_vec())
-                .unwrap_or_default();
-            let public_key = node.keypair.public().encode_protobuf();
-            ChunkReply::Delete(DeleteChunkResponse {
-                deleted,
-                timestamp_ms,
-                signature,
-                public_key,
-            })
+                };
+                let timestamp_ms = chrono::Utc::now().timestamp_millis() as u64;
+                let payload = AuditChunkResponse::audit_payload(
+                    &cid,
+                    &challenge_hex,
+                    &nonce_hex,
+                    &response_hash,
+                    timestamp_ms,
+                );
+                let signature = self
+                    .keypair
+                    .sign(&payload)
+                    .map(|sig| sig.to_vec())
+                    .unwrap_or_default();
+                let public_key = self.keypair.public().encode_protobuf();
+                ChunkReply::Audit(AuditChunkResponse {
+                    found,
+                    accepted,
+                    response_hash,
+                    timestamp_ms,
+                    signature,
+                    public_key,
+                })
+            }
+            ChunkCommand::Delete(DeleteChunkRequest { cid }) => {
+
+                let deleted = self.store.delete_chunk(&cid).ok().unwrap_or(false);
+                let timestamp_ms = chrono::Utc::now().timestamp_millis() as u64;
+                // PoE Payload: prove that [cid] was requested to be deleted at [timestamp]
+                let payload = DeleteChunkResponse::deletion_payload(&cid, timestamp_ms);
+                let signature = self
+                    .keypair
+                    .sign(&payload)
+                    .map(|sig| sig.to_vec())
+                    .unwrap_or_default();
+                let public_key = self.keypair.public().encode_protobuf();
+                ChunkReply::Delete(DeleteChunkResponse {
+                    deleted,
+                    timestamp_ms,
+                    signature,
+                    public_key,
+                })
+            }
+            ChunkCommand::Has(HasChunksRequest { cids }) => {
+                // Only chunks that still decrypt and match their checksum count;
+                // a corrupt one is quarantined here and the uploader resends it.
+                let held: Vec<String> = cids
+                    .into_iter()
+                    .take(MAX_HAS_CIDS)
+                    .filter(|cid| self.store.retrieve_chunk(cid).ok().flatten().is_some())
+                    .collect();
+                let timestamp_ms = chrono::Utc::now().timestamp_millis() as u64;
+                let payload = HasChunksResponse::inventory_payload(&held, timestamp_ms);
+                let signature = self
+                    .keypair
+                    .sign(&payload)
+                    .map(|sig| sig.to_vec())
+                    .unwrap_or_default();
+                let public_key = self.keypair.public().encode_protobuf();
+                ChunkReply::Has(HasChunksResponse {
+                    held,
+                    timestamp_ms,
+                    signature,
+                    public_key,
+                })
+            }
+            ChunkCommand::DeleteBatch(DeleteChunksRequest { mut cids }) => {
+                cids.truncate(MAX_DELETE_CIDS);
+                let deleted: Vec<String> = cids
+                    .iter()
+                    .filter(|cid| self.store.delete_chunk(cid).ok().unwrap_or(false))
+                    .cloned()
+                    .collect();
+                let timestamp_ms = chrono::Utc::now().timestamp_millis() as u64;
+                let payload = DeleteChunksResponse::deletion_payload(&cids, &deleted, timestamp_ms);
+                let signature = self
+                    .keypair
+                    .sign(&payload)
+                    .map(|sig| sig.to_vec())
+                    .unwrap_or_default();
+                let public_key = self.keypair.public().encode_protobuf();
+                ChunkReply::DeleteBatch(DeleteChunksResponse {
+                    deleted,
+                    timestamp_ms,
+                    signature,
+                    public_key,
+                })
+            }
         }
     }
 }
 
-fn register_audit_nonce(guard: &Mutex<HashMap<String, u64>>, cid: &str, nonce_hex: &str) -> bool {
+/// Accepts an audit only when it was issued within the replay window (and
+/// not from the future beyond [`MAX_AUDIT_CLOCK_SKEW`]) and its nonce is new.
+fn register_audit_nonce(
+    store: &SecureBlockStore,
+    cid: &str,
+    challenge_hex: &str,
+    nonce_hex: &str,
+    issued_at_ms: u64,
+) -> bool {
     let now = chrono::Utc::now().timestamp_millis() as u64;
-    let ttl_ms = 10 * 60 * 1000;
-    let key = format!("{cid}:{nonce_hex}");
-
-    let Ok(mut map) = guard.lock() else {
-        return false;
-    };
-    map.retain(|_, ts| now.saturating_sub(*ts) <= ttl_ms);
-    if map.contains_key(&key) {
+    if now.saturating_sub(issued_at_ms) > AUDIT_NONCE_TTL.as_millis() as u64
+        || issued_at_ms.saturating_sub(now) > MAX_AUDIT_CLOCK_SKEW.as_millis() as u64
+    {
+        warn!(issued_at_ms, "Rejected audit outside the replay window");
         return false;
     }
-    map.insert(key, now);
-    true
+    match store.register_audit_nonce(cid, challenge_hex, nonce_hex, now) {
+        Ok(true) => true,
+        Ok(false) => {
+            warn!("Rejected replayed audit nonce");
+            false
+        }
+        Err(e) => {
+            warn!(error = %e, "Failed to record audit nonce");
+            false
+        }
+    }
 }
 
-fn compute_audit_response_hash(challenge_hex: &str, data: &[u8]) -> Result<String, hex::FromHexError> {
+pub fn compute_audit_response_hash(challenge_hex: &str, data: &[u8]) -> Result<String, hex::FromHexError> {
     let mut hasher = Sha256::new();
     let challenge = hex::decode(challenge_hex)?;
     hasher.update(&challenge);
@@ -501,7 +802,7 @@ fn deny_chunk_command(cmd: ChunkCommand) -> ChunkReply {
             signature: Vec::new(),
             public_key: Vec::new(),
         }),
-        ChunkCommand::Retrieve(_) => ChunkReply::Retrieve(RetrieveChunkResponse {
+        ChunkCommand::Retrieve(_) | ChunkCommand::RetrieveVouchered(_) => ChunkReply::Retrieve(RetrieveChunkResponse {
             found: false,
             data: Vec::new(),
             timestamp_ms,
@@ -522,10 +823,31 @@ fn deny_chunk_command(cmd: ChunkCommand) -> ChunkReply {
             signature: Vec::new(),
             public_key: Vec::new(),
         }),
+        ChunkCommand::Has(_) => ChunkReply::Has(HasChunksResponse {
+            held: Vec::new(),
+            timestamp_ms,
+            signature: Vec::new(),
+            public_key: Vec::new(),
+        }),
+        ChunkCommand::DeleteBatch(_) => ChunkReply::DeleteBatch(DeleteChunksResponse {
+            deleted: Vec::new(),
+            timestamp_ms,
+            signature: Vec::new(),
+            public_key: Vec::new(),
+        }),
     }
 }
 
 
+/// Reads a swarm key in the go-ipfs `swarm.key` format
+/// (`/key/swarm/psk/1.0.0/`, `/base16/`, then 64 hex digits).
+pub fn load_swarm_key(path: &Path) -> Result<PreSharedKey> {
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("failed to read swarm key {}: {e}", path.display()))?;
+    raw.parse()
+        .map_err(|e| anyhow::anyhow!("invalid swarm key {}: {e}", path.display()))
+}
+
 fn peer_id_from_multiaddr(addr: &Multiaddr) -> Option<PeerId> {
     addr.iter().find_map(|p| match p {
         libp2p::multiaddr::Protocol::P2p(peer_id) => Some(peer_id),
@@ -0,0 +1,161 @@
+//! `neuro-node penalties` and `neuro-node appeal`: the penalty notices a
+//! gateway has issued this node for failed proofs and audits, and contesting
+//! one. Each notice is checked against the gateway's signature before it is
+//! shown; appeals are signed with the identity key in --storage-path, so
+//! only the penalized node can file them.
+
+use crate::identity::{self, KeyProtection};
+use crate::register;
+use anyhow::{anyhow, Context};
+use libp2p::PeerId;
+use neuro_protocol::{appeal_payload, penalty_payload, signer_of};
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct PenaltiesArgs {
+    /// Gateway base URL, e.g. `https://gateway.example.com`.
+    #[arg(long)]
+    gateway: String,
+    /// Shared node secret; falls back to `NODE_SHARED_SECRET`.
+    #[arg(long)]
+    node_secret: Option<String>,
+    /// The gateway's peer id. Notices signed by any other key are reported
+    /// as unverified; without it, any valid signature is accepted.
+    #[arg(long)]
+    gateway_peer_id: Option<PeerId>,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct AppealArgs {
+    /// Id of the penalty to contest, as listed by `neuro-node penalties`.
+    penalty_id: String,
+    /// Why the penalty is wrong, for the operator ruling on it.
+    #[arg(long)]
+    reason: String,
+    #[command(flatten)]
+    gateway: PenaltiesArgs,
+}
+
+pub async fn list(storage_path: &str, key: &KeyProtection, args: &PenaltiesArgs) -> anyhow::Result<()> {
+    let keypair = identity::load_or_create(storage_path, key)?;
+    let peer_id = keypair.public().to_peer_id();
+    let gateway = args.gateway.trim_end_matches('/');
+    let url = format!("{gateway}/api/nodes/{peer_id}/penalties");
+    let resp = register::client()?
+        .get(&url)
+        .header("x-node-secret", register::node_secret(args.node_secret.as_deref())?)
+        .send()
+        .await
+        .with_context(|| format!("gateway unreachable at {url}"))?;
+    let status = resp.status();
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(anyhow!("{url} returned {status}: {}", text.trim()));
+    }
+    let mut body: serde_json::Value = resp.json().await?;
+    if let Some(notices) = body.get_mut("penalties").and_then(|p| p.as_array_mut()) {
+        for notice in notices {
+            let verified = verify_notice(notice, args.gateway_peer_id.as_ref());
+            if !verified {
+                eprintln!(
+                    "warning: penalty {} does not carry a valid gateway signature",
+                    notice["penalty_id"].as_str().unwrap_or("?")
+                );
+            }
+            notice["verified"] = verified.into();
+        }
+    }
+    println!("{}", serde_json::to_string_pretty(&body)?);
+    Ok(())
+}
+
+/// Whether `notice` is signed by the gateway (by `gateway`, when given)
+/// over exactly the fields and evidence it shows.
+fn verify_notice(notice: &serde_json::Value, gateway: Option<&PeerId>) -> bool {
+    let field = |name: &str| notice[name].as_str().unwrap_or_default();
+    let (Some(points), Some(issued_at_ms)) = (notice["points"].as_i64(), notice["issued_at_ms"].as_i64()) else {
+        return false;
+    };
+    let (Ok(public_key), Ok(signature)) = (hex::decode(field("signer_public_key")), hex::decode(field("signature")))
+    else {
+        return false;
+    };
+    let payload = penalty_payload(
+        field("penalty_id"),
+        field("peer_id"),
+        field("kind"),
+        points as i32,
+        field("reference"),
+        field("detail"),
+        issued_at_ms,
+    );
+    match signer_of(&public_key, &signature, &payload) {
+        Some(signer) => gateway.is_none_or(|gateway| *gateway == signer),
+        None => false,
+    }
+}
+
+pub async fn appeal(storage_path: &str, key: &KeyProtection, args: &AppealArgs) -> anyhow::Result<()> {
+    let keypair = identity::load_or_create(storage_path, key)?;
+    let peer_id = keypair.public().to_peer_id().to_string();
+    let gateway = args.gateway.gateway.trim_end_matches('/');
+    let timestamp_ms = chrono::Utc::now().timestamp_millis().max(0) as u64;
+    let signature = keypair.sign(&appeal_payload(&args.penalty_id, &peer_id, timestamp_ms, &args.reason))?;
+    let resp: serde_json::Value = register::post_json(
+        &register::client()?,
+        &format!("{gateway}/api/nodes/{peer_id}/penalties/{}/appeal", args.penalty_id),
+        &register::node_secret(args.gateway.node_secret.as_deref())?,
+        &serde_json::json!({
+            "reason": args.reason,
+            "timestamp_ms": timestamp_ms,
+            "public_key": hex::encode(keypair.public().encode_protobuf()),
+            "signature": hex::encode(signature),
+        }),
+    )
+    .await?
+    .json()
+    .await?;
+    println!("appealed penalty_id={} peer_id={peer_id}", args.penalty_id);
+    println!("{}", serde_json::to_string_pretty(&resp)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::identity::Keypair;
+
+    fn signed_notice(gateway: &Keypair) -> serde_json::Value {
+        let payload = penalty_payload("pen-1", "peer", "audit_invalid", 5, "audit-7", "bad hash", 1_700_000_000_000);
+        serde_json::json!({
+            "penalty_id": "pen-1",
+            "peer_id": "peer",
+            "kind": "audit_invalid",
+            "points": 5,
+            "reference": "audit-7",
+            "detail": "bad hash",
+            "issued_at_ms": 1_700_000_000_000i64,
+            "signature": hex::encode(gateway.sign(&payload).unwrap()),
+            "signer_public_key": hex::encode(gateway.public().encode_protobuf()),
+        })
+    }
+
+    #[test]
+    fn notices_verify_against_the_gateway_key() {
+        let gateway = Keypair::generate_ed25519();
+        let gateway_id = gateway.public().to_peer_id();
+        let notice = signed_notice(&gateway);
+        assert!(verify_notice(&notice, None));
+        assert!(verify_notice(&notice, Some(&gateway_id)));
+
+        let other = Keypair::generate_ed25519().public().to_peer_id();
+        assert!(!verify_notice(&notice, Some(&other)));
+
+        let mut tampered = notice.clone();
+        tampered["detail"] = "something else".into();
+        assert!(!verify_notice(&tampered, None));
+
+        let mut unsigned = notice;
+        unsigned["signer_public_key"] = "".into();
+        assert!(!verify_notice(&unsigned, None));
+    }
+}
@@ -0,0 +1,120 @@
+//! `neuro-node register`: registers this node with a gateway. The gateway
+//! issues a one-time challenge for the node's peer id, and the node answers
+//! with its registration signed by the identity key in --storage-path, so
+//! only the holder of that key can claim the peer id or its capacity.
+
+use crate::identity::{self, KeyProtection};
+use anyhow::{anyhow, Context};
+use base64::Engine;
+use neuro_protocol::registration_payload;
+use serde::Deserialize;
+use std::time::Duration;
+
+const NODE_SECRET_ENV: &str = "NODE_SHARED_SECRET";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct RegisterArgs {
+    /// Gateway base URL, e.g. `https://gateway.example.com`.
+    #[arg(long)]
+    gateway: String,
+    /// Payout wallet, `0x` followed by 40 hex digits.
+    #[arg(long)]
+    wallet: String,
+    /// Capacity offered to the network, in GB.
+    #[arg(long)]
+    capacity_gb: i64,
+    /// ISO-style location such as `IN-KA`.
+    #[arg(long)]
+    location: String,
+    /// Shared node secret; falls back to `NODE_SHARED_SECRET`.
+    #[arg(long)]
+    node_secret: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Challenge {
+    challenge: String,
+}
+
+pub(crate) async fn post_json(
+    client: &reqwest::Client,
+    url: &str,
+    secret: &str,
+    body: &serde_json::Value,
+) -> anyhow::Result<reqwest::Response> {
+    let resp = client
+        .post(url)
+        .header("x-node-secret", secret)
+        .json(body)
+        .send()
+        .await
+        .with_context(|| format!("gateway unreachable at {url}"))?;
+    if resp.status().is_success() {
+        return Ok(resp);
+    }
+    let status = resp.status();
+    let text = resp.text().await.unwrap_or_default();
+    Err(anyhow!("{url} returned {status}: {}", text.trim()))
+}
+
+pub(crate) fn node_secret(flag: Option<&str>) -> anyhow::Result<String> {
+    flag.map(str::to_string)
+        .or_else(|| std::env::var(NODE_SECRET_ENV).ok())
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| anyhow!("--node-secret or {NODE_SECRET_ENV} is required"))
+}
+
+pub(crate) fn client() -> anyhow::Result<reqwest::Client> {
+    Ok(reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build()?)
+}
+
+pub async fn run(storage_path: &str, key: &KeyProtection, args: &RegisterArgs) -> anyhow::Result<()> {
+    let secret = node_secret(args.node_secret.as_deref())?;
+    std::fs::create_dir_all(storage_path)?;
+    let keypair = identity::load_or_create(storage_path, key)?;
+    let peer_id = keypair.public().to_peer_id().to_string();
+    let gateway = args.gateway.trim_end_matches('/');
+    let client = client()?;
+
+    let challenge: Challenge = post_json(
+        &client,
+        &format!("{gateway}/api/nodes/register/challenge"),
+        &secret,
+        &serde_json::json!({ "peer_id": peer_id }),
+    )
+    .await?
+    .json()
+    .await?;
+
+    let payload = registration_payload(
+        &challenge.challenge,
+        &peer_id,
+        &args.wallet,
+        args.capacity_gb,
+        &args.location,
+    );
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let signature = keypair.sign(&payload)?;
+    let resp: serde_json::Value = post_json(
+        &client,
+        &format!("{gateway}/api/nodes/register"),
+        &secret,
+        &serde_json::json!({
+            "peer_id": peer_id,
+            "wallet_address": args.wallet,
+            "capacity_gb": args.capacity_gb,
+            "declared_location": args.location,
+            "challenge": challenge.challenge,
+            "public_key": b64.encode(keypair.public().encode_protobuf()),
+            "signature": b64.encode(signature),
+        }),
+    )
+    .await?
+    .json()
+    .await?;
+
+    println!("registered peer_id={peer_id} gateway={gateway}");
+    println!("{}", serde_json::to_string_pretty(&resp)?);
+    Ok(())
+}
@@ -0,0 +1,172 @@
+//! Peer-to-peer repair of chunks this node lost to bit-rot. A scrub (or a
+//! failed read) moves corrupt chunks onto the store's want list; the node
+//! then gossips a [`WantMessage::Want`] on [`WANT_TOPIC`], holders answer with
+//! [`WantMessage::Have`], and the chunk is pulled back with an ordinary
+//! `Retrieve`. A copy is kept only if the holder's signature verifies and the
+//! bytes hash to what the lost chunk held, so no gateway is involved.
+
+use crate::p2p::{is_peer_allowed, NeuroNode};
+use libp2p::{gossipsub::IdentTopic as Topic, request_response::OutboundRequestId, PeerId};
+use neuro_protocol::{ChunkCommand, ChunkReply, RetrieveChunkRequest, WantMessage};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+pub const WANT_TOPIC: &str = "neurostore-wants";
+/// Keeps each gossip message comfortably under the gossipsub size limit.
+const MAX_WANTS_PER_MESSAGE: usize = 256;
+
+pub struct RepairState {
+    pub topic: Topic,
+    /// Time between full scrubs of the store; `None` disables scrubbing,
+    /// though chunks found corrupt on read are still repaired.
+    pub scrub_interval: Option<Duration>,
+    /// How often outstanding wants are re-announced.
+    pub want_interval: Duration,
+    last_scrub: Option<Instant>,
+    /// Outstanding repair fetches: request -> (holder, cid).
+    inflight: HashMap<OutboundRequestId, (PeerId, String)>,
+}
+
+impl Default for RepairState {
+    fn default() -> Self {
+        Self {
+            topic: Topic::new(WANT_TOPIC),
+            scrub_interval: Some(Duration::from_secs(6 * 60 * 60)),
+            want_interval: Duration::from_secs(5 * 60),
+            last_scrub: None,
+            inflight: HashMap::new(),
+        }
+    }
+}
+
+impl RepairState {
+    fn is_inflight(&self, cid: &str) -> bool {
+        self.inflight.values().any(|(_, c)| c == cid)
+    }
+}
+
+/// Runs on every `want_interval`: starts a scrub when one is due and
+/// re-announces whatever is still wanted.
+pub fn tick(node: &mut NeuroNode) {
+    let scrub_due = node.repair.scrub_interval.is_some_and(|every| {
+        node.repair.last_scrub.is_none_or(|at| at.elapsed() >= every)
+    });
+    if scrub_due {
+        node.repair.last_scrub = Some(Instant::now());
+        let store = node.store.clone();
+        // Reads every chunk, so keep it off the swarm task; anything it
+        // finds goes out with the next tick.
+        tokio::task::spawn_blocking(move || match store.scrub() {
+            Ok(report) if report.corrupt > 0 => warn!(
+                checked = report.checked,
+                corrupt = report.corrupt,
+                "Scrub found corrupt chunks; requesting replacements"
+            ),
+            Ok(report) => debug!(checked = report.checked, "Scrub clean"),
+            Err(e) => warn!(error = %e, "Scrub failed"),
+        });
+    }
+
+    let wanted = match node.store.wanted(MAX_WANTS_PER_MESSAGE) {
+        Ok(cids) => cids
+            .into_iter()
+            .filter(|cid| !node.repair.is_inflight(cid))
+            .collect::<Vec<_>>(),
+        Err(e) => {
+            warn!(error = %e, "Failed to read want list");
+            return;
+        }
+    };
+    if !wanted.is_empty() {
+        info!(count = wanted.len(), "Announcing wanted chunks");
+        publish(node, &WantMessage::Want { cids: wanted });
+    }
+}
+
+/// Handles a message on [`WANT_TOPIC`] authored by `source`.
+pub fn handle_message(node: &mut NeuroNode, source: PeerId, data: &[u8]) {
+    if !is_peer_allowed(node, &source) {
+        return;
+    }
+    let Ok(message) = serde_json::from_slice::<WantMessage>(data) else {
+        debug!(peer = %source, "Ignoring malformed want-list message");
+        return;
+    };
+    match message {
+        WantMessage::Want { cids } => {
+            let held = cids
+                .into_iter()
+                .take(MAX_WANTS_PER_MESSAGE)
+                .filter(|cid| {
+                    node.store.has_chunk(cid).unwrap_or(false)
+                        && !node.store.is_wanted(cid).unwrap_or(true)
+                })
+                .collect::<Vec<_>>();
+            if !held.is_empty() {
+                debug!(peer = %source, count = held.len(), "Offering wanted chunks");
+                let reply = WantMessage::Have {
+                    to: source.to_string(),
+                    cids: held,
+                };
+                publish(node, &reply);
+            }
+        }
+        WantMessage::Have { to, cids } => {
+            if to != node.peer_id.to_string() {
+                return;
+            }
+            for cid in cids.into_iter().take(MAX_WANTS_PER_MESSAGE) {
+                if node.repair.is_inflight(&cid) || !node.store.is_wanted(&cid).unwrap_or(false) {
+                    continue;
+                }
+                let request_id = node.swarm.behaviour_mut().chunk.send_request(
+                    &source,
+                    Ok(ChunkCommand::Retrieve(RetrieveChunkRequest { cid: cid.clone() })),
+                );
+                node.repair.inflight.insert(request_id, (source, cid));
+            }
+        }
+    }
+}
+
+/// Consumes the reply to a repair fetch. Returns `false` when `request_id`
+/// was not one of ours.
+pub fn handle_response(node: &mut NeuroNode, request_id: OutboundRequestId, reply: ChunkReply) -> bool {
+    let Some((peer, cid)) = node.repair.inflight.remove(&request_id) else {
+        return false;
+    };
+    let ChunkReply::Retrieve(response) = reply else {
+        warn!(peer = %peer, cid, "Unexpected reply to repair fetch");
+        return true;
+    };
+    if !response.found || !response.verify_proof(&peer, &cid) {
+        warn!(peer = %peer, cid, "Repair fetch returned no verifiable chunk");
+        return true;
+    }
+    match node.store.verifies_wanted(&cid, &response.data) {
+        Ok(true) => match node.store.save_chunk(&cid, &response.data) {
+            Ok(true) => info!(peer = %peer, cid, "Repaired chunk from replica holder"),
+            Ok(false) => warn!(peer = %peer, cid, "No capacity to store repaired chunk"),
+            Err(e) => warn!(peer = %peer, cid, error = %e, "Failed to store repaired chunk"),
+        },
+        Ok(false) => warn!(peer = %peer, cid, "Repair fetch does not match the lost chunk"),
+        Err(e) => warn!(peer = %peer, cid, error = %e, "Failed to check repaired chunk"),
+    }
+    true
+}
+
+/// Forgets a repair fetch that failed so the next tick can ask again.
+pub fn handle_failure(node: &mut NeuroNode, request_id: OutboundRequestId) -> bool {
+    node.repair.inflight.remove(&request_id).is_some()
+}
+
+fn publish(node: &mut NeuroNode, message: &WantMessage) {
+    let Ok(data) = serde_json::to_vec(message) else {
+        return;
+    };
+    let topic = node.repair.topic.clone();
+    if let Err(e) = node.swarm.behaviour_mut().gossipsub.publish(topic, data) {
+        debug!(error = %e, "Want-list publish skipped");
+    }
+}
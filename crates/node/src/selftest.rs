@@ -0,0 +1,482 @@
+//! `neuro-node selftest`: preflight checks an operator can run before starting
+//! the node. Every failure prints a hint, and any failure makes the process
+//! exit nonzero so the command can gate install scripts and service units.
+
+use crate::identity::{self, KeyProtection};
+use crate::RuntimeConfig;
+use futures::StreamExt;
+use libp2p::pnet::PreSharedKey;
+use libp2p::swarm::SwarmEvent;
+use libp2p::Multiaddr;
+use neuro_node::p2p::{
+    build_node, compute_audit_response_hash, handle_chunk_command, load_swarm_key,
+    parse_listen_multiaddr, NeuroNode,
+};
+use neuro_node::store::SecureBlockStore;
+use neuro_protocol::{
+    AuditChunkRequest, ChunkCommand, ChunkReply, DeleteChunkRequest, RetrieveChunkRequest,
+    StoreChunkRequest,
+};
+use rand::RngCore;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Below this much free space the node cannot usefully accept shards.
+const MIN_FREE_BYTES: u64 = 256 * 1024 * 1024;
+const PROBE_LEN: usize = 4096;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct SelftestArgs {
+    /// How long to wait for the listener to bind and for a bootstrap peer to answer.
+    #[arg(long, default_value_t = 10)]
+    timeout_secs: u64,
+}
+
+#[derive(Default)]
+struct Report {
+    failures: usize,
+    warnings: usize,
+}
+
+impl Report {
+    fn pass(&mut self, check: &str, detail: impl AsRef<str>) {
+        println!("[ ok ] {check}: {}", detail.as_ref());
+    }
+
+    fn warn(&mut self, check: &str, detail: impl AsRef<str>, hint: &str) {
+        self.warnings += 1;
+        println!("[warn] {check}: {}", detail.as_ref());
+        println!("       hint: {hint}");
+    }
+
+    fn fail(&mut self, check: &str, detail: impl AsRef<str>, hint: &str) {
+        self.failures += 1;
+        println!("[FAIL] {check}: {}", detail.as_ref());
+        println!("       hint: {hint}");
+    }
+
+    fn skip(&mut self, check: &str, reason: &str) {
+        println!("[skip] {check}: {reason}");
+    }
+}
+
+pub async fn run(runtime: &RuntimeConfig, args: &SelftestArgs) -> anyhow::Result<()> {
+    let timeout = Duration::from_secs(args.timeout_secs.max(1));
+    let mut report = Report::default();
+    println!("neuro-node selftest ({})", runtime.storage_path);
+
+    check_storage_writable(&mut report, &runtime.storage_path);
+    check_free_space(&mut report, &runtime.storage_path, runtime.max_gb);
+    let keypair = check_identity(&mut report, &runtime.storage_path, &runtime.key);
+    let swarm_key = check_swarm_key(&mut report, runtime.swarm_key.as_deref());
+
+    let store = match SecureBlockStore::open(&runtime.storage_path, runtime.max_gb) {
+        Ok(store) => {
+            report.pass("block store", "opened");
+            Some(Arc::new(store))
+        }
+        Err(e) => {
+            report.fail(
+                "block store",
+                format!("cannot open: {e}"),
+                "another neuro-node may already be running against this path; stop it first or pass a different --storage-path",
+            );
+            None
+        }
+    };
+
+    match store {
+        Some(store) => {
+            let mut node = build_node(store, keypair, Vec::new(), HashSet::new(), None, swarm_key).await?;
+            check_listen(&mut report, &mut node, &runtime.listen, timeout).await;
+            check_bootstrap(&mut report, &mut node, &runtime.bootstrap, timeout).await;
+            check_loopback(&mut report, &node);
+        }
+        None => {
+            for check in ["listen address", "bootstrap dial", "loopback"] {
+                report.skip(check, "block store unavailable");
+            }
+        }
+    }
+
+    println!(
+        "{} failure(s), {} warning(s)",
+        report.failures, report.warnings
+    );
+    if report.failures > 0 {
+        anyhow::bail!("selftest failed");
+    }
+    Ok(())
+}
+
+fn check_storage_writable(report: &mut Report, storage_path: &str) {
+    const CHECK: &str = "storage path";
+    const HINT: &str =
+        "make sure the directory exists and is writable by this user, or pass --storage-path";
+
+    if let Err(e) = fs::create_dir_all(storage_path) {
+        report.fail(CHECK, format!("cannot create {storage_path}: {e}"), HINT);
+        return;
+    }
+    let probe = Path::new(storage_path).join(format!(".selftest-{}", random_hex(8)));
+    let mut payload = vec![0u8; PROBE_LEN];
+    rand::thread_rng().fill_bytes(&mut payload);
+    let result = fs::write(&probe, &payload).and_then(|_| fs::read(&probe));
+    let _ = fs::remove_file(&probe);
+    match result {
+        Ok(read_back) if read_back == payload => {
+            report.pass(CHECK, format!("{storage_path} is writable"))
+        }
+        Ok(_) => report.fail(
+            CHECK,
+            format!("{storage_path} returned different bytes than were written"),
+            "the underlying disk or filesystem is unreliable; move the node to another volume",
+        ),
+        Err(e) => report.fail(CHECK, format!("cannot write to {storage_path}: {e}"), HINT),
+    }
+}
+
+fn check_free_space(report: &mut Report, storage_path: &str, max_gb: u64) {
+    const CHECK: &str = "free space";
+
+    let available = match fs2::available_space(storage_path) {
+        Ok(available) => available,
+        Err(e) => {
+            report.fail(
+                CHECK,
+                format!("cannot query {storage_path}: {e}"),
+                "make sure --storage-path points at a mounted local volume",
+            );
+            return;
+        }
+    };
+    let allocation = max_gb.saturating_mul(1024 * 1024 * 1024);
+    let available_gb = available as f64 / (1024.0 * 1024.0 * 1024.0);
+    if available < MIN_FREE_BYTES {
+        report.fail(
+            CHECK,
+            format!("only {available_gb:.2} GB free"),
+            "free up disk space or point --storage-path at a larger volume",
+        );
+    } else if available < allocation {
+        report.warn(
+            CHECK,
+            format!("{available_gb:.1} GB free but --max-gb is {max_gb}"),
+            "lower --max-gb to what the volume can hold, or stores will fail once the disk fills",
+        );
+    } else {
+        report.pass(
+            CHECK,
+            format!("{available_gb:.1} GB free for a {max_gb} GB allocation"),
+        );
+    }
+}
+
+fn check_identity(report: &mut Report, storage_path: &str, key: &KeyProtection) -> libp2p::identity::Keypair {
+    const CHECK: &str = "identity key";
+
+    let key_path = identity::identity_key_path(storage_path);
+    if !key_path.exists() {
+        report.warn(
+            CHECK,
+            format!("{} does not exist yet", key_path.display()),
+            "a new identity is generated on first start; the checks below use a throwaway key",
+        );
+        return libp2p::identity::Keypair::generate_ed25519();
+    }
+    let loaded = fs::read(&key_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|bytes| identity::decode(&bytes, &key_path, key));
+    match loaded {
+        Ok(keypair) => {
+            report.pass(CHECK, format!("peer id {}", keypair.public().to_peer_id()));
+            keypair
+        }
+        Err(e) => {
+            report.fail(
+                CHECK,
+                format!("cannot load {}: {e}", key_path.display()),
+                "the key file is unreadable, corrupt or sealed under another secret; pass its passphrase or keyring flag, restore it from backup, or move it aside to start over with a new peer id",
+            );
+            libp2p::identity::Keypair::generate_ed25519()
+        }
+    }
+}
+
+fn check_swarm_key(report: &mut Report, path: Option<&Path>) -> Option<PreSharedKey> {
+    const CHECK: &str = "swarm key";
+
+    let Some(path) = path else {
+        report.skip(CHECK, "public swarm (no --swarm-key)");
+        return None;
+    };
+    match load_swarm_key(path) {
+        Ok(psk) => {
+            report.pass(CHECK, format!("private swarm, fingerprint {}", psk.fingerprint()));
+            Some(psk)
+        }
+        Err(e) => {
+            report.fail(
+                CHECK,
+                format!("{e:#}"),
+                "the file must hold /key/swarm/psk/1.0.0/, /base16/ and 64 hex digits on separate lines; copy it from another node in the cluster",
+            );
+            None
+        }
+    }
+}
+
+async fn check_listen(report: &mut Report, node: &mut NeuroNode, listen: &str, timeout: Duration) {
+    const CHECK: &str = "listen address";
+    const HINT: &str = "another process may hold the port, or the address is not local to this host; change --listen";
+
+    let addr = match parse_listen_multiaddr(listen) {
+        Ok(addr) => addr,
+        Err(e) => {
+            report.fail(
+                CHECK,
+                e.to_string(),
+                "pass --listen as a multiaddr such as /ip4/0.0.0.0/tcp/9000",
+            );
+            return;
+        }
+    };
+    let listener = match node.swarm.listen_on(addr) {
+        Ok(listener) => listener,
+        Err(e) => {
+            report.fail(CHECK, format!("cannot bind {listen}: {e}"), HINT);
+            return;
+        }
+    };
+
+    let outcome = tokio::time::timeout(timeout, async {
+        loop {
+            match node.swarm.select_next_some().await {
+                SwarmEvent::NewListenAddr {
+                    listener_id,
+                    address,
+                } if listener_id == listener => {
+                    return Ok(address);
+                }
+                SwarmEvent::ListenerError { listener_id, error } if listener_id == listener => {
+                    return Err(error.to_string());
+                }
+                SwarmEvent::ListenerClosed {
+                    listener_id,
+                    reason,
+                    ..
+                } if listener_id == listener => {
+                    return Err(match reason {
+                        Ok(()) => "listener closed".to_string(),
+                        Err(e) => e.to_string(),
+                    });
+                }
+                _ => {}
+            }
+        }
+    })
+    .await;
+    match outcome {
+        Ok(Ok(address)) => report.pass(CHECK, format!("bound {address}")),
+        Ok(Err(e)) => report.fail(CHECK, format!("cannot bind {listen}: {e}"), HINT),
+        Err(_) => report.fail(
+            CHECK,
+            format!("no listen address reported for {listen}"),
+            HINT,
+        ),
+    }
+}
+
+async fn check_bootstrap(
+    report: &mut Report,
+    node: &mut NeuroNode,
+    bootstrap: &[String],
+    timeout: Duration,
+) {
+    const CHECK: &str = "bootstrap dial";
+    const HINT: &str = "check the --bootstrap multiaddr, that the peer is up, and that outbound TCP is not firewalled";
+
+    if bootstrap.is_empty() {
+        report.skip(CHECK, "no --bootstrap peers configured");
+        return;
+    }
+    let mut pending = 0usize;
+    for raw in bootstrap {
+        match raw.parse::<Multiaddr>() {
+            Ok(addr) => match node.swarm.dial(addr) {
+                Ok(()) => pending += 1,
+                Err(e) => report.warn(CHECK, format!("{raw}: {e}"), HINT),
+            },
+            Err(e) => report.warn(CHECK, format!("{raw} is not a multiaddr: {e}"), HINT),
+        }
+    }
+    if pending == 0 {
+        report.fail(CHECK, "no bootstrap peer could be dialed", HINT);
+        return;
+    }
+
+    let outcome = tokio::time::timeout(timeout, async {
+        let mut last_error = String::new();
+        while pending > 0 {
+            match node.swarm.select_next_some().await {
+                SwarmEvent::ConnectionEstablished {
+                    peer_id, endpoint, ..
+                } if endpoint.is_dialer() => {
+                    return Ok(format!(
+                        "connected to {peer_id} at {}",
+                        endpoint.get_remote_address()
+                    ));
+                }
+                SwarmEvent::OutgoingConnectionError { error, .. } => {
+                    pending -= 1;
+                    last_error = error.to_string();
+                }
+                _ => {}
+            }
+        }
+        Err(last_error)
+    })
+    .await;
+    match outcome {
+        Ok(Ok(detail)) => report.pass(CHECK, detail),
+        Ok(Err(e)) => report.fail(CHECK, format!("every bootstrap peer refused: {e}"), HINT),
+        Err(_) => report.fail(
+            CHECK,
+            format!("no bootstrap peer answered within {}s", timeout.as_secs()),
+            HINT,
+        ),
+    }
+}
+
+/// Runs store, retrieve, audit and delete through the same handler remote
+/// peers hit, and checks every signed reply the way the gateway would.
+fn check_loopback(report: &mut Report, node: &NeuroNode) {
+    const CHECK: &str = "loopback";
+
+    let cid = format!("selftest-{}", random_hex(16));
+    let mut data = vec![0u8; PROBE_LEN];
+    rand::thread_rng().fill_bytes(&mut data);
+
+    let result = loopback_round_trip(node, &cid, &data);
+    let cleanup = handle_chunk_command(
+        node,
+        ChunkCommand::Delete(DeleteChunkRequest { cid: cid.clone() }),
+    );
+
+    if let Err((detail, hint)) = result {
+        report.fail(CHECK, detail, hint);
+        return;
+    }
+    match cleanup {
+        ChunkReply::Delete(reply)
+            if reply.deleted && reply.verify_deletion(&node.peer_id, &cid) =>
+        {
+            report.pass(CHECK, "store, retrieve, audit and delete verified")
+        }
+        _ => report.fail(
+            CHECK,
+            "delete of the probe chunk was not confirmed",
+            "the block store may be read-only; check disk health and permissions",
+        ),
+    }
+}
+
+fn loopback_round_trip(
+    node: &NeuroNode,
+    cid: &str,
+    data: &[u8],
+) -> Result<(), (String, &'static str)> {
+    const SIGNATURE_HINT: &str =
+        "the identity key does not match its signatures; move the key file aside and rerun";
+
+    match handle_chunk_command(
+        node,
+        ChunkCommand::Store(StoreChunkRequest {
+            cid: cid.to_string(),
+            data: data.to_vec(),
+        }),
+    ) {
+        ChunkReply::Store(reply) if !reply.stored => return Err((
+            "store was refused".to_string(),
+            "the allocation is full or the disk rejected the write; raise --max-gb or free space",
+        )),
+        ChunkReply::Store(reply) if !reply.verify_receipt(&node.peer_id, cid, data.len()) => {
+            return Err((
+                "store receipt signature is invalid".to_string(),
+                SIGNATURE_HINT,
+            ))
+        }
+        ChunkReply::Store(_) => {}
+        _ => {
+            return Err((
+                "store returned the wrong reply type".to_string(),
+                SIGNATURE_HINT,
+            ))
+        }
+    }
+
+    match handle_chunk_command(
+        node,
+        ChunkCommand::Retrieve(RetrieveChunkRequest {
+            cid: cid.to_string(),
+        }),
+    ) {
+        ChunkReply::Retrieve(reply) if !reply.found || reply.data != data => {
+            return Err((
+                "retrieved chunk does not match what was stored".to_string(),
+                "the block store could not read back its own write; check disk health",
+            ))
+        }
+        ChunkReply::Retrieve(reply) if !reply.verify_proof(&node.peer_id, cid) => {
+            return Err((
+                "retrieve proof signature is invalid".to_string(),
+                SIGNATURE_HINT,
+            ))
+        }
+        ChunkReply::Retrieve(_) => {}
+        _ => {
+            return Err((
+                "retrieve returned the wrong reply type".to_string(),
+                SIGNATURE_HINT,
+            ))
+        }
+    }
+
+    let challenge_hex = random_hex(32);
+    let nonce_hex = random_hex(16);
+    let expected = compute_audit_response_hash(&challenge_hex, data)
+        .map_err(|e| (format!("audit challenge encoding: {e}"), SIGNATURE_HINT))?;
+    match handle_chunk_command(
+        node,
+        ChunkCommand::Audit(AuditChunkRequest {
+            cid: cid.to_string(),
+            challenge_hex: challenge_hex.clone(),
+            nonce_hex: nonce_hex.clone(),
+            issued_at_ms: chrono::Utc::now().timestamp_millis() as u64,
+        }),
+    ) {
+        ChunkReply::Audit(reply) if reply.response_hash != expected => Err((
+            "audit response hash is wrong".to_string(),
+            "the stored chunk no longer matches its contents; check disk health",
+        )),
+        ChunkReply::Audit(reply)
+            if !reply.verify_audit(&node.peer_id, cid, &challenge_hex, &nonce_hex) =>
+        {
+            Err(("audit signature is invalid".to_string(), SIGNATURE_HINT))
+        }
+        ChunkReply::Audit(_) => Ok(()),
+        _ => Err((
+            "audit returned the wrong reply type".to_string(),
+            SIGNATURE_HINT,
+        )),
+    }
+}
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
@@ -0,0 +1,43 @@
+//! Runtime settings that can change while the node runs. `neuro-node`
+//! re-reads its setup config on SIGHUP or when the file changes and sends
+//! the result to [`crate::p2p::drive_node`]; applying it only touches
+//! in-memory state, so connections and queued replies are kept.
+
+use crate::bandwidth::BandwidthConfig;
+use crate::p2p::NeuroNode;
+use libp2p::PeerId;
+use std::collections::HashSet;
+use tracing::info;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeSettings {
+    pub max_gb: u64,
+    /// Empty admits every peer not on the denylist.
+    pub allowlist: HashSet<PeerId>,
+    pub denylist: HashSet<PeerId>,
+    pub bandwidth: BandwidthConfig,
+}
+
+pub fn apply(node: &mut NeuroNode, settings: NodeSettings) {
+    let max_bytes = settings.max_gb.saturating_mul(1024 * 1024 * 1024);
+    if node.store.max_bytes() != max_bytes {
+        node.store.set_max_gb(settings.max_gb);
+        info!(max_gb = settings.max_gb, "Storage allocation updated");
+    }
+    if node.allowlist != settings.allowlist {
+        info!(peers = settings.allowlist.len(), "Peer allowlist updated");
+        node.allowlist = settings.allowlist;
+    }
+    if node.denylist != settings.denylist {
+        info!(peers = settings.denylist.len(), "Peer denylist updated");
+        node.denylist = settings.denylist;
+    }
+    if node.bandwidth.config() != settings.bandwidth {
+        info!(
+            serve_rate = ?settings.bandwidth.serve_rate,
+            per_peer_rate = ?settings.bandwidth.per_peer_rate,
+            "Retrieve bandwidth limits updated"
+        );
+        node.bandwidth.reconfigure(settings.bandwidth);
+    }
+}
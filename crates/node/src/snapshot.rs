@@ -0,0 +1,239 @@
+//! `neuro-node export` / `import`: moves a node to new hardware. A snapshot
+//! is a zstd-compressed tar of every chunk (as plaintext, re-encrypted under
+//! the new store's key on import), the identity key, and a closing
+//! `snapshot.json` listing each chunk's length and SHA-256 plus the want
+//! list. Import checks every chunk against its CID and that listing before
+//! the identity key is written, so a damaged snapshot never yields a node
+//! that claims chunks it does not have.
+
+use crate::identity::{self, identity_key_path, KeyProtection};
+use anyhow::{anyhow, bail, Context};
+use neuro_node::store::SecureBlockStore;
+use neuro_protocol::cid::{self, CidFormat};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read};
+use std::path::PathBuf;
+
+const SNAPSHOT_FORMAT: u32 = 1;
+const MANIFEST_ENTRY: &str = "snapshot.json";
+const IDENTITY_ENTRY: &str = "node_identity.key";
+const CHUNK_DIR: &str = "chunks/";
+const ZSTD_LEVEL: i32 = 3;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ExportArgs {
+    /// Snapshot file to write, e.g. `snapshot.tar.zst`.
+    #[arg(long)]
+    out: PathBuf,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ImportArgs {
+    /// Snapshot written by `neuro-node export`.
+    #[arg(long = "in")]
+    input: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotChunk {
+    cid: String,
+    len: u64,
+    sha256: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotWant {
+    cid: String,
+    /// Checksum recorded when the chunk was lost, hex; may be empty.
+    checksum: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotManifest {
+    format: u32,
+    peer_id: String,
+    created_at_ms: u64,
+    identity_sha256: String,
+    chunks: Vec<SnapshotChunk>,
+    wanted: Vec<SnapshotWant>,
+}
+
+fn open_store(storage_path: &str, max_gb: u64) -> anyhow::Result<SecureBlockStore> {
+    SecureBlockStore::open(storage_path, max_gb).with_context(|| {
+        format!("cannot open the chunk store at {storage_path}; stop the node first")
+    })
+}
+
+fn append(
+    builder: &mut tar::Builder<impl std::io::Write>,
+    path: &str,
+    data: &[u8],
+) -> anyhow::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+    header.set_cksum();
+    builder.append_data(&mut header, path, data)?;
+    Ok(())
+}
+
+pub fn export(storage_path: &str, max_gb: u64, key: &KeyProtection, args: &ExportArgs) -> anyhow::Result<()> {
+    let identity_path = identity_key_path(storage_path);
+    let identity = fs::read(&identity_path)
+        .with_context(|| format!("no node identity at {}", identity_path.display()))?;
+    let peer_id = identity::decode(&identity, &identity_path, key)?
+        .public()
+        .to_peer_id();
+    let store = open_store(storage_path, max_gb)?;
+
+    let file =
+        File::create(&args.out).with_context(|| format!("cannot create {}", args.out.display()))?;
+    let encoder = zstd::Encoder::new(BufWriter::new(file), ZSTD_LEVEL)?;
+    let mut builder = tar::Builder::new(encoder);
+    append(&mut builder, IDENTITY_ENTRY, &identity)?;
+
+    let mut chunks = Vec::new();
+    let mut skipped = 0usize;
+    for cid in store.chunk_cids()? {
+        // Reading verifies the checksum; a corrupt chunk is moved onto the
+        // want list instead and travels that way.
+        let Some(data) = store.retrieve_chunk(&cid)? else {
+            skipped += 1;
+            continue;
+        };
+        append(&mut builder, &format!("{CHUNK_DIR}{cid}"), &data)?;
+        chunks.push(SnapshotChunk {
+            cid,
+            len: data.len() as u64,
+            sha256: hex::encode(Sha256::digest(&data)),
+        });
+    }
+    let wanted: Vec<SnapshotWant> = store
+        .want_entries()?
+        .into_iter()
+        .map(|(cid, checksum)| SnapshotWant {
+            cid,
+            checksum: hex::encode(checksum),
+        })
+        .collect();
+
+    let manifest = SnapshotManifest {
+        format: SNAPSHOT_FORMAT,
+        peer_id: peer_id.to_string(),
+        created_at_ms: chrono::Utc::now().timestamp_millis() as u64,
+        identity_sha256: hex::encode(Sha256::digest(&identity)),
+        chunks,
+        wanted,
+    };
+    append(
+        &mut builder,
+        MANIFEST_ENTRY,
+        &serde_json::to_vec_pretty(&manifest)?,
+    )?;
+    builder.into_inner()?.finish()?;
+
+    let bytes: u64 = manifest.chunks.iter().map(|c| c.len).sum();
+    println!(
+        "exported peer_id={} chunks={} bytes={} wanted={} corrupt_skipped={} out={}",
+        manifest.peer_id,
+        manifest.chunks.len(),
+        bytes,
+        manifest.wanted.len(),
+        skipped,
+        args.out.display()
+    );
+    Ok(())
+}
+
+pub fn import(storage_path: &str, max_gb: u64, key: &KeyProtection, args: &ImportArgs) -> anyhow::Result<()> {
+    let identity_path = identity_key_path(storage_path);
+    if identity_path.exists() {
+        bail!(
+            "{} already has a node identity; import into an empty --storage-path",
+            storage_path
+        );
+    }
+    fs::create_dir_all(storage_path)?;
+    let store = open_store(storage_path, max_gb)?;
+    if !store.chunk_cids()?.is_empty() {
+        bail!("{storage_path} already holds chunks; import into an empty --storage-path");
+    }
+
+    let file =
+        File::open(&args.input).with_context(|| format!("cannot open {}", args.input.display()))?;
+    let mut archive = tar::Archive::new(zstd::Decoder::new(BufReader::new(file))?);
+    let mut identity = None;
+    let mut manifest: Option<SnapshotManifest> = None;
+    let mut imported: HashMap<String, (u64, String)> = HashMap::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().into_owned();
+        let mut data = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut data)?;
+
+        if path == IDENTITY_ENTRY {
+            let peer_id = identity::decode(&data, &identity_path, key)
+                .map_err(|e| anyhow!("snapshot identity key is invalid: {e}"))?
+                .public()
+                .to_peer_id();
+            identity = Some((data, peer_id));
+        } else if path == MANIFEST_ENTRY {
+            manifest = Some(serde_json::from_slice(&data).context("snapshot.json is invalid")?);
+        } else if let Some(cid) = path.strip_prefix(CHUNK_DIR) {
+            if cid.is_empty() || !cid.chars().all(|c| c.is_ascii_alphanumeric()) {
+                bail!("snapshot chunk name {path:?} is not a CID");
+            }
+            let digest: [u8; 32] = Sha256::digest(&data).into();
+            if CidFormat::of(cid).is_some() && !cid::verify(cid, &data) {
+                bail!("snapshot chunk {cid} does not match its CID");
+            }
+            if !store.save_chunk(cid, &data)? {
+                bail!("chunk {cid} does not fit; raise --max-gb");
+            }
+            imported.insert(cid.to_string(), (data.len() as u64, hex::encode(digest)));
+        } else {
+            bail!("unexpected snapshot entry {path:?}");
+        }
+    }
+
+    let manifest =
+        manifest.ok_or_else(|| anyhow!("snapshot has no {MANIFEST_ENTRY}; it is truncated"))?;
+    if manifest.format != SNAPSHOT_FORMAT {
+        bail!("unsupported snapshot format {}", manifest.format);
+    }
+    let (identity, peer_id) = identity.ok_or_else(|| anyhow!("snapshot has no identity key"))?;
+    if hex::encode(Sha256::digest(&identity)) != manifest.identity_sha256 || peer_id.to_string() != manifest.peer_id {
+        bail!("snapshot identity key does not match snapshot.json");
+    }
+    for chunk in &manifest.chunks {
+        match imported.remove(&chunk.cid) {
+            Some((len, sha256)) if len == chunk.len && sha256 == chunk.sha256 => {}
+            Some(_) => bail!("chunk {} does not match snapshot.json", chunk.cid),
+            None => bail!("chunk {} listed in snapshot.json is missing", chunk.cid),
+        }
+    }
+    if let Some(extra) = imported.keys().next() {
+        bail!("chunk {extra} is not listed in snapshot.json");
+    }
+    for want in &manifest.wanted {
+        let checksum = hex::decode(&want.checksum)
+            .map_err(|_| anyhow!("want entry {} has a malformed checksum", want.cid))?;
+        store.add_wanted(&want.cid, &checksum)?;
+    }
+    store.flush()?;
+    fs::write(&identity_path, &identity)?;
+
+    println!(
+        "imported peer_id={} chunks={} bytes={} wanted={} path={}",
+        manifest.peer_id,
+        manifest.chunks.len(),
+        manifest.chunks.iter().map(|c| c.len).sum::<u64>(),
+        manifest.wanted.len(),
+        storage_path
+    );
+    Ok(())
+}
@@ -0,0 +1,60 @@
+//! Snapshot of a running node's counters, rewritten periodically next to the
+//! chunk store so `neuro-node status` can report on a node without attaching
+//! to the process (the store itself is locked while the node runs).
+
+use crate::bandwidth::BandwidthStatus;
+use crate::disk::DiskStatus;
+use crate::p2p::NeuroNode;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const STATUS_FILE: &str = "node_status.json";
+pub const STATUS_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeStatus {
+    pub peer_id: String,
+    pub updated_at_ms: u64,
+    pub used_bytes: u64,
+    pub max_bytes: u64,
+    pub connected_peers: usize,
+    pub wanted_chunks: usize,
+    pub bandwidth: BandwidthStatus,
+    #[serde(default)]
+    pub disk: DiskStatus,
+    /// When the node went into maintenance, if it is in it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintenance_since_ms: Option<u64>,
+}
+
+pub fn status_path(storage_path: &str) -> PathBuf {
+    PathBuf::from(storage_path).join(STATUS_FILE)
+}
+
+pub fn snapshot(node: &NeuroNode) -> NodeStatus {
+    NodeStatus {
+        peer_id: node.peer_id.to_string(),
+        updated_at_ms: chrono::Utc::now().timestamp_millis() as u64,
+        used_bytes: node.store.get_used_bytes(),
+        max_bytes: node.store.max_bytes(),
+        connected_peers: node.swarm.connected_peers().count(),
+        wanted_chunks: node.store.wanted(usize::MAX).map(|w| w.len()).unwrap_or(0),
+        bandwidth: node.bandwidth.status(),
+        disk: node.disk.status(),
+        maintenance_since_ms: node.maintenance.as_ref().map(|m| m.since_ms),
+    }
+}
+
+/// Writes via a temporary file so readers never see a partial snapshot.
+pub fn write(node: &NeuroNode, path: &Path) -> anyhow::Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(&snapshot(node))?)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+pub fn read(path: &Path) -> anyhow::Result<NodeStatus> {
+    let raw = std::fs::read(path)?;
+    Ok(serde_json::from_slice(&raw)?)
+}
@@ -1,28 +1,75 @@
 use sled::Db;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 use aes_gcm::{
     aead::{Aead, KeyInit, OsRng},
     AeadCore, Aes256Gcm, Key, Nonce,
 };
+use neuro_protocol::Tombstone;
+use neuro_voucher::{Voucher, VoucherError};
+use serde::{Deserialize, Serialize};
 use sha2::Digest;
 
 const USED_BYTES_KEY: &[u8] = b"__meta:used_bytes";
 const ENCRYPTION_KEY: &[u8] = b"__meta:node_encryption_key";
 const CHUNK_PREFIX: &str = "c:";
+/// Chunks this node held but lost to corruption, keyed by CID; the value is
+/// the SHA-256 the original bytes had, or empty when that was unreadable too.
+const WANT_PREFIX: &str = "w:";
+/// Audit `(cid, challenge, nonce)` tuples seen recently; the value is when,
+/// in unix milliseconds.
+const AUDIT_NONCE_PREFIX: &str = "a:";
+/// When each chunk was last written, in unix milliseconds, so a tombstone
+/// only removes copies stored before the deletion it records.
+const STORED_AT_PREFIX: &str = "s:";
+/// Tombstones of deleted chunks, keyed by CID; the value is the JSON
+/// [`Tombstone`], kept to pass on to peers that were offline.
+const TOMBSTONE_PREFIX: &str = "t:";
+/// Bytes served under each bandwidth voucher, keyed by voucher id; the
+/// value is the JSON [`VoucherRecord`], kept until it is redeemed.
+const VOUCHER_PREFIX: &str = "v:";
+/// How long an audit nonce is remembered. Audit requests issued longer ago
+/// than this are refused outright, so forgetting a nonce afterwards cannot
+/// let it be replayed.
+pub const AUDIT_NONCE_TTL: Duration = Duration::from_secs(10 * 60);
+const AUDIT_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What this node served under one bandwidth voucher.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VoucherRecord {
+    pub id: String,
+    /// The token as the gateway minted it, needed to redeem it.
+    pub token: String,
+    pub bytes: u64,
+    /// Unix seconds.
+    pub expires_at: u64,
+}
+
+/// Outcome of one [`SecureBlockStore::scrub`] pass.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ScrubReport {
+    pub checked: usize,
+    pub corrupt: usize,
+}
 
 pub struct SecureBlockStore {
     db: Db,
-    max_bytes: u64,
+    max_bytes: AtomicU64,
     cipher: Aes256Gcm,
+    last_audit_sweep_ms: AtomicU64,
 }
 
 impl SecureBlockStore {
     pub fn new(storage_path: &str, max_gb: u64) -> Self {
-        let db = sled::open(Path::new(storage_path)).expect("Failed to open local block store");
-        let max_bytes = max_gb
-            .saturating_mul(1024)
-            .saturating_mul(1024)
-            .saturating_mul(1024);
+        Self::open(storage_path, max_gb).expect("Failed to open local block store")
+    }
+
+    /// Fallible variant of [`SecureBlockStore::new`]; sled refuses a second
+    /// open of the same path, so this is how callers detect a running node.
+    pub fn open(storage_path: &str, max_gb: u64) -> Result<Self, sled::Error> {
+        let db = sled::open(Path::new(storage_path))?;
+        let max_bytes = AtomicU64::new(gb_to_bytes(max_gb));
         let used_bytes = read_used_bytes(&db).unwrap_or(0);
 
         // Load or generate AES key for node-level end-to-end encryption
@@ -34,9 +81,8 @@ impl SecureBlockStore {
             }
             _ => {
                 let key = Aes256Gcm::generate_key(OsRng);
-                db.insert(ENCRYPTION_KEY, key.as_slice())
-                    .expect("Failed to save encryption key");
-                db.flush().unwrap();
+                db.insert(ENCRYPTION_KEY, key.as_slice())?;
+                db.flush()?;
                 Aes256Gcm::new(&key)
             }
         };
@@ -45,11 +91,22 @@ impl SecureBlockStore {
             "Secure node initialized at {}. Allocated capacity: {} GB. Used: {} bytes. E2E Encryption Enabled.",
             storage_path, max_gb, used_bytes
         );
-        Self {
+        Ok(Self {
             db,
             max_bytes,
             cipher,
-        }
+            last_audit_sweep_ms: AtomicU64::new(0),
+        })
+    }
+
+    /// Changes the allocation for future writes. Shrinking below what is
+    /// already used keeps existing chunks and only refuses new ones.
+    pub fn set_max_gb(&self, max_gb: u64) {
+        self.max_bytes.store(gb_to_bytes(max_gb), Ordering::Relaxed);
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes.load(Ordering::Relaxed)
     }
 
     pub fn save_chunk(&self, cid: &str, raw_data: &[u8]) -> Result<bool, sled::Error> {
@@ -84,58 +141,153 @@ impl SecureBlockStore {
             .saturating_sub(existing_len)
             .saturating_add(encrypted_data.len() as u64);
 
-        if projected > self.max_bytes {
+        if projected > self.max_bytes.load(Ordering::Relaxed) {
             return Ok(false);
         }
 
         self.db.insert(key, encrypted_data)?;
         write_used_bytes(&self.db, projected)?;
+        self.db.remove(want_key(cid))?;
+        let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+        self.db.insert(stored_at_key(cid), &now_ms.to_le_bytes())?;
 
         Ok(true)
     }
 
     pub fn retrieve_chunk(&self, cid: &str) -> Result<Option<Vec<u8>>, sled::Error> {
-        let raw_lookup = if let Some(v) = self.db.get(chunk_key(cid))? {
-            Some(v)
-        } else {
-            self.db.get(cid)?
-        };
+        if let Some(payload) = self.db.get(chunk_key(cid))? {
+            return match self.open_payload(&payload) {
+                Some(data) => Ok(Some(data)),
+                None => {
+                    // Physically corrupted on disk: drop it and ask the
+                    // swarm for a replacement (see `crate::repair`).
+                    eprintln!("CRITICAL ALERT: Silent Bit-Rot detected for shard CID {}", cid);
+                    self.quarantine(cid, &payload)?;
+                    Ok(None) // Treat as missing so the gateway asks another node
+                }
+            };
+        }
 
-        if let Some(payload) = raw_lookup {
+        // Legacy entries predate the prefix and may be unencrypted.
+        if let Some(payload) = self.db.get(cid)? {
             if payload.len() < 12 + 32 { // 12 bytes nonce + 32 bytes checksum
-                // Legacy unencrypted fallback (or corrupt data)
-                return Ok(Some(payload.to_vec())); 
+                return Ok(Some(payload.to_vec()));
             }
-            let nonce = Nonce::from_slice(&payload[0..12]);
-            let stored_checksum = &payload[12..44];
-            let ciphertext = &payload[44..];
-            
-            match self.cipher.decrypt(nonce, ciphertext) {
-                Ok(decrypted) => {
-                    // Verify the checksum to detect Bit-Rot
-                    let mut hasher = sha2::Sha256::new();
-                    sha2::Digest::update(&mut hasher, &decrypted);
-                    let computed_checksum = hasher.finalize();
-                    
-                    if computed_checksum.as_slice() != stored_checksum {
-                        // Data is decrypted but physically corrupted on disk.
-                        // In a full implementation, we trigger the Repair Daemon here.
-                        eprintln!("CRITICAL ALERT: Silent Bit-Rot detected for shard CID {}", cid);
-                        return Ok(None); // Treat as missing so the gateway asks another node
-                    }
-                    
-                    Ok(Some(decrypted))
-                },
-                Err(_) => Ok(Some(payload.to_vec())), // Legacy fallback
+            return Ok(Some(
+                self.open_payload(&payload)
+                    .unwrap_or_else(|| payload.to_vec()),
+            ));
+        }
+        Ok(None)
+    }
+
+    /// Decrypts a `c:` entry and checks it against the checksum written
+    /// alongside it; `None` means the entry is corrupt.
+    fn open_payload(&self, payload: &[u8]) -> Option<Vec<u8>> {
+        if payload.len() < 12 + 32 {
+            return None;
+        }
+        let nonce = Nonce::from_slice(&payload[0..12]);
+        let stored_checksum = &payload[12..44];
+        let decrypted = self.cipher.decrypt(nonce, &payload[44..]).ok()?;
+        let computed_checksum = sha2::Sha256::digest(&decrypted);
+        (computed_checksum.as_slice() == stored_checksum).then_some(decrypted)
+    }
+
+    /// Removes a corrupt chunk and puts it on the want list.
+    fn quarantine(&self, cid: &str, payload: &[u8]) -> Result<(), sled::Error> {
+        let expected = payload.get(12..44).unwrap_or_default();
+        self.db.insert(want_key(cid), expected)?;
+        self.delete_chunk(cid)?;
+        Ok(())
+    }
+
+    /// Re-reads every stored chunk, moving the ones that no longer decrypt
+    /// or match their checksum onto the want list.
+    pub fn scrub(&self) -> Result<ScrubReport, sled::Error> {
+        let mut report = ScrubReport::default();
+        for entry in self.db.scan_prefix(CHUNK_PREFIX) {
+            let (key, payload) = entry?;
+            report.checked += 1;
+            if self.open_payload(&payload).is_some() {
+                continue;
             }
-        } else {
-            Ok(None)
+            let cid = String::from_utf8_lossy(&key[CHUNK_PREFIX.len()..]).into_owned();
+            eprintln!("CRITICAL ALERT: Silent Bit-Rot detected for shard CID {}", cid);
+            self.quarantine(&cid, &payload)?;
+            report.corrupt += 1;
         }
+        Ok(report)
+    }
+
+    /// CIDs of every stored chunk, in key order.
+    pub fn chunk_cids(&self) -> Result<Vec<String>, sled::Error> {
+        self.db
+            .scan_prefix(CHUNK_PREFIX)
+            .keys()
+            .map(|key| key.map(|k| String::from_utf8_lossy(&k[CHUNK_PREFIX.len()..]).into_owned()))
+            .collect()
+    }
+
+    /// The want list with the checksum recorded for each entry.
+    pub fn want_entries(&self) -> Result<Vec<(String, Vec<u8>)>, sled::Error> {
+        self.db
+            .scan_prefix(WANT_PREFIX)
+            .map(|entry| {
+                entry.map(|(k, v)| {
+                    (String::from_utf8_lossy(&k[WANT_PREFIX.len()..]).into_owned(), v.to_vec())
+                })
+            })
+            .collect()
+    }
+
+    /// Puts `cid` on the want list, e.g. when restoring a snapshot.
+    pub fn add_wanted(&self, cid: &str, checksum: &[u8]) -> Result<(), sled::Error> {
+        self.db.insert(want_key(cid), checksum)?;
+        Ok(())
+    }
+
+    pub fn flush(&self) -> Result<(), sled::Error> {
+        self.db.flush()?;
+        Ok(())
+    }
+
+    pub fn has_chunk(&self, cid: &str) -> Result<bool, sled::Error> {
+        Ok(self.db.contains_key(chunk_key(cid))? || self.db.contains_key(cid)?)
+    }
+
+    /// CIDs on the want list, oldest first by key order.
+    pub fn wanted(&self, limit: usize) -> Result<Vec<String>, sled::Error> {
+        self.db
+            .scan_prefix(WANT_PREFIX)
+            .keys()
+            .take(limit)
+            .map(|key| key.map(|k| String::from_utf8_lossy(&k[WANT_PREFIX.len()..]).into_owned()))
+            .collect()
+    }
+
+    pub fn is_wanted(&self, cid: &str) -> Result<bool, sled::Error> {
+        self.db.contains_key(want_key(cid))
+    }
+
+    /// Whether `data` is a faithful copy of wanted chunk `cid`: it must hash
+    /// to the checksum recorded when the chunk was lost or, failing that, to
+    /// the digest in a self-certifying CID.
+    pub fn verifies_wanted(&self, cid: &str, data: &[u8]) -> Result<bool, sled::Error> {
+        let Some(expected) = self.db.get(want_key(cid))? else {
+            return Ok(false);
+        };
+        let digest: [u8; 32] = sha2::Sha256::digest(data).into();
+        if expected.len() == 32 && expected.as_ref() == digest {
+            return Ok(true);
+        }
+        Ok(neuro_protocol::cid::verify(cid, data))
     }
 
     pub fn delete_chunk(&self, cid: &str) -> Result<bool, sled::Error> {
         let key = chunk_key(cid);
         if let Some(v) = self.db.remove(&key)? {
+            self.db.remove(stored_at_key(cid))?;
             let used_bytes = read_used_bytes(&self.db).unwrap_or(0);
             let updated = used_bytes.saturating_sub(v.len() as u64);
             write_used_bytes(&self.db, updated)?;
@@ -146,16 +298,203 @@ impl SecureBlockStore {
         }
     }
 
+    /// Records an audit nonce seen at `now_ms`. Returns `false` when the
+    /// same `(cid, challenge, nonce)` was already seen within
+    /// [`AUDIT_NONCE_TTL`]. Survives restarts, and the insert is atomic so
+    /// concurrent disk workers cannot both accept one nonce.
+    pub fn register_audit_nonce(
+        &self,
+        cid: &str,
+        challenge_hex: &str,
+        nonce_hex: &str,
+        now_ms: u64,
+    ) -> Result<bool, sled::Error> {
+        self.sweep_audit_nonces(now_ms)?;
+        let key = format!("{AUDIT_NONCE_PREFIX}{cid}:{challenge_hex}:{nonce_hex}");
+        let seen = now_ms.to_le_bytes();
+        let mut current = None;
+        loop {
+            match self.db.compare_and_swap(&key, current.as_ref(), Some(&seen[..]))? {
+                Ok(()) => return Ok(true),
+                Err(conflict) => match conflict.current {
+                    // Expired but not swept yet: take it over.
+                    Some(old) if is_expired(&old, now_ms) => current = Some(old),
+                    Some(_) => return Ok(false),
+                    None => current = None,
+                },
+            }
+        }
+    }
+
+    /// Drops expired audit nonces, at most once per [`AUDIT_SWEEP_INTERVAL`].
+    fn sweep_audit_nonces(&self, now_ms: u64) -> Result<(), sled::Error> {
+        let last = self.last_audit_sweep_ms.load(Ordering::Relaxed);
+        if now_ms.saturating_sub(last) < AUDIT_SWEEP_INTERVAL.as_millis() as u64
+            || self
+                .last_audit_sweep_ms
+                .compare_exchange(last, now_ms, Ordering::Relaxed, Ordering::Relaxed)
+                .is_err()
+        {
+            return Ok(());
+        }
+        for entry in self.db.scan_prefix(AUDIT_NONCE_PREFIX) {
+            let (key, seen) = entry?;
+            if is_expired(&seen, now_ms) {
+                self.db.compare_and_swap(&key, Some(seen), None as Option<&[u8]>)?.ok();
+            }
+        }
+        Ok(())
+    }
+
+    /// Records `tombstone` and drops its chunk if the copy here predates the
+    /// deletion; a chunk stored again since (a re-upload) is kept. Returns
+    /// `None` when a tombstone at least as recent was already recorded, else
+    /// whether a chunk was deleted.
+    pub fn apply_tombstone(&self, tombstone: &Tombstone) -> Result<Option<bool>, sled::Error> {
+        let key = tombstone_key(&tombstone.cid);
+        if let Some(known) = self.db.get(&key)? {
+            let known = serde_json::from_slice::<Tombstone>(&known).map_or(0, |t| t.deleted_at_ms);
+            if known >= tombstone.deleted_at_ms {
+                return Ok(None);
+            }
+        }
+        let record = serde_json::to_vec(tombstone).unwrap_or_default();
+        self.db.insert(key, record)?;
+        // Nothing to repair once the chunk is gone for good.
+        self.db.remove(want_key(&tombstone.cid))?;
+
+        let stored_at = self
+            .db
+            .get(stored_at_key(&tombstone.cid))?
+            .and_then(|v| v.as_ref().try_into().ok().map(u64::from_le_bytes))
+            .unwrap_or(0);
+        if stored_at >= tombstone.deleted_at_ms {
+            return Ok(Some(false));
+        }
+        Ok(Some(self.delete_chunk(&tombstone.cid)?))
+    }
+
+    /// Recorded tombstones issued after `since_ms`, oldest first.
+    pub fn tombstones_since(&self, since_ms: u64) -> Result<Vec<Tombstone>, sled::Error> {
+        let mut tombstones = self.tombstones()?;
+        tombstones.retain(|t| t.deleted_at_ms > since_ms);
+        tombstones.sort_by_key(|t| t.deleted_at_ms);
+        Ok(tombstones)
+    }
+
+    /// When the most recent recorded tombstone was issued.
+    pub fn newest_tombstone_ms(&self) -> Result<Option<u64>, sled::Error> {
+        Ok(self.tombstones()?.iter().map(|t| t.deleted_at_ms).max())
+    }
+
+    /// Forgets tombstones issued before `cutoff_ms`, returning how many.
+    pub fn sweep_tombstones(&self, cutoff_ms: u64) -> Result<usize, sled::Error> {
+        let mut swept = 0;
+        for tombstone in self.tombstones()? {
+            if tombstone.deleted_at_ms < cutoff_ms {
+                self.db.remove(tombstone_key(&tombstone.cid))?;
+                swept += 1;
+            }
+        }
+        Ok(swept)
+    }
+
+    fn tombstones(&self) -> Result<Vec<Tombstone>, sled::Error> {
+        let mut tombstones = Vec::new();
+        for value in self.db.scan_prefix(TOMBSTONE_PREFIX).values() {
+            if let Ok(tombstone) = serde_json::from_slice(&value?) {
+                tombstones.push(tombstone);
+            }
+        }
+        Ok(tombstones)
+    }
+
+    /// Adds `bytes` to what was served under `voucher`, unless
+    /// [`Voucher::admit`] refuses them; the inner result is the voucher's
+    /// new total or why it was refused. Safe against concurrent charges
+    /// from other disk workers.
+    pub fn charge_voucher(
+        &self,
+        voucher: &Voucher,
+        token: &str,
+        bytes: u64,
+        now: u64,
+    ) -> Result<Result<u64, VoucherError>, sled::Error> {
+        let key = voucher_key(&voucher.id);
+        loop {
+            let current = self.db.get(&key)?;
+            let used = current
+                .as_ref()
+                .and_then(|v| serde_json::from_slice::<VoucherRecord>(v).ok())
+                .map_or(0, |r| r.bytes);
+            let total = match voucher.admit(used, bytes, now) {
+                Ok(total) => total,
+                Err(e) => return Ok(Err(e)),
+            };
+            let record = VoucherRecord {
+                id: voucher.id.clone(),
+                token: token.to_string(),
+                bytes: total,
+                expires_at: voucher.expires_at,
+            };
+            let record = serde_json::to_vec(&record).unwrap_or_default();
+            if self.db.compare_and_swap(&key, current, Some(record))?.is_ok() {
+                return Ok(Ok(total));
+            }
+        }
+    }
+
+    /// Every voucher served under and not yet redeemed.
+    pub fn voucher_records(&self) -> Result<Vec<VoucherRecord>, sled::Error> {
+        let mut records = Vec::new();
+        for value in self.db.scan_prefix(VOUCHER_PREFIX).values() {
+            if let Ok(record) = serde_json::from_slice(&value?) {
+                records.push(record);
+            }
+        }
+        Ok(records)
+    }
+
+    pub fn remove_voucher_record(&self, id: &str) -> Result<(), sled::Error> {
+        self.db.remove(voucher_key(id))?;
+        Ok(())
+    }
+
     #[allow(dead_code)]
     pub fn get_used_bytes(&self) -> u64 {
         read_used_bytes(&self.db).unwrap_or(0)
     }
 }
 
+fn gb_to_bytes(gb: u64) -> u64 {
+    gb.saturating_mul(1024).saturating_mul(1024).saturating_mul(1024)
+}
+
 fn chunk_key(cid: &str) -> String {
     format!("{CHUNK_PREFIX}{cid}")
 }
 
+fn want_key(cid: &str) -> String {
+    format!("{WANT_PREFIX}{cid}")
+}
+
+fn stored_at_key(cid: &str) -> String {
+    format!("{STORED_AT_PREFIX}{cid}")
+}
+
+fn tombstone_key(cid: &str) -> String {
+    format!("{TOMBSTONE_PREFIX}{cid}")
+}
+
+fn voucher_key(id: &str) -> String {
+    format!("{VOUCHER_PREFIX}{id}")
+}
+
+fn is_expired(seen: &[u8], now_ms: u64) -> bool {
+    let seen_ms = seen.try_into().map(u64::from_le_bytes).unwrap_or(0);
+    now_ms.saturating_sub(seen_ms) > AUDIT_NONCE_TTL.as_millis() as u64
+}
+
 fn read_used_bytes(db: &Db) -> Result<u64, sled::Error> {
     let Some(v) = db.get(USED_BYTES_KEY)? else {
         return Ok(0);
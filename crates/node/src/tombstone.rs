@@ -0,0 +1,169 @@
+//! Deletes that reach holders who were offline when they were issued. The
+//! gateway gossips a signed [`Tombstone`] for every chunk it deletes on
+//! [`TOMBSTONE_TOPIC`]; nodes apply and keep them, and a node that (re)joins
+//! the swarm asks its peers with [`TombstoneMessage::Sync`] for any it
+//! missed, so an offline replica cannot bring deleted data back.
+//!
+//! Tombstones are forgotten after the retention window; a node offline for
+//! longer than that keeps its copies of chunks deleted meanwhile.
+
+use crate::p2p::{is_peer_allowed, NeuroNode};
+use libp2p::{gossipsub::IdentTopic as Topic, PeerId};
+use neuro_protocol::{Tombstone, TombstoneMessage, MAX_TOMBSTONES_PER_MESSAGE, TOMBSTONE_TOPIC};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// How often an unsynced node retries its sync request and the retention
+/// sweep is considered.
+pub const TICK_INTERVAL: Duration = Duration::from_secs(15);
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How far before its newest tombstone a node asks to be caught up from,
+/// for tombstones that were gossiped out of order.
+const SYNC_OVERLAP: Duration = Duration::from_secs(10 * 60);
+/// Tombstones stamped further in the future than this are refused.
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(5 * 60);
+/// Bounds how many messages one sync request is answered with.
+const MAX_SYNC_MESSAGES: usize = 16;
+
+pub struct TombstoneState {
+    pub topic: Topic,
+    /// How long tombstones are kept and passed on.
+    pub retention: Duration,
+    /// Whether a sync request went out since the node last had no peers.
+    synced: bool,
+    last_sweep: Option<Instant>,
+}
+
+impl Default for TombstoneState {
+    fn default() -> Self {
+        Self {
+            topic: Topic::new(TOMBSTONE_TOPIC),
+            retention: Duration::from_secs(30 * 24 * 60 * 60),
+            synced: false,
+            last_sweep: None,
+        }
+    }
+}
+
+/// Runs on every [`TICK_INTERVAL`]: asks to be caught up until a request
+/// reaches a peer, and drops tombstones past the retention window.
+pub fn tick(node: &mut NeuroNode) {
+    let now_ms = now_ms();
+    let retention_ms = node.tombstones.retention.as_millis() as u64;
+    if !node.tombstones.synced {
+        let newest = match node.store.newest_tombstone_ms() {
+            Ok(newest) => newest.unwrap_or(0),
+            Err(e) => {
+                warn!(error = %e, "Failed to read tombstones");
+                return;
+            }
+        };
+        let since_ms = newest
+            .saturating_sub(SYNC_OVERLAP.as_millis() as u64)
+            .max(now_ms.saturating_sub(retention_ms));
+        if publish(node, &TombstoneMessage::Sync { since_ms }) {
+            debug!(since_ms, "Requested missed tombstones");
+            node.tombstones.synced = true;
+        }
+    }
+
+    let sweep_due = node
+        .tombstones
+        .last_sweep
+        .is_none_or(|at| at.elapsed() >= SWEEP_INTERVAL);
+    if sweep_due {
+        node.tombstones.last_sweep = Some(Instant::now());
+        match node.store.sweep_tombstones(now_ms.saturating_sub(retention_ms)) {
+            Ok(0) => {}
+            Ok(swept) => debug!(swept, "Forgot expired tombstones"),
+            Err(e) => warn!(error = %e, "Failed to sweep tombstones"),
+        }
+    }
+}
+
+/// Called when the node lost its last connection, so it catches up on
+/// deletes once it reconnects.
+pub fn disconnected(node: &mut NeuroNode) {
+    node.tombstones.synced = false;
+}
+
+/// Handles a message on [`TOMBSTONE_TOPIC`] relayed by `source`.
+pub fn handle_message(node: &mut NeuroNode, source: PeerId, data: &[u8]) {
+    if !is_peer_allowed(node, &source) {
+        return;
+    }
+    let Ok(message) = serde_json::from_slice::<TombstoneMessage>(data) else {
+        debug!(peer = %source, "Ignoring malformed tombstone message");
+        return;
+    };
+    match message {
+        TombstoneMessage::Tombstones { tombstones } => {
+            for tombstone in tombstones.into_iter().take(MAX_TOMBSTONES_PER_MESSAGE) {
+                apply(node, &tombstone);
+            }
+        }
+        TombstoneMessage::Sync { since_ms } => {
+            let held = match node.store.tombstones_since(since_ms) {
+                Ok(held) => held,
+                Err(e) => {
+                    warn!(error = %e, "Failed to read tombstones");
+                    return;
+                }
+            };
+            if held.is_empty() {
+                return;
+            }
+            debug!(peer = %source, count = held.len(), "Passing on tombstones");
+            for batch in held.chunks(MAX_TOMBSTONES_PER_MESSAGE).take(MAX_SYNC_MESSAGES) {
+                let message = TombstoneMessage::Tombstones {
+                    tombstones: batch.to_vec(),
+                };
+                publish(node, &message);
+            }
+        }
+    }
+}
+
+/// Applies one tombstone if it is signed by a peer this node takes deletes
+/// from and falls within the retention window.
+fn apply(node: &NeuroNode, tombstone: &Tombstone) {
+    let Some(issuer) = tombstone.issuer() else {
+        debug!(cid = tombstone.cid, "Ignoring tombstone with a bad signature");
+        return;
+    };
+    if !is_peer_allowed(node, &issuer) {
+        debug!(issuer = %issuer, cid = tombstone.cid, "Ignoring tombstone from disallowed peer");
+        return;
+    }
+    let now_ms = now_ms();
+    let retention_ms = node.tombstones.retention.as_millis() as u64;
+    if now_ms.saturating_sub(tombstone.deleted_at_ms) > retention_ms
+        || tombstone.deleted_at_ms.saturating_sub(now_ms) > MAX_CLOCK_SKEW.as_millis() as u64
+    {
+        debug!(cid = tombstone.cid, "Ignoring tombstone outside the retention window");
+        return;
+    }
+    match node.store.apply_tombstone(tombstone) {
+        Ok(Some(true)) => info!(issuer = %issuer, cid = tombstone.cid, "Deleted chunk from tombstone"),
+        Ok(_) => {}
+        Err(e) => warn!(cid = tombstone.cid, error = %e, "Failed to apply tombstone"),
+    }
+}
+
+fn publish(node: &mut NeuroNode, message: &TombstoneMessage) -> bool {
+    let Ok(data) = serde_json::to_vec(message) else {
+        return false;
+    };
+    let topic = node.tombstones.topic.clone();
+    match node.swarm.behaviour_mut().gossipsub.publish(topic, data) {
+        Ok(_) => true,
+        Err(e) => {
+            debug!(error = %e, "Tombstone publish skipped");
+            false
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    chrono::Utc::now().timestamp_millis() as u64
+}
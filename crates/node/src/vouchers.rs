@@ -0,0 +1,114 @@
+//! Bandwidth vouchers on the serving side. A vouchered retrieve is charged
+//! to its voucher in the store before the shard goes out, so a client can
+//! neither pull more than the voucher's byte cap from this node nor use it
+//! after it expires. Once a voucher has expired nothing more can be served
+//! under it, and a node started with `--redeem-gateway` claims what it
+//! served from that gateway, signed with its identity key, to be credited
+//! to its earnings.
+
+use crate::p2p::NeuroNode;
+use crate::store::SecureBlockStore;
+use libp2p::identity;
+use neuro_voucher::{Redemption, Voucher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, info_span, warn, Instrument};
+
+/// How often expired vouchers are redeemed.
+pub const REDEEM_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// How long after expiry the gateway still settles a voucher; records left
+/// unredeemed that long are dropped.
+const REDEMPTION_WINDOW: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+pub struct VoucherState {
+    /// Gateway base URL redemptions are sent to; `None` keeps them in the
+    /// store.
+    pub gateway: Option<String>,
+    /// Set while a redemption pass is running, so ticks do not overlap.
+    redeeming: Arc<AtomicBool>,
+}
+
+/// Counts `bytes` of a retrieve against `token`, returning why it was
+/// refused. The gateway checks who minted the voucher when it is redeemed;
+/// here only its claims matter.
+pub fn charge(store: &SecureBlockStore, token: &str, bytes: u64) -> Result<u64, String> {
+    let voucher = Voucher::parse(token).map_err(|e| e.to_string())?;
+    match store.charge_voucher(&voucher, token, bytes, now_secs()) {
+        Ok(Ok(total)) => Ok(total),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(e) => {
+            warn!(voucher = %voucher.id, error = %e, "Voucher ledger unavailable");
+            Err("voucher ledger unavailable".to_string())
+        }
+    }
+}
+
+/// Starts a redemption pass in the background unless one is running.
+pub fn redeem(node: &NeuroNode) {
+    let Some(gateway) = node.vouchers.gateway.clone() else {
+        return;
+    };
+    if node.vouchers.redeeming.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let store = Arc::clone(&node.store);
+    let keypair = node.keypair.clone();
+    let redeeming = Arc::clone(&node.vouchers.redeeming);
+    tokio::spawn(
+        async move {
+            if let Err(e) = redeem_expired(&store, &keypair, &gateway).await {
+                warn!(error = %e, "Voucher redemption failed, retrying next pass");
+            }
+            redeeming.store(false, Ordering::SeqCst);
+        }
+        .instrument(info_span!("voucher_redeem")),
+    );
+}
+
+/// Redeems every expired voucher in the store, forgetting each one the
+/// gateway settles, already settled or refuses. Stops at the first other
+/// failure so the rest wait for the next pass.
+async fn redeem_expired(
+    store: &SecureBlockStore,
+    keypair: &identity::Keypair,
+    gateway: &str,
+) -> anyhow::Result<()> {
+    let now = now_secs();
+    let url = format!("{}/api/vouchers/redeem", gateway.trim_end_matches('/'));
+    let client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+    for record in store.voucher_records()? {
+        if now < record.expires_at {
+            continue;
+        }
+        if now >= record.expires_at.saturating_add(REDEMPTION_WINDOW.as_secs()) {
+            warn!(voucher = %record.id, bytes = record.bytes, "Voucher went unredeemed past the window, dropped");
+            store.remove_voucher_record(&record.id)?;
+            continue;
+        }
+        let timestamp_ms = chrono::Utc::now().timestamp_millis() as u64;
+        let redemption = Redemption::sign(keypair, &record.token, record.bytes, timestamp_ms)?;
+        let resp = client.post(&url).json(&redemption).send().await?;
+        match resp.status() {
+            status if status.is_success() => {
+                info!(voucher = %record.id, bytes = record.bytes, "Redeemed voucher");
+            }
+            reqwest::StatusCode::CONFLICT => {
+                debug!(voucher = %record.id, "Voucher already redeemed");
+            }
+            reqwest::StatusCode::UNPROCESSABLE_ENTITY => {
+                let reason = resp.text().await.unwrap_or_default();
+                warn!(voucher = %record.id, reason = %reason.trim(), "Gateway refused voucher, dropped");
+            }
+            status => anyhow::bail!("{url} returned {status}"),
+        }
+        store.remove_voucher_record(&record.id)?;
+    }
+    Ok(())
+}
+
+fn now_secs() -> u64 {
+    chrono::Utc::now().timestamp().max(0) as u64
+}
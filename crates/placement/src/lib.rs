@@ -0,0 +1,539 @@
+//! Shard placement: which peers hold a given shard. Every strategy is a pure
+//! function of the shard key and the candidate list, so the uploader, its
+//! autopilot and the gateway pick the same peers for the same shard without
+//! coordinating, and a peer joining or leaving only moves the shards it
+//! gains or held.
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Score for peers nothing is known about yet, on the sentinel's 0–100 scale.
+pub const NEUTRAL_SCORE: u8 = 50;
+
+/// A peer that may receive a shard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub id: String,
+    /// 0–100; higher scores win proportionally more shards.
+    pub score: u8,
+    /// Country, zone or any other failure domain the peer sits in.
+    pub region: Option<String>,
+    /// Space the peer reports free. `Some(0)` takes it out of capacity-aware
+    /// placement entirely.
+    pub free_bytes: Option<u64>,
+}
+
+impl Candidate {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            score: NEUTRAL_SCORE,
+            region: None,
+            free_bytes: None,
+        }
+    }
+
+    pub fn with_score(mut self, score: u8) -> Self {
+        self.score = score;
+        self
+    }
+
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+
+    pub fn with_free_bytes(mut self, free_bytes: u64) -> Self {
+        self.free_bytes = Some(free_bytes);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// Weighted rendezvous hashing: each peer's share of shards follows its
+    /// score, and churn only moves the shards of the peer that changed.
+    #[default]
+    Rendezvous,
+    /// Consecutive peers in id order from a per-shard offset. Spreads shards
+    /// exactly evenly but ignores scores, and a peer joining shifts the
+    /// placement of its neighbours' shards.
+    RoundRobin,
+    /// Rendezvous order, but no two replicas share a region while an unused
+    /// region remains.
+    RegionAware,
+    /// Rendezvous weighted by score times free space; full peers are skipped.
+    CapacityAware,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 4] = [
+        Strategy::Rendezvous,
+        Strategy::RoundRobin,
+        Strategy::RegionAware,
+        Strategy::CapacityAware,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Strategy::Rendezvous => "rendezvous",
+            Strategy::RoundRobin => "round_robin",
+            Strategy::RegionAware => "region_aware",
+            Strategy::CapacityAware => "capacity_aware",
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("unknown placement strategy {0:?}; expected rendezvous, round_robin, region_aware or capacity_aware")]
+pub struct UnknownStrategy(pub String);
+
+impl FromStr for Strategy {
+    type Err = UnknownStrategy;
+
+    /// Accepts the serde names and their dashed spellings.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized = s.trim().to_ascii_lowercase().replace('-', "_");
+        Strategy::ALL
+            .into_iter()
+            .find(|strategy| strategy.as_str() == normalized)
+            .ok_or_else(|| UnknownStrategy(s.to_string()))
+    }
+}
+
+/// Up to `replicas` distinct candidates for the shard named `key`, best
+/// first. Candidates are told apart by id; repeats after the first are
+/// ignored.
+pub fn place<'a>(strategy: Strategy, key: &str, candidates: &'a [Candidate], replicas: usize) -> Vec<&'a Candidate> {
+    let mut seen = HashSet::new();
+    let candidates: Vec<&Candidate> = candidates.iter().filter(|c| seen.insert(c.id.as_str())).collect();
+    match strategy {
+        Strategy::Rendezvous => rank(key, &candidates, |c| score_weight(c.score))
+            .into_iter()
+            .take(replicas)
+            .collect(),
+        Strategy::RoundRobin => round_robin(key, candidates, replicas),
+        Strategy::RegionAware => spread_regions(rank(key, &candidates, |c| score_weight(c.score)), replicas),
+        Strategy::CapacityAware => {
+            let fallback = mean_free_bytes(&candidates);
+            let with_space: Vec<&Candidate> = candidates.into_iter().filter(|c| c.free_bytes != Some(0)).collect();
+            rank(key, &with_space, |c| {
+                score_weight(c.score) * c.free_bytes.map_or(fallback, |free| free as f64)
+            })
+            .into_iter()
+            .take(replicas)
+            .collect()
+        }
+    }
+}
+
+/// [`place`] over bare peer ids with optional scores, the form the uploader
+/// keeps its peers in. Returns ids.
+pub fn place_ids(
+    strategy: Strategy,
+    key: &str,
+    peers: &[String],
+    score: impl Fn(&str) -> Option<u8>,
+    replicas: usize,
+) -> Vec<String> {
+    let candidates: Vec<Candidate> = peers
+        .iter()
+        .map(|peer| Candidate::new(peer.clone()).with_score(score(peer).unwrap_or(NEUTRAL_SCORE)))
+        .collect();
+    place(strategy, key, &candidates, replicas)
+        .into_iter()
+        .map(|c| c.id.clone())
+        .collect()
+}
+
+/// Why [`place_spread`] could not cover the requested regions.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SpreadError {
+    #[error("{wanted} distinct regions need at least {wanted} replicas per shard, but only {replicas} are placed")]
+    TooFewReplicas { wanted: usize, replicas: usize },
+    #[error("{wanted} distinct regions requested, but the eligible peers cover only {available}: {regions:?}")]
+    TooFewRegions {
+        wanted: usize,
+        available: usize,
+        regions: Vec<String>,
+    },
+}
+
+/// [`place`], but the replicas must cover at least `min_regions` distinct
+/// regions. When the plain placement already does, it is returned as is, so
+/// the constraint moves nothing it does not have to. Otherwise the best
+/// peer of each region not yet held replaces the lowest ranked repeat.
+/// Peers with no region never count towards the spread.
+pub fn place_spread<'a>(
+    strategy: Strategy,
+    key: &str,
+    candidates: &'a [Candidate],
+    replicas: usize,
+    min_regions: usize,
+) -> Result<Vec<&'a Candidate>, SpreadError> {
+    if min_regions > replicas {
+        return Err(SpreadError::TooFewReplicas { wanted: min_regions, replicas });
+    }
+    let ordered = place(strategy, key, candidates, usize::MAX);
+    let mut regions: Vec<String> = ordered.iter().filter_map(|c| c.region.clone()).collect();
+    regions.sort();
+    regions.dedup();
+    if regions.len() < min_regions {
+        return Err(SpreadError::TooFewRegions {
+            wanted: min_regions,
+            available: regions.len(),
+            regions,
+        });
+    }
+
+    let plain: Vec<&Candidate> = ordered.iter().take(replicas).copied().collect();
+    if region_count(&plain) >= min_regions {
+        return Ok(plain);
+    }
+    let mut held = HashSet::new();
+    let mut chosen: HashSet<&str> = HashSet::new();
+    for candidate in &ordered {
+        if held.len() == min_regions {
+            break;
+        }
+        if let Some(region) = &candidate.region {
+            if held.insert(region.as_str()) {
+                chosen.insert(candidate.id.as_str());
+            }
+        }
+    }
+    for candidate in &ordered {
+        if chosen.len() == replicas {
+            break;
+        }
+        chosen.insert(candidate.id.as_str());
+    }
+    Ok(ordered.into_iter().filter(|c| chosen.contains(c.id.as_str())).collect())
+}
+
+/// [`place_spread`] over bare peer ids, with scores and regions looked up
+/// per peer. Returns ids.
+pub fn place_ids_spread(
+    strategy: Strategy,
+    key: &str,
+    peers: &[String],
+    score: impl Fn(&str) -> Option<u8>,
+    region: impl Fn(&str) -> Option<String>,
+    replicas: usize,
+    min_regions: usize,
+) -> Result<Vec<String>, SpreadError> {
+    let candidates: Vec<Candidate> = peers
+        .iter()
+        .map(|peer| Candidate {
+            region: region(peer),
+            ..Candidate::new(peer.clone()).with_score(score(peer).unwrap_or(NEUTRAL_SCORE))
+        })
+        .collect();
+    Ok(place_spread(strategy, key, &candidates, replicas, min_regions)?
+        .into_iter()
+        .map(|c| c.id.clone())
+        .collect())
+}
+
+fn region_count(placed: &[&Candidate]) -> usize {
+    placed.iter().filter_map(|c| c.region.as_deref()).collect::<HashSet<_>>().len()
+}
+
+/// Every candidate ordered by its weighted rendezvous score for `key`,
+/// highest first. Each peer draws `-weight / ln(u)` with `u` uniform in
+/// (0, 1) from a hash of key and id, which makes the chance of ranking
+/// first proportional to weight.
+fn rank<'a>(key: &str, candidates: &[&'a Candidate], weight: impl Fn(&Candidate) -> f64) -> Vec<&'a Candidate> {
+    let mut ranked: Vec<(f64, &Candidate)> = candidates
+        .iter()
+        .map(|c| (-weight(c) / unit_interval(key, &c.id).ln(), *c))
+        .collect();
+    ranked.sort_by(|a, b| {
+        b.0.partial_cmp(&a.0)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| a.1.id.cmp(&b.1.id))
+    });
+    ranked.into_iter().map(|(_, c)| c).collect()
+}
+
+fn round_robin<'a>(key: &str, mut candidates: Vec<&'a Candidate>, replicas: usize) -> Vec<&'a Candidate> {
+    if candidates.is_empty() {
+        return Vec::new();
+    }
+    candidates.sort_by(|a, b| a.id.cmp(&b.id));
+    let start = (key_hash(key, "") % candidates.len() as u64) as usize;
+    (0..replicas.min(candidates.len()))
+        .map(|i| candidates[(start + i) % candidates.len()])
+        .collect()
+}
+
+/// Takes `ranked` in order, skipping peers whose region already holds a
+/// replica until every region is used, then fills from the skipped ones.
+/// Peers with no region never count as a repeat.
+fn spread_regions(ranked: Vec<&Candidate>, replicas: usize) -> Vec<&Candidate> {
+    let mut used_regions = HashSet::new();
+    let mut chosen = Vec::new();
+    let mut deferred = Vec::new();
+    for candidate in ranked {
+        if chosen.len() == replicas {
+            break;
+        }
+        match &candidate.region {
+            Some(region) if !used_regions.insert(region.as_str()) => deferred.push(candidate),
+            _ => chosen.push(candidate),
+        }
+    }
+    let missing = replicas - chosen.len();
+    chosen.extend(deferred.into_iter().take(missing));
+    chosen
+}
+
+/// Scores of 0 still place, just rarely, so a lone poorly scored peer is
+/// not unusable.
+fn score_weight(score: u8) -> f64 {
+    f64::from(score.max(1))
+}
+
+/// Peers that report no free space are weighted as if they had the mean of
+/// those that do.
+fn mean_free_bytes(candidates: &[&Candidate]) -> f64 {
+    let known: Vec<f64> = candidates.iter().filter_map(|c| c.free_bytes).map(|f| f as f64).collect();
+    if known.is_empty() {
+        1.0
+    } else {
+        (known.iter().sum::<f64>() / known.len() as f64).max(1.0)
+    }
+}
+
+fn key_hash(key: &str, id: &str) -> u64 {
+    let mut hasher = Sha256::new();
+    hasher.update(key.as_bytes());
+    hasher.update(b"|");
+    hasher.update(id.as_bytes());
+    let digest = hasher.finalize();
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// The top 53 bits of the key hash as a float strictly inside (0, 1).
+fn unit_interval(key: &str, id: &str) -> f64 {
+    ((key_hash(key, id) >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::{any, prop_assert, prop_assert_eq, prop_assume, proptest, Just};
+    use proptest::strategy::Strategy as _;
+    use std::collections::HashMap;
+
+    fn peers(n: usize) -> Vec<Candidate> {
+        (0..n).map(|i| Candidate::new(format!("peer-{i}"))).collect()
+    }
+
+    fn ids(placed: &[&Candidate]) -> Vec<String> {
+        placed.iter().map(|c| c.id.clone()).collect()
+    }
+
+    fn counts(strategy: Strategy, candidates: &[Candidate], keys: usize) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for k in 0..keys {
+            for c in place(strategy, &format!("shard-{k}"), candidates, 1) {
+                *counts.entry(c.id.clone()).or_default() += 1;
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn strategies_parse_from_their_names() {
+        for strategy in Strategy::ALL {
+            assert_eq!(strategy.as_str().parse::<Strategy>().unwrap(), strategy);
+        }
+        assert_eq!("Round-Robin".parse::<Strategy>().unwrap(), Strategy::RoundRobin);
+        assert!("random".parse::<Strategy>().is_err());
+    }
+
+    #[test]
+    fn equal_peers_share_shards_evenly() {
+        let candidates = peers(10);
+        for strategy in Strategy::ALL {
+            let counts = counts(strategy, &candidates, 20_000);
+            for c in &candidates {
+                let n = counts.get(&c.id).copied().unwrap_or_default();
+                assert!((1700..=2300).contains(&n), "{strategy}: {} got {n} of 20000", c.id);
+            }
+        }
+    }
+
+    #[test]
+    fn shares_follow_score_and_free_space() {
+        let candidates = vec![
+            Candidate::new("a").with_score(80).with_free_bytes(1 << 30),
+            Candidate::new("b").with_score(40).with_free_bytes(1 << 30),
+            Candidate::new("c").with_score(40).with_free_bytes(2 << 30),
+            Candidate::new("full").with_score(100).with_free_bytes(0),
+        ];
+        let by_score = counts(Strategy::Rendezvous, &candidates, 20_000);
+        let ratio = by_score["a"] as f64 / by_score["b"] as f64;
+        assert!((1.8..2.2).contains(&ratio), "score ratio {ratio}");
+
+        let by_space = counts(Strategy::CapacityAware, &candidates, 20_000);
+        assert!(!by_space.contains_key("full"));
+        let ratio = by_space["c"] as f64 / by_space["b"] as f64;
+        assert!((1.8..2.2).contains(&ratio), "capacity ratio {ratio}");
+    }
+
+    #[test]
+    fn region_aware_spreads_replicas_before_repeating() {
+        let candidates: Vec<Candidate> = (0..9)
+            .map(|i| Candidate::new(format!("peer-{i}")).with_region(["eu", "us", "ap"][i % 3]))
+            .collect();
+        for k in 0..200 {
+            let placed = place(Strategy::RegionAware, &format!("shard-{k}"), &candidates, 3);
+            let regions: HashSet<_> = placed.iter().map(|c| c.region.clone()).collect();
+            assert_eq!(regions.len(), 3);
+            assert_eq!(place(Strategy::RegionAware, &format!("shard-{k}"), &candidates, 5).len(), 5);
+        }
+    }
+
+    #[test]
+    fn spread_reaches_low_scored_regions_only_when_asked() {
+        let mut candidates: Vec<Candidate> = (0..6)
+            .map(|i| Candidate::new(format!("eu-{i}")).with_score(100).with_region("eu"))
+            .collect();
+        candidates.push(Candidate::new("us-0").with_score(5).with_region("us"));
+        candidates.push(Candidate::new("ap-0").with_score(5).with_region("ap"));
+        for k in 0..100 {
+            let key = format!("shard-{k}");
+            let placed = place_spread(Strategy::Rendezvous, &key, &candidates, 3, 3).unwrap();
+            assert_eq!(placed.len(), 3);
+            assert_eq!(region_count(&placed), 3);
+            assert_eq!(
+                place_spread(Strategy::Rendezvous, &key, &candidates, 3, 1).unwrap(),
+                place(Strategy::Rendezvous, &key, &candidates, 3)
+            );
+        }
+        assert_eq!(
+            place_spread(Strategy::Rendezvous, "k", &candidates, 2, 3).unwrap_err(),
+            SpreadError::TooFewReplicas { wanted: 3, replicas: 2 }
+        );
+        assert_eq!(
+            place_spread(Strategy::Rendezvous, "k", &candidates, 4, 4).unwrap_err(),
+            SpreadError::TooFewRegions {
+                wanted: 4,
+                available: 3,
+                regions: vec!["ap".into(), "eu".into(), "us".into()],
+            }
+        );
+    }
+
+    fn peer_set() -> impl proptest::strategy::Strategy<Value = Vec<Candidate>> {
+        proptest::collection::btree_set(0u32..10_000, 1..24).prop_flat_map(|ids| {
+            let n = ids.len();
+            (
+                Just(ids),
+                proptest::collection::vec((0u8..=100, 0usize..4, proptest::option::of(0u64..1 << 40)), n),
+            )
+                .prop_map(|(ids, attrs)| {
+                    ids.into_iter()
+                        .zip(attrs)
+                        .map(|(id, (score, region, free))| Candidate {
+                            id: format!("peer-{id}"),
+                            score,
+                            region: Some(format!("r{region}")),
+                            free_bytes: free,
+                        })
+                        .collect()
+                })
+        })
+    }
+
+    fn any_strategy() -> impl proptest::strategy::Strategy<Value = Strategy> {
+        proptest::sample::select(Strategy::ALL.to_vec())
+    }
+
+    proptest! {
+        #[test]
+        fn placement_is_deterministic_and_distinct(
+            strategy in any_strategy(),
+            candidates in peer_set(),
+            key in "[a-z0-9]{1,64}",
+            replicas in 1usize..8,
+        ) {
+            let placed = ids(&place(strategy, &key, &candidates, replicas));
+            let eligible = candidates
+                .iter()
+                .filter(|c| strategy != Strategy::CapacityAware || c.free_bytes != Some(0))
+                .count();
+            prop_assert_eq!(placed.len(), replicas.min(eligible));
+            prop_assert_eq!(placed.iter().collect::<HashSet<_>>().len(), placed.len());
+
+            let mut shuffled = candidates.clone();
+            shuffled.reverse();
+            prop_assert_eq!(ids(&place(strategy, &key, &shuffled, replicas)), placed);
+        }
+
+        #[test]
+        fn spread_placements_cover_the_regions_asked_for(
+            strategy in any_strategy(),
+            candidates in peer_set(),
+            key in "[a-z0-9]{1,64}",
+            replicas in 1usize..8,
+            min_regions in 0usize..5,
+        ) {
+            let plain = ids(&place(strategy, &key, &candidates, replicas));
+            match place_spread(strategy, &key, &candidates, replicas, min_regions) {
+                Ok(placed) => {
+                    prop_assert_eq!(placed.len(), plain.len());
+                    prop_assert!(region_count(&placed) >= min_regions);
+                    prop_assert_eq!(placed.iter().map(|c| c.id.as_str()).collect::<HashSet<_>>().len(), placed.len());
+                }
+                Err(SpreadError::TooFewReplicas { .. }) => prop_assert!(min_regions > replicas),
+                Err(SpreadError::TooFewRegions { available, .. }) => prop_assert!(available < min_regions),
+            }
+        }
+
+        #[test]
+        fn losing_an_unused_peer_moves_nothing(
+            strategy in proptest::sample::select(vec![Strategy::Rendezvous, Strategy::RegionAware]),
+            candidates in peer_set(),
+            key in "[a-z0-9]{1,64}",
+            replicas in 1usize..6,
+            victim in any::<proptest::sample::Index>(),
+        ) {
+            let placed = ids(&place(strategy, &key, &candidates, replicas));
+            let victim = victim.get(&candidates).id.clone();
+            prop_assume!(!placed.contains(&victim));
+            let remaining: Vec<Candidate> = candidates.into_iter().filter(|c| c.id != victim).collect();
+            prop_assert_eq!(ids(&place(strategy, &key, &remaining, replicas)), placed);
+        }
+
+        #[test]
+        fn a_joining_peer_displaces_at_most_one_replica(
+            candidates in peer_set(),
+            key in "[a-z0-9]{1,64}",
+            replicas in 1usize..6,
+        ) {
+            let before: HashSet<String> = ids(&place(Strategy::Rendezvous, &key, &candidates, replicas)).into_iter().collect();
+            let mut grown = candidates;
+            grown.push(Candidate::new("newcomer"));
+            let after: HashSet<String> = ids(&place(Strategy::Rendezvous, &key, &grown, replicas)).into_iter().collect();
+            prop_assert!(before.difference(&after).count() <= 1);
+            prop_assert!(after.difference(&before).all(|id| id == "newcomer"));
+        }
+    }
+}
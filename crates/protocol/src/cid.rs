@@ -0,0 +1,188 @@
+//! Shard identifiers. Shards are addressed by the SHA-256 of their bytes,
+//! written either as bare lowercase hex (the original format) or as a CIDv1
+//! (`raw` codec, sha2-256 multihash, base32 multibase) that IPFS-compatible
+//! systems accept as-is. Nodes treat CIDs as opaque keys; only writers pick a
+//! format, and readers accept both.
+//!
+//! A CIDv1 may instead carry a BLAKE3 multihash, which is much cheaper to
+//! compute over large uploads. Bare hex names no algorithm, so it always
+//! means SHA-256 and BLAKE3 shards are only ever written as CIDv1.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// CIDv1 prefix: version 1, `raw` codec (0x55), sha2-256 (0x12), 32 bytes.
+const CIDV1_PREFIX: [u8; 4] = [0x01, 0x55, 0x12, 0x20];
+/// As above with the blake3 multihash (0x1e, a one-byte varint).
+const CIDV1_BLAKE3_PREFIX: [u8; 4] = [0x01, 0x55, 0x1e, 0x20];
+/// Inputs at least this large are hashed across threads when BLAKE3 is
+/// built with `parallel`; below it the thread handoff costs more than it saves.
+#[cfg(feature = "parallel")]
+const BLAKE3_PARALLEL_MIN: usize = 128 * 1024;
+/// Multibase prefix for lowercase RFC 4648 base32 without padding.
+const MULTIBASE_BASE32: char = 'b';
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CidFormat {
+    /// 64 lowercase hex characters.
+    #[default]
+    Sha256Hex,
+    /// `bafkrei…`, 59 characters.
+    V1,
+}
+
+impl CidFormat {
+    /// Which format `cid` is written in, if it is a well-formed shard CID.
+    pub fn of(cid: &str) -> Option<Self> {
+        parse(cid).map(|(format, _, _)| format)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    /// CIDv1 only.
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// Which algorithm `cid` was hashed with, if it is a well-formed shard CID.
+    pub fn of(cid: &str) -> Option<Self> {
+        parse(cid).map(|(_, algorithm, _)| algorithm)
+    }
+
+    pub fn digest(self, data: &[u8]) -> [u8; 32] {
+        match self {
+            HashAlgorithm::Sha256 => Sha256::digest(data).into(),
+            HashAlgorithm::Blake3 => {
+                let mut hasher = blake3::Hasher::new();
+                #[cfg(feature = "parallel")]
+                if data.len() >= BLAKE3_PARALLEL_MIN {
+                    hasher.update_rayon(data);
+                    return hasher.finalize().into();
+                }
+                hasher.update(data);
+                hasher.finalize().into()
+            }
+        }
+    }
+
+    /// Whether CIDs in `format` can name this algorithm.
+    pub fn writable_as(self, format: CidFormat) -> bool {
+        self == HashAlgorithm::Sha256 || format == CidFormat::V1
+    }
+}
+
+/// Writes a SHA-256 digest as a shard CID.
+pub fn encode(format: CidFormat, digest: &[u8; 32]) -> String {
+    encode_with(format, HashAlgorithm::Sha256, digest).expect("sha256 is writable in every format")
+}
+
+/// Writes a digest made with `algorithm` as a shard CID; `None` when the
+/// format cannot name the algorithm (BLAKE3 as bare hex).
+pub fn encode_with(format: CidFormat, algorithm: HashAlgorithm, digest: &[u8; 32]) -> Option<String> {
+    if !algorithm.writable_as(format) {
+        return None;
+    }
+    Some(match format {
+        CidFormat::Sha256Hex => digest.iter().map(|b| format!("{b:02x}")).collect(),
+        CidFormat::V1 => {
+            let prefix = match algorithm {
+                HashAlgorithm::Sha256 => CIDV1_PREFIX,
+                HashAlgorithm::Blake3 => CIDV1_BLAKE3_PREFIX,
+            };
+            let mut bytes = Vec::with_capacity(prefix.len() + digest.len());
+            bytes.extend_from_slice(&prefix);
+            bytes.extend_from_slice(digest);
+            let mut out = String::with_capacity(60);
+            out.push(MULTIBASE_BASE32);
+            base32_encode(&bytes, &mut out);
+            out
+        }
+    })
+}
+
+/// The CID of `data` in `format` under `algorithm`.
+pub fn for_data(format: CidFormat, algorithm: HashAlgorithm, data: &[u8]) -> Option<String> {
+    encode_with(format, algorithm, &algorithm.digest(data))
+}
+
+/// Splits a shard CID into its format, hash algorithm and digest. Anything
+/// other than a sha2-256 or blake3 `raw` CIDv1 or 64 hex characters is
+/// rejected.
+pub fn parse(cid: &str) -> Option<(CidFormat, HashAlgorithm, [u8; 32])> {
+    let mut digest = [0u8; 32];
+    if cid.len() == 64 {
+        for (i, pair) in cid.as_bytes().chunks(2).enumerate() {
+            let hex = std::str::from_utf8(pair).ok()?;
+            digest[i] = u8::from_str_radix(hex, 16).ok()?;
+        }
+        return Some((CidFormat::Sha256Hex, HashAlgorithm::Sha256, digest));
+    }
+
+    let body = cid.strip_prefix(MULTIBASE_BASE32)?;
+    let bytes = base32_decode(body)?;
+    if bytes.len() != CIDV1_PREFIX.len() + 32 {
+        return None;
+    }
+    let algorithm = match &bytes[..4] {
+        p if p == CIDV1_PREFIX.as_slice() => HashAlgorithm::Sha256,
+        p if p == CIDV1_BLAKE3_PREFIX.as_slice() => HashAlgorithm::Blake3,
+        _ => return None,
+    };
+    digest.copy_from_slice(&bytes[4..]);
+    Some((CidFormat::V1, algorithm, digest))
+}
+
+/// True when `cid`, in either format, names content with this SHA-256 digest.
+pub fn matches(cid: &str, digest: &[u8; 32]) -> bool {
+    parse(cid).is_some_and(|(_, algorithm, d)| algorithm == HashAlgorithm::Sha256 && &d == digest)
+}
+
+/// True when `cid` names `data`, hashing it with whichever algorithm the
+/// CID declares.
+pub fn verify(cid: &str, data: &[u8]) -> bool {
+    parse(cid).is_some_and(|(_, algorithm, d)| algorithm.digest(data) == d)
+}
+
+fn base32_encode(bytes: &[u8], out: &mut String) {
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+}
+
+fn base32_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in text.bytes() {
+        let value = BASE32_ALPHABET.iter().position(|&a| a == c)? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    // Leftover bits are padding and must be zero for a canonical encoding.
+    if bits >= 5 || buffer != 0 {
+        return None;
+    }
+    Some(out)
+}
@@ -1,5 +1,11 @@
+pub mod cid;
+#[cfg(feature = "grpc")]
+pub mod sentinel;
+pub mod wire;
+
 use libp2p_identity::{PeerId, PublicKey};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StoreChunkRequest {
@@ -12,17 +18,49 @@ pub struct RetrieveChunkRequest {
     pub cid: String,
 }
 
+/// A retrieve on behalf of a client holding a bandwidth voucher. The node
+/// counts the shard against the voucher's byte cap, refuses it once the cap
+/// is spent or the voucher has expired, and later redeems what it served.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoucheredRetrieveRequest {
+    pub cid: String,
+    /// The `neuro-voucher` token, passed through from the client.
+    pub voucher: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeleteChunkRequest {
     pub cid: String,
 }
 
+/// Deletes several chunks in one round trip. At most `MAX_DELETE_CIDS` are
+/// acted on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteChunksRequest {
+    pub cids: Vec<String>,
+}
+
+pub const MAX_DELETE_CIDS: usize = 256;
+
+
+/// Asks which of `cids` the node already holds intact, so an uploader can
+/// skip sending them again. At most `MAX_HAS_CIDS` are answered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HasChunksRequest {
+    pub cids: Vec<String>,
+}
+
+pub const MAX_HAS_CIDS: usize = 256;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuditChunkRequest {
     pub cid: String,
     pub challenge_hex: String,
     pub nonce_hex: String,
+    /// When the auditor issued the challenge; nodes refuse requests older
+    /// than their replay window, so they only need to remember nonces that
+    /// long.
+    pub issued_at_ms: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,6 +79,15 @@ pub struct DeleteChunkResponse {
     pub public_key: Vec<u8>,
 }
 
+/// The requested CIDs the node deleted; ones it did not hold are left out.
+/// Signed over every CID it was asked to delete, like a single deletion.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteChunksResponse {
+    pub deleted: Vec<String>,
+    pub timestamp_ms: u64,
+    pub signature: Vec<u8>,
+    pub public_key: Vec<u8>,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RetrieveChunkResponse {
@@ -51,6 +98,16 @@ pub struct RetrieveChunkResponse {
     pub public_key: Vec<u8>,
 }
 
+/// The requested CIDs the node holds, signed like a store receipt so it can
+/// stand in for one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HasChunksResponse {
+    pub held: Vec<String>,
+    pub timestamp_ms: u64,
+    pub signature: Vec<u8>,
+    pub public_key: Vec<u8>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuditChunkResponse {
     pub found: bool,
@@ -61,12 +118,33 @@ pub struct AuditChunkResponse {
     pub public_key: Vec<u8>,
 }
 
+/// Sent instead of a command's reply when the node's disk queue is full.
+/// Nothing was read or written; the requester should back off for
+/// `retry_after_ms` or go to another holder.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BusyResponse {
+    pub retry_after_ms: u64,
+}
+
+/// Sent instead of a `Store` reply while the node is in maintenance. It
+/// keeps serving retrieves and audits; the requester should place the shard
+/// on another peer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceResponse {
+    /// When the node went into maintenance.
+    pub since_ms: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ChunkCommand {
     Store(StoreChunkRequest),
     Retrieve(RetrieveChunkRequest),
     Audit(AuditChunkRequest),
     Delete(DeleteChunkRequest),
+    Has(HasChunksRequest),
+    DeleteBatch(DeleteChunksRequest),
+    /// Answered with `ChunkReply::Retrieve`, like `Retrieve`.
+    RetrieveVouchered(VoucheredRetrieveRequest),
 }
 
 
@@ -76,6 +154,158 @@ pub enum ChunkReply {
     Retrieve(RetrieveChunkResponse),
     Audit(AuditChunkResponse),
     Delete(DeleteChunkResponse),
+    Has(HasChunksResponse),
+    Busy(BusyResponse),
+    DeleteBatch(DeleteChunksResponse),
+    /// The command was malformed or out of bounds and was not run.
+    Rejected(wire::RejectedResponse),
+    Maintenance(MaintenanceResponse),
+}
+
+/// identify protocol version spoken by every NeuroStore peer.
+pub const PROTOCOL_VERSION: &str = "/neurostore/2.0.0";
+
+/// `ChunkCommand::Has`.
+pub const FEATURE_HAS: &str = "has";
+
+/// `ChunkCommand::DeleteBatch`.
+pub const FEATURE_DELETE_BATCH: &str = "delete-batch";
+
+/// Verifies BLAKE3 CIDs when repairing and restoring chunks; older nodes
+/// can only check SHA-256 ones.
+pub const FEATURE_BLAKE3: &str = "blake3";
+
+/// `ChunkCommand::RetrieveVouchered`.
+pub const FEATURE_VOUCHERS: &str = "vouchers";
+
+/// Features this build of the node serves, advertised in its identify agent
+/// version.
+pub const NODE_FEATURES: &[&str] = &[FEATURE_HAS, FEATURE_DELETE_BATCH, FEATURE_BLAKE3, FEATURE_VOUCHERS];
+
+/// `<name>/<version> (<feature>,<feature>)`, the identify agent version
+/// [`PeerCapabilities::from_identify`] reads back.
+pub fn agent_version(name: &str, version: &str, features: &[&str]) -> String {
+    format!("{name}/{version} ({})", features.join(","))
+}
+
+/// What a peer advertised over identify. Peers from before feature flags
+/// advertise none, so they only ever get base protocol commands.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerCapabilities {
+    pub protocol_version: String,
+    pub agent_version: String,
+    pub features: Vec<String>,
+}
+
+impl PeerCapabilities {
+    pub fn from_identify(protocol_version: &str, agent_version: &str) -> Self {
+        let features = agent_version
+            .rsplit_once(" (")
+            .and_then(|(_, rest)| rest.strip_suffix(')'))
+            .map(|list| {
+                list.split(',')
+                    .map(str::trim)
+                    .filter(|f| !f.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            protocol_version: protocol_version.to_string(),
+            agent_version: agent_version.to_string(),
+            features,
+        }
+    }
+
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+
+    /// Whether `command` may be sent to this peer.
+    pub fn accepts(&self, command: &ChunkCommand) -> bool {
+        command.required_feature().is_none_or(|f| self.supports(f))
+    }
+}
+
+impl ChunkCommand {
+    /// The feature a peer must advertise to understand this command; `None`
+    /// for the base protocol every node serves.
+    pub fn required_feature(&self) -> Option<&'static str> {
+        match self {
+            ChunkCommand::Has(_) => Some(FEATURE_HAS),
+            ChunkCommand::DeleteBatch(_) => Some(FEATURE_DELETE_BATCH),
+            ChunkCommand::RetrieveVouchered(_) => Some(FEATURE_VOUCHERS),
+            ChunkCommand::Store(_)
+            | ChunkCommand::Retrieve(_)
+            | ChunkCommand::Audit(_)
+            | ChunkCommand::Delete(_) => None,
+        }
+    }
+}
+
+/// Gossip topic nodes announce their own state on.
+pub const ANNOUNCE_TOPIC: &str = "neurostore-announce";
+
+/// Published by a node on [`ANNOUNCE_TOPIC`] about itself; the gossip
+/// message's signed source is the node it concerns.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum NodeAnnouncement {
+    /// Entering (repeated while it lasts, for peers that join later) or
+    /// leaving maintenance. Placement leaves a node in maintenance out.
+    Maintenance { enabled: bool, since_ms: u64 },
+}
+
+/// Gossip topic deletions are propagated on, so holders that were offline
+/// when a chunk was deleted still drop it once they reconnect.
+pub const TOMBSTONE_TOPIC: &str = "neurostore-tombstones";
+
+/// Keeps each tombstone message comfortably under the gossipsub size limit.
+pub const MAX_TOMBSTONES_PER_MESSAGE: usize = 256;
+
+/// Signed record that chunk `cid` was deleted at `deleted_at_ms`. The
+/// signature is the issuer's own, not the gossip envelope's, so a tombstone
+/// stays verifiable when a node relays it long after it was issued.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tombstone {
+    pub cid: String,
+    pub deleted_at_ms: u64,
+    pub signature: Vec<u8>,
+    pub public_key: Vec<u8>,
+}
+
+impl Tombstone {
+    pub fn payload(cid: &str, deleted_at_ms: u64) -> Vec<u8> {
+        format!("POW:TOMBSTONE:{cid}:{deleted_at_ms}").into_bytes()
+    }
+
+    /// The peer that signed this tombstone, if the signature verifies.
+    pub fn issuer(&self) -> Option<PeerId> {
+        let public_key = PublicKey::try_decode_protobuf(&self.public_key).ok()?;
+        public_key
+            .verify(&Self::payload(&self.cid, self.deleted_at_ms), &self.signature)
+            .then(|| PeerId::from_public_key(&public_key))
+    }
+}
+
+/// Gossiped on [`TOMBSTONE_TOPIC`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TombstoneMessage {
+    /// Deletions to apply, at most [`MAX_TOMBSTONES_PER_MESSAGE`].
+    Tombstones { tombstones: Vec<Tombstone> },
+    /// Sent by a node that (re)joined the swarm: peers answer with the
+    /// tombstones they hold issued after `since_ms`.
+    Sync { since_ms: u64 },
+}
+
+/// Gossiped between nodes so one that lost chunks can find replica holders
+/// and pull fresh copies with `ChunkCommand::Retrieve`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum WantMessage {
+    /// Chunks the sender no longer holds intact.
+    Want { cids: Vec<String> },
+    /// Reply to a `Want`: the subset of its CIDs the sender can serve to
+    /// peer `to`.
+    Have { to: String, cids: Vec<String> },
 }
 
 
@@ -117,6 +347,30 @@ impl DeleteChunkResponse {
     }
 }
 
+impl DeleteChunksResponse {
+    pub fn deletion_payload(requested: &[String], deleted: &[String], timestamp_ms: u64) -> Vec<u8> {
+        format!(
+            "POW:DELETE-BATCH:{}:{}:{timestamp_ms}",
+            requested.join(","),
+            deleted.join(",")
+        )
+        .into_bytes()
+    }
+
+    pub fn verify_deletion(&self, expected_peer_id: &PeerId, requested: &[String]) -> bool {
+        verify_signature(
+            expected_peer_id,
+            &self.public_key,
+            &self.signature,
+            &Self::deletion_payload(requested, &self.deleted, self.timestamp_ms),
+        )
+    }
+
+    pub fn is_fresh(&self, now_ms: u64, max_age_ms: u64) -> bool {
+        now_ms.saturating_sub(self.timestamp_ms) <= max_age_ms
+    }
+}
+
 impl RetrieveChunkResponse {
     pub fn proof_payload(cid: &str, len: usize, timestamp_ms: u64) -> Vec<u8> {
         format!("retrieve:{cid}:{len}:{timestamp_ms}").into_bytes()
@@ -139,6 +393,25 @@ impl RetrieveChunkResponse {
     }
 }
 
+impl HasChunksResponse {
+    pub fn inventory_payload(held: &[String], timestamp_ms: u64) -> Vec<u8> {
+        format!("has:{}:{timestamp_ms}", held.join(",")).into_bytes()
+    }
+
+    pub fn verify_inventory(&self, expected_peer_id: &PeerId) -> bool {
+        verify_signature(
+            expected_peer_id,
+            &self.public_key,
+            &self.signature,
+            &Self::inventory_payload(&self.held, self.timestamp_ms),
+        )
+    }
+
+    pub fn is_fresh(&self, now_ms: u64, max_age_ms: u64) -> bool {
+        now_ms.saturating_sub(self.timestamp_ms) <= max_age_ms
+    }
+}
+
 impl AuditChunkResponse {
     pub fn audit_payload(
         cid: &str,
@@ -180,6 +453,100 @@ impl AuditChunkResponse {
     }
 }
 
+/// Registering a node with a gateway: the node signs the gateway's one-time
+/// challenge together with the details it registers, proving it holds the
+/// key behind `peer_id` and tying the proof to exactly that registration.
+pub fn registration_payload(
+    challenge: &str,
+    peer_id: &str,
+    wallet_address: &str,
+    capacity_gb: i64,
+    declared_location: &str,
+) -> Vec<u8> {
+    format!("POW:REGISTER:{challenge}:{peer_id}:{wallet_address}:{capacity_gb}:{declared_location}")
+        .into_bytes()
+}
+
+pub fn verify_registration(
+    expected_peer_id: &PeerId,
+    public_key: &[u8],
+    signature: &[u8],
+    payload: &[u8],
+) -> bool {
+    verify_signature(expected_peer_id, public_key, signature, payload)
+}
+
+/// A node contesting a gateway penalty, signed with its identity key and
+/// checked with [`verify_registration`]. The reason is bound by its hash.
+pub fn appeal_payload(penalty_id: &str, peer_id: &str, timestamp_ms: u64, reason: &str) -> Vec<u8> {
+    let reason_hash = hex::encode(Sha256::digest(reason.as_bytes()));
+    format!("NEURO:APPEAL:{penalty_id}:{peer_id}:{timestamp_ms}:{reason_hash}").into_bytes()
+}
+
+/// A penalty a gateway issued to a node, signed with the gateway's identity
+/// key so the node can check it before appealing. The detail, which carries
+/// the evidence, is bound by its hash.
+pub fn penalty_payload(
+    penalty_id: &str,
+    peer_id: &str,
+    kind: &str,
+    points: i32,
+    reference: &str,
+    detail: &str,
+    issued_at_ms: i64,
+) -> Vec<u8> {
+    let detail_hash = hex::encode(Sha256::digest(detail.as_bytes()));
+    format!("NEURO:PENALTY:{penalty_id}:{peer_id}:{kind}:{points}:{reference}:{issued_at_ms}:{detail_hash}")
+        .into_bytes()
+}
+
+/// The peer id of the key that signed `payload`, if `signature` verifies
+/// under `public_key` (protobuf-encoded).
+pub fn signer_of(public_key: &[u8], signature: &[u8], payload: &[u8]) -> Option<PeerId> {
+    let public_key = PublicKey::try_decode_protobuf(public_key).ok()?;
+    public_key
+        .verify(payload, signature)
+        .then(|| PeerId::from_public_key(&public_key))
+}
+
+/// Moving a node to a new identity key: the old key signs to authorize the
+/// move and the new key signs to prove it is held, so a gateway can carry
+/// the node's record over to the new peer id.
+pub fn rotation_payload(old_peer_id: &str, new_peer_id: &str, timestamp_ms: u64) -> Vec<u8> {
+    format!("NEURO:ROTATE:{old_peer_id}:{new_peer_id}:{timestamp_ms}").into_bytes()
+}
+
+/// A signed key rotation announcement. Keys are protobuf-encoded and, like
+/// the signatures over [`rotation_payload`], hex.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyRotation {
+    pub old_peer_id: String,
+    pub new_peer_id: String,
+    pub timestamp_ms: u64,
+    pub old_public_key: String,
+    pub old_signature: String,
+    pub new_public_key: String,
+    pub new_signature: String,
+}
+
+impl KeyRotation {
+    pub fn payload(&self) -> Vec<u8> {
+        rotation_payload(&self.old_peer_id, &self.new_peer_id, self.timestamp_ms)
+    }
+
+    /// Both peer ids parse, differ, and each key signed the move.
+    pub fn verify(&self) -> bool {
+        let (Ok(old), Ok(new)) = (self.old_peer_id.parse::<PeerId>(), self.new_peer_id.parse::<PeerId>()) else {
+            return false;
+        };
+        let decode = |s: &str| hex::decode(s).unwrap_or_default();
+        let payload = self.payload();
+        old != new
+            && verify_signature(&old, &decode(&self.old_public_key), &decode(&self.old_signature), &payload)
+            && verify_signature(&new, &decode(&self.new_public_key), &decode(&self.new_signature), &payload)
+    }
+}
+
 fn verify_signature(
     expected_peer_id: &PeerId,
     public_key_bytes: &[u8],
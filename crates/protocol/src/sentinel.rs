@@ -0,0 +1,125 @@
+//! gRPC messages for the sentinel policy service (`proto/sentinel.proto`).
+//! Hand-written prost types so neither side needs `protoc` at build time.
+
+/// Fully-qualified service name, as routed by tonic.
+pub const SERVICE_NAME: &str = "neurostore.sentinel.v1.Sentinel";
+pub const STREAM_METRICS_PATH: &str = "/neurostore.sentinel.v1.Sentinel/StreamMetrics";
+pub const GET_PEER_POLICY_PATH: &str = "/neurostore.sentinel.v1.Sentinel/GetPeerPolicy";
+
+/// Pricing model (INR per GB per month) behind the sentinel's payout rates
+/// and the gateway's cost estimates. Users pay per logical GB; nodes are
+/// paid per physical GB, and payouts may take at most `MAX_COGS_SHARE` of
+/// the charge (₹0.50 of ₹1.00 at 2.0x redundancy).
+pub const PRICE_CURRENCY: &str = "INR";
+pub const USER_CHARGE_PER_GB_MONTH: f64 = 1.00;
+pub const BASE_PHYSICAL_PAYOUT_PER_GB_MONTH: f64 = 0.25;
+pub const MAX_COGS_SHARE: f64 = 0.50;
+
+/// One telemetry sample for a peer.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct NodeMetrics {
+    #[prost(string, tag = "1")]
+    pub peer: String,
+    #[prost(double, tag = "2")]
+    pub latency_ms: f64,
+    #[prost(double, tag = "3")]
+    pub uptime_pct: f64,
+    #[prost(double, tag = "4")]
+    pub verify_success_pct: f64,
+    #[prost(double, tag = "5")]
+    pub bandwidth_mbps: f64,
+    #[prost(double, tag = "6")]
+    pub object_heat_index: f64,
+    #[prost(double, tag = "7")]
+    pub regional_qos_penalty: f64,
+    /// When the sample was taken; 0 means when it arrived.
+    #[prost(uint64, tag = "8")]
+    pub timestamp_ms: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PeerPolicyRequest {
+    #[prost(string, tag = "1")]
+    pub peer: String,
+}
+
+/// The sentinel's verdict on a peer. `action` is one of `promote`, `hold`,
+/// `probation`, `proactive_evict`, `quarantine` or `evict`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PeerPolicy {
+    #[prost(string, tag = "1")]
+    pub peer: String,
+    #[prost(double, tag = "2")]
+    pub score: f64,
+    #[prost(double, tag = "3")]
+    pub reputation: f64,
+    #[prost(string, tag = "4")]
+    pub anomaly_level: String,
+    #[prost(double, tag = "5")]
+    pub anomaly_score: f64,
+    #[prost(string, tag = "6")]
+    pub trend: String,
+    #[prost(double, tag = "7")]
+    pub trend_velocity: f64,
+    #[prost(string, tag = "8")]
+    pub action: String,
+    #[prost(double, tag = "9")]
+    pub churn_probability: f64,
+    #[prost(double, tag = "10")]
+    pub price_per_gb: f64,
+    #[prost(double, tag = "11")]
+    pub confidence: f64,
+    #[prost(uint64, tag = "12")]
+    pub observations: u64,
+    #[prost(uint32, tag = "13")]
+    pub slo_violations: u32,
+    #[prost(double, tag = "14")]
+    pub recommended_redundancy_multiplier: f64,
+    /// Correlated-failure group; empty from a sentinel that predates it.
+    #[prost(string, tag = "15")]
+    pub cohort_id: String,
+    #[prost(uint32, tag = "16")]
+    pub cohort_size: u32,
+    /// 0.0 - 1.0.
+    #[prost(double, tag = "17")]
+    pub collusion_risk: f64,
+    #[prost(uint32, tag = "18")]
+    pub clean_windows: u32,
+    /// Windows until the next step down; 0 in good standing.
+    #[prost(uint32, tag = "19")]
+    pub windows_to_recovery: u32,
+    /// Why, most significant first; empty from a sentinel that predates it
+    /// or runs with `--explain off`.
+    #[prost(message, repeated, tag = "20")]
+    pub explanations: Vec<Explanation>,
+}
+
+/// One reason behind a policy. `kind` is `zscore`, `slo` or `trend`;
+/// `baseline` is the running mean, SLO target or trend threshold `value` is
+/// measured against.
+#[derive(Clone, PartialEq, prost::Message, serde::Serialize, serde::Deserialize)]
+pub struct Explanation {
+    #[prost(string, tag = "1")]
+    pub kind: String,
+    #[prost(string, tag = "2")]
+    pub signal: String,
+    #[prost(double, tag = "3")]
+    pub value: f64,
+    #[prost(double, tag = "4")]
+    pub baseline: f64,
+    #[prost(double, tag = "5")]
+    pub deviation: f64,
+    #[prost(string, tag = "6")]
+    pub detail: String,
+}
+
+impl PeerPolicy {
+    pub fn excludes_peer(&self) -> bool {
+        excludes_peer(&self.action)
+    }
+}
+
+/// Whether a peer under `action` should stop receiving new shards.
+pub fn excludes_peer(action: &str) -> bool {
+    matches!(action, "quarantine" | "evict" | "proactive_evict")
+}
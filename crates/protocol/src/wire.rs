@@ -0,0 +1,154 @@
+//! Bounded decoding of [`ChunkCommand`]s from untrusted peers. A node reads
+//! at most [`MAX_COMMAND_BYTES`] from a stream, decodes them under the same
+//! limit, and checks the fields it uses as storage keys or hashes before
+//! touching its store. Whatever fails is answered with
+//! [`ChunkReply::Rejected`] naming the [`ProtocolError`], so a buggy client
+//! learns why instead of seeing its stream reset.
+
+use crate::{ChunkCommand, ChunkReply};
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+
+/// Largest shard a node stores; gateway and uploader shards stay well below.
+pub const MAX_CHUNK_BYTES: usize = 64 * 1024 * 1024;
+/// Largest encoded command: one full shard plus its CID and framing.
+pub const MAX_COMMAND_BYTES: usize = MAX_CHUNK_BYTES + 64 * 1024;
+/// Longest CID accepted. Hex and CIDv1 shard CIDs are 64 and 59 characters;
+/// gateway-derived ones (`Qm…-shard-N`, `meta-…`) stay under 80.
+pub const MAX_CID_LEN: usize = 128;
+/// Longest audit challenge, in hex characters (64 bytes).
+pub const MAX_CHALLENGE_HEX_LEN: usize = 128;
+/// Longest audit nonce, in hex characters (64 bytes).
+pub const MAX_NONCE_HEX_LEN: usize = 128;
+/// Longest bandwidth voucher token; real ones are well under 512.
+pub const MAX_VOUCHER_LEN: usize = 2048;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProtocolError {
+    /// The request was longer than [`MAX_COMMAND_BYTES`].
+    TooLarge { limit: u64 },
+    /// The bytes were not a `ChunkCommand` this node understands.
+    Malformed(String),
+    /// A CID was empty, longer than [`MAX_CID_LEN`], or used characters
+    /// other than ASCII letters, digits, `-`, `_` and `.`.
+    InvalidCid,
+    /// A hex field held non-hex characters or an odd number of them.
+    InvalidHex { field: String },
+    /// A hex field was longer than its limit, in hex characters.
+    FieldTooLong { field: String, limit: u64 },
+    /// A vouchered retrieve whose voucher was unreadable, expired or spent.
+    VoucherRefused(String),
+}
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolError::TooLarge { limit } => write!(f, "request exceeds {limit} bytes"),
+            ProtocolError::Malformed(reason) => write!(f, "malformed request: {reason}"),
+            ProtocolError::InvalidCid => write!(f, "invalid cid"),
+            ProtocolError::InvalidHex { field } => write!(f, "{field} is not valid hex"),
+            ProtocolError::FieldTooLong { field, limit } => {
+                write!(f, "{field} exceeds {limit} characters")
+            }
+            ProtocolError::VoucherRefused(reason) => write!(f, "voucher refused: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+/// Why a node refused a command without running it. Unsigned: it proves
+/// nothing about the node's store.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejectedResponse {
+    pub error: ProtocolError,
+}
+
+impl From<ProtocolError> for ChunkReply {
+    fn from(error: ProtocolError) -> Self {
+        ChunkReply::Rejected(RejectedResponse { error })
+    }
+}
+
+/// The encoding every peer already speaks (`bincode::serialize`), capped at
+/// [`MAX_COMMAND_BYTES`] so a forged length prefix cannot ask for more.
+fn options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(MAX_COMMAND_BYTES as u64)
+}
+
+/// Decodes and validates one command read from a peer.
+pub fn decode_command(bytes: &[u8]) -> Result<ChunkCommand, ProtocolError> {
+    if bytes.len() > MAX_COMMAND_BYTES {
+        return Err(ProtocolError::TooLarge {
+            limit: MAX_COMMAND_BYTES as u64,
+        });
+    }
+    let command: ChunkCommand = options()
+        .deserialize(bytes)
+        .map_err(|e| ProtocolError::Malformed(e.to_string()))?;
+    command.validate()?;
+    Ok(command)
+}
+
+fn check_cid(cid: &str) -> Result<(), ProtocolError> {
+    let allowed = |b: u8| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.');
+    if cid.is_empty() || cid.len() > MAX_CID_LEN || !cid.bytes().all(allowed) {
+        return Err(ProtocolError::InvalidCid);
+    }
+    Ok(())
+}
+
+fn check_hex(field: &'static str, value: &str, limit: usize) -> Result<(), ProtocolError> {
+    if value.len() > limit {
+        return Err(ProtocolError::FieldTooLong {
+            field: field.to_string(),
+            limit: limit as u64,
+        });
+    }
+    if !value.len().is_multiple_of(2) || !value.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(ProtocolError::InvalidHex {
+            field: field.to_string(),
+        });
+    }
+    Ok(())
+}
+
+impl ChunkCommand {
+    /// Checks the fields a node keys its store by or hashes. Batches longer
+    /// than their limit are still truncated by the handler, as before.
+    pub fn validate(&self) -> Result<(), ProtocolError> {
+        match self {
+            ChunkCommand::Store(req) => {
+                check_cid(&req.cid)?;
+                if req.data.len() > MAX_CHUNK_BYTES {
+                    return Err(ProtocolError::TooLarge {
+                        limit: MAX_CHUNK_BYTES as u64,
+                    });
+                }
+                Ok(())
+            }
+            ChunkCommand::Retrieve(req) => check_cid(&req.cid),
+            ChunkCommand::RetrieveVouchered(req) => {
+                check_cid(&req.cid)?;
+                if req.voucher.len() > MAX_VOUCHER_LEN {
+                    return Err(ProtocolError::FieldTooLong {
+                        field: "voucher".to_string(),
+                        limit: MAX_VOUCHER_LEN as u64,
+                    });
+                }
+                Ok(())
+            }
+            ChunkCommand::Delete(req) => check_cid(&req.cid),
+            ChunkCommand::Audit(req) => {
+                check_cid(&req.cid)?;
+                check_hex("challenge_hex", &req.challenge_hex, MAX_CHALLENGE_HEX_LEN)?;
+                check_hex("nonce_hex", &req.nonce_hex, MAX_NONCE_HEX_LEN)
+            }
+            ChunkCommand::Has(req) => req.cids.iter().try_for_each(|cid| check_cid(cid)),
+            ChunkCommand::DeleteBatch(req) => req.cids.iter().try_for_each(|cid| check_cid(cid)),
+        }
+    }
+}
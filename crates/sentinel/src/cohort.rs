@@ -0,0 +1,243 @@
+// ── Peer Cohort Detection ───────────────────────────────────────
+//
+// Per-peer scoring cannot see nodes that fail or lie together, such as
+// several nodes run by one operator behind one uplink. Every peer keeps a
+// short history of its failure rate and latency; peers whose histories move
+// together (Pearson correlation at or above the threshold) are linked, and
+// each connected group is a cohort. A cohort is named after its
+// lexicographically smallest member, so ids stay stable while membership
+// does; a peer correlated with no one is its own cohort of one.
+//
+// Samples are lined up by round: a round ends when a peer reports a second
+// time, as the gateway sends every peer once per telemetry sweep. Cohorts
+// are recomputed at each round boundary rather than on every sample, since
+// correlation is pairwise over the fleet.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Samples of history kept per peer.
+const COHORT_WINDOW: usize = 32;
+/// Overlapping samples two peers need before they can be linked.
+const MIN_OVERLAP: usize = 8;
+/// Below this variance a series is flat and says nothing about correlation.
+const MIN_VARIANCE: f64 = 1e-6;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cohort {
+    pub id: String,
+    pub size: usize,
+    /// 0.0 - 1.0: how strongly and how widely the peer moves with others.
+    pub collusion_risk: f64,
+}
+
+impl Cohort {
+    fn alone(peer: &str) -> Self {
+        Self {
+            id: peer.to_string(),
+            size: 1,
+            collusion_risk: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    round: u64,
+    failure: f64,
+    latency_ms: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct CohortTracker {
+    series: HashMap<String, VecDeque<Sample>>,
+    cohorts: HashMap<String, Cohort>,
+    round: u64,
+    reported: HashSet<String>,
+}
+
+impl CohortTracker {
+    /// Records a sample for `peer` and returns its current cohort.
+    /// `failure` is any "how badly is it doing" measure where higher is
+    /// worse; `latency_ms` of zero means unknown.
+    pub fn observe(&mut self, peer: &str, failure: f64, latency_ms: f64, threshold: f64) -> Cohort {
+        if !self.reported.insert(peer.to_string()) {
+            self.round += 1;
+            self.reported.clear();
+            self.reported.insert(peer.to_string());
+            self.refresh(threshold);
+        }
+        let series = self.series.entry(peer.to_string()).or_default();
+        if series.len() == COHORT_WINDOW {
+            series.pop_front();
+        }
+        series.push_back(Sample {
+            round: self.round,
+            failure,
+            latency_ms,
+        });
+        self.cohorts.get(peer).cloned().unwrap_or_else(|| Cohort::alone(peer))
+    }
+
+    fn refresh(&mut self, threshold: f64) {
+        // Peers silent for a whole window have left the fleet.
+        let oldest = self.round.saturating_sub(COHORT_WINDOW as u64);
+        self.series.retain(|_, s| s.back().is_some_and(|last| last.round >= oldest));
+        let mut peers: Vec<&String> = self.series.keys().collect();
+        peers.sort();
+
+        // Union-find over the correlation graph, remembering each peer's
+        // strongest link for its risk score.
+        let mut parent: Vec<usize> = (0..peers.len()).collect();
+        let mut strongest = vec![0.0f64; peers.len()];
+        for a in 0..peers.len() {
+            for b in a + 1..peers.len() {
+                let Some(r) = correlation(&self.series[peers[a]], &self.series[peers[b]]) else {
+                    continue;
+                };
+                if r < threshold {
+                    continue;
+                }
+                strongest[a] = strongest[a].max(r);
+                strongest[b] = strongest[b].max(r);
+                let (root_a, root_b) = (find(&mut parent, a), find(&mut parent, b));
+                // Peers are sorted, so the smaller index is the cohort's name.
+                parent[root_a.max(root_b)] = root_a.min(root_b);
+            }
+        }
+
+        let roots: Vec<usize> = (0..peers.len()).map(|i| find(&mut parent, i)).collect();
+        let mut sizes: HashMap<usize, usize> = HashMap::new();
+        for root in &roots {
+            *sizes.entry(*root).or_default() += 1;
+        }
+        self.cohorts = peers
+            .iter()
+            .enumerate()
+            .map(|(i, peer)| {
+                let size = sizes[&roots[i]];
+                let breadth = (1.0 - 1.0 / size as f64).sqrt();
+                let cohort = Cohort {
+                    id: peers[roots[i]].clone(),
+                    size,
+                    collusion_risk: (strongest[i] * breadth).clamp(0.0, 1.0),
+                };
+                ((*peer).clone(), cohort)
+            })
+            .collect();
+    }
+}
+
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+/// Mean of the failure and latency correlations over the rounds both peers
+/// reported in; `None` without enough overlap or variance.
+fn correlation(a: &VecDeque<Sample>, b: &VecDeque<Sample>) -> Option<f64> {
+    let by_round: HashMap<u64, &Sample> = b.iter().map(|s| (s.round, s)).collect();
+    let pairs: Vec<(&Sample, &Sample)> = a
+        .iter()
+        .filter_map(|x| by_round.get(&x.round).map(|y| (x, *y)))
+        .collect();
+    if pairs.len() < MIN_OVERLAP {
+        return None;
+    }
+
+    let failure = pearson(pairs.iter().map(|(x, y)| (x.failure, y.failure)));
+    // Unknown latency is reported as zero; only compare where both know it.
+    let latency = pearson(
+        pairs
+            .iter()
+            .filter(|(x, y)| x.latency_ms > 0.0 && y.latency_ms > 0.0)
+            .map(|(x, y)| (x.latency_ms, y.latency_ms)),
+    );
+    match (failure, latency) {
+        (Some(f), Some(l)) => Some((f + l) / 2.0),
+        (Some(r), None) | (None, Some(r)) => Some(r),
+        (None, None) => None,
+    }
+}
+
+fn pearson(pairs: impl Iterator<Item = (f64, f64)>) -> Option<f64> {
+    let pairs: Vec<(f64, f64)> = pairs.collect();
+    if pairs.len() < MIN_OVERLAP {
+        return None;
+    }
+    let n = pairs.len() as f64;
+    let mean_x = pairs.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = pairs.iter().map(|p| p.1).sum::<f64>() / n;
+    let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (x, y) in &pairs {
+        cov += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x) * (x - mean_x);
+        var_y += (y - mean_y) * (y - mean_y);
+    }
+    if var_x / n < MIN_VARIANCE || var_y / n < MIN_VARIANCE {
+        return None;
+    }
+    Some(cov / (var_x * var_y).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROUNDS: usize = 12;
+
+    /// `a` and `b` fail together; `c` follows a pattern orthogonal to both.
+    fn failures(round: usize) -> [(&'static str, f64); 3] {
+        let a = round.is_multiple_of(2) as u8 as f64;
+        let c = (round % 4 < 2) as u8 as f64;
+        [("a", a), ("b", 0.8 * a + 0.2 * c), ("c", c)]
+    }
+
+    fn feed(tracker: &mut CohortTracker, threshold: f64) {
+        for round in 0..ROUNDS {
+            for (peer, failure) in failures(round) {
+                tracker.observe(peer, failure, 0.0, threshold);
+            }
+        }
+    }
+
+    /// The cohorts computed when the next round starts.
+    fn next_round(tracker: &mut CohortTracker, threshold: f64) -> HashMap<&'static str, Cohort> {
+        failures(ROUNDS).map(|(peer, failure)| (peer, tracker.observe(peer, failure, 0.0, threshold))).into()
+    }
+
+    #[test]
+    fn correlated_peers_share_a_cohort_at_the_threshold() {
+        let mut probe = CohortTracker::default();
+        feed(&mut probe, 1.0);
+        let r = correlation(&probe.series["a"], &probe.series["b"]).expect("enough overlap");
+        assert!(r > 0.9, "{}", r);
+        let unrelated = correlation(&probe.series["a"], &probe.series["c"]).expect("enough overlap");
+        assert!(unrelated.abs() < 1e-9, "{}", unrelated);
+
+        let mut tracker = CohortTracker::default();
+        feed(&mut tracker, r);
+        let cohorts = next_round(&mut tracker, r);
+        assert_eq!(cohorts["a"].id, "a");
+        assert_eq!(cohorts["b"].id, "a");
+        assert_eq!(cohorts["b"].size, 2);
+        assert!(cohorts["b"].collusion_risk > 0.0);
+        assert_eq!(cohorts["c"], Cohort::alone("c"));
+    }
+
+    #[test]
+    fn peers_just_below_the_threshold_stay_apart() {
+        let mut probe = CohortTracker::default();
+        feed(&mut probe, 1.0);
+        let threshold = correlation(&probe.series["a"], &probe.series["b"]).expect("enough overlap") + 1e-9;
+
+        let mut tracker = CohortTracker::default();
+        feed(&mut tracker, threshold);
+        let cohorts = next_round(&mut tracker, threshold);
+        for peer in ["a", "b", "c"] {
+            assert_eq!(cohorts[peer], Cohort::alone(peer));
+        }
+    }
+}
@@ -0,0 +1,216 @@
+// ── gRPC Policy Service ─────────────────────────────────────────
+//
+// `--grpc-listen` serves the engine as `neurostore.sentinel.v1.Sentinel`
+// (see crates/protocol/proto/sentinel.proto). The gateway keeps one
+// StreamMetrics call open and gets a policy back for every sample it sends;
+// GetPeerPolicy answers from the latest policy computed for a peer, with its
+// reputation decayed for the time since. Peer models live for the life of
+// the process, shared by every stream.
+
+use crate::{
+    decay_toward_prior, default_bandwidth, evaluate, now_ms, Args, CohortTracker, NodeMetrics, PeerModel,
+    PolicyOutput,
+};
+use neuro_protocol::sentinel as wire;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::body::BoxBody;
+use tonic::codec::ProstCodec;
+use tonic::codegen::{http, BoxFuture, Context, Poll, Service};
+use tonic::server::{Grpc, NamedService, StreamingService, UnaryService};
+use tonic::{Request, Response, Status, Streaming};
+
+/// Policies buffered per stream before a slow client stalls its own reads.
+const POLICY_BUFFER: usize = 64;
+
+struct Engine {
+    args: Args,
+    models: Mutex<HashMap<String, PeerModel>>,
+    cohorts: Mutex<CohortTracker>,
+    /// Latest policy per peer and when its sample was taken.
+    latest: Mutex<HashMap<String, (wire::PeerPolicy, u64)>>,
+}
+
+impl Engine {
+    /// Scores one sample; `None` when it names no peer.
+    fn observe(&self, sample: wire::NodeMetrics) -> Option<wire::PeerPolicy> {
+        if sample.peer.trim().is_empty() {
+            return None;
+        }
+        let metrics = NodeMetrics {
+            peer: sample.peer,
+            latency_ms: sample.latency_ms,
+            uptime_pct: sample.uptime_pct,
+            verify_success_pct: sample.verify_success_pct,
+            // proto3 cannot tell "unset" from zero; treat both as unknown,
+            // as the JSON input does for a missing field.
+            bandwidth_mbps: if sample.bandwidth_mbps > 0.0 { sample.bandwidth_mbps } else { default_bandwidth() },
+            object_heat_index: sample.object_heat_index,
+            regional_qos_penalty: sample.regional_qos_penalty,
+            timestamp_ms: (sample.timestamp_ms > 0).then_some(sample.timestamp_ms),
+        };
+        let observed_ms = metrics.timestamp_ms.unwrap_or_else(now_ms);
+
+        let output = {
+            let mut models = self.models.lock().unwrap_or_else(PoisonError::into_inner);
+            let mut cohorts = self.cohorts.lock().unwrap_or_else(PoisonError::into_inner);
+            evaluate(models.entry(metrics.peer.clone()).or_default(), &mut cohorts, &metrics, &self.args)
+        };
+        let policy = to_wire(output);
+        self.latest
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(policy.peer.clone(), (policy.clone(), observed_ms));
+        Some(policy)
+    }
+
+    fn latest(&self, peer: &str) -> Option<wire::PeerPolicy> {
+        let (mut policy, observed_ms) =
+            self.latest.lock().unwrap_or_else(PoisonError::into_inner).get(peer).cloned()?;
+        let idle_secs = now_ms().saturating_sub(observed_ms) as f64 / 1000.0;
+        let reputation = decay_toward_prior(policy.reputation, idle_secs, self.args.decay_half_life_secs);
+        policy.reputation = (reputation * 100.0).round() / 100.0;
+        Some(policy)
+    }
+}
+
+fn to_wire(output: PolicyOutput) -> wire::PeerPolicy {
+    wire::PeerPolicy {
+        peer: output.peer,
+        score: output.score,
+        reputation: output.reputation,
+        anomaly_level: output.anomaly_level,
+        anomaly_score: output.anomaly_score,
+        trend: output.trend,
+        trend_velocity: output.trend_velocity,
+        action: output.action,
+        churn_probability: output.churn_probability,
+        price_per_gb: output.price_per_gb,
+        confidence: output.confidence,
+        observations: output.observations,
+        slo_violations: output.slo_violations.violations_count,
+        recommended_redundancy_multiplier: output.recommended_redundancy_multiplier,
+        cohort_id: output.cohort_id,
+        cohort_size: output.cohort_size,
+        collusion_risk: output.collusion_risk,
+        clean_windows: output.clean_windows,
+        windows_to_recovery: output.windows_to_recovery,
+        explanations: output
+            .explanations
+            .into_iter()
+            .map(|e| wire::Explanation {
+                kind: e.kind,
+                signal: e.signal,
+                value: e.value,
+                baseline: e.baseline,
+                deviation: e.deviation,
+                detail: e.detail,
+            })
+            .collect(),
+    }
+}
+
+struct StreamMetrics(Arc<Engine>);
+
+impl StreamingService<wire::NodeMetrics> for StreamMetrics {
+    type Response = wire::PeerPolicy;
+    type ResponseStream = ReceiverStream<Result<wire::PeerPolicy, Status>>;
+    type Future = BoxFuture<Response<Self::ResponseStream>, Status>;
+
+    fn call(&mut self, request: Request<Streaming<wire::NodeMetrics>>) -> Self::Future {
+        let engine = Arc::clone(&self.0);
+        Box::pin(async move {
+            let mut samples = request.into_inner();
+            let (tx, rx) = mpsc::channel(POLICY_BUFFER);
+            tokio::spawn(async move {
+                loop {
+                    let reply = match samples.message().await {
+                        Ok(Some(sample)) => engine
+                            .observe(sample)
+                            .ok_or_else(|| Status::invalid_argument("metrics sample without a peer")),
+                        Ok(None) => break,
+                        Err(status) => Err(status),
+                    };
+                    // An error ends the stream; the client reconnects.
+                    let failed = reply.is_err();
+                    if tx.send(reply).await.is_err() || failed {
+                        break;
+                    }
+                }
+            });
+            Ok(Response::new(ReceiverStream::new(rx)))
+        })
+    }
+}
+
+struct GetPeerPolicy(Arc<Engine>);
+
+impl UnaryService<wire::PeerPolicyRequest> for GetPeerPolicy {
+    type Response = wire::PeerPolicy;
+    type Future = BoxFuture<Response<wire::PeerPolicy>, Status>;
+
+    fn call(&mut self, request: Request<wire::PeerPolicyRequest>) -> Self::Future {
+        let policy = self.0.latest(&request.get_ref().peer);
+        Box::pin(async move {
+            policy
+                .map(Response::new)
+                .ok_or_else(|| Status::not_found("no telemetry received for this peer"))
+        })
+    }
+}
+
+#[derive(Clone)]
+struct SentinelServer {
+    engine: Arc<Engine>,
+}
+
+impl NamedService for SentinelServer {
+    const NAME: &'static str = wire::SERVICE_NAME;
+}
+
+impl Service<http::Request<BoxBody>> for SentinelServer {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<BoxBody>) -> Self::Future {
+        let engine = Arc::clone(&self.engine);
+        match req.uri().path() {
+            wire::STREAM_METRICS_PATH => Box::pin(async move {
+                let mut grpc = Grpc::new(ProstCodec::default());
+                Ok(grpc.streaming(StreamMetrics(engine), req).await)
+            }),
+            wire::GET_PEER_POLICY_PATH => Box::pin(async move {
+                let mut grpc = Grpc::new(ProstCodec::default());
+                Ok(grpc.unary(GetPeerPolicy(engine), req).await)
+            }),
+            _ => Box::pin(async move { Ok(Status::unimplemented("unknown sentinel method").into_http()) }),
+        }
+    }
+}
+
+pub fn serve(addr: SocketAddr, args: Args) -> anyhow::Result<()> {
+    let engine = Arc::new(Engine {
+        args,
+        models: Mutex::new(HashMap::new()),
+        cohorts: Mutex::new(CohortTracker::default()),
+        latest: Mutex::new(HashMap::new()),
+    });
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async move {
+        eprintln!("neuro-sentinel: serving policy stream on {}", addr);
+        tonic::transport::Server::builder()
+            .add_service(SentinelServer { engine })
+            .serve(addr)
+            .await
+    })?;
+    Ok(())
+}
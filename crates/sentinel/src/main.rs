@@ -11,11 +11,21 @@
 // - SLO-aware scoring with configurable thresholds
 // - Bandwidth saturation awareness
 // - RL-Guided Dynamic Redundancy (Object Heat & Regional QoS)
+// - Cohort detection: peers that fail together share a cohort_id
+// - Standing state machine: probation terms, stepwise recovery, idle decay
+// - Explanations: the z-scores, SLO breaches and trend behind each verdict
 
 use clap::{Parser, ValueEnum};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::io::{self, BufRead};
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+mod cohort;
+mod grpc;
+
+use cohort::CohortTracker;
 
 // ── CLI ──────────────────────────────────────────────────────────
 
@@ -58,9 +68,33 @@ struct Args {
     #[arg(long, default_value_t = 10)]
     min_observations: u64,
 
+    /// Correlation of failure/latency history that puts two peers in one cohort
+    #[arg(long, default_value_t = 0.8)]
+    cohort_threshold: f64,
+
+    /// Minimum windows a peer spends on probation before it can be released
+    #[arg(long, default_value_t = 6)]
+    probation_windows: u32,
+
+    /// Consecutive clean windows needed to step down one level (quarantine → probation → good)
+    #[arg(long, default_value_t = 3)]
+    recovery_windows: u32,
+
+    /// Half-life of the pull toward a neutral reputation while a peer is silent
+    #[arg(long, default_value_t = 6.0 * 3600.0)]
+    decay_half_life_secs: f64,
+
+    /// How much of the reasoning behind each policy to include in `explanations`
+    #[arg(long, value_enum, default_value_t = Explain::Brief)]
+    explain: Explain,
+
     /// Output format
     #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
     output: OutputFormat,
+
+    /// Serve the gRPC policy stream on this address instead of reading stdin
+    #[arg(long)]
+    grpc_listen: Option<SocketAddr>,
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
@@ -71,6 +105,16 @@ enum Mode {
     Adaptive,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Explain {
+    /// No explanations
+    Off,
+    /// SLO breaches, a degrading trend, and the top z-scores behind an anomaly
+    Brief,
+    /// Every signal that deviates from its baseline, on every window
+    Full,
+}
+
 #[derive(Debug, Clone, Copy, ValueEnum)]
 enum OutputFormat {
     Json,
@@ -93,6 +137,9 @@ struct NodeMetrics {
     // RL Feature: Geolocation QoS penalty
     #[serde(default)]
     pub regional_qos_penalty: f64,
+    // When the sample was taken; arrival time if absent
+    #[serde(default)]
+    pub timestamp_ms: Option<u64>,
 }
 
 fn default_bandwidth() -> f64 {
@@ -117,6 +164,26 @@ struct PolicyOutput {
     factors: ScoreFactors,
     // RL Extensions
     recommended_redundancy_multiplier: f64, // Factor to scale RS chunks (e.g., 1.5x for hot objects)
+    // Correlated-failure cohort; placement should spread an object across cohorts
+    cohort_id: String,
+    cohort_size: u32,
+    collusion_risk: f64,        // 0.0 - 1.0
+    // Recovery progress while on probation or quarantine
+    clean_windows: u32,         // consecutive clean windows so far
+    windows_to_recovery: u32,   // windows until the next step down (0 when in good standing)
+    // Why: most significant reason first (see --explain)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    explanations: Vec<Explanation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Explanation {
+    kind: String,               // zscore | slo | trend
+    signal: String,             // latency_ms | uptime_pct | verify_success_pct | bandwidth_mbps | regional_qos_penalty | score
+    value: f64,                 // observed value (trend: velocity per window)
+    baseline: f64,              // running mean, SLO target, or trend threshold
+    deviation: f64,             // z-score (positive = worse), fraction past the SLO, or velocity / threshold
+    detail: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -236,6 +303,120 @@ struct PeerModel {
     
     // Predictive AI: Churn Signatures
     latency_jitter: RunningStat,
+
+    standing: StandingTracker,
+    last_seen_ms: Option<u64>,
+}
+
+// ── Standing: Probation & Recovery ──────────────────────────────
+//
+// Each window's verdict from decide_action escalates a peer at once, but
+// it only climbs back one level at a time: quarantine → probation → good,
+// each step after `recovery_windows` consecutive clean windows (verdict
+// hold or promote), and probation additionally lasts at least
+// `probation_windows`. Eviction is final for the life of the model.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+enum Standing {
+    #[default]
+    Good,
+    Probation,
+    Quarantine,
+    Evicted,
+}
+
+fn severity(action: &str) -> Standing {
+    match action {
+        "probation" => Standing::Probation,
+        "quarantine" | "proactive_evict" => Standing::Quarantine,
+        "evict" => Standing::Evicted,
+        _ => Standing::Good,
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct StandingTracker {
+    level: Standing,
+    label: &'static str,    // action that put the peer at `level`
+    windows_in_level: u32,
+    clean_streak: u32,
+}
+
+impl StandingTracker {
+    /// Folds one window's verdict into the standing; returns the action to publish.
+    fn apply(&mut self, verdict: &'static str, probation_windows: u32, recovery_windows: u32) -> &'static str {
+        if self.level == Standing::Evicted {
+            return self.label;
+        }
+        let level = severity(verdict);
+        if level != Standing::Good && level >= self.level {
+            if level > self.level {
+                self.level = level;
+                self.windows_in_level = 0;
+            }
+            self.label = verdict;
+            self.windows_in_level += 1;
+            self.clean_streak = 0;
+            return verdict;
+        }
+        if self.level == Standing::Good {
+            return verdict;
+        }
+
+        self.windows_in_level += 1;
+        if level == Standing::Good {
+            self.clean_streak += 1;
+        } else {
+            self.clean_streak = 0;
+        }
+        let term_served = self.level != Standing::Probation || self.windows_in_level >= probation_windows;
+        if self.clean_streak >= recovery_windows && term_served {
+            self.windows_in_level = 0;
+            self.clean_streak = 0;
+            if self.level == Standing::Quarantine {
+                self.level = Standing::Probation;
+                self.label = "probation";
+            } else {
+                self.level = Standing::Good;
+                return verdict;
+            }
+        }
+        self.label
+    }
+
+    fn windows_to_recovery(&self, probation_windows: u32, recovery_windows: u32) -> u32 {
+        match self.level {
+            Standing::Good | Standing::Evicted => 0,
+            Standing::Quarantine => recovery_windows.saturating_sub(self.clean_streak),
+            Standing::Probation => recovery_windows
+                .saturating_sub(self.clean_streak)
+                .max(probation_windows.saturating_sub(self.windows_in_level)),
+        }
+    }
+}
+
+// ── Idle Decay ──────────────────────────────────────────────────
+
+/// Reputation a peer drifts back to when nothing is heard from it.
+const NEUTRAL_REPUTATION: f64 = 50.0;
+/// Silence shorter than this (a few missed telemetry sweeps) is not decayed.
+const DECAY_GRACE_SECS: f64 = 300.0;
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default()
+}
+
+/// Pulls `reputation` toward the neutral prior by half the remaining
+/// distance every `half_life_secs` of silence beyond the grace period.
+/// Only peers that have been scored are decayed, so a reputation of zero is
+/// a real score and recovers like any other.
+fn decay_toward_prior(reputation: f64, idle_secs: f64, half_life_secs: f64) -> f64 {
+    let idle_secs = idle_secs - DECAY_GRACE_SECS;
+    if idle_secs <= 0.0 || half_life_secs <= 0.0 {
+        return reputation;
+    }
+    let keep = 0.5f64.powf(idle_secs / half_life_secs);
+    NEUTRAL_REPUTATION + (reputation - NEUTRAL_REPUTATION) * keep
 }
 
 // ── Non-Linear Scoring Functions ────────────────────────────────
@@ -313,8 +494,9 @@ fn compute_dynamic_price(reputation: f64, action: &str) -> f64 {
     // User Charge: ₹1.00 / GB / Month
     // Max COGS budget: ₹0.50 (for 2.0x redundancy)
     // Target Physical Payout: ₹0.25 - ₹0.30 per GB
-    
-    let base_physical_payout = 0.25; 
+    // (constants shared with the gateway's estimator in neuro_protocol::sentinel)
+
+    let base_physical_payout = neuro_protocol::sentinel::BASE_PHYSICAL_PAYOUT_PER_GB_MONTH;
     
     let multiplier = match action {
         "promote" => 1.2,         // Best nodes earn ₹0.30
@@ -346,27 +528,45 @@ fn compute_composite_score(factors: &ScoreFactors) -> f64 {
 
 // ── Multi-Dimensional Anomaly Detection ─────────────────────────
 
-fn compute_anomaly_score(model: &PeerModel, metrics: &NodeMetrics) -> f64 {
-    let z_lat = model.latency_stat.zscore(metrics.latency_ms);
-    let z_up = model.uptime_stat.zscore(metrics.uptime_pct);
-    let z_ver = model.verify_stat.zscore(metrics.verify_success_pct);
-    let z_bw = model.bandwidth_stat.zscore(metrics.bandwidth_mbps);
-    let z_qos = model.qos_stat.zscore(metrics.regional_qos_penalty);
+/// Each signal's deviation from its running mean: (signal, value, mean, z),
+/// with z oriented so that positive is worse.
+fn signal_deviations(model: &PeerModel, metrics: &NodeMetrics) -> [(&'static str, f64, f64, f64); 5] {
+    [
+        // high latency is bad
+        ("latency_ms", metrics.latency_ms, model.latency_stat.mean, model.latency_stat.zscore(metrics.latency_ms)),
+        // low uptime is bad
+        ("uptime_pct", metrics.uptime_pct, model.uptime_stat.mean, -model.uptime_stat.zscore(metrics.uptime_pct)),
+        // low verify is bad
+        (
+            "verify_success_pct",
+            metrics.verify_success_pct,
+            model.verify_stat.mean,
+            -model.verify_stat.zscore(metrics.verify_success_pct),
+        ),
+        // low bandwidth is bad
+        (
+            "bandwidth_mbps",
+            metrics.bandwidth_mbps,
+            model.bandwidth_stat.mean,
+            -model.bandwidth_stat.zscore(metrics.bandwidth_mbps),
+        ),
+        // high QoS routing penalty is bad
+        (
+            "regional_qos_penalty",
+            metrics.regional_qos_penalty,
+            model.qos_stat.mean,
+            model.qos_stat.zscore(metrics.regional_qos_penalty),
+        ),
+    ]
+}
 
+fn compute_anomaly_score(deviations: &[(&'static str, f64, f64, f64)]) -> f64 {
     // Composite magnitude — high value = multi-dimensional outlier
-    // Only penalize negative deviations for uptime/verify/bandwidth
-    // and positive deviations for latency (higher latency = bad)
-    let lat_penalty = z_lat.max(0.0);          // high latency is bad
-    let up_penalty = (-z_up).max(0.0);         // low uptime is bad
-    let ver_penalty = (-z_ver).max(0.0);       // low verify is bad
-    let bw_penalty = (-z_bw).max(0.0);         // low bandwidth is bad
-    let qos_penalty = z_qos.max(0.0);          // high QoS routing penalty is bad
-
-    (lat_penalty * lat_penalty
-        + up_penalty * up_penalty
-        + ver_penalty * ver_penalty
-        + bw_penalty * bw_penalty
-        + qos_penalty * qos_penalty)
+    // Only deviations in the bad direction count
+    deviations
+        .iter()
+        .map(|&(_, _, _, z)| z.max(0.0) * z.max(0.0))
+        .sum::<f64>()
         .sqrt()
 }
 
@@ -380,6 +580,95 @@ fn anomaly_level(score: f64, threshold: f64) -> &'static str {
     }
 }
 
+// ── Explanations ────────────────────────────────────────────────
+
+/// Z-scores listed by --explain brief.
+const BRIEF_ZSCORES: usize = 3;
+/// Deviations smaller than this are noise, not a reason.
+const MIN_EXPLAINED_Z: f64 = 1.0;
+
+fn round3(x: f64) -> f64 {
+    (x * 1000.0).round() / 1000.0
+}
+
+/// SLO breaches in this sample, worst first.
+fn explain_slo(metrics: &NodeMetrics, args: &Args) -> Vec<Explanation> {
+    let checks = [
+        ("latency_ms", metrics.latency_ms, args.slo_latency_ms, metrics.latency_ms > args.slo_latency_ms, "above"),
+        ("uptime_pct", metrics.uptime_pct, args.slo_uptime_pct, metrics.uptime_pct < args.slo_uptime_pct, "below"),
+        (
+            "bandwidth_mbps",
+            metrics.bandwidth_mbps,
+            args.slo_bandwidth_mbps,
+            metrics.bandwidth_mbps < args.slo_bandwidth_mbps,
+            "below",
+        ),
+    ];
+    let mut out: Vec<Explanation> = checks
+        .into_iter()
+        .filter(|&(_, _, _, breached, _)| breached)
+        .map(|(signal, value, target, _, side)| Explanation {
+            kind: "slo".to_string(),
+            signal: signal.to_string(),
+            value: round3(value),
+            baseline: target,
+            deviation: round3(if target != 0.0 { (value - target).abs() / target } else { 0.0 }),
+            detail: format!("{signal} {value:.2} is {side} the SLO of {target:.2}"),
+        })
+        .collect();
+    out.sort_by(|a, b| b.deviation.total_cmp(&a.deviation));
+    out
+}
+
+/// The reasons behind an adaptive verdict, most significant first: the
+/// z-scores that drove the anomaly score, SLO breaches, then the trend.
+fn explain_adaptive(
+    level: Explain,
+    deviations: &[(&'static str, f64, f64, f64)],
+    anomaly_lvl: &str,
+    metrics: &NodeMetrics,
+    trend: &TrendTracker,
+    trend_label: &str,
+    args: &Args,
+) -> Vec<Explanation> {
+    if level == Explain::Off {
+        return Vec::new();
+    }
+    let mut zscores: Vec<_> = deviations.iter().filter(|d| d.3 >= MIN_EXPLAINED_Z).collect();
+    zscores.sort_by(|a, b| b.3.total_cmp(&a.3));
+    if level == Explain::Brief {
+        zscores.truncate(if anomaly_lvl == "none" { 0 } else { BRIEF_ZSCORES });
+    }
+    let mut out: Vec<Explanation> = zscores
+        .into_iter()
+        .map(|&(signal, value, mean, z)| Explanation {
+            kind: "zscore".to_string(),
+            signal: signal.to_string(),
+            value: round3(value),
+            baseline: round3(mean),
+            deviation: round3(z),
+            detail: format!("{signal} {value:.2} is {z:.1} standard deviations worse than its running mean of {mean:.2}"),
+        })
+        .collect();
+    out.extend(explain_slo(metrics, args));
+    if trend_label == "degrading" || (level == Explain::Full && trend_label != "stable") {
+        let threshold = args.trend_threshold;
+        out.push(Explanation {
+            kind: "trend".to_string(),
+            signal: "score".to_string(),
+            value: round3(trend.velocity),
+            baseline: threshold,
+            deviation: round3(if threshold > 0.0 { trend.velocity / threshold } else { 0.0 }),
+            detail: format!(
+                "score is {trend_label} by {:.3} per window (threshold {threshold}, acceleration {:.3})",
+                trend.velocity.abs(),
+                trend.acceleration
+            ),
+        });
+    }
+    out
+}
+
 // ── RL-Guided Redundancy Multiplier ─────────────────────────────
 fn compute_rl_redundancy(heat_accumulator: f64, reputation: f64, action: &str) -> f64 {
     // Core RL Logic: Reward nodes that frequently serve high-heat data by 
@@ -459,8 +748,13 @@ fn decide_action(
 
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
+    if let Some(addr) = args.grpc_listen {
+        return grpc::serve(addr, args);
+    }
+
     let stdin = io::stdin();
     let mut models: HashMap<String, PeerModel> = HashMap::new();
+    let mut cohorts = CohortTracker::default();
 
     for line in stdin.lock().lines() {
         let line = line?;
@@ -470,11 +764,7 @@ fn main() -> anyhow::Result<()> {
 
         let metrics: NodeMetrics = serde_json::from_str(&line)?;
         let model = models.entry(metrics.peer.clone()).or_default();
-
-        let output = match args.mode {
-            Mode::Static => process_static(&metrics, &args),
-            Mode::Adaptive => process_adaptive(model, &metrics, &args),
-        };
+        let output = evaluate(model, &mut cohorts, &metrics, &args);
 
         let json = match args.output {
             OutputFormat::Json => serde_json::to_string(&output)?,
@@ -486,6 +776,13 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+fn evaluate(model: &mut PeerModel, cohorts: &mut CohortTracker, metrics: &NodeMetrics, args: &Args) -> PolicyOutput {
+    match args.mode {
+        Mode::Static => process_static(metrics, args),
+        Mode::Adaptive => process_adaptive(model, cohorts, metrics, args),
+    }
+}
+
 fn process_static(metrics: &NodeMetrics, args: &Args) -> PolicyOutput {
     let factors = ScoreFactors {
         latency_score: score_latency(metrics.latency_ms, args.slo_latency_ms),
@@ -503,6 +800,11 @@ fn process_static(metrics: &NodeMetrics, args: &Args) -> PolicyOutput {
         violations_count: 0,
     };
 
+    let explanations = match args.explain {
+        Explain::Off => Vec::new(),
+        Explain::Brief | Explain::Full => explain_slo(metrics, args),
+    };
+
     PolicyOutput {
         peer: metrics.peer.clone(),
         score,
@@ -519,12 +821,26 @@ fn process_static(metrics: &NodeMetrics, args: &Args) -> PolicyOutput {
         slo_violations: slo,
         factors,
         recommended_redundancy_multiplier: 1.0,
+        cohort_id: metrics.peer.clone(),
+        cohort_size: 1,
+        collusion_risk: 0.0,
+        clean_windows: 0,
+        windows_to_recovery: 0,
+        explanations,
     }
 }
 
-fn process_adaptive(model: &mut PeerModel, metrics: &NodeMetrics, args: &Args) -> PolicyOutput {
+fn process_adaptive(model: &mut PeerModel, cohorts: &mut CohortTracker, metrics: &NodeMetrics, args: &Args) -> PolicyOutput {
     let alpha = args.alpha.clamp(0.01, 0.5);
 
+    // 0. Idle decay since the previous sample
+    let observed_ms = metrics.timestamp_ms.unwrap_or_else(now_ms);
+    if let Some(last) = model.last_seen_ms {
+        let idle_secs = observed_ms.saturating_sub(last) as f64 / 1000.0;
+        model.reputation = decay_toward_prior(model.reputation, idle_secs, args.decay_half_life_secs);
+    }
+    model.last_seen_ms = Some(observed_ms.max(model.last_seen_ms.unwrap_or_default()));
+
     // 1. Compute non-linear factor scores
     let factors = ScoreFactors {
         latency_score: score_latency(metrics.latency_ms, args.slo_latency_ms),
@@ -536,7 +852,8 @@ fn process_adaptive(model: &mut PeerModel, metrics: &NodeMetrics, args: &Args) -
     let score = compute_composite_score(&factors);
 
     // 2. Multi-dimensional anomaly detection (BEFORE updating stats)
-    let anomaly_magnitude = compute_anomaly_score(model, metrics);
+    let deviations = signal_deviations(model, metrics);
+    let anomaly_magnitude = compute_anomaly_score(&deviations);
     let anomaly_lvl = anomaly_level(anomaly_magnitude, args.anomaly_threshold);
 
     // 3. Update running statistics
@@ -601,8 +918,13 @@ fn process_adaptive(model: &mut PeerModel, metrics: &NodeMetrics, args: &Args) -
 
     let churn_prob = compute_churn_probability(model, metrics);
 
-    // 8. 5-tier action decision
-    let action = decide_action(
+    // 8. Cohort membership from failure (missed proofs + downtime) and latency history
+    let failure = (100.0 - metrics.verify_success_pct.clamp(0.0, 100.0))
+        + (100.0 - metrics.uptime_pct.clamp(0.0, 100.0));
+    let cohort = cohorts.observe(&metrics.peer, failure, metrics.latency_ms, args.cohort_threshold);
+
+    // 9. 5-tier verdict for this window, then the standing it leads to
+    let verdict = decide_action(
         model.reputation,
         anomaly_lvl,
         trend_label,
@@ -611,6 +933,7 @@ fn process_adaptive(model: &mut PeerModel, metrics: &NodeMetrics, args: &Args) -
         model.slo_violation_count,
         churn_prob,
     );
+    let action = model.standing.apply(verdict, args.probation_windows, args.recovery_windows);
 
     let slo = SloStatus {
         latency_ok: lat_ok,
@@ -618,6 +941,8 @@ fn process_adaptive(model: &mut PeerModel, metrics: &NodeMetrics, args: &Args) -
         bandwidth_ok: bw_ok,
         violations_count: model.slo_violation_count,
     };
+    let explanations =
+        explain_adaptive(args.explain, &deviations, anomaly_lvl, metrics, &model.trend, trend_label, args);
 
     PolicyOutput {
         peer: metrics.peer.clone(),
@@ -635,5 +960,99 @@ fn process_adaptive(model: &mut PeerModel, metrics: &NodeMetrics, args: &Args) -
         slo_violations: slo,
         factors,
         recommended_redundancy_multiplier: compute_rl_redundancy(model.heat_accumulator, model.reputation, action),
+        cohort_id: cohort.id,
+        cohort_size: cohort.size as u32,
+        collusion_risk: (cohort.collusion_risk * 1000.0).round() / 1000.0,
+        clean_windows: model.standing.clean_streak,
+        windows_to_recovery: model.standing.windows_to_recovery(args.probation_windows, args.recovery_windows),
+        explanations,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROBATION_WINDOWS: u32 = 3;
+    const RECOVERY_WINDOWS: u32 = 2;
+
+    fn run(tracker: &mut StandingTracker, verdicts: &[&'static str]) -> Vec<&'static str> {
+        verdicts.iter().map(|v| tracker.apply(v, PROBATION_WINDOWS, RECOVERY_WINDOWS)).collect()
+    }
+
+    #[test]
+    fn verdicts_escalate_at_once() {
+        let mut tracker = StandingTracker::default();
+        assert_eq!(run(&mut tracker, &["hold", "probation", "quarantine"]), ["hold", "probation", "quarantine"]);
+        assert_eq!(tracker.level, Standing::Quarantine);
+        // A milder verdict does not lower the standing.
+        assert_eq!(run(&mut tracker, &["probation"]), ["quarantine"]);
+        assert_eq!(tracker.level, Standing::Quarantine);
+    }
+
+    #[test]
+    fn quarantine_recovers_one_level_at_a_time() {
+        let mut tracker = StandingTracker::default();
+        run(&mut tracker, &["quarantine"]);
+        assert_eq!(run(&mut tracker, &["hold", "hold"]), ["quarantine", "probation"]);
+        assert_eq!(tracker.level, Standing::Probation);
+        assert_eq!(run(&mut tracker, &["hold", "hold", "promote"]), ["probation", "probation", "promote"]);
+        assert_eq!(tracker.level, Standing::Good);
+    }
+
+    #[test]
+    fn probation_lasts_its_minimum_term() {
+        let mut tracker = StandingTracker::default();
+        run(&mut tracker, &["probation"]);
+        // Clean enough to recover after one window, but the term is three.
+        assert_eq!(run(&mut tracker, &["hold"]), ["probation"]);
+        assert_eq!(tracker.windows_to_recovery(PROBATION_WINDOWS, RECOVERY_WINDOWS), 1);
+        assert_eq!(run(&mut tracker, &["hold"]), ["hold"]);
+        assert_eq!(tracker.level, Standing::Good);
+
+        // The window that set probation counts toward a five-window term.
+        let mut strict = StandingTracker::default();
+        strict.apply("probation", 5, 1);
+        let verdicts: Vec<_> = (0..3).map(|_| strict.apply("promote", 5, 1)).collect();
+        assert_eq!(verdicts, ["probation"; 3]);
+        assert_eq!(strict.apply("promote", 5, 1), "promote");
+    }
+
+    #[test]
+    fn dirty_window_resets_the_clean_streak() {
+        let mut tracker = StandingTracker::default();
+        run(&mut tracker, &["quarantine", "hold"]);
+        assert_eq!(tracker.clean_streak, 1);
+        assert_eq!(run(&mut tracker, &["probation"]), ["quarantine"]);
+        assert_eq!(tracker.clean_streak, 0);
+        assert_eq!(run(&mut tracker, &["hold", "hold"]), ["quarantine", "probation"]);
+    }
+
+    #[test]
+    fn eviction_is_final() {
+        let mut tracker = StandingTracker::default();
+        run(&mut tracker, &["evict"]);
+        assert_eq!(run(&mut tracker, &["promote"; 10]), ["evict"; 10]);
+        assert_eq!(tracker.level, Standing::Evicted);
+        assert_eq!(tracker.windows_to_recovery(PROBATION_WINDOWS, RECOVERY_WINDOWS), 0);
+    }
+
+    #[test]
+    fn decay_waits_out_the_grace_period() {
+        assert_eq!(decay_toward_prior(90.0, DECAY_GRACE_SECS, 3600.0), 90.0);
+    }
+
+    #[test]
+    fn decay_halves_the_distance_to_the_prior_per_half_life() {
+        let high = decay_toward_prior(90.0, DECAY_GRACE_SECS + 3600.0, 3600.0);
+        assert!((high - 70.0).abs() < 1e-9, "{}", high);
+        let low = decay_toward_prior(10.0, DECAY_GRACE_SECS + 3600.0, 3600.0);
+        assert!((low - 30.0).abs() < 1e-9, "{}", low);
+    }
+
+    #[test]
+    fn zero_reputation_decays_upward_like_any_other() {
+        let zero = decay_toward_prior(0.0, DECAY_GRACE_SECS + 3600.0, 3600.0);
+        assert!((zero - 25.0).abs() < 1e-9, "{}", zero);
     }
 }
@@ -0,0 +1,450 @@
+//! `bench`: measures what each peer sustains before real data is committed
+//! to it. Random shards are stored on every `--peer`, retrieved back and
+//! deleted again, and the per-peer results print as JSON rows that
+//! `upload --telemetry-file` reads as they are.
+
+use crate::{
+    extract_peer_id, make_client_swarm, wait_for_peer_connections, UploaderBehaviour, UploaderEvent,
+};
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use libp2p::{
+    request_response::{Event as RequestResponseEvent, Message as RequestResponseMessage},
+    swarm::{Swarm, SwarmEvent},
+    PeerId,
+};
+use neuro_client_sdk::shard_cid_matches;
+use neuro_protocol::{
+    cid::{self, CidFormat},
+    ChunkCommand, ChunkReply, DeleteChunkRequest, RetrieveChunkRequest, StoreChunkRequest,
+};
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::time::{Duration, Instant};
+
+const MAX_SHARD_SIZE: u64 = 16 * 1024 * 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(clap::Args, Debug)]
+pub struct BenchArgs {
+    #[arg(long, num_args = 1.., required = true)]
+    peer: Vec<String>,
+
+    /// Data sent to each peer, e.g. `256MB` or `1GB` (KB/MB/GB are powers
+    /// of 1000, KiB/MiB/GiB of 1024).
+    #[arg(long, default_value = "64MB", value_parser = parse_size)]
+    size: u64,
+
+    /// Size of each synthetic shard.
+    #[arg(long, default_value = "1MiB", value_parser = parse_size)]
+    shard_size: u64,
+
+    /// Requests in flight per peer.
+    #[arg(long, default_value_t = 8)]
+    concurrency: usize,
+
+    #[arg(long, default_value_t = 120)]
+    max_response_age_secs: u64,
+
+    #[arg(long)]
+    report_out: Option<String>,
+}
+
+/// Parses a byte size such as `1GB`, `512MiB` or `4096`.
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size {value:?}"))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" | "k" => 1_000,
+        "mb" | "m" => 1_000_000,
+        "gb" | "g" => 1_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        _ => return Err(format!("unknown size unit in {value:?}")),
+    };
+    let bytes = (number * multiplier as f64).round();
+    if !(1.0..=u64::MAX as f64).contains(&bytes) {
+        return Err(format!("size out of range: {value:?}"));
+    }
+    Ok(bytes as u64)
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Phase {
+    Store,
+    Retrieve,
+    Delete,
+}
+
+#[derive(Debug, Default)]
+struct PhaseTally {
+    sent: usize,
+    answered: usize,
+    ok: usize,
+    busy: usize,
+    bytes: u64,
+    latencies_ms: Vec<f64>,
+    first_sent: Option<Instant>,
+    last_done: Option<Instant>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct OpStats {
+    pub ok: usize,
+    pub failed: usize,
+    /// Answered busy by the node's disk queue.
+    pub busy: usize,
+    pub bytes: u64,
+    pub elapsed_ms: u64,
+    pub throughput_mbps: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+/// One row per peer. `latency_ms`, `uptime_pct` and `verify_success_pct`
+/// are the fields `telemetry_scores` derives a placement score from.
+#[derive(Debug, Serialize)]
+pub struct PeerBench {
+    pub peer: String,
+    pub connected: bool,
+    /// Median retrieve round trip.
+    pub latency_ms: f64,
+    /// Requests that got any reply.
+    pub uptime_pct: f64,
+    /// Shards that came back intact after being stored.
+    pub verify_success_pct: f64,
+    pub store: OpStats,
+    pub retrieve: OpStats,
+    pub delete: OpStats,
+}
+
+/// Nearest-rank percentile of sorted values.
+fn percentile(sorted: &[f64], pct: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+impl PhaseTally {
+    fn stats(&self) -> OpStats {
+        let mut sorted = self.latencies_ms.clone();
+        sorted.sort_by(f64::total_cmp);
+        let elapsed = match (self.first_sent, self.last_done) {
+            (Some(first), Some(last)) => last.saturating_duration_since(first),
+            _ => Duration::ZERO,
+        };
+        let throughput_mbps = if elapsed.is_zero() {
+            0.0
+        } else {
+            self.bytes as f64 * 8.0 / elapsed.as_secs_f64() / 1_000_000.0
+        };
+        OpStats {
+            ok: self.ok,
+            failed: self.sent - self.ok - self.busy,
+            busy: self.busy,
+            bytes: self.bytes,
+            elapsed_ms: elapsed.as_millis() as u64,
+            throughput_mbps,
+            p50_ms: percentile(&sorted, 50.0),
+            p90_ms: percentile(&sorted, 90.0),
+            p99_ms: percentile(&sorted, 99.0),
+            max_ms: sorted.last().copied().unwrap_or(0.0),
+        }
+    }
+}
+
+struct Job {
+    peer: usize,
+    cid: String,
+    len: usize,
+    command: ChunkCommand,
+}
+
+fn synthetic_store(peer: usize, len: usize) -> Job {
+    let mut data = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut data);
+    let cid = cid::encode(CidFormat::Sha256Hex, &Sha256::digest(&data).into());
+    Job {
+        peer,
+        cid: cid.clone(),
+        len,
+        command: ChunkCommand::Store(StoreChunkRequest { cid, data }),
+    }
+}
+
+/// Runs one phase's jobs, keeping up to `concurrency` requests per peer in
+/// flight. Returns the CIDs each peer handled successfully.
+async fn run_phase(
+    swarm: &mut Swarm<UploaderBehaviour>,
+    phase: Phase,
+    peer_ids: &[PeerId],
+    mut jobs: impl Iterator<Item = Job>,
+    concurrency: usize,
+    max_age_ms: u64,
+    tallies: &mut [PhaseTally],
+) -> Vec<Vec<String>> {
+    let window = concurrency.max(1) * peer_ids.len().max(1);
+    let mut succeeded = vec![Vec::new(); peer_ids.len()];
+    let mut inflight = HashMap::new();
+    loop {
+        while inflight.len() < window {
+            let Some(job) = jobs.next() else {
+                break;
+            };
+            let tally = &mut tallies[job.peer];
+            tally.sent += 1;
+            let now = Instant::now();
+            tally.first_sent.get_or_insert(now);
+            let request_id = swarm
+                .behaviour_mut()
+                .chunk
+                .send_request(&peer_ids[job.peer], job.command);
+            inflight.insert(request_id, (job.peer, job.cid, job.len, now));
+        }
+        if inflight.is_empty() {
+            break;
+        }
+        let (request_id, reply) = match swarm.select_next_some().await {
+            SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::Message {
+                message:
+                    RequestResponseMessage::Response {
+                        request_id,
+                        response,
+                    },
+                ..
+            })) => (request_id, Some(response)),
+            SwarmEvent::Behaviour(UploaderEvent::Chunk(
+                RequestResponseEvent::OutboundFailure { request_id, .. },
+            )) => (request_id, None),
+            _ => continue,
+        };
+        let Some((peer, cid, len, sent_at)) = inflight.remove(&request_id) else {
+            continue;
+        };
+        let peer_id = &peer_ids[peer];
+        let tally = &mut tallies[peer];
+        tally.last_done = Some(Instant::now());
+        let Some(reply) = reply else {
+            continue;
+        };
+        tally.answered += 1;
+        tally
+            .latencies_ms
+            .push(sent_at.elapsed().as_secs_f64() * 1000.0);
+        let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+        let bytes = match (phase, reply) {
+            (_, ChunkReply::Busy(_) | ChunkReply::Maintenance(_)) => {
+                tally.busy += 1;
+                continue;
+            }
+            (Phase::Store, ChunkReply::Store(r))
+                if r.stored
+                    && r.verify_receipt(peer_id, &cid, len)
+                    && r.is_fresh(now_ms, max_age_ms) =>
+            {
+                len as u64
+            }
+            (Phase::Retrieve, ChunkReply::Retrieve(r))
+                if r.found
+                    && r.verify_proof(peer_id, &cid)
+                    && r.is_fresh(now_ms, max_age_ms)
+                    && shard_cid_matches(&cid, &r.data) =>
+            {
+                r.data.len() as u64
+            }
+            (Phase::Delete, ChunkReply::Delete(r)) if r.deleted => 0,
+            _ => {
+                eprintln!("bench {phase:?} failed peer={peer_id} cid={cid}");
+                continue;
+            }
+        };
+        tally.ok += 1;
+        tally.bytes += bytes;
+        succeeded[peer].push(cid);
+    }
+    succeeded
+}
+
+pub async fn run_bench(args: BenchArgs) -> Result<()> {
+    if args.shard_size > MAX_SHARD_SIZE {
+        return Err(anyhow!(
+            "--shard-size must be at most {MAX_SHARD_SIZE} bytes"
+        ));
+    }
+    let peer_ids = args
+        .peer
+        .iter()
+        .map(|peer| extract_peer_id(peer))
+        .collect::<Result<Vec<_>>>()?;
+    let shard_size = args.shard_size.min(args.size) as usize;
+    let shards_per_peer = args.size.div_ceil(shard_size as u64) as usize;
+    let max_age_ms = args.max_response_age_secs.saturating_mul(1000);
+
+    let (mut swarm, _) = make_client_swarm(&args.peer)?;
+    let connected = wait_for_peer_connections(&mut swarm, &args.peer, CONNECT_TIMEOUT).await?;
+    let live: Vec<usize> = (0..peer_ids.len())
+        .filter(|&i| connected.contains(&peer_ids[i]))
+        .collect();
+    if live.is_empty() {
+        return Err(anyhow!("no peer reachable"));
+    }
+    eprintln!(
+        "bench peers={} connected={} shards_per_peer={shards_per_peer} shard_size={shard_size}",
+        peer_ids.len(),
+        live.len()
+    );
+
+    let mut tallies: [Vec<PhaseTally>; 3] = Default::default();
+    for tallies in &mut tallies {
+        tallies.resize_with(peer_ids.len(), PhaseTally::default);
+    }
+    let [store_tallies, retrieve_tallies, delete_tallies] = &mut tallies;
+
+    // Shards are generated as they are sent, so memory stays at the window
+    // however large --size is; peers take turns so each sees the same load.
+    let store_jobs = (0..shards_per_peer).flat_map(|i| {
+        let len = (args.size as usize - i * shard_size).min(shard_size);
+        live.iter().map(move |&peer| synthetic_store(peer, len))
+    });
+    let stored = run_phase(
+        &mut swarm,
+        Phase::Store,
+        &peer_ids,
+        store_jobs,
+        args.concurrency,
+        max_age_ms,
+        store_tallies,
+    )
+    .await;
+    eprintln!(
+        "bench store done shards={}",
+        stored.iter().map(Vec::len).sum::<usize>()
+    );
+
+    let jobs_for = |cids: &[Vec<String>], make: fn(String) -> ChunkCommand| {
+        let longest = cids.iter().map(Vec::len).max().unwrap_or(0);
+        (0..longest)
+            .flat_map(|i| {
+                cids.iter()
+                    .enumerate()
+                    .filter_map(move |(peer, c)| c.get(i).map(|cid| (peer, cid.clone())))
+            })
+            .map(move |(peer, cid)| Job {
+                peer,
+                cid: cid.clone(),
+                len: 0,
+                command: make(cid),
+            })
+            .collect::<Vec<_>>()
+    };
+    let retrieve_jobs = jobs_for(&stored, |cid| {
+        ChunkCommand::Retrieve(RetrieveChunkRequest { cid })
+    });
+    let retrieved = run_phase(
+        &mut swarm,
+        Phase::Retrieve,
+        &peer_ids,
+        retrieve_jobs.into_iter(),
+        args.concurrency,
+        max_age_ms,
+        retrieve_tallies,
+    )
+    .await;
+    eprintln!(
+        "bench retrieve done shards={}",
+        retrieved.iter().map(Vec::len).sum::<usize>()
+    );
+
+    let delete_jobs = jobs_for(&stored, |cid| {
+        ChunkCommand::Delete(DeleteChunkRequest { cid })
+    });
+    run_phase(
+        &mut swarm,
+        Phase::Delete,
+        &peer_ids,
+        delete_jobs.into_iter(),
+        args.concurrency,
+        max_age_ms,
+        delete_tallies,
+    )
+    .await;
+
+    let rows: Vec<PeerBench> = (0..peer_ids.len())
+        .map(|i| {
+            let [store, retrieve, delete] =
+                [&store_tallies[i], &retrieve_tallies[i], &delete_tallies[i]]
+                    .map(PhaseTally::stats);
+            let sent = store_tallies[i].sent + retrieve_tallies[i].sent + delete_tallies[i].sent;
+            let answered = store_tallies[i].answered
+                + retrieve_tallies[i].answered
+                + delete_tallies[i].answered;
+            let pct = |part: usize, whole: usize| {
+                if whole == 0 {
+                    0.0
+                } else {
+                    part as f64 * 100.0 / whole as f64
+                }
+            };
+            PeerBench {
+                peer: args.peer[i].clone(),
+                connected: live.contains(&i),
+                latency_ms: if retrieve.ok > 0 {
+                    retrieve.p50_ms
+                } else {
+                    store.p50_ms
+                },
+                uptime_pct: pct(answered, sent),
+                verify_success_pct: pct(retrieve.ok, store_tallies[i].sent),
+                store,
+                retrieve,
+                delete,
+            }
+        })
+        .collect();
+
+    let json = serde_json::to_vec_pretty(&rows)?;
+    if let Some(path) = &args.report_out {
+        fs::write(path, &json)?;
+    }
+    println!("{}", String::from_utf8_lossy(&json));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_parse_with_decimal_and_binary_units() {
+        assert_eq!(parse_size("1GB"), Ok(1_000_000_000));
+        assert_eq!(parse_size("512MiB"), Ok(512 << 20));
+        assert_eq!(parse_size("1.5kb"), Ok(1_500));
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("12XB").is_err());
+    }
+
+    #[test]
+    fn percentiles_use_nearest_rank() {
+        let sorted: Vec<f64> = (1..=100).map(f64::from).collect();
+        assert_eq!(percentile(&sorted, 50.0), 50.0);
+        assert_eq!(percentile(&sorted, 99.0), 99.0);
+        assert_eq!(percentile(&[7.0], 90.0), 7.0);
+        assert_eq!(percentile(&[], 50.0), 0.0);
+    }
+}
@@ -0,0 +1,56 @@
+//! Graceful stop for `upload`, `retrieve` and `audit`. Once their token is
+//! cancelled they send no new chunk requests, wait for the ones in flight,
+//! and write a partial report (`ok: false`, `cancelled: true`) describing
+//! what was done, before failing with [`Cancelled`].
+
+use crate::write_report;
+use anyhow::Result;
+use tokio_util::sync::CancellationToken;
+
+/// Error of an operation that stopped because its token was cancelled.
+#[derive(Debug)]
+pub(crate) struct Cancelled {
+    pub(crate) operation: &'static str,
+}
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} cancelled", self.operation)
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// A token cancelled by the first Ctrl-C; a second one exits at once.
+pub(crate) fn on_ctrl_c() -> CancellationToken {
+    let token = CancellationToken::new();
+    let cancel = token.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        eprintln!("uploader cancelling: finishing in-flight requests (Ctrl-C again to quit now)");
+        cancel.cancel();
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
+        }
+    });
+    token
+}
+
+/// Writes the partial report, if one was asked for, and returns the
+/// [`Cancelled`] error to end the operation with.
+pub(crate) fn finish(
+    report_out: Option<&str>,
+    operation: &'static str,
+    mut details: serde_json::Value,
+) -> Result<anyhow::Error> {
+    details["cancelled"] = serde_json::Value::Bool(true);
+    if let Some(path) = report_out {
+        write_report(path, operation, false, details)?;
+        println!("{operation} cancelled; partial report={path}");
+    } else {
+        println!("{operation} cancelled");
+    }
+    Ok(Cancelled { operation }.into())
+}
@@ -0,0 +1,472 @@
+//! Local sqlite catalog of manifests: where each one lives, what it holds,
+//! which peers carry it and how its last audit went. `audit` and `validate`
+//! update entries that are already cataloged; they never add new ones.
+
+use crate::{verify_manifest_without_password, UploadManifest, MAX_MANIFEST_BYTES};
+use anyhow::{anyhow, Result};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const CATALOG_ENV: &str = "NEURO_CATALOG";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS manifests (
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL UNIQUE,
+    label TEXT,
+    manifest_root TEXT NOT NULL,
+    total_bytes INTEGER NOT NULL,
+    chunk_count INTEGER NOT NULL,
+    shard_count INTEGER NOT NULL,
+    peers TEXT NOT NULL,
+    added_ms INTEGER NOT NULL,
+    updated_ms INTEGER NOT NULL,
+    last_audit_ms INTEGER,
+    last_audit_ok INTEGER,
+    last_audit_detail TEXT
+);
+CREATE INDEX IF NOT EXISTS manifests_label ON manifests(label);
+";
+
+#[derive(clap::Args, Debug)]
+pub struct CatalogArgs {
+    /// Catalog database; defaults to `$NEURO_CATALOG`, then
+    /// `~/.neurostore/catalog.db`.
+    #[arg(long, global = true)]
+    catalog: Option<String>,
+
+    #[command(subcommand)]
+    command: CatalogCommand,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum CatalogCommand {
+    /// Record a manifest, or refresh the entry if it is already cataloged.
+    Add {
+        manifest: String,
+
+        #[arg(long)]
+        label: Option<String>,
+    },
+    List {
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Show one entry by id, label or manifest path.
+    Show { entry: String },
+    /// Drop entries whose manifest file no longer exists.
+    Gc {
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogEntry {
+    pub id: i64,
+    pub path: String,
+    pub label: Option<String>,
+    pub manifest_root: String,
+    pub total_bytes: u64,
+    pub chunk_count: u64,
+    pub shard_count: u64,
+    pub peers: Vec<String>,
+    pub added_ms: u64,
+    pub updated_ms: u64,
+    pub last_audit_ms: Option<u64>,
+    pub last_audit_ok: Option<bool>,
+    pub last_audit_detail: Option<String>,
+}
+
+pub struct Catalog {
+    conn: Connection,
+}
+
+impl Catalog {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    fn from_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    /// Inserts or refreshes the entry for `path`. A `None` label keeps the
+    /// existing one.
+    pub fn upsert(
+        &self,
+        path: &str,
+        label: Option<&str>,
+        manifest: &UploadManifest,
+    ) -> Result<i64> {
+        let now = now_ms();
+        let peers = serde_json::to_string(&manifest_peers(manifest))?;
+        let id = self.conn.query_row(
+            "INSERT INTO manifests
+                (path, label, manifest_root, total_bytes, chunk_count, shard_count, peers, added_ms, updated_ms)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8)
+             ON CONFLICT(path) DO UPDATE SET
+                label = COALESCE(excluded.label, manifests.label),
+                manifest_root = excluded.manifest_root,
+                total_bytes = excluded.total_bytes,
+                chunk_count = excluded.chunk_count,
+                shard_count = excluded.shard_count,
+                peers = excluded.peers,
+                updated_ms = excluded.updated_ms
+             RETURNING id",
+            params![
+                path,
+                label,
+                manifest.manifest_root,
+                manifest.total_bytes as i64,
+                manifest.chunk_count as i64,
+                manifest.shards.len() as i64,
+                peers,
+                now as i64,
+            ],
+            |row| row.get(0),
+        )?;
+        Ok(id)
+    }
+
+    pub fn list(&self) -> Result<Vec<CatalogEntry>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT * FROM manifests ORDER BY COALESCE(label, path), id")?;
+        let rows = stmt.query_map([], entry_from_row)?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// Looks an entry up by numeric id, then label, then manifest path.
+    pub fn find(&self, key: &str) -> Result<Option<CatalogEntry>> {
+        if let Ok(id) = key.parse::<i64>() {
+            if let Some(entry) = self.query_one("SELECT * FROM manifests WHERE id = ?1", id)? {
+                return Ok(Some(entry));
+            }
+        }
+        if let Some(entry) = self.query_one(
+            "SELECT * FROM manifests WHERE label = ?1 ORDER BY id LIMIT 1",
+            key,
+        )? {
+            return Ok(Some(entry));
+        }
+        self.query_one(
+            "SELECT * FROM manifests WHERE path = ?1",
+            canonical_path(key),
+        )
+    }
+
+    fn query_one(&self, sql: &str, key: impl rusqlite::ToSql) -> Result<Option<CatalogEntry>> {
+        Ok(self.conn.query_row(sql, [key], entry_from_row).optional()?)
+    }
+
+    /// Returns false when `path` is not cataloged.
+    pub fn record_audit(&self, path: &str, ok: bool, detail: &str) -> Result<bool> {
+        let changed = self.conn.execute(
+            "UPDATE manifests SET last_audit_ms = ?2, last_audit_ok = ?3, last_audit_detail = ?4
+             WHERE path = ?1",
+            params![path, now_ms() as i64, ok, detail],
+        )?;
+        Ok(changed > 0)
+    }
+
+    pub fn contains(&self, path: &str) -> Result<bool> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT 1 FROM manifests WHERE path = ?1",
+                [path],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some())
+    }
+
+    pub fn remove(&self, id: i64) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM manifests WHERE id = ?1", [id])?;
+        Ok(())
+    }
+}
+
+fn entry_from_row(row: &Row<'_>) -> rusqlite::Result<CatalogEntry> {
+    let peers: String = row.get("peers")?;
+    Ok(CatalogEntry {
+        id: row.get("id")?,
+        path: row.get("path")?,
+        label: row.get("label")?,
+        manifest_root: row.get("manifest_root")?,
+        total_bytes: row.get::<_, i64>("total_bytes")? as u64,
+        chunk_count: row.get::<_, i64>("chunk_count")? as u64,
+        shard_count: row.get::<_, i64>("shard_count")? as u64,
+        peers: serde_json::from_str(&peers).unwrap_or_default(),
+        added_ms: row.get::<_, i64>("added_ms")? as u64,
+        updated_ms: row.get::<_, i64>("updated_ms")? as u64,
+        last_audit_ms: row
+            .get::<_, Option<i64>>("last_audit_ms")?
+            .map(|v| v as u64),
+        last_audit_ok: row.get("last_audit_ok")?,
+        last_audit_detail: row.get("last_audit_detail")?,
+    })
+}
+
+pub fn run_catalog(args: CatalogArgs) -> Result<()> {
+    let catalog = Catalog::open(&catalog_path(args.catalog.as_deref()))?;
+    match args.command {
+        CatalogCommand::Add { manifest, label } => {
+            let parsed = read_manifest(&manifest)?;
+            let path = canonical_path(&manifest);
+            let id = catalog.upsert(&path, label.as_deref(), &parsed)?;
+            println!(
+                "catalog add id={} path={} shards={} bytes={}",
+                id,
+                path,
+                parsed.shards.len(),
+                parsed.total_bytes
+            );
+        }
+        CatalogCommand::List { json } => {
+            let entries = catalog.list()?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+                return Ok(());
+            }
+            println!(
+                "{:>4}  {:<20}  {:>10}  {:>6}  {:>5}  {:<22}  path",
+                "id", "label", "bytes", "shards", "peers", "last audit"
+            );
+            for entry in entries {
+                println!(
+                    "{:>4}  {:<20}  {:>10}  {:>6}  {:>5}  {:<22}  {}",
+                    entry.id,
+                    entry.label.as_deref().unwrap_or("-"),
+                    entry.total_bytes,
+                    entry.shard_count,
+                    entry.peers.len(),
+                    audit_summary(&entry),
+                    entry.path
+                );
+            }
+        }
+        CatalogCommand::Show { entry } => {
+            let entry = catalog
+                .find(&entry)?
+                .ok_or_else(|| anyhow!("no catalog entry matches {entry}"))?;
+            println!("id:            {}", entry.id);
+            println!("path:          {}", entry.path);
+            println!("label:         {}", entry.label.as_deref().unwrap_or("-"));
+            println!("manifest_root: {}", entry.manifest_root);
+            println!("bytes:         {}", entry.total_bytes);
+            println!("chunks:        {}", entry.chunk_count);
+            println!("shards:        {}", entry.shard_count);
+            println!("added:         {}", format_ms(entry.added_ms));
+            println!("updated:       {}", format_ms(entry.updated_ms));
+            println!("last audit:    {}", audit_summary(&entry));
+            if let Some(detail) = &entry.last_audit_detail {
+                println!("audit detail:  {detail}");
+            }
+            println!("peers:");
+            for peer in &entry.peers {
+                println!("  {peer}");
+            }
+        }
+        CatalogCommand::Gc { dry_run } => {
+            let mut removed = 0usize;
+            for entry in catalog.list()? {
+                if Path::new(&entry.path).exists() {
+                    continue;
+                }
+                println!("catalog gc id={} missing={}", entry.id, entry.path);
+                if !dry_run {
+                    catalog.remove(entry.id)?;
+                }
+                removed += 1;
+            }
+            println!("catalog gc removed={removed} dry_run={dry_run}");
+        }
+    }
+    Ok(())
+}
+
+/// Stamps the audit outcome on the manifest's entry, if the catalog exists
+/// and already holds it. Catalog problems are reported but never fail the
+/// audit itself.
+pub fn note_audit(catalog: Option<&str>, manifest: &str, result: &Result<()>) {
+    let Some(catalog) = open_existing(catalog) else {
+        return;
+    };
+    let (ok, detail) = match result {
+        Ok(()) => (true, "passed".to_string()),
+        Err(e) => (false, format!("{e:#}")),
+    };
+    if let Err(e) = catalog.record_audit(&canonical_path(manifest), ok, &detail) {
+        eprintln!("catalog update failed: {e:#}");
+    }
+}
+
+/// Refreshes a cataloged entry after `validate` has re-read the manifest.
+pub fn note_validated(catalog: Option<&str>, manifest_path: &str, manifest: &UploadManifest) {
+    let Some(catalog) = open_existing(catalog) else {
+        return;
+    };
+    let path = canonical_path(manifest_path);
+    let result = catalog.contains(&path).and_then(|present| match present {
+        true => catalog.upsert(&path, None, manifest).map(|_| ()),
+        false => Ok(()),
+    });
+    if let Err(e) = result {
+        eprintln!("catalog update failed: {e:#}");
+    }
+}
+
+fn open_existing(catalog: Option<&str>) -> Option<Catalog> {
+    let path = catalog_path(catalog);
+    if !path.exists() {
+        return None;
+    }
+    match Catalog::open(&path) {
+        Ok(catalog) => Some(catalog),
+        Err(e) => {
+            eprintln!("catalog {} unavailable: {e:#}", path.display());
+            None
+        }
+    }
+}
+
+fn catalog_path(arg: Option<&str>) -> PathBuf {
+    if let Some(path) = arg {
+        return PathBuf::from(path);
+    }
+    if let Ok(path) = std::env::var(CATALOG_ENV) {
+        if !path.is_empty() {
+            return PathBuf::from(path);
+        }
+    }
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".neurostore").join("catalog.db"))
+        .unwrap_or_else(|| PathBuf::from("neuro-catalog.db"))
+}
+
+fn read_manifest(path: &str) -> Result<UploadManifest> {
+    let bytes = fs::read(path)?;
+    if bytes.len() > MAX_MANIFEST_BYTES {
+        return Err(anyhow!(
+            "manifest too large: {} bytes > {} bytes",
+            bytes.len(),
+            MAX_MANIFEST_BYTES
+        ));
+    }
+    let manifest: UploadManifest = serde_json::from_slice(&bytes)?;
+    verify_manifest_without_password(&manifest)?;
+    Ok(manifest)
+}
+
+/// Entries are keyed by absolute path so the same manifest reached through
+/// different relative paths is one entry.
+fn canonical_path(path: &str) -> String {
+    fs::canonicalize(path)
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| path.to_string())
+}
+
+fn manifest_peers(manifest: &UploadManifest) -> Vec<String> {
+    manifest
+        .shards
+        .iter()
+        .flat_map(|s| s.peers.iter().cloned())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+fn audit_summary(entry: &CatalogEntry) -> String {
+    match (entry.last_audit_ms, entry.last_audit_ok) {
+        (Some(ms), Some(true)) => format!("{} ok", format_ms(ms)),
+        (Some(ms), _) => format!("{} FAILED", format_ms(ms)),
+        (None, _) => "never".to_string(),
+    }
+}
+
+fn format_ms(ms: u64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms as i64)
+        .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| ms.to_string())
+}
+
+fn now_ms() -> u64 {
+    chrono::Utc::now().timestamp_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(peers: &[&str]) -> UploadManifest {
+        serde_json::from_value(serde_json::json!({
+            "version": "2.2.0",
+            "salt": "00",
+            "manifest_root": "root",
+            "total_bytes": 10,
+            "chunk_count": 1,
+            "shards": [{
+                "chunk_index": 0,
+                "shard_index": 0,
+                "cid": "cid",
+                "payload_len": 10,
+                "data_shards": 1,
+                "parity_shards": 0,
+                "peers": peers,
+                "audit_challenges": [],
+                "audit_tokens": []
+            }],
+            "manifest_hash": "",
+            "manifest_auth_tag": ""
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn upsert_keeps_label_and_records_audit() {
+        let catalog = Catalog::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let id = catalog
+            .upsert(
+                "/m/a.json",
+                Some("photos"),
+                &manifest(&["/p/b", "/p/a", "/p/b"]),
+            )
+            .unwrap();
+        assert_eq!(
+            catalog
+                .upsert("/m/a.json", None, &manifest(&["/p/c"]))
+                .unwrap(),
+            id
+        );
+        assert!(catalog.record_audit("/m/a.json", false, "timeout").unwrap());
+        assert!(!catalog
+            .record_audit("/m/other.json", true, "passed")
+            .unwrap());
+
+        let entry = catalog.find("photos").unwrap().unwrap();
+        assert_eq!(entry.id, id);
+        assert_eq!(entry.label.as_deref(), Some("photos"));
+        assert_eq!(entry.peers, vec!["/p/c".to_string()]);
+        assert_eq!(entry.last_audit_ok, Some(false));
+        assert_eq!(
+            catalog.find(&id.to_string()).unwrap().unwrap().path,
+            "/m/a.json"
+        );
+
+        catalog.remove(id).unwrap();
+        assert!(catalog.list().unwrap().is_empty());
+    }
+}
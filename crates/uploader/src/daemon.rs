@@ -0,0 +1,309 @@
+//! `autopilot --daemon`: repeats the autopilot repair pass on a schedule over
+//! a manifest file or directory, refreshing sentinel policies each cycle, and
+//! reports progress on an optional plain-HTTP status endpoint.
+
+use crate::policies::PolicySource;
+use crate::{autopilot_manifest, AutopilotArgs, SentinelPolicyRow};
+use anyhow::{anyhow, Result};
+use rand::Rng;
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+#[derive(clap::Args, Debug)]
+pub struct DaemonArgs {
+    /// Keep running, repeating the repair cycle every `--interval`.
+    #[arg(long, default_value_t = false)]
+    pub daemon: bool,
+
+    /// Time between cycles, e.g. `90s`, `15m`, `6h`, `1d`.
+    #[arg(long, default_value = "6h", value_parser = parse_duration)]
+    interval: Duration,
+
+    /// Random extra delay added to each wait, up to this much. Defaults to a
+    /// tenth of `--interval` so daemons started together drift apart.
+    #[arg(long, value_parser = parse_duration)]
+    jitter: Option<Duration>,
+
+    /// Where per-manifest reports go; defaults to `autopilot-reports/` next
+    /// to the manifests.
+    #[arg(long)]
+    report_dir: Option<String>,
+
+    /// Serve daemon status as JSON on this address, e.g. `127.0.0.1:9797`.
+    #[arg(long)]
+    status_addr: Option<SocketAddr>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+struct DaemonStatus {
+    state: &'static str,
+    cycles: u64,
+    last_cycle_started_ms: Option<u64>,
+    last_cycle_finished_ms: Option<u64>,
+    next_cycle_ms: Option<u64>,
+    policy_rows: usize,
+    policy_error: Option<String>,
+    manifests: Vec<ManifestStatus>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ManifestStatus {
+    manifest: String,
+    ok: bool,
+    shards_total: usize,
+    shards_repaired: usize,
+    shards_failed: usize,
+    error: Option<String>,
+}
+
+pub async fn run_autopilot_daemon(args: AutopilotArgs) -> Result<()> {
+    let unlock = args.password.unlock()?;
+    let opts = &args.daemon;
+    if opts.interval.is_zero() {
+        return Err(anyhow!("--interval must be greater than zero"));
+    }
+    let jitter = opts.jitter.unwrap_or(opts.interval / 10);
+    let report_dir = match &opts.report_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => manifest_root(Path::new(&args.manifest)).join("autopilot-reports"),
+    };
+    std::fs::create_dir_all(&report_dir)?;
+
+    let status = Arc::new(Mutex::new(DaemonStatus {
+        state: "starting",
+        ..DaemonStatus::default()
+    }));
+    if let Some(addr) = opts.status_addr {
+        let listener = TcpListener::bind(addr).await?;
+        println!("autopilot status listening on http://{addr}/");
+        tokio::spawn(serve_status(listener, status.clone()));
+    }
+
+    let mut source = PolicySource::from_args(&args)?;
+    let mut policies: Option<Vec<SentinelPolicyRow>> = None;
+    loop {
+        let started_ms = now_ms();
+        update(&status, |s| {
+            s.state = "running";
+            s.last_cycle_started_ms = Some(started_ms);
+            s.next_cycle_ms = None;
+        });
+
+        // A failed refresh keeps the previous cycle's policies rather than
+        // repairing blind.
+        match source.load().await {
+            Ok(rows) => {
+                policies = Some(rows);
+                update(&status, |s| s.policy_error = None);
+            }
+            Err(e) => {
+                eprintln!("autopilot policy refresh failed: {e:#}");
+                update(&status, |s| s.policy_error = Some(format!("{e:#}")));
+            }
+        }
+
+        let mut results = Vec::new();
+        match (manifest_paths(Path::new(&args.manifest)), &policies) {
+            (Ok(paths), None) => {
+                eprintln!(
+                    "autopilot skipping {} manifest(s): no sentinel policies loaded",
+                    paths.len()
+                );
+            }
+            (Ok(paths), Some(policies)) => {
+                // The policy file or cache may live alongside the manifests.
+                let policy_file = source.local_path().and_then(|p| std::fs::canonicalize(p).ok());
+                for path in paths {
+                    if policy_file.is_some() && std::fs::canonicalize(&path).ok() == policy_file {
+                        continue;
+                    }
+                    results.push(run_one(&path, &unlock, policies, &args, &report_dir).await);
+                }
+            }
+            (Err(e), _) => eprintln!("autopilot cannot list {}: {e}", args.manifest),
+        }
+
+        let delay = opts.interval + random_jitter(jitter);
+        let finished_ms = now_ms();
+        update(&status, |s| {
+            s.state = "idle";
+            s.cycles += 1;
+            s.last_cycle_finished_ms = Some(finished_ms);
+            s.next_cycle_ms = Some(finished_ms + delay.as_millis() as u64);
+            s.policy_rows = policies.as_ref().map_or(0, Vec::len);
+            s.manifests = results;
+        });
+        println!(
+            "autopilot cycle done manifests={} next_in_secs={}",
+            status.lock().map(|s| s.manifests.len()).unwrap_or(0),
+            delay.as_secs()
+        );
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = tokio::signal::ctrl_c() => {
+                println!("autopilot daemon stopping");
+                return Ok(());
+            }
+        }
+    }
+}
+
+async fn run_one(
+    path: &Path,
+    unlock: &crate::x25519::Unlock,
+    policies: &[SentinelPolicyRow],
+    args: &AutopilotArgs,
+    report_dir: &Path,
+) -> ManifestStatus {
+    let manifest = path.to_string_lossy().into_owned();
+    let result = async {
+        let report = autopilot_manifest(&manifest, unlock, policies, None, args).await?;
+        let report_path = report_dir.join(path.file_name().unwrap_or_default());
+        std::fs::write(&report_path, serde_json::to_vec_pretty(&report)?)?;
+        Ok::<_, anyhow::Error>(report)
+    }
+    .await;
+
+    match result {
+        Ok(report) => {
+            println!(
+                "autopilot manifest={} repaired={} failed={}",
+                manifest, report.summary.shards_repaired, report.summary.shards_failed
+            );
+            ManifestStatus {
+                manifest,
+                ok: report.summary.shards_failed == 0,
+                shards_total: report.summary.shards_total,
+                shards_repaired: report.summary.shards_repaired,
+                shards_failed: report.summary.shards_failed,
+                error: None,
+            }
+        }
+        Err(e) => {
+            eprintln!("autopilot manifest={manifest} error={e:#}");
+            ManifestStatus {
+                manifest,
+                ok: false,
+                shards_total: 0,
+                shards_repaired: 0,
+                shards_failed: 0,
+                error: Some(format!("{e:#}")),
+            }
+        }
+    }
+}
+
+fn manifest_root(path: &Path) -> PathBuf {
+    if path.is_dir() {
+        return path.to_path_buf();
+    }
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// The manifest itself, or the `*.json` files directly inside a directory,
+/// sorted so cycles visit them in a stable order.
+fn manifest_paths(path: &Path) -> std::io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        let entry_path = entry?.path();
+        if entry_path.is_file() && entry_path.extension().is_some_and(|ext| ext == "json") {
+            paths.push(entry_path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+async fn serve_status(listener: TcpListener, status: Arc<Mutex<DaemonStatus>>) {
+    loop {
+        let Ok((mut stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let body = status
+            .lock()
+            .ok()
+            .and_then(|s| serde_json::to_vec(&*s).ok())
+            .unwrap_or_default();
+        tokio::spawn(async move {
+            // Any request gets the status document; the request itself is
+            // only drained so clients see a clean response.
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf).await;
+            let head = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(head.as_bytes()).await;
+            let _ = stream.write_all(&body).await;
+            let _ = stream.shutdown().await;
+        });
+    }
+}
+
+fn update(status: &Mutex<DaemonStatus>, f: impl FnOnce(&mut DaemonStatus)) {
+    if let Ok(mut guard) = status.lock() {
+        f(&mut guard);
+    }
+}
+
+fn random_jitter(max: Duration) -> Duration {
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+    Duration::from_millis(rand::thread_rng().gen_range(0..=max.as_millis() as u64))
+}
+
+fn now_ms() -> u64 {
+    chrono::Utc::now().timestamp_millis() as u64
+}
+
+/// `90`, `90s`, `15m`, `6h` or `1d`.
+pub(crate) fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (digits, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => value.split_at(idx),
+        None => (value, "s"),
+    };
+    let n: u64 = digits
+        .parse()
+        .map_err(|_| format!("invalid duration `{value}`"))?;
+    let secs = match unit {
+        "s" => n,
+        "m" => n.saturating_mul(60),
+        "h" => n.saturating_mul(60 * 60),
+        "d" => n.saturating_mul(24 * 60 * 60),
+        _ => {
+            return Err(format!(
+                "invalid duration unit in `{value}`; use s, m, h or d"
+            ))
+        }
+    };
+    Ok(Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_interval_suffixes() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("15m"), Ok(Duration::from_secs(900)));
+        assert_eq!(parse_duration("6h"), Ok(Duration::from_secs(21_600)));
+        assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(86_400)));
+        assert!(parse_duration("6x").is_err());
+        assert!(parse_duration("h").is_err());
+    }
+}
@@ -0,0 +1,137 @@
+//! Upload deduplication. Before any shard bytes go out, every target peer is
+//! asked (`ChunkCommand::Has`) which of its CIDs it already holds, e.g. after
+//! an interrupted upload of the same file. Held shards are dropped from the
+//! send queue; the signed inventory reply counts as their store receipt.
+//! Peers that do not understand `Has`, or answer late or unsigned, simply
+//! get every shard as before.
+
+use crate::{StoreDispatch, UploaderBehaviour, UploaderEvent};
+use futures::StreamExt;
+use libp2p::{
+    request_response::{Event as RequestResponseEvent, Message as RequestResponseMessage},
+    swarm::{Swarm, SwarmEvent},
+    PeerId,
+};
+use neuro_protocol::{ChunkCommand, ChunkReply, HasChunksRequest, HasChunksResponse, MAX_HAS_CIDS};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// How long to wait for inventory replies before sending everything left.
+const NEGOTIATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default, Serialize)]
+pub struct DedupSummary {
+    pub peers_asked: usize,
+    pub peers_answered: usize,
+    pub skipped_shards: usize,
+    pub saved_bytes: u64,
+    /// The verified replies that held any of the asked CIDs, kept for the
+    /// receipt bundle.
+    #[serde(skip)]
+    pub inventories: Vec<(PeerId, HasChunksResponse)>,
+}
+
+/// A planned store: the peer it goes to, the CID and its length.
+pub(crate) trait PlannedStore {
+    fn target(&self) -> (PeerId, &str, usize);
+}
+
+impl PlannedStore for StoreDispatch {
+    fn target(&self) -> (PeerId, &str, usize) {
+        (self.peer_id, &self.cid, self.len)
+    }
+}
+
+/// Splits `queue` into the stores still to send and those the target peer
+/// already holds.
+pub(crate) async fn negotiate<T: PlannedStore>(
+    swarm: &mut Swarm<UploaderBehaviour>,
+    queue: Vec<T>,
+    max_age_ms: u64,
+) -> (Vec<T>, Vec<T>, DedupSummary) {
+    let mut by_peer: HashMap<PeerId, Vec<String>> = HashMap::new();
+    for item in &queue {
+        let (peer, cid, _) = item.target();
+        let cids = by_peer.entry(peer).or_default();
+        if !cids.iter().any(|c| c == cid) {
+            cids.push(cid.to_string());
+        }
+    }
+
+    let mut summary = DedupSummary {
+        peers_asked: by_peer.len(),
+        ..DedupSummary::default()
+    };
+    let mut inflight = HashMap::new();
+    for (peer, cids) in &by_peer {
+        for batch in cids.chunks(MAX_HAS_CIDS) {
+            let request = ChunkCommand::Has(HasChunksRequest {
+                cids: batch.to_vec(),
+            });
+            let request_id = swarm.behaviour_mut().chunk.send_request(peer, request);
+            inflight.insert(request_id, (*peer, batch.to_vec()));
+        }
+    }
+
+    let mut held: HashSet<(PeerId, String)> = HashSet::new();
+    let mut answered: HashSet<PeerId> = HashSet::new();
+    let deadline = Instant::now() + NEGOTIATION_TIMEOUT;
+    while !inflight.is_empty() {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let Ok(event) = tokio::time::timeout(remaining, swarm.select_next_some()).await else {
+            eprintln!("uploader dedup timed out waiting for {} inventory replies", inflight.len());
+            break;
+        };
+        match event {
+            SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::Message {
+                message: RequestResponseMessage::Response { request_id, response },
+                ..
+            })) => {
+                let Some((peer, asked)) = inflight.remove(&request_id) else {
+                    continue;
+                };
+                let ChunkReply::Has(reply) = response else {
+                    eprintln!("uploader dedup unexpected reply peer={peer}");
+                    continue;
+                };
+                let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+                if !reply.verify_inventory(&peer) || !reply.is_fresh(now_ms, max_age_ms) {
+                    eprintln!("uploader dedup ignoring unverified inventory peer={peer}");
+                    continue;
+                }
+                answered.insert(peer);
+                let before = held.len();
+                held.extend(
+                    reply
+                        .held
+                        .iter()
+                        .filter(|cid| asked.contains(cid))
+                        .map(|cid| (peer, cid.clone())),
+                );
+                if held.len() > before {
+                    summary.inventories.push((peer, reply));
+                }
+            }
+            SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::OutboundFailure {
+                request_id,
+                error,
+                ..
+            })) => {
+                if let Some((peer, _)) = inflight.remove(&request_id) {
+                    eprintln!("uploader dedup inventory failed peer={peer} err={error:?}");
+                }
+            }
+            _ => {}
+        }
+    }
+    summary.peers_answered = answered.len();
+
+    let (skipped, remaining): (Vec<_>, Vec<_>) = queue.into_iter().partition(|item| {
+        let (peer, cid, _) = item.target();
+        held.contains(&(peer, cid.to_string()))
+    });
+    summary.skipped_shards = skipped.len();
+    summary.saved_bytes = skipped.iter().map(|item| item.target().2 as u64).sum();
+    (remaining, skipped, summary)
+}
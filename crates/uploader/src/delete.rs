@@ -0,0 +1,214 @@
+//! `delete`: removes a manifest's shards from every peer that holds them,
+//! replicas included, then forgets the manifest on its gateways. Each peer
+//! gets its CIDs as `DeleteBatch` requests and has to answer with a signed
+//! receipt; the gateways are only told once every peer has, so a delete
+//! that fails part way can be re-run with the same manifest.
+
+use crate::{
+    extract_peer_id, gateways, manifest_dial_addrs, swarm_pool, verify_manifest, wait_for_peer_connections,
+    write_report, PasswordArgs, UploaderEvent, MAX_MANIFEST_BYTES, PEER_CONNECT_WARMUP_SECS,
+};
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use libp2p::{
+    request_response::{Event as RequestResponseEvent, Message as RequestResponseMessage, OutboundRequestId},
+    swarm::SwarmEvent,
+    PeerId,
+};
+use neuro_client_sdk::UploadManifest;
+use neuro_protocol::{ChunkCommand, ChunkReply, DeleteChunksRequest, MAX_DELETE_CIDS};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::time::Duration;
+
+#[derive(clap::Args, Debug)]
+pub struct DeleteArgs {
+    #[arg(long)]
+    manifest: String,
+
+    #[command(flatten)]
+    password: PasswordArgs,
+
+    /// Gateways to unregister the manifest from, besides those it names.
+    #[command(flatten)]
+    gateway: gateways::GatewayArgs,
+
+    #[arg(long, default_value_t = 120)]
+    max_response_age_secs: u64,
+
+    #[arg(long)]
+    report_out: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct PeerDeletion {
+    peer: String,
+    requested: usize,
+    /// Requested CIDs the peer's receipts say it removed; the rest it no
+    /// longer held.
+    deleted: usize,
+    error: Option<String>,
+}
+
+pub async fn run_delete(args: DeleteArgs) -> Result<()> {
+    let unlock = args.password.unlock()?;
+    let manifest_bytes = fs::read(&args.manifest)?;
+    if manifest_bytes.len() > MAX_MANIFEST_BYTES {
+        return Err(anyhow!(
+            "manifest too large: {} bytes > {} bytes",
+            manifest_bytes.len(),
+            MAX_MANIFEST_BYTES
+        ));
+    }
+    let manifest: UploadManifest = serde_json::from_slice(&manifest_bytes)?;
+    verify_manifest(&manifest, &unlock)?;
+    let max_age_ms = args.max_response_age_secs.saturating_mul(1000);
+
+    // Checked before any shard goes, so a missing token cannot leave the
+    // gateways pointing at deleted data.
+    let gateway_urls = gateways::merge_urls(&manifest.gateways, &args.gateway.gateways)?;
+    let gateway_token = if gateway_urls.is_empty() {
+        None
+    } else {
+        Some(args.gateway.require_token()?)
+    };
+
+    let mut by_peer: BTreeMap<PeerId, Vec<String>> = BTreeMap::new();
+    for shard in &manifest.shards {
+        for peer in &shard.peers {
+            by_peer.entry(extract_peer_id(peer)?).or_default().push(shard.cid.clone());
+        }
+    }
+    for cids in by_peer.values_mut() {
+        cids.sort();
+        cids.dedup();
+    }
+
+    let dial = manifest_dial_addrs(&manifest);
+    let mut swarm = swarm_pool::checkout(&dial).await?;
+    wait_for_peer_connections(&mut swarm, &dial, Duration::from_secs(PEER_CONNECT_WARMUP_SECS)).await?;
+
+    let mut outcomes: BTreeMap<PeerId, PeerDeletion> = by_peer
+        .iter()
+        .map(|(peer_id, cids)| {
+            let outcome = PeerDeletion {
+                peer: peer_id.to_string(),
+                requested: cids.len(),
+                deleted: 0,
+                error: None,
+            };
+            (*peer_id, outcome)
+        })
+        .collect();
+    let mut inflight: HashMap<OutboundRequestId, (PeerId, Vec<String>)> = HashMap::new();
+    for (peer_id, cids) in &by_peer {
+        for batch in cids.chunks(MAX_DELETE_CIDS) {
+            let request_id = swarm.behaviour_mut().chunk.send_request(
+                peer_id,
+                ChunkCommand::DeleteBatch(DeleteChunksRequest { cids: batch.to_vec() }),
+            );
+            inflight.insert(request_id, (*peer_id, batch.to_vec()));
+        }
+    }
+
+    while !inflight.is_empty() {
+        let (request_id, error) = match swarm.select_next_some().await {
+            SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::Message {
+                message: RequestResponseMessage::Response { request_id, response },
+                ..
+            })) => {
+                let Some((peer_id, cids)) = inflight.get(&request_id) else {
+                    continue;
+                };
+                let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+                let error = match response {
+                    ChunkReply::DeleteBatch(r)
+                        if r.verify_deletion(peer_id, cids) && r.is_fresh(now_ms, max_age_ms) =>
+                    {
+                        if let Some(outcome) = outcomes.get_mut(peer_id) {
+                            outcome.deleted += r.deleted.iter().filter(|cid| cids.contains(cid)).count();
+                        }
+                        None
+                    }
+                    ChunkReply::DeleteBatch(_) => Some("invalid or stale deletion receipt".to_string()),
+                    ChunkReply::Busy(_) | ChunkReply::Maintenance(_) => Some("peer is busy".to_string()),
+                    _ => Some("unexpected response to delete".to_string()),
+                };
+                (request_id, error)
+            }
+            SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::OutboundFailure {
+                request_id,
+                error,
+                ..
+            })) => (request_id, Some(error.to_string())),
+            _ => continue,
+        };
+        if let Some((peer_id, _)) = inflight.remove(&request_id) {
+            if let (Some(error), Some(outcome)) = (error, outcomes.get_mut(&peer_id)) {
+                outcome.error.get_or_insert(error);
+            }
+        }
+    }
+
+    let outcomes: Vec<PeerDeletion> = outcomes.into_values().collect();
+    for outcome in &outcomes {
+        match &outcome.error {
+            None => println!(
+                "delete peer={} requested={} deleted={}",
+                outcome.peer, outcome.requested, outcome.deleted
+            ),
+            Some(e) => eprintln!("delete failed peer={} err={e}", outcome.peer),
+        }
+    }
+    let failed = outcomes.iter().filter(|o| o.error.is_some()).count();
+    if failed > 0 {
+        if let Some(path) = &args.report_out {
+            write_report(
+                path,
+                "delete",
+                false,
+                serde_json::json!({ "manifest_path": args.manifest, "peers": outcomes }),
+            )?;
+        }
+        return Err(anyhow!(
+            "{failed} of {} peers did not confirm the delete; the manifest stays registered, run delete again",
+            outcomes.len()
+        ));
+    }
+
+    let unregistrations = match &gateway_token {
+        Some(token) => gateways::unregister_manifest(&gateway_urls, token, &manifest.manifest_root).await?,
+        None => Vec::new(),
+    };
+    for r in &unregistrations {
+        match &r.error {
+            None => println!("gateway unregistered gateway={}", r.gateway),
+            Some(e) => eprintln!("gateway unregister failed gateway={} err={e}", r.gateway),
+        }
+    }
+
+    println!(
+        "delete complete shards={} peers={} manifest={}",
+        manifest.shards.len(),
+        outcomes.len(),
+        args.manifest
+    );
+    if let Some(path) = &args.report_out {
+        write_report(
+            path,
+            "delete",
+            true,
+            serde_json::json!({
+                "manifest_path": args.manifest,
+                "manifest_root": manifest.manifest_root,
+                "peers": outcomes,
+                "gateways": unregistrations,
+            }),
+        )?;
+    }
+    if unregistrations.iter().any(|r| !r.ok) {
+        return Err(anyhow!("shards deleted but some gateways still list the manifest"));
+    }
+    Ok(())
+}
@@ -0,0 +1,260 @@
+//! Gateway copies of a manifest. `upload` and `store-prepared` register the
+//! finished manifest with every `--gateway` and record them as hints in a
+//! 3.x manifest; `retrieve` falls back to those gateways for shards no peer
+//! returned, so no single gateway (or the swarm itself) has to be reachable.
+
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use neuro_client_sdk::shard_cid_matches;
+use neuro_gateway_client::{normalize_base_url, GatewayClient};
+use serde::Serialize;
+use std::time::Duration;
+
+/// Gateway hints a manifest may carry.
+pub const MAX_GATEWAYS: usize = 16;
+const GATEWAY_TOKEN_ENV: &str = "NEURO_GATEWAY_TOKEN";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(clap::Args, Debug)]
+pub struct GatewayArgs {
+    /// Gateway base URL, e.g. `https://gw1.example.com`; repeat for more.
+    #[arg(long = "gateway")]
+    pub gateways: Vec<String>,
+
+    /// Bearer token accepted by the gateways; falls back to
+    /// `NEURO_GATEWAY_TOKEN`.
+    #[arg(long)]
+    gateway_token: Option<String>,
+}
+
+impl GatewayArgs {
+    pub fn urls(&self) -> Result<Vec<String>> {
+        merge_urls(&[], &self.gateways)
+    }
+
+    pub fn token(&self) -> Option<String> {
+        self.gateway_token
+            .clone()
+            .or_else(|| std::env::var(GATEWAY_TOKEN_ENV).ok())
+            .filter(|t| !t.is_empty())
+    }
+
+    /// The token, required once any gateway is configured.
+    pub fn require_token(&self) -> Result<String> {
+        self.token().ok_or_else(|| {
+            anyhow!("--gateway needs --gateway-token or {GATEWAY_TOKEN_ENV}")
+        })
+    }
+}
+
+/// Canonical form of a gateway base URL: http(s) only, no trailing slash.
+pub fn normalize_url(url: &str) -> Result<String> {
+    Ok(normalize_base_url(url)?)
+}
+
+/// Normalizes and de-duplicates `first` then `extra`, keeping their order.
+pub fn merge_urls(first: &[String], extra: &[String]) -> Result<Vec<String>> {
+    let mut out = Vec::new();
+    for url in first.iter().chain(extra) {
+        let url = normalize_url(url)?;
+        if !out.contains(&url) {
+            out.push(url);
+        }
+    }
+    if out.len() > MAX_GATEWAYS {
+        return Err(anyhow!(
+            "too many gateways: {} > {}",
+            out.len(),
+            MAX_GATEWAYS
+        ));
+    }
+    Ok(out)
+}
+
+#[derive(Debug, Serialize)]
+pub struct Registration {
+    pub gateway: String,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+/// One client per gateway, sharing a connection pool.
+fn clients(gateways: &[String], token: Option<&str>) -> Result<Vec<GatewayClient>> {
+    let http = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+    gateways
+        .iter()
+        .map(|gateway| {
+            let builder = GatewayClient::builder(gateway.as_str()).http_client(http.clone());
+            Ok(match token {
+                Some(token) => builder.token(token).build()?,
+                None => builder.build()?,
+            })
+        })
+        .collect()
+}
+
+/// POSTs the manifest to every gateway at once; one entry per gateway.
+pub async fn register_manifest(
+    gateways: &[String],
+    token: &str,
+    manifest_json: &[u8],
+) -> Result<Vec<Registration>> {
+    let clients = clients(gateways, Some(token))?;
+    let requests = clients.iter().map(|client| async move {
+        let error = client.register_manifest(manifest_json.to_vec(), None).await.err().map(|e| e.to_string());
+        Registration {
+            gateway: client.base_url().to_string(),
+            ok: error.is_none(),
+            error,
+        }
+    });
+    Ok(futures::future::join_all(requests).await)
+}
+
+/// Forgets the manifest on every gateway at once. One that no longer knows
+/// it counts as done, so an interrupted `delete` can simply be re-run.
+pub async fn unregister_manifest(gateways: &[String], token: &str, manifest_root: &str) -> Result<Vec<Registration>> {
+    let clients = clients(gateways, Some(token))?;
+    let requests = clients.iter().map(|client| async move {
+        let error = match client.delete_manifest(manifest_root).await {
+            Err(e) if e.status() != Some(reqwest::StatusCode::NOT_FOUND) => Some(e.to_string()),
+            _ => None,
+        };
+        Registration {
+            gateway: client.base_url().to_string(),
+            ok: error.is_none(),
+            error,
+        }
+    });
+    Ok(futures::future::join_all(requests).await)
+}
+
+/// Current addresses of `peer_id` from the first gateway that knows it.
+pub async fn locate_peer(gateways: &[String], token: &str, peer_id: &str) -> Vec<String> {
+    let Ok(clients) = clients(gateways, Some(token)) else {
+        return Vec::new();
+    };
+    for client in &clients {
+        match client.locate_peer(peer_id).await {
+            Ok(found) if !found.addrs.is_empty() => return found.addrs,
+            Ok(_) => {}
+            Err(e) => eprintln!("gateway peer lookup failed gateway={} peer={peer_id} err={e}", client.base_url()),
+        }
+    }
+    Vec::new()
+}
+
+/// Publishes a sealed share token with the first gateway that accepts it.
+/// Returns the link, without the share key that opens it.
+pub async fn publish_share(gateways: &[String], token: &str, sealed: &str) -> Result<String> {
+    let mut last_error = anyhow!("no gateway configured");
+    for client in clients(gateways, Some(token))? {
+        match client.create_share(sealed).await {
+            Ok(published) => return Ok(format!("{}/api/shares/{}", client.base_url(), published.share_id)),
+            Err(e) => last_error = anyhow!("gateway {} refused the share: {e}", client.base_url()),
+        }
+    }
+    Err(last_error)
+}
+
+/// The sealed token behind a share link (`link` without its `#fragment`).
+pub async fn fetch_share(link: &str) -> Result<String> {
+    let (gateway, share_id) = link
+        .rsplit_once("/api/shares/")
+        .filter(|(_, id)| !id.is_empty() && !id.contains('/'))
+        .ok_or_else(|| anyhow!("not a share link: {link}"))?;
+    let client = GatewayClient::new(gateway, None)?;
+    Ok(client.get_share(share_id).await?.token)
+}
+
+pub struct GatewayShard {
+    pub cid: String,
+    pub gateway: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Fetches each shard from the first gateway whose bytes hash to its CID,
+/// `concurrency` shards at a time. Shards no gateway could supply are left
+/// out of the result.
+pub async fn fetch_shards(
+    gateways: &[String],
+    token: &str,
+    manifest_root: &str,
+    cids: Vec<String>,
+    concurrency: usize,
+) -> Result<Vec<GatewayShard>> {
+    let clients = clients(gateways, Some(token))?;
+    let fetched = futures::stream::iter(cids)
+        .map(|cid| {
+            let clients = &clients;
+            async move {
+                for client in clients {
+                    let gateway = client.base_url();
+                    let bytes = match client.manifest_shard(manifest_root, &cid).await {
+                        Ok(bytes) => Some(bytes),
+                        Err(e) => {
+                            eprintln!("gateway shard fetch failed gateway={gateway} cid={cid} err={e}");
+                            None
+                        }
+                    };
+                    match bytes {
+                        Some(bytes) if shard_cid_matches(&cid, &bytes) => {
+                            return Some(GatewayShard {
+                                cid,
+                                gateway: gateway.to_string(),
+                                bytes: bytes.to_vec(),
+                            });
+                        }
+                        Some(_) => {
+                            eprintln!("gateway returned bytes not matching cid gateway={gateway} cid={cid}")
+                        }
+                        None => {}
+                    }
+                }
+                None
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .filter_map(|shard| async move { shard })
+        .collect()
+        .await;
+    Ok(fetched)
+}
+
+/// Checks `--gateway-quorum` against the configured gateways before any
+/// shard is stored.
+pub fn check_quorum(gateways: &[String], quorum: usize) -> Result<()> {
+    if !gateways.is_empty() && (quorum == 0 || quorum > gateways.len()) {
+        return Err(anyhow!(
+            "--gateway-quorum must be between 1 and {} (the number of gateways)",
+            gateways.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Registers the manifest and fails unless at least `quorum` gateways took
+/// it. The manifest file is written first, so a shortfall still leaves it
+/// usable through the peers and whichever gateways did accept it.
+pub async fn register_with_quorum(
+    gateways: &[String],
+    token: &str,
+    manifest_json: &[u8],
+    quorum: usize,
+) -> Result<Vec<Registration>> {
+    let registrations = register_manifest(gateways, token, manifest_json).await?;
+    for r in &registrations {
+        match &r.error {
+            None => println!("gateway registered gateway={}", r.gateway),
+            Some(e) => eprintln!("gateway registration failed gateway={} err={e}", r.gateway),
+        }
+    }
+    let accepted = registrations.iter().filter(|r| r.ok).count();
+    if accepted < quorum {
+        return Err(anyhow!(
+            "manifest registered with {accepted} of {} gateways; quorum is {quorum}",
+            registrations.len()
+        ));
+    }
+    Ok(registrations)
+}
@@ -2,33 +2,76 @@ use anyhow::{anyhow, Result};
 use base64::Engine;
 use clap::{Parser, ValueEnum};
 use futures::StreamExt;
+use either::Either;
 use libp2p::{
+    core::upgrade::Version,
     identity, noise,
+    pnet::{PnetConfig, PreSharedKey},
     request_response::{
         self, Behaviour as RequestResponse, Codec as RequestResponseCodec,
         Event as RequestResponseEvent, Message as RequestResponseMessage, OutboundRequestId,
     },
     swarm::{NetworkBehaviour, Swarm, SwarmEvent},
-    tcp, yamux, Multiaddr, PeerId, StreamProtocol,
+    tcp, yamux, Multiaddr, PeerId, StreamProtocol, Transport,
 };
 use neuro_client_sdk::{
-    adaptive_config, manifest_root_from_shards, process_bytes, reconstruct_bytes,
-    RedundancyProfile, Shard,
+    adaptive_config, build_audit_vectors, check_manifest_version, compute_manifest_hash,
+    erasure_regenerate, generate_salt, manifest_cid_format, manifest_root_from_shards,
+    manifest_version, process_bytes, process_bytes_for_recipients,
+    reconstruct_sparse_with_key, shard_cid_matches, simd_enabled, ChunkDecoder, CidFormat,
+    ErasureBackend, HashAlgorithm, ManifestBuilder, ManifestShard, PipelineConfig, Recipient,
+    RedundancyProfile, Shard, ShareClaims, ShareToken, UploadManifest, MANIFEST_VERSION,
+    SCOPE_RETRIEVE,
 };
+use neuro_placement::Strategy;
 use neuro_protocol::{
     AuditChunkRequest, ChunkCommand, ChunkReply, RetrieveChunkRequest, StoreChunkRequest,
 };
 use rand::{rngs::OsRng, RngCore};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+use tokio_util::sync::CancellationToken;
 use std::{fs, io, time::Duration, time::Instant};
+use progress::{Progress, ProgressEvent};
+use zeroize::Zeroizing;
+
+mod bench;
+mod cancel;
+mod catalog;
+mod daemon;
+mod dedup;
+mod delete;
+mod gateways;
+#[cfg(all(unix, feature = "mount"))]
+mod mount;
+mod policies;
+mod prepared;
+mod progress;
+mod receipts;
+#[cfg(unix)]
+mod serve;
+mod swarm_pool;
+mod tuning;
+#[cfg(feature = "tui")]
+mod tui;
+mod upload_dir;
+mod x25519;
 
 const MAX_MANIFEST_BYTES: usize = 16 * 1024 * 1024;
 const MAX_SHARDS: usize = 250_000;
 const MAX_PEERS_PER_SHARD: usize = 64;
 const MAX_AUDIT_ROUNDS: usize = 64;
 const PEER_CONNECT_WARMUP_SECS: u64 = 5;
+const PASSWORD_ENV: &str = "NEURO_PASSWORD";
+// Same service name as the tauri-shell secret store.
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "neurostore-next";
+
+/// Set once from `--swarm-key`; every client swarm uses it.
+static SWARM_KEY: OnceLock<PreSharedKey> = OnceLock::new();
 
 #[derive(Parser, Debug)]
 #[command(
@@ -37,6 +80,11 @@ const PEER_CONNECT_WARMUP_SECS: u64 = 5;
     about = "Neurostore full-loop uploader/retriever/auditor"
 )]
 struct Args {
+    /// Pre-shared key of a private swarm (go-ipfs `swarm.key` format);
+    /// required to reach nodes started with `--swarm-key`.
+    #[arg(long, global = true)]
+    swarm_key: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -44,13 +92,49 @@ struct Args {
 #[derive(clap::Subcommand, Debug)]
 enum Commands {
     Upload(UploadArgs),
+    /// Upload every file under a directory through one swarm, with all
+    /// their shards under one in-flight budget and a manifest per file.
+    UploadDir(upload_dir::UploadDirArgs),
     Retrieve(RetrieveArgs),
+    /// Delete a manifest's shards from every peer holding them, then
+    /// unregister it from its gateways.
+    Delete(delete::DeleteArgs),
     StorePrepared(StorePreparedArgs),
     RetrieveRaw(RetrieveRawArgs),
     Audit(AuditArgs),
     Validate(ValidateArgs),
+    /// Check the store receipts written next to a manifest offline: every
+    /// signature, and that each peer the manifest lists signed for its
+    /// shards.
+    VerifyReceipts(VerifyReceiptsArgs),
+    /// Re-encode a file offline and check it yields a deterministic
+    /// manifest's root, without contacting any peer.
+    Reproduce(ReproduceArgs),
     MigrateManifest(MigrateManifestArgs),
+    /// Point a manifest's peer lists at nodes' new addresses and re-sign it.
+    Rebind(RebindArgs),
+    /// Retrieve each shard and replace its audit vectors with fresh
+    /// challenge/token pairs, so spent ones are never replayed.
+    RefreshAudits(RefreshAuditsArgs),
+    /// Issue a share token that lets someone retrieve a manifest without
+    /// the password.
+    Share(ShareArgs),
+    /// Store, retrieve and delete synthetic shards on peers and report
+    /// per-peer throughput and latency as telemetry JSON.
+    Bench(bench::BenchArgs),
     Autopilot(AutopilotArgs),
+    /// Track manifests in a local catalog.
+    Catalog(catalog::CatalogArgs),
+    /// Create or show the X25519 identities that `--recipient-key` uploads
+    /// are encrypted to.
+    Identity(x25519::IdentityArgs),
+    /// Mount manifests as a read-only filesystem (unix, `mount` feature).
+    #[cfg(all(unix, feature = "mount"))]
+    Mount(MountArgs),
+    /// Take upload, retrieve and audit requests as JSON-RPC in one
+    /// long-running process that reuses peer connections (unix).
+    #[cfg(unix)]
+    Serve(serve::ServeArgs),
 }
 
 #[derive(Parser, Debug)]
@@ -58,8 +142,8 @@ struct UploadArgs {
     #[arg(long)]
     file: String,
 
-    #[arg(long)]
-    password: String,
+    #[command(flatten)]
+    password: PasswordArgs,
 
     #[arg(long, num_args = 1..)]
     peer: Vec<String>,
@@ -73,15 +157,44 @@ struct UploadArgs {
     #[arg(long, value_enum, default_value_t = ProfileArg::Balanced)]
     profile: ProfileArg,
 
+    #[arg(long, value_enum, default_value_t = ErasureBackendArg::Auto)]
+    erasure_backend: ErasureBackendArg,
+
+    /// Shard CID format. `v1` writes IPFS-compatible CIDv1s and a 2.3.0
+    /// manifest, which older uploaders refuse to read.
+    #[arg(long, value_enum, default_value_t = CidFormatArg::Hex)]
+    cid_format: CidFormatArg,
+
+    /// Hash for shard CIDs and the manifest root. `blake3` is much faster on
+    /// large files and needs `--cid-format v1`.
+    #[arg(long, value_enum, default_value_t = HashAlgorithmArg::Sha256)]
+    hash_algorithm: HashAlgorithmArg,
+
     #[arg(long, default_value_t = 2)]
     replica_factor: usize,
 
+    /// How shards are spread over peers; see `neuro_placement::Strategy`.
+    #[arg(long, value_enum, default_value_t = PlacementArg::Rendezvous)]
+    placement: PlacementArg,
+
     #[arg(long, num_args = 0..)]
     peer_score: Vec<String>,
 
     #[arg(long)]
     telemetry_file: Option<String>,
 
+    /// JSON list of `{"peer": ..., "country_code": ...}` saying where each
+    /// peer sits; `peer` may be the address or its peer id, and `region`
+    /// may stand in for `country_code`.
+    #[arg(long)]
+    peer_metadata: Option<String>,
+
+    /// Hold every shard's replicas in at least this many countries from
+    /// `--peer-metadata`. Fails before encrypting when the peers or
+    /// `--replica-factor` cannot meet it.
+    #[arg(long, requires = "peer_metadata")]
+    distinct_regions: Option<usize>,
+
     #[arg(long, default_value_t = 3)]
     audit_rounds: usize,
 
@@ -90,6 +203,66 @@ struct UploadArgs {
 
     #[arg(long)]
     report_out: Option<String>,
+
+    /// Full-screen live dashboard instead of line-per-shard output.
+    #[arg(long, default_value_t = false)]
+    tui: bool,
+
+    /// Also register the manifest with these gateways and list them in it
+    /// (a 3.x manifest), so `retrieve` can fall back to them.
+    #[command(flatten)]
+    gateway: gateways::GatewayArgs,
+
+    /// Gateways that must accept the manifest for the upload to succeed.
+    #[arg(long, default_value_t = 1)]
+    gateway_quorum: usize,
+
+    /// Reproducible encryption for audit/escrow: the same file, password,
+    /// salt and layout always give the same manifest root. Marked in the
+    /// manifest; identical chunks become linkable, so not for everyday use.
+    #[arg(long, default_value_t = false)]
+    deterministic: bool,
+
+    /// Salt for `--deterministic` (base64, as in a manifest's `salt`);
+    /// a fresh one is generated and printed when omitted.
+    #[arg(long, requires = "deterministic")]
+    salt: Option<String>,
+
+    /// Keep chunks that are one byte repeated (the zero runs of disk images
+    /// and VM snapshots) in the manifest instead of storing them. Which
+    /// chunks are constant is readable from the manifest.
+    #[arg(long, default_value_t = false)]
+    holes: bool,
+
+    /// Record each shard's merkle proof in the manifest, so `retrieve
+    /// --verify-membership` can check every served shard against the root.
+    #[arg(long, default_value_t = false)]
+    merkle_proofs: bool,
+
+    /// Send every shard without first asking peers which they already hold.
+    #[arg(long, default_value_t = false)]
+    no_dedup: bool,
+
+    /// Give each peer its own in-flight window, sized from a warmup RTT
+    /// probe and tuned while storing; `--concurrency` then caps the first
+    /// window instead of the total.
+    #[arg(long, default_value_t = false)]
+    auto_concurrency: bool,
+
+    /// Also let whoever knows the password in this file decrypt the
+    /// upload. Repeatable; any recipient makes a 5.x manifest.
+    #[arg(long, conflicts_with = "deterministic")]
+    recipient_password_file: Vec<String>,
+
+    /// Also let the holder of this X25519 public key (hex, from
+    /// `identity generate`) decrypt the upload. Repeatable.
+    #[arg(long, conflicts_with = "deterministic")]
+    recipient_key: Vec<String>,
+
+    /// Encrypt to the `--recipient-key`s only: no password is read and no
+    /// password opens the upload.
+    #[arg(long, default_value_t = false, requires = "recipient_key")]
+    no_password: bool,
 }
 
 #[derive(Parser, Debug)]
@@ -97,12 +270,40 @@ struct RetrieveArgs {
     #[arg(long)]
     manifest: String,
 
-    #[arg(long)]
-    password: String,
+    #[command(flatten)]
+    password: PasswordArgs,
+
+    /// Decrypt with a share token, or a gateway share link with the share
+    /// key as its `#fragment`, instead of the password.
+    #[arg(long, conflicts_with_all = ["identity", "identity_keyring"])]
+    share_token: Option<String>,
 
     #[arg(long, default_value = "recovered.bin")]
     out: String,
 
+    /// Shards saved by a cancelled retrieve (`<out>.resume.json`); they are
+    /// not fetched again.
+    #[arg(long)]
+    resume: Option<String>,
+
+    /// Stop fetching after this long (`90s`, `10m`, `1h`) and finish with
+    /// the shards that arrived. Unless `--allow-partial` makes do with
+    /// them, the retrieve then fails and saves `<out>.resume.json`.
+    #[arg(long, value_parser = daemon::parse_duration)]
+    deadline: Option<Duration>,
+
+    /// When shards stay missing, write every chunk that still decodes to a
+    /// sparse `--out`, zeros in the gaps, and list the gaps in
+    /// `<out>.gaps.json`. The retrieve still fails unless nothing is lost.
+    #[arg(long, default_value_t = false)]
+    allow_partial: bool,
+
+    /// Check every served shard's merkle proof against the manifest root
+    /// and leave out, without retrying, those that fail; the offending
+    /// peers are reported. Needs a manifest from `upload --merkle-proofs`.
+    #[arg(long, default_value_t = false)]
+    verify_membership: bool,
+
     #[arg(long, num_args = 0..)]
     peer: Vec<String>,
 
@@ -114,10 +315,19 @@ struct RetrieveArgs {
 
     #[arg(long)]
     report_out: Option<String>,
+
+    /// Full-screen live dashboard instead of line-per-shard output.
+    #[arg(long, default_value_t = false)]
+    tui: bool,
+
+    /// Extra gateways to fall back to, tried after those the manifest lists.
+    #[command(flatten)]
+    gateway: gateways::GatewayArgs,
 }
 
 #[derive(Parser, Debug)]
 struct StorePreparedArgs {
+    /// A JSON bundle, or NDJSON: a header line then one shard per line.
     #[arg(long)]
     prepared: String,
 
@@ -132,6 +342,27 @@ struct StorePreparedArgs {
 
     #[arg(long)]
     report_out: Option<String>,
+
+    #[command(flatten)]
+    gateway: gateways::GatewayArgs,
+
+    #[arg(long, default_value_t = 1)]
+    gateway_quorum: usize,
+
+    /// Send every shard without first asking peers which they already hold.
+    #[arg(long, default_value_t = false)]
+    no_dedup: bool,
+
+    /// Give each peer its own in-flight window, sized from a warmup RTT
+    /// probe and tuned while storing; `--concurrency` then caps the first
+    /// window instead of the total.
+    #[arg(long, default_value_t = false)]
+    auto_concurrency: bool,
+
+    /// Stop reading shards from `--prepared` while this many bytes are
+    /// queued or in flight. One shard is always let through.
+    #[arg(long, default_value_t = 256 * 1024 * 1024)]
+    max_inflight_bytes: usize,
 }
 
 #[derive(Parser, Debug)]
@@ -172,14 +403,22 @@ struct AuditArgs {
     #[arg(long, default_value_t = 8)]
     concurrency: usize,
 
-    #[arg(long)]
-    password: String,
+    #[command(flatten)]
+    password: PasswordArgs,
 
     #[arg(long, default_value_t = 120)]
     max_response_age_secs: u64,
 
     #[arg(long)]
     report_out: Option<String>,
+
+    /// Catalog to stamp with the audit result; see `catalog --help`.
+    #[arg(long)]
+    catalog: Option<String>,
+
+    /// Full-screen live dashboard instead of line-per-shard output.
+    #[arg(long, default_value_t = false)]
+    tui: bool,
 }
 
 #[derive(Parser, Debug)]
@@ -187,8 +426,42 @@ struct ValidateArgs {
     #[arg(long)]
     manifest: String,
 
+    #[command(flatten)]
+    password: PasswordArgs,
+
+    #[arg(long)]
+    report_out: Option<String>,
+
+    /// Catalog whose entry for this manifest is refreshed on success.
+    #[arg(long)]
+    catalog: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct VerifyReceiptsArgs {
+    #[arg(long)]
+    manifest: String,
+
+    /// Defaults to `<manifest>.receipts.json`.
+    #[arg(long)]
+    receipts: Option<String>,
+
+    #[arg(long)]
+    report_out: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct ReproduceArgs {
+    /// The original file.
+    #[arg(long)]
+    file: String,
+
+    /// A manifest written by `upload --deterministic`.
     #[arg(long)]
-    password: String,
+    manifest: String,
+
+    #[command(flatten)]
+    password: PasswordArgs,
 
     #[arg(long)]
     report_out: Option<String>,
@@ -202,24 +475,119 @@ struct MigrateManifestArgs {
     #[arg(long)]
     output: String,
 
+    #[command(flatten)]
+    password: PasswordArgs,
+}
+
+#[derive(Parser, Debug)]
+struct ShareArgs {
+    #[arg(long)]
+    manifest: String,
+
+    #[command(flatten)]
+    password: PasswordArgs,
+
+    /// How long the token opens the manifest.
+    #[arg(long, default_value_t = 7 * 24 * 3600)]
+    expires_in_secs: u64,
+
+    /// Also publish the token as a link on the first of these gateways that
+    /// accepts it; the manifest must be registered there.
+    #[command(flatten)]
+    gateway: gateways::GatewayArgs,
+
     #[arg(long)]
-    password: String,
+    report_out: Option<String>,
 }
 
 #[derive(Parser, Debug)]
-struct AutopilotArgs {
+struct RebindArgs {
+    #[arg(long)]
+    manifest: String,
+
+    /// Where to write the rebound manifest; defaults to `--manifest`.
+    #[arg(long)]
+    output: Option<String>,
+
+    #[command(flatten)]
+    password: PasswordArgs,
+
+    /// `old=new`: `old` is a multiaddr from the manifest or a bare PeerId
+    /// (every address of that peer), `new` a multiaddr of the same peer.
+    #[arg(long = "peer-map")]
+    peer_map: Vec<String>,
+
+    /// Look up every peer not in `--peer-map` by PeerId through the
+    /// manifest's gateways and `--gateway`, which resolve it in the DHT.
+    #[arg(long, default_value_t = false)]
+    auto: bool,
+
+    #[command(flatten)]
+    gateway: gateways::GatewayArgs,
+
+    #[arg(long)]
+    report_out: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct RefreshAuditsArgs {
     #[arg(long)]
     manifest: String,
 
+    /// Where to write the refreshed manifest; defaults to `--manifest`.
+    #[arg(long)]
+    output: Option<String>,
+
+    #[command(flatten)]
+    password: PasswordArgs,
+
+    /// Challenge/token pairs generated per shard, replacing the old ones.
+    #[arg(long, default_value_t = 3)]
+    rounds: usize,
+
+    /// Only fetch shards from these peers.
+    #[arg(long, num_args = 0..)]
+    peer: Vec<String>,
+
+    #[arg(long, default_value_t = 8)]
+    concurrency: usize,
+
+    #[arg(long, default_value_t = 120)]
+    max_response_age_secs: u64,
+
     #[arg(long)]
-    password: String,
+    report_out: Option<String>,
+}
 
+#[derive(Parser, Debug)]
+struct AutopilotArgs {
+    /// A manifest file; with `--daemon`, may also be a directory of `*.json`
+    /// manifests that is re-scanned every cycle.
     #[arg(long)]
-    policy_file: String,
+    manifest: String,
+
+    #[command(flatten)]
+    password: PasswordArgs,
+
+    #[arg(long, required_unless_present_any = ["policy_url", "approve"])]
+    policy_file: Option<String>,
+
+    /// Fetch a signed sentinel policy document from this URL instead of a
+    /// file.
+    #[arg(long, conflicts_with = "policy_file")]
+    policy_url: Option<String>,
+
+    #[command(flatten)]
+    policy_fetch: policies::PolicyFetchArgs,
 
     #[arg(long, default_value_t = 2)]
     replica_factor: usize,
 
+    /// Must match the strategy the manifest was uploaded with, or repairs
+    /// pick different peers than the upload would have.
+    #[arg(long, value_enum, default_value_t = PlacementArg::Rendezvous)]
+    placement: PlacementArg,
+
     #[arg(long, default_value_t = 40.0)]
     quarantine_reputation: f64,
 
@@ -231,6 +599,147 @@ struct AutopilotArgs {
 
     #[arg(long, default_value = "autopilot-report.json")]
     report_out: String,
+
+    /// Write the repairs this pass would make to `--report-out` as a signed
+    /// plan, without contacting any peer or touching the manifest.
+    #[arg(long, default_value_t = false, conflicts_with_all = ["daemon", "approve"])]
+    dry_run: bool,
+
+    /// Carry out a plan written by `--dry-run`, after checking its signature
+    /// and that the manifest has not changed since it was planned.
+    #[arg(long, conflicts_with = "daemon")]
+    approve: Option<String>,
+
+    #[command(flatten)]
+    daemon: daemon::DaemonArgs,
+}
+
+/// Password sources, tried in order: `--password`, `--password-file`,
+/// `NEURO_PASSWORD`, `--password-keyring`, then a hidden terminal prompt.
+#[cfg(all(unix, feature = "mount"))]
+#[derive(Parser, Debug)]
+struct MountArgs {
+    /// A manifest file, or a directory of `*.json` manifests mounted as a tree.
+    #[arg(long)]
+    manifest: String,
+
+    #[command(flatten)]
+    password: PasswordArgs,
+
+    mountpoint: String,
+
+    #[arg(long, num_args = 0..)]
+    peer: Vec<String>,
+
+    #[arg(long, default_value_t = 120)]
+    max_response_age_secs: u64,
+
+    /// Decoded chunks kept in memory for repeated reads.
+    #[arg(long, default_value_t = 32)]
+    cache_chunks: usize,
+
+    #[arg(long, default_value_t = false)]
+    allow_other: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct PasswordArgs {
+    /// Visible in shell history and process listings; prefer the other sources.
+    #[arg(long, value_parser = parse_secret)]
+    password: Option<Zeroizing<String>>,
+
+    #[arg(long)]
+    password_file: Option<String>,
+
+    /// Account name in the OS keyring entry shared with the desktop shell.
+    #[arg(long)]
+    password_keyring: Option<String>,
+
+    /// Open manifests encrypted to an X25519 public key with this identity
+    /// file from `identity generate`, instead of a password.
+    #[arg(long, conflicts_with = "identity_keyring")]
+    identity: Option<String>,
+
+    /// Like `--identity`, from an OS keyring account (the desktop shell
+    /// keeps its identity under `x25519_identity`).
+    #[arg(long)]
+    identity_keyring: Option<String>,
+}
+
+impl PasswordArgs {
+    /// The identity when one was given, otherwise the password.
+    fn unlock(&self) -> Result<x25519::Unlock> {
+        match x25519::load(self.identity.as_deref(), self.identity_keyring.as_deref())? {
+            Some(secret) => Ok(x25519::Unlock::Identity(secret)),
+            None => Ok(x25519::Unlock::Password(self.resolve()?)),
+        }
+    }
+
+    fn resolve(&self) -> Result<Zeroizing<String>> {
+        if let Some(password) = &self.password {
+            eprintln!(
+                "warning: --password is visible to other local users; prefer --password-file, {PASSWORD_ENV} or --password-keyring"
+            );
+            return Ok(password.clone());
+        }
+        if let Some(path) = &self.password_file {
+            return read_secret_file(path);
+        }
+        if let Ok(password) = std::env::var(PASSWORD_ENV) {
+            if !password.is_empty() {
+                return Ok(Zeroizing::new(password));
+            }
+        }
+        if let Some(account) = &self.password_keyring {
+            return keyring_password(account);
+        }
+        if io::stdin().is_terminal() {
+            let password = Zeroizing::new(rpassword::prompt_password("password: ")?);
+            if password.is_empty() {
+                return Err(anyhow!("empty password"));
+            }
+            return Ok(password);
+        }
+        Err(anyhow!(
+            "no password provided; use --password-file, {PASSWORD_ENV}, --password-keyring or run interactively"
+        ))
+    }
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_password(account: &str) -> Result<Zeroizing<String>> {
+    let entry = keyring::Entry::new(KEYRING_SERVICE, account)?;
+    match entry.get_password() {
+        Ok(password) => Ok(Zeroizing::new(password)),
+        Err(keyring::Error::NoEntry) => Err(anyhow!(
+            "no keyring entry for service={} account={}",
+            KEYRING_SERVICE,
+            account
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_password(_account: &str) -> Result<Zeroizing<String>> {
+    Err(anyhow!(
+        "--password-keyring requires neuro-uploader built with the `keyring` feature"
+    ))
+}
+
+/// A password or key file's contents without the trailing newline.
+fn read_secret_file(path: &str) -> Result<Zeroizing<String>> {
+    let raw = Zeroizing::new(fs::read_to_string(path)?);
+    let secret = raw.trim_end_matches(['\r', '\n']);
+    if secret.is_empty() {
+        return Err(anyhow!("{} is empty", path));
+    }
+    Ok(Zeroizing::new(secret.to_string()))
+}
+
+/// Keeps password arguments in a buffer that is wiped on drop.
+fn parse_secret(value: &str) -> Result<Zeroizing<String>, std::convert::Infallible> {
+    Ok(Zeroizing::new(value.to_string()))
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
@@ -250,29 +759,72 @@ impl From<ProfileArg> for RedundancyProfile {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct ManifestShard {
-    chunk_index: usize,
-    shard_index: usize,
-    cid: String,
-    payload_len: usize,
-    data_shards: usize,
-    parity_shards: usize,
-    peers: Vec<String>,
-    audit_challenges: Vec<String>,
-    audit_tokens: Vec<String>,
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ErasureBackendArg {
+    Auto,
+    Serial,
+    Parallel,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct UploadManifest {
-    version: String,
-    salt: String,
-    manifest_root: String,
-    total_bytes: usize,
-    chunk_count: usize,
-    shards: Vec<ManifestShard>,
-    manifest_hash: String,
-    manifest_auth_tag: String,
+impl From<ErasureBackendArg> for ErasureBackend {
+    fn from(value: ErasureBackendArg) -> Self {
+        match value {
+            ErasureBackendArg::Auto => ErasureBackend::Auto,
+            ErasureBackendArg::Serial => ErasureBackend::Serial,
+            ErasureBackendArg::Parallel => ErasureBackend::Parallel,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum CidFormatArg {
+    Hex,
+    V1,
+}
+
+impl From<CidFormatArg> for CidFormat {
+    fn from(value: CidFormatArg) -> Self {
+        match value {
+            CidFormatArg::Hex => CidFormat::Sha256Hex,
+            CidFormatArg::V1 => CidFormat::V1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum HashAlgorithmArg {
+    Sha256,
+    Blake3,
+}
+
+impl From<HashAlgorithmArg> for HashAlgorithm {
+    fn from(value: HashAlgorithmArg) -> Self {
+        match value {
+            HashAlgorithmArg::Sha256 => HashAlgorithm::Sha256,
+            HashAlgorithmArg::Blake3 => HashAlgorithm::Blake3,
+        }
+    }
+}
+
+/// Peers named on the command line carry no region or free space, so
+/// `region-aware` and `capacity-aware` rank exactly like `rendezvous` here.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum PlacementArg {
+    Rendezvous,
+    RoundRobin,
+    RegionAware,
+    CapacityAware,
+}
+
+impl From<PlacementArg> for Strategy {
+    fn from(value: PlacementArg) -> Self {
+        match value {
+            PlacementArg::Rendezvous => Strategy::Rendezvous,
+            PlacementArg::RoundRobin => Strategy::RoundRobin,
+            PlacementArg::RegionAware => Strategy::RegionAware,
+            PlacementArg::CapacityAware => Strategy::CapacityAware,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -286,26 +838,6 @@ struct LegacyUploadManifest {
     manifest_hash: String,
 }
 
-#[derive(Debug, Clone, Deserialize)]
-struct PreparedUploadBundle {
-    salt: String,
-    total_bytes: usize,
-    chunk_count: usize,
-    shards: Vec<PreparedUploadShard>,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-struct PreparedUploadShard {
-    chunk_index: usize,
-    shard_index: usize,
-    cid: String,
-    payload_len: usize,
-    data_shards: usize,
-    parity_shards: usize,
-    peers: Vec<String>,
-    bytes_b64: String,
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct RawRetrieveBundle {
     version: String,
@@ -342,26 +874,62 @@ struct SentinelPolicyRow {
     confidence: Option<f64>,
     anomaly: Option<bool>,
     recommendation: Option<String>,
+    /// The sentinel's reasons for its verdict, most significant first.
+    #[serde(default)]
+    explanations: Vec<PolicyExplanation>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Deserialize)]
+struct PolicyExplanation {
+    detail: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 struct ActionReport {
     operation: String,
     timestamp_ms: u64,
+    /// A proposal from `--dry-run`; nothing was moved.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    dry_run: bool,
+    /// The manifest's `manifest_hash` before the pass.
+    #[serde(default)]
+    manifest_hash: String,
     quarantined_peers: Vec<String>,
+    /// Why the sentinel flagged each quarantined peer, where it said.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    quarantine_reasons: BTreeMap<String, Vec<String>>,
     actions: Vec<ShardAction>,
     summary: ActionSummary,
     signature: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct ActionSummary {
     shards_total: usize,
     shards_repaired: usize,
     shards_failed: usize,
+    /// Shards a dry run would repair.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    shards_planned: usize,
 }
 
-#[derive(Debug, Serialize)]
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+/// A reviewed `--dry-run` plan: the quarantine it was made under and the
+/// peers each shard is to be copied to.
+struct ApprovedPlan {
+    manifest_hash: String,
+    quarantined: HashSet<String>,
+    quarantine_reasons: BTreeMap<String, Vec<String>>,
+    targets: HashMap<String, Vec<String>>,
+}
+
+/// `ShardAction::reason` of the copies a dry run proposes.
+const PLANNED: &str = "planned";
+
+#[derive(Debug, Serialize, Deserialize)]
 struct ShardAction {
     cid: String,
     from_peer: String,
@@ -370,16 +938,6 @@ struct ShardAction {
     reason: String,
 }
 
-#[derive(Serialize)]
-struct ManifestHashView<'a> {
-    version: &'a str,
-    salt: &'a str,
-    manifest_root: &'a str,
-    total_bytes: usize,
-    chunk_count: usize,
-    shards: &'a [ManifestShard],
-}
-
 #[derive(Debug, Clone, Deserialize)]
 struct PeerTelemetryInput {
     peer: String,
@@ -391,6 +949,13 @@ struct PeerTelemetryInput {
     confidence: Option<f64>,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+struct PeerMetadataInput {
+    peer: String,
+    #[serde(alias = "region")]
+    country_code: Option<String>,
+}
+
 #[derive(Clone, Default)]
 pub struct ChunkCodec;
 
@@ -475,22 +1040,56 @@ impl From<RequestResponseEvent<ChunkCommand, ChunkReply>> for UploaderEvent {
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
+    if let Some(path) = &args.swarm_key {
+        let raw = fs::read_to_string(path)
+            .map_err(|e| anyhow!("failed to read swarm key {path}: {e}"))?;
+        let psk: PreSharedKey = raw
+            .parse()
+            .map_err(|e| anyhow!("invalid swarm key {path}: {e}"))?;
+        let _ = SWARM_KEY.set(psk);
+    }
     match args.command {
-        Commands::Upload(upload) => run_upload(upload).await,
-        Commands::Retrieve(retrieve) => run_retrieve(retrieve).await,
+        Commands::Upload(upload) => run_upload(upload, cancel::on_ctrl_c()).await,
+        Commands::UploadDir(upload_dir) => upload_dir::run_upload_dir(upload_dir, cancel::on_ctrl_c()).await,
+        Commands::Retrieve(retrieve) => run_retrieve(retrieve, cancel::on_ctrl_c()).await,
+        Commands::Delete(delete) => delete::run_delete(delete).await,
         Commands::StorePrepared(store_prepared) => run_store_prepared(store_prepared).await,
         Commands::RetrieveRaw(retrieve_raw) => run_retrieve_raw(retrieve_raw).await,
-        Commands::Audit(audit) => run_audit(audit).await,
+        Commands::Audit(audit) => run_audit(audit, cancel::on_ctrl_c()).await,
         Commands::Validate(validate) => run_validate(validate).await,
+        Commands::VerifyReceipts(verify) => run_verify_receipts(verify),
+        Commands::Reproduce(reproduce) => run_reproduce(reproduce).await,
         Commands::MigrateManifest(migrate) => run_migrate_manifest(migrate).await,
+        Commands::Rebind(rebind) => run_rebind(rebind).await,
+        Commands::RefreshAudits(refresh) => run_refresh_audits(refresh).await,
+        Commands::Share(share) => run_share(share).await,
+        Commands::Bench(bench) => bench::run_bench(bench).await,
         Commands::Autopilot(autopilot) => run_autopilot(autopilot).await,
+        Commands::Catalog(catalog) => catalog::run_catalog(catalog),
+        Commands::Identity(identity) => x25519::run_identity(identity),
+        #[cfg(all(unix, feature = "mount"))]
+        Commands::Mount(mount) => mount::run_mount(mount).await,
+        #[cfg(unix)]
+        Commands::Serve(serve) => serve::run_serve(serve).await,
     }
 }
 
-async fn run_upload(args: UploadArgs) -> Result<()> {
+async fn run_upload(args: UploadArgs, cancel: CancellationToken) -> Result<()> {
+    let password = if args.no_password {
+        None
+    } else {
+        Some(args.password.resolve()?)
+    };
     if args.peer.is_empty() {
         return Err(anyhow!("at least one --peer is required"));
     }
+    let gateway_urls = args.gateway.urls()?;
+    gateways::check_quorum(&gateway_urls, args.gateway_quorum)?;
+    let gateway_token = if gateway_urls.is_empty() {
+        None
+    } else {
+        Some(args.gateway.require_token()?)
+    };
 
     if args.audit_rounds == 0 || args.audit_rounds > MAX_AUDIT_ROUNDS {
         return Err(anyhow!(
@@ -506,10 +1105,63 @@ async fn run_upload(args: UploadArgs) -> Result<()> {
     for (peer, score) in parse_peer_scores(&args.peer_score)? {
         peer_scores.insert(peer, score);
     }
+    let peer_regions = match args.peer_metadata.as_deref() {
+        Some(path) => parse_peer_metadata(&fs::read(path)?)?,
+        None => HashMap::new(),
+    };
+    let placement: Strategy = args.placement.into();
+    if let Some(min_regions) = args.distinct_regions {
+        for peer in unique_peers.iter().filter(|p| peer_region(&peer_regions, p).is_none()) {
+            eprintln!("uploader placement warning: no region known for peer {peer}");
+        }
+        // Whether the spread is possible depends only on the peers, so one
+        // trial placement settles it for every shard.
+        select_spread_peers_for_cid(placement, "", &unique_peers, &peer_scores, &peer_regions, replica_target, min_regions)?;
+        println!("uploader placement distinct_regions={min_regions}");
+    }
 
     let data = fs::read(&args.file)?;
-    let cfg = adaptive_config(data.len(), unique_peers.len(), args.profile.into());
-    let output = process_bytes(&data, &args.password, cfg)?;
+    let mut cfg = adaptive_config(data.len(), unique_peers.len(), args.profile.into());
+    cfg.erasure_backend = args.erasure_backend.into();
+    cfg.cid_format = args.cid_format.into();
+    cfg.hash_algorithm = args.hash_algorithm.into();
+    if args.deterministic {
+        let salt = args.salt.clone().unwrap_or_else(generate_salt);
+        println!("uploader deterministic salt={salt}");
+        cfg.deterministic_salt = Some(salt);
+    }
+    cfg.holes = args.holes;
+    for warning in cfg.adjust_for_peers(unique_peers.len()) {
+        eprintln!("uploader erasure warning: {warning}");
+    }
+    println!(
+        "uploader erasure backend={:?} simd={}",
+        cfg.erasure_backend.resolve(),
+        simd_enabled()
+    );
+    let mut recipients = Vec::new();
+    for path in &args.recipient_password_file {
+        recipients.push(Recipient::Password(read_secret_file(path)?));
+    }
+    for key in &args.recipient_key {
+        recipients.push(Recipient::x25519_hex(key)?);
+    }
+    let (output, content_key) = match &password {
+        Some(password) if recipients.is_empty() => (process_bytes(&data, password, cfg)?, None),
+        _ => {
+            // The uploader's own password is always one of the recipients.
+            if let Some(password) = &password {
+                recipients.insert(0, Recipient::Password(password.clone()));
+            }
+            let (output, key) = process_bytes_for_recipients(&data, &recipients, cfg)?;
+            println!("uploader recipients={}", output.recipients.len());
+            (output, Some(key))
+        }
+    };
+    println!(
+        "uploader erasure data_shards={} parity_shards={} chunk_size={}",
+        output.config.data_shards, output.config.parity_shards, output.config.chunk_size
+    );
     if output.shards.len() > MAX_SHARDS {
         return Err(anyhow!(
             "too many shards generated: {} > {}",
@@ -518,7 +1170,7 @@ async fn run_upload(args: UploadArgs) -> Result<()> {
         ));
     }
 
-    let (mut swarm, _) = make_client_swarm(&unique_peers)?;
+    let mut swarm = swarm_pool::checkout(&unique_peers).await?;
     let warm_connected = wait_for_peer_connections(
         &mut swarm,
         &unique_peers,
@@ -535,13 +1187,21 @@ async fn run_upload(args: UploadArgs) -> Result<()> {
     );
 
     let mut queue = Vec::<StoreDispatch>::new();
-    let mut manifest_shards = Vec::with_capacity(output.shards.len());
+    let mut builder = ManifestBuilder::for_output(&output).merkle_proofs(args.merkle_proofs);
 
     for shard in &output.shards {
-        if !is_valid_cid_hex(&shard.cid) {
-            return Err(anyhow!("invalid cid format generated: {}", shard.cid));
-        }
-        let targets = select_peers_for_cid(&shard.cid, &unique_peers, &peer_scores, replica_target);
+        let targets = match args.distinct_regions {
+            Some(min_regions) => select_spread_peers_for_cid(
+                placement,
+                &shard.cid,
+                &unique_peers,
+                &peer_scores,
+                &peer_regions,
+                replica_target,
+                min_regions,
+            )?,
+            None => select_peers_for_cid(placement, &shard.cid, &unique_peers, &peer_scores, replica_target),
+        };
         if targets.len() > MAX_PEERS_PER_SHARD {
             return Err(anyhow!(
                 "too many peer targets for shard {}: {} > {}",
@@ -550,9 +1210,11 @@ async fn run_upload(args: UploadArgs) -> Result<()> {
                 MAX_PEERS_PER_SHARD
             ));
         }
-        let (audit_challenges, audit_tokens) = build_audit_vectors(&shard.bytes, args.audit_rounds);
+        let placed = builder
+            .place_shard(shard, targets, args.audit_rounds)
+            .map_err(|e| anyhow!("invalid cid format generated: {e}"))?;
 
-        for peer in &targets {
+        for peer in &placed.peers {
             queue.push(StoreDispatch {
                 request: ChunkCommand::Store(StoreChunkRequest {
                     cid: shard.cid.clone(),
@@ -563,45 +1225,98 @@ async fn run_upload(args: UploadArgs) -> Result<()> {
                 peer_id: extract_peer_id(peer)?,
             });
         }
+    }
 
-        manifest_shards.push(ManifestShard {
-            chunk_index: shard.chunk_index,
-            shard_index: shard.shard_index,
-            cid: shard.cid.clone(),
-            payload_len: shard.payload_len,
-            data_shards: shard.data_shards,
-            parity_shards: shard.parity_shards,
-            peers: targets,
-            audit_challenges,
-            audit_tokens,
-        });
+    let max_age_ms = args.max_response_age_secs.saturating_mul(1000);
+    let mut acked_by_cid: HashMap<String, usize> = HashMap::new();
+    let mut receipts = receipts::ReceiptCollector::default();
+    let dedup_summary = if args.no_dedup {
+        None
+    } else {
+        let (remaining, held, summary) = dedup::negotiate(&mut swarm, queue, max_age_ms).await;
+        queue = remaining;
+        for item in held {
+            *acked_by_cid.entry(item.cid).or_insert(0) += 1;
+        }
+        for (peer, reply) in &summary.inventories {
+            receipts.record_inventory(*peer, reply);
+        }
+        println!(
+            "uploader dedup peers_answered={}/{} skipped_shards={} saved_bytes={}",
+            summary.peers_answered, summary.peers_asked, summary.skipped_shards, summary.saved_bytes
+        );
+        Some(summary)
+    };
+
+    let mut tuner = if args.auto_concurrency {
+        let targets: HashSet<PeerId> = queue.iter().map(|item| item.peer_id).collect();
+        let probes = tuning::probe(&mut swarm, &targets).await;
+        println!("uploader rtt probe answered={}/{}", probes.len(), targets.len());
+        tuning::Tuner::auto(args.concurrency, probes)
+    } else {
+        tuning::Tuner::fixed(args.concurrency)
+    };
+    let total = queue.len();
+    for dispatch in queue {
+        tuner.push(
+            dispatch.peer_id,
+            InflightStore {
+                dispatch,
+                attempt: 0,
+                busy_replies: 0,
+                started: Instant::now(),
+            },
+        );
     }
 
     let mut inflight: HashMap<OutboundRequestId, InflightStore> = HashMap::new();
-    let mut sent = 0usize;
     let mut acked_requests = 0usize;
-    let mut acked_by_cid: HashMap<String, usize> = HashMap::new();
-    let max_age_ms = args.max_response_age_secs.saturating_mul(1000);
 
-    while acked_requests < queue.len() {
-        while inflight.len() < args.concurrency && sent < queue.len() {
-            let item = &queue[sent];
+    let progress = Progress::start(args.tui)?;
+    progress.emit(ProgressEvent::Begin {
+        op: "upload",
+        total,
+    });
+
+    while acked_requests < total {
+        if cancel.is_cancelled() && inflight.is_empty() {
+            break;
+        }
+        while !cancel.is_cancelled() {
+            let Some(mut state) = tuner.next() else {
+                break;
+            };
             let request_id = swarm
                 .behaviour_mut()
                 .chunk
-                .send_request(&item.peer_id, item.request.clone());
-            inflight.insert(
-                request_id,
-                InflightStore {
-                    dispatch: item.clone(),
-                    attempt: 0,
-                    started: Instant::now(),
-                },
-            );
-            sent += 1;
+                .send_request(&state.dispatch.peer_id, state.dispatch.request.clone());
+            progress.emit(ProgressEvent::Sent {
+                peer: state.dispatch.peer_id,
+            });
+            state.started = Instant::now();
+            inflight.insert(request_id, state);
         }
+        progress.emit(ProgressEvent::Queue {
+            pending: tuner.pending(),
+            inflight: inflight.len(),
+        });
 
-        match swarm.select_next_some().await {
+        // Cancelling wakes the loop, which then only drains what is in flight.
+        let resume_in = tuner.resume_in().filter(|_| !cancel.is_cancelled());
+        let next_event = async {
+            match resume_in {
+                Some(wait) => tokio::time::timeout(wait, swarm.select_next_some()).await.ok(),
+                None => Some(swarm.select_next_some().await),
+            }
+        };
+        let event = tokio::select! {
+            event = next_event => match event {
+                Some(event) => event,
+                None => continue,
+            },
+            _ = cancel.cancelled(), if !cancel.is_cancelled() => continue,
+        };
+        match event {
             SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::Message { 
                 message: RequestResponseMessage::Response { request_id, response },
                 ..
@@ -616,23 +1331,68 @@ async fn run_upload(args: UploadArgs) -> Result<()> {
                             );
                             let now_ms = chrono::Utc::now().timestamp_millis() as u64;
                             let fresh = store_resp.is_fresh(now_ms, max_age_ms);
-                            println!(
+                            progress.log(format!(
                                 "store cid={} ok={} verified={} fresh={} rtt_ms={}",
                                 state.dispatch.cid,
                                 store_resp.stored,
                                 verified,
                                 fresh,
                                 state.started.elapsed().as_millis()
-                            );
+                            ));
                             if !store_resp.stored || !verified || !fresh {
                                 return Err(anyhow!(
                                     "failed store or invalid receipt for {}",
                                     state.dispatch.cid
                                 ));
                             }
+                            tuner.completed(
+                                state.dispatch.peer_id,
+                                state.started.elapsed(),
+                                state.dispatch.len,
+                            );
+                            progress.emit(ProgressEvent::Completed {
+                                peer: state.dispatch.peer_id,
+                                bytes: state.dispatch.len,
+                            });
+                            receipts.record_store(
+                                state.dispatch.peer_id,
+                                &state.dispatch.cid,
+                                state.dispatch.len,
+                                &store_resp,
+                            );
                             *acked_by_cid.entry(state.dispatch.cid).or_insert(0) += 1;
                             acked_requests += 1;
                         }
+                        ChunkReply::Busy(busy) => {
+                            let mut state = state;
+                            state.busy_replies += 1;
+                            if state.busy_replies > tuning::MAX_BUSY_REPLIES {
+                                return Err(anyhow!(
+                                    "peer {} stayed busy for cid={}",
+                                    state.dispatch.peer_id,
+                                    state.dispatch.cid
+                                ));
+                            }
+                            progress.emit(ProgressEvent::Retry {
+                                peer: state.dispatch.peer_id,
+                            });
+                            tuner.busy(state.dispatch.peer_id, state, busy.retry_after_ms);
+                        }
+                        ChunkReply::Rejected(rejected) => {
+                            return Err(anyhow!(
+                                "peer {} rejected store for cid={}: {}",
+                                state.dispatch.peer_id,
+                                state.dispatch.cid,
+                                rejected.error
+                            ))
+                        }
+                        ChunkReply::Maintenance(_) => {
+                            return Err(anyhow!(
+                                "peer {} is in maintenance and refused store for cid={}",
+                                state.dispatch.peer_id,
+                                state.dispatch.cid
+                            ))
+                        }
                         _ => {
                             return Err(anyhow!(
                                 "unexpected response type for store request"
@@ -645,8 +1405,15 @@ async fn run_upload(args: UploadArgs) -> Result<()> {
                 request_id, error, ..
             })) => {
                 if let Some(mut state) = inflight.remove(&request_id) {
-                    if state.attempt < 3 {
+                    if cancel.is_cancelled() {
+                        // Not retried; the shard shows as unstored in the
+                        // partial report.
+                    } else if state.attempt < 3 {
                         state.attempt += 1;
+                        tuner.retried(state.dispatch.peer_id);
+                        progress.emit(ProgressEvent::Retry {
+                            peer: state.dispatch.peer_id,
+                        });
                         let retry_id = swarm.behaviour_mut().chunk.send_request(
                             &state.dispatch.peer_id,
                             state.dispatch.request.clone(),
@@ -662,19 +1429,47 @@ async fn run_upload(args: UploadArgs) -> Result<()> {
                 }
             }
             SwarmEvent::OutgoingConnectionError { peer_id, error, .. } => {
-                eprintln!("uploader outgoing connection error peer={peer_id:?} err={error:?}");
+                progress.warn(format!(
+                    "uploader outgoing connection error peer={peer_id:?} err={error:?}"
+                ));
             }
             SwarmEvent::ConnectionEstablished {
                 peer_id, endpoint, ..
             } => {
-                eprintln!("uploader connected peer={peer_id} endpoint={endpoint:?}");
+                progress.warn(format!("uploader connected peer={peer_id} endpoint={endpoint:?}"));
             }
             _ => {}
         }
 
     }
+    progress.finish();
+    let concurrency = tuner.summary();
+    print_concurrency("uploader", &concurrency);
+    if cancel.is_cancelled() {
+        let shards_stored = builder
+            .shards()
+            .iter()
+            .filter(|ms| acked_by_cid.get(&ms.cid).copied().unwrap_or(0) >= ms.peers.len())
+            .count();
+        return Err(cancel::finish(
+            args.report_out.as_deref(),
+            "upload",
+            serde_json::json!({
+                "file": args.file,
+                "shards": builder.shards().len(),
+                "shards_stored": shards_stored,
+                "requests_acked": acked_requests,
+                "requests_total": total,
+                "acked_by_cid": acked_by_cid,
+                // Re-running with `--deterministic --salt` rebuilds the same
+                // shards, and dedup skips those already stored.
+                "resume_salt": args.deterministic.then(|| output.salt.clone()),
+                "concurrency": concurrency
+            }),
+        )?);
+    }
 
-    for ms in &manifest_shards {
+    for ms in builder.shards() {
         let got = acked_by_cid.get(&ms.cid).copied().unwrap_or(0);
         if got < ms.peers.len() {
             return Err(anyhow!(
@@ -686,19 +1481,11 @@ async fn run_upload(args: UploadArgs) -> Result<()> {
         }
     }
 
-    let mut manifest = UploadManifest {
-        version: "2.2.0".to_string(),
-        salt: output.salt,
-        manifest_root: output.manifest_root,
-        total_bytes: output.total_bytes,
-        chunk_count: output.chunk_count,
-        shards: manifest_shards,
-        manifest_hash: String::new(),
-        manifest_auth_tag: String::new(),
+    let builder = builder.gateways(gateway_urls);
+    let manifest = match &content_key {
+        Some(key) => builder.seal_with_key(key)?,
+        None => builder.seal(password.as_deref().expect("password-only uploads have a password"))?,
     };
-    manifest.manifest_hash = compute_manifest_hash(&manifest)?;
-    manifest.manifest_auth_tag =
-        derive_manifest_auth_tag(&args.password, &manifest.salt, &manifest.manifest_hash);
     let manifest_bytes = serde_json::to_vec_pretty(&manifest)?;
     if manifest_bytes.len() > MAX_MANIFEST_BYTES {
         return Err(anyhow!(
@@ -707,7 +1494,21 @@ async fn run_upload(args: UploadArgs) -> Result<()> {
             MAX_MANIFEST_BYTES
         ));
     }
-    fs::write(&args.manifest_out, manifest_bytes)?;
+    fs::write(&args.manifest_out, &manifest_bytes)?;
+    let receipts_out = receipts::receipts_path(&args.manifest_out);
+    receipts::write(&receipts_out, &receipts.finish(&manifest.manifest_root))?;
+    let registrations = match &gateway_token {
+        Some(token) => {
+            gateways::register_with_quorum(
+                &manifest.gateways,
+                token,
+                &manifest_bytes,
+                args.gateway_quorum,
+            )
+            .await?
+        }
+        None => Vec::new(),
+    };
 
     println!(
         "upload complete shards={} replicas={} manifest={}",
@@ -725,14 +1526,19 @@ async fn run_upload(args: UploadArgs) -> Result<()> {
                 "shards": manifest.shards.len(),
                 "replicas": replica_target,
                 "chunk_count": manifest.chunk_count,
-                "total_bytes": manifest.total_bytes
+                "total_bytes": manifest.total_bytes,
+                "gateways": registrations,
+                "dedup": dedup_summary,
+                "receipts_path": receipts_out,
+                "concurrency": concurrency
             }),
         )?;
     }
     Ok(())
 }
 
-async fn run_retrieve(args: RetrieveArgs) -> Result<()> {
+async fn run_retrieve(args: RetrieveArgs, cancel: CancellationToken) -> Result<()> {
+    let deadline = args.deadline.map(|budget| tokio::time::Instant::now() + budget);
     let manifest_bytes = fs::read(&args.manifest)?;
     if manifest_bytes.len() > MAX_MANIFEST_BYTES {
         return Err(anyhow!(
@@ -742,43 +1548,112 @@ async fn run_retrieve(args: RetrieveArgs) -> Result<()> {
         ));
     }
     let manifest: UploadManifest = serde_json::from_slice(&manifest_bytes)?;
-    verify_manifest(&manifest, &args.password)?;
+    // A share token cannot check the password-keyed auth tag, but it only
+    // opens for the manifest root, which the structure check ties to the
+    // shard list.
+    let key = match &args.share_token {
+        Some(token) => {
+            verify_manifest_without_password(&manifest)?;
+            let token = resolve_share_token(token).await?;
+            let now_secs = chrono::Utc::now().timestamp().max(0) as u64;
+            token.open(&manifest.manifest_root, now_secs)?
+        }
+        None => {
+            let unlock = args.password.unlock()?;
+            verify_manifest(&manifest, &unlock)?;
+            unlock.chunk_key(&manifest)?
+        }
+    };
+    if args.verify_membership && (0..manifest.shards.len()).any(|i| manifest.shard_membership(i).is_none()) {
+        return Err(anyhow!(
+            "manifest carries no merkle proofs to verify membership with; upload with --merkle-proofs"
+        ));
+    }
     let max_age_ms = args.max_response_age_secs.saturating_mul(1000);
+    // Shards the swarm cannot supply are fetched from these afterwards.
+    let gateway_urls = gateways::merge_urls(&manifest.gateways, &args.gateway.gateways)?;
 
     let all_peer_set = if args.peer.is_empty() {
-        let mut set = HashSet::<String>::new();
-        for ms in &manifest.shards {
-            for p in &ms.peers {
-                set.insert(p.clone());
-            }
-        }
-        set.into_iter().collect::<Vec<_>>()
+        manifest_dial_addrs(&manifest)
     } else {
         dedup_peers(&args.peer)
     };
-    if all_peer_set.is_empty() {
+    if all_peer_set.is_empty() && gateway_urls.is_empty() {
         return Err(anyhow!("no peers available for retrieval"));
     }
 
-    let (mut swarm, _) = make_client_swarm(&all_peer_set)?;
-    let warm_connected = wait_for_peer_connections(
+    let mut completed: HashMap<(usize, usize), Shard> = HashMap::new();
+    // Shards left out under --verify-membership, and who served them.
+    let mut excluded: HashSet<(usize, usize)> = HashSet::new();
+    let mut membership_failures = Vec::<MembershipFailure>::new();
+    if let Some(path) = &args.resume {
+        let bundle: RawRetrieveBundle = serde_json::from_slice(&fs::read(path)?)?;
+        if bundle.manifest_root != manifest.manifest_root {
+            return Err(anyhow!("{path} was saved for a different manifest"));
+        }
+        for saved in bundle.shards {
+            let Some(ms) = manifest.shards.iter().find(|ms| ms.cid == saved.cid) else {
+                continue;
+            };
+            let bytes = decode_b64(&saved.bytes_b64)?;
+            if shard_cid_matches(&ms.cid, &bytes) {
+                if args.verify_membership {
+                    if let Some(failure) = membership_failure(&manifest, &ms.cid, path) {
+                        excluded.insert((ms.chunk_index, ms.shard_index));
+                        membership_failures.push(failure);
+                        continue;
+                    }
+                }
+                let mut shard = ms.to_template();
+                shard.bytes = bytes;
+                completed.insert((ms.chunk_index, ms.shard_index), shard);
+            }
+        }
+        println!("retrieve resume shards={} from={}", completed.len(), path);
+    }
+
+    let mut swarm = swarm_pool::checkout(&all_peer_set).await?;
+    let mut warm_connected = wait_for_peer_connections(
         &mut swarm,
         &all_peer_set,
         Duration::from_secs(PEER_CONNECT_WARMUP_SECS),
     )
     .await?;
-    if warm_connected.is_empty() {
-        return Err(anyhow!("unable to connect to any retrieval peer during warmup"));
+    // Peers that moved since the manifest was written are looked up by
+    // their PeerId; `rebind --auto` makes what is found stick.
+    let mut relocated = Vec::new();
+    if let (true, false, Some(token)) = (args.peer.is_empty(), gateway_urls.is_empty(), args.gateway.token()) {
+        if completed.len() < manifest.shards.len() {
+            relocated =
+                relocate_peers(&mut swarm, &all_peer_set, &mut warm_connected, &gateway_urls, &token).await?;
+        }
+    }
+    for addr in &relocated {
+        println!("retrieve relocated peer addr={addr}");
+    }
+    if warm_connected.is_empty() && completed.len() < manifest.shards.len() {
+        if gateway_urls.is_empty() {
+            return Err(anyhow!("unable to connect to any retrieval peer during warmup"));
+        }
+        eprintln!("warning: no retrieval peer reachable; falling back to gateways");
     }
 
     let mut pending = VecDeque::<RetrieveAttemptState>::new();
     for ms in &manifest.shards {
-        let peers = if args.peer.is_empty() {
+        if completed.contains_key(&(ms.chunk_index, ms.shard_index)) {
+            continue;
+        }
+        let peers = if warm_connected.is_empty() {
+            Vec::new()
+        } else if args.peer.is_empty() {
             ms.peers.clone()
         } else {
             intersect_peers(&ms.peers, &all_peer_set)
         };
         if peers.is_empty() {
+            if !gateway_urls.is_empty() {
+                continue;
+            }
             return Err(anyhow!("no available peer candidates for cid={}", ms.cid));
         }
         pending.push_back(RetrieveAttemptState {
@@ -791,10 +1666,24 @@ async fn run_retrieve(args: RetrieveArgs) -> Result<()> {
     }
 
     let mut inflight: HashMap<OutboundRequestId, RetrieveAttemptState> = HashMap::new();
-    let mut completed: HashMap<(usize, usize), Shard> = HashMap::new();
 
-    while completed.len() < manifest.shards.len() {
-        while inflight.len() < args.concurrency {
+    let progress = Progress::start(args.tui)?;
+    progress.emit(ProgressEvent::Begin {
+        op: "retrieve",
+        total: manifest.shards.len(),
+    });
+
+    let expiry = async move {
+        match deadline {
+            Some(at) => tokio::time::sleep_until(at).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::pin!(expiry);
+    let mut timed_out = false;
+
+    while completed.len() + excluded.len() < manifest.shards.len() {
+        while inflight.len() < args.concurrency && !cancel.is_cancelled() {
             let Some(state) = pending.pop_front() else {
                 break;
             };
@@ -806,14 +1695,29 @@ async fn run_retrieve(args: RetrieveArgs) -> Result<()> {
                     cid: state.cid.clone(),
                 }),
             );
+            progress.emit(ProgressEvent::Sent { peer: peer_id });
             inflight.insert(request_id, state);
         }
 
         if inflight.is_empty() {
             break;
         }
+        progress.emit(ProgressEvent::Queue {
+            pending: pending.len(),
+            inflight: inflight.len(),
+        });
 
-        match swarm.select_next_some().await {
+        // Past the deadline the requests in flight are abandoned; their
+        // replies, if any, are never read.
+        let event = tokio::select! {
+            event = swarm.select_next_some() => event,
+            _ = cancel.cancelled(), if !cancel.is_cancelled() => continue,
+            _ = &mut expiry => {
+                timed_out = true;
+                break;
+            }
+        };
+        match event {
             SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::Message { 
                 message: RequestResponseMessage::Response { request_id, response },
                 ..
@@ -832,26 +1736,43 @@ async fn run_retrieve(args: RetrieveArgs) -> Result<()> {
                                         chrono::Utc::now().timestamp_millis() as u64,
                                         max_age_ms,
                                     )
-                                    && sha256_hex(&reply.data) == state.cid
+                                    && shard_cid_matches(&state.cid, &reply.data)
                                 {
-                                    if let Some(template) = manifest
+                                    let failure = if args.verify_membership {
+                                        membership_failure(&manifest, &state.cid, &peer_id.to_string())
+                                    } else {
+                                        None
+                                    };
+                                    if let Some(failure) = failure {
+                                        progress.warn(format!(
+                                            "retrieve membership_failed cid={} chunk={} shard={} peer={}",
+                                            state.cid, state.chunk_index, state.shard_index, peer_id
+                                        ));
+                                        excluded.insert(key);
+                                        membership_failures.push(failure);
+                                    } else if let Some(template) = manifest
                                         .shards
                                         .iter()
                                         .find(|x| x.cid == state.cid)
-                                        .map(manifest_shard_to_template)
+                                        .map(ManifestShard::to_template)
                                     {
+                                        progress.emit(ProgressEvent::Completed {
+                                            peer: peer_id,
+                                            bytes: reply.data.len(),
+                                        });
                                         let mut shard = template;
                                         shard.bytes = reply.data;
                                         e.insert(shard);
-                                        println!(
+                                        progress.log(format!(
                                             "retrieve cid={} chunk={} shard={} via_attempt={}",
                                             state.cid,
                                             state.chunk_index,
                                             state.shard_index,
                                             state.attempt + 1
-                                        );
+                                        ));
                                     }
                                 } else {
+                                    progress.emit(ProgressEvent::Retry { peer: peer_id });
                                     state.attempt += 1;
                                     if state.attempt < state.peers.len() {
                                         pending.push_back(state);
@@ -867,8 +1788,9 @@ async fn run_retrieve(args: RetrieveArgs) -> Result<()> {
                     }
                 }
             }
-            SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::OutboundFailure { request_id, .. })) => {
+            SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::OutboundFailure { request_id, peer, .. })) => {
                 if let Some(mut state) = inflight.remove(&request_id) {
+                    progress.emit(ProgressEvent::Retry { peer });
                     state.attempt += 1;
                     if state.attempt < state.peers.len() {
                         pending.push_back(state);
@@ -884,7 +1806,115 @@ async fn run_retrieve(args: RetrieveArgs) -> Result<()> {
         }
     }
 
-    if completed.len() != manifest.shards.len() {
+    progress.finish();
+    if cancel.is_cancelled() {
+        // The shards fetched so far, for `retrieve --resume`.
+        let resume_path = format!("{}.resume.json", args.out);
+        fs::write(&resume_path, serde_json::to_vec_pretty(&raw_bundle_of(&manifest, completed.values()))?)?;
+        return Err(cancel::finish(
+            args.report_out.as_deref(),
+            "retrieve",
+            serde_json::json!({
+                "manifest_path": args.manifest,
+                "out_path": args.out,
+                "shards": manifest.shards.len(),
+                "shards_fetched": completed.len(),
+                "resume_path": resume_path
+            }),
+        )?);
+    }
+
+    let mut gateway_shards = 0;
+    if completed.len() + excluded.len() < manifest.shards.len() && !gateway_urls.is_empty() && !timed_out {
+        match args.gateway.token() {
+            Some(token) => {
+                let missing: Vec<String> = manifest
+                    .shards
+                    .iter()
+                    .filter(|ms| {
+                        let key = (ms.chunk_index, ms.shard_index);
+                        !completed.contains_key(&key) && !excluded.contains(&key)
+                    })
+                    .map(|ms| ms.cid.clone())
+                    .collect();
+                let fetch = gateways::fetch_shards(
+                    &gateway_urls,
+                    &token,
+                    &manifest.manifest_root,
+                    missing,
+                    args.concurrency,
+                );
+                let fetched = match deadline {
+                    Some(at) => tokio::time::timeout_at(at, fetch).await.unwrap_or_else(|_| {
+                        timed_out = true;
+                        Ok(Vec::new())
+                    })?,
+                    None => fetch.await?,
+                };
+                for fetched in fetched {
+                    let Some(ms) = manifest.shards.iter().find(|x| x.cid == fetched.cid) else {
+                        continue;
+                    };
+                    if args.verify_membership {
+                        if let Some(failure) = membership_failure(&manifest, &ms.cid, &fetched.gateway) {
+                            eprintln!(
+                                "retrieve membership_failed cid={} chunk={} shard={} gateway={}",
+                                ms.cid, ms.chunk_index, ms.shard_index, fetched.gateway
+                            );
+                            excluded.insert((ms.chunk_index, ms.shard_index));
+                            membership_failures.push(failure);
+                            continue;
+                        }
+                    }
+                    println!(
+                        "retrieve cid={} chunk={} shard={} via_gateway={}",
+                        ms.cid, ms.chunk_index, ms.shard_index, fetched.gateway
+                    );
+                    let mut shard = ms.to_template();
+                    shard.bytes = fetched.bytes;
+                    completed.insert((ms.chunk_index, ms.shard_index), shard);
+                    gateway_shards += 1;
+                }
+            }
+            None => eprintln!(
+                "warning: {} shards missing but no gateway token; set --gateway-token or NEURO_GATEWAY_TOKEN to fall back to gateways",
+                manifest.shards.len() - completed.len() - excluded.len()
+            ),
+        }
+    }
+
+    // Excluded shards are not missing: erasure coding may decode without
+    // them, and refetching would only serve the same bytes again.
+    let missing = manifest.shards.len() - completed.len() - excluded.len();
+    if missing > 0 && (timed_out || args.allow_partial) {
+        // The shards fetched so far, for `retrieve --resume`.
+        let resume_path = format!("{}.resume.json", args.out);
+        fs::write(&resume_path, serde_json::to_vec_pretty(&raw_bundle_of(&manifest, completed.values()))?)?;
+        let details = serde_json::json!({
+            "manifest_path": args.manifest,
+            "out_path": args.out,
+            "shards": manifest.shards.len(),
+            "shards_fetched": completed.len(),
+            "gateway_shards": gateway_shards,
+            "relocated_peers": relocated,
+            "membership_failures": membership_failures,
+            "deadline_exceeded": timed_out,
+            "resume_path": resume_path
+        });
+        if args.allow_partial {
+            return finish_partial_retrieve(&args, &manifest, &completed, key, details);
+        }
+        if let Some(path) = &args.report_out {
+            write_report(path, "retrieve", false, details)?;
+        }
+        return Err(anyhow!(
+            "retrieve deadline exceeded recovered={} expected={}; resume with --resume {}",
+            completed.len(),
+            manifest.shards.len(),
+            resume_path
+        ));
+    }
+    if missing > 0 {
         return Err(anyhow!(
             "retrieval incomplete recovered={} expected={}",
             completed.len(),
@@ -893,14 +1923,11 @@ async fn run_retrieve(args: RetrieveArgs) -> Result<()> {
     }
 
     let recovered_shards: Vec<Shard> = completed.into_values().collect();
-    let recovered = reconstruct_bytes(&recovered_shards, &args.password, &manifest.salt)?;
-    if recovered.len() != manifest.total_bytes {
-        return Err(anyhow!(
-            "recovered size mismatch expected={} actual={}",
-            manifest.total_bytes,
-            recovered.len()
-        ));
-    }
+    let recovered = reconstruct_sparse_with_key(&recovered_shards, &manifest.holes, key, manifest.total_bytes)
+        .map_err(|err| match excluded.len() {
+            0 => err,
+            n => err.context(format!("{n} shards excluded for failing membership")),
+        })?;
     fs::write(&args.out, &recovered)?;
     println!(
         "retrieve complete bytes={} out={}",
@@ -916,33 +1943,203 @@ async fn run_retrieve(args: RetrieveArgs) -> Result<()> {
                 "manifest_path": args.manifest,
                 "out_path": args.out,
                 "bytes": recovered.len(),
-                "shards": manifest.shards.len()
+                "shards": manifest.shards.len(),
+                "gateway_shards": gateway_shards,
+                "relocated_peers": relocated,
+                "membership_failures": membership_failures
             }),
         )?;
     }
     Ok(())
 }
 
-async fn run_store_prepared(args: StorePreparedArgs) -> Result<()> {
-    let prepared_bytes = fs::read(&args.prepared)?;
-    let prepared: PreparedUploadBundle = serde_json::from_slice(&prepared_bytes)?;
-    if prepared.shards.is_empty() {
-        return Err(anyhow!("prepared bundle has no shards"));
+/// A served shard whose merkle proof does not reach the manifest root,
+/// left out by `retrieve --verify-membership`. `source` is the peer id,
+/// gateway URL or resume file it came from.
+#[derive(Debug, Serialize)]
+struct MembershipFailure {
+    cid: String,
+    chunk_index: usize,
+    shard_index: usize,
+    source: String,
+}
+
+fn membership_failure(manifest: &UploadManifest, cid: &str, source: &str) -> Option<MembershipFailure> {
+    let position = manifest.shards.iter().position(|ms| ms.cid == cid)?;
+    if manifest.shard_membership(position) != Some(false) {
+        return None;
     }
-    if prepared.shards.len() > MAX_SHARDS {
-        return Err(anyhow!(
-            "prepared shard count exceeds limit: {} > {}",
-            prepared.shards.len(),
-            MAX_SHARDS
-        ));
+    let ms = &manifest.shards[position];
+    Some(MembershipFailure {
+        cid: ms.cid.clone(),
+        chunk_index: ms.chunk_index,
+        shard_index: ms.shard_index,
+        source: source.to_string(),
+    })
+}
+
+/// A chunk `retrieve --allow-partial` could not rebuild, as listed in the
+/// gap report. `offset` and `len` are in the plaintext.
+#[derive(Debug, Serialize)]
+struct ChunkGap {
+    chunk_index: usize,
+    offset: u64,
+    len: u64,
+    shards_fetched: usize,
+    shards_needed: usize,
+    reason: String,
+}
+
+struct PartialRecovery {
+    /// Plaintext offset and bytes of every chunk that decoded.
+    chunks: Vec<(u64, Vec<u8>)>,
+    gaps: Vec<ChunkGap>,
+}
+
+/// Decodes every chunk with enough fetched shards.
+fn recover_chunks(
+    manifest: &UploadManifest,
+    completed: &HashMap<(usize, usize), Shard>,
+    decoder: &ChunkDecoder,
+) -> Result<PartialRecovery> {
+    let spans = chunk_spans(manifest)?;
+    let mut by_chunk: HashMap<usize, Vec<Shard>> = HashMap::new();
+    for shard in completed.values() {
+        by_chunk.entry(shard.chunk_index).or_default().push(shard.clone());
+    }
+    let mut recovered = Vec::new();
+    let mut gaps = Vec::new();
+    for (chunk_index, &(offset, len)) in spans.iter().enumerate() {
+        if let Some(hole) = manifest.holes.iter().find(|h| h.chunk_index == chunk_index) {
+            recovered.push((offset, hole.bytes()));
+            continue;
+        }
+        let shards = by_chunk.remove(&chunk_index).unwrap_or_default();
+        let shards_needed = manifest
+            .shards
+            .iter()
+            .find(|ms| ms.chunk_index == chunk_index)
+            .map_or(0, |ms| ms.data_shards);
+        let reason = if shards.len() < shards_needed {
+            "too few shards fetched".to_string()
+        } else {
+            match decoder.decode(&shards, spans.len()) {
+                Ok(plain) if plain.len() as u64 == len => {
+                    recovered.push((offset, plain));
+                    continue;
+                }
+                Ok(plain) => format!("decoded {} bytes, expected {len}", plain.len()),
+                Err(e) => format!("decode failed: {e}"),
+            }
+        };
+        gaps.push(ChunkGap {
+            chunk_index,
+            offset,
+            len,
+            shards_fetched: shards.len(),
+            shards_needed,
+            reason,
+        });
+    }
+    Ok(PartialRecovery { chunks: recovered, gaps })
+}
+
+/// Writes the chunks at their offsets into a file of `total_bytes`; what
+/// they leave out reads as zeros and, on most filesystems, takes no space.
+fn write_sparse(path: &str, total_bytes: u64, chunks: &[(u64, Vec<u8>)]) -> Result<()> {
+    use std::io::{Seek, SeekFrom, Write};
+    let mut file = fs::File::create(path)?;
+    file.set_len(total_bytes)?;
+    for (offset, bytes) in chunks {
+        file.seek(SeekFrom::Start(*offset))?;
+        file.write_all(bytes)?;
     }
+    Ok(())
+}
 
-    let mut all_peers = Vec::<String>::new();
-    let mut queue = Vec::<StoreDispatch>::new();
-    let mut manifest_shards = Vec::with_capacity(prepared.shards.len());
+/// The end of `retrieve --allow-partial` with shards still missing: writes
+/// what decodes, the gap report and the run report. Succeeds only when the
+/// missing shards cost no chunk.
+fn finish_partial_retrieve(
+    args: &RetrieveArgs,
+    manifest: &UploadManifest,
+    completed: &HashMap<(usize, usize), Shard>,
+    key: Zeroizing<[u8; 32]>,
+    mut details: serde_json::Value,
+) -> Result<()> {
+    let PartialRecovery { chunks, gaps } = recover_chunks(manifest, completed, &ChunkDecoder::from_key(key))?;
+    write_sparse(&args.out, manifest.total_bytes as u64, &chunks)?;
+    let recovered_bytes: u64 = chunks.iter().map(|(_, bytes)| bytes.len() as u64).sum();
+    let gap_path = format!("{}.gaps.json", args.out);
+    fs::write(
+        &gap_path,
+        serde_json::to_vec_pretty(&serde_json::json!({
+            "manifest_root": manifest.manifest_root,
+            "total_bytes": manifest.total_bytes,
+            "recovered_bytes": recovered_bytes,
+            "gaps": gaps,
+        }))?,
+    )?;
+    println!(
+        "retrieve partial bytes={}/{} chunks={}/{} out={} gaps={}",
+        recovered_bytes,
+        manifest.total_bytes,
+        chunks.len(),
+        chunks.len() + gaps.len(),
+        args.out,
+        gap_path
+    );
+    details["bytes"] = recovered_bytes.into();
+    details["chunks_missing"] = gaps.len().into();
+    details["gap_path"] = gap_path.clone().into();
+    if let Some(path) = &args.report_out {
+        write_report(path, "retrieve", gaps.is_empty(), details)?;
+    }
+    if gaps.is_empty() {
+        return Ok(());
+    }
+    Err(anyhow!(
+        "retrieve partial: {} of {} chunks could not be rebuilt; see {}",
+        gaps.len(),
+        chunks.len() + gaps.len(),
+        gap_path
+    ))
+}
+
+async fn run_store_prepared(args: StorePreparedArgs) -> Result<()> {
+    let (header, source) = prepared::PreparedSource::open(&args.prepared)?;
+    let gateway_urls = args.gateway.urls()?;
+    gateways::check_quorum(&gateway_urls, args.gateway_quorum)?;
+    let gateway_token = if gateway_urls.is_empty() {
+        None
+    } else {
+        Some(args.gateway.require_token()?)
+    };
 
-    for shard in &prepared.shards {
-        if !is_valid_cid_hex(&shard.cid) {
+    let mut all_peers = Vec::<String>::new();
+    let mut queue = Vec::<PendingStore>::new();
+
+    // First pass: check every shard and lay out the manifest. Shard bytes
+    // are decoded for their audit vectors and dropped again.
+    let mut builder = ManifestBuilder::new(header.salt.clone(), header.total_bytes, header.chunk_count)
+        .deterministic(header.deterministic);
+    let mut format: Option<(CidFormat, HashAlgorithm)> = None;
+    let mut shard_count = 0usize;
+    for (index, shard) in source.shards()?.enumerate() {
+        let shard = shard?;
+        shard_count += 1;
+        if shard_count > MAX_SHARDS {
+            return Err(anyhow!("prepared shard count exceeds limit: > {}", MAX_SHARDS));
+        }
+        // Prepared bundles carry no version; the first shard's CID decides
+        // the format and hash, and every other shard has to agree.
+        let (cid_format, hash_algorithm) = *format.get_or_insert_with(|| {
+            (
+                CidFormat::of(&shard.cid).unwrap_or_default(),
+                HashAlgorithm::of(&shard.cid).unwrap_or_default(),
+            )
+        });
+        if CidFormat::of(&shard.cid) != Some(cid_format) || HashAlgorithm::of(&shard.cid) != Some(hash_algorithm) {
             return Err(anyhow!("invalid cid in prepared shard: {}", shard.cid));
         }
         if shard.peers.is_empty() {
@@ -963,44 +2160,34 @@ async fn run_store_prepared(args: StorePreparedArgs) -> Result<()> {
             all_peers.push(peer.clone());
         }
 
-        let shard_bytes = decode_b64(&shard.bytes_b64)?;
-        if shard_bytes.is_empty() {
-            return Err(anyhow!("prepared shard {} has empty bytes", shard.cid));
-        }
-        let digest = sha256_hex(&shard_bytes);
-        if digest != shard.cid {
-            return Err(anyhow!(
-                "prepared shard cid mismatch cid={} computed={}",
-                shard.cid,
-                digest
-            ));
-        }
-
+        let shard_bytes = shard.bytes()?;
         let (audit_challenges, audit_tokens) = build_audit_vectors(&shard_bytes, 3);
         for peer in &dedup_targets {
-            queue.push(StoreDispatch {
-                request: ChunkCommand::Store(StoreChunkRequest {
-                    cid: shard.cid.clone(),
-                    data: shard_bytes.clone(),
-                }),
+            queue.push(PendingStore {
+                shard: index,
                 cid: shard.cid.clone(),
                 len: shard_bytes.len(),
                 peer_id: extract_peer_id(peer)?,
             });
         }
 
-        manifest_shards.push(ManifestShard {
+        builder.push_shard(ManifestShard {
             chunk_index: shard.chunk_index,
             shard_index: shard.shard_index,
-            cid: shard.cid.clone(),
+            cid: shard.cid,
             payload_len: shard.payload_len,
             data_shards: shard.data_shards,
             parity_shards: shard.parity_shards,
             peers: dedup_targets,
             audit_challenges,
             audit_tokens,
+            merkle_proof: Vec::new(),
         });
     }
+    let Some((cid_format, hash_algorithm)) = format else {
+        return Err(anyhow!("prepared bundle has no shards"));
+    };
+    builder = builder.cid_format(cid_format).hash_algorithm(hash_algorithm);
 
     let unique_peers = dedup_peers(&all_peers);
     if unique_peers.is_empty() {
@@ -1023,31 +2210,110 @@ async fn run_store_prepared(args: StorePreparedArgs) -> Result<()> {
         unique_peers.len()
     );
 
+    let max_age_ms = args.max_response_age_secs.saturating_mul(1000);
+    let mut acked_by_cid: HashMap<String, usize> = HashMap::new();
+    let mut receipts = receipts::ReceiptCollector::default();
+    let dedup_summary = if args.no_dedup {
+        None
+    } else {
+        let (remaining, held, summary) = dedup::negotiate(&mut swarm, queue, max_age_ms).await;
+        queue = remaining;
+        for item in held {
+            *acked_by_cid.entry(item.cid).or_insert(0) += 1;
+        }
+        for (peer, reply) in &summary.inventories {
+            receipts.record_inventory(*peer, reply);
+        }
+        println!(
+            "store-prepared dedup peers_answered={}/{} skipped_shards={} saved_bytes={}",
+            summary.peers_answered, summary.peers_asked, summary.skipped_shards, summary.saved_bytes
+        );
+        Some(summary)
+    };
+
+    let mut tuner = if args.auto_concurrency {
+        let targets: HashSet<PeerId> = queue.iter().map(|item| item.peer_id).collect();
+        let probes = tuning::probe(&mut swarm, &targets).await;
+        println!("store-prepared rtt probe answered={}/{}", probes.len(), targets.len());
+        tuning::Tuner::auto(args.concurrency, probes)
+    } else {
+        tuning::Tuner::fixed(args.concurrency)
+    };
+    let total = queue.len();
+    let mut remaining: HashMap<usize, (String, Vec<PeerId>)> = HashMap::new();
+    for item in queue {
+        remaining
+            .entry(item.shard)
+            .or_insert_with(|| (item.cid, Vec::new()))
+            .1
+            .push(item.peer_id);
+    }
+
+    // Second pass: read shards again only as the bytes already queued or in
+    // flight drop below `--max-inflight-bytes`.
+    let mut reader = source.shards()?.enumerate();
+    let mut queued_bytes = 0usize;
     let mut inflight: HashMap<OutboundRequestId, InflightStore> = HashMap::new();
-    let mut sent = 0usize;
     let mut acked_requests = 0usize;
-    let mut acked_by_cid: HashMap<String, usize> = HashMap::new();
-    let max_age_ms = args.max_response_age_secs.saturating_mul(1000);
 
-    while acked_requests < queue.len() {
-        while inflight.len() < args.concurrency && sent < queue.len() {
-            let item = &queue[sent];
+    while acked_requests < total {
+        while queued_bytes == 0 || queued_bytes < args.max_inflight_bytes {
+            let Some((index, shard)) = reader.next() else {
+                break;
+            };
+            let Some((cid, peers)) = remaining.remove(&index) else {
+                continue;
+            };
+            let shard = shard?;
+            if shard.cid != cid {
+                return Err(anyhow!("prepared file changed while storing: shard {index} is no longer {cid}"));
+            }
+            let data = shard.bytes()?;
+            for peer_id in peers {
+                queued_bytes += data.len();
+                tuner.push(
+                    peer_id,
+                    InflightStore {
+                        dispatch: StoreDispatch {
+                            request: ChunkCommand::Store(StoreChunkRequest {
+                                cid: cid.clone(),
+                                data: data.clone(),
+                            }),
+                            cid: cid.clone(),
+                            len: data.len(),
+                            peer_id,
+                        },
+                        attempt: 0,
+                        busy_replies: 0,
+                        started: Instant::now(),
+                    },
+                );
+            }
+        }
+        if queued_bytes == 0 {
+            return Err(anyhow!(
+                "prepared file changed while storing: {} shard stores never read",
+                total - acked_requests
+            ));
+        }
+
+        while let Some(mut state) = tuner.next() {
             let request_id = swarm
                 .behaviour_mut()
                 .chunk
-                .send_request(&item.peer_id, item.request.clone());
-            inflight.insert(
-                request_id,
-                InflightStore {
-                    dispatch: item.clone(),
-                    attempt: 0,
-                    started: Instant::now(),
-                },
-            );
-            sent += 1;
+                .send_request(&state.dispatch.peer_id, state.dispatch.request.clone());
+            state.started = Instant::now();
+            inflight.insert(request_id, state);
         }
 
-        match swarm.select_next_some().await {
+        let event = match tuner.resume_in() {
+            Some(wait) => match tokio::time::timeout(wait, swarm.select_next_some()).await {
+                Ok(event) => event,
+                Err(_) => continue,
+            },
+            None => swarm.select_next_some().await,
+        };
+        match event {
             SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::Message { 
                 message: RequestResponseMessage::Response { request_id, response },
                 ..
@@ -1076,9 +2342,48 @@ async fn run_store_prepared(args: StorePreparedArgs) -> Result<()> {
                                     state.dispatch.cid
                                 ));
                             }
+                            tuner.completed(
+                                state.dispatch.peer_id,
+                                state.started.elapsed(),
+                                state.dispatch.len,
+                            );
+                            receipts.record_store(
+                                state.dispatch.peer_id,
+                                &state.dispatch.cid,
+                                state.dispatch.len,
+                                &store_resp,
+                            );
+                            queued_bytes -= state.dispatch.len;
                             *acked_by_cid.entry(state.dispatch.cid).or_insert(0) += 1;
                             acked_requests += 1;
                         }
+                        ChunkReply::Busy(busy) => {
+                            let mut state = state;
+                            state.busy_replies += 1;
+                            if state.busy_replies > tuning::MAX_BUSY_REPLIES {
+                                return Err(anyhow!(
+                                    "peer {} stayed busy for cid={}",
+                                    state.dispatch.peer_id,
+                                    state.dispatch.cid
+                                ));
+                            }
+                            tuner.busy(state.dispatch.peer_id, state, busy.retry_after_ms);
+                        }
+                        ChunkReply::Rejected(rejected) => {
+                            return Err(anyhow!(
+                                "peer {} rejected store for cid={}: {}",
+                                state.dispatch.peer_id,
+                                state.dispatch.cid,
+                                rejected.error
+                            ))
+                        }
+                        ChunkReply::Maintenance(_) => {
+                            return Err(anyhow!(
+                                "peer {} is in maintenance and refused store for cid={}",
+                                state.dispatch.peer_id,
+                                state.dispatch.cid
+                            ))
+                        }
                         _ => {
                             return Err(anyhow!(
                                 "unexpected response type for store request"
@@ -1093,6 +2398,7 @@ async fn run_store_prepared(args: StorePreparedArgs) -> Result<()> {
                 if let Some(mut state) = inflight.remove(&request_id) {
                     if state.attempt < 3 {
                         state.attempt += 1;
+                        tuner.retried(state.dispatch.peer_id);
                         let retry_id = swarm.behaviour_mut().chunk.send_request(
                             &state.dispatch.peer_id,
                             state.dispatch.request.clone(),
@@ -1117,8 +2423,10 @@ async fn run_store_prepared(args: StorePreparedArgs) -> Result<()> {
             _ => {}
         }
     }
+    let concurrency = tuner.summary();
+    print_concurrency("store-prepared", &concurrency);
 
-    for ms in &manifest_shards {
+    for ms in builder.shards() {
         let got = acked_by_cid.get(&ms.cid).copied().unwrap_or(0);
         if got < ms.peers.len() {
             return Err(anyhow!(
@@ -1130,27 +2438,9 @@ async fn run_store_prepared(args: StorePreparedArgs) -> Result<()> {
         }
     }
 
-    // Always recompute root from shard layout so prepared uploads can come
-    // from different client implementations without sharing root logic.
-    let manifest_root = {
-        let template_shards: Vec<Shard> = manifest_shards
-            .iter()
-            .map(manifest_shard_to_template)
-            .collect();
-        manifest_root_from_shards(&template_shards)
-    };
-
-    let mut manifest = UploadManifest {
-        version: "2.2.0".to_string(),
-        salt: prepared.salt,
-        manifest_root,
-        total_bytes: prepared.total_bytes,
-        chunk_count: prepared.chunk_count,
-        shards: manifest_shards,
-        manifest_hash: String::new(),
-        manifest_auth_tag: String::new(),
-    };
-    manifest.manifest_hash = compute_manifest_hash(&manifest)?;
+    // The builder recomputes the root from the shard layout, so prepared
+    // uploads can come from other client implementations.
+    let manifest = builder.gateways(gateway_urls).build()?;
     verify_manifest_without_password(&manifest)?;
 
     let manifest_bytes = serde_json::to_vec_pretty(&manifest)?;
@@ -1161,7 +2451,21 @@ async fn run_store_prepared(args: StorePreparedArgs) -> Result<()> {
             MAX_MANIFEST_BYTES
         ));
     }
-    fs::write(&args.manifest_out, manifest_bytes)?;
+    fs::write(&args.manifest_out, &manifest_bytes)?;
+    let receipts_out = receipts::receipts_path(&args.manifest_out);
+    receipts::write(&receipts_out, &receipts.finish(&manifest.manifest_root))?;
+    let registrations = match &gateway_token {
+        Some(token) => {
+            gateways::register_with_quorum(
+                &manifest.gateways,
+                token,
+                &manifest_bytes,
+                args.gateway_quorum,
+            )
+            .await?
+        }
+        None => Vec::new(),
+    };
 
     println!(
         "store-prepared complete shards={} peers={} manifest={}",
@@ -1178,7 +2482,11 @@ async fn run_store_prepared(args: StorePreparedArgs) -> Result<()> {
                 "manifest_path": args.manifest_out,
                 "shards": manifest.shards.len(),
                 "peers": unique_peers.len(),
-                "total_bytes": manifest.total_bytes
+                "total_bytes": manifest.total_bytes,
+                "gateways": registrations,
+                "dedup": dedup_summary,
+                "receipts_path": receipts_out,
+                "concurrency": concurrency
             }),
         )?;
     }
@@ -1200,13 +2508,7 @@ async fn run_retrieve_raw(args: RetrieveRawArgs) -> Result<()> {
     let max_age_ms = args.max_response_age_secs.saturating_mul(1000);
 
     let all_peer_set = if args.peer.is_empty() {
-        let mut set = HashSet::<String>::new();
-        for ms in &manifest.shards {
-            for p in &ms.peers {
-                set.insert(p.clone());
-            }
-        }
-        set.into_iter().collect::<Vec<_>>()
+        manifest_dial_addrs(&manifest)
     } else {
         dedup_peers(&args.peer)
     };
@@ -1286,13 +2588,13 @@ async fn run_retrieve_raw(args: RetrieveRawArgs) -> Result<()> {
                                         chrono::Utc::now().timestamp_millis() as u64,
                                         max_age_ms,
                                     )
-                                    && sha256_hex(&reply.data) == state.cid
+                                    && shard_cid_matches(&state.cid, &reply.data)
                                 {
                                     if let Some(template) = manifest
                                         .shards
                                         .iter()
                                         .find(|x| x.cid == state.cid)
-                                        .map(manifest_shard_to_template)
+                                        .map(ManifestShard::to_template)
                                     {
                                         let mut shard = template;
                                         shard.bytes = reply.data;
@@ -1347,28 +2649,7 @@ async fn run_retrieve_raw(args: RetrieveRawArgs) -> Result<()> {
         ));
     }
 
-    let mut recovered_shards: Vec<Shard> = completed.into_values().collect();
-    recovered_shards.sort_by_key(|s| (s.chunk_index, s.shard_index));
-
-    let raw_bundle = RawRetrieveBundle {
-        version: "raw-v1".to_string(),
-        salt: manifest.salt.clone(),
-        manifest_root: manifest.manifest_root.clone(),
-        total_bytes: manifest.total_bytes,
-        chunk_count: manifest.chunk_count,
-        shards: recovered_shards
-            .iter()
-            .map(|s| RawRetrieveShard {
-                chunk_index: s.chunk_index,
-                shard_index: s.shard_index,
-                cid: s.cid.clone(),
-                payload_len: s.payload_len,
-                data_shards: s.data_shards,
-                parity_shards: s.parity_shards,
-                bytes_b64: encode_b64(&s.bytes),
-            })
-            .collect(),
-    };
+    let raw_bundle = raw_bundle_of(&manifest, completed.values());
     fs::write(&args.raw_out, serde_json::to_vec_pretty(&raw_bundle)?)?;
 
     println!(
@@ -1392,7 +2673,19 @@ async fn run_retrieve_raw(args: RetrieveRawArgs) -> Result<()> {
     Ok(())
 }
 
-async fn run_audit(args: AuditArgs) -> Result<()> {
+async fn run_audit(args: AuditArgs, cancel: CancellationToken) -> Result<()> {
+    let manifest = args.manifest.clone();
+    let catalog = args.catalog.clone();
+    let result = audit_manifest(args, cancel).await;
+    // A cancelled audit says nothing about the manifest.
+    if !result.as_ref().is_err_and(|e| e.is::<cancel::Cancelled>()) {
+        catalog::note_audit(catalog.as_deref(), &manifest, &result);
+    }
+    result
+}
+
+async fn audit_manifest(args: AuditArgs, cancel: CancellationToken) -> Result<()> {
+    let unlock = args.password.unlock()?;
     let manifest_bytes = fs::read(&args.manifest)?;
     if manifest_bytes.len() > MAX_MANIFEST_BYTES {
         return Err(anyhow!(
@@ -1402,18 +2695,12 @@ async fn run_audit(args: AuditArgs) -> Result<()> {
         ));
     }
     let manifest: UploadManifest = serde_json::from_slice(&manifest_bytes)?;
-    verify_manifest(&manifest, &args.password)?;
+    verify_manifest(&manifest, &unlock)?;
     let max_age_ms = args.max_response_age_secs.saturating_mul(1000);
 
     let allowed = dedup_peers(&args.peer);
     let peer_pool: Vec<String> = if allowed.is_empty() {
-        let mut set = HashSet::new();
-        for ms in &manifest.shards {
-            for p in &ms.peers {
-                set.insert(p.clone());
-            }
-        }
-        set.into_iter().collect()
+        manifest_dial_addrs(&manifest)
     } else {
         allowed
     };
@@ -1421,7 +2708,7 @@ async fn run_audit(args: AuditArgs) -> Result<()> {
         return Err(anyhow!("no peers available for audit"));
     }
 
-    let (mut swarm, _) = make_client_swarm(&peer_pool)?;
+    let mut swarm = swarm_pool::checkout(&peer_pool).await?;
     let warm_connected = wait_for_peer_connections(
         &mut swarm,
         &peer_pool,
@@ -1469,8 +2756,14 @@ async fn run_audit(args: AuditArgs) -> Result<()> {
     let mut inflight: HashMap<OutboundRequestId, AuditAttemptState> = HashMap::new();
     let mut passed = 0usize;
 
+    let progress = Progress::start(args.tui)?;
+    progress.emit(ProgressEvent::Begin {
+        op: "audit",
+        total: sample_count,
+    });
+
     while passed < sample_count {
-        while inflight.len() < args.concurrency {
+        while inflight.len() < args.concurrency && !cancel.is_cancelled() {
             let Some(state) = pending.pop_front() else {
                 break;
             };
@@ -1482,16 +2775,26 @@ async fn run_audit(args: AuditArgs) -> Result<()> {
                     cid: state.cid.clone(),
                     challenge_hex: state.challenge_hex.clone(),
                     nonce_hex: state.nonce_hex.clone(),
+                    issued_at_ms: chrono::Utc::now().timestamp_millis() as u64,
                 }),
             );
+            progress.emit(ProgressEvent::Sent { peer: peer_id });
             inflight.insert(request_id, state);
         }
 
         if inflight.is_empty() {
             break;
         }
+        progress.emit(ProgressEvent::Queue {
+            pending: pending.len(),
+            inflight: inflight.len(),
+        });
 
-        match swarm.select_next_some().await {
+        let event = tokio::select! {
+            event = swarm.select_next_some() => event,
+            _ = cancel.cancelled(), if !cancel.is_cancelled() => continue,
+        };
+        match event {
             SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::Message { 
                 message: RequestResponseMessage::Response { request_id, response },
                 ..
@@ -1516,12 +2819,17 @@ async fn run_audit(args: AuditArgs) -> Result<()> {
                                 && resp.response_hash == state.expected_token;
                             if ok {
                                 passed += 1;
-                                println!(
+                                progress.emit(ProgressEvent::Completed {
+                                    peer: peer_id,
+                                    bytes: 0,
+                                });
+                                progress.log(format!(
                                     "audit cid={} passed attempt={}",
                                     state.cid,
                                     state.attempt + 1
-                                );
+                                ));
                             } else {
+                                progress.emit(ProgressEvent::Retry { peer: peer_id });
                                 state.attempt += 1;
                                 if state.attempt < state.peers.len() {
                                     state.nonce_hex = random_nonce_hex();
@@ -1542,8 +2850,9 @@ async fn run_audit(args: AuditArgs) -> Result<()> {
                     }
                 }
             }
-            SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::OutboundFailure { request_id, .. })) => {
+            SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::OutboundFailure { request_id, peer, .. })) => {
                 if let Some(mut state) = inflight.remove(&request_id) {
+                    progress.emit(ProgressEvent::Retry { peer });
                     state.attempt += 1;
                     if state.attempt < state.peers.len() {
                         state.nonce_hex = random_nonce_hex();
@@ -1558,6 +2867,19 @@ async fn run_audit(args: AuditArgs) -> Result<()> {
 
     }
 
+    progress.finish();
+    if cancel.is_cancelled() {
+        return Err(cancel::finish(
+            args.report_out.as_deref(),
+            "audit",
+            serde_json::json!({
+                "manifest_path": args.manifest,
+                "sampled": sample_count,
+                "passed": passed
+            }),
+        )?);
+    }
+
     if passed != sample_count {
         return Err(anyhow!(
             "audit incomplete passed={} sampled={}",
@@ -1583,6 +2905,7 @@ async fn run_audit(args: AuditArgs) -> Result<()> {
 }
 
 async fn run_validate(args: ValidateArgs) -> Result<()> {
+    let unlock = args.password.unlock()?;
     let manifest_bytes = fs::read(&args.manifest)?;
     if manifest_bytes.len() > MAX_MANIFEST_BYTES {
         return Err(anyhow!(
@@ -1592,7 +2915,8 @@ async fn run_validate(args: ValidateArgs) -> Result<()> {
         ));
     }
     let manifest: UploadManifest = serde_json::from_slice(&manifest_bytes)?;
-    verify_manifest(&manifest, &args.password)?;
+    verify_manifest(&manifest, &unlock)?;
+    catalog::note_validated(args.catalog.as_deref(), &args.manifest, &manifest);
     println!(
         "manifest valid shards={} chunks={} bytes={}",
         manifest.shards.len(),
@@ -1602,64 +2926,698 @@ async fn run_validate(args: ValidateArgs) -> Result<()> {
     if let Some(path) = &args.report_out {
         write_report(
             path,
-            "validate",
-            true,
+            "validate",
+            true,
+            serde_json::json!({
+                "manifest_path": args.manifest,
+                "shards": manifest.shards.len(),
+                "chunk_count": manifest.chunk_count,
+                "total_bytes": manifest.total_bytes
+            }),
+        )?;
+    }
+    Ok(())
+}
+
+fn run_verify_receipts(args: VerifyReceiptsArgs) -> Result<()> {
+    let manifest_bytes = fs::read(&args.manifest)?;
+    if manifest_bytes.len() > MAX_MANIFEST_BYTES {
+        return Err(anyhow!(
+            "manifest too large: {} bytes > {} bytes",
+            manifest_bytes.len(),
+            MAX_MANIFEST_BYTES
+        ));
+    }
+    let manifest: UploadManifest = serde_json::from_slice(&manifest_bytes)?;
+    verify_manifest_without_password(&manifest)?;
+    let receipts_path = args
+        .receipts
+        .clone()
+        .unwrap_or_else(|| receipts::receipts_path(&args.manifest));
+    let bundle = receipts::read(&receipts_path)?;
+    let report = receipts::verify(&bundle, &manifest)?;
+    for line in &report.invalid {
+        eprintln!("invalid receipt {line}");
+    }
+    for line in &report.missing {
+        eprintln!("missing receipt {line}");
+    }
+    println!(
+        "receipts peers={} stores={} inventories={} placements={} invalid={} missing={}",
+        report.peers,
+        report.store_receipts,
+        report.inventory_receipts,
+        report.placements,
+        report.invalid.len(),
+        report.missing.len()
+    );
+    if let Some(path) = &args.report_out {
+        write_report(
+            path,
+            "verify-receipts",
+            report.ok(),
+            serde_json::json!({
+                "manifest_path": args.manifest,
+                "receipts_path": receipts_path,
+                "receipts": report
+            }),
+        )?;
+    }
+    if !report.ok() {
+        return Err(anyhow!(
+            "receipt bundle does not cover the manifest: {} invalid, {} missing",
+            report.invalid.len(),
+            report.missing.len()
+        ));
+    }
+    Ok(())
+}
+
+async fn run_reproduce(args: ReproduceArgs) -> Result<()> {
+    // Deterministic uploads never have recipients, so only a password fits.
+    let password = args.password.resolve()?;
+    let unlock = x25519::Unlock::Password(password.clone());
+    let manifest_bytes = fs::read(&args.manifest)?;
+    if manifest_bytes.len() > MAX_MANIFEST_BYTES {
+        return Err(anyhow!(
+            "manifest too large: {} bytes > {} bytes",
+            manifest_bytes.len(),
+            MAX_MANIFEST_BYTES
+        ));
+    }
+    let manifest: UploadManifest = serde_json::from_slice(&manifest_bytes)?;
+    verify_manifest(&manifest, &unlock)?;
+    if !manifest.deterministic {
+        return Err(anyhow!(
+            "manifest was not uploaded with --deterministic; its root cannot be reproduced"
+        ));
+    }
+
+    // The manifest does not record the chunk size, but the first chunk is
+    // always a full one (or the whole file): a hole of that length, or
+    // shards of a 12-byte nonce + 16-byte tag more.
+    let chunk_size = match manifest.holes.iter().find(|h| h.chunk_index == 0) {
+        Some(hole) => hole.len,
+        None => {
+            let first = manifest
+                .shards
+                .iter()
+                .find(|s| s.chunk_index == 0)
+                .ok_or_else(|| anyhow!("manifest has no first chunk"))?;
+            first
+                .payload_len
+                .checked_sub(28)
+                .filter(|n| *n > 0)
+                .ok_or_else(|| anyhow!("manifest shard {} has an invalid payload_len", first.cid))?
+        }
+    };
+    // An upload of nothing but holes has no shard layout to follow.
+    let defaults = PipelineConfig::default();
+    let cfg = PipelineConfig {
+        chunk_size,
+        data_shards: manifest.shards.first().map_or(defaults.data_shards, |s| s.data_shards),
+        parity_shards: manifest.shards.first().map_or(defaults.parity_shards, |s| s.parity_shards),
+        cid_format: manifest_cid_format(&manifest.version)?,
+        hash_algorithm: manifest.hash_algorithm,
+        deterministic_salt: Some(manifest.salt.clone()),
+        holes: !manifest.holes.is_empty(),
+        ..defaults
+    };
+    let data = fs::read(&args.file)?;
+    let output = process_bytes(&data, &password, cfg)?;
+
+    let expected: HashMap<(usize, usize), &str> = manifest
+        .shards
+        .iter()
+        .map(|ms| ((ms.chunk_index, ms.shard_index), ms.cid.as_str()))
+        .collect();
+    let mismatched = output
+        .shards
+        .iter()
+        .filter(|s| expected.get(&(s.chunk_index, s.shard_index)) != Some(&s.cid.as_str()))
+        .count();
+    let ok = output.manifest_root == manifest.manifest_root
+        && output.total_bytes == manifest.total_bytes
+        && output.shards.len() == manifest.shards.len()
+        && output.holes == manifest.holes
+        && mismatched == 0;
+
+    println!(
+        "reproduce {} root={} reproduced_root={} mismatched_shards={}",
+        if ok { "ok" } else { "MISMATCH" },
+        manifest.manifest_root,
+        output.manifest_root,
+        mismatched
+    );
+    if let Some(path) = &args.report_out {
+        write_report(
+            path,
+            "reproduce",
+            ok,
+            serde_json::json!({
+                "manifest_path": args.manifest,
+                "file_path": args.file,
+                "manifest_root": manifest.manifest_root,
+                "reproduced_root": output.manifest_root,
+                "mismatched_shards": mismatched
+            }),
+        )?;
+    }
+    if !ok {
+        return Err(anyhow!(
+            "file does not reproduce manifest root {}",
+            manifest.manifest_root
+        ));
+    }
+    Ok(())
+}
+
+async fn run_migrate_manifest(args: MigrateManifestArgs) -> Result<()> {
+    let unlock = args.password.unlock()?;
+    let bytes = fs::read(&args.input)?;
+    if bytes.len() > MAX_MANIFEST_BYTES {
+        return Err(anyhow!(
+            "manifest too large: {} bytes > {} bytes",
+            bytes.len(),
+            MAX_MANIFEST_BYTES
+        ));
+    }
+
+    let mut manifest = if let Ok(m) = serde_json::from_slice::<UploadManifest>(&bytes) {
+        m
+    } else {
+        let legacy: LegacyUploadManifest = serde_json::from_slice(&bytes)?;
+        UploadManifest {
+            version: MANIFEST_VERSION.to_string(),
+            salt: legacy.salt,
+            manifest_root: legacy.manifest_root,
+            total_bytes: legacy.total_bytes,
+            chunk_count: legacy.chunk_count,
+            shards: legacy.shards,
+            gateways: Vec::new(),
+            deterministic: false,
+            hash_algorithm: HashAlgorithm::Sha256,
+            recipients: Vec::new(),
+            outer_code: None,
+            peer_addrs: BTreeMap::new(),
+            holes: Vec::new(),
+            manifest_hash: legacy.manifest_hash,
+            manifest_auth_tag: String::new(),
+        }
+    };
+    manifest.separate_peer_addrs();
+
+    // Older versions only ever wrote hex CIDs; the shards say which
+    // current version applies.
+    let cid_format = manifest
+        .shards
+        .first()
+        .and_then(|s| CidFormat::of(&s.cid))
+        .unwrap_or_default();
+    manifest.version = manifest_version(cid_format).to_string();
+    unlock.reseal(&mut manifest)?;
+    verify_manifest(&manifest, &unlock)?;
+
+    let out = serde_json::to_vec_pretty(&manifest)?;
+    fs::write(&args.output, out)?;
+    println!(
+        "manifest migrated input={} output={}",
+        args.input, args.output
+    );
+    Ok(())
+}
+
+/// A token as printed by `share`, or a gateway link carrying the share key
+/// in its fragment.
+async fn resolve_share_token(value: &str) -> Result<ShareToken> {
+    if !(value.starts_with("https://") || value.starts_with("http://")) {
+        return ShareToken::parse(value);
+    }
+    let (link, share_key) = value
+        .split_once('#')
+        .ok_or_else(|| anyhow!("share link has no #share-key fragment"))?;
+    ShareToken::parse(&gateways::fetch_share(link).await?)?.with_share_key(share_key)
+}
+
+async fn run_share(args: ShareArgs) -> Result<()> {
+    let unlock = args.password.unlock()?;
+    let manifest_bytes = fs::read(&args.manifest)?;
+    if manifest_bytes.len() > MAX_MANIFEST_BYTES {
+        return Err(anyhow!(
+            "manifest too large: {} bytes > {} bytes",
+            manifest_bytes.len(),
+            MAX_MANIFEST_BYTES
+        ));
+    }
+    let manifest: UploadManifest = serde_json::from_slice(&manifest_bytes)?;
+    verify_manifest(&manifest, &unlock)?;
+    if args.expires_in_secs == 0 {
+        return Err(anyhow!("--expires-in-secs must be greater than 0"));
+    }
+
+    let key = unlock.chunk_key(&manifest)?;
+    let expires_at = (chrono::Utc::now().timestamp().max(0) as u64).saturating_add(args.expires_in_secs);
+    let token = ShareToken::create(
+        &key,
+        ShareClaims {
+            manifest_root: manifest.manifest_root.clone(),
+            scope: SCOPE_RETRIEVE.to_string(),
+            expires_at,
+        },
+    )?;
+    println!("share token={}", token.encode());
+
+    let gateway_urls = args.gateway.urls()?;
+    let link = if gateway_urls.is_empty() {
+        None
+    } else {
+        let bearer = args.gateway.require_token()?;
+        let link = gateways::publish_share(&gateway_urls, &bearer, &token.sealed()).await?;
+        let share_key = token.share_key().unwrap_or_default();
+        println!("share link={link}#{share_key}");
+        Some(link)
+    };
+    println!(
+        "share complete manifest_root={} expires_at={}",
+        manifest.manifest_root, expires_at
+    );
+    if let Some(path) = &args.report_out {
+        // The token itself stays out of reports.
+        write_report(
+            path,
+            "share",
+            true,
+            serde_json::json!({
+                "manifest_path": args.manifest,
+                "manifest_root": manifest.manifest_root,
+                "expires_at": expires_at,
+                "link": link
+            }),
+        )?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct PeerRebinding {
+    old: String,
+    new: String,
+    source: &'static str,
+}
+
+async fn run_rebind(args: RebindArgs) -> Result<()> {
+    let unlock = args.password.unlock()?;
+    let manifest_bytes = fs::read(&args.manifest)?;
+    if manifest_bytes.len() > MAX_MANIFEST_BYTES {
+        return Err(anyhow!(
+            "manifest too large: {} bytes > {} bytes",
+            manifest_bytes.len(),
+            MAX_MANIFEST_BYTES
+        ));
+    }
+    let mut manifest: UploadManifest = serde_json::from_slice(&manifest_bytes)?;
+    verify_manifest(&manifest, &unlock)?;
+    if args.peer_map.is_empty() && !args.auto {
+        return Err(anyhow!("rebind needs --peer-map old=new or --auto"));
+    }
+    // Rebinding works on each peer's latest address; the new ones go back
+    // into the address book ahead of the old.
+    manifest.join_peer_addrs();
+
+    let current: Vec<String> = dedup_peers(
+        &manifest
+            .shards
+            .iter()
+            .flat_map(|s| s.peers.iter().cloned())
+            .collect::<Vec<_>>(),
+    );
+    let mut rebindings = Vec::<PeerRebinding>::new();
+    for (old, new) in parse_peer_map(&args.peer_map)? {
+        let matched: Vec<&String> = current
+            .iter()
+            .filter(|addr| **addr == old || (!old.starts_with('/') && peer_identity_key(addr) == old))
+            .collect();
+        if matched.is_empty() {
+            return Err(anyhow!("--peer-map {old} matches no peer in the manifest"));
+        }
+        for addr in matched {
+            rebindings.push(PeerRebinding {
+                old: addr.clone(),
+                new: new.clone(),
+                source: "peer-map",
+            });
+        }
+    }
+
+    if args.auto {
+        let gateway_urls = gateways::merge_urls(&manifest.gateways, &args.gateway.gateways)?;
+        if gateway_urls.is_empty() {
+            return Err(anyhow!("--auto needs gateways in the manifest or --gateway"));
+        }
+        let token = args.gateway.require_token()?;
+        for addr in &current {
+            if rebindings.iter().any(|r| &r.old == addr) {
+                continue;
+            }
+            let peer_id = extract_peer_id(addr)?;
+            let found = gateways::locate_peer(&gateway_urls, &token, &peer_id.to_string()).await;
+            // The uploader only dials TCP, so other transports cannot replace it.
+            let Some(new) = found.into_iter().find(|a| is_tcp_peer_addr(a, &peer_id)) else {
+                eprintln!("rebind no tcp address found peer={peer_id}; keeping {addr}");
+                continue;
+            };
+            if &new != addr {
+                rebindings.push(PeerRebinding {
+                    old: addr.clone(),
+                    new,
+                    source: "gateway",
+                });
+            }
+        }
+    }
+
+    let map: HashMap<&str, &str> = rebindings
+        .iter()
+        .map(|r| (r.old.as_str(), r.new.as_str()))
+        .collect();
+    for shard in &mut manifest.shards {
+        let rebound: Vec<String> = shard
+            .peers
+            .iter()
+            .map(|p| map.get(p.as_str()).map_or_else(|| p.clone(), |n| n.to_string()))
+            .collect();
+        shard.peers = dedup_peers(&rebound);
+    }
+    manifest.separate_peer_addrs();
+    unlock.reseal(&mut manifest)?;
+    verify_manifest(&manifest, &unlock)?;
+
+    let output = args.output.as_deref().unwrap_or(&args.manifest);
+    fs::write(output, serde_json::to_vec_pretty(&manifest)?)?;
+    for r in &rebindings {
+        println!("rebind {} -> {} source={}", r.old, r.new, r.source);
+    }
+    println!(
+        "rebind complete peers={} rebound={} manifest={}",
+        current.len(),
+        rebindings.len(),
+        output
+    );
+    if let Some(path) = &args.report_out {
+        write_report(
+            path,
+            "rebind",
+            true,
+            serde_json::json!({
+                "manifest_path": output,
+                "peers": current.len(),
+                "rebindings": rebindings
+            }),
+        )?;
+    }
+    Ok(())
+}
+
+/// Parses `old=new` pairs. `new` must be a dialable multiaddr of the same
+/// peer as `old`: store receipts are signed by the PeerId, so only the
+/// address may change.
+fn parse_peer_map(items: &[String]) -> Result<Vec<(String, String)>> {
+    items
+        .iter()
+        .map(|item| {
+            let (old, new) = item
+                .split_once('=')
+                .ok_or_else(|| anyhow!("--peer-map must be old=new: {item}"))?;
+            let (old, new) = (old.trim(), new.trim());
+            validate_peer_multiaddr(new)?;
+            if peer_identity_key(old) != extract_peer_id(new)?.to_string() {
+                return Err(anyhow!("--peer-map {item} changes the peer id"));
+            }
+            Ok((old.to_string(), new.to_string()))
+        })
+        .collect()
+}
+
+fn is_tcp_peer_addr(addr: &str, peer_id: &PeerId) -> bool {
+    let Ok(ma) = addr.parse::<Multiaddr>() else {
+        return false;
+    };
+    ma.iter().any(|p| matches!(p, libp2p::multiaddr::Protocol::Tcp(_)))
+        && extract_peer_id(addr).ok().as_ref() == Some(peer_id)
+}
+
+/// Asks the gateways where the peers in `peers` that never connected are
+/// now, dials what they find and waits for it. Returns the addresses that
+/// reached a peer.
+async fn relocate_peers(
+    swarm: &mut Swarm<UploaderBehaviour>,
+    peers: &[String],
+    connected: &mut HashSet<PeerId>,
+    gateway_urls: &[String],
+    token: &str,
+) -> Result<Vec<String>> {
+    let mut missing: Vec<PeerId> = peers
+        .iter()
+        .filter_map(|p| extract_peer_id(p).ok())
+        .filter(|id| !connected.contains(id))
+        .collect();
+    missing.sort();
+    missing.dedup();
+    let lookups = missing.iter().map(|peer_id| async move {
+        let found = gateways::locate_peer(gateway_urls, token, &peer_id.to_string()).await;
+        found
+            .into_iter()
+            .filter(|a| is_tcp_peer_addr(a, peer_id) && !peers.contains(a))
+            .collect::<Vec<_>>()
+    });
+    let found: Vec<String> = futures::future::join_all(lookups).await.concat();
+    if found.is_empty() {
+        return Ok(Vec::new());
+    }
+    for addr in &found {
+        let ma: Multiaddr = addr.parse()?;
+        swarm.add_peer_address(extract_peer_id(addr)?, ma.clone());
+        let _ = swarm.dial(ma);
+    }
+    let reached =
+        wait_for_peer_connections(swarm, &found, Duration::from_secs(PEER_CONNECT_WARMUP_SECS)).await?;
+    connected.extend(reached.iter().copied());
+    Ok(found
+        .into_iter()
+        .filter(|a| extract_peer_id(a).is_ok_and(|id| reached.contains(&id)))
+        .collect())
+}
+
+async fn run_refresh_audits(args: RefreshAuditsArgs) -> Result<()> {
+    if args.rounds == 0 || args.rounds > MAX_AUDIT_ROUNDS {
+        return Err(anyhow!("--rounds must be between 1 and {}", MAX_AUDIT_ROUNDS));
+    }
+    let unlock = args.password.unlock()?;
+    let manifest_bytes = fs::read(&args.manifest)?;
+    if manifest_bytes.len() > MAX_MANIFEST_BYTES {
+        return Err(anyhow!(
+            "manifest too large: {} bytes > {} bytes",
+            manifest_bytes.len(),
+            MAX_MANIFEST_BYTES
+        ));
+    }
+    let mut manifest: UploadManifest = serde_json::from_slice(&manifest_bytes)?;
+    verify_manifest(&manifest, &unlock)?;
+    let max_age_ms = args.max_response_age_secs.saturating_mul(1000);
+
+    let allowed = dedup_peers(&args.peer);
+    let candidates: Vec<Vec<String>> = manifest
+        .shards
+        .iter()
+        .map(|ms| {
+            if allowed.is_empty() {
+                dedup_peers(&ms.peers)
+            } else {
+                intersect_peers(&ms.peers, &allowed)
+            }
+        })
+        .collect();
+    let peer_pool = dedup_peers(&candidates.concat());
+    if peer_pool.is_empty() {
+        return Err(anyhow!("no peers available to fetch shards from"));
+    }
+    let dial = if allowed.is_empty() {
+        dedup_peers(&peer_pool.iter().flat_map(|p| manifest.dial_addrs(p)).collect::<Vec<_>>())
+    } else {
+        allowed.clone()
+    };
+
+    let (mut swarm, _) = make_client_swarm(&dial)?;
+    let warm_connected = wait_for_peer_connections(
+        &mut swarm,
+        &dial,
+        Duration::from_secs(PEER_CONNECT_WARMUP_SECS),
+    )
+    .await?;
+    if warm_connected.is_empty() {
+        return Err(anyhow!("unable to connect to any shard peer during warmup"));
+    }
+
+    // (shard index, attempt); an attempt indexes that shard's candidates.
+    let mut pending: VecDeque<(usize, usize)> = (0..manifest.shards.len()).map(|i| (i, 0)).collect();
+    let mut inflight: HashMap<OutboundRequestId, (usize, usize)> = HashMap::new();
+    let mut refreshed = 0usize;
+    let mut unreachable = Vec::<String>::new();
+    loop {
+        while inflight.len() < args.concurrency.max(1) {
+            let Some((index, attempt)) = pending.pop_front() else {
+                break;
+            };
+            let Some(peer) = candidates[index].get(attempt) else {
+                eprintln!("refresh-audits no peer returned cid={}; keeping its vectors", manifest.shards[index].cid);
+                unreachable.push(manifest.shards[index].cid.clone());
+                continue;
+            };
+            let request_id = swarm.behaviour_mut().chunk.send_request(
+                &extract_peer_id(peer)?,
+                ChunkCommand::Retrieve(RetrieveChunkRequest {
+                    cid: manifest.shards[index].cid.clone(),
+                }),
+            );
+            inflight.insert(request_id, (index, attempt));
+        }
+        if inflight.is_empty() {
+            break;
+        }
+
+        let (request_id, response) = match swarm.select_next_some().await {
+            SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::Message {
+                message: RequestResponseMessage::Response { request_id, response },
+                ..
+            })) => (request_id, Some(response)),
+            SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::OutboundFailure {
+                request_id,
+                ..
+            })) => (request_id, None),
+            _ => continue,
+        };
+        let Some((index, attempt)) = inflight.remove(&request_id) else {
+            continue;
+        };
+        let shard = &mut manifest.shards[index];
+        let peer_id = extract_peer_id(&candidates[index][attempt])?;
+        match response {
+            Some(ChunkReply::Retrieve(resp))
+                if resp.found
+                    && resp.verify_proof(&peer_id, &shard.cid)
+                    && resp.is_fresh(chrono::Utc::now().timestamp_millis() as u64, max_age_ms)
+                    && shard_cid_matches(&shard.cid, &resp.data) =>
+            {
+                let (challenges, tokens) = build_audit_vectors(&resp.data, args.rounds);
+                shard.audit_challenges = challenges;
+                shard.audit_tokens = tokens;
+                refreshed += 1;
+            }
+            _ => pending.push_back((index, attempt + 1)),
+        }
+    }
+
+    if refreshed > 0 {
+        unlock.reseal(&mut manifest)?;
+        verify_manifest(&manifest, &unlock)?;
+    }
+    let output = args.output.as_deref().unwrap_or(&args.manifest);
+    fs::write(output, serde_json::to_vec_pretty(&manifest)?)?;
+    println!(
+        "refresh-audits complete shards={} refreshed={} unreachable={} rounds={} manifest={}",
+        manifest.shards.len(),
+        refreshed,
+        unreachable.len(),
+        args.rounds,
+        output
+    );
+    if let Some(path) = &args.report_out {
+        write_report(
+            path,
+            "refresh-audits",
+            unreachable.is_empty(),
             serde_json::json!({
-                "manifest_path": args.manifest,
+                "manifest_path": output,
                 "shards": manifest.shards.len(),
-                "chunk_count": manifest.chunk_count,
-                "total_bytes": manifest.total_bytes
+                "refreshed": refreshed,
+                "rounds": args.rounds,
+                "unreachable": unreachable
             }),
         )?;
     }
-    Ok(())
-}
-
-async fn run_migrate_manifest(args: MigrateManifestArgs) -> Result<()> {
-    let bytes = fs::read(&args.input)?;
-    if bytes.len() > MAX_MANIFEST_BYTES {
+    if !unreachable.is_empty() {
         return Err(anyhow!(
-            "manifest too large: {} bytes > {} bytes",
-            bytes.len(),
-            MAX_MANIFEST_BYTES
+            "{} shards could not be retrieved and keep their old audit vectors",
+            unreachable.len()
         ));
     }
+    Ok(())
+}
 
-    let mut manifest = if let Ok(m) = serde_json::from_slice::<UploadManifest>(&bytes) {
-        m
-    } else {
-        let legacy: LegacyUploadManifest = serde_json::from_slice(&bytes)?;
-        UploadManifest {
-            version: "2.2.0".to_string(),
-            salt: legacy.salt,
-            manifest_root: legacy.manifest_root,
-            total_bytes: legacy.total_bytes,
-            chunk_count: legacy.chunk_count,
-            shards: legacy.shards,
-            manifest_hash: legacy.manifest_hash,
-            manifest_auth_tag: String::new(),
-        }
+async fn run_autopilot(args: AutopilotArgs) -> Result<()> {
+    if args.daemon.daemon {
+        return daemon::run_autopilot_daemon(args).await;
+    }
+    let unlock = args.password.unlock()?;
+    let (policies, plan) = match &args.approve {
+        Some(path) => (Vec::new(), Some(load_approved_plan(path, &args.manifest, &unlock)?)),
+        None => (policies::PolicySource::from_args(&args)?.load().await?, None),
     };
+    let report = autopilot_manifest(&args.manifest, &unlock, &policies, plan.as_ref(), &args).await?;
+    fs::write(&args.report_out, serde_json::to_vec_pretty(&report)?)?;
 
-    if manifest.version != "2.2.0" {
-        manifest.version = "2.2.0".to_string();
+    if report.dry_run {
+        println!(
+            "autopilot plan shards_planned={} unrepairable={} report={}",
+            report.summary.shards_planned, report.summary.shards_failed, args.report_out
+        );
+    } else {
+        println!(
+            "autopilot complete repaired={} failed={} report={}",
+            report.summary.shards_repaired, report.summary.shards_failed, args.report_out
+        );
     }
-    manifest.manifest_hash = compute_manifest_hash(&manifest)?;
-    manifest.manifest_auth_tag =
-        derive_manifest_auth_tag(&args.password, &manifest.salt, &manifest.manifest_hash);
-    verify_manifest(&manifest, &args.password)?;
-
-    let out = serde_json::to_vec_pretty(&manifest)?;
-    fs::write(&args.output, out)?;
-    println!(
-        "manifest migrated input={} output={}",
-        args.input, args.output
-    );
     Ok(())
 }
 
-async fn run_autopilot(args: AutopilotArgs) -> Result<()> {
-    let manifest_bytes = fs::read(&args.manifest)?;
+/// Reads a `--dry-run` report for `--approve`, refusing one that is not a
+/// plan or was not signed for this manifest.
+fn load_approved_plan(path: &str, manifest_path: &str, unlock: &x25519::Unlock) -> Result<ApprovedPlan> {
+    let report: ActionReport = serde_json::from_slice(&fs::read(path)?)?;
+    if report.operation != "autopilot" || !report.dry_run {
+        return Err(anyhow!("{path} is not an autopilot --dry-run plan"));
+    }
+    let manifest: UploadManifest = serde_json::from_slice(&fs::read(manifest_path)?)?;
+    let expected = sign_action_report(&report, &unlock.report_secret(), &manifest.salt)?;
+    if expected != report.signature {
+        return Err(anyhow!("plan signature mismatch; {path} was altered or made for another manifest"));
+    }
+    let mut targets: HashMap<String, Vec<String>> = HashMap::new();
+    for action in report.actions.into_iter().filter(|a| a.reason == PLANNED) {
+        targets.entry(action.cid).or_default().push(action.to_peer);
+    }
+    Ok(ApprovedPlan {
+        manifest_hash: report.manifest_hash,
+        quarantined: report.quarantined_peers.into_iter().collect(),
+        quarantine_reasons: report.quarantine_reasons,
+        targets,
+    })
+}
+
+/// One repair pass over a single manifest: re-replicates shards held by
+/// quarantined or missing peers (regenerating them from their chunk's other
+/// shards when no replica is left), rewrites the manifest in place and
+/// returns the signed action report. With `--dry-run` it stops after
+/// choosing targets and returns the plan; with an approved `plan` it uses
+/// the plan's quarantine and targets instead of the policies.
+async fn autopilot_manifest(
+    manifest_path: &str,
+    unlock: &x25519::Unlock,
+    policies: &[SentinelPolicyRow],
+    plan: Option<&ApprovedPlan>,
+    args: &AutopilotArgs,
+) -> Result<ActionReport> {
+    let manifest_bytes = fs::read(manifest_path)?;
     if manifest_bytes.len() > MAX_MANIFEST_BYTES {
         return Err(anyhow!(
             "manifest too large: {} bytes > {} bytes",
@@ -1668,7 +3626,15 @@ async fn run_autopilot(args: AutopilotArgs) -> Result<()> {
         ));
     }
     let mut manifest: UploadManifest = serde_json::from_slice(&manifest_bytes)?;
-    verify_manifest(&manifest, &args.password)?;
+    verify_manifest(&manifest, unlock)?;
+    let manifest_hash = manifest.manifest_hash.clone();
+    if let Some(plan) = plan {
+        if plan.manifest_hash != manifest_hash {
+            return Err(anyhow!("manifest changed since the plan was made; run --dry-run again"));
+        }
+    }
+    // Placements are chosen between addresses; the book is rebuilt on write.
+    manifest.join_peer_addrs();
 
     let all_peers = {
         let mut set = HashSet::new();
@@ -1681,14 +3647,20 @@ async fn run_autopilot(args: AutopilotArgs) -> Result<()> {
         v.sort();
         v
     };
-    let policies: Vec<SentinelPolicyRow> = serde_json::from_slice(&fs::read(&args.policy_file)?)?;
-    let score_map = policy_scores(&policies, &all_peers);
-    let quarantined = quarantined_peers(
-        &policies,
-        args.quarantine_reputation,
-        args.min_confidence.clamp(0.0, 1.0),
-        &all_peers,
-    );
+    let score_map = policy_scores(policies, &all_peers);
+    let quarantined = match plan {
+        Some(plan) => all_peers
+            .iter()
+            .filter(|p| plan.quarantined.contains(*p))
+            .cloned()
+            .collect(),
+        None => quarantined_peers(
+            policies,
+            args.quarantine_reputation,
+            args.min_confidence.clamp(0.0, 1.0),
+            &all_peers,
+        ),
+    };
     let healthy_peers: Vec<String> = all_peers
         .iter()
         .filter(|p| !quarantined.contains(*p))
@@ -1700,11 +3672,27 @@ async fn run_autopilot(args: AutopilotArgs) -> Result<()> {
 
     let replica_target = args.replica_factor.clamp(1, MAX_PEERS_PER_SHARD);
     let max_age_ms = args.max_response_age_secs.saturating_mul(1000);
+    let placement: Strategy = args.placement.into();
 
-    let (mut swarm, _) = make_client_swarm(&all_peers)?;
+    let mut swarm = if args.dry_run {
+        None
+    } else {
+        let (mut swarm, _) = make_client_swarm(&all_peers)?;
+        // Peers still unreachable after the warmup are retried per request.
+        wait_for_peer_connections(
+            &mut swarm,
+            &all_peers,
+            Duration::from_secs(PEER_CONNECT_WARMUP_SECS),
+        )
+        .await?;
+        Some(swarm)
+    };
     let mut actions = Vec::<ShardAction>::new();
     let mut repaired = 0usize;
     let mut failed = 0usize;
+    let mut planned = 0usize;
+    let layout = manifest.shards.clone();
+    let mut regenerated_chunks = HashMap::new();
 
     for shard in &mut manifest.shards {
         let original_peers = dedup_peers(&shard.peers);
@@ -1715,41 +3703,50 @@ async fn run_autopilot(args: AutopilotArgs) -> Result<()> {
             .collect();
 
         if healthy_current.len() >= replica_target {
-            shard.peers = truncate_ranked_peers(&healthy_current, &shard.cid, &score_map);
+            shard.peers = truncate_ranked_peers(placement, &healthy_current, &shard.cid, &score_map);
             continue;
         }
 
-        let needed = replica_target.saturating_sub(healthy_current.len());
-        let candidates: Vec<String> = healthy_peers
-            .iter()
-            .filter(|p| !healthy_current.contains(*p))
-            .cloned()
-            .collect();
-        if candidates.is_empty() {
-            actions.push(ShardAction {
-                cid: shard.cid.clone(),
-                from_peer: "-".to_string(),
-                to_peer: "-".to_string(),
-                ok: false,
-                reason: "no healthy target candidates".to_string(),
-            });
-            shard.peers = truncate_ranked_peers(&original_peers, &shard.cid, &score_map);
-            failed += 1;
-            continue;
-        }
-        let targets = select_peers_for_cid(&shard.cid, &candidates, &score_map, needed);
-        if targets.is_empty() {
-            actions.push(ShardAction {
-                cid: shard.cid.clone(),
-                from_peer: "-".to_string(),
-                to_peer: "-".to_string(),
-                ok: false,
-                reason: "no target selected".to_string(),
-            });
-            shard.peers = truncate_ranked_peers(&original_peers, &shard.cid, &score_map);
-            failed += 1;
-            continue;
-        }
+        let targets = match plan {
+            Some(plan) => plan
+                .targets
+                .get(&shard.cid)
+                .cloned()
+                .ok_or("no repair in the approved plan"),
+            None => {
+                let needed = replica_target.saturating_sub(healthy_current.len());
+                let candidates: Vec<String> = healthy_peers
+                    .iter()
+                    .filter(|p| !healthy_current.contains(*p))
+                    .cloned()
+                    .collect();
+                if candidates.is_empty() {
+                    Err("no healthy target candidates")
+                } else {
+                    let targets = select_peers_for_cid(placement, &shard.cid, &candidates, &score_map, needed);
+                    if targets.is_empty() {
+                        Err("no target selected")
+                    } else {
+                        Ok(targets)
+                    }
+                }
+            }
+        };
+        let targets = match targets {
+            Ok(targets) => targets,
+            Err(reason) => {
+                actions.push(ShardAction {
+                    cid: shard.cid.clone(),
+                    from_peer: "-".to_string(),
+                    to_peer: "-".to_string(),
+                    ok: false,
+                    reason: reason.to_string(),
+                });
+                shard.peers = truncate_ranked_peers(placement, &original_peers, &shard.cid, &score_map);
+                failed += 1;
+                continue;
+            }
+        };
 
         let mut source_candidates = healthy_current.clone();
         for peer in &original_peers {
@@ -1758,23 +3755,42 @@ async fn run_autopilot(args: AutopilotArgs) -> Result<()> {
             }
         }
 
+        let Some(swarm) = swarm.as_mut() else {
+            // Dry run: which replica (or sibling shards) will serve as the
+            // source is only known once they are asked.
+            let from_peer = source_candidates.first().cloned().unwrap_or_else(|| "-".to_string());
+            for target in targets {
+                actions.push(ShardAction {
+                    cid: shard.cid.clone(),
+                    from_peer: from_peer.clone(),
+                    to_peer: target,
+                    ok: false,
+                    reason: PLANNED.to_string(),
+                });
+            }
+            planned += 1;
+            continue;
+        };
+
         let mut source_peer = None;
         let mut data = None;
         for candidate in source_candidates {
             let candidate_peer_id = extract_peer_id(&candidate)?;
+            // An unreachable replica is just skipped; with none left the
+            // shard is regenerated below.
             let reply = send_chunk_request(
-                &mut swarm,
+                swarm,
                 &candidate_peer_id,
                 ChunkCommand::Retrieve(RetrieveChunkRequest {
                     cid: shard.cid.clone(),
                 }),
             )
-            .await?;
-            if let ChunkReply::Retrieve(resp) = reply {
+            .await;
+            if let Ok(ChunkReply::Retrieve(resp)) = reply {
                 if resp.found
                     && resp.verify_proof(&candidate_peer_id, &shard.cid)
                     && resp.is_fresh(chrono::Utc::now().timestamp_millis() as u64, max_age_ms)
-                    && sha256_hex(&resp.data) == shard.cid
+                    && shard_cid_matches(&shard.cid, &resp.data)
                 {
                     source_peer = Some(candidate);
                     data = Some(resp.data);
@@ -1783,15 +3799,26 @@ async fn run_autopilot(args: AutopilotArgs) -> Result<()> {
             }
         }
 
+        let mut regenerated = false;
+        if source_peer.is_none() {
+            if let Some(bytes) =
+                regenerate_shard(swarm, &layout, shard, &mut regenerated_chunks, max_age_ms).await
+            {
+                source_peer = Some(format!("rs:chunk={}", shard.chunk_index));
+                data = Some(bytes);
+                regenerated = true;
+            }
+        }
+
         let Some(source_peer) = source_peer else {
             actions.push(ShardAction {
                 cid: shard.cid.clone(),
                 from_peer: "-".to_string(),
                 to_peer: "-".to_string(),
                 ok: false,
-                reason: "no retrievable source peer".to_string(),
+                reason: "no retrievable source peer or enough sibling shards".to_string(),
             });
-            shard.peers = truncate_ranked_peers(&original_peers, &shard.cid, &score_map);
+            shard.peers = truncate_ranked_peers(placement, &original_peers, &shard.cid, &score_map);
             failed += 1;
             continue;
         };
@@ -1801,7 +3828,7 @@ async fn run_autopilot(args: AutopilotArgs) -> Result<()> {
         for target in targets {
             let target_peer_id = extract_peer_id(&target)?;
             let store_reply = send_chunk_request(
-                &mut swarm,
+                swarm,
                 &target_peer_id,
                 ChunkCommand::Store(StoreChunkRequest {
                     cid: shard.cid.clone(),
@@ -1817,7 +3844,8 @@ async fn run_autopilot(args: AutopilotArgs) -> Result<()> {
                         && resp
                             .is_fresh(chrono::Utc::now().timestamp_millis() as u64, max_age_ms) =>
                 {
-                    (true, "replicated".to_string())
+                    let how = if regenerated { "regenerated" } else { "replicated" };
+                    (true, how.to_string())
                 }
                 ChunkReply::Store(_) => (false, "store verification failed".to_string()),
                 _ => (false, "unexpected store response".to_string()),
@@ -1845,25 +3873,32 @@ async fn run_autopilot(args: AutopilotArgs) -> Result<()> {
         }
 
         if shard_ok && healthy_current.len() >= replica_target {
-            shard.peers = truncate_ranked_peers(&healthy_current, &shard.cid, &score_map);
+            shard.peers = truncate_ranked_peers(placement, &healthy_current, &shard.cid, &score_map);
             repaired += 1;
         } else {
             let mut merged = original_peers.clone();
             merged.extend(healthy_current.clone());
-            shard.peers = truncate_ranked_peers(&merged, &shard.cid, &score_map);
+            shard.peers = truncate_ranked_peers(placement, &merged, &shard.cid, &score_map);
             failed += 1;
         }
     }
 
-    manifest.manifest_hash = compute_manifest_hash(&manifest)?;
-    manifest.manifest_auth_tag =
-        derive_manifest_auth_tag(&args.password, &manifest.salt, &manifest.manifest_hash);
-    verify_manifest(&manifest, &args.password)?;
-    fs::write(&args.manifest, serde_json::to_vec_pretty(&manifest)?)?;
+    if !args.dry_run {
+        manifest.separate_peer_addrs();
+        unlock.reseal(&mut manifest)?;
+        verify_manifest(&manifest, unlock)?;
+        fs::write(manifest_path, serde_json::to_vec_pretty(&manifest)?)?;
+    }
 
     let mut report = ActionReport {
         operation: "autopilot".to_string(),
         timestamp_ms: chrono::Utc::now().timestamp_millis() as u64,
+        dry_run: args.dry_run,
+        manifest_hash,
+        quarantine_reasons: match plan {
+            Some(plan) => plan.quarantine_reasons.clone(),
+            None => quarantine_reasons(policies, &quarantined),
+        },
         quarantined_peers: {
             let mut v: Vec<String> = quarantined.into_iter().collect();
             v.sort();
@@ -1874,17 +3909,72 @@ async fn run_autopilot(args: AutopilotArgs) -> Result<()> {
             shards_total: manifest.shards.len(),
             shards_repaired: repaired,
             shards_failed: failed,
+            shards_planned: planned,
         },
         signature: String::new(),
     };
-    report.signature = sign_action_report(&report, &args.password, &manifest.salt)?;
-    fs::write(&args.report_out, serde_json::to_vec_pretty(&report)?)?;
+    report.signature = sign_action_report(&report, &unlock.report_secret(), &manifest.salt)?;
+    Ok(report)
+}
 
-    println!(
-        "autopilot complete repaired={} failed={} report={}",
-        report.summary.shards_repaired, report.summary.shards_failed, args.report_out
-    );
-    Ok(())
+/// Rebuilds a shard none of whose replicas answered: fetches `data_shards`
+/// of its chunk siblings from any peer listing them and re-runs Reed-Solomon
+/// over them. Each chunk is regenerated at most once per pass; the outcome
+/// is kept in `cache` for its other lost shards.
+async fn regenerate_shard(
+    swarm: &mut Swarm<UploaderBehaviour>,
+    layout: &[ManifestShard],
+    lost: &ManifestShard,
+    cache: &mut HashMap<usize, Option<Vec<Vec<u8>>>>,
+    max_age_ms: u64,
+) -> Option<Vec<u8>> {
+    if let std::collections::hash_map::Entry::Vacant(slot) = cache.entry(lost.chunk_index) {
+        let total = lost.data_shards + lost.parity_shards;
+        let mut survivors: Vec<Option<Vec<u8>>> = vec![None; total];
+        let mut found = 0usize;
+        let siblings = layout
+            .iter()
+            .filter(|s| s.chunk_index == lost.chunk_index && s.shard_index < total && s.cid != lost.cid);
+        for sibling in siblings {
+            if found >= lost.data_shards {
+                break;
+            }
+            for peer in dedup_peers(&sibling.peers) {
+                let Ok(peer_id) = extract_peer_id(&peer) else {
+                    continue;
+                };
+                let request = ChunkCommand::Retrieve(RetrieveChunkRequest {
+                    cid: sibling.cid.clone(),
+                });
+                let Ok(ChunkReply::Retrieve(resp)) = send_chunk_request(swarm, &peer_id, request).await else {
+                    continue;
+                };
+                if resp.found
+                    && resp.verify_proof(&peer_id, &sibling.cid)
+                    && resp.is_fresh(chrono::Utc::now().timestamp_millis() as u64, max_age_ms)
+                    && shard_cid_matches(&sibling.cid, &resp.data)
+                {
+                    survivors[sibling.shard_index] = Some(resp.data);
+                    found += 1;
+                    break;
+                }
+            }
+        }
+        let shards = if found >= lost.data_shards {
+            erasure_regenerate(survivors, lost.data_shards, lost.parity_shards)
+                .map_err(|e| eprintln!("autopilot regenerate failed chunk={} err={e}", lost.chunk_index))
+                .ok()
+        } else {
+            eprintln!(
+                "autopilot regenerate chunk={} found {found} of {} sibling shards",
+                lost.chunk_index, lost.data_shards
+            );
+            None
+        };
+        slot.insert(shards);
+    }
+    let bytes = cache.get(&lost.chunk_index)?.as_ref()?.get(lost.shard_index)?.clone();
+    shard_cid_matches(&lost.cid, &bytes).then_some(bytes)
 }
 
 fn make_client_swarm(
@@ -1893,11 +3983,20 @@ fn make_client_swarm(
     let keypair = identity::Keypair::generate_ed25519();
     let mut swarm = libp2p::SwarmBuilder::with_existing_identity(keypair)
         .with_tokio()
-        .with_tcp(
-            tcp::Config::default().nodelay(true),
-            noise::Config::new,
-            yamux::Config::default,
-        )
+        .with_other_transport(|key| {
+            let tcp = tcp::tokio::Transport::new(tcp::Config::default().nodelay(true));
+            let tcp = match SWARM_KEY.get().copied() {
+                Some(psk) => Either::Left(
+                    tcp.and_then(move |socket, _| PnetConfig::new(psk).handshake(socket)),
+                ),
+                None => Either::Right(tcp),
+            };
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(
+                tcp.upgrade(Version::V1Lazy)
+                    .authenticate(noise::Config::new(key)?)
+                    .multiplex(yamux::Config::default()),
+            )
+        })
         .map_err(|e| anyhow!("tcp/noise init failed: {e}"))?
         .with_behaviour(|_| UploaderBehaviour {
             chunk: RequestResponse::<ChunkCodec>::new(
@@ -1909,7 +4008,14 @@ fn make_client_swarm(
             ),
         })
         .map_err(|e| anyhow!("uploader behaviour init failed: {e}"))?
-        .with_swarm_config(|cfg| cfg.with_idle_connection_timeout(Duration::from_secs(60)))
+        .with_swarm_config(|cfg| {
+            let idle = if swarm_pool::enabled() {
+                swarm_pool::IDLE_CONNECTION_TIMEOUT
+            } else {
+                Duration::from_secs(60)
+            };
+            cfg.with_idle_connection_timeout(idle)
+        })
         .build();
 
     let mut map = HashMap::new();
@@ -1939,7 +4045,12 @@ async fn wait_for_peer_connections(
     }
 
     let deadline = Instant::now() + timeout;
-    let mut connected = HashSet::new();
+    // A pooled swarm may already hold some of the connections.
+    let mut connected: HashSet<PeerId> = wanted
+        .iter()
+        .filter(|peer| swarm.is_connected(peer))
+        .copied()
+        .collect();
 
     while Instant::now() < deadline && connected.len() < wanted.len() {
         let remaining = deadline.saturating_duration_since(Instant::now());
@@ -1949,10 +4060,8 @@ async fn wait_for_peer_connections(
 
         match tokio::time::timeout(remaining, swarm.select_next_some()).await {
             Ok(event) => match event {
-                SwarmEvent::ConnectionEstablished { peer_id, .. } => {
-                    if wanted.contains(&peer_id) {
-                        connected.insert(peer_id);
-                    }
+                SwarmEvent::ConnectionEstablished { peer_id, .. } if wanted.contains(&peer_id) => {
+                    connected.insert(peer_id);
                 }
                 SwarmEvent::OutgoingConnectionError { peer_id, error, .. } => {
                     eprintln!("uploader warmup dial error peer={peer_id:?} err={error:?}");
@@ -1988,6 +4097,7 @@ fn peer_identity_key(value: &str) -> String {
 }
 
 fn truncate_ranked_peers(
+    strategy: Strategy,
     peers: &[String],
     cid: &str,
     peer_scores: &HashMap<String, u8>,
@@ -1996,7 +4106,7 @@ fn truncate_ranked_peers(
     if dedup.len() <= MAX_PEERS_PER_SHARD {
         return dedup;
     }
-    select_peers_for_cid(cid, &dedup, peer_scores, MAX_PEERS_PER_SHARD)
+    select_peers_for_cid(strategy, cid, &dedup, peer_scores, MAX_PEERS_PER_SHARD)
 }
 
 fn dedup_peers(peers: &[String]) -> Vec<String> {
@@ -2009,10 +4119,26 @@ fn dedup_peers(peers: &[String]) -> Vec<String> {
     out
 }
 
+/// Every address to dial for the manifest's peers, with identity-only
+/// entries expanded through its address book.
+pub(crate) fn manifest_dial_addrs(manifest: &UploadManifest) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+    for peer in manifest.shards.iter().flat_map(|s| s.peers.iter()) {
+        if seen.insert(peer.as_str()) {
+            out.extend(manifest.dial_addrs(peer));
+        }
+    }
+    dedup_peers(&out)
+}
+
+/// The entries of `left` whose peer is also in `right`, compared by PeerId
+/// so an identity-only entry matches any address of its peer.
 fn intersect_peers(left: &[String], right: &[String]) -> Vec<String> {
+    let wanted: HashSet<String> = right.iter().map(|p| peer_identity_key(p)).collect();
     let mut out = Vec::new();
     for p in left {
-        if right.contains(p) && !out.contains(p) {
+        if wanted.contains(&peer_identity_key(p)) && !out.contains(p) {
             out.push(p.clone());
         }
     }
@@ -2034,6 +4160,24 @@ fn parse_peer_scores(items: &[String]) -> Result<HashMap<String, u8>> {
     Ok(map)
 }
 
+/// Regions from a `--peer-metadata` file, keyed by peer identity. Rows
+/// with no region, or the gateway's unknown `XX`, are left out.
+fn parse_peer_metadata(bytes: &[u8]) -> Result<HashMap<String, String>> {
+    let rows: Vec<PeerMetadataInput> =
+        serde_json::from_slice(bytes).map_err(|e| anyhow!("invalid peer metadata file: {e}"))?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            let region = row.country_code?.trim().to_string();
+            (!region.is_empty() && region != "XX").then(|| (peer_identity_key(&row.peer), region))
+        })
+        .collect())
+}
+
+fn peer_region<'a>(peer_regions: &'a HashMap<String, String>, peer: &str) -> Option<&'a str> {
+    peer_regions.get(&peer_identity_key(peer)).map(String::as_str)
+}
+
 fn telemetry_scores(path: Option<&str>) -> Result<HashMap<String, u8>> {
     let Some(path) = path else {
         return Ok(HashMap::new());
@@ -2133,6 +4277,24 @@ fn quarantined_peers(
     out
 }
 
+/// The sentinel's explanations for each quarantined peer, keyed like
+/// `quarantined`.
+fn quarantine_reasons(rows: &[SentinelPolicyRow], quarantined: &HashSet<String>) -> BTreeMap<String, Vec<String>> {
+    let mut out = BTreeMap::new();
+    for peer in quarantined {
+        let key = peer_identity_key(peer);
+        let reasons: Vec<String> = rows
+            .iter()
+            .filter(|row| row.peer == *peer || peer_identity_key(&row.peer) == key)
+            .flat_map(|row| row.explanations.iter().map(|e| e.detail.clone()))
+            .collect();
+        if !reasons.is_empty() {
+            out.insert(peer.clone(), reasons);
+        }
+    }
+    out
+}
+
 async fn send_chunk_request(
     swarm: &mut Swarm<UploaderBehaviour>,
     peer_id: &PeerId,
@@ -2144,23 +4306,19 @@ async fn send_chunk_request(
             SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::Message { 
                 message: RequestResponseMessage::Response { request_id: rid, response },
                 ..
-            })) => {
-                if rid == request_id {
-                    return Ok(response);
-                }
+            })) if rid == request_id => {
+                return Ok(response);
             }
             SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::OutboundFailure {
                 request_id: rid,
                 error,
                 ..
-            })) => {
-                if rid == request_id {
-                    return Err(anyhow!(
-                        "request to peer {} failed for request {:?}: {error}",
-                        peer_id,
-                        request_id
-                    ));
-                }
+            })) if rid == request_id => {
+                return Err(anyhow!(
+                    "request to peer {} failed for request {:?}: {error}",
+                    peer_id,
+                    request_id
+                ));
             }
             _ => {}
         }
@@ -2172,7 +4330,10 @@ fn sign_action_report(report: &ActionReport, password: &str, salt: &str) -> Resu
     let payload = serde_json::to_vec(&serde_json::json!({
         "operation": &report.operation,
         "timestamp_ms": report.timestamp_ms,
+        "dry_run": report.dry_run,
+        "manifest_hash": &report.manifest_hash,
         "quarantined_peers": &report.quarantined_peers,
+        "quarantine_reasons": &report.quarantine_reasons,
         "actions": &report.actions,
         "summary": &report.summary,
     }))?;
@@ -2186,59 +4347,37 @@ fn sign_action_report(report: &ActionReport, password: &str, salt: &str) -> Resu
 }
 
 fn select_peers_for_cid(
+    strategy: Strategy,
     cid: &str,
     peers: &[String],
     peer_scores: &HashMap<String, u8>,
     replicas: usize,
 ) -> Vec<String> {
-    let mut ranked = peers
-        .iter()
-        .map(|peer| {
-            let quality = *peer_scores.get(peer).unwrap_or(&50) as u64;
-            let entropy = shard_peer_entropy(cid, peer) % 1_000_000;
-            let rank = quality * 1_000_000 + entropy;
-            (rank, peer.clone())
-        })
-        .collect::<Vec<_>>();
-
-    ranked.sort_by(|a, b| b.0.cmp(&a.0));
-    ranked.into_iter().take(replicas).map(|x| x.1).collect()
-}
-
-fn shard_peer_entropy(cid: &str, peer: &str) -> u64 {
-    let mut hasher = Sha256::new();
-    hasher.update(cid.as_bytes());
-    hasher.update(b"|");
-    hasher.update(peer.as_bytes());
-    let digest = hasher.finalize();
-    let mut bytes = [0u8; 8];
-    bytes.copy_from_slice(&digest[..8]);
-    u64::from_le_bytes(bytes)
-}
-
-fn build_audit_vectors(data: &[u8], rounds: usize) -> (Vec<String>, Vec<String>) {
-    let rounds = rounds.max(1);
-    let mut challenges = Vec::with_capacity(rounds);
-    let mut tokens = Vec::with_capacity(rounds);
-    for _ in 0..rounds {
-        let mut challenge = [0u8; 16];
-        OsRng.fill_bytes(&mut challenge);
-        let challenge_hex = hex::encode(challenge);
-        challenges.push(challenge_hex.clone());
-        tokens.push(audit_token(&challenge_hex, data));
-    }
-    (challenges, tokens)
+    neuro_placement::place_ids(strategy, cid, peers, |peer| peer_scores.get(peer).copied(), replicas)
 }
 
-fn audit_token(challenge_hex: &str, data: &[u8]) -> String {
-    let mut hasher = Sha256::new();
-    let challenge = hex::decode(challenge_hex).unwrap_or_default();
-    hasher.update(challenge);
-    hasher.update(data);
-    hex::encode(hasher.finalize())
+fn select_spread_peers_for_cid(
+    strategy: Strategy,
+    cid: &str,
+    peers: &[String],
+    peer_scores: &HashMap<String, u8>,
+    peer_regions: &HashMap<String, String>,
+    replicas: usize,
+    min_regions: usize,
+) -> Result<Vec<String>> {
+    neuro_placement::place_ids_spread(
+        strategy,
+        cid,
+        peers,
+        |peer| peer_scores.get(peer).copied(),
+        |peer| peer_region(peer_regions, peer).map(str::to_string),
+        replicas,
+        min_regions,
+    )
+    .map_err(|e| anyhow!("--distinct-regions {min_regions} cannot be met: {e}"))
 }
 
-fn verify_manifest(manifest: &UploadManifest, password: &str) -> Result<()> {
+fn verify_manifest(manifest: &UploadManifest, unlock: &x25519::Unlock) -> Result<()> {
     if manifest.shards.is_empty() {
         return Err(anyhow!("manifest has no shards"));
     }
@@ -2254,13 +4393,7 @@ fn verify_manifest(manifest: &UploadManifest, password: &str) -> Result<()> {
     if expected_hash != manifest.manifest_hash {
         return Err(anyhow!("manifest hash mismatch; manifest appears tampered"));
     }
-    let expected_auth_tag =
-        derive_manifest_auth_tag(password, &manifest.salt, &manifest.manifest_hash);
-    if expected_auth_tag != manifest.manifest_auth_tag {
-        return Err(anyhow!(
-            "manifest auth mismatch; incorrect password or tampered manifest"
-        ));
-    }
+    unlock.verify(manifest)?;
     verify_manifest_structure(manifest)?;
     Ok(())
 }
@@ -2288,14 +4421,29 @@ fn verify_manifest_structure(manifest: &UploadManifest) -> Result<()> {
     let template_shards: Vec<Shard> = manifest
         .shards
         .iter()
-        .map(manifest_shard_to_template)
+        .map(ManifestShard::to_template)
         .collect();
 
     let mut shard_index_seen: HashSet<(usize, usize)> = HashSet::new();
     let mut cid_peer_seen: HashSet<(String, String)> = HashSet::new();
+    let cid_format = check_manifest_version(&manifest.version, !manifest.gateways.is_empty())?;
+    if gateways::merge_urls(&manifest.gateways, &[])? != manifest.gateways {
+        return Err(anyhow!("manifest gateway hints are not normalized or repeat"));
+    }
     for ms in &manifest.shards {
-        if !is_valid_cid_hex(&ms.cid) {
-            return Err(anyhow!("manifest shard has invalid cid format: {}", ms.cid));
+        if CidFormat::of(&ms.cid) != Some(cid_format) {
+            return Err(anyhow!(
+                "manifest shard has invalid cid format for version {}: {}",
+                manifest.version,
+                ms.cid
+            ));
+        }
+        if HashAlgorithm::of(&ms.cid) != Some(manifest.hash_algorithm) {
+            return Err(anyhow!(
+                "manifest shard {} is not hashed with {:?}",
+                ms.cid,
+                manifest.hash_algorithm
+            ));
         }
         if !shard_index_seen.insert((ms.chunk_index, ms.shard_index)) {
             return Err(anyhow!(
@@ -2353,45 +4501,6 @@ fn verify_manifest_structure(manifest: &UploadManifest) -> Result<()> {
     Ok(())
 }
 
-fn derive_manifest_auth_tag(password: &str, salt: &str, manifest_hash: &str) -> String {
-    let mut key_hasher = Sha256::new();
-    key_hasher.update(password.as_bytes());
-    key_hasher.update(b"|");
-    key_hasher.update(salt.as_bytes());
-    let key = key_hasher.finalize();
-
-    let mut mac_hasher = Sha256::new();
-    mac_hasher.update(key);
-    mac_hasher.update(b"|");
-    mac_hasher.update(manifest_hash.as_bytes());
-    hex::encode(mac_hasher.finalize())
-}
-
-fn compute_manifest_hash(manifest: &UploadManifest) -> Result<String> {
-    let view = ManifestHashView {
-        version: &manifest.version,
-        salt: &manifest.salt,
-        manifest_root: &manifest.manifest_root,
-        total_bytes: manifest.total_bytes,
-        chunk_count: manifest.chunk_count,
-        shards: &manifest.shards,
-    };
-    let bytes = serde_json::to_vec(&view)?;
-    Ok(sha256_hex(&bytes))
-}
-
-fn manifest_shard_to_template(ms: &ManifestShard) -> Shard {
-    Shard {
-        chunk_index: ms.chunk_index,
-        shard_index: ms.shard_index,
-        cid: ms.cid.clone(),
-        bytes: Vec::new(),
-        payload_len: ms.payload_len,
-        data_shards: ms.data_shards,
-        parity_shards: ms.parity_shards,
-    }
-}
-
 fn sha256_hex(data: &[u8]) -> String {
     let mut hasher = Sha256::new();
     hasher.update(data);
@@ -2416,10 +4525,6 @@ fn hash_to_index(value: &str, len: usize) -> usize {
         % len
 }
 
-fn is_valid_cid_hex(cid: &str) -> bool {
-    cid.len() == 64 && cid.as_bytes().iter().all(|b| b.is_ascii_hexdigit())
-}
-
 fn validate_peer_multiaddr(addr: &str) -> Result<()> {
     let ma: Multiaddr = addr.parse()?;
     let has_p2p = ma
@@ -2442,6 +4547,85 @@ fn write_report(path: &str, operation: &str, ok: bool, details: serde_json::Valu
     Ok(())
 }
 
+/// Plaintext `(offset, len)` of every chunk, holes included, indexed by
+/// chunk index.
+pub(crate) fn chunk_spans(manifest: &UploadManifest) -> Result<Vec<(u64, u64)>> {
+    // nonce || ciphertext || tag; the plaintext chunk is what remains.
+    const CHUNK_OVERHEAD: usize = 12 + 16;
+    let mut lens: BTreeMap<usize, usize> = BTreeMap::new();
+    for shard in &manifest.shards {
+        let len = shard
+            .payload_len
+            .checked_sub(CHUNK_OVERHEAD)
+            .ok_or_else(|| anyhow!("manifest payload_len too small"))?;
+        lens.insert(shard.chunk_index, len);
+    }
+    for hole in &manifest.holes {
+        if lens.insert(hole.chunk_index, hole.len).is_some() {
+            return Err(anyhow!("manifest chunk {} is both a hole and sharded", hole.chunk_index));
+        }
+    }
+    if lens.len() != manifest.chunk_count || lens.keys().enumerate().any(|(i, idx)| i != *idx) {
+        return Err(anyhow!("manifest chunk indexes are not contiguous"));
+    }
+
+    let mut spans = Vec::with_capacity(lens.len());
+    let mut offset = 0u64;
+    for len in lens.into_values() {
+        let len = len as u64;
+        spans.push((offset, len));
+        offset += len;
+    }
+    if offset != manifest.total_bytes as u64 {
+        return Err(anyhow!(
+            "manifest chunk layout covers {} bytes, total_bytes={}",
+            offset,
+            manifest.total_bytes
+        ));
+    }
+    Ok(spans)
+}
+
+/// `shards` as a `retrieve-raw` bundle, in chunk order.
+fn raw_bundle_of<'a>(manifest: &UploadManifest, shards: impl Iterator<Item = &'a Shard>) -> RawRetrieveBundle {
+    let mut shards: Vec<RawRetrieveShard> = shards
+        .map(|s| RawRetrieveShard {
+            chunk_index: s.chunk_index,
+            shard_index: s.shard_index,
+            cid: s.cid.clone(),
+            payload_len: s.payload_len,
+            data_shards: s.data_shards,
+            parity_shards: s.parity_shards,
+            bytes_b64: encode_b64(&s.bytes),
+        })
+        .collect();
+    shards.sort_by_key(|s| (s.chunk_index, s.shard_index));
+    RawRetrieveBundle {
+        version: "raw-v1".to_string(),
+        salt: manifest.salt.clone(),
+        manifest_root: manifest.manifest_root.clone(),
+        total_bytes: manifest.total_bytes,
+        chunk_count: manifest.chunk_count,
+        shards,
+    }
+}
+
+/// A shard store planned by `store-prepared`'s first pass, before its
+/// bytes are read again to send it.
+struct PendingStore {
+    /// Position of the shard in the prepared file.
+    shard: usize,
+    cid: String,
+    len: usize,
+    peer_id: PeerId,
+}
+
+impl dedup::PlannedStore for PendingStore {
+    fn target(&self) -> (PeerId, &str, usize) {
+        (self.peer_id, &self.cid, self.len)
+    }
+}
+
 #[derive(Clone)]
 struct StoreDispatch {
     request: ChunkCommand,
@@ -2453,9 +4637,28 @@ struct StoreDispatch {
 struct InflightStore {
     dispatch: StoreDispatch,
     attempt: usize,
+    busy_replies: usize,
     started: Instant,
 }
 
+fn print_concurrency(prefix: &str, summary: &tuning::ConcurrencySummary) {
+    for peer in &summary.peers {
+        let window = peer
+            .final_window
+            .map_or_else(|| "-".to_string(), |w| w.to_string());
+        println!(
+            "{prefix} concurrency mode={} peer={} window={} srtt_ms={:.1} throughput_mbps={:.2} busy={} failures={}",
+            summary.mode,
+            peer.peer,
+            window,
+            peer.srtt_ms.unwrap_or(0.0),
+            peer.throughput_mbps,
+            peer.busy,
+            peer.failures
+        );
+    }
+}
+
 #[derive(Clone)]
 struct RetrieveAttemptState {
     cid: String,
@@ -2484,6 +4687,100 @@ fn random_nonce_hex() -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use neuro_client_sdk::MANIFEST_VERSION_GATEWAYS;
+
+    #[test]
+    fn peer_map_keeps_the_peer_id_and_accepts_bare_ids() {
+        let peer = PeerId::from(identity::Keypair::generate_ed25519().public());
+        let other = PeerId::from(identity::Keypair::generate_ed25519().public());
+        let old = format!("/ip4/10.0.0.1/tcp/9000/p2p/{peer}");
+        let new = format!("/ip4/10.0.0.2/tcp/9100/p2p/{peer}");
+
+        let map = parse_peer_map(&[format!("{old}={new}"), format!("{peer}={new}")]).unwrap();
+        assert_eq!(map[0], (old.clone(), new.clone()));
+        assert_eq!(map[1], (peer.to_string(), new.clone()));
+
+        let moved = format!("/ip4/10.0.0.2/tcp/9100/p2p/{other}");
+        assert!(parse_peer_map(&[format!("{old}={moved}")]).is_err());
+        assert!(parse_peer_map(&[format!("{old}=/ip4/10.0.0.2/tcp/9100")]).is_err());
+        assert!(is_tcp_peer_addr(&new, &peer));
+        assert!(!is_tcp_peer_addr(&new, &other));
+    }
+
+    #[test]
+    fn distinct_regions_spread_replicas_or_fail_clearly() {
+        let ids: Vec<PeerId> = (0..4)
+            .map(|_| PeerId::from(identity::Keypair::generate_ed25519().public()))
+            .collect();
+        let peers: Vec<String> = ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| format!("/ip4/10.0.0.{i}/tcp/9000/p2p/{id}"))
+            .collect();
+        let metadata = serde_json::json!([
+            { "peer": peers[0], "country_code": "IN" },
+            { "peer": ids[1].to_string(), "country_code": "IN" },
+            { "peer": ids[2].to_string(), "region": "SG" },
+            { "peer": peers[3], "country_code": "XX" },
+        ]);
+        let regions = parse_peer_metadata(metadata.to_string().as_bytes()).unwrap();
+        assert_eq!(peer_region(&regions, &peers[1]), Some("IN"));
+        assert_eq!(peer_region(&regions, &peers[2]), Some("SG"));
+        assert_eq!(peer_region(&regions, &peers[3]), None);
+
+        let scores = HashMap::new();
+        for k in 0..50 {
+            let cid = format!("cid-{k}");
+            let placed = select_spread_peers_for_cid(Strategy::Rendezvous, &cid, &peers, &scores, &regions, 2, 2).unwrap();
+            let held: HashSet<_> = placed.iter().filter_map(|p| peer_region(&regions, p)).collect();
+            assert_eq!(held.len(), 2, "{placed:?}");
+        }
+        let err = select_spread_peers_for_cid(Strategy::Rendezvous, "cid", &peers, &scores, &regions, 3, 3).unwrap_err();
+        assert!(err.to_string().contains("--distinct-regions 3"), "{err}");
+        assert!(err.to_string().contains("cover only 2"), "{err}");
+    }
+
+    #[test]
+    fn partial_recovery_keeps_decodable_chunks_and_reports_the_rest() {
+        let data: Vec<u8> = (0..3000u32).map(|i| (i % 251) as u8).collect();
+        let cfg = PipelineConfig {
+            chunk_size: 1024,
+            data_shards: 2,
+            parity_shards: 1,
+            ..PipelineConfig::default()
+        };
+        let output = process_bytes(&data, "pw", cfg).unwrap();
+        let mut builder = ManifestBuilder::for_output(&output);
+        for shard in &output.shards {
+            builder.place_shard(shard, vec!["peer".to_string()], 1).unwrap();
+        }
+        let manifest = builder.build().unwrap();
+        let spans = chunk_spans(&manifest).unwrap();
+        assert_eq!(spans, vec![(0, 1024), (1024, 1024), (2048, 952)]);
+
+        // Chunk 0 loses its parity, chunk 1 two of three shards.
+        let completed: HashMap<(usize, usize), Shard> = output
+            .shards
+            .iter()
+            .filter(|s| !matches!((s.chunk_index, s.shard_index), (0, 2) | (1, 0) | (1, 1)))
+            .map(|s| ((s.chunk_index, s.shard_index), s.clone()))
+            .collect();
+        let decoder = ChunkDecoder::new("pw", &output.salt).unwrap();
+        let PartialRecovery { chunks, gaps } = recover_chunks(&manifest, &completed, &decoder).unwrap();
+        assert_eq!(chunks.iter().map(|(offset, _)| *offset).collect::<Vec<_>>(), vec![0, 2048]);
+        assert_eq!(gaps.len(), 1);
+        assert_eq!((gaps[0].chunk_index, gaps[0].offset, gaps[0].len), (1, 1024, 1024));
+        assert_eq!((gaps[0].shards_fetched, gaps[0].shards_needed), (1, 2));
+
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("partial.bin");
+        write_sparse(out.to_str().unwrap(), data.len() as u64, &chunks).unwrap();
+        let written = fs::read(&out).unwrap();
+        assert_eq!(written.len(), data.len());
+        assert_eq!(written[..1024], data[..1024]);
+        assert!(written[1024..2048].iter().all(|b| *b == 0));
+        assert_eq!(written[2048..], data[2048..]);
+    }
 
     #[test]
     fn policy_maps_peer_id_only_rows_to_manifest_multiaddr() {
@@ -2495,6 +4792,7 @@ mod tests {
             confidence: Some(0.9),
             anomaly: Some(false),
             recommendation: Some("accept".to_string()),
+            explanations: Vec::new(),
         }];
 
         let scores = policy_scores(&rows, std::slice::from_ref(&addr));
@@ -2512,9 +4810,62 @@ mod tests {
             confidence: Some(0.9),
             anomaly: Some(false),
             recommendation: Some("quarantine".to_string()),
+            explanations: Vec::new(),
         }];
 
         let quarantined = quarantined_peers(&rows, 40.0, 0.5, std::slice::from_ref(&addr));
         assert!(quarantined.contains(&addr));
     }
+
+    #[test]
+    fn quarantine_reasons_carry_the_sentinel_explanations() {
+        let peer = PeerId::from(identity::Keypair::generate_ed25519().public());
+        let addr = format!("/ip4/127.0.0.1/tcp/9000/p2p/{peer}");
+        let rows: Vec<SentinelPolicyRow> = serde_json::from_value(serde_json::json!([{
+            "peer": peer.to_string(),
+            "reputation": 12.0,
+            "confidence": 0.9,
+            "explanations": [
+                {"kind": "zscore", "signal": "latency_ms", "detail": "latency_ms 900.00 is 4.1 standard deviations worse than its running mean of 120.00"},
+                {"kind": "slo", "signal": "latency_ms", "detail": "latency_ms 900.00 is above the SLO of 400.00"}
+            ]
+        }]))
+        .unwrap();
+
+        let quarantined = quarantined_peers(&rows, 40.0, 0.5, std::slice::from_ref(&addr));
+        let reasons = quarantine_reasons(&rows, &quarantined);
+        assert_eq!(reasons[&addr].len(), 2);
+        assert!(reasons[&addr][0].contains("standard deviations"));
+    }
+
+    #[test]
+    fn gateway_hints_are_hashed_and_need_a_3x_version() {
+        let mut manifest: UploadManifest = serde_json::from_value(serde_json::json!({
+            "version": MANIFEST_VERSION,
+            "salt": "00",
+            "manifest_root": "root",
+            "total_bytes": 0,
+            "chunk_count": 0,
+            "shards": [],
+            "manifest_hash": "",
+            "manifest_auth_tag": ""
+        }))
+        .unwrap();
+        let v2_hash = compute_manifest_hash(&manifest).unwrap();
+        let view = serde_json::to_value(&manifest).unwrap();
+        assert!(view.get("gateways").is_none());
+
+        manifest.gateways = vec!["https://gw1.example.com".to_string()];
+        assert_ne!(compute_manifest_hash(&manifest).unwrap(), v2_hash);
+        let err = verify_manifest_structure(&manifest).unwrap_err();
+        assert!(err.to_string().contains("gateway hints"));
+
+        manifest.version = MANIFEST_VERSION_GATEWAYS.to_string();
+        assert_eq!(manifest_cid_format(&manifest.version).unwrap(), CidFormat::Sha256Hex);
+        assert_eq!(
+            gateways::merge_urls(&manifest.gateways, &["https://gw1.example.com/".to_string()])
+                .unwrap(),
+            manifest.gateways
+        );
+    }
 }
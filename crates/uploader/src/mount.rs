@@ -0,0 +1,565 @@
+// ═══════════════════════════════════════════════════════════════
+// NeuroStore — Read-only FUSE Mount
+// Manifest(s) → inode tree · lazy chunk fetch/decode on read
+// ═══════════════════════════════════════════════════════════════
+
+use crate::{
+    chunk_spans, dedup_peers, extract_peer_id, intersect_peers, make_client_swarm, manifest_dial_addrs,
+    verify_manifest, wait_for_peer_connections, x25519::Unlock, ManifestShard, MountArgs,
+    UploadManifest, UploaderBehaviour, UploaderEvent, MAX_MANIFEST_BYTES, PEER_CONNECT_WARMUP_SECS,
+};
+use anyhow::{anyhow, Result};
+use fuser::{
+    FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry,
+    Request,
+};
+use futures::StreamExt;
+use libp2p::{
+    request_response::{Event as RequestResponseEvent, Message as RequestResponseMessage},
+    swarm::{Swarm, SwarmEvent},
+    PeerId,
+};
+use neuro_client_sdk::{shard_cid_matches, ChunkDecoder, Shard};
+use neuro_protocol::{ChunkCommand, ChunkReply, RetrieveChunkRequest};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::runtime::Handle;
+
+const ROOT_INO: u64 = 1;
+const ATTR_TTL: Duration = Duration::from_secs(60);
+const BLOCK_SIZE: u32 = 4096;
+
+pub(crate) async fn run_mount(args: MountArgs) -> Result<()> {
+    let unlock = args.password.unlock()?;
+    let objects = load_objects(Path::new(&args.manifest), &unlock)?;
+    if objects.is_empty() {
+        return Err(anyhow!("no valid manifests found at {}", args.manifest));
+    }
+
+    let manifest_peers: Vec<String> = objects
+        .iter()
+        .flat_map(|(_, o)| manifest_dial_addrs(&o.manifest))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let peers = if args.peer.is_empty() {
+        manifest_peers
+    } else {
+        dedup_peers(&args.peer)
+    };
+    if peers.is_empty() {
+        return Err(anyhow!("no peers available for mount"));
+    }
+
+    let (mut swarm, _) = make_client_swarm(&peers)?;
+    let warm_connected = wait_for_peer_connections(
+        &mut swarm,
+        &peers,
+        Duration::from_secs(PEER_CONNECT_WARMUP_SECS),
+    )
+    .await?;
+    if warm_connected.is_empty() {
+        return Err(anyhow!(
+            "unable to connect to any retrieval peer during warmup"
+        ));
+    }
+
+    let fs = ManifestFs::new(
+        objects,
+        ShardFetcher {
+            runtime: Handle::current(),
+            swarm,
+            peer_filter: (!args.peer.is_empty()).then_some(peers),
+            max_age_ms: args.max_response_age_secs.saturating_mul(1000),
+        },
+        unlock,
+        args.cache_chunks.max(1),
+    );
+
+    let mut options = vec![
+        MountOption::RO,
+        MountOption::FSName("neurostore".to_string()),
+        MountOption::Subtype("neuro".to_string()),
+        MountOption::DefaultPermissions,
+    ];
+    if args.allow_other {
+        options.push(MountOption::AllowOther);
+    }
+
+    println!(
+        "mount ready manifest={} mountpoint={} (unmount with `fusermount -u {}`)",
+        args.manifest, args.mountpoint, args.mountpoint
+    );
+    // The FUSE session loop is blocking and drives the swarm through
+    // `Handle::block_on`, so it must run off the async workers.
+    let mountpoint = args.mountpoint.clone();
+    tokio::task::spawn_blocking(move || fuser::mount2(fs, &mountpoint, &options))
+        .await
+        .map_err(|e| anyhow!("mount task failed: {e}"))??;
+    println!("mount closed mountpoint={}", args.mountpoint);
+    Ok(())
+}
+
+struct MountedObject {
+    manifest: UploadManifest,
+    /// Plaintext (offset, len) per chunk, indexed by chunk_index.
+    spans: Vec<(u64, u64)>,
+    decoder: Option<ChunkDecoder>,
+}
+
+impl MountedObject {
+    fn new(manifest: UploadManifest) -> Result<Self> {
+        let spans = chunk_spans(&manifest)?;
+        Ok(Self {
+            manifest,
+            spans,
+            decoder: None,
+        })
+    }
+
+    fn size(&self) -> u64 {
+        self.manifest.total_bytes as u64
+    }
+}
+
+enum Node {
+    Dir {
+        parent: u64,
+        children: BTreeMap<OsString, u64>,
+    },
+    File {
+        object: usize,
+    },
+}
+
+struct ShardFetcher {
+    runtime: Handle,
+    swarm: Swarm<UploaderBehaviour>,
+    peer_filter: Option<Vec<String>>,
+    max_age_ms: u64,
+}
+
+impl ShardFetcher {
+    fn fetch_chunk(&mut self, shards: &[ManifestShard]) -> Result<Vec<Shard>> {
+        let runtime = self.runtime.clone();
+        runtime.block_on(self.fetch_chunk_async(shards))
+    }
+
+    /// Requests every shard of one chunk and stops as soon as enough have
+    /// verified to run Reed-Solomon, moving on to the next peer on failure.
+    async fn fetch_chunk_async(&mut self, shards: &[ManifestShard]) -> Result<Vec<Shard>> {
+        let Some(first) = shards.first() else {
+            return Ok(Vec::new());
+        };
+        let needed = first.data_shards;
+
+        let mut attempts: VecDeque<(usize, Vec<String>, usize)> = VecDeque::new();
+        for (i, ms) in shards.iter().enumerate() {
+            let peers = match &self.peer_filter {
+                Some(filter) => intersect_peers(&ms.peers, filter),
+                None => ms.peers.clone(),
+            };
+            if !peers.is_empty() {
+                attempts.push_back((i, peers, 0));
+            }
+        }
+
+        let mut inflight = HashMap::new();
+        let mut recovered = Vec::with_capacity(needed);
+        while recovered.len() < needed {
+            while let Some((i, peers, attempt)) = attempts.pop_front() {
+                let peer_id = extract_peer_id(&peers[attempt])?;
+                let request_id = self.swarm.behaviour_mut().chunk.send_request(
+                    &peer_id,
+                    ChunkCommand::Retrieve(RetrieveChunkRequest {
+                        cid: shards[i].cid.clone(),
+                    }),
+                );
+                inflight.insert(request_id, (i, peers, attempt, peer_id));
+            }
+            if inflight.is_empty() {
+                break;
+            }
+
+            let (request_id, reply) = match self.swarm.select_next_some().await {
+                SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::Message {
+                    message:
+                        RequestResponseMessage::Response {
+                            request_id,
+                            response,
+                        },
+                    ..
+                })) => (request_id, Some(response)),
+                SwarmEvent::Behaviour(UploaderEvent::Chunk(
+                    RequestResponseEvent::OutboundFailure { request_id, .. },
+                )) => (request_id, None),
+                _ => continue,
+            };
+            let Some((i, peers, attempt, peer_id)) = inflight.remove(&request_id) else {
+                continue;
+            };
+
+            match reply {
+                Some(ChunkReply::Retrieve(reply))
+                    if self.accept(&reply, &peer_id, &shards[i].cid) =>
+                {
+                    let mut shard = shards[i].to_template();
+                    shard.bytes = reply.data;
+                    recovered.push(shard);
+                }
+                _ if attempt + 1 < peers.len() => attempts.push_back((i, peers, attempt + 1)),
+                _ => {}
+            }
+        }
+
+        if recovered.len() < needed {
+            return Err(anyhow!(
+                "chunk {} unavailable recovered={} needed={}",
+                first.chunk_index,
+                recovered.len(),
+                needed
+            ));
+        }
+        Ok(recovered)
+    }
+
+    fn accept(
+        &self,
+        reply: &neuro_protocol::RetrieveChunkResponse,
+        peer: &PeerId,
+        cid: &str,
+    ) -> bool {
+        reply.found
+            && reply.verify_proof(peer, cid)
+            && reply.is_fresh(
+                chrono::Utc::now().timestamp_millis() as u64,
+                self.max_age_ms,
+            )
+            && shard_cid_matches(cid, &reply.data)
+    }
+}
+
+/// ((object, chunk_index), plaintext), least recently read first.
+type CachedChunk = ((usize, usize), Arc<Vec<u8>>);
+
+struct ManifestFs {
+    nodes: Vec<Node>,
+    objects: Vec<MountedObject>,
+    fetcher: ShardFetcher,
+    unlock: Unlock,
+    cache: VecDeque<CachedChunk>,
+    cache_capacity: usize,
+    mounted_at: SystemTime,
+    uid: u32,
+    gid: u32,
+}
+
+impl ManifestFs {
+    fn new(
+        objects: Vec<(Vec<OsString>, MountedObject)>,
+        fetcher: ShardFetcher,
+        unlock: Unlock,
+        cache_capacity: usize,
+    ) -> Self {
+        // Inode N lives at nodes[N - 1]; nodes[0] is the root directory.
+        let mut nodes = vec![Node::Dir {
+            parent: ROOT_INO,
+            children: BTreeMap::new(),
+        }];
+        let mut mounted = Vec::with_capacity(objects.len());
+
+        for (path, object) in objects {
+            let mut dir = ROOT_INO;
+            let Some((name, parents)) = path.split_last() else {
+                continue;
+            };
+            for part in parents {
+                let existing = match &nodes[(dir - 1) as usize] {
+                    Node::Dir { children, .. } => children.get(part).copied(),
+                    Node::File { .. } => None,
+                };
+                dir = existing.unwrap_or_else(|| {
+                    nodes.push(Node::Dir {
+                        parent: dir,
+                        children: BTreeMap::new(),
+                    });
+                    let ino = nodes.len() as u64;
+                    if let Node::Dir { children, .. } = &mut nodes[(dir - 1) as usize] {
+                        children.insert(part.clone(), ino);
+                    }
+                    ino
+                });
+            }
+
+            nodes.push(Node::File {
+                object: mounted.len(),
+            });
+            let ino = nodes.len() as u64;
+            if let Node::Dir { children, .. } = &mut nodes[(dir - 1) as usize] {
+                children.insert(name.clone(), ino);
+            }
+            mounted.push(object);
+        }
+
+        // SAFETY: getuid/getgid have no preconditions and cannot fail.
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        Self {
+            nodes,
+            objects: mounted,
+            fetcher,
+            unlock,
+            cache: VecDeque::new(),
+            cache_capacity,
+            mounted_at: SystemTime::now(),
+            uid,
+            gid,
+        }
+    }
+
+    fn node(&self, ino: u64) -> Option<&Node> {
+        ino.checked_sub(1).and_then(|i| self.nodes.get(i as usize))
+    }
+
+    fn attr(&self, ino: u64) -> Option<FileAttr> {
+        let (kind, perm, size, nlink) = match self.node(ino)? {
+            Node::Dir { .. } => (FileType::Directory, 0o555, 0, 2),
+            Node::File { object } => (
+                FileType::RegularFile,
+                0o444,
+                self.objects[*object].size(),
+                1,
+            ),
+        };
+        Some(FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: self.mounted_at,
+            mtime: self.mounted_at,
+            ctime: self.mounted_at,
+            crtime: self.mounted_at,
+            kind,
+            perm,
+            nlink,
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: BLOCK_SIZE,
+            flags: 0,
+        })
+    }
+
+    fn chunk(&mut self, object: usize, chunk_index: usize) -> Result<Arc<Vec<u8>>> {
+        let key = (object, chunk_index);
+        if let Some(pos) = self.cache.iter().position(|(k, _)| *k == key) {
+            let entry = self.cache.remove(pos).expect("cached chunk");
+            let data = Arc::clone(&entry.1);
+            self.cache.push_back(entry);
+            return Ok(data);
+        }
+
+        let obj = &mut self.objects[object];
+        if let Some(hole) = obj.manifest.holes.iter().find(|h| h.chunk_index == chunk_index) {
+            return Ok(Arc::new(hole.bytes()));
+        }
+        let shards: Vec<ManifestShard> = obj
+            .manifest
+            .shards
+            .iter()
+            .filter(|s| s.chunk_index == chunk_index)
+            .cloned()
+            .collect();
+        let fetched = self.fetcher.fetch_chunk(&shards)?;
+        if obj.decoder.is_none() {
+            obj.decoder = Some(ChunkDecoder::from_key(self.unlock.chunk_key(&obj.manifest)?));
+        }
+        let plaintext = obj
+            .decoder
+            .as_ref()
+            .expect("decoder")
+            .decode(&fetched, obj.manifest.chunk_count)?;
+        if plaintext.len() as u64 != obj.spans[chunk_index].1 {
+            return Err(anyhow!(
+                "chunk {} decoded to {} bytes, manifest expects {}",
+                chunk_index,
+                plaintext.len(),
+                obj.spans[chunk_index].1
+            ));
+        }
+
+        let data = Arc::new(plaintext);
+        if self.cache.len() >= self.cache_capacity {
+            self.cache.pop_front();
+        }
+        self.cache.push_back((key, Arc::clone(&data)));
+        Ok(data)
+    }
+
+    fn read_range(&mut self, object: usize, offset: u64, size: u64) -> Result<Vec<u8>> {
+        let total = self.objects[object].size();
+        let end = offset.saturating_add(size).min(total);
+        let mut out = Vec::with_capacity(end.saturating_sub(offset) as usize);
+        if offset >= end {
+            return Ok(out);
+        }
+
+        let first = self.objects[object]
+            .spans
+            .partition_point(|(start, len)| start + len <= offset);
+        for chunk_index in first..self.objects[object].spans.len() {
+            let (start, len) = self.objects[object].spans[chunk_index];
+            if start >= end {
+                break;
+            }
+            let data = self.chunk(object, chunk_index)?;
+            let from = offset.max(start) - start;
+            let to = end.min(start + len) - start;
+            out.extend_from_slice(&data[from as usize..to as usize]);
+        }
+        Ok(out)
+    }
+}
+
+impl Filesystem for ManifestFs {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let child = match self.node(parent) {
+            Some(Node::Dir { children, .. }) => children.get(name).copied(),
+            _ => None,
+        };
+        match child.and_then(|ino| self.attr(ino)) {
+            Some(attr) => reply.entry(&ATTR_TTL, &attr, 0),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+        match self.attr(ino) {
+            Some(attr) => reply.attr(&ATTR_TTL, &attr),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let object = match self.node(ino) {
+            Some(Node::File { object }) => *object,
+            Some(Node::Dir { .. }) => return reply.error(libc::EISDIR),
+            None => return reply.error(libc::ENOENT),
+        };
+        let Ok(offset) = u64::try_from(offset) else {
+            return reply.error(libc::EINVAL);
+        };
+        match self.read_range(object, offset, size as u64) {
+            Ok(data) => reply.data(&data),
+            Err(e) => {
+                eprintln!("mount read failed ino={ino} offset={offset} err={e:#}");
+                reply.error(libc::EIO);
+            }
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let Some(Node::Dir { parent, children }) = self.node(ino) else {
+            return reply.error(libc::ENOTDIR);
+        };
+        let mut entries = vec![
+            (ino, FileType::Directory, OsString::from(".")),
+            (*parent, FileType::Directory, OsString::from("..")),
+        ];
+        for (name, child) in children {
+            let kind = match self.node(*child) {
+                Some(Node::Dir { .. }) => FileType::Directory,
+                _ => FileType::RegularFile,
+            };
+            entries.push((*child, kind, name.clone()));
+        }
+
+        for (i, (child, kind, name)) in entries.into_iter().enumerate().skip(offset.max(0) as usize)
+        {
+            if reply.add(child, (i + 1) as i64, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
+
+/// Loads one manifest, or every `*.json` manifest under a directory with
+/// the relative layout preserved. Each entry carries its path components.
+fn load_objects(path: &Path, unlock: &Unlock) -> Result<Vec<(Vec<OsString>, MountedObject)>> {
+    if !path.is_dir() {
+        let object = MountedObject::new(read_manifest(path, unlock)?)?;
+        return Ok(vec![(vec![object_name(path)], object)]);
+    }
+
+    let mut out = Vec::new();
+    let mut stack = vec![(path.to_path_buf(), Vec::<OsString>::new())];
+    while let Some((dir, prefix)) = stack.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let entry_path = entry.path();
+            let mut components = prefix.clone();
+            if entry.file_type()?.is_dir() {
+                components.push(entry.file_name());
+                stack.push((entry_path, components));
+                continue;
+            }
+            if entry_path.extension() != Some(OsStr::new("json")) {
+                continue;
+            }
+            match read_manifest(&entry_path, unlock).and_then(MountedObject::new) {
+                Ok(object) => {
+                    components.push(object_name(&entry_path));
+                    out.push((components, object));
+                }
+                Err(e) => eprintln!("mount skipping {}: {e:#}", entry_path.display()),
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn read_manifest(path: &Path, unlock: &Unlock) -> Result<UploadManifest> {
+    let bytes = fs::read(path)?;
+    if bytes.len() > MAX_MANIFEST_BYTES {
+        return Err(anyhow!(
+            "manifest too large: {} bytes > {} bytes",
+            bytes.len(),
+            MAX_MANIFEST_BYTES
+        ));
+    }
+    let manifest: UploadManifest = serde_json::from_slice(&bytes)?;
+    verify_manifest(&manifest, unlock)?;
+    Ok(manifest)
+}
+
+/// `photo.jpg.manifest.json` is exposed as `photo.jpg`.
+fn object_name(path: &Path) -> OsString {
+    let name = path
+        .file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy();
+    let name = name.strip_suffix(".json").unwrap_or(&name);
+    let name = name.strip_suffix(".manifest").unwrap_or(name);
+    OsString::from(name)
+}
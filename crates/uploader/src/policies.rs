@@ -0,0 +1,311 @@
+//! Where autopilot's sentinel policy rows come from: a local
+//! `--policy-file`, or a sentinel service at `--policy-url`. The service
+//! answers with a signed document,
+//!
+//! ```json
+//! {"issued_at_ms": …, "policies": [ … ], "public_key": "<hex>", "signature": "<hex>"}
+//! ```
+//!
+//! where `public_key` is the protobuf-encoded ed25519 key of the sentinel
+//! and `signature` covers `NEURO:POLICY:<issued_at_ms>:<sha256>`, the hash
+//! taken over `policies` serialized compactly with object keys sorted. A
+//! document is accepted only when signed by `--policy-signer` and issued
+//! within `--policy-max-age`; `--allow-unsigned` also takes a bare array of
+//! rows, or an unsigned or stale document.
+//!
+//! The last accepted response is kept with its ETag, in memory and in
+//! `--policy-cache` when given, so later fetches revalidate instead of
+//! downloading again, and an unreachable sentinel falls back to the cached
+//! document while it is still fresh.
+
+use crate::{sha256_hex, AutopilotArgs, SentinelPolicyRow};
+use anyhow::{anyhow, Result};
+use libp2p::identity::PublicKey;
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::time::Duration;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(clap::Args, Debug)]
+pub struct PolicyFetchArgs {
+    /// PeerId of the sentinel key `--policy-url` documents must be signed
+    /// with.
+    #[arg(long, requires = "policy_url")]
+    policy_signer: Option<String>,
+
+    /// Refuse `--policy-url` documents issued longer ago than this, e.g.
+    /// `6h` or `1d`.
+    #[arg(long, default_value = "24h", value_parser = crate::daemon::parse_duration)]
+    policy_max_age: Duration,
+
+    /// Keep the last document fetched from `--policy-url` in this file, so
+    /// the next run revalidates it by ETag.
+    #[arg(long, requires = "policy_url")]
+    policy_cache: Option<String>,
+
+    /// Accept unsigned or stale documents from `--policy-url`. Documents
+    /// signed by a key other than `--policy-signer` are still refused.
+    #[arg(long, default_value_t = false)]
+    allow_unsigned: bool,
+}
+
+pub enum PolicySource {
+    File(String),
+    Url(PolicyFetcher),
+}
+
+impl PolicySource {
+    pub fn from_args(args: &AutopilotArgs) -> Result<Self> {
+        if let Some(url) = &args.policy_url {
+            return Ok(Self::Url(PolicyFetcher::new(url, &args.policy_fetch)?));
+        }
+        args.policy_file
+            .clone()
+            .map(Self::File)
+            .ok_or_else(|| anyhow!("one of --policy-file or --policy-url is required"))
+    }
+
+    /// The local file policies are read from or cached in, if any.
+    pub fn local_path(&self) -> Option<&str> {
+        match self {
+            Self::File(path) => Some(path),
+            Self::Url(fetcher) => fetcher.cache_path.as_deref(),
+        }
+    }
+
+    pub async fn load(&mut self) -> Result<Vec<SentinelPolicyRow>> {
+        match self {
+            Self::File(path) => Ok(serde_json::from_slice(&std::fs::read(path)?)?),
+            Self::Url(fetcher) => fetcher.fetch().await,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedDocument {
+    etag: Option<String>,
+    body: String,
+}
+
+#[derive(Deserialize)]
+struct SignedPolicies {
+    issued_at_ms: u64,
+    policies: serde_json::Value,
+    #[serde(default)]
+    public_key: Option<String>,
+    #[serde(default)]
+    signature: Option<String>,
+}
+
+pub struct PolicyFetcher {
+    url: String,
+    signer: Option<PeerId>,
+    max_age: Duration,
+    allow_unsigned: bool,
+    cache_path: Option<String>,
+    cached: Option<CachedDocument>,
+    client: reqwest::Client,
+}
+
+impl PolicyFetcher {
+    fn new(url: &str, args: &PolicyFetchArgs) -> Result<Self> {
+        if !(url.starts_with("https://") || url.starts_with("http://")) {
+            return Err(anyhow!("--policy-url must be an http(s) URL: {url}"));
+        }
+        let signer = args
+            .policy_signer
+            .as_deref()
+            .map(|s| PeerId::from_str(s).map_err(|e| anyhow!("invalid --policy-signer {s}: {e}")))
+            .transpose()?;
+        if signer.is_none() && !args.allow_unsigned {
+            return Err(anyhow!("--policy-url needs --policy-signer, or --allow-unsigned"));
+        }
+        // An unreadable cache only costs a full download.
+        let cached = args
+            .policy_cache
+            .as_deref()
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok());
+        Ok(Self {
+            url: url.to_string(),
+            signer,
+            max_age: args.policy_max_age,
+            allow_unsigned: args.allow_unsigned,
+            cache_path: args.policy_cache.clone(),
+            cached,
+            client: reqwest::Client::builder().timeout(FETCH_TIMEOUT).build()?,
+        })
+    }
+
+    /// Fetches, or revalidates, the policy document and returns its rows.
+    async fn fetch(&mut self) -> Result<Vec<SentinelPolicyRow>> {
+        let mut request = self.client.get(&self.url);
+        if let Some(etag) = self.cached.as_ref().and_then(|c| c.etag.as_deref()) {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        let resp = match request.send().await {
+            Ok(resp) => resp,
+            Err(e) => {
+                let Some(cached) = &self.cached else {
+                    return Err(e.into());
+                };
+                eprintln!("autopilot policy fetch failed ({e}); using the cached document");
+                return self.accept(&cached.body);
+            }
+        };
+        if resp.status() == reqwest::StatusCode::NOT_MODIFIED {
+            if let Some(cached) = &self.cached {
+                return self.accept(&cached.body);
+            }
+        }
+        let resp = resp.error_for_status()?;
+        let etag = resp
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let body = resp.text().await?;
+        let rows = self.accept(&body)?;
+
+        let cached = CachedDocument { etag, body };
+        if let Some(path) = &self.cache_path {
+            if let Err(e) = std::fs::write(path, serde_json::to_vec(&cached)?) {
+                eprintln!("autopilot cannot write policy cache {path}: {e}");
+            }
+        }
+        self.cached = Some(cached);
+        Ok(rows)
+    }
+
+    /// The rows of a policy document, if it is signed by the expected key
+    /// and fresh enough.
+    fn accept(&self, body: &str) -> Result<Vec<SentinelPolicyRow>> {
+        let value: serde_json::Value = serde_json::from_str(body)?;
+        if value.is_array() {
+            self.allow_unsigned_or(|| anyhow!("policy document from {} is unsigned", self.url))?;
+            return Ok(serde_json::from_value(value)?);
+        }
+
+        let document: SignedPolicies = serde_json::from_value(value)?;
+        match (&document.public_key, &document.signature, &self.signer) {
+            (Some(public_key), Some(signature), Some(signer)) => {
+                verify(&document, public_key, signature, signer)?;
+            }
+            (Some(_), Some(_), None) => {
+                eprintln!("autopilot accepting policies from {} without checking their signer", self.url);
+            }
+            _ => self.allow_unsigned_or(|| anyhow!("policy document from {} is unsigned", self.url))?,
+        }
+
+        let now_ms = chrono::Utc::now().timestamp_millis().max(0) as u64;
+        let age = Duration::from_millis(now_ms.saturating_sub(document.issued_at_ms));
+        if age > self.max_age {
+            self.allow_unsigned_or(|| {
+                anyhow!(
+                    "policy document from {} is stale: issued {}s ago, --policy-max-age is {}s",
+                    self.url,
+                    age.as_secs(),
+                    self.max_age.as_secs()
+                )
+            })?;
+        }
+        Ok(serde_json::from_value(document.policies)?)
+    }
+
+    /// Lets a document through with a warning under `--allow-unsigned`,
+    /// and refuses it otherwise.
+    fn allow_unsigned_or(&self, refusal: impl FnOnce() -> anyhow::Error) -> Result<()> {
+        let refusal = refusal();
+        if !self.allow_unsigned {
+            return Err(anyhow!("{refusal}; pass --allow-unsigned to accept it"));
+        }
+        eprintln!("autopilot accepting policies anyway: {refusal}");
+        Ok(())
+    }
+}
+
+fn signed_payload(issued_at_ms: u64, policies: &serde_json::Value) -> Result<Vec<u8>> {
+    let digest = sha256_hex(&serde_json::to_vec(policies)?);
+    Ok(format!("NEURO:POLICY:{issued_at_ms}:{digest}").into_bytes())
+}
+
+fn verify(document: &SignedPolicies, public_key: &str, signature: &str, signer: &PeerId) -> Result<()> {
+    let public_key = hex::decode(public_key)
+        .ok()
+        .and_then(|bytes| PublicKey::try_decode_protobuf(&bytes).ok())
+        .ok_or_else(|| anyhow!("policy document has a malformed public key"))?;
+    if PeerId::from(public_key.clone()) != *signer {
+        return Err(anyhow!("policy document is signed by {}, not --policy-signer", PeerId::from(public_key)));
+    }
+    let signature = hex::decode(signature).map_err(|_| anyhow!("policy document has a malformed signature"))?;
+    if !public_key.verify(&signed_payload(document.issued_at_ms, &document.policies)?, &signature) {
+        return Err(anyhow!("policy document signature does not verify"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::identity::Keypair;
+
+    fn signed(key: &Keypair, issued_at_ms: u64) -> String {
+        let policies = serde_json::json!([{"peer": "p1", "reputation": 12.5, "recommendation": "quarantine"}]);
+        let signature = key.sign(&signed_payload(issued_at_ms, &policies).unwrap()).unwrap();
+        serde_json::json!({
+            "issued_at_ms": issued_at_ms,
+            "policies": policies,
+            "public_key": hex::encode(key.public().encode_protobuf()),
+            "signature": hex::encode(signature),
+        })
+        .to_string()
+    }
+
+    fn fetcher(signer: Option<PeerId>, allow_unsigned: bool) -> PolicyFetcher {
+        PolicyFetcher {
+            url: "https://sentinel.test/policies".to_string(),
+            signer,
+            max_age: Duration::from_secs(3600),
+            allow_unsigned,
+            cache_path: None,
+            cached: None,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    #[test]
+    fn signed_fresh_documents_from_the_signer_are_accepted() {
+        let key = Keypair::generate_ed25519();
+        let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+        let fetcher = fetcher(Some(key.public().to_peer_id()), false);
+
+        let rows = fetcher.accept(&signed(&key, now_ms)).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].peer, "p1");
+
+        let stale = fetcher.accept(&signed(&key, now_ms - 2 * 3600 * 1000)).unwrap_err();
+        assert!(stale.to_string().contains("stale"), "{stale}");
+        let other = fetcher.accept(&signed(&Keypair::generate_ed25519(), now_ms)).unwrap_err();
+        assert!(other.to_string().contains("not --policy-signer"), "{other}");
+
+        let mut tampered: serde_json::Value = serde_json::from_str(&signed(&key, now_ms)).unwrap();
+        tampered["policies"][0]["reputation"] = 99.0.into();
+        assert!(fetcher.accept(&tampered.to_string()).is_err());
+    }
+
+    #[test]
+    fn unsigned_or_stale_documents_need_allow_unsigned() {
+        let key = Keypair::generate_ed25519();
+        let bare = r#"[{"peer": "p1"}]"#;
+        assert!(fetcher(Some(key.public().to_peer_id()), false).accept(bare).is_err());
+
+        let lenient = fetcher(Some(key.public().to_peer_id()), true);
+        assert_eq!(lenient.accept(bare).unwrap().len(), 1);
+        assert_eq!(lenient.accept(&signed(&key, 0)).unwrap().len(), 1);
+        // A signature from another key is refused even so.
+        let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+        assert!(lenient.accept(&signed(&Keypair::generate_ed25519(), now_ms)).is_err());
+    }
+}
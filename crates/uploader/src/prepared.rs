@@ -0,0 +1,182 @@
+//! The `--prepared` input of `store-prepared`. Two layouts are read:
+//!
+//! - a JSON bundle, `{"salt": …, "shards": [ … ]}`, parsed whole;
+//! - NDJSON: a header line holding the bundle's fields other than `shards`,
+//!   then one shard object per line. Shards are read a line at a time, so
+//!   a prepared upload of any size is stored in memory bounded by
+//!   `--max-inflight-bytes`.
+//!
+//! `store-prepared` walks the shards twice: once to check them and build
+//! the manifest, and again to send them, decoding each shard's bytes only
+//! while it is being stored.
+
+use crate::{decode_b64, sha256_hex};
+use anyhow::{anyhow, Result};
+use neuro_client_sdk::shard_cid_matches;
+use serde::Deserialize;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+/// Everything in a prepared upload but its shards. Unknown fields are
+/// refused so that a one-line JSON bundle is not mistaken for an NDJSON
+/// header.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PreparedHeader {
+    pub salt: String,
+    /// Set by clients that encrypted with derived nonces.
+    #[serde(default)]
+    pub deterministic: bool,
+    pub total_bytes: usize,
+    pub chunk_count: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PreparedUploadShard {
+    pub chunk_index: usize,
+    pub shard_index: usize,
+    pub cid: String,
+    pub payload_len: usize,
+    pub data_shards: usize,
+    pub parity_shards: usize,
+    pub peers: Vec<String>,
+    pub bytes_b64: String,
+}
+
+impl PreparedUploadShard {
+    /// The shard's bytes, checked against its CID.
+    pub fn bytes(&self) -> Result<Vec<u8>> {
+        let bytes = decode_b64(&self.bytes_b64)?;
+        if bytes.is_empty() {
+            return Err(anyhow!("prepared shard {} has empty bytes", self.cid));
+        }
+        if !shard_cid_matches(&self.cid, &bytes) {
+            return Err(anyhow!(
+                "prepared shard cid mismatch cid={} computed={}",
+                self.cid,
+                sha256_hex(&bytes)
+            ));
+        }
+        Ok(bytes)
+    }
+}
+
+#[derive(Deserialize)]
+struct PreparedUploadBundle {
+    salt: String,
+    #[serde(default)]
+    deterministic: bool,
+    total_bytes: usize,
+    chunk_count: usize,
+    shards: Vec<PreparedUploadShard>,
+}
+
+pub enum PreparedSource {
+    Bundle(Vec<PreparedUploadShard>),
+    /// Path of an NDJSON file; shards start on its second line.
+    Ndjson(String),
+}
+
+pub type Shards<'a> = Box<dyn Iterator<Item = Result<PreparedUploadShard>> + 'a>;
+
+impl PreparedSource {
+    /// Reads the header of the prepared upload at `path`, and the shards
+    /// too when it is a JSON bundle.
+    pub fn open(path: &str) -> Result<(PreparedHeader, Self)> {
+        let mut first_line = String::new();
+        BufReader::new(File::open(path)?).read_line(&mut first_line)?;
+        if let Ok(header) = serde_json::from_str::<PreparedHeader>(&first_line) {
+            return Ok((header, Self::Ndjson(path.to_string())));
+        }
+        let bundle: PreparedUploadBundle = serde_json::from_slice(&std::fs::read(path)?)?;
+        let header = PreparedHeader {
+            salt: bundle.salt,
+            deterministic: bundle.deterministic,
+            total_bytes: bundle.total_bytes,
+            chunk_count: bundle.chunk_count,
+        };
+        Ok((header, Self::Bundle(bundle.shards)))
+    }
+
+    /// The shards in file order, starting over on every call. Blank NDJSON
+    /// lines are skipped.
+    pub fn shards(&self) -> Result<Shards<'_>> {
+        match self {
+            Self::Bundle(shards) => Ok(Box::new(shards.iter().cloned().map(Ok))),
+            Self::Ndjson(path) => {
+                let lines = BufReader::new(File::open(path)?).lines().skip(1);
+                Ok(Box::new(lines.enumerate().filter_map(|(n, line)| match line {
+                    Ok(line) if line.trim().is_empty() => None,
+                    Ok(line) => Some(
+                        serde_json::from_str(&line)
+                            .map_err(|e| anyhow!("prepared shard on line {}: {e}", n + 2)),
+                    ),
+                    Err(e) => Some(Err(e.into())),
+                })))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode_b64;
+
+    fn shard_json(data: &[u8]) -> serde_json::Value {
+        serde_json::json!({
+            "chunk_index": 0,
+            "shard_index": 0,
+            "cid": sha256_hex(data),
+            "payload_len": data.len(),
+            "data_shards": 1,
+            "parity_shards": 0,
+            "peers": ["/ip4/127.0.0.1/tcp/9000/p2p/12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN"],
+            "bytes_b64": encode_b64(data),
+        })
+    }
+
+    fn write(dir: &tempfile::TempDir, name: &str, contents: &str) -> String {
+        let path = dir.path().join(name);
+        std::fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn ndjson_shards_are_read_line_by_line_and_bundles_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let header = r#"{"salt":"s","total_bytes":6,"chunk_count":1}"#;
+        let ndjson = format!("{header}\n{}\n\n{}\n", shard_json(b"abc"), shard_json(b"def"));
+        let (header, source) = PreparedSource::open(&write(&dir, "p.ndjson", &ndjson)).unwrap();
+        assert!(matches!(source, PreparedSource::Ndjson(_)));
+        assert_eq!(header.total_bytes, 6);
+        for _ in 0..2 {
+            let shards: Vec<_> = source.shards().unwrap().collect::<Result<_>>().unwrap();
+            assert_eq!(shards.len(), 2);
+            assert_eq!(shards[1].bytes().unwrap(), b"def");
+        }
+
+        // A bundle on a single line has a `shards` field, so it is no header.
+        let bundle = serde_json::json!({
+            "salt": "s", "total_bytes": 3, "chunk_count": 1, "shards": [shard_json(b"abc")],
+        });
+        let (_, source) = PreparedSource::open(&write(&dir, "p.json", &bundle.to_string())).unwrap();
+        assert!(matches!(source, PreparedSource::Bundle(ref s) if s.len() == 1));
+    }
+
+    #[test]
+    fn bad_lines_and_tampered_bytes_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tampered = shard_json(b"abc");
+        tampered["bytes_b64"] = encode_b64(b"abd").into();
+        let ndjson = format!(
+            "{}\n{tampered}\nnot json\n",
+            r#"{"salt":"s","total_bytes":3,"chunk_count":1}"#
+        );
+        let (_, source) = PreparedSource::open(&write(&dir, "p.ndjson", &ndjson)).unwrap();
+        let mut shards = source.shards().unwrap();
+        assert!(shards.next().unwrap().unwrap().bytes().is_err());
+        let err = shards.next().unwrap().unwrap_err().to_string();
+        assert!(err.contains("line 3"), "{err}");
+    }
+}
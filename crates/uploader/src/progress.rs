@@ -0,0 +1,102 @@
+//! Progress reporting for the long-running commands (upload, retrieve,
+//! audit). Commands report through a [`Progress`] handle; with no listener
+//! attached it prints the same `key=value` lines the CLI always has, and with
+//! a listener (the `--tui` dashboard) every event goes down a channel instead
+//! so nothing writes over the screen.
+
+use libp2p::PeerId;
+use std::sync::mpsc;
+
+// Only the dashboard reads the payloads; plain output just needs the lines.
+#[cfg_attr(not(feature = "tui"), allow(dead_code))]
+#[derive(Debug, Clone)]
+pub enum ProgressEvent {
+    /// Emitted once the swarm is warm and the work list is known.
+    Begin {
+        op: &'static str,
+        total: usize,
+    },
+    /// Requests not yet sent and requests awaiting a reply.
+    Queue {
+        pending: usize,
+        inflight: usize,
+    },
+    Sent {
+        peer: PeerId,
+    },
+    /// A verified reply; `bytes` is the shard payload moved.
+    Completed {
+        peer: PeerId,
+        bytes: usize,
+    },
+    /// A request to `peer` failed and was requeued, either to the same peer
+    /// or the next candidate.
+    Retry {
+        peer: PeerId,
+    },
+    Log(String),
+    Warn(String),
+}
+
+#[derive(Default)]
+pub struct Progress {
+    tx: Option<mpsc::Sender<ProgressEvent>>,
+    #[cfg(feature = "tui")]
+    _dashboard: Option<crate::tui::Dashboard>,
+}
+
+impl Progress {
+    /// `tui` selects the full-screen dashboard; otherwise output stays plain.
+    #[cfg(feature = "tui")]
+    pub fn start(tui: bool) -> anyhow::Result<Self> {
+        use std::io::IsTerminal;
+
+        if !tui {
+            return Ok(Self::default());
+        }
+        if !std::io::stdout().is_terminal() {
+            anyhow::bail!("--tui needs stdout to be a terminal");
+        }
+        let (tx, rx) = mpsc::channel();
+        Ok(Self {
+            tx: Some(tx),
+            _dashboard: Some(crate::tui::Dashboard::start(rx)?),
+        })
+    }
+
+    #[cfg(not(feature = "tui"))]
+    pub fn start(tui: bool) -> anyhow::Result<Self> {
+        if tui {
+            anyhow::bail!("--tui requires neuro-uploader built with the `tui` feature");
+        }
+        Ok(Self::default())
+    }
+
+    /// Tears the dashboard down so the command's closing lines (or its
+    /// error) print on a normal terminal.
+    pub fn finish(self) {}
+
+    pub fn emit(&self, event: ProgressEvent) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(event);
+        }
+    }
+
+    pub fn log(&self, line: String) {
+        match &self.tx {
+            Some(tx) => {
+                let _ = tx.send(ProgressEvent::Log(line));
+            }
+            None => println!("{line}"),
+        }
+    }
+
+    pub fn warn(&self, line: String) {
+        match &self.tx {
+            Some(tx) => {
+                let _ = tx.send(ProgressEvent::Warn(line));
+            }
+            None => eprintln!("{line}"),
+        }
+    }
+}
@@ -0,0 +1,342 @@
+//! Store receipt bundles. Every signed `StoreChunkResponse` an upload
+//! collects, and every signed inventory that let dedup skip a shard, is kept
+//! per peer in a bundle written next to the manifest
+//! (`<manifest>.receipts.json`). `verify-receipts` checks the bundle offline:
+//! each signature against the peer that made it, and that every peer the
+//! manifest lists for a shard signed for holding it.
+
+use crate::{decode_b64, encode_b64, extract_peer_id, UploadManifest};
+use anyhow::{anyhow, Result};
+use libp2p::PeerId;
+use neuro_protocol::{HasChunksResponse, StoreChunkResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+pub const RECEIPTS_VERSION: u32 = 1;
+const MAX_RECEIPTS_BYTES: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiptBundle {
+    pub version: u32,
+    /// The `manifest_root` of the manifest the receipts were collected for.
+    pub manifest_root: String,
+    pub created_at_ms: u64,
+    pub peers: Vec<PeerReceipts>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerReceipts {
+    pub peer_id: String,
+    /// Protobuf-encoded public key, base64; shared by all of the peer's
+    /// receipts.
+    pub public_key: String,
+    pub stores: Vec<StoreReceipt>,
+    pub inventories: Vec<InventoryReceipt>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoreReceipt {
+    pub cid: String,
+    pub len: usize,
+    pub timestamp_ms: u64,
+    pub signature: String,
+}
+
+/// A signed `Has` reply; `held` is kept whole because the signature covers
+/// all of it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InventoryReceipt {
+    pub held: Vec<String>,
+    pub timestamp_ms: u64,
+    pub signature: String,
+}
+
+#[derive(Default)]
+pub struct ReceiptCollector {
+    peers: BTreeMap<PeerId, PeerReceipts>,
+}
+
+impl ReceiptCollector {
+    fn peer(&mut self, peer: PeerId, public_key: &[u8]) -> &mut PeerReceipts {
+        self.peers.entry(peer).or_insert_with(|| PeerReceipts {
+            peer_id: peer.to_string(),
+            public_key: encode_b64(public_key),
+            stores: Vec::new(),
+            inventories: Vec::new(),
+        })
+    }
+
+    /// Records a verified store receipt.
+    pub fn record_store(&mut self, peer: PeerId, cid: &str, len: usize, resp: &StoreChunkResponse) {
+        self.peer(peer, &resp.public_key).stores.push(StoreReceipt {
+            cid: cid.to_string(),
+            len,
+            timestamp_ms: resp.timestamp_ms,
+            signature: encode_b64(&resp.signature),
+        });
+    }
+
+    /// Records a verified inventory reply that stood in for store receipts.
+    pub fn record_inventory(&mut self, peer: PeerId, resp: &HasChunksResponse) {
+        self.peer(peer, &resp.public_key)
+            .inventories
+            .push(InventoryReceipt {
+                held: resp.held.clone(),
+                timestamp_ms: resp.timestamp_ms,
+                signature: encode_b64(&resp.signature),
+            });
+    }
+
+    pub fn finish(self, manifest_root: &str) -> ReceiptBundle {
+        ReceiptBundle {
+            version: RECEIPTS_VERSION,
+            manifest_root: manifest_root.to_string(),
+            created_at_ms: chrono::Utc::now().timestamp_millis() as u64,
+            peers: self.peers.into_values().collect(),
+        }
+    }
+}
+
+pub fn receipts_path(manifest_path: &str) -> String {
+    format!("{manifest_path}.receipts.json")
+}
+
+pub fn write(path: &str, bundle: &ReceiptBundle) -> Result<()> {
+    std::fs::write(path, serde_json::to_vec_pretty(bundle)?)?;
+    Ok(())
+}
+
+pub fn read(path: &str) -> Result<ReceiptBundle> {
+    let raw = std::fs::read(path)?;
+    if raw.len() > MAX_RECEIPTS_BYTES {
+        return Err(anyhow!(
+            "receipt bundle too large: {} bytes > {} bytes",
+            raw.len(),
+            MAX_RECEIPTS_BYTES
+        ));
+    }
+    let bundle: ReceiptBundle = serde_json::from_slice(&raw)?;
+    if bundle.version != RECEIPTS_VERSION {
+        return Err(anyhow!("unsupported receipt bundle version {}", bundle.version));
+    }
+    Ok(bundle)
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct VerifyReport {
+    pub peers: usize,
+    pub store_receipts: usize,
+    pub inventory_receipts: usize,
+    /// `(peer, shard)` placements the manifest lists.
+    pub placements: usize,
+    /// Receipts whose signature does not check out, or that name a shard
+    /// the manifest does not place on that peer.
+    pub invalid: Vec<String>,
+    /// Placements no valid receipt covers, as `peer cid`.
+    pub missing: Vec<String>,
+}
+
+impl VerifyReport {
+    pub fn ok(&self) -> bool {
+        self.invalid.is_empty() && self.missing.is_empty()
+    }
+}
+
+/// Checks `bundle` against `manifest` without contacting any peer.
+pub fn verify(bundle: &ReceiptBundle, manifest: &UploadManifest) -> Result<VerifyReport> {
+    if bundle.manifest_root != manifest.manifest_root {
+        return Err(anyhow!(
+            "receipt bundle is for manifest root {}, not {}",
+            bundle.manifest_root,
+            manifest.manifest_root
+        ));
+    }
+    let mut placements: HashSet<(PeerId, &str)> = HashSet::new();
+    for shard in &manifest.shards {
+        for addr in &shard.peers {
+            placements.insert((extract_peer_id(addr)?, shard.cid.as_str()));
+        }
+    }
+
+    let mut report = VerifyReport {
+        peers: bundle.peers.len(),
+        placements: placements.len(),
+        ..VerifyReport::default()
+    };
+    let mut covered: HashSet<(PeerId, &str)> = HashSet::new();
+    for entry in &bundle.peers {
+        let Ok(peer) = entry.peer_id.parse::<PeerId>() else {
+            report.invalid.push(format!("{}: bad peer id", entry.peer_id));
+            continue;
+        };
+        let Ok(public_key) = decode_b64(&entry.public_key) else {
+            report.invalid.push(format!("{peer}: bad public key encoding"));
+            continue;
+        };
+        for store in &entry.stores {
+            report.store_receipts += 1;
+            let resp = StoreChunkResponse {
+                stored: true,
+                timestamp_ms: store.timestamp_ms,
+                signature: decode_b64(&store.signature).unwrap_or_default(),
+                public_key: public_key.clone(),
+            };
+            if !resp.verify_receipt(&peer, &store.cid, store.len) {
+                report.invalid.push(format!("{peer} {}: bad store signature", store.cid));
+            } else if let Some(&(_, cid)) = placements.get(&(peer, store.cid.as_str())) {
+                covered.insert((peer, cid));
+            } else {
+                report.invalid.push(format!("{peer} {}: not placed on this peer", store.cid));
+            }
+        }
+        for inventory in &entry.inventories {
+            report.inventory_receipts += 1;
+            let resp = HasChunksResponse {
+                held: inventory.held.clone(),
+                timestamp_ms: inventory.timestamp_ms,
+                signature: decode_b64(&inventory.signature).unwrap_or_default(),
+                public_key: public_key.clone(),
+            };
+            if !resp.verify_inventory(&peer) {
+                report.invalid.push(format!("{peer}: bad inventory signature"));
+                continue;
+            }
+            // An inventory may list chunks of other uploads; only the ones
+            // this manifest places on the peer count.
+            for cid in &inventory.held {
+                if let Some(&(_, cid)) = placements.get(&(peer, cid.as_str())) {
+                    covered.insert((peer, cid));
+                }
+            }
+        }
+    }
+
+    let mut missing: Vec<String> = placements
+        .difference(&covered)
+        .map(|(peer, cid)| format!("{peer} {cid}"))
+        .collect();
+    missing.sort();
+    report.missing = missing;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::identity::Keypair;
+    use neuro_client_sdk::{HashAlgorithm, ManifestShard, MANIFEST_VERSION};
+
+    fn store_resp(key: &Keypair, cid: &str, len: usize) -> StoreChunkResponse {
+        let timestamp_ms = 1_700_000_000_000;
+        StoreChunkResponse {
+            stored: true,
+            timestamp_ms,
+            signature: key
+                .sign(&StoreChunkResponse::receipt_payload(cid, len, timestamp_ms))
+                .unwrap(),
+            public_key: key.public().encode_protobuf(),
+        }
+    }
+
+    fn has_resp(key: &Keypair, held: &[&str]) -> HasChunksResponse {
+        let held: Vec<String> = held.iter().map(|c| c.to_string()).collect();
+        let timestamp_ms = 1_700_000_000_000;
+        HasChunksResponse {
+            signature: key
+                .sign(&HasChunksResponse::inventory_payload(&held, timestamp_ms))
+                .unwrap(),
+            held,
+            timestamp_ms,
+            public_key: key.public().encode_protobuf(),
+        }
+    }
+
+    fn manifest(placements: &[(&str, Vec<PeerId>)]) -> UploadManifest {
+        let shards = placements
+            .iter()
+            .enumerate()
+            .map(|(i, (cid, peers))| ManifestShard {
+                chunk_index: 0,
+                shard_index: i,
+                cid: cid.to_string(),
+                payload_len: 4,
+                data_shards: 1,
+                parity_shards: 0,
+                peers: peers
+                    .iter()
+                    .map(|p| format!("/ip4/127.0.0.1/tcp/4001/p2p/{p}"))
+                    .collect(),
+                audit_challenges: Vec::new(),
+                audit_tokens: Vec::new(),
+                merkle_proof: Vec::new(),
+            })
+            .collect();
+        UploadManifest {
+            version: MANIFEST_VERSION.to_string(),
+            salt: String::new(),
+            manifest_root: "root".to_string(),
+            total_bytes: 4,
+            chunk_count: 1,
+            shards,
+            gateways: Vec::new(),
+            deterministic: false,
+            hash_algorithm: HashAlgorithm::Sha256,
+            recipients: Vec::new(),
+            outer_code: None,
+            peer_addrs: BTreeMap::new(),
+            holes: Vec::new(),
+            manifest_hash: String::new(),
+            manifest_auth_tag: String::new(),
+        }
+    }
+
+    #[test]
+    fn bundle_covering_every_placement_verifies() {
+        let (a, b) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+        let (pa, pb) = (a.public().to_peer_id(), b.public().to_peer_id());
+        let manifest = manifest(&[("cid-1", vec![pa, pb]), ("cid-2", vec![pa])]);
+
+        let mut collector = ReceiptCollector::default();
+        collector.record_store(pa, "cid-1", 10, &store_resp(&a, "cid-1", 10));
+        collector.record_store(pb, "cid-1", 10, &store_resp(&b, "cid-1", 10));
+        // cid-2 was already on the peer; its inventory also names a chunk
+        // of some other upload.
+        collector.record_inventory(pa, &has_resp(&a, &["cid-2", "elsewhere"]));
+        let bundle = collector.finish("root");
+        assert_eq!(bundle.peers.len(), 2);
+
+        let report = verify(&bundle, &manifest).unwrap();
+        assert!(report.ok(), "{report:?}");
+        assert_eq!(report.placements, 3);
+        assert_eq!(report.store_receipts, 2);
+        assert_eq!(report.inventory_receipts, 1);
+    }
+
+    #[test]
+    fn tampered_or_incomplete_bundles_fail() {
+        let (a, b) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+        let (pa, pb) = (a.public().to_peer_id(), b.public().to_peer_id());
+        let manifest = manifest(&[("cid-1", vec![pa, pb])]);
+
+        let mut collector = ReceiptCollector::default();
+        collector.record_store(pa, "cid-1", 10, &store_resp(&a, "cid-1", 10));
+        let mut bundle = collector.finish("root");
+        let report = verify(&bundle, &manifest).unwrap();
+        assert!(!report.ok());
+        assert_eq!(report.missing, vec![format!("{pb} cid-1")]);
+
+        bundle.peers[0].stores[0].len = 11;
+        let report = verify(&bundle, &manifest).unwrap();
+        assert_eq!(report.invalid.len(), 1);
+        assert_eq!(report.missing.len(), 2);
+
+        // Receipts from one peer cannot be passed off as another's.
+        let mut collector = ReceiptCollector::default();
+        collector.record_store(pb, "cid-1", 10, &store_resp(&a, "cid-1", 10));
+        let report = verify(&collector.finish("root"), &manifest).unwrap();
+        assert_eq!(report.invalid.len(), 1);
+
+        let bundle = ReceiptCollector::default().finish("other-root");
+        assert!(verify(&bundle, &manifest).is_err());
+    }
+}
@@ -0,0 +1,470 @@
+//! `serve`: a long-running uploader for backup agents and CI. Requests are
+//! JSON-RPC 2.0, one per line, read from stdin (`--stdio`) or from each
+//! connection to a unix socket (`--unix-socket`); answers go back the same
+//! way, also one per line, in the order operations finish.
+//!
+//! `upload`, `retrieve` and `audit` take the command's options as an object
+//! keyed by flag name (`{"file": "a.bin", "peer": [...], "password_file":
+//! "pw"}`) and answer with the report the command would write to
+//! `--report-out`. They run concurrently and share warm peer connections
+//! through `swarm_pool`. `status` lists what is running; `cancel` takes
+//! `{"id": <request id>}` and stops that operation gracefully: it drains its
+//! in-flight chunk requests and the cancelled request is answered with a
+//! `-32800` error carrying the partial report as `data`. Ctrl-C cancels
+//! every operation the same way before the server exits.
+
+use crate::cancel::Cancelled;
+use crate::{audit_manifest, run_retrieve, run_upload, swarm_pool, AuditArgs, RetrieveArgs, UploadArgs};
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::FromRawFd;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The operation ran and failed; the message is its error chain.
+const OPERATION_FAILED: i64 = -32000;
+/// Answer to a request stopped by `cancel` (as in LSP).
+const REQUEST_CANCELLED: i64 = -32800;
+
+#[derive(clap::Args, Debug)]
+#[command(group(clap::ArgGroup::new("transport").required(true).args(["stdio", "unix_socket"])))]
+pub struct ServeArgs {
+    /// Read requests on stdin and answer on stdout. Progress lines the
+    /// commands would print to stdout go to stderr instead.
+    #[arg(long, default_value_t = false)]
+    stdio: bool,
+
+    /// Listen on this unix socket (created mode 0600); each connection is
+    /// its own session, but `status` and `cancel` see every operation.
+    #[arg(long)]
+    unix_socket: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    jsonrpc: Option<String>,
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+type Responder = mpsc::UnboundedSender<Value>;
+
+fn reply(tx: &Responder, id: Value, outcome: Result<Value, RpcError>) {
+    let message = match outcome {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(e) => {
+            let mut error = json!({"code": e.code, "message": e.message});
+            if let Some(data) = e.data {
+                error["data"] = data;
+            }
+            json!({"jsonrpc": "2.0", "id": id, "error": error})
+        }
+    };
+    let _ = tx.send(message);
+}
+
+enum Operation {
+    Upload(Box<UploadArgs>),
+    Retrieve(Box<RetrieveArgs>),
+    Audit(Box<AuditArgs>),
+}
+
+impl Operation {
+    /// Parses `params` with the command's own argument parser, so defaults
+    /// and validation match the CLI exactly.
+    fn parse(method: &str, params: &Value) -> Result<Self, String> {
+        let argv = argv(method, params)?;
+        let operation = match method {
+            "upload" => Operation::Upload(Box::new(UploadArgs::try_parse_from(&argv).map_err(|e| e.to_string())?)),
+            "retrieve" => Operation::Retrieve(Box::new(RetrieveArgs::try_parse_from(&argv).map_err(|e| e.to_string())?)),
+            "audit" => Operation::Audit(Box::new(AuditArgs::try_parse_from(&argv).map_err(|e| e.to_string())?)),
+            other => return Err(format!("{other} is not an operation")),
+        };
+        let (report_out, tui) = match &operation {
+            Operation::Upload(args) => (&args.report_out, args.tui),
+            Operation::Retrieve(args) => (&args.report_out, args.tui),
+            Operation::Audit(args) => (&args.report_out, args.tui),
+        };
+        if report_out.is_some() || tui {
+            return Err("report_out and tui are not available over serve; the report is the result".to_string());
+        }
+        Ok(operation)
+    }
+
+    /// Runs the command with its report redirected to a temporary file and
+    /// returns that report; a cancelled run fails with the partial one.
+    async fn run(mut self, report_path: PathBuf, cancel: CancellationToken) -> Result<Value, RpcError> {
+        let path = Some(report_path.to_string_lossy().into_owned());
+        match &mut self {
+            Operation::Upload(args) => args.report_out = path,
+            Operation::Retrieve(args) => args.report_out = path,
+            Operation::Audit(args) => args.report_out = path,
+        }
+        let outcome = match self {
+            Operation::Upload(args) => run_upload(*args, cancel).await,
+            Operation::Retrieve(args) => run_retrieve(*args, cancel).await,
+            // Not `run_audit`: catalog stamping stays with the CLI.
+            Operation::Audit(args) => audit_manifest(*args, cancel).await,
+        };
+        let report = std::fs::read(&report_path)
+            .ok()
+            .and_then(|raw| serde_json::from_slice::<Value>(&raw).ok());
+        let _ = std::fs::remove_file(&report_path);
+        match outcome {
+            Ok(()) => report.ok_or_else(|| RpcError::new(OPERATION_FAILED, "the operation wrote no report")),
+            Err(e) if e.is::<Cancelled>() => Err(RpcError {
+                data: report,
+                ..RpcError::new(REQUEST_CANCELLED, e.to_string())
+            }),
+            Err(e) => Err(RpcError::new(OPERATION_FAILED, format!("{e:#}"))),
+        }
+    }
+}
+
+/// `{"peer": ["a", "b"], "no_dedup": true}` as
+/// `["upload", "--peer=a", "--peer=b", "--no-dedup"]`.
+fn argv(method: &str, params: &Value) -> Result<Vec<String>, String> {
+    let mut argv = vec![method.to_string()];
+    let options = match params {
+        Value::Null => return Ok(argv),
+        Value::Object(options) => options,
+        _ => return Err("params must be an object keyed by option name".to_string()),
+    };
+    for (name, value) in options {
+        let flag = format!("--{}", name.replace('_', "-"));
+        let scalar = |value: &Value| match value {
+            Value::String(s) => Ok(s.clone()),
+            Value::Number(n) => Ok(n.to_string()),
+            _ => Err(format!("{name} must be a string, number or list of them")),
+        };
+        match value {
+            Value::Bool(true) => argv.push(flag),
+            Value::Bool(false) | Value::Null => {}
+            Value::Array(items) => {
+                for item in items {
+                    argv.push(format!("{flag}={}", scalar(item)?));
+                }
+            }
+            other => argv.push(format!("{flag}={}", scalar(other)?)),
+        }
+    }
+    Ok(argv)
+}
+
+struct Running {
+    id: Value,
+    method: String,
+    started: Instant,
+    cancel: CancellationToken,
+}
+
+#[derive(Default)]
+struct Counters {
+    completed: AtomicU64,
+    failed: AtomicU64,
+    cancelled: AtomicU64,
+}
+
+struct Server {
+    started: Instant,
+    /// Parent of every operation's token; cancelled on Ctrl-C.
+    shutdown: CancellationToken,
+    running: Mutex<HashMap<String, Running>>,
+    counters: Counters,
+    next_report: AtomicU64,
+}
+
+impl Server {
+    fn handle(self: &Arc<Self>, line: &str, tx: &Responder) {
+        let request: Request = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => return reply(tx, Value::Null, Err(RpcError::new(PARSE_ERROR, e.to_string()))),
+        };
+        let Some(id) = request.id.filter(|id| !id.is_null()) else {
+            return reply(tx, Value::Null, Err(RpcError::new(INVALID_REQUEST, "requests need an id")));
+        };
+        if request.jsonrpc.as_deref().is_some_and(|v| v != "2.0") {
+            return reply(tx, id, Err(RpcError::new(INVALID_REQUEST, "only JSON-RPC 2.0 is spoken")));
+        }
+        match request.method.as_str() {
+            "status" => reply(tx, id, Ok(self.status())),
+            "cancel" => {
+                let outcome = self.cancel(&request.params);
+                reply(tx, id, outcome)
+            }
+            "upload" | "retrieve" | "audit" => self.start(id, request.method, &request.params, tx),
+            other => reply(tx, id, Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method {other}")))),
+        }
+    }
+
+    fn start(self: &Arc<Self>, id: Value, method: String, params: &Value, tx: &Responder) {
+        let key = id.to_string();
+        let mut running = self.running.lock().unwrap();
+        if running.contains_key(&key) {
+            return reply(tx, id, Err(RpcError::new(INVALID_REQUEST, format!("request {key} is already running"))));
+        }
+        let operation = match Operation::parse(&method, params) {
+            Ok(operation) => operation,
+            Err(e) => return reply(tx, id, Err(RpcError::new(INVALID_PARAMS, e))),
+        };
+        let report_path = std::env::temp_dir().join(format!(
+            "neuro-serve-{}-{}.json",
+            std::process::id(),
+            self.next_report.fetch_add(1, Ordering::SeqCst)
+        ));
+
+        let cancel = self.shutdown.child_token();
+        let server = self.clone();
+        let (task_key, task_id, task_tx, task_cancel) = (key.clone(), id.clone(), tx.clone(), cancel.clone());
+        // `running` stays locked until the entry is in, so the task cannot
+        // finish before it is registered.
+        tokio::spawn(async move {
+            let outcome = operation.run(report_path, task_cancel).await;
+            server.finish(&task_key, &outcome);
+            reply(&task_tx, task_id, outcome);
+        });
+        running.insert(
+            key,
+            Running {
+                id,
+                method,
+                started: Instant::now(),
+                cancel,
+            },
+        );
+    }
+
+    fn finish(&self, key: &str, outcome: &Result<Value, RpcError>) {
+        self.running.lock().unwrap().remove(key);
+        let counter = match outcome {
+            Ok(_) => &self.counters.completed,
+            Err(e) if e.code == REQUEST_CANCELLED => &self.counters.cancelled,
+            Err(_) => &self.counters.failed,
+        };
+        counter.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn cancel(&self, params: &Value) -> Result<Value, RpcError> {
+        let target = params
+            .get("id")
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "cancel needs the id of the request to stop"))?;
+        let key = target.to_string();
+        let running = self.running.lock().unwrap();
+        let operation = running
+            .get(&key)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("no running request {key}")))?;
+        operation.cancel.cancel();
+        Ok(json!({ "cancelling": target }))
+    }
+
+    fn status(&self) -> Value {
+        let running: Vec<Value> = self
+            .running
+            .lock()
+            .unwrap()
+            .values()
+            .map(|op| {
+                json!({
+                    "id": op.id,
+                    "method": op.method,
+                    "elapsed_ms": op.started.elapsed().as_millis() as u64,
+                    "cancelling": op.cancel.is_cancelled(),
+                })
+            })
+            .collect();
+        json!({
+            "uptime_secs": self.started.elapsed().as_secs(),
+            "running": running,
+            "completed": self.counters.completed.load(Ordering::SeqCst),
+            "failed": self.counters.failed.load(Ordering::SeqCst),
+            "cancelled": self.counters.cancelled.load(Ordering::SeqCst),
+            "idle_swarms": swarm_pool::idle_count(),
+        })
+    }
+}
+
+/// Answers requests from `reader` on `writer` until `reader` ends or the
+/// server shuts down, then waits for that session's operations to answer.
+async fn session<R, W>(server: Arc<Server>, reader: R, mut writer: W) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let (tx, mut rx) = mpsc::unbounded_channel::<Value>();
+    let writer_task = tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            let mut line = serde_json::to_vec(&message)?;
+            line.push(b'\n');
+            writer.write_all(&line).await?;
+            writer.flush().await?;
+        }
+        Ok::<_, anyhow::Error>(())
+    });
+
+    let mut lines = BufReader::new(reader).lines();
+    loop {
+        let line = tokio::select! {
+            line = lines.next_line() => line?,
+            _ = server.shutdown.cancelled() => None,
+        };
+        let Some(line) = line else {
+            break;
+        };
+        if !line.trim().is_empty() {
+            server.handle(&line, &tx);
+        }
+    }
+    drop(tx);
+    writer_task.await.map_err(|e| anyhow!("serve writer crashed: {e}"))?
+}
+
+pub(crate) async fn run_serve(args: ServeArgs) -> Result<()> {
+    swarm_pool::enable();
+    let server = Arc::new(Server {
+        started: Instant::now(),
+        shutdown: CancellationToken::new(),
+        running: Mutex::new(HashMap::new()),
+        counters: Counters::default(),
+        next_report: AtomicU64::new(0),
+    });
+
+    let shutdown = server.shutdown.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("uploader serve stopping: cancelling running operations");
+            shutdown.cancel();
+        }
+    });
+
+    if args.stdio {
+        let out = redirect_stdout()?;
+        eprintln!("uploader serve listening on stdio");
+        return session(server, tokio::io::stdin(), out).await;
+    }
+    let Some(path) = args.unix_socket else {
+        return Err(anyhow!("one of --stdio or --unix-socket is required"));
+    };
+    if path.exists() {
+        if UnixStream::connect(&path).await.is_ok() {
+            return Err(anyhow!("another server is listening on {}", path.display()));
+        }
+        std::fs::remove_file(&path)?;
+    }
+    let listener = UnixListener::bind(&path)?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    println!("uploader serve listening on {}", path.display());
+
+    let result = loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => {
+                    let server = server.clone();
+                    tokio::spawn(async move {
+                        let (reader, writer) = stream.into_split();
+                        if let Err(e) = session(server, reader, writer).await {
+                            eprintln!("uploader serve session ended: {e:#}");
+                        }
+                    });
+                }
+                Err(e) => break Err(e.into()),
+            },
+            _ = server.shutdown.cancelled() => break Ok(()),
+        }
+    };
+    let _ = std::fs::remove_file(&path);
+    // Sessions answer their cancelled operations before the process ends.
+    while !server.running.lock().unwrap().is_empty() {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    result
+}
+
+/// Keeps the real stdout for answers and points fd 1 at stderr, so the
+/// commands' progress lines cannot interleave with them.
+fn redirect_stdout() -> Result<tokio::fs::File> {
+    use std::io::Write;
+    std::io::stdout().flush()?;
+    // SAFETY: dup/dup2 on the process's own standard descriptors; the
+    // duplicate is owned by the returned file from here on.
+    let answers = unsafe {
+        let fd = libc::dup(libc::STDOUT_FILENO);
+        if fd < 0 || libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        std::fs::File::from_raw_fd(fd)
+    };
+    Ok(tokio::fs::File::from_std(answers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn params_become_the_commands_flags() {
+        let params = json!({
+            "file": "in.bin",
+            "peer": ["/ip4/127.0.0.1/tcp/1/p2p/a", "/ip4/127.0.0.1/tcp/2/p2p/b"],
+            "replica_factor": 3,
+            "no_dedup": true,
+            "deterministic": false,
+        });
+        let argv = argv("upload", &params).unwrap();
+        assert_eq!(argv[0], "upload");
+        assert!(argv.contains(&"--file=in.bin".to_string()));
+        assert!(argv.contains(&"--replica-factor=3".to_string()));
+        assert!(argv.contains(&"--no-dedup".to_string()));
+        assert_eq!(argv.iter().filter(|a| a.starts_with("--peer=")).count(), 2);
+        assert!(!argv.iter().any(|a| a.contains("deterministic")));
+    }
+
+    #[test]
+    fn operations_are_parsed_like_the_cli() {
+        let params = json!({"manifest": "m.json", "password": "pw", "sample": 4});
+        let Ok(Operation::Audit(args)) = Operation::parse("audit", &params) else {
+            panic!("audit params should parse");
+        };
+        assert_eq!(args.sample, 4);
+        assert_eq!(args.concurrency, 8);
+
+        let unknown = Operation::parse("retrieve", &json!({"manifest": "m.json", "bogus": 1}));
+        assert!(unknown.is_err());
+        let nested = argv("upload", &json!({"file": {"path": "x"}}));
+        assert!(nested.is_err());
+        let tui = Operation::parse("audit", &json!({"manifest": "m.json", "tui": true}));
+        assert!(tui.is_err());
+    }
+}
@@ -0,0 +1,119 @@
+//! Idle client swarms kept for reuse by `serve`, so a long-lived uploader
+//! dials each peer once instead of once per operation. Outside `serve` the
+//! pool is off and every checkout builds a fresh swarm that is dropped with
+//! its operation.
+
+use crate::{extract_peer_id, make_client_swarm, UploaderBehaviour};
+use anyhow::Result;
+use futures::StreamExt;
+use libp2p::{Multiaddr, Swarm};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+/// Swarms beyond this many idle ones are dropped when their operation ends.
+const MAX_IDLE: usize = 4;
+/// How long pooled connections survive without traffic.
+pub(crate) const IDLE_CONNECTION_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static IDLE: Mutex<Vec<IdleSwarm>> = Mutex::new(Vec::new());
+
+/// A parked swarm is still polled, so its connections answer keep-alives and
+/// replies to requests its last operation gave up on are drained.
+struct IdleSwarm {
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<Swarm<UploaderBehaviour>>,
+}
+
+pub(crate) fn enable() {
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+pub(crate) fn enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+pub(crate) fn idle_count() -> usize {
+    IDLE.lock().unwrap().len()
+}
+
+/// A client swarm on loan to one operation; returned to the pool on drop.
+pub(crate) struct PooledSwarm {
+    swarm: Option<Swarm<UploaderBehaviour>>,
+}
+
+/// An idle swarm dialling whichever of `peers` it is not connected to, or a
+/// new one from `make_client_swarm`.
+pub(crate) async fn checkout(peers: &[String]) -> Result<PooledSwarm> {
+    let idle = if enabled() { IDLE.lock().unwrap().pop() } else { None };
+    let swarm = match idle {
+        Some(idle) => {
+            let _ = idle.stop.send(());
+            match idle.task.await {
+                Ok(mut swarm) => {
+                    dial_missing(&mut swarm, peers)?;
+                    swarm
+                }
+                Err(_) => make_client_swarm(peers)?.0,
+            }
+        }
+        None => make_client_swarm(peers)?.0,
+    };
+    Ok(PooledSwarm { swarm: Some(swarm) })
+}
+
+fn dial_missing(swarm: &mut Swarm<UploaderBehaviour>, peers: &[String]) -> Result<()> {
+    for addr in peers {
+        let ma: Multiaddr = addr.parse()?;
+        let pid = extract_peer_id(addr)?;
+        swarm.add_peer_address(pid, ma.clone());
+        if !swarm.is_connected(&pid) {
+            let _ = swarm.dial(ma);
+        }
+    }
+    Ok(())
+}
+
+impl Deref for PooledSwarm {
+    type Target = Swarm<UploaderBehaviour>;
+
+    fn deref(&self) -> &Self::Target {
+        self.swarm.as_ref().expect("swarm is present until drop")
+    }
+}
+
+impl DerefMut for PooledSwarm {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.swarm.as_mut().expect("swarm is present until drop")
+    }
+}
+
+impl Drop for PooledSwarm {
+    fn drop(&mut self) {
+        let Some(mut swarm) = self.swarm.take() else {
+            return;
+        };
+        if !enabled() {
+            return;
+        }
+        let mut idle = IDLE.lock().unwrap();
+        if idle.len() >= MAX_IDLE {
+            return;
+        }
+        let (stop, mut stopped) = oneshot::channel();
+        let task = tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = &mut stopped => break,
+                    _ = swarm.select_next_some() => {}
+                }
+            }
+            swarm
+        });
+        idle.push(IdleSwarm { stop, task });
+    }
+}
@@ -0,0 +1,294 @@
+//! Full-screen `--tui` dashboard fed by [`crate::progress`]. It runs on its
+//! own thread so a slow terminal never stalls the swarm loop, and tears the
+//! screen down when dropped, so errors returned by the command print on a
+//! normal terminal.
+
+use crate::progress::ProgressEvent;
+use libp2p::PeerId;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Gauge, List, ListItem, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(5);
+const LOG_LINES: usize = 500;
+
+pub struct Dashboard {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl Dashboard {
+    pub fn start(rx: mpsc::Receiver<ProgressEvent>) -> anyhow::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        let handle = std::thread::Builder::new()
+            .name("uploader-tui".to_string())
+            .spawn(move || {
+                let mut terminal = ratatui::init();
+                let result = run(&mut terminal, rx, &flag);
+                ratatui::restore();
+                result
+            })?;
+        Ok(Self {
+            stop,
+            handle: Some(handle),
+        })
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            if let Ok(Err(e)) = handle.join() {
+                eprintln!("uploader tui error: {e}");
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct PeerStats {
+    sent: usize,
+    completed: usize,
+    retries: usize,
+    bytes: u64,
+    recent: VecDeque<(Instant, usize)>,
+}
+
+impl PeerStats {
+    fn throughput(&self, now: Instant, elapsed: Duration) -> f64 {
+        let window = THROUGHPUT_WINDOW.min(elapsed).as_secs_f64().max(0.1);
+        let bytes: usize = self
+            .recent
+            .iter()
+            .filter(|(at, _)| now.duration_since(*at) <= THROUGHPUT_WINDOW)
+            .map(|(_, n)| n)
+            .sum();
+        bytes as f64 / window
+    }
+}
+
+struct State {
+    op: &'static str,
+    total: usize,
+    done: usize,
+    pending: usize,
+    inflight: usize,
+    retries: usize,
+    started: Instant,
+    peers: BTreeMap<PeerId, PeerStats>,
+    log: VecDeque<(bool, String)>,
+}
+
+impl State {
+    fn new() -> Self {
+        Self {
+            op: "starting",
+            total: 0,
+            done: 0,
+            pending: 0,
+            inflight: 0,
+            retries: 0,
+            started: Instant::now(),
+            peers: BTreeMap::new(),
+            log: VecDeque::new(),
+        }
+    }
+
+    fn apply(&mut self, event: ProgressEvent) {
+        match event {
+            ProgressEvent::Begin { op, total } => {
+                self.op = op;
+                self.total = total;
+                self.started = Instant::now();
+            }
+            ProgressEvent::Queue { pending, inflight } => {
+                self.pending = pending;
+                self.inflight = inflight;
+            }
+            ProgressEvent::Sent { peer } => self.peers.entry(peer).or_default().sent += 1,
+            ProgressEvent::Completed { peer, bytes } => {
+                self.done += 1;
+                let stats = self.peers.entry(peer).or_default();
+                stats.completed += 1;
+                stats.bytes += bytes as u64;
+                stats.recent.push_back((Instant::now(), bytes));
+            }
+            ProgressEvent::Retry { peer } => {
+                self.retries += 1;
+                self.peers.entry(peer).or_default().retries += 1;
+            }
+            ProgressEvent::Log(line) => self.push_log(false, line),
+            ProgressEvent::Warn(line) => self.push_log(true, line),
+        }
+    }
+
+    fn push_log(&mut self, warn: bool, line: String) {
+        if self.log.len() == LOG_LINES {
+            self.log.pop_front();
+        }
+        self.log.push_back((warn, line));
+    }
+
+    fn prune(&mut self, now: Instant) {
+        for stats in self.peers.values_mut() {
+            while stats
+                .recent
+                .front()
+                .is_some_and(|(at, _)| now.duration_since(*at) > THROUGHPUT_WINDOW)
+            {
+                stats.recent.pop_front();
+            }
+        }
+    }
+}
+
+fn run(
+    terminal: &mut DefaultTerminal,
+    rx: mpsc::Receiver<ProgressEvent>,
+    stop: &AtomicBool,
+) -> io::Result<()> {
+    let mut state = State::new();
+    loop {
+        while let Ok(event) = rx.try_recv() {
+            state.apply(event);
+        }
+        state.prune(Instant::now());
+        terminal.draw(|frame| draw(frame, &state))?;
+        if stop.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        // Raw mode swallows SIGINT, so Ctrl-C has to be handled here.
+        if event::poll(FRAME_INTERVAL)? {
+            if let Event::Key(key) = event::read()? {
+                let interrupt =
+                    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+                if key.kind == KeyEventKind::Press
+                    && (interrupt || matches!(key.code, KeyCode::Char('q') | KeyCode::Esc))
+                {
+                    ratatui::restore();
+                    eprintln!("{} aborted from tui", state.op);
+                    std::process::exit(130);
+                }
+            }
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, state: &State) {
+    let [header, peers, log] = Layout::vertical([
+        Constraint::Length(4),
+        Constraint::Min(6),
+        Constraint::Percentage(40),
+    ])
+    .areas(frame.area());
+
+    let now = Instant::now();
+    let elapsed = now.duration_since(state.started);
+    let ratio = if state.total == 0 {
+        0.0
+    } else {
+        (state.done as f64 / state.total as f64).min(1.0)
+    };
+    let title = format!(
+        " {} · {}/{} shards · queue {} · in flight {} · retries {} · {}s · q to abort ",
+        state.op,
+        state.done,
+        state.total,
+        state.pending,
+        state.inflight,
+        state.retries,
+        elapsed.as_secs()
+    );
+    frame.render_widget(
+        Gauge::default()
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .gauge_style(Style::default().fg(Color::Green))
+            .ratio(ratio),
+        header,
+    );
+
+    let rows = state.peers.iter().map(|(peer, stats)| {
+        Row::new(vec![
+            short_peer(peer),
+            stats.sent.to_string(),
+            stats.completed.to_string(),
+            stats.retries.to_string(),
+            human_bytes(stats.bytes as f64),
+            format!("{}/s", human_bytes(stats.throughput(now, elapsed))),
+        ])
+    });
+    frame.render_widget(
+        Table::new(
+            rows,
+            [
+                Constraint::Min(16),
+                Constraint::Length(8),
+                Constraint::Length(8),
+                Constraint::Length(8),
+                Constraint::Length(12),
+                Constraint::Length(14),
+            ],
+        )
+        .header(
+            Row::new(["peer", "sent", "done", "retries", "bytes", "throughput"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::default().borders(Borders::ALL).title(" peers ")),
+        peers,
+    );
+
+    let visible = log.height.saturating_sub(2) as usize;
+    let items: Vec<ListItem> = state
+        .log
+        .iter()
+        .skip(state.log.len().saturating_sub(visible))
+        .map(|(warn, line)| {
+            let style = if *warn {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Line::from(Span::styled(line.as_str(), style)))
+        })
+        .collect();
+    frame.render_widget(
+        List::new(items).block(Block::default().borders(Borders::ALL).title(" log ")),
+        log,
+    );
+}
+
+fn short_peer(peer: &PeerId) -> String {
+    let s = peer.to_string();
+    match s.len() {
+        0..=16 => s,
+        n => format!("{}…{}", &s[..8], &s[n - 6..]),
+    }
+}
+
+fn human_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{value:.0} {}", UNITS[unit])
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
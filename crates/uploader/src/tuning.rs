@@ -0,0 +1,471 @@
+//! Store concurrency. By default `--concurrency` caps the requests in flight
+//! across all peers. With `--auto-concurrency` every peer gets its own
+//! window instead. The window starts from a warmup round-trip probe and is
+//! then tuned AIMD-style. Each window's worth of timely acks adds one slot.
+//! A failure, a busy reply, or a smoothed round trip well past the fastest
+//! store seen halves the window.
+
+use crate::{UploaderBehaviour, UploaderEvent};
+use futures::StreamExt;
+use libp2p::{
+    request_response::{Event as RequestResponseEvent, Message as RequestResponseMessage},
+    swarm::{Swarm, SwarmEvent},
+    PeerId,
+};
+use neuro_protocol::{ChunkCommand, HasChunksRequest};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Busy replies one store may collect before the upload gives up on it.
+pub(crate) const MAX_BUSY_REPLIES: usize = 20;
+
+const PROBES_PER_PEER: usize = 3;
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Round trip each extra starting slot is meant to cover, so a peer 40 ms
+/// away starts with four stores in flight and a LAN peer with two.
+const SLOT_RTT: Duration = Duration::from_millis(10);
+const MIN_INITIAL_WINDOW: usize = 2;
+const MAX_WINDOW: f64 = 64.0;
+/// Smoothed RTT over the fastest store seen that counts as queueing.
+const RTT_INFLATION: f64 = 2.0;
+const SRTT_GAIN: f64 = 0.125;
+/// Longest a busy peer's `retry_after_ms` is honoured.
+const MAX_PAUSE: Duration = Duration::from_secs(5);
+
+/// Measures each peer's network round trip with empty inventory requests,
+/// keeping the fastest of a few. Peers that do not answer in time, or do
+/// not speak `Has`, are left out and start from the smallest window.
+pub(crate) async fn probe(
+    swarm: &mut Swarm<UploaderBehaviour>,
+    peers: &HashSet<PeerId>,
+) -> HashMap<PeerId, Duration> {
+    let mut inflight = HashMap::new();
+    for peer in peers {
+        for _ in 0..PROBES_PER_PEER {
+            let request = ChunkCommand::Has(HasChunksRequest { cids: Vec::new() });
+            let request_id = swarm.behaviour_mut().chunk.send_request(peer, request);
+            inflight.insert(request_id, (*peer, Instant::now()));
+        }
+    }
+
+    let mut rtts: HashMap<PeerId, Duration> = HashMap::new();
+    let deadline = Instant::now() + PROBE_TIMEOUT;
+    while !inflight.is_empty() {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let Ok(event) = tokio::time::timeout(remaining, swarm.select_next_some()).await else {
+            break;
+        };
+        match event {
+            SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::Message {
+                message: RequestResponseMessage::Response { request_id, .. },
+                ..
+            })) => {
+                if let Some((peer, sent_at)) = inflight.remove(&request_id) {
+                    let rtt = sent_at.elapsed();
+                    rtts.entry(peer)
+                        .and_modify(|best| *best = (*best).min(rtt))
+                        .or_insert(rtt);
+                }
+            }
+            SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::OutboundFailure {
+                request_id,
+                ..
+            })) => {
+                inflight.remove(&request_id);
+            }
+            _ => {}
+        }
+    }
+    rtts
+}
+
+fn initial_window(probe: Option<Duration>, ceiling: usize) -> f64 {
+    let ceiling = ceiling.max(MIN_INITIAL_WINDOW);
+    let slots = probe.map_or(MIN_INITIAL_WINDOW, |rtt| {
+        rtt.as_millis().div_ceil(SLOT_RTT.as_millis()) as usize
+    });
+    slots.clamp(MIN_INITIAL_WINDOW, ceiling) as f64
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConcurrencySummary {
+    /// `fixed` or `auto`.
+    pub mode: &'static str,
+    /// The global cap in `fixed` mode, the starting window ceiling in `auto`.
+    pub concurrency: usize,
+    pub peers: Vec<PeerConcurrency>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PeerConcurrency {
+    pub peer: String,
+    pub probe_rtt_ms: Option<f64>,
+    pub srtt_ms: Option<f64>,
+    pub min_rtt_ms: Option<f64>,
+    /// Absent in `fixed` mode, where peers share the global cap.
+    pub initial_window: Option<usize>,
+    pub final_window: Option<usize>,
+    pub peak_window: Option<usize>,
+    pub increases: usize,
+    pub decreases: usize,
+    pub completed: usize,
+    pub failures: usize,
+    pub busy: usize,
+    pub bytes: u64,
+    /// Bytes over the time the peer had at least one store in flight.
+    pub throughput_mbps: f64,
+}
+
+struct Lane<T> {
+    peer: PeerId,
+    queue: VecDeque<T>,
+    in_flight: usize,
+    window: f64,
+    initial_window: f64,
+    peak_window: f64,
+    paused_until: Option<Instant>,
+    probe_rtt: Option<Duration>,
+    srtt_ms: Option<f64>,
+    min_rtt_ms: Option<f64>,
+    last_decrease: Option<Instant>,
+    increases: usize,
+    decreases: usize,
+    completed: usize,
+    failures: usize,
+    busy: usize,
+    bytes: u64,
+    active: Duration,
+    active_since: Option<Instant>,
+}
+
+impl<T> Lane<T> {
+    fn has_room(&self, adaptive: bool, now: Instant) -> bool {
+        !self.queue.is_empty()
+            && self.paused_until.is_none_or(|until| until <= now)
+            && (!adaptive || self.in_flight < (self.window as usize).max(1))
+    }
+
+    fn release(&mut self) {
+        self.in_flight = self.in_flight.saturating_sub(1);
+        if self.in_flight == 0 {
+            if let Some(since) = self.active_since.take() {
+                self.active += since.elapsed();
+            }
+        }
+    }
+
+    /// Halves the window, at most once per smoothed round trip so that one
+    /// congested burst is not punished once per request in it.
+    fn decrease(&mut self, now: Instant) {
+        let guard = Duration::from_secs_f64(self.srtt_ms.unwrap_or(0.0) / 1000.0);
+        if self.last_decrease.is_some_and(|at| now.duration_since(at) < guard) {
+            return;
+        }
+        self.window = (self.window / 2.0).max(1.0);
+        self.decreases += 1;
+        self.last_decrease = Some(now);
+    }
+}
+
+/// Per-peer send queues drained round-robin under the configured limits.
+pub(crate) struct Tuner<T> {
+    adaptive: bool,
+    concurrency: usize,
+    probes: HashMap<PeerId, Duration>,
+    lanes: Vec<Lane<T>>,
+    index: HashMap<PeerId, usize>,
+    cursor: usize,
+    in_flight: usize,
+}
+
+impl<T> Tuner<T> {
+    /// At most `concurrency` requests in flight across all peers.
+    pub(crate) fn fixed(concurrency: usize) -> Self {
+        Self::new(false, concurrency.max(1), HashMap::new())
+    }
+
+    /// A window per peer, starting from its probed round trip and never
+    /// larger than `concurrency` to begin with.
+    pub(crate) fn auto(concurrency: usize, probes: HashMap<PeerId, Duration>) -> Self {
+        Self::new(true, concurrency.max(1), probes)
+    }
+
+    fn new(adaptive: bool, concurrency: usize, probes: HashMap<PeerId, Duration>) -> Self {
+        Self {
+            adaptive,
+            concurrency,
+            probes,
+            lanes: Vec::new(),
+            index: HashMap::new(),
+            cursor: 0,
+            in_flight: 0,
+        }
+    }
+
+    fn lane(&mut self, peer: PeerId) -> &mut Lane<T> {
+        let next = self.lanes.len();
+        let idx = *self.index.entry(peer).or_insert(next);
+        if idx == next {
+            let probe_rtt = self.probes.get(&peer).copied();
+            let window = initial_window(probe_rtt, self.concurrency);
+            self.lanes.push(Lane {
+                peer,
+                queue: VecDeque::new(),
+                in_flight: 0,
+                window,
+                initial_window: window,
+                peak_window: window,
+                paused_until: None,
+                probe_rtt,
+                srtt_ms: None,
+                min_rtt_ms: None,
+                last_decrease: None,
+                increases: 0,
+                decreases: 0,
+                completed: 0,
+                failures: 0,
+                busy: 0,
+                bytes: 0,
+                active: Duration::ZERO,
+                active_since: None,
+            });
+        }
+        &mut self.lanes[idx]
+    }
+
+    pub(crate) fn push(&mut self, peer: PeerId, item: T) {
+        self.lane(peer).queue.push_back(item);
+    }
+
+    /// The next request to send, if any peer has room for one.
+    pub(crate) fn next(&mut self) -> Option<T> {
+        if !self.adaptive && self.in_flight >= self.concurrency {
+            return None;
+        }
+        let now = Instant::now();
+        for step in 0..self.lanes.len() {
+            let idx = (self.cursor + step) % self.lanes.len();
+            let lane = &mut self.lanes[idx];
+            if !lane.has_room(self.adaptive, now) {
+                continue;
+            }
+            lane.paused_until = None;
+            lane.in_flight += 1;
+            lane.active_since.get_or_insert(now);
+            self.in_flight += 1;
+            self.cursor = idx + 1;
+            return lane.queue.pop_front();
+        }
+        None
+    }
+
+    pub(crate) fn pending(&self) -> usize {
+        self.lanes.iter().map(|lane| lane.queue.len()).sum()
+    }
+
+    /// How long until a busy peer may be sent to again, when that is the
+    /// only thing holding queued requests back.
+    pub(crate) fn resume_in(&self) -> Option<Duration> {
+        let now = Instant::now();
+        self.lanes
+            .iter()
+            .filter(|lane| !lane.queue.is_empty())
+            .filter_map(|lane| lane.paused_until)
+            .map(|until| until.saturating_duration_since(now))
+            .min()
+    }
+
+    /// A store came back acknowledged after `rtt`.
+    pub(crate) fn completed(&mut self, peer: PeerId, rtt: Duration, bytes: usize) {
+        let adaptive = self.adaptive;
+        self.in_flight = self.in_flight.saturating_sub(1);
+        let lane = self.lane(peer);
+        lane.release();
+        lane.completed += 1;
+        lane.bytes += bytes as u64;
+
+        let sample = rtt.as_secs_f64() * 1000.0;
+        let srtt = lane
+            .srtt_ms
+            .map_or(sample, |srtt| srtt + SRTT_GAIN * (sample - srtt));
+        let min_rtt = lane.min_rtt_ms.map_or(sample, |min| min.min(sample));
+        lane.srtt_ms = Some(srtt);
+        lane.min_rtt_ms = Some(min_rtt);
+        if !adaptive {
+            return;
+        }
+        if srtt > min_rtt * RTT_INFLATION {
+            lane.decrease(Instant::now());
+        } else if lane.window < MAX_WINDOW {
+            let before = lane.window as usize;
+            lane.window = (lane.window + 1.0 / lane.window).min(MAX_WINDOW);
+            if lane.window as usize > before {
+                lane.increases += 1;
+            }
+            lane.peak_window = lane.peak_window.max(lane.window);
+        }
+    }
+
+    /// A request to `peer` failed in transport and is being resent; it
+    /// keeps its slot.
+    pub(crate) fn retried(&mut self, peer: PeerId) {
+        let adaptive = self.adaptive;
+        let lane = self.lane(peer);
+        lane.failures += 1;
+        if adaptive {
+            lane.decrease(Instant::now());
+        }
+    }
+
+    /// A request to `peer` was given up on without an answer worth timing;
+    /// its slot is freed.
+    pub(crate) fn dropped(&mut self, peer: PeerId) {
+        self.in_flight = self.in_flight.saturating_sub(1);
+        let lane = self.lane(peer);
+        lane.release();
+        lane.failures += 1;
+    }
+
+    /// `peer` answered busy: `item` goes back to the head of its queue and
+    /// the peer gets nothing new until `retry_after_ms` has passed.
+    pub(crate) fn busy(&mut self, peer: PeerId, item: T, retry_after_ms: u64) {
+        let adaptive = self.adaptive;
+        self.in_flight = self.in_flight.saturating_sub(1);
+        let now = Instant::now();
+        let lane = self.lane(peer);
+        lane.release();
+        lane.busy += 1;
+        lane.queue.push_front(item);
+        lane.paused_until = Some(now + Duration::from_millis(retry_after_ms).min(MAX_PAUSE));
+        if adaptive {
+            lane.decrease(now);
+        }
+    }
+
+    pub(crate) fn summary(&self) -> ConcurrencySummary {
+        let window = |w: f64| self.adaptive.then_some(w as usize);
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let peers = self
+            .lanes
+            .iter()
+            .map(|lane| {
+                let active = lane.active + lane.active_since.map_or(Duration::ZERO, |s| s.elapsed());
+                let secs = active.as_secs_f64();
+                PeerConcurrency {
+                    peer: lane.peer.to_string(),
+                    probe_rtt_ms: lane.probe_rtt.map(ms),
+                    srtt_ms: lane.srtt_ms,
+                    min_rtt_ms: lane.min_rtt_ms,
+                    initial_window: window(lane.initial_window),
+                    final_window: window(lane.window),
+                    peak_window: window(lane.peak_window),
+                    increases: lane.increases,
+                    decreases: lane.decreases,
+                    completed: lane.completed,
+                    failures: lane.failures,
+                    busy: lane.busy,
+                    bytes: lane.bytes,
+                    throughput_mbps: if secs > 0.0 {
+                        lane.bytes as f64 * 8.0 / 1_000_000.0 / secs
+                    } else {
+                        0.0
+                    },
+                }
+            })
+            .collect();
+        ConcurrencySummary {
+            mode: if self.adaptive { "auto" } else { "fixed" },
+            concurrency: self.concurrency,
+            peers,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(tuner: &mut Tuner<usize>) -> Vec<usize> {
+        std::iter::from_fn(|| tuner.next()).collect()
+    }
+
+    #[test]
+    fn fixed_mode_caps_requests_across_peers() {
+        let (a, b) = (PeerId::random(), PeerId::random());
+        let mut tuner = Tuner::fixed(3);
+        for i in 0..4 {
+            tuner.push(a, i);
+            tuner.push(b, 10 + i);
+        }
+        assert_eq!(drain(&mut tuner), vec![0, 10, 1]);
+        tuner.completed(a, Duration::from_millis(5), 100);
+        assert_eq!(drain(&mut tuner), vec![11]);
+        assert_eq!(tuner.pending(), 4);
+        assert_eq!(tuner.summary().peers[0].final_window, None);
+    }
+
+    #[test]
+    fn first_window_follows_the_probed_round_trip() {
+        assert_eq!(initial_window(None, 8), 2.0);
+        assert_eq!(initial_window(Some(Duration::from_millis(1)), 8), 2.0);
+        assert_eq!(initial_window(Some(Duration::from_millis(40)), 8), 4.0);
+        assert_eq!(initial_window(Some(Duration::from_millis(400)), 8), 8.0);
+    }
+
+    #[test]
+    fn timely_acks_grow_the_window_one_slot_per_window() {
+        let peer = PeerId::random();
+        let mut tuner = Tuner::auto(8, HashMap::new());
+        for i in 0..100 {
+            tuner.push(peer, i);
+        }
+        for _ in 0..2 {
+            assert_eq!(drain(&mut tuner).len(), 2);
+            for _ in 0..2 {
+                tuner.completed(peer, Duration::from_millis(10), 1);
+            }
+        }
+        assert_eq!(drain(&mut tuner).len(), 3);
+        let stats = &tuner.summary().peers[0];
+        assert_eq!(stats.final_window, Some(3));
+        assert_eq!(stats.increases, 1);
+    }
+
+    #[test]
+    fn inflated_round_trips_halve_the_window() {
+        let peer = PeerId::random();
+        let mut tuner: Tuner<usize> = Tuner::auto(8, HashMap::from([(peer, Duration::from_millis(80))]));
+        tuner.push(peer, 0);
+        assert_eq!(drain(&mut tuner).len(), 1);
+        tuner.completed(peer, Duration::from_millis(10), 1);
+        for _ in 0..20 {
+            tuner.push(peer, 0);
+            drain(&mut tuner);
+            tuner.completed(peer, Duration::from_millis(200), 1);
+        }
+        let stats = &tuner.summary().peers[0];
+        assert_eq!(stats.initial_window, Some(8));
+        assert!(stats.decreases >= 1);
+        assert!(stats.final_window < Some(8));
+    }
+
+    #[test]
+    fn busy_peer_is_paused_and_its_request_requeued() {
+        let (slow, fast) = (PeerId::random(), PeerId::random());
+        let mut tuner = Tuner::auto(8, HashMap::new());
+        tuner.push(slow, 1);
+        tuner.push(fast, 2);
+        assert_eq!(drain(&mut tuner), vec![1, 2]);
+
+        tuner.busy(slow, 1, 60_000);
+        tuner.push(fast, 3);
+        assert_eq!(drain(&mut tuner), vec![3]);
+        assert_eq!(tuner.pending(), 1);
+        let wait = tuner.resume_in().unwrap();
+        assert!(wait > Duration::from_secs(4) && wait <= MAX_PAUSE);
+
+        let stats = &tuner.summary().peers[0];
+        assert_eq!((stats.busy, stats.final_window), (1, Some(1)));
+    }
+}
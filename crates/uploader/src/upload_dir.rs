@@ -0,0 +1,598 @@
+//! `upload-dir`: uploads every file under a directory through one swarm,
+//! with the shards of all of them sharing a single in-flight budget, so
+//! peers stay busy across file boundaries instead of idling while the next
+//! file is encrypted. Files are encrypted one after another as the queue
+//! runs low, each gets its manifest and receipts under `--manifest-dir` as
+//! soon as its last shard is acknowledged, and one report covers them all.
+//! A file whose shards fail is reported and the rest carry on.
+
+use crate::progress::{Progress, ProgressEvent};
+use crate::{
+    cancel, dedup_peers, extract_peer_id, gateways, receipts, select_peers_for_cid, swarm_pool, tuning,
+    wait_for_peer_connections, write_report, CidFormatArg, HashAlgorithmArg, PasswordArgs, PlacementArg,
+    ProfileArg, StoreDispatch, UploaderEvent, MAX_AUDIT_ROUNDS, MAX_MANIFEST_BYTES, MAX_PEERS_PER_SHARD,
+    MAX_SHARDS, PEER_CONNECT_WARMUP_SECS,
+};
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use libp2p::{
+    request_response::{Event as RequestResponseEvent, Message as RequestResponseMessage, OutboundRequestId},
+    swarm::SwarmEvent,
+};
+use neuro_client_sdk::{adaptive_config, process_bytes, ManifestBuilder, PipelineConfig};
+use neuro_placement::Strategy;
+use neuro_protocol::{ChunkCommand, ChunkReply, StoreChunkRequest};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use zeroize::Zeroizing;
+
+#[derive(clap::Args, Debug)]
+pub struct UploadDirArgs {
+    /// Directory to upload; files in its subdirectories are included.
+    #[arg(long)]
+    dir: String,
+
+    /// Where each file's manifest goes, as `<path>.manifest.json` at the
+    /// file's path relative to `--dir`, with its receipts alongside.
+    #[arg(long)]
+    manifest_dir: String,
+
+    #[command(flatten)]
+    password: PasswordArgs,
+
+    #[arg(long, num_args = 1.., required = true)]
+    peer: Vec<String>,
+
+    /// Store requests in flight across all files together.
+    #[arg(long, default_value_t = 16)]
+    concurrency: usize,
+
+    /// Give each peer its own in-flight window, sized from a warmup RTT
+    /// probe; `--concurrency` then caps the first window.
+    #[arg(long, default_value_t = false)]
+    auto_concurrency: bool,
+
+    #[arg(long, value_enum, default_value_t = ProfileArg::Balanced)]
+    profile: ProfileArg,
+
+    #[arg(long, value_enum, default_value_t = CidFormatArg::Hex)]
+    cid_format: CidFormatArg,
+
+    #[arg(long, value_enum, default_value_t = HashAlgorithmArg::Sha256)]
+    hash_algorithm: HashAlgorithmArg,
+
+    #[arg(long, default_value_t = 2)]
+    replica_factor: usize,
+
+    #[arg(long, value_enum, default_value_t = PlacementArg::Rendezvous)]
+    placement: PlacementArg,
+
+    #[arg(long, default_value_t = 3)]
+    audit_rounds: usize,
+
+    #[arg(long, default_value_t = 120)]
+    max_response_age_secs: u64,
+
+    /// Keep constant chunks in the manifests instead of storing them, as
+    /// `upload --holes` does.
+    #[arg(long, default_value_t = false)]
+    holes: bool,
+
+    #[arg(long)]
+    report_out: Option<String>,
+
+    /// Full-screen live dashboard instead of line-per-shard output.
+    #[arg(long, default_value_t = false)]
+    tui: bool,
+
+    /// Register every manifest with these gateways too.
+    #[command(flatten)]
+    gateway: gateways::GatewayArgs,
+
+    #[arg(long, default_value_t = 1)]
+    gateway_quorum: usize,
+}
+
+/// One file's way through the batch, and its line in the report.
+#[derive(Serialize)]
+struct FileUpload {
+    path: String,
+    manifest_path: String,
+    total_bytes: u64,
+    requests_total: usize,
+    requests_acked: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    manifest_root: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    gateways: Vec<gateways::Registration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip)]
+    source: PathBuf,
+    /// Set once the file is encrypted, until its manifest is written.
+    #[serde(skip)]
+    pending: Option<PendingManifest>,
+}
+
+struct PendingManifest {
+    builder: ManifestBuilder,
+    acked_by_cid: HashMap<String, usize>,
+    receipts: receipts::ReceiptCollector,
+}
+
+impl FileUpload {
+    fn done(&self) -> bool {
+        self.error.is_some() || self.manifest_root.is_some()
+    }
+
+    fn fail(&mut self, error: String, progress: &Progress) {
+        progress.warn(format!("upload-dir failed file={} err={error}", self.path));
+        self.error = Some(error);
+        self.pending = None;
+    }
+}
+
+struct InflightStore {
+    file: usize,
+    dispatch: StoreDispatch,
+    attempt: usize,
+    busy_replies: usize,
+    started: Instant,
+}
+
+pub async fn run_upload_dir(args: UploadDirArgs, cancel: CancellationToken) -> Result<()> {
+    let password = args.password.resolve()?;
+    if args.audit_rounds == 0 || args.audit_rounds > MAX_AUDIT_ROUNDS {
+        return Err(anyhow!("audit_rounds must be between 1 and {}", MAX_AUDIT_ROUNDS));
+    }
+    let gateway_urls = args.gateway.urls()?;
+    gateways::check_quorum(&gateway_urls, args.gateway_quorum)?;
+    let gateway_token = if gateway_urls.is_empty() {
+        None
+    } else {
+        Some(args.gateway.require_token()?)
+    };
+
+    let root = Path::new(&args.dir);
+    let mut sources = Vec::new();
+    collect_files(root, &mut sources)?;
+    sources.sort();
+    if sources.is_empty() {
+        return Err(anyhow!("no files under {}", args.dir));
+    }
+
+    let unique_peers = dedup_peers(&args.peer);
+    let replica_target = args.replica_factor.clamp(1, unique_peers.len());
+    let peer_scores = HashMap::new();
+    let placement: Strategy = args.placement.into();
+
+    let mut files = Vec::with_capacity(sources.len());
+    let mut estimated_requests = 0usize;
+    for source in sources {
+        let rel = source.strip_prefix(root).unwrap_or(&source).to_string_lossy().into_owned();
+        let total_bytes = fs::metadata(&source)?.len();
+        let cfg = file_config(&args, total_bytes as usize, unique_peers.len());
+        // Exact unless holes leave chunks out.
+        estimated_requests += (total_bytes as usize).div_ceil(cfg.chunk_size)
+            * (cfg.data_shards + cfg.parity_shards)
+            * replica_target;
+        files.push(FileUpload {
+            manifest_path: Path::new(&args.manifest_dir)
+                .join(format!("{rel}.manifest.json"))
+                .to_string_lossy()
+                .into_owned(),
+            path: rel,
+            total_bytes,
+            requests_total: 0,
+            requests_acked: 0,
+            manifest_root: None,
+            gateways: Vec::new(),
+            error: None,
+            source,
+            pending: None,
+        });
+    }
+    println!(
+        "upload-dir files={} bytes={} peers={}",
+        files.len(),
+        files.iter().map(|f| f.total_bytes).sum::<u64>(),
+        unique_peers.len()
+    );
+
+    let mut swarm = swarm_pool::checkout(&unique_peers).await?;
+    let warm_connected = wait_for_peer_connections(
+        &mut swarm,
+        &unique_peers,
+        Duration::from_secs(PEER_CONNECT_WARMUP_SECS),
+    )
+    .await?;
+    if warm_connected.is_empty() {
+        return Err(anyhow!("unable to connect to any peer during warmup"));
+    }
+    println!(
+        "upload-dir warmup connected_peers={}/{}",
+        warm_connected.len(),
+        unique_peers.len()
+    );
+
+    let mut tuner = if args.auto_concurrency {
+        let probes = tuning::probe(&mut swarm, &warm_connected).await;
+        println!("upload-dir rtt probe answered={}/{}", probes.len(), warm_connected.len());
+        tuning::Tuner::auto(args.concurrency, probes)
+    } else {
+        tuning::Tuner::fixed(args.concurrency)
+    };
+
+    let max_age_ms = args.max_response_age_secs.saturating_mul(1000);
+    let mut inflight: HashMap<OutboundRequestId, InflightStore> = HashMap::new();
+    let mut next_file = 0usize;
+    let progress = Progress::start(args.tui)?;
+    progress.emit(ProgressEvent::Begin {
+        op: "upload-dir",
+        total: estimated_requests,
+    });
+
+    loop {
+        // Encrypt more files while the queue could run dry before the
+        // next reply, so the budget never waits on a file boundary.
+        while !cancel.is_cancelled() && next_file < files.len() && tuner.pending() < args.concurrency {
+            let file = next_file;
+            next_file += 1;
+            match encode_file(&args, &files[file], &password, &unique_peers, &peer_scores, placement, replica_target) {
+                Ok((pending, dispatches)) => {
+                    files[file].requests_total = dispatches.len();
+                    files[file].pending = Some(pending);
+                    progress.log(format!(
+                        "upload-dir encoded file={} requests={}",
+                        files[file].path,
+                        dispatches.len()
+                    ));
+                    for dispatch in dispatches {
+                        tuner.push(
+                            dispatch.peer_id,
+                            InflightStore {
+                                file,
+                                dispatch,
+                                attempt: 0,
+                                busy_replies: 0,
+                                started: Instant::now(),
+                            },
+                        );
+                    }
+                    // Empty files, or files of nothing but holes, have no
+                    // shards to wait for.
+                    if files[file].requests_total == 0 {
+                        finish_file(&mut files[file], &password, &gateway_urls, &progress);
+                    }
+                }
+                Err(e) => files[file].fail(e.to_string(), &progress),
+            }
+        }
+
+        while !cancel.is_cancelled() {
+            let Some(mut state) = tuner.next() else {
+                break;
+            };
+            // Whatever is still queued for a failed file is dropped.
+            if files[state.file].done() {
+                tuner.dropped(state.dispatch.peer_id);
+                continue;
+            }
+            let request_id = swarm
+                .behaviour_mut()
+                .chunk
+                .send_request(&state.dispatch.peer_id, state.dispatch.request.clone());
+            progress.emit(ProgressEvent::Sent {
+                peer: state.dispatch.peer_id,
+            });
+            state.started = Instant::now();
+            inflight.insert(request_id, state);
+        }
+        progress.emit(ProgressEvent::Queue {
+            pending: tuner.pending(),
+            inflight: inflight.len(),
+        });
+        let drained = tuner.pending() == 0 && (next_file == files.len() || cancel.is_cancelled());
+        if inflight.is_empty() && (drained || cancel.is_cancelled()) {
+            break;
+        }
+
+        let resume_in = tuner.resume_in().filter(|_| !cancel.is_cancelled());
+        let next_event = async {
+            match resume_in {
+                Some(wait) => tokio::time::timeout(wait, swarm.select_next_some()).await.ok(),
+                None => Some(swarm.select_next_some().await),
+            }
+        };
+        let event = tokio::select! {
+            event = next_event => match event {
+                Some(event) => event,
+                None => continue,
+            },
+            _ = cancel.cancelled(), if !cancel.is_cancelled() => continue,
+        };
+        match event {
+            SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::Message {
+                message: RequestResponseMessage::Response { request_id, response },
+                ..
+            })) => {
+                let Some(mut state) = inflight.remove(&request_id) else {
+                    continue;
+                };
+                let file = &mut files[state.file];
+                let peer = state.dispatch.peer_id;
+                if file.done() {
+                    tuner.dropped(peer);
+                    continue;
+                }
+                let error = match response {
+                    ChunkReply::Store(store_resp) => {
+                        let dispatch = &state.dispatch;
+                        let verified = store_resp.verify_receipt(&peer, &dispatch.cid, dispatch.len);
+                        let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+                        let fresh = store_resp.is_fresh(now_ms, max_age_ms);
+                        progress.log(format!(
+                            "store file={} cid={} ok={} verified={} fresh={} rtt_ms={}",
+                            file.path,
+                            dispatch.cid,
+                            store_resp.stored,
+                            verified,
+                            fresh,
+                            state.started.elapsed().as_millis()
+                        ));
+                        if !store_resp.stored || !verified || !fresh {
+                            format!("failed store or invalid receipt for {}", dispatch.cid)
+                        } else {
+                            tuner.completed(peer, state.started.elapsed(), dispatch.len);
+                            progress.emit(ProgressEvent::Completed {
+                                peer,
+                                bytes: dispatch.len,
+                            });
+                            let pending = file.pending.as_mut().expect("files in flight are encoded");
+                            pending.receipts.record_store(peer, &dispatch.cid, dispatch.len, &store_resp);
+                            *pending.acked_by_cid.entry(dispatch.cid.clone()).or_insert(0) += 1;
+                            file.requests_acked += 1;
+                            if file.requests_acked == file.requests_total {
+                                finish_file(file, &password, &gateway_urls, &progress);
+                            }
+                            continue;
+                        }
+                    }
+                    ChunkReply::Busy(busy) => {
+                        state.busy_replies += 1;
+                        if state.busy_replies > tuning::MAX_BUSY_REPLIES {
+                            format!("peer {peer} stayed busy for cid={}", state.dispatch.cid)
+                        } else {
+                            progress.emit(ProgressEvent::Retry { peer });
+                            tuner.busy(peer, state, busy.retry_after_ms);
+                            continue;
+                        }
+                    }
+                    ChunkReply::Rejected(rejected) => format!(
+                        "peer {peer} rejected store for cid={}: {}",
+                        state.dispatch.cid, rejected.error
+                    ),
+                    ChunkReply::Maintenance(_) => format!(
+                        "peer {peer} is in maintenance and refused store for cid={}",
+                        state.dispatch.cid
+                    ),
+                    _ => "unexpected response type for store request".to_string(),
+                };
+                tuner.dropped(peer);
+                file.fail(error, &progress);
+            }
+            SwarmEvent::Behaviour(UploaderEvent::Chunk(RequestResponseEvent::OutboundFailure {
+                request_id,
+                error,
+                ..
+            })) => {
+                let Some(mut state) = inflight.remove(&request_id) else {
+                    continue;
+                };
+                if cancel.is_cancelled() || files[state.file].done() {
+                    tuner.dropped(state.dispatch.peer_id);
+                    continue;
+                }
+                if state.attempt < 3 {
+                    state.attempt += 1;
+                    tuner.retried(state.dispatch.peer_id);
+                    progress.emit(ProgressEvent::Retry {
+                        peer: state.dispatch.peer_id,
+                    });
+                    let retry_id = swarm
+                        .behaviour_mut()
+                        .chunk
+                        .send_request(&state.dispatch.peer_id, state.dispatch.request.clone());
+                    state.started = Instant::now();
+                    inflight.insert(retry_id, state);
+                } else {
+                    let error = format!("store request failed cid={} error={error:?}", state.dispatch.cid);
+                    tuner.dropped(state.dispatch.peer_id);
+                    files[state.file].fail(error, &progress);
+                }
+            }
+            SwarmEvent::OutgoingConnectionError { peer_id, error, .. } => {
+                progress.warn(format!("upload-dir outgoing connection error peer={peer_id:?} err={error:?}"));
+            }
+            _ => {}
+        }
+    }
+    progress.finish();
+    let concurrency = tuner.summary();
+    crate::print_concurrency("upload-dir", &concurrency);
+
+    if let Some(token) = &gateway_token {
+        for file in files.iter_mut().filter(|f| f.manifest_root.is_some()) {
+            let manifest_bytes = fs::read(&file.manifest_path)?;
+            match gateways::register_with_quorum(&gateway_urls, token, &manifest_bytes, args.gateway_quorum).await {
+                Ok(registrations) => file.gateways = registrations,
+                Err(e) => file.error = Some(e.to_string()),
+            }
+        }
+    }
+
+    let uploaded = files.iter().filter(|f| f.manifest_root.is_some() && f.error.is_none()).count();
+    let failed = files.iter().filter(|f| f.error.is_some()).count();
+    for file in &files {
+        match (&file.manifest_root, &file.error) {
+            (_, Some(error)) => println!("upload-dir file={} failed: {error}", file.path),
+            (Some(root), None) => println!(
+                "upload-dir file={} root={root} manifest={}",
+                file.path, file.manifest_path
+            ),
+            (None, None) => println!("upload-dir file={} not uploaded", file.path),
+        }
+    }
+    let details = serde_json::json!({
+        "dir": args.dir,
+        "manifest_dir": args.manifest_dir,
+        "files_total": files.len(),
+        "files_uploaded": uploaded,
+        "files_failed": failed,
+        "replicas": replica_target,
+        "files": files,
+        "concurrency": concurrency
+    });
+    if cancel.is_cancelled() {
+        return Err(cancel::finish(args.report_out.as_deref(), "upload-dir", details)?);
+    }
+    println!("upload-dir complete files={uploaded}/{} failed={failed}", files.len());
+    if let Some(path) = &args.report_out {
+        write_report(path, "upload-dir", failed == 0, details)?;
+    }
+    if failed > 0 {
+        return Err(anyhow!("{failed} of {} files failed to upload", files.len()));
+    }
+    Ok(())
+}
+
+/// Regular files under `dir`, recursively. Symlinks are skipped rather than
+/// followed, so a link cannot pull in files from outside the directory.
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).map_err(|e| anyhow!("cannot read {}: {e}", dir.display()))? {
+        let entry = entry?;
+        let kind = entry.file_type()?;
+        if kind.is_dir() {
+            collect_files(&entry.path(), out)?;
+        } else if kind.is_file() {
+            out.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// The layout `upload` would pick for a file of `len` bytes.
+fn file_config(args: &UploadDirArgs, len: usize, peer_count: usize) -> PipelineConfig {
+    let mut cfg = adaptive_config(len, peer_count, args.profile.into());
+    cfg.cid_format = args.cid_format.into();
+    cfg.hash_algorithm = args.hash_algorithm.into();
+    cfg.holes = args.holes;
+    cfg.clamp_to_limits();
+    cfg
+}
+
+/// Reads and encrypts one file and places its shards, returning the
+/// manifest under construction and a store request per replica.
+fn encode_file(
+    args: &UploadDirArgs,
+    file: &FileUpload,
+    password: &str,
+    peers: &[String],
+    peer_scores: &HashMap<String, u8>,
+    placement: Strategy,
+    replicas: usize,
+) -> Result<(PendingManifest, Vec<StoreDispatch>)> {
+    let data = fs::read(&file.source)?;
+    let output = process_bytes(&data, password, file_config(args, data.len(), peers.len()))?;
+    if output.shards.len() > MAX_SHARDS {
+        return Err(anyhow!("too many shards generated: {} > {}", output.shards.len(), MAX_SHARDS));
+    }
+    let mut builder = ManifestBuilder::for_output(&output);
+    let mut dispatches = Vec::new();
+    for shard in &output.shards {
+        let targets = select_peers_for_cid(placement, &shard.cid, peers, peer_scores, replicas);
+        if targets.len() > MAX_PEERS_PER_SHARD {
+            return Err(anyhow!(
+                "too many peer targets for shard {}: {} > {}",
+                shard.cid,
+                targets.len(),
+                MAX_PEERS_PER_SHARD
+            ));
+        }
+        let placed = builder.place_shard(shard, targets, args.audit_rounds)?;
+        for peer in &placed.peers {
+            dispatches.push(StoreDispatch {
+                request: ChunkCommand::Store(StoreChunkRequest {
+                    cid: shard.cid.clone(),
+                    data: shard.bytes.clone(),
+                }),
+                cid: shard.cid.clone(),
+                len: shard.bytes.len(),
+                peer_id: extract_peer_id(peer)?,
+            });
+        }
+    }
+    let pending = PendingManifest {
+        builder,
+        acked_by_cid: HashMap::new(),
+        receipts: receipts::ReceiptCollector::default(),
+    };
+    Ok((pending, dispatches))
+}
+
+/// Seals and writes the manifest and receipts of a file whose every store
+/// was acknowledged.
+fn finish_file(file: &mut FileUpload, password: &Zeroizing<String>, gateway_urls: &[String], progress: &Progress) {
+    let pending = file.pending.take().expect("finished files are encoded");
+    match write_manifest(file, pending, password, gateway_urls) {
+        Ok(root) => {
+            progress.log(format!(
+                "upload-dir stored file={} requests={} manifest={}",
+                file.path, file.requests_total, file.manifest_path
+            ));
+            file.manifest_root = Some(root);
+        }
+        Err(e) => file.fail(e.to_string(), progress),
+    }
+}
+
+fn write_manifest(
+    file: &FileUpload,
+    pending: PendingManifest,
+    password: &str,
+    gateway_urls: &[String],
+) -> Result<String> {
+    for ms in pending.builder.shards() {
+        let got = pending.acked_by_cid.get(&ms.cid).copied().unwrap_or(0);
+        if got < ms.peers.len() {
+            return Err(anyhow!(
+                "replication shortfall cid={} expected={} got={}",
+                ms.cid,
+                ms.peers.len(),
+                got
+            ));
+        }
+    }
+    let manifest = pending.builder.gateways(gateway_urls.to_vec()).seal(password)?;
+    let manifest_bytes = serde_json::to_vec_pretty(&manifest)?;
+    if manifest_bytes.len() > MAX_MANIFEST_BYTES {
+        return Err(anyhow!(
+            "manifest too large: {} bytes > {} bytes",
+            manifest_bytes.len(),
+            MAX_MANIFEST_BYTES
+        ));
+    }
+    if let Some(parent) = Path::new(&file.manifest_path).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&file.manifest_path, &manifest_bytes)?;
+    receipts::write(
+        &receipts::receipts_path(&file.manifest_path),
+        &pending.receipts.finish(&manifest.manifest_root),
+    )?;
+    Ok(manifest.manifest_root)
+}
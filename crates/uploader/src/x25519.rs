@@ -0,0 +1,150 @@
+//! X25519 identities for password-free uploads. `upload --no-password
+//! --recipient-key <hex>` encrypts only to public keys. The matching secret
+//! is kept in a file or the OS keyring and stands in for the password
+//! wherever a manifest has to be opened or re-signed.
+
+use crate::{read_secret_file, UploadManifest};
+use anyhow::{anyhow, Result};
+use neuro_client_sdk::{parse_x25519_secret, x25519_identity, x25519_public_key};
+use std::{fs, io};
+use zeroize::Zeroizing;
+
+#[derive(clap::Args, Debug)]
+pub struct IdentityArgs {
+    #[command(subcommand)]
+    command: IdentityCommand,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum IdentityCommand {
+    /// Create an identity and print the public key uploaders encrypt to.
+    Generate {
+        /// Secret key file to create; an existing file is never replaced.
+        #[arg(long, required_unless_present = "keyring")]
+        out: Option<String>,
+
+        /// Keep the secret in this OS keyring account instead
+        /// (`x25519_identity` is the desktop shell's).
+        #[arg(long, conflicts_with = "out")]
+        keyring: Option<String>,
+    },
+    /// Print the public key of an identity file or keyring account.
+    Show {
+        #[arg(long, required_unless_present = "keyring")]
+        identity: Option<String>,
+
+        #[arg(long, conflicts_with = "identity")]
+        keyring: Option<String>,
+    },
+}
+
+pub fn run_identity(args: IdentityArgs) -> Result<()> {
+    match args.command {
+        IdentityCommand::Generate { out, keyring } => {
+            let (secret, public) = x25519_identity();
+            match (out, keyring) {
+                (_, Some(account)) => {
+                    keyring_store(&account, &secret)?;
+                    println!("identity keyring={account} public_key={public}");
+                }
+                (Some(path), None) => {
+                    write_new_secret(&path, &secret)?;
+                    println!("identity secret={path} public_key={public}");
+                }
+                (None, None) => unreachable!("clap requires --out or --keyring"),
+            }
+        }
+        IdentityCommand::Show { identity, keyring } => {
+            let secret = load(identity.as_deref(), keyring.as_deref())?
+                .ok_or_else(|| anyhow!("--identity or --keyring is required"))?;
+            println!("public_key={}", x25519_public_key(&secret));
+        }
+    }
+    Ok(())
+}
+
+/// The identity from a file or keyring account, if either was given.
+pub fn load(file: Option<&str>, keyring: Option<&str>) -> Result<Option<Zeroizing<[u8; 32]>>> {
+    let hex = match (file, keyring) {
+        (Some(path), _) => read_secret_file(path)?,
+        (None, Some(account)) => crate::keyring_password(account)?,
+        (None, None) => return Ok(None),
+    };
+    parse_x25519_secret(&hex).map(Some)
+}
+
+fn write_new_secret(path: &str, secret: &str) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(path)
+        .map_err(|e| anyhow!("cannot create {path}: {e}"))?;
+    io::Write::write_all(&mut file, format!("{secret}\n").as_bytes())?;
+    Ok(())
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_store(account: &str, secret: &str) -> Result<()> {
+    let entry = keyring::Entry::new(crate::KEYRING_SERVICE, account)?;
+    match entry.get_password() {
+        Ok(_) => Err(anyhow!(
+            "keyring account {account} already holds a secret; remove it first"
+        )),
+        Err(keyring::Error::NoEntry) => Ok(entry.set_password(secret)?),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_store(_account: &str, _secret: &str) -> Result<()> {
+    Err(anyhow!(
+        "--keyring requires neuro-uploader built with the `keyring` feature"
+    ))
+}
+
+/// What opens a manifest: the upload password (or any recipient password),
+/// or the secret of an X25519 recipient.
+pub enum Unlock {
+    Password(Zeroizing<String>),
+    Identity(Zeroizing<[u8; 32]>),
+}
+
+impl Unlock {
+    pub fn verify(&self, manifest: &UploadManifest) -> Result<()> {
+        match self {
+            Self::Password(password) => manifest.verify_auth_tag(password),
+            Self::Identity(_) => {
+                let key = self.chunk_key(manifest)?;
+                manifest.verify_auth_tag_with_key(&key)
+            }
+        }
+    }
+
+    pub fn chunk_key(&self, manifest: &UploadManifest) -> Result<Zeroizing<[u8; 32]>> {
+        match self {
+            Self::Password(password) => manifest.chunk_key(password),
+            Self::Identity(secret) => manifest.chunk_key_for_identity(secret),
+        }
+    }
+
+    /// Recomputes the manifest hash and signs it again.
+    pub fn reseal(&self, manifest: &mut UploadManifest) -> Result<()> {
+        match self {
+            Self::Password(password) => manifest.reseal(Some(password)),
+            Self::Identity(_) => {
+                let key = self.chunk_key(manifest)?;
+                manifest.reseal_with_key(&key)
+            }
+        }
+    }
+
+    /// The secret autopilot keys its action report signatures with.
+    pub fn report_secret(&self) -> Zeroizing<String> {
+        match self {
+            Self::Password(password) => password.clone(),
+            Self::Identity(secret) => Zeroizing::new(hex::encode(secret.as_ref())),
+        }
+    }
+}
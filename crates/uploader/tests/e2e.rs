@@ -0,0 +1,817 @@
+// ═══════════════════════════════════════════════════════════════
+// NeuroStore — End-to-End Chunk Protocol Harness
+// In-process nodes · uploader binary · upload/audit/retrieve/failover
+// ═══════════════════════════════════════════════════════════════
+
+use libp2p::{identity, Multiaddr, PeerId};
+use neuro_gateway_client::GatewayClient;
+use neuro_node::maintenance;
+use neuro_node::p2p::{build_node, drive_node};
+use neuro_node::store::SecureBlockStore;
+use std::collections::HashSet;
+use std::net::TcpListener;
+use std::path::Path;
+use sqlx::postgres::PgPoolOptions;
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use tokio::runtime::Runtime;
+use tokio::sync::oneshot;
+
+const PASSWORD: &str = "e2e-harness-passphrase";
+
+struct TestNode {
+    multiaddr: String,
+    shutdown: Option<oneshot::Sender<()>>,
+    store: Arc<SecureBlockStore>,
+    storage: TempDir,
+}
+
+impl TestNode {
+    fn kill(&mut self) {
+        if let Some(tx) = self.shutdown.take() {
+            let _ = tx.send(());
+        }
+    }
+}
+
+struct Cluster {
+    // Owns the node tasks; dropping it tears the swarm down.
+    _runtime: Runtime,
+    nodes: Vec<TestNode>,
+}
+
+impl Cluster {
+    fn spawn(count: usize) -> Self {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("node runtime");
+
+        let nodes = (0..count)
+            .map(|_| {
+                let storage = tempfile::tempdir().expect("node storage dir");
+                let port = ephemeral_port();
+                let keypair = identity::Keypair::generate_ed25519();
+                let peer_id = PeerId::from(keypair.public());
+                let listen: Multiaddr = format!("/ip4/127.0.0.1/tcp/{port}").parse().unwrap();
+                let store = Arc::new(SecureBlockStore::new(
+                    storage.path().to_str().expect("utf-8 temp path"),
+                    1,
+                ));
+                let mut node = runtime
+                    .block_on(build_node(Arc::clone(&store), keypair, Vec::new(), HashSet::new(), None, None))
+                    .expect("build node");
+                node.maintenance_path = Some(maintenance::marker_path(storage.path().to_str().unwrap()));
+                let (shutdown_tx, shutdown_rx) = oneshot::channel();
+                runtime.spawn(drive_node(node, listen.clone(), shutdown_rx));
+
+                TestNode {
+                    multiaddr: format!("{listen}/p2p/{peer_id}"),
+                    shutdown: Some(shutdown_tx),
+                    store,
+                    storage,
+                }
+            })
+            .collect();
+
+        // Give every listener a moment to bind before the uploader dials.
+        std::thread::sleep(Duration::from_millis(300));
+        Self {
+            _runtime: runtime,
+            nodes,
+        }
+    }
+
+    fn peers(&self) -> Vec<String> {
+        self.nodes.iter().map(|n| n.multiaddr.clone()).collect()
+    }
+
+    fn kill(&mut self, index: usize) {
+        self.nodes[index].kill();
+        std::thread::sleep(Duration::from_millis(300));
+    }
+
+    /// Drops the maintenance marker in every node's storage and waits out
+    /// the poll that picks it up.
+    fn enter_maintenance(&self) {
+        for node in &self.nodes {
+            maintenance::enter(node.storage.path().to_str().unwrap()).expect("maintenance marker");
+        }
+        std::thread::sleep(maintenance::POLL_INTERVAL + Duration::from_millis(500));
+    }
+}
+
+impl Drop for Cluster {
+    fn drop(&mut self) {
+        for node in &mut self.nodes {
+            node.kill();
+        }
+    }
+}
+
+fn ephemeral_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|l| l.local_addr())
+        .map(|a| a.port())
+        .expect("ephemeral port")
+}
+
+fn uploader(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_neuro-uploader"))
+        .args(args)
+        .output()
+        .expect("spawn neuro-uploader");
+    assert!(
+        output.status.success(),
+        "neuro-uploader {:?} failed\nstdout:\n{}\nstderr:\n{}",
+        args,
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn upload(workdir: &Path, payload: &[u8], peers: &[String], replicas: usize) -> String {
+    let input = workdir.join("input.bin");
+    let manifest = workdir.join("manifest.json");
+    std::fs::write(&input, payload).unwrap();
+
+    let replicas = replicas.to_string();
+    let mut args = vec![
+        "upload",
+        "--file",
+        input.to_str().unwrap(),
+        "--password",
+        PASSWORD,
+        "--manifest-out",
+        manifest.to_str().unwrap(),
+        "--replica-factor",
+        &replicas,
+        "--peer",
+    ];
+    args.extend(peers.iter().map(String::as_str));
+    uploader(&args);
+    manifest.to_str().unwrap().to_string()
+}
+
+fn retrieve(workdir: &Path, manifest: &str) -> Vec<u8> {
+    let out = workdir.join("recovered.bin");
+    uploader(&[
+        "retrieve",
+        "--manifest",
+        manifest,
+        "--password",
+        PASSWORD,
+        "--out",
+        out.to_str().unwrap(),
+    ]);
+    std::fs::read(out).unwrap()
+}
+
+/// A `neurostore-gateway` process on a scratch database, killed and the
+/// database dropped on drop.
+struct TestGateway {
+    url: String,
+    process: Child,
+    database: String,
+    runtime: Runtime,
+    _kad_store: TempDir,
+}
+
+impl TestGateway {
+    /// `None` when no Postgres server is configured in `DATABASE_URL` or
+    /// the gateway binary has not been built next to this test.
+    fn spawn() -> Option<Self> {
+        let server = std::env::var("DATABASE_URL").ok()?;
+        let binary = std::env::current_exe()
+            .ok()?
+            .parent()?
+            .parent()?
+            .join(format!("neurostore-gateway{}", std::env::consts::EXE_SUFFIX));
+        if !binary.exists() {
+            eprintln!("skipping: {} not built", binary.display());
+            return None;
+        }
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("gateway client runtime");
+        let database = format!("neurostore_e2e_{}", hex::encode(rand::random::<[u8; 6]>()));
+        runtime.block_on(async {
+            let admin = PgPoolOptions::new().max_connections(1).connect(&server).await.expect("DATABASE_URL unreachable");
+            sqlx::query(&format!("CREATE DATABASE {database}")).execute(&admin).await.expect("create scratch database");
+        });
+        let mut database_url = reqwest::Url::parse(&server).expect("DATABASE_URL");
+        database_url.set_path(&database);
+
+        let kad_store = tempfile::tempdir().expect("gateway kad store");
+        let port = ephemeral_port();
+        let process = Command::new(binary)
+            .env("DATABASE_URL", database_url.as_str())
+            .env("PORT", port.to_string())
+            .env("P2P_PORT", ephemeral_port().to_string())
+            .env("KAD_STORE_PATH", kad_store.path())
+            .env("METADATA_SECRET", "e2e-metadata-secret")
+            .env("JWT_SECRET", "e2e-jwt-secret-of-reasonable-length")
+            .env("PROOF_SUBMIT_TOKEN", "e2e-proof-token")
+            .env("COMPLIANCE_SIGNING_KEY", "e2e-compliance-key")
+            .env("NODE_SHARED_SECRET", "e2e-node-secret")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("spawn neurostore-gateway");
+        let gateway = Self {
+            url: format!("http://127.0.0.1:{port}"),
+            process,
+            database,
+            runtime,
+            _kad_store: kad_store,
+        };
+
+        // Migrations run before the listener binds.
+        let ready = format!("{}/readyz", gateway.url);
+        let deadline = Instant::now() + Duration::from_secs(60);
+        while !gateway
+            .runtime
+            .block_on(reqwest::get(&ready))
+            .is_ok_and(|resp| resp.status().is_success())
+        {
+            assert!(Instant::now() < deadline, "gateway never became ready");
+            std::thread::sleep(Duration::from_millis(200));
+        }
+        Some(gateway)
+    }
+
+    /// A client signed in as a freshly registered user.
+    fn user(&self) -> GatewayClient {
+        let client = GatewayClient::new(&self.url, None).unwrap();
+        self.runtime
+            .block_on(client.register("e2e@example.com", "e2e-user-password", None))
+            .expect("register gateway user");
+        client
+    }
+}
+
+impl Drop for TestGateway {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        let server = std::env::var("DATABASE_URL").expect("DATABASE_URL");
+        let database = self.database.clone();
+        self.runtime.block_on(async {
+            if let Ok(admin) = PgPoolOptions::new().max_connections(1).connect(&server).await {
+                let _ = sqlx::query(&format!("DROP DATABASE IF EXISTS {database} WITH (FORCE)")).execute(&admin).await;
+            }
+        });
+    }
+}
+
+fn public_key_of(identity_output: &str) -> &str {
+    identity_output
+        .split("public_key=")
+        .nth(1)
+        .expect("identity commands print the public key")
+        .trim()
+}
+
+fn payload(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+#[test]
+fn upload_audit_retrieve_round_trip() {
+    let cluster = Cluster::spawn(4);
+    let workdir = tempfile::tempdir().unwrap();
+    let original = payload(700_000);
+
+    let manifest = upload(workdir.path(), &original, &cluster.peers(), 2);
+    uploader(&["audit", "--manifest", &manifest, "--password", PASSWORD]);
+
+    let recovered = retrieve(workdir.path(), &manifest);
+    assert_eq!(recovered, original, "round-trip must be byte-for-byte");
+}
+
+#[test]
+fn gateway_round_trip_ends_with_the_object_gone() {
+    let Some(gateway) = TestGateway::spawn() else {
+        return;
+    };
+    let client = gateway.user();
+    let token = client.token().expect("registration signs the user in");
+    let cluster = Cluster::spawn(3);
+    let workdir = tempfile::tempdir().unwrap();
+    let original = payload(300_000);
+    let input = workdir.path().join("input.bin");
+    let manifest = workdir.path().join("manifest.json");
+    std::fs::write(&input, &original).unwrap();
+    let manifest = manifest.to_str().unwrap();
+
+    let mut args = vec![
+        "upload", "--file", input.to_str().unwrap(), "--password", PASSWORD, "--manifest-out", manifest,
+        "--replica-factor", "2", "--gateway", &gateway.url, "--gateway-token", &token, "--peer",
+    ];
+    let peers = cluster.peers();
+    args.extend(peers.iter().map(String::as_str));
+    uploader(&args);
+    assert_eq!(retrieve(workdir.path(), manifest), original);
+
+    let json: serde_json::Value = serde_json::from_slice(&std::fs::read(manifest).unwrap()).unwrap();
+    let root = json["manifest_root"].as_str().unwrap();
+    let cid = json["shards"][0]["cid"].as_str().unwrap();
+    let located = gateway.runtime.block_on(client.locate_manifest(root)).expect("gateway knows the manifest");
+    assert_eq!(located.manifest["shards"].as_array().map(Vec::len), json["shards"].as_array().map(Vec::len));
+    // The gateway dials the manifest's peers when it is registered.
+    let deadline = Instant::now() + Duration::from_secs(15);
+    let shard = loop {
+        match gateway.runtime.block_on(client.manifest_shard(root, cid)) {
+            Ok(bytes) => break bytes,
+            Err(e) => assert!(Instant::now() < deadline, "gateway never served the shard: {e}"),
+        }
+        std::thread::sleep(Duration::from_millis(250));
+    };
+    assert!(!shard.is_empty());
+
+    let out = uploader(&["delete", "--manifest", manifest, "--password", PASSWORD, "--gateway-token", &token]);
+    assert!(out.contains("delete complete"), "{out}");
+    assert!(out.contains(&format!("gateway unregistered gateway={}", gateway.url)), "{out}");
+
+    let gone = gateway.runtime.block_on(client.locate_manifest(root)).unwrap_err();
+    assert_eq!(gone.status(), Some(reqwest::StatusCode::NOT_FOUND), "{gone}");
+    for node in &cluster.nodes {
+        for shard in json["shards"].as_array().unwrap() {
+            let cid = shard["cid"].as_str().unwrap();
+            assert!(!node.store.has_chunk(cid).unwrap(), "shard {cid} left on {}", node.multiaddr);
+        }
+    }
+    let out = workdir.path().join("after-delete.bin");
+    let output = Command::new(env!("CARGO_BIN_EXE_neuro-uploader"))
+        .args(["retrieve", "--manifest", manifest, "--password", PASSWORD, "--out", out.to_str().unwrap()])
+        .output()
+        .expect("spawn neuro-uploader");
+    assert!(!output.status.success(), "a deleted object must not come back");
+
+    // Deleting again finds nothing left and still succeeds.
+    uploader(&["delete", "--manifest", manifest, "--password", PASSWORD, "--gateway-token", &token]);
+}
+
+#[test]
+fn retrieve_survives_killed_node() {
+    let mut cluster = Cluster::spawn(4);
+    let workdir = tempfile::tempdir().unwrap();
+    let original = payload(300_000);
+
+    let manifest = upload(workdir.path(), &original, &cluster.peers(), 2);
+    cluster.kill(0);
+
+    let recovered = retrieve(workdir.path(), &manifest);
+    assert_eq!(recovered, original, "replicas must cover a lost node");
+}
+
+#[test]
+fn shards_keep_peer_ids_while_their_addresses_change() {
+    let cluster = Cluster::spawn(1);
+    let workdir = tempfile::tempdir().unwrap();
+    let original = payload(150_000);
+    let manifest = upload(workdir.path(), &original, &cluster.peers(), 1);
+    let addr = cluster.peers()[0].clone();
+    let (_, peer_id) = addr.rsplit_once("/p2p/").unwrap();
+    let read = || -> serde_json::Value { serde_json::from_slice(&std::fs::read(&manifest).unwrap()).unwrap() };
+
+    let before = read();
+    for shard in before["shards"].as_array().unwrap() {
+        assert_eq!(shard["peers"], serde_json::json!([format!("/p2p/{peer_id}")]));
+    }
+    assert_eq!(before["peer_addrs"][peer_id], serde_json::json!([addr]));
+
+    // A newer address that does not answer goes first; the older one is
+    // still dialled, and the placements never change.
+    let stale = format!("/ip4/127.0.0.1/tcp/{}/p2p/{peer_id}", ephemeral_port());
+    let peer_map = format!("{peer_id}={stale}");
+    uploader(&["rebind", "--manifest", &manifest, "--password", PASSWORD, "--peer-map", &peer_map]);
+    let after = read();
+    assert_eq!(after["shards"], before["shards"]);
+    assert_eq!(after["peer_addrs"][peer_id], serde_json::json!([stale, addr]));
+    assert_eq!(retrieve(workdir.path(), &manifest), original);
+}
+
+#[test]
+fn receipt_bundle_verifies_offline_and_catches_tampering() {
+    let cluster = Cluster::spawn(3);
+    let workdir = tempfile::tempdir().unwrap();
+    let manifest = upload(workdir.path(), &payload(200_000), &cluster.peers(), 2);
+    drop(cluster);
+
+    let out = uploader(&["verify-receipts", "--manifest", &manifest]);
+    assert!(out.contains("invalid=0 missing=0"), "{out}");
+
+    let receipts = format!("{manifest}.receipts.json");
+    let mut bundle: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&receipts).unwrap()).unwrap();
+    bundle["peers"][0]["stores"][0]["len"] = serde_json::json!(1);
+    std::fs::write(&receipts, serde_json::to_vec(&bundle).unwrap()).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_neuro-uploader"))
+        .args(["verify-receipts", "--manifest", &manifest])
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "a tampered receipt must fail");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("bad store signature"), "{stderr}");
+}
+
+#[test]
+fn autopilot_dry_run_plans_and_approve_carries_out_the_plan() {
+    let cluster = Cluster::spawn(4);
+    let workdir = tempfile::tempdir().unwrap();
+    let original = payload(200_000);
+    let manifest = upload(workdir.path(), &original, &cluster.peers(), 2);
+    let before = std::fs::read(&manifest).unwrap();
+
+    let quarantined = cluster.peers()[0].clone();
+    let policies = workdir.path().join("policies.json");
+    std::fs::write(
+        &policies,
+        serde_json::to_vec(&serde_json::json!([{ "peer": quarantined, "recommendation": "quarantine" }])).unwrap(),
+    )
+    .unwrap();
+    let plan = workdir.path().join("plan.json");
+    let plan = plan.to_str().unwrap();
+    let out = uploader(&[
+        "autopilot", "--manifest", &manifest, "--password", PASSWORD,
+        "--policy-file", policies.to_str().unwrap(), "--report-out", plan, "--dry-run",
+    ]);
+    assert!(out.contains("autopilot plan"), "{out}");
+    assert_eq!(std::fs::read(&manifest).unwrap(), before, "a dry run must not touch the manifest");
+    let report: serde_json::Value = serde_json::from_slice(&std::fs::read(plan).unwrap()).unwrap();
+    assert_eq!(report["dry_run"], true);
+    assert!(report["summary"]["shards_planned"].as_u64().unwrap() > 0);
+
+    let executed = workdir.path().join("executed.json");
+    uploader(&[
+        "autopilot", "--manifest", &manifest, "--password", PASSWORD,
+        "--approve", plan, "--report-out", executed.to_str().unwrap(),
+    ]);
+    let rewritten = std::fs::read_to_string(&manifest).unwrap();
+    assert!(!rewritten.contains(&quarantined), "the quarantined peer must be dropped");
+    assert_eq!(retrieve(workdir.path(), &manifest), original);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_neuro-uploader"))
+        .args(["autopilot", "--manifest", &manifest, "--password", PASSWORD, "--approve", plan])
+        .current_dir(workdir.path())
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "a plan for an older manifest must be refused");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("manifest changed"), "{stderr}");
+}
+
+#[test]
+fn nodes_in_maintenance_refuse_stores_but_keep_serving_reads() {
+    let cluster = Cluster::spawn(3);
+    let workdir = tempfile::tempdir().unwrap();
+    let original = payload(200_000);
+
+    let manifest = upload(workdir.path(), &original, &cluster.peers(), 2);
+    cluster.enter_maintenance();
+
+    uploader(&["audit", "--manifest", &manifest, "--password", PASSWORD]);
+    assert_eq!(retrieve(workdir.path(), &manifest), original);
+
+    let input = workdir.path().join("input.bin");
+    let refused = workdir.path().join("refused.json");
+    let mut args = vec![
+        "upload",
+        "--file",
+        input.to_str().unwrap(),
+        "--password",
+        PASSWORD,
+        "--manifest-out",
+        refused.to_str().unwrap(),
+        "--peer",
+    ];
+    let peers = cluster.peers();
+    args.extend(peers.iter().map(String::as_str));
+    let output = Command::new(env!("CARGO_BIN_EXE_neuro-uploader")).args(&args).output().unwrap();
+    assert!(!output.status.success(), "every peer is in maintenance");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("in maintenance"), "{stderr}");
+}
+
+#[test]
+fn retrieve_resumes_from_saved_shards_without_peers() {
+    let mut cluster = Cluster::spawn(3);
+    let workdir = tempfile::tempdir().unwrap();
+    let original = payload(200_000);
+
+    let manifest = upload(workdir.path(), &original, &cluster.peers(), 1);
+    // A cancelled retrieve saves the same bundle `retrieve-raw` writes.
+    let saved = workdir.path().join("recovered.bin.resume.json");
+    uploader(&["retrieve-raw", "--manifest", &manifest, "--raw-out", saved.to_str().unwrap()]);
+    for index in 0..3 {
+        cluster.kill(index);
+    }
+
+    let out = workdir.path().join("recovered.bin");
+    let stdout = uploader(&[
+        "retrieve",
+        "--manifest",
+        &manifest,
+        "--password",
+        PASSWORD,
+        "--out",
+        out.to_str().unwrap(),
+        "--resume",
+        saved.to_str().unwrap(),
+    ]);
+    assert!(stdout.contains("retrieve resume shards="), "{stdout}");
+    assert_eq!(std::fs::read(out).unwrap(), original);
+}
+
+#[test]
+fn verify_membership_checks_every_shard_against_the_root() {
+    let cluster = Cluster::spawn(3);
+    let workdir = tempfile::tempdir().unwrap();
+    let original = payload(400_000);
+    let plain = upload(workdir.path(), &original, &cluster.peers(), 1);
+
+    let input = workdir.path().join("input.bin");
+    let manifest = workdir.path().join("proved.json");
+    let manifest = manifest.to_str().unwrap();
+    let mut args = vec![
+        "upload",
+        "--file",
+        input.to_str().unwrap(),
+        "--password",
+        PASSWORD,
+        "--manifest-out",
+        manifest,
+        "--merkle-proofs",
+        "--peer",
+    ];
+    let peers = cluster.peers();
+    args.extend(peers.iter().map(String::as_str));
+    uploader(&args);
+
+    let out = workdir.path().join("recovered.bin");
+    let out = out.to_str().unwrap();
+    let report = workdir.path().join("report.json");
+    uploader(&[
+        "retrieve",
+        "--manifest",
+        manifest,
+        "--password",
+        PASSWORD,
+        "--out",
+        out,
+        "--verify-membership",
+        "--report-out",
+        report.to_str().unwrap(),
+    ]);
+    assert_eq!(std::fs::read(out).unwrap(), original);
+    let report: serde_json::Value = serde_json::from_slice(&std::fs::read(&report).unwrap()).unwrap();
+    assert_eq!(report["details"]["membership_failures"], serde_json::json!([]));
+
+    let without_proofs = Command::new(env!("CARGO_BIN_EXE_neuro-uploader"))
+        .args(["retrieve", "--manifest", &plain, "--password", PASSWORD, "--out", out, "--verify-membership"])
+        .output()
+        .expect("spawn neuro-uploader");
+    assert!(!without_proofs.status.success(), "a manifest without proofs cannot be verified");
+    let stderr = String::from_utf8_lossy(&without_proofs.stderr);
+    assert!(stderr.contains("--merkle-proofs"), "{stderr}");
+}
+
+#[test]
+fn refreshed_audit_vectors_replace_the_old_ones_and_pass() {
+    let cluster = Cluster::spawn(3);
+    let workdir = tempfile::tempdir().unwrap();
+    let manifest = upload(workdir.path(), &payload(200_000), &cluster.peers(), 2);
+    let challenges = |path: &str| -> Vec<Vec<String>> {
+        let json: serde_json::Value = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+        json["shards"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| serde_json::from_value(s["audit_challenges"].clone()).unwrap())
+            .collect()
+    };
+    let before = challenges(&manifest);
+
+    uploader(&["refresh-audits", "--manifest", &manifest, "--password", PASSWORD, "--rounds", "5"]);
+    let after = challenges(&manifest);
+    assert_eq!(after.len(), before.len());
+    for (old, new) in before.iter().zip(&after) {
+        assert_eq!(new.len(), 5);
+        assert!(new.iter().all(|c| !old.contains(c)), "every challenge must be fresh");
+    }
+    uploader(&["audit", "--manifest", &manifest, "--password", PASSWORD]);
+}
+
+#[test]
+fn every_recipient_can_retrieve_a_shared_upload() {
+    let cluster = Cluster::spawn(3);
+    let workdir = tempfile::tempdir().unwrap();
+    let dir = workdir.path();
+    let original = payload(200_000);
+    let input = dir.join("input.bin");
+    let manifest = dir.join("manifest.json");
+    let teammate = dir.join("teammate.pw");
+    let identity = dir.join("identity.key");
+    std::fs::write(&input, &original).unwrap();
+    std::fs::write(&teammate, "teammate-passphrase\n").unwrap();
+
+    let generated = uploader(&["identity", "generate", "--out", identity.to_str().unwrap()]);
+    let public_key = public_key_of(&generated);
+
+    let mut args = vec![
+        "upload",
+        "--file",
+        input.to_str().unwrap(),
+        "--password",
+        PASSWORD,
+        "--manifest-out",
+        manifest.to_str().unwrap(),
+        "--recipient-password-file",
+        teammate.to_str().unwrap(),
+        "--recipient-key",
+        public_key,
+        "--peer",
+    ];
+    let peers = cluster.peers();
+    args.extend(peers.iter().map(String::as_str));
+    uploader(&args);
+    let manifest = manifest.to_str().unwrap();
+
+    assert_eq!(retrieve(dir, manifest), original);
+    let out = dir.join("recovered-by-recipient.bin");
+    for unlock in [
+        ["--password-file", teammate.to_str().unwrap()],
+        ["--identity", identity.to_str().unwrap()],
+    ] {
+        let _ = std::fs::remove_file(&out);
+        let mut args = vec!["retrieve", "--manifest", manifest, "--out", out.to_str().unwrap()];
+        args.extend(unlock);
+        uploader(&args);
+        assert_eq!(std::fs::read(&out).unwrap(), original, "{unlock:?} must decrypt");
+    }
+}
+
+#[test]
+fn password_free_upload_opens_with_the_identity_alone() {
+    let cluster = Cluster::spawn(3);
+    let workdir = tempfile::tempdir().unwrap();
+    let dir = workdir.path();
+    let original = payload(250_000);
+    let input = dir.join("input.bin");
+    let manifest = dir.join("manifest.json");
+    let identity = dir.join("identity.key");
+    std::fs::write(&input, &original).unwrap();
+    let identity = identity.to_str().unwrap();
+
+    let generated = uploader(&["identity", "generate", "--out", identity]);
+    let public_key = public_key_of(&generated).to_string();
+    assert_eq!(public_key_of(&uploader(&["identity", "show", "--identity", identity])), public_key);
+
+    let mut args = vec![
+        "upload",
+        "--file",
+        input.to_str().unwrap(),
+        "--no-password",
+        "--recipient-key",
+        &public_key,
+        "--manifest-out",
+        manifest.to_str().unwrap(),
+        "--peer",
+    ];
+    let peers = cluster.peers();
+    args.extend(peers.iter().map(String::as_str));
+    uploader(&args);
+    let manifest = manifest.to_str().unwrap();
+
+    // Re-signing under the identity must keep the manifest verifiable.
+    uploader(&["refresh-audits", "--manifest", manifest, "--identity", identity, "--rounds", "3"]);
+    uploader(&["audit", "--manifest", manifest, "--identity", identity]);
+    let out = dir.join("recovered.bin");
+    let out = out.to_str().unwrap();
+    uploader(&["retrieve", "--manifest", manifest, "--identity", identity, "--out", out]);
+    assert_eq!(std::fs::read(out).unwrap(), original);
+
+    let with_password = Command::new(env!("CARGO_BIN_EXE_neuro-uploader"))
+        .args(["retrieve", "--manifest", manifest, "--password", PASSWORD, "--out", out])
+        .output()
+        .expect("spawn neuro-uploader");
+    assert!(!with_password.status.success(), "no password was a recipient");
+}
+
+#[test]
+fn upload_dir_shares_one_budget_and_writes_a_manifest_per_file() {
+    let cluster = Cluster::spawn(3);
+    let workdir = tempfile::tempdir().unwrap();
+    let source = workdir.path().join("photos");
+    let manifests = workdir.path().join("manifests");
+    let report = workdir.path().join("report.json");
+    std::fs::create_dir_all(source.join("2024")).unwrap();
+    let files = [
+        ("a.bin", payload(300_000)),
+        ("2024/b.bin", payload(40_000)),
+        ("c.bin", payload(900)),
+    ];
+    for (name, bytes) in &files {
+        std::fs::write(source.join(name), bytes).unwrap();
+    }
+
+    let mut args = vec![
+        "upload-dir",
+        "--dir",
+        source.to_str().unwrap(),
+        "--manifest-dir",
+        manifests.to_str().unwrap(),
+        "--password",
+        PASSWORD,
+        "--concurrency",
+        "4",
+        "--report-out",
+        report.to_str().unwrap(),
+        "--peer",
+    ];
+    let peers = cluster.peers();
+    args.extend(peers.iter().map(String::as_str));
+    uploader(&args);
+
+    let report: serde_json::Value = serde_json::from_slice(&std::fs::read(report).unwrap()).unwrap();
+    assert_eq!(report["ok"], true, "{report}");
+    assert_eq!(report["details"]["files_uploaded"], 3, "{report}");
+    for file in report["details"]["files"].as_array().unwrap() {
+        assert_eq!(file["requests_acked"], file["requests_total"], "{file}");
+    }
+    for (name, bytes) in &files {
+        let manifest = manifests.join(format!("{name}.manifest.json"));
+        assert_eq!(&retrieve(workdir.path(), manifest.to_str().unwrap()), bytes, "{name}");
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn serve_runs_operations_over_stdio_json_rpc() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::process::Stdio;
+
+    let cluster = Cluster::spawn(3);
+    let workdir = tempfile::tempdir().unwrap();
+    let dir = workdir.path();
+    let original = payload(150_000);
+    let input = dir.join("input.bin");
+    let manifest = dir.join("manifest.json");
+    let out = dir.join("recovered.bin");
+    std::fs::write(&input, &original).unwrap();
+
+    let mut serve = Command::new(env!("CARGO_BIN_EXE_neuro-uploader"))
+        .args(["serve", "--stdio"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("spawn neuro-uploader serve");
+    let mut stdin = serve.stdin.take().unwrap();
+    let mut answers = BufReader::new(serve.stdout.take().unwrap()).lines();
+    let mut call = |request: serde_json::Value| -> serde_json::Value {
+        writeln!(stdin, "{request}").unwrap();
+        let line = answers.next().expect("an answer per request").unwrap();
+        serde_json::from_str(&line).expect("answers are JSON")
+    };
+
+    let uploaded = call(serde_json::json!({
+        "jsonrpc": "2.0", "id": 1, "method": "upload",
+        "params": {
+            "file": input, "password": PASSWORD, "manifest_out": manifest,
+            "replica_factor": 2, "peer": cluster.peers(),
+        },
+    }));
+    assert_eq!(uploaded["id"], 1);
+    assert_eq!(uploaded["result"]["operation"], "upload", "{uploaded}");
+    assert_eq!(uploaded["result"]["ok"], true);
+
+    let audited = call(serde_json::json!({
+        "jsonrpc": "2.0", "id": 2, "method": "audit",
+        "params": {"manifest": manifest, "password": PASSWORD},
+    }));
+    assert_eq!(audited["result"]["details"]["passed"], audited["result"]["details"]["sampled"], "{audited}");
+
+    let retrieved = call(serde_json::json!({
+        "jsonrpc": "2.0", "id": "r", "method": "retrieve",
+        "params": {"manifest": manifest, "password": PASSWORD, "out": out},
+    }));
+    assert_eq!(retrieved["result"]["details"]["bytes"], original.len(), "{retrieved}");
+    assert_eq!(std::fs::read(&out).unwrap(), original);
+
+    let status = call(serde_json::json!({"jsonrpc": "2.0", "id": 3, "method": "status"}));
+    assert_eq!(status["result"]["completed"], 3, "{status}");
+    assert_eq!(status["result"]["idle_swarms"], 1, "operations reuse one swarm");
+
+    let bad = call(serde_json::json!({"jsonrpc": "2.0", "id": 4, "method": "upload", "params": {"bogus": 1}}));
+    assert_eq!(bad["error"]["code"], -32602);
+
+    drop(stdin);
+    assert!(serve.wait().unwrap().success(), "serve exits once stdin closes");
+}
@@ -0,0 +1,332 @@
+//! Bandwidth vouchers. The gateway mints one with every presigned manifest:
+//! it names the object, who may pull it, how many shard bytes it covers and
+//! until when. Whoever serves shards for it counts the bytes against that
+//! cap, and a node that served some redeems them with a [`Redemption`]
+//! signed by its identity key, which the gateway settles into the node's
+//! earnings.
+//!
+//! Only the gateway holds the voucher secret. Nodes read the claims with
+//! [`Voucher::parse`] to protect their own uplink; a forged voucher is
+//! caught when its redemption fails [`Voucher::verify_signature`].
+
+use std::collections::HashMap;
+
+use hmac::{Hmac, Mac};
+use libp2p_identity::{Keypair, PeerId, PublicKey, SigningError};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// Prefix of every token this crate mints. The `v1.` tokens before it
+/// carried no byte cap and are no longer accepted.
+pub const PREFIX: &str = "v2.";
+/// Hex characters in a voucher id.
+const ID_HEX_LEN: usize = 32;
+/// Entries a [`Ledger`] holds before it drops expired ones.
+const PRUNE_AT: usize = 4096;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum VoucherError {
+    #[error("malformed bandwidth voucher")]
+    Malformed,
+    #[error("bandwidth voucher signature does not verify")]
+    BadSignature,
+    #[error("bandwidth voucher expired")]
+    Expired,
+    #[error("bandwidth voucher exhausted: {used} of {max_bytes} bytes already served")]
+    Exhausted { used: u64, max_bytes: u64 },
+}
+
+/// The claims behind a voucher token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Voucher {
+    /// Random, so two vouchers for the same object are capped and redeemed
+    /// separately.
+    pub id: String,
+    /// The account the manifest was issued to.
+    pub principal: String,
+    pub object_cid: String,
+    /// Shard bytes that may be served under this voucher, across all nodes.
+    pub max_bytes: u64,
+    /// Unix seconds.
+    pub expires_at: u64,
+}
+
+impl Voucher {
+    pub fn new(
+        principal: impl Into<String>,
+        object_cid: impl Into<String>,
+        max_bytes: u64,
+        expires_at: u64,
+    ) -> Self {
+        let mut id = [0u8; ID_HEX_LEN / 2];
+        rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut id);
+        Self {
+            id: hex::encode(id),
+            principal: principal.into(),
+            object_cid: object_cid.into(),
+            max_bytes,
+            expires_at,
+        }
+    }
+
+    /// The token handed to clients: `v2.<claims>.<hmac>`. The principal goes
+    /// last in the claims, so it may itself contain `:` or `.`.
+    pub fn mint(&self, secret: &[u8]) -> String {
+        let claims = format!(
+            "{}:{}:{}:{}:{}",
+            self.id, self.max_bytes, self.expires_at, self.object_cid, self.principal
+        );
+        let signature = hex::encode(mac(secret, &claims).finalize().into_bytes());
+        format!("{PREFIX}{claims}.{signature}")
+    }
+
+    /// Reads a token's claims without checking who minted it.
+    pub fn parse(token: &str) -> Result<Self, VoucherError> {
+        split(token).map(|(voucher, _, _)| voucher)
+    }
+
+    /// Checks the token was minted with `secret`, whether or not it has
+    /// expired since; redemptions arrive after expiry.
+    pub fn verify_signature(token: &str, secret: &[u8]) -> Result<Self, VoucherError> {
+        let (voucher, claims, signature) = split(token)?;
+        mac(secret, claims)
+            .verify_slice(&signature)
+            .map_err(|_| VoucherError::BadSignature)?;
+        Ok(voucher)
+    }
+
+    /// [`Voucher::verify_signature`], then refuses the voucher once it has
+    /// expired at `now` (Unix seconds).
+    pub fn verify(token: &str, secret: &[u8], now: u64) -> Result<Self, VoucherError> {
+        let voucher = Self::verify_signature(token, secret)?;
+        if voucher.is_expired(now) {
+            return Err(VoucherError::Expired);
+        }
+        Ok(voucher)
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+
+    /// What has been served under the voucher once `bytes` more are, given
+    /// `used` so far; refused if that passes `max_bytes` or it has expired.
+    pub fn admit(&self, used: u64, bytes: u64, now: u64) -> Result<u64, VoucherError> {
+        if self.is_expired(now) {
+            return Err(VoucherError::Expired);
+        }
+        match used.checked_add(bytes) {
+            Some(total) if total <= self.max_bytes => Ok(total),
+            _ => Err(VoucherError::Exhausted {
+                used,
+                max_bytes: self.max_bytes,
+            }),
+        }
+    }
+}
+
+fn mac(secret: &[u8], claims: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(claims.as_bytes());
+    mac
+}
+
+fn split(token: &str) -> Result<(Voucher, &str, Vec<u8>), VoucherError> {
+    let (claims, signature) = token
+        .strip_prefix(PREFIX)
+        .and_then(|rest| rest.rsplit_once('.'))
+        .ok_or(VoucherError::Malformed)?;
+    let signature = hex::decode(signature).map_err(|_| VoucherError::Malformed)?;
+
+    let mut parts = claims.splitn(5, ':');
+    let mut next = || parts.next().ok_or(VoucherError::Malformed);
+    let id = next()?;
+    let max_bytes = next()?.parse().map_err(|_| VoucherError::Malformed)?;
+    let expires_at = next()?.parse().map_err(|_| VoucherError::Malformed)?;
+    let object_cid = next()?;
+    let principal = next()?;
+    if id.len() != ID_HEX_LEN || !id.bytes().all(|b| b.is_ascii_hexdigit()) || object_cid.is_empty() {
+        return Err(VoucherError::Malformed);
+    }
+    let voucher = Voucher {
+        id: id.to_string(),
+        principal: principal.to_string(),
+        object_cid: object_cid.to_string(),
+        max_bytes,
+        expires_at,
+    };
+    Ok((voucher, claims, signature))
+}
+
+/// Bytes served per voucher, held in memory by whoever serves shards.
+/// Vouchers are forgotten once expired, since [`Voucher::admit`] refuses
+/// them from then on anyway.
+#[derive(Debug, Default)]
+pub struct Ledger {
+    used: HashMap<String, (u64, u64)>,
+}
+
+impl Ledger {
+    /// Counts `bytes` against the voucher, returning how many it has left,
+    /// or leaves the count alone and refuses them.
+    pub fn charge(&mut self, voucher: &Voucher, bytes: u64, now: u64) -> Result<u64, VoucherError> {
+        if self.used.len() >= PRUNE_AT {
+            self.prune(now);
+        }
+        let used = self.used(&voucher.id);
+        let total = voucher.admit(used, bytes, now)?;
+        self.used.insert(voucher.id.clone(), (total, voucher.expires_at));
+        Ok(voucher.max_bytes - total)
+    }
+
+    pub fn used(&self, id: &str) -> u64 {
+        self.used.get(id).map_or(0, |&(used, _)| used)
+    }
+
+    /// Drops vouchers expired at `now`, returning how many.
+    pub fn prune(&mut self, now: u64) -> usize {
+        let before = self.used.len();
+        self.used.retain(|_, &mut (_, expires_at)| now < expires_at);
+        before - self.used.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.used.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.used.is_empty()
+    }
+}
+
+/// A node's claim to have served `bytes` under a voucher, signed with its
+/// libp2p identity key. Keys and signatures are hex so the claim travels
+/// as JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Redemption {
+    /// The voucher token as the node received it.
+    pub voucher: String,
+    pub peer_id: String,
+    pub bytes: u64,
+    pub timestamp_ms: u64,
+    /// Protobuf-encoded public key.
+    pub public_key: String,
+    pub signature: String,
+}
+
+impl Redemption {
+    pub fn sign(
+        keypair: &Keypair,
+        voucher: &str,
+        bytes: u64,
+        timestamp_ms: u64,
+    ) -> Result<Self, SigningError> {
+        let peer_id = keypair.public().to_peer_id().to_string();
+        let signature = keypair.sign(&Self::payload(voucher, &peer_id, bytes, timestamp_ms))?;
+        Ok(Self {
+            voucher: voucher.to_string(),
+            peer_id,
+            bytes,
+            timestamp_ms,
+            public_key: hex::encode(keypair.public().encode_protobuf()),
+            signature: hex::encode(signature),
+        })
+    }
+
+    /// What the node signs.
+    pub fn payload(voucher: &str, peer_id: &str, bytes: u64, timestamp_ms: u64) -> Vec<u8> {
+        format!("NEURO:REDEEM:{voucher}:{peer_id}:{bytes}:{timestamp_ms}").into_bytes()
+    }
+
+    /// Whether the claim was signed by the key behind `peer_id`. Says
+    /// nothing about the voucher itself.
+    pub fn verify_signer(&self) -> bool {
+        let (Ok(peer_id), Ok(public_key), Ok(signature)) = (
+            self.peer_id.parse::<PeerId>(),
+            hex::decode(&self.public_key),
+            hex::decode(&self.signature),
+        ) else {
+            return false;
+        };
+        let Ok(public_key) = PublicKey::try_decode_protobuf(&public_key) else {
+            return false;
+        };
+        let payload = Self::payload(&self.voucher, &self.peer_id, self.bytes, self.timestamp_ms);
+        PeerId::from_public_key(&public_key) == peer_id && public_key.verify(&payload, &signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"gateway-voucher-secret";
+    const NOW: u64 = 1_800_000_000;
+
+    fn voucher(max_bytes: u64) -> Voucher {
+        Voucher::new("alice@example.com", "QmObject", max_bytes, NOW + 3600)
+    }
+
+    fn keypair(seed: u8) -> Keypair {
+        Keypair::ed25519_from_bytes([seed; 32]).unwrap()
+    }
+
+    #[test]
+    fn minted_vouchers_verify_and_parse_back() {
+        let voucher = Voucher::new("ops:team.lead@example.com", "QmObject", 1 << 20, NOW + 60);
+        let token = voucher.mint(SECRET);
+        assert!(token.starts_with(PREFIX));
+        assert_eq!(Voucher::verify(&token, SECRET, NOW).unwrap(), voucher);
+        assert_eq!(Voucher::parse(&token).unwrap(), voucher);
+    }
+
+    #[test]
+    fn tampered_or_foreign_vouchers_are_refused() {
+        let token = voucher(1024).mint(SECRET);
+        let raised = token.replacen(":1024:", ":999999:", 1);
+        assert_eq!(Voucher::verify(&raised, SECRET, NOW), Err(VoucherError::BadSignature));
+        assert_eq!(Voucher::verify(&token, b"other", NOW), Err(VoucherError::BadSignature));
+        assert_eq!(Voucher::parse("v1.a:b:1.00"), Err(VoucherError::Malformed));
+        assert_eq!(Voucher::parse("v2.nope.zz"), Err(VoucherError::Malformed));
+    }
+
+    #[test]
+    fn expired_vouchers_still_verify_for_redemption() {
+        let token = voucher(1024).mint(SECRET);
+        let later = NOW + 3600;
+        assert_eq!(Voucher::verify(&token, SECRET, later), Err(VoucherError::Expired));
+        assert!(Voucher::verify_signature(&token, SECRET).is_ok());
+    }
+
+    #[test]
+    fn ledger_enforces_the_byte_cap() {
+        let voucher = voucher(1000);
+        let mut ledger = Ledger::default();
+        assert_eq!(ledger.charge(&voucher, 600, NOW), Ok(400));
+        assert_eq!(
+            ledger.charge(&voucher, 600, NOW),
+            Err(VoucherError::Exhausted { used: 600, max_bytes: 1000 })
+        );
+        assert_eq!(ledger.charge(&voucher, 400, NOW), Ok(0));
+        assert_eq!(ledger.used(&voucher.id), 1000);
+        assert_eq!(ledger.charge(&voucher, 0, NOW + 3600), Err(VoucherError::Expired));
+        assert_eq!(ledger.prune(NOW + 3600), 1);
+        assert!(ledger.is_empty());
+    }
+
+    #[test]
+    fn redemptions_are_bound_to_the_signing_peer() {
+        let token = voucher(1024).mint(SECRET);
+        let redemption = Redemption::sign(&keypair(1), &token, 512, 42).unwrap();
+        assert!(redemption.verify_signer());
+
+        let inflated = Redemption { bytes: 1024, ..redemption.clone() };
+        assert!(!inflated.verify_signer());
+
+        let impostor = Redemption {
+            peer_id: keypair(2).public().to_peer_id().to_string(),
+            ..redemption
+        };
+        assert!(!impostor.verify_signer());
+    }
+}
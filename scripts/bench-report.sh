@@ -0,0 +1,75 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+BENCH_REPORT="bench-report.json"
+BASELINE=""
+FILTER=""
+
+while [[ $# -gt 0 ]]; do
+  case "$1" in
+    --bench-report)
+      BENCH_REPORT="$2"
+      shift 2
+      ;;
+    --baseline)
+      BASELINE="$2"
+      shift 2
+      ;;
+    --filter)
+      FILTER="$2"
+      shift 2
+      ;;
+    *)
+      echo "unknown argument: $1" >&2
+      exit 1
+      ;;
+  esac
+done
+
+require_cmd() {
+  if ! command -v "$1" >/dev/null 2>&1; then
+    echo "missing required command: $1" >&2
+    exit 1
+  fi
+}
+
+require_cmd cargo
+require_cmd jq
+
+ROOT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")/.." && pwd)"
+CRITERION_DIR="${ROOT_DIR}/target/criterion"
+
+BENCH_ARGS=()
+if [[ -n "${BASELINE}" ]]; then
+  BENCH_ARGS+=(--save-baseline "${BASELINE}")
+fi
+if [[ -n "${FILTER}" ]]; then
+  BENCH_ARGS+=("${FILTER}")
+fi
+
+cd "${ROOT_DIR}"
+cargo bench -p neuro-client-sdk --bench pipeline -- "${BENCH_ARGS[@]}"
+
+ESTIMATE_NAME="new"
+if [[ -n "${BASELINE}" ]]; then
+  ESTIMATE_NAME="${BASELINE}"
+fi
+
+# One row per benchmark id: mean/median in ns and throughput in bytes/s when declared.
+find "${CRITERION_DIR}" -path "*/${ESTIMATE_NAME}/estimates.json" | sort | while read -r estimates; do
+  dir="$(dirname "${estimates}")"
+  jq -c --slurpfile bench "${dir}/benchmark.json" '{
+    id: $bench[0].full_id,
+    mean_ns: .mean.point_estimate,
+    median_ns: .median.point_estimate,
+    std_dev_ns: .std_dev.point_estimate,
+    throughput_bytes_per_sec: (
+      if $bench[0].throughput.Bytes then
+        ($bench[0].throughput.Bytes / (.mean.point_estimate / 1e9))
+      else null end
+    )
+  }' "${estimates}"
+done | jq -s --arg commit "$(git rev-parse HEAD 2>/dev/null || echo unknown)" \
+  '{commit: $commit, generated_at: (now | todate), results: .}' > "${BENCH_REPORT}"
+
+echo "bench report written to ${BENCH_REPORT}"
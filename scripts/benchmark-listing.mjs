@@ -0,0 +1,139 @@
+#!/usr/bin/env node
+// S3 listing latency against a running gateway: a cold listing, the same
+// listing again (served from the gateway's listing cache), a full paginated
+// walk with ListObjectsV2 continuation tokens, and a delimiter listing of
+// one directory. Point it at a large bucket, or let it seed one.
+import crypto from "node:crypto";
+import process from "node:process";
+
+function required(name) {
+    const v = process.env[name];
+    if (!v) {
+        throw new Error(`Missing required env var: ${name}`);
+    }
+    return v;
+}
+
+function percentile(values, p) {
+    if (values.length === 0) return 0;
+    const sorted = [...values].sort((a, b) => a - b);
+    const idx = Math.min(sorted.length - 1, Math.floor((p / 100) * sorted.length));
+    return sorted[idx];
+}
+
+function stats(values) {
+    const sum = values.reduce((a, b) => a + b, 0);
+    return {
+        avg: values.length ? sum / values.length : 0,
+        p50: percentile(values, 50),
+        p95: percentile(values, 95),
+        p99: percentile(values, 99),
+        min: values.length ? Math.min(...values) : 0,
+        max: values.length ? Math.max(...values) : 0,
+    };
+}
+
+function tag(xml, name) {
+    const m = xml.match(new RegExp(`<${name}>([^<]*)</${name}>`));
+    return m ? m[1] : null;
+}
+
+function count(xml, name) {
+    return (xml.match(new RegExp(`<${name}>`, "g")) || []).length;
+}
+
+async function timedList(base, bucket, token, params) {
+    const url = `${base}/${encodeURIComponent(bucket)}?${new URLSearchParams(params)}`;
+    const start = performance.now();
+    const res = await fetch(url, { headers: { authorization: `Bearer ${token}` } });
+    const body = await res.text();
+    const elapsed = performance.now() - start;
+    if (!res.ok) {
+        throw new Error(`HTTP ${res.status} for ${url.slice(0, 80)}: ${body.slice(0, 120)}`);
+    }
+    return { elapsed, body };
+}
+
+async function seed(base, bucket, token, objects, dirs) {
+    const payload = crypto.randomBytes(64);
+    const concurrency = 16;
+    let next = 0;
+    async function worker() {
+        while (next < objects) {
+            const i = next++;
+            const key = `bench/dir-${i % dirs}/object-${String(i).padStart(8, "0")}`;
+            const res = await fetch(`${base}/${encodeURIComponent(bucket)}/${key}`, {
+                method: "PUT",
+                body: payload,
+                headers: { authorization: `Bearer ${token}`, "content-type": "application/octet-stream" },
+            });
+            if (!res.ok) {
+                throw new Error(`seeding ${key} failed: HTTP ${res.status}`);
+            }
+            if ((i + 1) % 1000 === 0) process.stdout.write(`seeded ${i + 1}/${objects}\n`);
+        }
+    }
+    await Promise.all(Array.from({ length: concurrency }, worker));
+}
+
+function printReport(name, values, extra = "") {
+    const s = stats(values);
+    console.log(
+        `${name.padEnd(22)} avg=${s.avg.toFixed(1)}ms p50=${s.p50.toFixed(1)} p95=${s.p95.toFixed(1)} p99=${s.p99.toFixed(1)} min=${s.min.toFixed(1)} max=${s.max.toFixed(1)}${extra}`
+    );
+}
+
+async function main() {
+    const base = required("GATEWAY_URL").replace(/\/$/, "");
+    const token = required("NEURO_TOKEN");
+    const bucket = required("BENCH_BUCKET");
+    const runs = parseInt(process.env.BENCH_RUNS || "20", 10);
+    const seedObjects = parseInt(process.env.BENCH_SEED_OBJECTS || "0", 10);
+    const dirs = parseInt(process.env.BENCH_DIRS || "100", 10);
+    const prefix = process.env.BENCH_PREFIX || "bench/";
+
+    if (seedObjects > 0) {
+        console.log(`Seeding ${seedObjects} objects across ${dirs} directories under bench/`);
+        await seed(base, bucket, token, seedObjects, dirs);
+    }
+
+    const query = { "list-type": "2", prefix, "max-keys": "1000" };
+    const cold = await timedList(base, bucket, token, query);
+    console.log(`\nCold first page: ${cold.elapsed.toFixed(1)}ms (${count(cold.body, "Key")} keys)`);
+
+    const warm = [];
+    for (let i = 0; i < runs; i++) {
+        warm.push((await timedList(base, bucket, token, query)).elapsed);
+    }
+
+    const pages = [];
+    let keys = 0;
+    let continuation = null;
+    do {
+        const params = { ...query };
+        if (continuation) params["continuation-token"] = continuation;
+        const page = await timedList(base, bucket, token, params);
+        pages.push(page.elapsed);
+        keys += count(page.body, "Key");
+        continuation = tag(page.body, "IsTruncated") === "true" ? tag(page.body, "NextContinuationToken") : null;
+    } while (continuation);
+
+    const delimited = [];
+    let commonPrefixes = 0;
+    for (let i = 0; i < runs; i++) {
+        const page = await timedList(base, bucket, token, { "list-type": "2", prefix, delimiter: "/" });
+        delimited.push(page.elapsed);
+        commonPrefixes = count(page.body, "CommonPrefixes");
+    }
+
+    console.log(`\n[${bucket} prefix=${prefix}]`);
+    printReport("first page (warm)", warm);
+    const walkMs = pages.reduce((a, b) => a + b, 0);
+    printReport("paginated walk", pages, ` pages=${pages.length} keys=${keys} total=${walkMs.toFixed(0)}ms`);
+    printReport("delimiter listing", delimited, ` common_prefixes=${commonPrefixes}`);
+}
+
+main().catch((err) => {
+    console.error("Benchmark failed:", err.message);
+    process.exit(1);
+});
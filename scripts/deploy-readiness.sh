@@ -231,14 +231,26 @@ if [[ -z "${NODE_SHARED_SECRET_VALUE}" ]]; then
   echo "NODE_SHARED_SECRET missing from env file" >&2
   exit 1
 fi
-NODE_REG_AUTH_CODE="$(curl -sS -o "${tmpdir}/node-reg-auth.out" -w '%{http_code}' \
+READINESS_PEER_ID="12D3KooWD3eckifWpRn9wQpMG9R9hX3sD158z7EqHWmweQAJU5SA"
+NODE_REG_UNSIGNED_CODE="$(curl -sS -o "${tmpdir}/node-reg-unsigned.out" -w '%{http_code}' \
   -X POST "${BASE_URL}/api/nodes/register" \
   -H 'content-type: application/json' \
   -H "x-node-secret: ${NODE_SHARED_SECRET_VALUE}" \
-  -d '{"peer_id":"ReadinessNode123","wallet_address":"0x1111111111111111111111111111111111111111","capacity_gb":50,"declared_location":"IN-KA"}')"
-if [[ "${NODE_REG_AUTH_CODE}" != "200" ]]; then
-  echo "node registration with secret failed (http ${NODE_REG_AUTH_CODE})" >&2
-  cat "${tmpdir}/node-reg-auth.out" >&2
+  -d "{\"peer_id\":\"${READINESS_PEER_ID}\",\"wallet_address\":\"0x1111111111111111111111111111111111111111\",\"capacity_gb\":50,\"declared_location\":\"IN-KA\"}")"
+if [[ "${NODE_REG_UNSIGNED_CODE}" != "400" ]]; then
+  echo "node registration should reject a request without a signed challenge, got ${NODE_REG_UNSIGNED_CODE}" >&2
+  cat "${tmpdir}/node-reg-unsigned.out" >&2
+  exit 1
+fi
+
+NODE_CHALLENGE_CODE="$(curl -sS -o "${tmpdir}/node-challenge.out" -w '%{http_code}' \
+  -X POST "${BASE_URL}/api/nodes/register/challenge" \
+  -H 'content-type: application/json' \
+  -H "x-node-secret: ${NODE_SHARED_SECRET_VALUE}" \
+  -d "{\"peer_id\":\"${READINESS_PEER_ID}\"}")"
+if [[ "${NODE_CHALLENGE_CODE}" != "200" ]]; then
+  echo "node registration challenge with secret failed (http ${NODE_CHALLENGE_CODE})" >&2
+  cat "${tmpdir}/node-challenge.out" >&2
   exit 1
 fi
 
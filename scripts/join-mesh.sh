@@ -53,14 +53,15 @@ docker run -d
 
 # 5. Register with AI Sentinel
 echo "Registering with Indian AI Sentinel Mesh..."
-curl -X POST https://gateway.neurostore.in/api/nodes/register 
-  -H "Content-Type: application/json" 
-  -d "{
-    "peer_id": "$PEER_ID",
-    "wallet_address": "$WALLET_ADDRESS",
-    "capacity_gb": ${STORAGE_SIZE//[!0-9]/},
-    "declared_location": "IN-AUTO"
-  }"
+docker run --rm \
+  -v $(pwd)/blobs:/var/lib/neurostore/blobs \
+  -e NODE_SHARED_SECRET="$NODE_SHARED_SECRET" \
+  neurostore/node-agent:latest \
+  --storage-path /var/lib/neurostore/blobs register \
+  --gateway https://gateway.neurostore.in \
+  --wallet "$WALLET_ADDRESS" \
+  --capacity-gb "${STORAGE_SIZE//[!0-9]/}" \
+  --location IN-AUTO
 
 echo "---------------------------------------------------"
 echo "SUCCESS: Your node is now active and earning $NEURO."
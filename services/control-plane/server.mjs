@@ -94,6 +94,22 @@ async function initDb() {
             ALTER TABLE cp_sessions
             ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ NOT NULL DEFAULT (NOW() + INTERVAL '24 hours');
         `);
+        await client.query(`
+            ALTER TABLE cp_nodes
+            ADD COLUMN IF NOT EXISTS accrued_earnings_usd DOUBLE PRECISION NOT NULL DEFAULT 0,
+            ADD COLUMN IF NOT EXISTS last_accrual_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            ADD COLUMN IF NOT EXISTS audit_pass_streak INTEGER NOT NULL DEFAULT 0,
+            ADD COLUMN IF NOT EXISTS egress_bytes BIGINT NOT NULL DEFAULT 0;
+            CREATE TABLE IF NOT EXISTS cp_node_penalties (
+                id SERIAL PRIMARY KEY,
+                peer_id TEXT NOT NULL,
+                reason TEXT NOT NULL,
+                score_delta DOUBLE PRECISION NOT NULL,
+                details JSONB,
+                created_at TIMESTAMPTZ DEFAULT NOW()
+            );
+            CREATE INDEX IF NOT EXISTS idx_cp_node_penalties_peer ON cp_node_penalties(peer_id, created_at DESC);
+        `);
     } finally {
         client.release();
     }
@@ -320,6 +336,79 @@ const PRICING = {
     enterprise: { storage_per_gb: 0.011, egress_per_gb: 0.008, max_storage_gb: Infinity, max_egress_gb: Infinity },
 };
 
+// ── Node Earnings ──────────────────────────────────────────────
+// Nodes earn NODE_BASE_RATE_USD per stored GB-month, scaled by score
+// (50 = 1.0x, floored at 0.1x). Earnings accrue on every heartbeat for the
+// time since the last one, and only while the node is active.
+const NODE_BASE_RATE_USD = 0.05;
+const SECONDS_PER_MONTH = 30 * 24 * 3600;
+const DASHBOARD_PENALTY_LIMIT = 20;
+const HIGH_LATENCY_MS = 500;
+
+function scoreMultiplier(score) {
+    return Math.max(0.1, score / 50.0);
+}
+
+async function accrueEarnings(peerId) {
+    await pool.query(`
+        UPDATE cp_nodes
+        SET accrued_earnings_usd = accrued_earnings_usd
+                + COALESCE(used_gb, 0) * $2 * GREATEST(0.1, score / 50.0)
+                  * EXTRACT(EPOCH FROM (NOW() - last_accrual_at)) / $3,
+            last_accrual_at = NOW()
+        WHERE peer_id = $1 AND status = 'active'
+    `, [peerId, NODE_BASE_RATE_USD, SECONDS_PER_MONTH]);
+}
+
+// Audit results and penalties reported with a heartbeat: a failed proof
+// resets the pass streak, and every penalty is kept for the dashboard.
+async function recordNodeHealth(peerId, { passedProofs = 0, failedProofs = 0, latencyMs = 0 }) {
+    await pool.query(`
+        UPDATE cp_nodes
+        SET audit_pass_streak = CASE WHEN $2 > 0 THEN 0 ELSE audit_pass_streak + $3 END
+        WHERE peer_id = $1
+    `, [peerId, failedProofs, passedProofs]);
+    const penalties = [];
+    if (failedProofs > 0) {
+        penalties.push(["failed_proofs", -5.0 * failedProofs, { failed_proofs: failedProofs }]);
+    }
+    if (latencyMs > HIGH_LATENCY_MS) {
+        penalties.push(["high_latency", -2.0, { latency_ms: latencyMs }]);
+    }
+    for (const [reason, delta, details] of penalties) {
+        await pool.query(
+            "INSERT INTO cp_node_penalties (peer_id, reason, score_delta, details) VALUES ($1, $2, $3, $4)",
+            [peerId, reason, delta, JSON.stringify(details)]
+        );
+    }
+}
+
+async function nodeDashboard(peerId) {
+    const nodeRes = await pool.query("SELECT * FROM cp_nodes WHERE peer_id = $1", [peerId]);
+    const node = nodeRes.rows[0];
+    if (!node) return null;
+    const penaltiesRes = await pool.query(
+        "SELECT reason, score_delta, details, created_at FROM cp_node_penalties WHERE peer_id = $1 ORDER BY created_at DESC LIMIT $2",
+        [peerId, DASHBOARD_PENALTY_LIMIT]
+    );
+    const penaltyCount = await pool.query("SELECT COUNT(*) FROM cp_node_penalties WHERE peer_id = $1", [peerId]);
+    const usedGb = node.used_gb || 0;
+    return {
+        peer_id: node.peer_id,
+        status: node.status,
+        score: node.score,
+        used_gb: usedGb,
+        max_gb: node.max_gb,
+        accrued_earnings_usd: +node.accrued_earnings_usd.toFixed(6),
+        monthly_rate_usd: +(usedGb * NODE_BASE_RATE_USD * scoreMultiplier(node.score)).toFixed(4),
+        audit_pass_streak: node.audit_pass_streak,
+        egress_bytes: parseInt(node.egress_bytes, 10),
+        penalty_count: parseInt(penaltyCount.rows[0].count, 10),
+        recent_penalties: penaltiesRes.rows,
+        last_heartbeat: node.last_heartbeat,
+    };
+}
+
 // ── Express App ────────────────────────────────────────────────
 const app = express();
 app.disable("x-powered-by");
@@ -376,9 +465,7 @@ app.get("/v1/nodes/earnings", requireUserSession, async (req, res) => {
     const node = nodeRes.rows[0];
 
     const gbStored = node.used_gb || 0;
-    const baseRate = 0.05;
-    const scoreMultiplier = Math.max(0.1, node.score / 50.0);
-    const estimatedEarnings = (gbStored * baseRate * scoreMultiplier).toFixed(4);
+    const estimatedEarnings = (gbStored * NODE_BASE_RATE_USD * scoreMultiplier(node.score)).toFixed(4);
 
     res.json({
         peer_id: node.peer_id,
@@ -629,15 +716,18 @@ app.post("/v1/nodes/register", requireNodeSecret, async (req, res) => {
 });
 
 app.post("/v1/nodes/heartbeat", requireNodeSecret, async (req, res) => {
-    const { peer_id, used_gb, latency_ms = 50, failed_proofs = 0, metrics } = req.body;
+    const { peer_id, used_gb, latency_ms = 50, failed_proofs = 0, passed_proofs = 0, served_bytes = 0, metrics } = req.body;
     if (!peer_id) return res.status(400).json({ error: "peer_id is required" });
 
     const nodeRes = await pool.query("SELECT * FROM cp_nodes WHERE peer_id = $1", [peer_id]);
     const node = nodeRes.rows[0];
     if (!node) return res.status(404).json({ error: "node not registered" });
 
+    // Earnings up to now accrue at the old score and size.
+    await accrueEarnings(peer_id);
+
     let scoreImpact = 0.5;
-    if (latency_ms > 500) scoreImpact -= 2.0;
+    if (latency_ms > HIGH_LATENCY_MS) scoreImpact -= 2.0;
     else if (latency_ms > 200) scoreImpact -= 0.5;
     else scoreImpact += 0.5;
     if (failed_proofs > 0) scoreImpact -= (failed_proofs * 5.0);
@@ -648,14 +738,24 @@ app.post("/v1/nodes/heartbeat", requireNodeSecret, async (req, res) => {
             used_gb = COALESCE($2, used_gb),
             score = GREATEST(0, LEAST(100, score + $3)),
             latest_metrics = $4,
+            egress_bytes = egress_bytes + $5,
             status = 'active'
         WHERE peer_id = $1
-    `, [peer_id, used_gb, scoreImpact, JSON.stringify(metrics)]);
+    `, [peer_id, used_gb, scoreImpact, JSON.stringify(metrics), Math.max(0, Math.floor(+served_bytes || 0))]);
+    await recordNodeHealth(peer_id, { passedProofs: +passed_proofs || 0, failedProofs: +failed_proofs || 0, latencyMs: +latency_ms || 0 });
 
     const updated = await pool.query("SELECT score, status FROM cp_nodes WHERE peer_id = $1", [peer_id]);
     res.json({ peer_id, ...updated.rows[0] });
 });
 
+// Earnings, audit streak, egress and penalties for node-desktop; the same
+// payload is pushed over the node WebSocket after every heartbeat.
+app.get(["/api/nodes/:peer/dashboard", "/v1/nodes/:peer/dashboard"], requireNodeSecret, async (req, res) => {
+    const dashboard = await nodeDashboard(req.params.peer);
+    if (!dashboard) return res.status(404).json({ error: "Node not found" });
+    res.json(dashboard);
+});
+
 // ── Payouts & Summary ───────────────────────────────────────────
 app.get("/v1/payouts/preview", requireUserSession, async (_req, res) => {
     const totalPool = 1000;
@@ -708,15 +808,27 @@ wss.on("connection", (ws) => {
                     return;
                 }
                 state.wsClients.set(peerId, ws);
+                // Time spent offline does not earn, so accrual restarts now.
                 await pool.query(`
                     INSERT INTO cp_nodes (peer_id, addr, max_gb, used_gb, status)
                     VALUES ($1, 'websocket', $2, $3, 'active')
-                    ON CONFLICT (peer_id) DO UPDATE SET status = 'active', last_heartbeat = NOW()
+                    ON CONFLICT (peer_id) DO UPDATE SET status = 'active', last_heartbeat = NOW(), last_accrual_at = NOW()
                 `, [peerId, +(msg.capacity_bytes / 1024 ** 3), +(msg.used_bytes / 1024 ** 3)]);
                 ws.send(JSON.stringify({ type: "registered", node_id: peerId }));
             } else if (msg.type === "heartbeat" && peerId) {
+                await accrueEarnings(peerId);
                 await pool.query("UPDATE cp_nodes SET last_heartbeat = NOW(), used_gb = $2 WHERE peer_id = $1", [peerId, +(msg.used_bytes / 1024 ** 3)]);
+                await recordNodeHealth(peerId, { passedProofs: +msg.passed_proofs || 0, failedProofs: +msg.failed_proofs || 0 });
+                ws.send(JSON.stringify({ type: "dashboard", dashboard: await nodeDashboard(peerId) }));
+            } else if (msg.type === "dashboard:request" && peerId) {
+                ws.send(JSON.stringify({ type: "dashboard", dashboard: await nodeDashboard(peerId) }));
             } else if ((msg.type === "store:response" || msg.type === "retrieve:response") && state.pendingRequests.has(msg.request_id)) {
+                if (msg.type === "retrieve:response" && msg.success && msg.data_b64) {
+                    await pool.query(
+                        "UPDATE cp_nodes SET egress_bytes = egress_bytes + $2 WHERE peer_id = $1",
+                        [peerId, Buffer.byteLength(msg.data_b64, "base64")]
+                    );
+                }
                 const pending = state.pendingRequests.get(msg.request_id);
                 clearTimeout(pending.timeout);
                 if (msg.success) pending.resolve(msg); else pending.reject(new Error(msg.error || "unknown fail"));
@@ -727,6 +839,7 @@ wss.on("connection", (ws) => {
     ws.on("close", async () => {
         if (peerId) {
             state.wsClients.delete(peerId);
+            await accrueEarnings(peerId);
             await pool.query("UPDATE cp_nodes SET status = 'offline' WHERE peer_id = $1", [peerId]);
         }
     });